@@ -4,10 +4,27 @@
 
 use anyhow::{Context, Result, bail};
 use clap::Parser;
+use replicante::mcp_server::{
+    McpServer, Param, Tool, ToolContext, ToolHandler, ToolOutput, UnknownTool,
+};
 use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Longest a tool may block waiting for changes, below the client's 30s request timeout
+const MAX_WAIT_SECS: u64 = 25;
+
+/// How often waiting tools poll the filesystem
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// watch_path snapshots kept before the oldest tokens expire
+const MAX_WATCHES: usize = 32;
+
+/// Size and modification time of each file under a watched path
+type Snapshot = HashMap<PathBuf, (u64, Option<SystemTime>)>;
 
 /// Command-line arguments for the filesystem MCP server
 #[derive(Parser, Debug)]
@@ -28,15 +45,54 @@ struct Args {
     /// Maximum number of directory entries to list
     #[arg(long, env = "MAX_DIR_ENTRIES", default_value = "10000")]
     max_dir_entries: usize,
+
+    /// Maximum number of search results to return
+    #[arg(long, env = "MAX_SEARCH_RESULTS", default_value = "500")]
+    max_search_results: usize,
+
+    /// Workspace disk quota in megabytes, like `max_size_mb` in [sandbox.filesystem]
+    #[arg(long, env = "MAX_WORKSPACE_MB")]
+    max_workspace_mb: Option<u64>,
 }
 
+/// How long a computed workspace usage is trusted before walking the tree again
+const USAGE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// A write rejected because it would take the workspace over its quota
+#[derive(Debug)]
+struct QuotaExceeded {
+    quota_bytes: u64,
+    used_bytes: u64,
+    requested_bytes: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Workspace quota exceeded: writing {} more bytes would use {} of {} bytes ({} bytes free)",
+            self.requested_bytes,
+            self.used_bytes + self.requested_bytes,
+            self.quota_bytes,
+            self.quota_bytes.saturating_sub(self.used_bytes)
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
 /// Filesystem MCP Server implementation
 struct FilesystemMCPServer {
-    initialized: bool,
     workspace_root: PathBuf,
-    verbose: bool,
     max_file_size: u64,
     max_dir_entries: usize,
+    max_search_results: usize,
+    /// watch_path snapshots by token number, with the path they cover
+    watches: BTreeMap<u64, (PathBuf, Snapshot)>,
+    next_watch: u64,
+    max_workspace_bytes: Option<u64>,
+    /// Last measured workspace usage, adjusted by this server's own writes
+    usage_cache: Option<(u64, Instant)>,
 }
 
 impl FilesystemMCPServer {
@@ -56,14 +112,20 @@ impl FilesystemMCPServer {
                 args.max_file_size_mb
             );
             eprintln!("[Filesystem MCP] Max dir entries: {}", max_dir_entries);
+            if let Some(quota) = args.max_workspace_mb {
+                eprintln!("[Filesystem MCP] Workspace quota: {} MB", quota);
+            }
         }
 
         Ok(Self {
-            initialized: false,
             workspace_root,
-            verbose: args.verbose,
             max_file_size,
             max_dir_entries,
+            max_search_results: args.max_search_results,
+            watches: BTreeMap::new(),
+            next_watch: 1,
+            max_workspace_bytes: args.max_workspace_mb.map(|mb| mb * 1024 * 1024),
+            usage_cache: None,
         })
     }
 
@@ -94,178 +156,268 @@ impl FilesystemMCPServer {
         Ok(canonical)
     }
 
-    /// Handle JSON-RPC request
-    fn handle_request(&mut self, request: Value) -> Result<Option<Value>> {
-        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
-        let default_params = json!({});
-        let params = request.get("params").unwrap_or(&default_params);
-        let request_id = request.get("id");
+    /// Where safe_path will resolve a path once create_parents has made its
+    /// missing parents, without creating them: the nearest existing ancestor
+    /// resolved, with the missing components appended
+    fn planned_path(&self, path: &str) -> Result<PathBuf> {
+        let full_path = if path.starts_with('/') {
+            PathBuf::from(path)
+        } else {
+            self.workspace_root.join(path)
+        };
 
-        if self.verbose {
-            eprintln!("[Filesystem MCP] Handling request: {}", method);
+        let mut existing = full_path.as_path();
+        let mut missing = Vec::new();
+        while !existing.exists() {
+            let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                bail!("Cannot resolve path: {path}");
+            };
+            missing.push(name);
+            existing = parent;
         }
 
-        match method {
-            "initialize" => Ok(Some(self.handle_initialize(request_id)?)),
-            "initialized" => {
-                self.initialized = true;
-                if self.verbose {
-                    eprintln!("[Filesystem MCP] Client confirmed initialization");
-                }
-                Ok(None)
-            }
-            "tools/list" => Ok(Some(self.handle_tools_list(request_id)?)),
-            "tools/call" => Ok(Some(self.handle_tool_call(request_id, params)?)),
-            _ => Ok(Some(self.error_response(
-                request_id,
-                -32601,
-                &format!("Method not found: {}", method),
-            ))),
+        let mut planned = existing.canonicalize()?;
+        planned.extend(missing.iter().rev());
+        if !planned.starts_with(&self.workspace_root) {
+            bail!("Path {path} is outside workspace");
         }
+        Ok(planned)
     }
 
-    /// Handle initialize request
-    fn handle_initialize(&self, request_id: Option<&Value>) -> Result<Value> {
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": {
-                "protocolVersion": "2024-11-05",
-                "serverInfo": {
-                    "name": "filesystem-mcp-server",
-                    "version": "1.0.0"
-                },
-                "capabilities": {
-                    "tools": {}
-                }
-            }
-        }))
+    /// Create missing parent directories of a path inside the workspace, so
+    /// safe_path can resolve it; paths with '..' are left for safe_path to
+    /// reject or resolve
+    fn create_parents(&self, path: &str) -> Result<()> {
+        let full_path = if path.starts_with('/') {
+            PathBuf::from(path)
+        } else {
+            self.workspace_root.join(path)
+        };
+        let lexically_inside = full_path.starts_with(&self.workspace_root)
+            && !full_path
+                .components()
+                .any(|c| c == std::path::Component::ParentDir);
+        if lexically_inside && let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(())
     }
 
-    /// Return list of available tools
-    fn handle_tools_list(&self, request_id: Option<&Value>) -> Result<Value> {
-        let tools = json!([
-            {
-                "name": "read_file",
-                "description": "Read the contents of a file",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "Path to the file relative to workspace"}
-                    },
-                    "required": ["path"]
-                }
-            },
-            {
-                "name": "write_file",
-                "description": "Write content to a file",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "Path to the file relative to workspace"},
-                        "content": {"type": "string", "description": "Content to write"},
-                        "append": {"type": "boolean", "description": "Append instead of overwrite", "default": false}
-                    },
-                    "required": ["path", "content"]
-                }
-            },
-            {
-                "name": "list_directory",
-                "description": "List files and directories",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "Directory path", "default": "."},
-                        "recursive": {"type": "boolean", "description": "List recursively", "default": false}
-                    }
-                }
-            },
-            {
-                "name": "create_directory",
-                "description": "Create a directory",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "Directory path"}
-                    },
-                    "required": ["path"]
-                }
-            },
-            {
-                "name": "delete_file",
-                "description": "Delete a file or empty directory",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "Path to delete"}
-                    },
-                    "required": ["path"]
-                }
-            },
-            {
-                "name": "file_exists",
-                "description": "Check if a file or directory exists",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "Path to check"}
-                    },
-                    "required": ["path"]
-                }
-            }
-        ]);
-
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": {
-                "tools": tools
-            }
-        }))
+    /// Bytes used by the workspace, re-measured when the cached value is stale
+    fn workspace_usage(&mut self) -> u64 {
+        if let Some((used, measured)) = self.usage_cache
+            && measured.elapsed() < USAGE_CACHE_TTL
+        {
+            return used;
+        }
+        let used = directory_size(&self.workspace_root);
+        self.usage_cache = Some((used, Instant::now()));
+        used
     }
 
-    /// Handle tool execution
-    fn handle_tool_call(&mut self, request_id: Option<&Value>, params: &Value) -> Result<Value> {
-        let tool_name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
-        let default_args = json!({});
-        let arguments = params.get("arguments").unwrap_or(&default_args);
+    /// Check that replacing `target` with `new_size` bytes stays within the
+    /// quota, and count the write against the cached usage
+    fn reserve_space(&mut self, target: &Path, new_size: u64) -> Result<()> {
+        let Some(quota) = self.max_workspace_bytes else {
+            return Ok(());
+        };
+
+        let existing = fs::symlink_metadata(target)
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let used = self.workspace_usage();
+
+        if new_size > existing && used + (new_size - existing) > quota {
+            return Err(QuotaExceeded {
+                quota_bytes: quota,
+                used_bytes: used,
+                requested_bytes: new_size - existing,
+            }
+            .into());
+        }
 
-        if self.verbose {
-            eprintln!("[Filesystem MCP] Executing tool: {}", tool_name);
+        if let Some((cached, _)) = &mut self.usage_cache {
+            *cached = (*cached + new_size).saturating_sub(existing);
         }
+        Ok(())
+    }
+}
+
+impl ToolHandler for FilesystemMCPServer {
+    fn tools(&self) -> Vec<Tool> {
+        let path = || Param::string("path", "Path to the file relative to workspace").required();
+        vec![
+            Tool::new(
+                "read_file",
+                "Read the contents of a file, or a range of it with offset/limit",
+            )
+            .param(path())
+            .param(Param::integer("offset", "Lines or bytes to skip from the start").default(0))
+            .param(Param::integer("limit", "Maximum lines or bytes to return"))
+            .param(
+                Param::string("unit", "What offset and limit count")
+                    .one_of(&["lines", "bytes"])
+                    .default("lines"),
+            ),
+            Tool::new("write_file", "Write content to a file")
+                .param(path())
+                .param(Param::string("content", "Content to write").required())
+                .param(Param::boolean("append", "Append instead of overwrite").default(false)),
+            Tool::new("read_file_base64", "Read a binary file as base64").param(path()),
+            Tool::new(
+                "write_file_base64",
+                "Write base64-encoded content to a binary file",
+            )
+            .param(path())
+            .param(Param::string("content", "Base64-encoded content").required()),
+            Tool::new(
+                "edit_file",
+                "Edit a file in place with a unified diff or a list of edits, applied atomically; returns the resulting diff",
+            )
+            .param(path())
+            .param(Param::string(
+                "diff",
+                "Unified diff to apply; hunks are located by their context if line numbers have drifted",
+            ))
+            .param(Param::array(
+                "edits",
+                "Edits applied in order: {old_text, new_text, replace_all} replaces text that must match exactly once unless replace_all is set; {start_line, end_line, new_text} replaces the 1-based inclusive line range (end_line = start_line - 1 inserts before start_line)",
+                "object",
+            ))
+            .param(
+                Param::boolean("dry_run", "Return the diff without writing the file")
+                    .default(false),
+            ),
+            Tool::new(
+                "tail_file",
+                "Read the last lines of a file, optionally following it for new output",
+            )
+            .param(path())
+            .param(Param::integer("lines", "Number of lines from the end").default(10))
+            .param(
+                Param::integer(
+                    "follow_secs",
+                    "Keep reading appended output for this many seconds (max 25)",
+                )
+                .default(0),
+            ),
+            Tool::new(
+                "watch_path",
+                "Report files created, modified or deleted under a path since a previous call's token",
+            )
+            .param(Param::string("path", "File or directory to watch").default("."))
+            .param(Param::string(
+                "token",
+                "Token from a previous call; omit to start watching",
+            ))
+            .param(
+                Param::integer(
+                    "timeout_secs",
+                    "Wait up to this many seconds for a change (max 25)",
+                )
+                .default(0),
+            ),
+            Tool::new("list_directory", "List files and directories")
+                .param(Param::string("path", "Directory path").default("."))
+                .param(Param::boolean("recursive", "List recursively").default(false)),
+            Tool::new("create_directory", "Create a directory")
+                .param(Param::string("path", "Directory path").required()),
+            Tool::new("delete_file", "Delete a file or empty directory")
+                .param(Param::string("path", "Path to delete").required()),
+            Tool::new(
+                "move_file",
+                "Move or rename a file or directory within the workspace",
+            )
+            .param(Param::string("source", "Path to move").required())
+            .param(Param::string("destination", "New path").required())
+            .param(
+                Param::boolean("overwrite", "Replace an existing destination file").default(false),
+            ),
+            Tool::new(
+                "copy_file",
+                "Copy a file or directory (recursively) within the workspace",
+            )
+            .param(Param::string("source", "Path to copy").required())
+            .param(Param::string("destination", "Path of the copy").required())
+            .param(
+                Param::boolean("overwrite", "Replace an existing destination file").default(false),
+            ),
+            Tool::new(
+                "search_files",
+                "Find files by glob pattern and/or search their contents with a regex",
+            )
+            .param(Param::string("path", "Directory to search").default("."))
+            .param(Param::string(
+                "pattern",
+                "Glob matched against paths relative to the search directory, e.g. '**/*.rs'; without '/' it matches file names",
+            ))
+            .param(Param::string("content", "Regex searched for in file contents"))
+            .param(
+                Param::boolean("case_insensitive", "Match content case-insensitively")
+                    .default(false),
+            )
+            .param(
+                Param::integer("context_lines", "Lines of context around each content match")
+                    .default(2),
+            )
+            .param(
+                Param::integer("max_results", "Maximum files or matches to return").default(100),
+            ),
+            Tool::new("file_exists", "Check if a file or directory exists")
+                .param(Param::string("path", "Path to check").required()),
+            Tool::new(
+                "file_info",
+                "Get size, modification time, permissions and SHA-256 checksum of a file or directory",
+            )
+            .param(Param::string("path", "Path to inspect").required())
+            .param(
+                Param::boolean("checksum", "Compute the SHA-256 checksum of files").default(true),
+            ),
+        ]
+    }
 
-        let result = match tool_name {
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        _context: &ToolContext,
+    ) -> Result<ToolOutput> {
+        let result = match name {
             "read_file" => self.read_file(arguments),
             "write_file" => self.write_file(arguments),
+            "read_file_base64" => self.read_file_base64(arguments),
+            "write_file_base64" => self.write_file_base64(arguments),
+            "edit_file" => self.edit_file(arguments),
+            "tail_file" => self.tail_file(arguments),
+            "watch_path" => self.watch_path(arguments),
             "list_directory" => self.list_directory(arguments),
             "create_directory" => self.create_directory(arguments),
             "delete_file" => self.delete_file(arguments),
+            "move_file" => self.move_file(arguments),
+            "copy_file" => self.copy_file(arguments),
             "file_exists" => self.file_exists(arguments),
-            _ => Err(anyhow::anyhow!("Unknown tool: {}", tool_name)),
-        };
+            "file_info" => self.file_info(arguments),
+            "search_files" => self.search_files(arguments),
+            _ => Err(UnknownTool(name.to_string()).into()),
+        }?;
+        Ok(ToolOutput::json(&result))
+    }
 
-        match result {
-            Ok(content) => Ok(json!({
-                "jsonrpc": "2.0",
-                "id": request_id,
-                "result": {
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": content
-                        }
-                    ]
-                }
-            })),
-            Err(e) => Ok(self.error_response(
-                request_id,
-                -32603,
-                &format!("Tool execution failed: {}", e),
-            )),
-        }
+    fn error_data(&self, error: &anyhow::Error) -> Option<Value> {
+        let quota = error.downcast_ref::<QuotaExceeded>()?;
+        Some(json!({
+            "type": "quota_exceeded",
+            "quota_bytes": quota.quota_bytes,
+            "used_bytes": quota.used_bytes,
+            "requested_bytes": quota.requested_bytes
+        }))
     }
+}
 
-    fn read_file(&mut self, args: &Value) -> Result<String> {
+impl FilesystemMCPServer {
+    fn read_file(&mut self, args: &Value) -> Result<Value> {
         let path = args
             .get("path")
             .and_then(|p| p.as_str())
@@ -278,9 +430,14 @@ impl FilesystemMCPServer {
             .with_context(|| format!("Failed to get metadata for file: {:?}", safe_path))?;
 
         let file_size = metadata.len();
+
+        if args.get("offset").is_some() || args.get("limit").is_some() {
+            return self.read_range(path, &safe_path, file_size, args);
+        }
+
         if file_size > self.max_file_size {
             bail!(
-                "File too large: {} MB (max allowed: {} MB)",
+                "File too large: {} MB (max allowed: {} MB); read it in ranges with offset and limit",
                 file_size / (1024 * 1024),
                 self.max_file_size / (1024 * 1024)
             );
@@ -296,19 +453,107 @@ impl FilesystemMCPServer {
                 .read_to_string(&mut buffer)
                 .with_context(|| format!("Failed to read file: {:?}", safe_path))?;
 
-            if buffer.len() as u64 >= self.max_file_size {
-                buffer.push_str("\n\n[FILE TRUNCATED - EXCEEDED SIZE LIMIT]");
-            }
-            Ok(buffer)
+            Ok(json!({
+                "path": path,
+                "content": buffer,
+                "size": file_size,
+                "truncated": buffer.len() as u64 >= self.max_file_size
+            }))
         } else {
             // Small files can be read normally
             let content = fs::read_to_string(&safe_path)
                 .with_context(|| format!("Failed to read file: {:?}", safe_path))?;
-            Ok(content)
+            Ok(json!({
+                "path": path,
+                "content": content,
+                "size": file_size,
+                "truncated": false
+            }))
         }
     }
 
-    fn write_file(&mut self, args: &Value) -> Result<String> {
+    /// Read part of a file, by lines or bytes, without loading the rest
+    fn read_range(
+        &self,
+        path: &str,
+        safe_path: &Path,
+        file_size: u64,
+        args: &Value,
+    ) -> Result<Value> {
+        let offset = args.get("offset").and_then(|o| o.as_u64()).unwrap_or(0);
+        let limit = args.get("limit").and_then(|l| l.as_u64());
+        let unit = args.get("unit").and_then(|u| u.as_str()).unwrap_or("lines");
+
+        let mut file = fs::File::open(safe_path)
+            .with_context(|| format!("Failed to open file: {:?}", safe_path))?;
+
+        match unit {
+            "bytes" => {
+                let length = limit
+                    .unwrap_or(u64::MAX)
+                    .min(self.max_file_size)
+                    .min(file_size.saturating_sub(offset));
+                let mut buffer = Vec::new();
+                file.seek(SeekFrom::Start(offset))?;
+                file.take(length)
+                    .read_to_end(&mut buffer)
+                    .with_context(|| format!("Failed to read file: {:?}", safe_path))?;
+
+                Ok(json!({
+                    "path": path,
+                    "content": String::from_utf8_lossy(&buffer),
+                    "unit": "bytes",
+                    "offset": offset,
+                    "end": offset + buffer.len() as u64,
+                    "size": file_size
+                }))
+            }
+            "lines" => {
+                // Every line is scanned so the total can be reported
+                let mut reader = BufReader::new(file);
+                let mut buffer = Vec::new();
+                let mut line = Vec::new();
+                let mut total = 0u64;
+                let mut returned = 0u64;
+                let mut truncated = false;
+                loop {
+                    line.clear();
+                    if reader.read_until(b'\n', &mut line)? == 0 {
+                        break;
+                    }
+                    total += 1;
+                    if total <= offset || truncated || limit.is_some_and(|l| returned >= l) {
+                        continue;
+                    }
+                    if (buffer.len() + line.len()) as u64 > self.max_file_size {
+                        truncated = true;
+                        continue;
+                    }
+                    buffer.extend_from_slice(&line);
+                    returned += 1;
+                }
+
+                let mut content = String::from_utf8_lossy(&buffer).to_string();
+                if content.ends_with('\n') {
+                    content.pop();
+                }
+                Ok(json!({
+                    "path": path,
+                    "content": content,
+                    "unit": "lines",
+                    // 1-based and inclusive; both None when no lines follow the offset
+                    "first_line": (returned > 0).then_some(offset + 1),
+                    "last_line": (returned > 0).then_some(offset + returned),
+                    "total_lines": total,
+                    "size": file_size,
+                    "truncated": truncated
+                }))
+            }
+            _ => bail!("Invalid unit '{}': expected 'lines' or 'bytes'", unit),
+        }
+    }
+
+    fn write_file(&mut self, args: &Value) -> Result<Value> {
         let path = args
             .get("path")
             .and_then(|p| p.as_str())
@@ -331,6 +576,13 @@ impl FilesystemMCPServer {
             fs::create_dir_all(parent)?;
         }
 
+        let new_size = if append {
+            fs::metadata(&safe_path).map(|m| m.len()).unwrap_or(0) + content.len() as u64
+        } else {
+            content.len() as u64
+        };
+        self.reserve_space(&safe_path, new_size)?;
+
         if append {
             use std::fs::OpenOptions;
             use std::io::Write;
@@ -343,10 +595,341 @@ impl FilesystemMCPServer {
             fs::write(&safe_path, content)?;
         }
 
-        Ok(format!("Successfully wrote to {}", path))
+        Ok(json!({
+            "path": path,
+            "bytes_written": content.len(),
+            "appended": append
+        }))
+    }
+
+    fn read_file_base64(&mut self, args: &Value) -> Result<Value> {
+        use base64::Engine;
+
+        let path = args
+            .get("path")
+            .and_then(|p| p.as_str())
+            .context("Missing 'path' parameter")?;
+
+        let safe_path = self.safe_path(path)?;
+
+        let metadata = fs::metadata(&safe_path)
+            .with_context(|| format!("Failed to get metadata for file: {:?}", safe_path))?;
+        if metadata.len() > self.max_file_size {
+            bail!(
+                "File too large: {} MB (max allowed: {} MB)",
+                metadata.len() / (1024 * 1024),
+                self.max_file_size / (1024 * 1024)
+            );
+        }
+
+        let bytes = fs::read(&safe_path)
+            .with_context(|| format!("Failed to read file: {:?}", safe_path))?;
+        Ok(json!({
+            "path": path,
+            "size": bytes.len(),
+            "content_base64": base64::engine::general_purpose::STANDARD.encode(&bytes)
+        }))
+    }
+
+    fn write_file_base64(&mut self, args: &Value) -> Result<Value> {
+        use base64::Engine;
+
+        let path = args
+            .get("path")
+            .and_then(|p| p.as_str())
+            .context("Missing 'path' parameter")?;
+
+        let content = args
+            .get("content")
+            .and_then(|c| c.as_str())
+            .context("Missing 'content' parameter")?;
+
+        // Decoded size is about 3/4 of the encoded length; reject before decoding
+        if content.len() as u64 / 4 * 3 > self.max_file_size {
+            bail!(
+                "Content too large (max allowed: {} MB)",
+                self.max_file_size / (1024 * 1024)
+            );
+        }
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(content.trim())
+            .context("Invalid base64 content")?;
+
+        self.create_parents(path)?;
+        let safe_path = self.safe_path(path)?;
+        self.reserve_space(&safe_path, bytes.len() as u64)?;
+
+        fs::write(&safe_path, &bytes)?;
+
+        Ok(json!({
+            "path": path,
+            "bytes_written": bytes.len()
+        }))
+    }
+
+    fn edit_file(&mut self, args: &Value) -> Result<Value> {
+        let path = args
+            .get("path")
+            .and_then(|p| p.as_str())
+            .context("Missing 'path' parameter")?;
+
+        let dry_run = args
+            .get("dry_run")
+            .and_then(|d| d.as_bool())
+            .unwrap_or(false);
+
+        let safe_path = self.safe_path(path)?;
+
+        let metadata = fs::metadata(&safe_path)
+            .with_context(|| format!("Failed to get metadata for file: {:?}", safe_path))?;
+        if metadata.len() > self.max_file_size {
+            bail!(
+                "File too large: {} MB (max allowed: {} MB)",
+                metadata.len() / (1024 * 1024),
+                self.max_file_size / (1024 * 1024)
+            );
+        }
+        let original = fs::read_to_string(&safe_path)
+            .with_context(|| format!("Failed to read file: {:?}", safe_path))?;
+
+        // Every change is applied in memory first so a failing edit leaves the file untouched
+        let edited = match (args.get("diff"), args.get("edits")) {
+            (Some(diff), None) => {
+                let diff = diff.as_str().context("'diff' must be a string")?;
+                apply_unified_diff(&original, diff)?
+            }
+            (None, Some(edits)) => {
+                let edits = edits.as_array().context("'edits' must be an array")?;
+                apply_edits(&original, edits)?
+            }
+            _ => bail!("Provide exactly one of 'diff' or 'edits'"),
+        };
+
+        let diff = unified_diff(path, &original, &edited);
+        if diff.is_empty() || dry_run {
+            return Ok(json!({
+                "path": path,
+                "changed": false,
+                "dry_run": dry_run,
+                "diff": diff
+            }));
+        }
+
+        self.reserve_space(&safe_path, edited.len() as u64)?;
+
+        // Write a sibling temp file and rename it over the original
+        let file_name = safe_path
+            .file_name()
+            .context("Path has no file name")?
+            .to_string_lossy();
+        let temp_path =
+            safe_path.with_file_name(format!(".{}.edit-{}", file_name, std::process::id()));
+        fs::write(&temp_path, &edited)
+            .with_context(|| format!("Failed to write temporary file: {:?}", temp_path))?;
+        let renamed = fs::set_permissions(&temp_path, metadata.permissions())
+            .and_then(|_| fs::rename(&temp_path, &safe_path));
+        if let Err(e) = renamed {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e).with_context(|| format!("Failed to replace file: {:?}", safe_path));
+        }
+
+        Ok(json!({
+            "path": path,
+            "changed": true,
+            "dry_run": false,
+            "diff": diff
+        }))
+    }
+
+    fn tail_file(&mut self, args: &Value) -> Result<Value> {
+        let path = args
+            .get("path")
+            .and_then(|p| p.as_str())
+            .context("Missing 'path' parameter")?;
+
+        let lines = args.get("lines").and_then(|l| l.as_u64()).unwrap_or(10) as usize;
+
+        let follow_secs = args
+            .get("follow_secs")
+            .and_then(|f| f.as_u64())
+            .unwrap_or(0)
+            .min(MAX_WAIT_SECS);
+
+        let safe_path = self.safe_path(path)?;
+
+        let mut file = fs::File::open(&safe_path)
+            .with_context(|| format!("Failed to open file: {:?}", safe_path))?;
+        let size = file.metadata()?.len();
+
+        // Read backwards in chunks until enough lines are buffered
+        let mut buffer = Vec::new();
+        let mut position = size;
+        while position > 0 && (buffer.len() as u64) < self.max_file_size {
+            let chunk = position.min(8192);
+            position -= chunk;
+            file.seek(SeekFrom::Start(position))?;
+            let mut part = vec![0; chunk as usize];
+            file.read_exact(&mut part)?;
+            part.extend_from_slice(&buffer);
+            buffer = part;
+
+            let body = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+            if body.iter().filter(|&&b| b == b'\n').count() >= lines {
+                break;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&buffer);
+        let body = text.strip_suffix('\n').unwrap_or(&text);
+        let all_lines: Vec<&str> = if body.is_empty() {
+            Vec::new()
+        } else {
+            body.split('\n').collect()
+        };
+        let content = all_lines[all_lines.len().saturating_sub(lines)..].join("\n");
+
+        if follow_secs == 0 {
+            return Ok(json!({
+                "path": path,
+                "content": content
+            }));
+        }
+
+        // Follow appended output until the deadline
+        let deadline = Instant::now() + Duration::from_secs(follow_secs);
+        let mut offset = size;
+        let mut appended = Vec::new();
+        while Instant::now() < deadline {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = fs::metadata(&safe_path)
+                .with_context(|| format!("Failed to get metadata for file: {:?}", safe_path))?
+                .len();
+            if current < offset {
+                // Truncated or rotated: start again from the beginning
+                offset = 0;
+            }
+            if current > offset {
+                let remaining = self.max_file_size.saturating_sub(appended.len() as u64);
+                let mut file = fs::File::open(&safe_path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                file.take((current - offset).min(remaining))
+                    .read_to_end(&mut appended)?;
+                offset = current;
+            }
+        }
+
+        Ok(json!({
+            "path": path,
+            "content": content,
+            "appended": String::from_utf8_lossy(&appended),
+            "followed_secs": follow_secs
+        }))
+    }
+
+    fn watch_path(&mut self, args: &Value) -> Result<Value> {
+        let path = args.get("path").and_then(|p| p.as_str()).unwrap_or(".");
+
+        let token = args.get("token").and_then(|t| t.as_str());
+
+        let timeout_secs = args
+            .get("timeout_secs")
+            .and_then(|t| t.as_u64())
+            .unwrap_or(0)
+            .min(MAX_WAIT_SECS);
+
+        let safe_path = self.safe_path(path)?;
+
+        let previous = token
+            .map(|token| {
+                let (root, snapshot) = token
+                    .strip_prefix("watch-")
+                    .and_then(|id| id.parse().ok())
+                    .and_then(|id| self.watches.remove(&id))
+                    .with_context(|| format!("Unknown or expired watch token: {}", token))?;
+                if root != safe_path {
+                    bail!("Watch token {} is for a different path", token);
+                }
+                Ok(snapshot)
+            })
+            .transpose()?;
+
+        let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+        let (snapshot, created, modified, deleted) = loop {
+            let snapshot = self.snapshot(&safe_path)?;
+            let Some(previous) = &previous else {
+                break (snapshot, Vec::new(), Vec::new(), Vec::new());
+            };
+
+            let mut created = Vec::new();
+            let mut modified = Vec::new();
+            for (file, state) in &snapshot {
+                match previous.get(file) {
+                    None => created.push(self.relative(file)),
+                    Some(old) if old != state => modified.push(self.relative(file)),
+                    Some(_) => {}
+                }
+            }
+            let mut deleted: Vec<String> = previous
+                .keys()
+                .filter(|file| !snapshot.contains_key(*file))
+                .map(|file| self.relative(file))
+                .collect();
+
+            let changed = !created.is_empty() || !modified.is_empty() || !deleted.is_empty();
+            if changed || Instant::now() >= deadline {
+                created.sort();
+                modified.sort();
+                deleted.sort();
+                break (snapshot, created, modified, deleted);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        let files = snapshot.len();
+        let id = self.next_watch;
+        self.next_watch += 1;
+        self.watches.insert(id, (safe_path, snapshot));
+        while self.watches.len() > MAX_WATCHES {
+            self.watches.pop_first();
+        }
+
+        let result = json!({
+            "token": format!("watch-{}", id),
+            "files": files,
+            "created": created,
+            "modified": modified,
+            "deleted": deleted
+        });
+
+        Ok(result)
     }
 
-    fn list_directory(&mut self, args: &Value) -> Result<String> {
+    /// Size and modification time of the file, or every file under the directory
+    fn snapshot(&self, path: &Path) -> Result<Snapshot> {
+        let mut files = Vec::new();
+        if path.is_dir() {
+            self.collect_files(path, &mut files)?;
+        } else if path.exists() {
+            files.push(path.to_path_buf());
+        }
+
+        Ok(files
+            .into_iter()
+            .filter_map(|file| {
+                let metadata = fs::metadata(&file).ok()?;
+                Some((file, (metadata.len(), metadata.modified().ok())))
+            })
+            .collect())
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.workspace_root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn list_directory(&mut self, args: &Value) -> Result<Value> {
         let path = args.get("path").and_then(|p| p.as_str()).unwrap_or(".");
 
         let recursive = args
@@ -375,31 +958,21 @@ impl FilesystemMCPServer {
                     .to_string_lossy();
 
                 let metadata = entry.metadata()?;
-                let prefix = if metadata.is_dir() { "[DIR] " } else { "" };
-                entries.push(format!("{}{}", prefix, rel_path));
+                entries.push(directory_entry(&rel_path, metadata.is_dir()));
             }
         }
 
-        entries.sort();
-
-        let mut result = if entries.is_empty() {
-            "Empty directory".to_string()
-        } else {
-            entries.join("\n")
-        };
-
-        if truncated || entries.len() >= self.max_dir_entries {
-            result.push_str(&format!(
-                "\n\n[LISTING TRUNCATED - SHOWING FIRST {} ENTRIES]",
-                self.max_dir_entries
-            ));
-        }
+        entries.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
 
-        Ok(result)
+        Ok(json!({
+            "path": path,
+            "entries": entries,
+            "truncated": truncated || entries.len() >= self.max_dir_entries
+        }))
     }
 
     #[allow(clippy::only_used_in_recursion)]
-    fn list_recursive(&self, dir: &Path, base: &Path, entries: &mut Vec<String>) -> Result<()> {
+    fn list_recursive(&self, dir: &Path, base: &Path, entries: &mut Vec<Value>) -> Result<()> {
         for entry in fs::read_dir(dir)? {
             if entries.len() >= self.max_dir_entries {
                 break; // Stop if we've hit the limit
@@ -409,19 +982,148 @@ impl FilesystemMCPServer {
             let rel_path = path.strip_prefix(base).unwrap_or(&path).to_string_lossy();
 
             let metadata = entry.metadata()?;
-            if metadata.is_dir() {
-                entries.push(format!("[DIR] {}", rel_path));
-                if entries.len() < self.max_dir_entries {
-                    self.list_recursive(&path, base, entries)?;
+            entries.push(directory_entry(&rel_path, metadata.is_dir()));
+            if metadata.is_dir() && entries.len() < self.max_dir_entries {
+                self.list_recursive(&path, base, entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn search_files(&mut self, args: &Value) -> Result<Value> {
+        let path = args.get("path").and_then(|p| p.as_str()).unwrap_or(".");
+        let pattern = args.get("pattern").and_then(|p| p.as_str());
+        let content = args.get("content").and_then(|c| c.as_str());
+        if pattern.is_none() && content.is_none() {
+            bail!("Provide a 'pattern', a 'content' regex, or both");
+        }
+
+        let case_insensitive = args
+            .get("case_insensitive")
+            .and_then(|c| c.as_bool())
+            .unwrap_or(false);
+        let context_lines = args
+            .get("context_lines")
+            .and_then(|c| c.as_u64())
+            .unwrap_or(2) as usize;
+        let max_results = args
+            .get("max_results")
+            .and_then(|m| m.as_u64())
+            .map(|m| m as usize)
+            .unwrap_or(100)
+            .min(self.max_search_results);
+
+        let regex = content
+            .map(|content| {
+                regex::RegexBuilder::new(content)
+                    .case_insensitive(case_insensitive)
+                    .build()
+                    .with_context(|| format!("Invalid content regex: {}", content))
+            })
+            .transpose()?;
+
+        let search_root = self.safe_path(path)?;
+        let mut files = Vec::new();
+        self.collect_files(&search_root, &mut files)?;
+        files.sort();
+
+        let mut results = Vec::new();
+        let mut matches = 0;
+        let mut truncated = false;
+
+        for file in files {
+            let rel_to_root = file.strip_prefix(&search_root).unwrap_or(&file);
+            if let Some(pattern) = pattern {
+                let candidate = if pattern.contains('/') {
+                    rel_to_root.to_string_lossy().to_string()
+                } else {
+                    rel_to_root
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                };
+                if !glob_matches(pattern, &candidate) {
+                    continue;
                 }
-            } else {
-                entries.push(rel_path.to_string());
+            }
+
+            let rel_path = file
+                .strip_prefix(&self.workspace_root)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .to_string();
+
+            let Some(regex) = &regex else {
+                if matches >= max_results {
+                    truncated = true;
+                    break;
+                }
+                matches += 1;
+                results.push(json!(rel_path));
+                continue;
+            };
+
+            // Skip files too large to read and binary files
+            if fs::metadata(&file).map(|m| m.len()).unwrap_or(u64::MAX) > self.max_file_size {
+                continue;
+            }
+            let Ok(text) = fs::read_to_string(&file) else {
+                continue;
+            };
+
+            let lines: Vec<&str> = text.lines().collect();
+            for (index, line) in lines.iter().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+                if matches >= max_results {
+                    truncated = true;
+                    break;
+                }
+                matches += 1;
+
+                let start = index.saturating_sub(context_lines);
+                let end = (index + context_lines + 1).min(lines.len());
+                results.push(json!({
+                    "path": rel_path,
+                    "line": index + 1,
+                    "text": line,
+                    "before": lines[start..index],
+                    "after": lines[index + 1..end]
+                }));
+            }
+
+            if truncated {
+                break;
+            }
+        }
+
+        // File names alone when only a pattern is given, otherwise matching lines
+        let kind = if regex.is_some() { "matches" } else { "files" };
+        Ok(json!({
+            kind: results,
+            "truncated": truncated
+        }))
+    }
+
+    /// Files under `dir`, not following symlinks, up to the directory entry limit
+    fn collect_files(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            if files.len() >= self.max_dir_entries {
+                break;
+            }
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                self.collect_files(&entry.path(), files)?;
+            } else if file_type.is_file() {
+                files.push(entry.path());
             }
         }
         Ok(())
     }
 
-    fn create_directory(&mut self, args: &Value) -> Result<String> {
+    fn create_directory(&mut self, args: &Value) -> Result<Value> {
         let path = args
             .get("path")
             .and_then(|p| p.as_str())
@@ -430,10 +1132,10 @@ impl FilesystemMCPServer {
         let safe_path = self.safe_path(path)?;
 
         fs::create_dir_all(&safe_path)?;
-        Ok(format!("Created directory: {}", path))
+        Ok(json!({"path": path, "created": true}))
     }
 
-    fn delete_file(&mut self, args: &Value) -> Result<String> {
+    fn delete_file(&mut self, args: &Value) -> Result<Value> {
         let path = args
             .get("path")
             .and_then(|p| p.as_str())
@@ -451,10 +1153,114 @@ impl FilesystemMCPServer {
                 .with_context(|| format!("Failed to delete file: {:?}", safe_path))?;
         }
 
-        Ok(format!("Deleted: {}", path))
+        Ok(json!({"path": path, "deleted": true}))
+    }
+
+    /// Resolve and check the source and destination of a move or copy
+    fn transfer_paths(&self, args: &Value) -> Result<(String, String, PathBuf, PathBuf)> {
+        let source = args
+            .get("source")
+            .and_then(|s| s.as_str())
+            .context("Missing 'source' parameter")?;
+
+        let destination = args
+            .get("destination")
+            .and_then(|d| d.as_str())
+            .context("Missing 'destination' parameter")?;
+
+        let overwrite = args
+            .get("overwrite")
+            .and_then(|o| o.as_bool())
+            .unwrap_or(false);
+
+        let safe_source = self.safe_path(source)?;
+        if !safe_source.exists() {
+            bail!("Source does not exist: {source}");
+        }
+        if safe_source == self.workspace_root {
+            bail!("Cannot move or copy the workspace root");
+        }
+
+        let safe_destination = self.planned_path(destination)?;
+        if safe_destination.starts_with(&safe_source) {
+            bail!("Destination {destination} is inside source {source}");
+        }
+        if safe_destination.exists() {
+            if !overwrite {
+                bail!("Destination already exists: {destination} (set overwrite to replace it)");
+            }
+            if safe_destination.is_dir() {
+                bail!("Refusing to overwrite directory: {destination}");
+            }
+        }
+        self.create_parents(destination)?;
+
+        Ok((
+            source.to_string(),
+            destination.to_string(),
+            safe_source,
+            safe_destination,
+        ))
+    }
+
+    fn move_file(&mut self, args: &Value) -> Result<Value> {
+        let (source, destination, safe_source, safe_destination) = self.transfer_paths(args)?;
+
+        fs::rename(&safe_source, &safe_destination)
+            .with_context(|| format!("Failed to move {safe_source:?} to {safe_destination:?}"))?;
+
+        Ok(json!({"source": source, "destination": destination, "moved": true}))
+    }
+
+    fn copy_file(&mut self, args: &Value) -> Result<Value> {
+        let (source, destination, safe_source, safe_destination) = self.transfer_paths(args)?;
+        self.reserve_space(&safe_destination, directory_size(&safe_source))?;
+
+        let mut copied = 0;
+        if safe_source.is_dir() {
+            self.copy_recursive(&safe_source, &safe_destination, &mut copied)?;
+        } else {
+            fs::copy(&safe_source, &safe_destination).with_context(|| {
+                format!("Failed to copy {safe_source:?} to {safe_destination:?}")
+            })?;
+            copied = 1;
+        }
+        Ok(json!({
+            "source": source,
+            "destination": destination,
+            "files_copied": copied
+        }))
+    }
+
+    /// Copy a directory tree, skipping symlinks, up to the directory entry limit
+    fn copy_recursive(&self, source: &Path, destination: &Path, copied: &mut usize) -> Result<()> {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            if *copied >= self.max_dir_entries {
+                bail!(
+                    "Copy stopped after {limit} files (directory entry limit)",
+                    limit = self.max_dir_entries
+                );
+            }
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let target = destination.join(entry.file_name());
+            if file_type.is_dir() {
+                self.copy_recursive(&entry.path(), &target, copied)?;
+            } else if file_type.is_file() {
+                fs::copy(entry.path(), &target).with_context(|| {
+                    format!(
+                        "Failed to copy {source:?} to {target:?}",
+                        source = entry.path()
+                    )
+                })?;
+                *copied += 1;
+            }
+        }
+        Ok(())
     }
 
-    fn file_exists(&mut self, args: &Value) -> Result<String> {
+    fn file_exists(&mut self, args: &Value) -> Result<Value> {
         let path = args
             .get("path")
             .and_then(|p| p.as_str())
@@ -479,82 +1285,447 @@ impl FilesystemMCPServer {
             })
         };
 
-        Ok(serde_json::to_string(&result)?)
+        Ok(result)
     }
 
-    /// Create error response
-    fn error_response(&self, request_id: Option<&Value>, code: i64, message: &str) -> Value {
-        json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "error": {
-                "code": code,
-                "message": message
-            }
+    fn file_info(&mut self, args: &Value) -> Result<Value> {
+        let path = args
+            .get("path")
+            .and_then(|p| p.as_str())
+            .context("Missing 'path' parameter")?;
+
+        let checksum = args
+            .get("checksum")
+            .and_then(|c| c.as_bool())
+            .unwrap_or(true);
+
+        let safe_path = self.safe_path(path)?;
+
+        let metadata = fs::metadata(&safe_path)
+            .with_context(|| format!("Failed to get metadata for: {:?}", safe_path))?;
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339());
+
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(format!("{:o}", metadata.permissions().mode() & 0o7777))
+        };
+        #[cfg(not(unix))]
+        let mode: Option<String> = None;
+
+        // Hash in chunks so large files are never held in memory
+        let sha256 = if checksum && metadata.is_file() {
+            use sha2::{Digest, Sha256};
+            let mut file = fs::File::open(&safe_path)
+                .with_context(|| format!("Failed to open file: {:?}", safe_path))?;
+            let mut hasher = Sha256::new();
+            io::copy(&mut file, &mut hasher)
+                .with_context(|| format!("Failed to read file: {:?}", safe_path))?;
+            Some(format!("{:x}", hasher.finalize()))
+        } else {
+            None
+        };
+
+        let result = json!({
+            "path": path,
+            "type": if metadata.is_dir() { "directory" } else { "file" },
+            "size": metadata.len(),
+            "modified": modified,
+            "mode": mode,
+            "readonly": metadata.permissions().readonly(),
+            "sha256": sha256
+        });
+
+        Ok(result)
+    }
+}
+
+/// A list_directory entry
+fn directory_entry(path: &str, is_dir: bool) -> Value {
+    json!({
+        "path": path,
+        "type": if is_dir { "directory" } else { "file" }
+    })
+}
+
+/// Total size of the files under `path` (or of `path` itself), not following symlinks
+fn directory_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return if metadata.is_file() {
+            metadata.len()
+        } else {
+            0
+        };
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| directory_size(&entry.path()))
+                .sum()
         })
+        .unwrap_or(0)
+}
+
+/// Match `text` against a glob where `*` matches within a path component,
+/// `**` across components and `?` one character
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_matches_from(&pattern, &text)
+}
+
+fn glob_matches_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // `**/` also matches no directories at all
+            glob_matches_from(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == '/')
+                    .any(|i| glob_matches_from(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob_matches_from(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_matches_from(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            !text.is_empty() && text[0] != '/' && glob_matches_from(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_matches_from(rest, &text[1..]),
     }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let verbose = args.verbose;
+/// Lines of unchanged context around each hunk of a generated diff
+const DIFF_CONTEXT: usize = 3;
 
-    if verbose {
-        eprintln!("[Filesystem MCP] Starting server...");
+/// Largest middle section (old lines x new lines) diffed line by line;
+/// anything bigger is reported as one replaced block
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Rebuild text from lines, keeping the original's trailing newline
+fn join_lines(lines: &[String], trailing_newline: bool) -> String {
+    let mut text = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        text.push('\n');
     }
+    text
+}
 
-    let mut server = FilesystemMCPServer::new(args)?;
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let reader = BufReader::new(stdin.lock());
+/// Apply `edits` in order, failing on the first one that does not apply
+fn apply_edits(original: &str, edits: &[Value]) -> Result<String> {
+    let mut text = original.to_string();
 
-    if verbose {
-        eprintln!("[Filesystem MCP] Ready for requests");
+    for (index, edit) in edits.iter().enumerate() {
+        let number = index + 1;
+        let new_text = edit
+            .get("new_text")
+            .and_then(|n| n.as_str())
+            .with_context(|| format!("Edit {} is missing 'new_text'", number))?;
+
+        if let Some(old_text) = edit.get("old_text").and_then(|o| o.as_str()) {
+            if old_text.is_empty() {
+                bail!("Edit {}: 'old_text' must not be empty", number);
+            }
+            let replace_all = edit
+                .get("replace_all")
+                .and_then(|r| r.as_bool())
+                .unwrap_or(false);
+            let occurrences = text.matches(old_text).count();
+            if occurrences == 0 {
+                bail!("Edit {}: 'old_text' not found", number);
+            }
+            if occurrences > 1 && !replace_all {
+                bail!(
+                    "Edit {}: 'old_text' matches {} times; add surrounding context or set replace_all",
+                    number,
+                    occurrences
+                );
+            }
+            text = text.replace(old_text, new_text);
+        } else if let Some(start_line) = edit.get("start_line").and_then(|s| s.as_u64()) {
+            let start_line = start_line as usize;
+            let end_line = edit
+                .get("end_line")
+                .and_then(|e| e.as_u64())
+                .map(|e| e as usize)
+                .unwrap_or(start_line);
+            let trailing_newline = text.ends_with('\n') || text.is_empty();
+            let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+            if start_line == 0 || start_line > lines.len() + 1 {
+                bail!(
+                    "Edit {}: start_line {} is outside the file ({} lines)",
+                    number,
+                    start_line,
+                    lines.len()
+                );
+            }
+            if end_line + 1 < start_line || end_line > lines.len() {
+                bail!(
+                    "Edit {}: invalid line range {}-{} ({} lines)",
+                    number,
+                    start_line,
+                    end_line,
+                    lines.len()
+                );
+            }
+            lines.splice(
+                start_line - 1..end_line,
+                new_text.lines().map(str::to_string),
+            );
+            text = join_lines(&lines, trailing_newline);
+        } else {
+            bail!("Edit {} needs either 'old_text' or 'start_line'", number);
+        }
     }
 
-    for line in reader.lines() {
-        let line = line?;
-        if line.trim().is_empty() {
+    Ok(text)
+}
+
+/// Parse `-a,b +c,d @@` into (old start, old count, new count)
+fn parse_hunk_header(header: &str) -> Result<(usize, usize, usize)> {
+    let mut ranges = header.split_whitespace();
+    let mut range = |prefix: char| -> Result<(usize, usize)> {
+        let range = ranges
+            .next()
+            .and_then(|r| r.strip_prefix(prefix))
+            .with_context(|| format!("Invalid hunk header: @@ {}", header))?;
+        let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+        Ok((
+            start
+                .parse()
+                .with_context(|| format!("Invalid hunk header: @@ {}", header))?,
+            count
+                .parse()
+                .with_context(|| format!("Invalid hunk header: @@ {}", header))?,
+        ))
+    };
+    let (old_start, old_count) = range('-')?;
+    let (_, new_count) = range('+')?;
+    Ok((old_start, old_count, new_count))
+}
+
+/// Position of `needle` in `lines` closest to `expected`
+fn find_hunk(lines: &[String], needle: &[&str], expected: usize) -> Option<usize> {
+    let last = lines.len().checked_sub(needle.len())?;
+    let matches_at = |position: usize| {
+        position <= last
+            && lines[position..position + needle.len()]
+                .iter()
+                .zip(needle)
+                .all(|(line, wanted)| line == wanted)
+    };
+    (0..=lines.len()).find_map(|delta| {
+        [expected.checked_add(delta), expected.checked_sub(delta)]
+            .into_iter()
+            .flatten()
+            .find(|&position| matches_at(position))
+    })
+}
+
+/// Apply a unified diff, locating each hunk by its context near the stated line
+fn apply_unified_diff(original: &str, diff: &str) -> Result<String> {
+    let trailing_newline = original.ends_with('\n') || original.is_empty();
+    let mut lines: Vec<String> = original.lines().map(str::to_string).collect();
+    // Shift between the original's line numbers and the partially patched file
+    let mut offset: isize = 0;
+    let mut hunks = 0;
+
+    let mut diff_lines = diff.lines();
+    while let Some(line) = diff_lines.next() {
+        // File headers and anything else outside a hunk are skipped
+        let Some(header) = line.strip_prefix("@@ ") else {
             continue;
+        };
+        hunks += 1;
+        let (old_start, old_count, new_count) = parse_hunk_header(header)?;
+
+        // Hunk bodies are read by count, so removed lines starting with "--" are safe
+        let mut old = Vec::new();
+        let mut new = Vec::new();
+        while old.len() < old_count || new.len() < new_count {
+            let line = diff_lines
+                .next()
+                .with_context(|| format!("Hunk {} ends early", hunks))?;
+            match line.chars().next() {
+                Some(' ') | None => {
+                    let text = line.get(1..).unwrap_or("");
+                    old.push(text);
+                    new.push(text);
+                }
+                Some('-') => old.push(&line[1..]),
+                Some('+') => new.push(&line[1..]),
+                Some('\\') => {}
+                _ => bail!("Invalid line in hunk {}: {}", hunks, line),
+            }
         }
 
-        if verbose {
-            eprintln!("[Filesystem MCP] Received: {}", line);
-        }
+        // An empty old range means "insert after line old_start"
+        let base = if old_count == 0 {
+            old_start
+        } else {
+            old_start.saturating_sub(1)
+        };
+        let expected = (base as isize + offset).max(0) as usize;
+        let position = find_hunk(&lines, &old, expected)
+            .with_context(|| format!("Hunk {} does not apply: context not found", hunks))?;
+        lines.splice(
+            position..position + old.len(),
+            new.iter().map(|line| line.to_string()),
+        );
+        offset = position as isize - base as isize + new.len() as isize - old.len() as isize;
+    }
+
+    if hunks == 0 {
+        bail!("Diff contains no hunks");
+    }
 
-        let request: Value = serde_json::from_str(&line)
-            .with_context(|| format!("Failed to parse JSON: {}", line))?;
+    Ok(join_lines(&lines, trailing_newline))
+}
 
-        match server.handle_request(request) {
-            Ok(Some(response)) => {
-                let response_str = serde_json::to_string(&response)?;
-                writeln!(stdout, "{}", response_str)?;
-                stdout.flush()?;
-                if verbose {
-                    eprintln!("[Filesystem MCP] Sent response: {}", response_str);
-                }
+/// Line-level edit script turning `old` into `new`: ' ' keeps, '-' removes, '+' adds
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<(char, &str)> = old[..prefix].iter().map(|line| (' ', *line)).collect();
+
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_DIFF_CELLS {
+        ops.extend(old_middle.iter().map(|line| ('-', *line)));
+        ops.extend(new_middle.iter().map(|line| ('+', *line)));
+    } else {
+        // Longest common subsequence table, filled from the end
+        let width = new_middle.len() + 1;
+        let mut table = vec![0u32; (old_middle.len() + 1) * width];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                table[i * width + j] = if old_middle[i] == new_middle[j] {
+                    table[(i + 1) * width + j + 1] + 1
+                } else {
+                    table[(i + 1) * width + j].max(table[i * width + j + 1])
+                };
             }
-            Ok(None) => {
-                if verbose {
-                    eprintln!("[Filesystem MCP] No response needed");
-                }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() && j < new_middle.len() {
+            if old_middle[i] == new_middle[j] {
+                ops.push((' ', old_middle[i]));
+                i += 1;
+                j += 1;
+            } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+                ops.push(('-', old_middle[i]));
+                i += 1;
+            } else {
+                ops.push(('+', new_middle[j]));
+                j += 1;
             }
-            Err(e) => {
-                eprintln!("[Filesystem MCP] Error handling request: {}", e);
-                let error_response = json!({
-                    "jsonrpc": "2.0",
-                    "id": null,
-                    "error": {
-                        "code": -32603,
-                        "message": format!("Internal error: {}", e)
-                    }
-                });
-                writeln!(stdout, "{}", serde_json::to_string(&error_response)?)?;
-                stdout.flush()?;
+        }
+        ops.extend(old_middle[i..].iter().map(|line| ('-', *line)));
+        ops.extend(new_middle[j..].iter().map(|line| ('+', *line)));
+    }
+
+    ops.extend(old[old.len() - suffix..].iter().map(|line| (' ', *line)));
+    ops
+}
+
+/// Unified diff between two versions of `path`, empty when they are equal
+fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| *kind != ' ')
+        .map(|(index, _)| index)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // 1-based (old, new) line numbers at each op
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_line, mut new_line) = (1, 1);
+    for (kind, _) in &ops {
+        positions.push((old_line, new_line));
+        match kind {
+            ' ' => {
+                old_line += 1;
+                new_line += 1;
             }
+            '-' => old_line += 1,
+            _ => new_line += 1,
+        }
+    }
+
+    let mut output = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let mut index = 0;
+    while index < changes.len() {
+        let start = changes[index].saturating_sub(DIFF_CONTEXT);
+        let mut end = changes[index] + 1;
+        // Changes whose context would overlap share a hunk
+        while index + 1 < changes.len() && changes[index + 1] <= end + 2 * DIFF_CONTEXT {
+            index += 1;
+            end = changes[index] + 1;
+        }
+        index += 1;
+        let end = (end + DIFF_CONTEXT).min(ops.len());
+
+        let hunk = &ops[start..end];
+        let old_count = hunk.iter().filter(|(kind, _)| *kind != '+').count();
+        let new_count = hunk.iter().filter(|(kind, _)| *kind != '-').count();
+        let (old_start, new_start) = positions[start];
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_count == 0 {
+                old_start - 1
+            } else {
+                old_start
+            },
+            old_count,
+            if new_count == 0 {
+                new_start - 1
+            } else {
+                new_start
+            },
+            new_count
+        ));
+        for (kind, line) in hunk {
+            output.push(*kind);
+            output.push_str(line);
+            output.push('\n');
         }
     }
 
-    eprintln!("[Filesystem MCP] Server shutting down");
-    Ok(())
+    output
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let verbose = args.verbose;
+
+    if verbose {
+        eprintln!("[Filesystem MCP] Starting server...");
+    }
+
+    let server = FilesystemMCPServer::new(args)?;
+    McpServer::new("filesystem-mcp-server", "Filesystem MCP", server)
+        .verbose(verbose)
+        .run()
 }
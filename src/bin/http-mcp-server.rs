@@ -1,262 +1,1260 @@
 #!/usr/bin/env rust
 //! HTTP MCP Server for testing - provides tools for web requests
 //!
-//! Provides tools: fetch_url, check_weather, get_time, calculate
+//! Provides tools: fetch_url, http_request, download_file, create_http_session,
+//! close_http_session, check_weather, get_time, calculate
 
 use anyhow::{Context, Result};
 use chrono::{FixedOffset, Utc};
+use clap::Parser;
 use rand::Rng;
+use replicante::domains::domain_matches;
+use replicante::mcp_server::{
+    LogLevel, McpServer, Param, Tool, ToolContext, ToolHandler, ToolOutput, UnknownTool, log,
+};
 use serde_json::{Value, json};
-use std::io::{self, BufRead, BufReader, Write};
-use std::time::Duration;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Command-line arguments for the HTTP MCP server
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Workspace root directory that downloads are written to
+    #[arg(long, env = "WORKSPACE_PATH", default_value = "/workspace")]
+    workspace: PathBuf,
+
+    /// Maximum download size in megabytes
+    #[arg(long, env = "MAX_DOWNLOAD_SIZE_MB", default_value = "100")]
+    max_download_size_mb: u64,
+
+    /// TOML file with `allow` and `deny` domain lists and an optional `allow_all` flag
+    #[arg(long, env = "DOMAIN_POLICY_FILE")]
+    domain_policy: Option<PathBuf>,
+
+    /// Domain requests may go to, e.g. example.com or *.example.com (repeatable; comma-separated in the env var)
+    #[arg(long = "allow-domain", env = "ALLOW_DOMAINS", value_delimiter = ',')]
+    allow_domains: Vec<String>,
+
+    /// Domain requests must never go to (repeatable; comma-separated in the env var)
+    #[arg(long = "deny-domain", env = "DENY_DOMAINS", value_delimiter = ',')]
+    deny_domains: Vec<String>,
+
+    /// Allow every domain not explicitly denied, for sandboxed deployments
+    #[arg(long, env = "ALLOW_ALL_DOMAINS")]
+    allow_all_domains: bool,
+
+    /// User-Agent header sent with every request
+    #[arg(long, env = "HTTP_USER_AGENT", default_value = "MCP-Test/1.0")]
+    user_agent: String,
+
+    /// Minimum time between requests to the same domain, in milliseconds
+    #[arg(long, env = "HTTP_MIN_REQUEST_INTERVAL_MS", default_value = "1000")]
+    min_request_interval_ms: u64,
+
+    /// Check robots.txt before fetch_url and download_file requests
+    #[arg(long, env = "RESPECT_ROBOTS_TXT", default_value = "true", action = clap::ArgAction::Set)]
+    respect_robots: bool,
+}
+
+/// Domains allowed when no allow rules are configured
+const DEFAULT_ALLOWED_DOMAINS: [&str; 3] = [
+    "httpbin.org",
+    "jsonplaceholder.typicode.com",
+    "api.github.com",
+];
+
+/// Domain policy file contents
+#[derive(Debug, Default, serde::Deserialize)]
+struct DomainPolicyFile {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+    #[serde(default)]
+    allow_all: bool,
+}
+
+/// Domain patterns deciding which hosts may be contacted; deny rules win.
+/// A pattern is an exact host, `*.domain` for any subdomain, or `*` for all
+#[derive(Debug)]
+struct DomainPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+    allow_all: bool,
+}
+
+impl DomainPolicy {
+    fn load(args: &Args) -> Result<Self> {
+        let file = match &args.domain_policy {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read domain policy {:?}", path))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("Failed to parse domain policy {:?}", path))?
+            }
+            None => DomainPolicyFile::default(),
+        };
+
+        let normalize = |patterns: Vec<&String>| -> Vec<String> {
+            patterns
+                .into_iter()
+                .map(|pattern| pattern.trim().trim_end_matches('.').to_lowercase())
+                .filter(|pattern| !pattern.is_empty())
+                .collect()
+        };
+
+        let mut allow = normalize(file.allow.iter().chain(&args.allow_domains).collect());
+        let deny = normalize(file.deny.iter().chain(&args.deny_domains).collect());
+        let allow_all = file.allow_all || args.allow_all_domains;
+
+        if allow.is_empty() && !allow_all {
+            allow = DEFAULT_ALLOWED_DOMAINS
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+        }
+
+        Ok(Self {
+            allow,
+            deny,
+            allow_all,
+        })
+    }
+
+    fn check(&self, host: &str) -> Result<(), String> {
+        let host = host.trim_end_matches('.').to_lowercase();
+
+        if let Some(rule) = self.deny.iter().find(|rule| domain_matches(rule, &host)) {
+            return Err(format!(
+                "Error: URL domain '{}' denied by domain policy rule '{}'",
+                host, rule
+            ));
+        }
+        if !self.allow_all && !self.allow.iter().any(|rule| domain_matches(rule, &host)) {
+            return Err(format!(
+                "Error: URL domain '{}' not in allowed domain list",
+                host
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Environment variable prefix for auth profiles, e.g. HTTP_AUTH_GITHUB_BEARER
+const AUTH_ENV_PREFIX: &str = "HTTP_AUTH_";
+
+/// A named credential the agent can use without seeing it
+struct AuthProfile {
+    header: reqwest::header::HeaderName,
+    value: reqwest::header::HeaderValue,
+    /// Strings redacted from responses made with this profile
+    secrets: Vec<String>,
+    /// Domain patterns the credential may be sent to; empty means any allowed domain
+    domains: Vec<String>,
+}
+
+impl std::fmt::Debug for AuthProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthProfile")
+            .field("header", &self.header)
+            .field("domains", &self.domains)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Load auth profiles from `HTTP_AUTH_<NAME>_<KIND>` variables, where KIND is
+/// BEARER (a token), BASIC (`user:password`), HEADER (`Name: value`) or
+/// DOMAINS (comma-separated domain patterns the profile is limited to)
+fn load_auth_profiles(
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<BTreeMap<String, AuthProfile>> {
+    use base64::Engine;
+    use reqwest::header::{AUTHORIZATION, HeaderName, HeaderValue};
+
+    let mut credentials: BTreeMap<String, (HeaderName, String, Vec<String>)> = BTreeMap::new();
+    let mut domains: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (key, value) in vars {
+        let Some(rest) = key.strip_prefix(AUTH_ENV_PREFIX) else {
+            continue;
+        };
+        let Some((name, kind)) = rest.rsplit_once('_') else {
+            continue;
+        };
+        let name = name.to_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+
+        let credential = match kind {
+            "BEARER" => (AUTHORIZATION, format!("Bearer {}", value), vec![value]),
+            "BASIC" => {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(&value);
+                let mut secrets = vec![encoded.clone()];
+                if let Some((_, password)) = value.split_once(':')
+                    && !password.is_empty()
+                {
+                    secrets.push(password.to_string());
+                }
+                (AUTHORIZATION, format!("Basic {}", encoded), secrets)
+            }
+            "HEADER" => {
+                let (header, header_value) = value
+                    .split_once(':')
+                    .with_context(|| format!("{} must look like 'Name: value'", key))?;
+                let header = HeaderName::from_bytes(header.trim().as_bytes())
+                    .with_context(|| format!("Invalid header name in {}", key))?;
+                let header_value = header_value.trim().to_string();
+                (header, header_value.clone(), vec![header_value])
+            }
+            "DOMAINS" => {
+                domains.insert(
+                    name,
+                    value
+                        .split(',')
+                        .map(|domain| domain.trim().to_lowercase())
+                        .filter(|domain| !domain.is_empty())
+                        .collect(),
+                );
+                continue;
+            }
+            _ => continue,
+        };
+
+        if credentials.insert(name.clone(), credential).is_some() {
+            anyhow::bail!("Auth profile '{}' has more than one credential", name);
+        }
+    }
+
+    if let Some(name) = domains.keys().find(|name| !credentials.contains_key(*name)) {
+        anyhow::bail!("Auth profile '{}' has domains but no credential", name);
+    }
+
+    credentials
+        .into_iter()
+        .map(|(name, (header, value, secrets))| {
+            let mut value = HeaderValue::from_str(&value)
+                .with_context(|| format!("Invalid credential for auth profile '{}'", name))?;
+            value.set_sensitive(true);
+            let profile = AuthProfile {
+                header,
+                value,
+                secrets: secrets.into_iter().filter(|s| !s.is_empty()).collect(),
+                domains: domains.remove(&name).unwrap_or_default(),
+            };
+            Ok((name, profile))
+        })
+        .collect()
+}
+
+/// Replace every secret in `text` with a placeholder
+fn redact(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret.as_str(), "[REDACTED]")
+    })
+}
+
+/// Largest response body returned by http_request
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// Longest timeout an http_request may ask for
+const MAX_REQUEST_TIMEOUT_SECS: u64 = 120;
+
+/// Longest timeout a download_file may ask for
+const MAX_DOWNLOAD_TIMEOUT_SECS: u64 = 600;
+
+/// Minimum time between download progress notifications
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Maximum number of open HTTP sessions
+const MAX_HTTP_SESSIONS: usize = 16;
+
+/// Maximum number of redirects followed for one request
+const MAX_REDIRECTS: usize = 10;
+
+/// How long a fetched robots.txt is trusted before fetching it again
+const ROBOTS_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Longest Crawl-delay honoured from a robots.txt
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+
+/// The robots.txt rules that apply to this server's user agent
+#[derive(Debug, Default)]
+struct RobotsRules {
+    /// (allow, path pattern) pairs
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    /// Parse robots.txt, keeping the group for `user_agent` or else the `*` group
+    fn parse(content: &str, user_agent: &str) -> Self {
+        // The product token, e.g. "mcp-test" for "MCP-Test/1.0"
+        let token = user_agent
+            .split('/')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+
+        let mut specific = RobotsRules::default();
+        let mut wildcard = RobotsRules::default();
+        let mut found_specific = false;
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let field = field.trim().to_lowercase();
+            let value = value.trim();
+
+            if field == "user-agent" {
+                // A user-agent line after rules starts a new group
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_lowercase());
+                continue;
+            }
+            in_rules = true;
+
+            let matches_us = agents
+                .iter()
+                .any(|agent| agent != "*" && !token.is_empty() && token.contains(agent.as_str()));
+            let group = if matches_us {
+                found_specific = true;
+                &mut specific
+            } else if agents.iter().any(|agent| agent == "*") {
+                &mut wildcard
+            } else {
+                continue;
+            };
+
+            match field.as_str() {
+                // An empty Disallow allows everything
+                "allow" | "disallow" if !value.is_empty() => {
+                    group.rules.push((field == "allow", value.to_string()));
+                }
+                "crawl-delay" => {
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                        .map(|secs| Duration::from_secs_f64(secs).min(MAX_CRAWL_DELAY));
+                }
+                _ => {}
+            }
+        }
+
+        if found_specific { specific } else { wildcard }
+    }
+
+    /// Whether `path` may be fetched; the longest matching rule wins and
+    /// Allow wins ties
+    fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| robots_pattern_matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Match a robots.txt path pattern, where `*` matches any run of characters
+/// and a trailing `$` anchors the end
+fn robots_pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index == parts.len() - 1;
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+/// A session's client with the cookie jar and default headers it was built
+/// with, to build clients for the same session with other settings
+#[derive(Clone)]
+struct HttpSession {
+    client: reqwest::Client,
+    jar: Arc<reqwest::cookie::Jar>,
+    headers: reqwest::header::HeaderMap,
+}
+
+/// Open HTTP sessions, keyed by the number in their "session-N" id. Each
+/// session client has its own cookie jar, so cookies set by one response are
+/// sent with later requests in the same session
+#[derive(Default)]
+struct HttpSessions {
+    sessions: BTreeMap<u64, HttpSession>,
+    next_id: u64,
+}
 
 /// HTTP MCP Server implementation
 struct HttpMCPServer {
-    initialized: bool,
     client: reqwest::Client,
+    /// One runtime for all requests, so pooled connections stay usable
+    runtime: tokio::runtime::Runtime,
+    workspace_root: PathBuf,
+    max_download_size: u64,
+    domain_policy: Arc<DomainPolicy>,
+    sessions: RefCell<HttpSessions>,
+    user_agent: String,
+    min_request_interval: Duration,
+    respect_robots: bool,
+    /// Robots rules per origin, with when they were fetched
+    robots: RefCell<HashMap<String, (Instant, Arc<RobotsRules>)>>,
+    /// When each domain was last requested
+    last_request: RefCell<HashMap<String, Instant>>,
+    auth_profiles: BTreeMap<String, AuthProfile>,
+}
+
+/// Client settings shared by the default client and session clients.
+/// `profile_domains` are the domains of the auth profile the client's
+/// requests carry, if it is limited to some
+fn client_builder(
+    user_agent: &str,
+    domain_policy: &Arc<DomainPolicy>,
+    profile_domains: &[String],
+) -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .user_agent(user_agent)
+        .redirect(redirect_policy(
+            Arc::clone(domain_policy),
+            profile_domains.to_vec(),
+        ))
+}
+
+/// Follow redirects only to hosts the domain policy allows and, when an auth
+/// profile is limited to some domains, only to those, so a redirect cannot
+/// reach a domain the agent may not request directly or carry a credential
+/// off its domains
+fn redirect_policy(
+    domain_policy: Arc<DomainPolicy>,
+    profile_domains: Vec<String>,
+) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error(format!("more than {MAX_REDIRECTS} redirects"));
+        }
+        let host = attempt.url().host_str().unwrap_or("").to_string();
+        if let Err(message) = domain_policy.check(&host) {
+            return attempt.error(message.trim_start_matches("Error: ").to_string());
+        }
+        if !profile_domains.is_empty()
+            && !profile_domains
+                .iter()
+                .any(|pattern| domain_matches(pattern, &host))
+        {
+            return attempt.error(format!(
+                "redirect to '{host}' is outside the auth profile's domains"
+            ));
+        }
+        attempt.follow()
+    })
 }
 
 impl HttpMCPServer {
-    fn new() -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(5))
-            .user_agent("MCP-Test/1.0")
+    fn new(args: Args) -> Result<Self> {
+        let runtime = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+
+        let workspace_root = args
+            .workspace
+            .canonicalize()
+            .unwrap_or_else(|_| args.workspace.clone());
+
+        let domain_policy = Arc::new(DomainPolicy::load(&args)?);
+        if domain_policy.allow_all {
+            eprintln!(
+                "[HTTP MCP] Domain policy: all domains allowed, {} denied",
+                domain_policy.deny.len()
+            );
+        } else {
+            eprintln!(
+                "[HTTP MCP] Domain policy: allowing {}; {} denied",
+                domain_policy.allow.join(", "),
+                domain_policy.deny.len()
+            );
+        }
+
+        let auth_profiles = load_auth_profiles(std::env::vars())?;
+        if !auth_profiles.is_empty() {
+            eprintln!(
+                "[HTTP MCP] Auth profiles: {}",
+                auth_profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+
+        let client = client_builder(&args.user_agent, &domain_policy, &[])
             .build()
             .expect("Failed to create HTTP client");
 
-        Self {
-            initialized: false,
+        Ok(Self {
             client,
+            runtime,
+            workspace_root,
+            max_download_size: args.max_download_size_mb * 1024 * 1024,
+            domain_policy,
+            sessions: RefCell::new(HttpSessions::default()),
+            user_agent: args.user_agent,
+            min_request_interval: Duration::from_millis(args.min_request_interval_ms),
+            respect_robots: args.respect_robots,
+            robots: RefCell::new(HashMap::new()),
+            last_request: RefCell::new(HashMap::new()),
+            auth_profiles,
+        })
+    }
+}
+
+impl ToolHandler for HttpMCPServer {
+    fn tools(&self) -> Vec<Tool> {
+        let auth_description = if self.auth_profiles.is_empty() {
+            "Named credential to authenticate with (none configured)".to_string()
+        } else {
+            format!(
+                "Named credential to authenticate with: {}",
+                self.auth_profiles
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        let session = || {
+            Param::string(
+                "session",
+                "Session id from create_http_session whose cookies to use",
+            )
+        };
+
+        vec![
+            Tool::new("fetch_url", "Fetch content from a URL")
+                .param(Param::string("url", "The URL to fetch").required()),
+            Tool::new(
+                "http_request",
+                "Make an HTTP request with any method, headers and body; returns status, headers and body",
+            )
+            .param(Param::string("url", "The URL to request").required())
+            .param(
+                Param::string("method", "HTTP method")
+                    .one_of(&["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD"])
+                    .default("GET"),
+            )
+            .param(Param::object("headers", "Request headers"))
+            .param(Param::any(
+                "json",
+                "JSON body; sets Content-Type: application/json",
+            ))
+            .param(Param::object(
+                "form",
+                "Form fields sent as application/x-www-form-urlencoded",
+            ))
+            .param(Param::string("body", "Raw request body"))
+            .param(session())
+            .param(Param::string("auth_profile", &auth_description))
+            .param(
+                Param::integer("timeout_secs", "Request timeout in seconds (max 120)").default(30),
+            ),
+            Tool::new(
+                "download_file",
+                "Download a URL to a file in the workspace, optionally verifying its SHA-256 checksum",
+            )
+            .param(Param::string("url", "The URL to download").required())
+            .param(Param::string("path", "Destination path relative to the workspace").required())
+            .param(Param::string(
+                "sha256",
+                "Expected SHA-256 checksum (hex); the file is discarded on mismatch",
+            ))
+            .param(Param::boolean("overwrite", "Replace an existing file").default(false))
+            .param(session())
+            .param(
+                Param::integer("timeout_secs", "Download timeout in seconds (max 600)").default(25),
+            ),
+            Tool::new(
+                "create_http_session",
+                "Create a session that keeps cookies between http_request and download_file calls, e.g. to stay logged in",
+            )
+            .param(Param::object(
+                "headers",
+                "Headers sent with every request in the session",
+            )),
+            Tool::new(
+                "close_http_session",
+                "Close an HTTP session and discard its cookies",
+            )
+            .param(Param::string("session", "Session id from create_http_session").required()),
+            Tool::new("check_weather", "Get current weather (mock data)")
+                .param(Param::string("city", "City name").required()),
+            Tool::new("get_time", "Get current time in various timezones")
+                .param(Param::string("timezone", "Timezone (e.g., UTC, EST, PST)")),
+            Tool::new("calculate", "Perform basic calculations")
+                .param(Param::string("expression", "Math expression to evaluate").required()),
+        ]
+    }
+
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        context: &ToolContext,
+    ) -> Result<ToolOutput> {
+        log(
+            LogLevel::Debug,
+            format!("Tool call: {} with args: {}", name, arguments),
+        );
+
+        match name {
+            "fetch_url" => self.fetch_url(arguments),
+            "http_request" => self.http_request(arguments),
+            "download_file" => self.download_file(arguments, context),
+            "create_http_session" => self.create_http_session(arguments),
+            "close_http_session" => self.close_http_session(arguments),
+            "check_weather" => self.check_weather(arguments),
+            "get_time" => self.get_time(arguments),
+            "calculate" => self.calculate(arguments),
+            _ => Err(UnknownTool(name.to_string()).into()),
+        }
+    }
+}
+
+impl HttpMCPServer {
+    /// Fetch content from a URL
+    fn fetch_url(&self, args: &Value) -> Result<ToolOutput> {
+        let url = args.get("url").and_then(|u| u.as_str()).unwrap_or("");
+
+        let url = match self.check_url(url) {
+            Ok(url) => url,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+        if let Err(message) = self.check_robots(&url) {
+            return Ok(ToolOutput::error(message));
+        }
+        self.wait_for_turn(&url);
+
+        let result = self.runtime.block_on(async {
+            match self.client.get(url.clone()).send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    match response.text().await {
+                        Ok(content) => {
+                            let truncated = content.len() > 500;
+                            let content = if truncated { &content[..500] } else { &content };
+
+                            ToolOutput::json(&json!({
+                                "url": url.as_str(),
+                                "status": status.as_u16(),
+                                "content": content,
+                                "truncated": truncated
+                            }))
+                        }
+                        Err(e) => ToolOutput::error(format!("Error reading response body: {}", e)),
+                    }
+                }
+                Err(e) => ToolOutput::error(format!("Error fetching URL: {}", e)),
+            }
+        });
+
+        Ok(result)
+    }
+
+    /// Check that a URL is well formed and its domain may be requested
+    fn check_url(&self, url: &str) -> Result<url::Url, String> {
+        let parsed_url =
+            url::Url::parse(url).map_err(|_| "Error: Invalid URL format".to_string())?;
+
+        if !matches!(parsed_url.scheme(), "http" | "https") {
+            return Err(format!(
+                "Error: Unsupported URL scheme '{}'",
+                parsed_url.scheme()
+            ));
+        }
+
+        let host = parsed_url.host_str().unwrap_or("");
+        self.domain_policy.check(host)?;
+
+        Ok(parsed_url)
+    }
+
+    /// Check robots.txt for a URL, fetching and caching it per origin
+    fn check_robots(&self, url: &url::Url) -> Result<(), String> {
+        if !self.respect_robots {
+            return Ok(());
+        }
+
+        let rules = self.robots_rules(url);
+        if rules.allows(url.path()) {
+            Ok(())
+        } else {
+            Err(format!("Error: {} is disallowed by robots.txt", url))
         }
     }
 
-    /// Handle JSON-RPC request
-    fn handle_request(&mut self, request: Value) -> Result<Option<Value>> {
-        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
+    /// The cached robots rules for a URL's origin
+    fn robots_rules(&self, url: &url::Url) -> Arc<RobotsRules> {
+        let origin = url.origin().ascii_serialization();
+        if let Some((fetched, rules)) = self.robots.borrow().get(&origin)
+            && fetched.elapsed() < ROBOTS_CACHE_TTL
+        {
+            return Arc::clone(rules);
+        }
+
+        // A missing or unreachable robots.txt allows everything
+        let robots_url = format!("{}/robots.txt", origin);
+        let content = self.runtime.block_on(async {
+            let response = self.client.get(&robots_url).send().await.ok()?;
+            if !response.status().is_success() {
+                return None;
+            }
+            response.text().await.ok()
+        });
+        let rules = Arc::new(
+            content
+                .map(|content| RobotsRules::parse(&content, &self.user_agent))
+                .unwrap_or_default(),
+        );
 
-        let default_params = json!({});
-        let params = request.get("params").unwrap_or(&default_params);
+        self.robots
+            .borrow_mut()
+            .insert(origin, (Instant::now(), Arc::clone(&rules)));
+        rules
+    }
 
-        let request_id = request.get("id");
+    /// Sleep until the URL's domain may be requested again, honouring the
+    /// configured interval and any robots.txt Crawl-delay
+    fn wait_for_turn(&self, url: &url::Url) {
+        let host = url.host_str().unwrap_or("").to_string();
 
-        eprintln!("[HTTP MCP] Handling request: {}", method);
+        let mut interval = self.min_request_interval;
+        if self.respect_robots
+            && let Some((_, rules)) = self
+                .robots
+                .borrow()
+                .get(&url.origin().ascii_serialization())
+            && let Some(delay) = rules.crawl_delay
+        {
+            interval = interval.max(delay);
+        }
 
-        match method {
-            "initialize" => Ok(Some(self.handle_initialize(request_id, params)?)),
-            "initialized" => {
-                // Notification, no response needed
-                self.initialized = true;
-                eprintln!("[HTTP MCP] Client confirmed initialization");
-                Ok(None)
+        let last = self.last_request.borrow().get(&host).copied();
+        if let Some(last) = last {
+            let elapsed = last.elapsed();
+            if elapsed < interval {
+                let wait = interval - elapsed;
+                log(
+                    LogLevel::Info,
+                    format!("Waiting {:?} before requesting {}", wait, host),
+                );
+                std::thread::sleep(wait);
             }
-            "tools/list" => Ok(Some(self.handle_tools_list(request_id)?)),
-            "tools/call" => Ok(Some(self.handle_tool_call(request_id, params)?)),
-            _ => Ok(Some(self.error_response(
-                request_id,
-                -32601,
-                &format!("Method not found: {}", method),
-            ))),
         }
+        self.last_request.borrow_mut().insert(host, Instant::now());
     }
 
-    /// Handle initialize request
-    fn handle_initialize(&self, request_id: Option<&Value>, params: &Value) -> Result<Value> {
-        let default_client_info = json!({});
-        let client_info = params.get("clientInfo").unwrap_or(&default_client_info);
-        eprintln!("[HTTP MCP] Initialize request from client: {}", client_info);
-
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": {
-                "protocolVersion": "2024-11-05",
-                "serverInfo": {
-                    "name": "http-mcp-server",
-                    "version": "1.0.0"
-                },
-                "capabilities": {
-                    "tools": {
-                        "listChanged": true
-                    }
-                }
+    /// Make an HTTP request with an arbitrary method, headers and body
+    fn http_request(&self, args: &Value) -> Result<ToolOutput> {
+        let url = args.get("url").and_then(|u| u.as_str()).unwrap_or("");
+
+        let url = match self.check_url(url) {
+            Ok(url) => url,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+
+        let method = args
+            .get("method")
+            .and_then(|m| m.as_str())
+            .unwrap_or("GET")
+            .to_uppercase();
+        let method = match method.as_str() {
+            "GET" | "POST" | "PUT" | "PATCH" | "DELETE" | "HEAD" => {
+                reqwest::Method::from_bytes(method.as_bytes())?
             }
-        }))
-    }
-
-    /// Return list of available tools
-    fn handle_tools_list(&self, request_id: Option<&Value>) -> Result<Value> {
-        let tools = json!([
-            {
-                "name": "fetch_url",
-                "description": "Fetch content from a URL",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "url": {"type": "string", "description": "The URL to fetch"}
-                    },
-                    "required": ["url"]
-                }
+            _ => {
+                return Ok(ToolOutput::error(format!(
+                    "Error: Unsupported method '{}'",
+                    method
+                )));
+            }
+        };
+
+        let bodies = ["json", "form", "body"]
+            .iter()
+            .filter(|key| args.get(**key).is_some())
+            .count();
+        if bodies > 1 {
+            return Ok(ToolOutput::error(
+                "Error: Use only one of 'json', 'form' or 'body'".to_string(),
+            ));
+        }
+
+        let timeout_secs = args
+            .get("timeout_secs")
+            .and_then(|t| t.as_u64())
+            .unwrap_or(30)
+            .min(MAX_REQUEST_TIMEOUT_SECS);
+
+        let auth = match args.get("auth_profile").and_then(|a| a.as_str()) {
+            Some(name) => match self.auth_profile(name, &url) {
+                Ok(profile) => Some(profile),
+                Err(message) => return Ok(ToolOutput::error(message)),
             },
-            {
-                "name": "check_weather",
-                "description": "Get current weather (mock data)",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "city": {"type": "string", "description": "City name"}
-                    },
-                    "required": ["city"]
+            None => None,
+        };
+
+        // A credential limited to some domains must not follow a redirect off them
+        let client = match auth.filter(|profile| !profile.domains.is_empty()) {
+            Some(profile) => self.profile_client(args, profile),
+            None => self.session_client(args),
+        };
+        let client = match client {
+            Ok(client) => client,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+        let secrets = auth
+            .map(|profile| profile.secrets.as_slice())
+            .unwrap_or(&[]);
+
+        self.wait_for_turn(&url);
+        let mut request = client
+            .request(method, url)
+            .timeout(Duration::from_secs(timeout_secs));
+
+        if let Some(headers) = args.get("headers").and_then(|h| h.as_object()) {
+            for (name, value) in headers {
+                // The auth profile's header takes the place of the agent's
+                if auth.is_some_and(|profile| profile.header.as_str().eq_ignore_ascii_case(name)) {
+                    continue;
                 }
-            },
-            {
-                "name": "get_time",
-                "description": "Get current time in various timezones",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "timezone": {"type": "string", "description": "Timezone (e.g., UTC, EST, PST)"}
+                request = request.header(name.as_str(), plain_string(value));
+            }
+        }
+
+        if let Some(profile) = auth {
+            request = request.header(profile.header.clone(), profile.value.clone());
+        }
+
+        if let Some(body) = args.get("json") {
+            request = request.json(body);
+        } else if let Some(form) = args.get("form").and_then(|f| f.as_object()) {
+            let fields: Vec<(String, String)> = form
+                .iter()
+                .map(|(key, value)| (key.clone(), plain_string(value)))
+                .collect();
+            request = request.form(&fields);
+        } else if let Some(body) = args.get("body").and_then(|b| b.as_str()) {
+            request = request.body(body.to_string());
+        }
+
+        let result = self.runtime.block_on(async {
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => return ToolOutput::error(format!("Error making request: {}", e)),
+            };
+
+            let status = response.status();
+            let mut headers = serde_json::Map::new();
+            for (name, value) in response.headers() {
+                let value = redact(&String::from_utf8_lossy(value.as_bytes()), secrets);
+                // Repeated headers such as Set-Cookie are joined like HTTP does
+                match headers.get_mut(name.as_str()) {
+                    Some(Value::String(existing)) => {
+                        existing.push_str(", ");
+                        existing.push_str(&value);
+                    }
+                    _ => {
+                        headers.insert(name.to_string(), Value::String(value));
                     }
-                }
-            },
-            {
-                "name": "calculate",
-                "description": "Perform basic calculations",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "expression": {"type": "string", "description": "Math expression to evaluate"}
-                    },
-                    "required": ["expression"]
                 }
             }
-        ]);
 
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": {
-                "tools": tools
-            }
-        }))
+            let bytes = match response.bytes().await {
+                Ok(bytes) => bytes,
+                Err(e) => return ToolOutput::error(format!("Error reading response body: {}", e)),
+            };
+            let truncated = bytes.len() > MAX_RESPONSE_BYTES;
+            let body = redact(
+                &String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_RESPONSE_BYTES)]),
+                secrets,
+            );
+
+            let response = json!({
+                "status": status.as_u16(),
+                "headers": headers,
+                "body": body,
+                "body_truncated": truncated
+            });
+
+            ToolOutput::json(&response)
+        });
+
+        Ok(result)
     }
 
-    /// Execute a tool and return the result
-    fn handle_tool_call(&self, request_id: Option<&Value>, params: &Value) -> Result<Value> {
-        let tool_name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
+    /// Look up an auth profile and check it may be sent to the URL's domain
+    fn auth_profile(&self, name: &str, url: &url::Url) -> Result<&AuthProfile, String> {
+        let profile = self
+            .auth_profiles
+            .get(&name.to_lowercase())
+            .ok_or_else(|| {
+                let available: Vec<&str> = self.auth_profiles.keys().map(|k| k.as_str()).collect();
+                format!(
+                    "Error: Unknown auth profile '{}' (available: {})",
+                    name,
+                    if available.is_empty() {
+                        "none".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                )
+            })?;
 
-        let default_arguments = json!({});
-        let arguments = params.get("arguments").unwrap_or(&default_arguments);
+        let host = url.host_str().unwrap_or("").to_lowercase();
+        if !profile.domains.is_empty()
+            && !profile
+                .domains
+                .iter()
+                .any(|pattern| domain_matches(pattern, &host))
+        {
+            return Err(format!(
+                "Error: Auth profile '{}' may not be used with domain '{}'",
+                name, host
+            ));
+        }
 
-        eprintln!(
-            "[HTTP MCP] Tool call: {} with args: {}",
-            tool_name, arguments
-        );
+        Ok(profile)
+    }
 
-        let result = match tool_name {
-            "fetch_url" => self.fetch_url(arguments)?,
-            "check_weather" => self.check_weather(arguments)?,
-            "get_time" => self.get_time(arguments)?,
-            "calculate" => self.calculate(arguments)?,
-            _ => {
-                return Ok(self.error_response(
-                    request_id,
-                    -32602,
-                    &format!("Unknown tool: {}", tool_name),
-                ));
+    /// Resolve a download destination inside the workspace, creating its parent directories
+    fn download_path(&self, path: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        if relative.is_absolute()
+            || relative
+                .components()
+                .any(|c| matches!(c, Component::ParentDir))
+        {
+            return Err(format!(
+                "Error: Path {} must be relative to the workspace without '..'",
+                path
+            ));
+        }
+
+        let full_path = self.workspace_root.join(relative);
+        let parent = full_path
+            .parent()
+            .ok_or_else(|| format!("Error: Invalid path {}", path))?;
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Error: Failed to create {:?}: {}", parent, e))?;
+
+        // Symlinked directories could still point outside
+        let canonical_parent = parent
+            .canonicalize()
+            .map_err(|e| format!("Error: Failed to resolve {:?}: {}", parent, e))?;
+        if !canonical_parent.starts_with(&self.workspace_root) {
+            return Err(format!("Error: Path {} is outside workspace", path));
+        }
+
+        Ok(canonical_parent.join(full_path.file_name().unwrap_or_default()))
+    }
+
+    /// Stream a URL to a workspace file
+    fn download_file(&self, args: &Value, context: &ToolContext) -> Result<ToolOutput> {
+        let url = args.get("url").and_then(|u| u.as_str()).unwrap_or("");
+        let path = args.get("path").and_then(|p| p.as_str()).unwrap_or("");
+
+        let url = match self.check_url(url) {
+            Ok(url) => url,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+        let destination = match self.download_path(path) {
+            Ok(destination) => destination,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+
+        let overwrite = args
+            .get("overwrite")
+            .and_then(|o| o.as_bool())
+            .unwrap_or(false);
+        if destination.exists() && !overwrite {
+            return Ok(ToolOutput::error(format!(
+                "Error: {} already exists (set overwrite to replace it)",
+                path
+            )));
+        }
+
+        let client = match self.session_client(args) {
+            Ok(client) => client,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+
+        let expected_sha256 = args
+            .get("sha256")
+            .and_then(|s| s.as_str())
+            .map(|s| s.trim().to_lowercase());
+
+        let timeout_secs = args
+            .get("timeout_secs")
+            .and_then(|t| t.as_u64())
+            .unwrap_or(25)
+            .min(MAX_DOWNLOAD_TIMEOUT_SECS);
+
+        // Downloads land in a temporary file and are renamed once complete and verified
+        let temp_path = destination.with_file_name(format!(
+            ".{}.download",
+            destination
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        ));
+
+        if let Err(message) = self.check_robots(&url) {
+            return Ok(ToolOutput::error(message));
+        }
+        self.wait_for_turn(&url);
+
+        let started = Instant::now();
+        let outcome = self.runtime.block_on(self.stream_to_file(
+            &client,
+            url,
+            &temp_path,
+            Duration::from_secs(timeout_secs),
+            context,
+        ));
+
+        let (bytes, sha256) = match outcome {
+            Ok(result) => result,
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                return Ok(ToolOutput::error(format!("Error downloading: {}", e)));
             }
         };
 
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": result
-        }))
+        if let Some(expected) = &expected_sha256
+            && *expected != sha256
+        {
+            let _ = std::fs::remove_file(&temp_path);
+            return Ok(ToolOutput::error(format!(
+                "Error: Checksum mismatch for {}: expected {}, got {}",
+                path, expected, sha256
+            )));
+        }
+
+        if let Err(e) = std::fs::rename(&temp_path, &destination) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Ok(ToolOutput::error(format!("Error saving {}: {}", path, e)));
+        }
+
+        let elapsed = started.elapsed().as_secs_f64();
+        let result = json!({
+            "path": path,
+            "bytes": bytes,
+            "sha256": sha256,
+            "verified": expected_sha256.is_some(),
+            "seconds": (elapsed * 1000.0).round() / 1000.0
+        });
+
+        Ok(ToolOutput::json(&result))
     }
 
-    /// Fetch content from a URL
-    fn fetch_url(&self, args: &Value) -> Result<Value> {
-        let url = args.get("url").and_then(|u| u.as_str()).unwrap_or("");
+    /// Write a response body to `path` chunk by chunk, enforcing the size
+    /// limit; returns the size and SHA-256 of what was written
+    async fn stream_to_file(
+        &self,
+        client: &reqwest::Client,
+        url: url::Url,
+        path: &Path,
+        timeout: Duration,
+        context: &ToolContext,
+    ) -> Result<(u64, String)> {
+        use sha2::{Digest, Sha256};
+
+        let mut response = client
+            .get(url)
+            .timeout(timeout)
+            .send()
+            .await?
+            .error_for_status()?;
 
-        // Only fetch URLs from safe domains for testing
-        let safe_domains = [
-            "httpbin.org",
-            "jsonplaceholder.typicode.com",
-            "api.github.com",
-        ];
-
-        if let Ok(parsed_url) = url::Url::parse(url) {
-            let host = parsed_url.host_str().unwrap_or("");
-
-            if !safe_domains.iter().any(|&domain| host.contains(domain)) {
-                return Ok(json!({
-                    "content": [{
-                        "type": "text",
-                        "text": format!("Error: URL domain '{}' not in safe list for testing", host)
-                    }],
-                    "isError": true
-                }));
+        let total = response.content_length();
+        if let Some(total) = total
+            && total > self.max_download_size
+        {
+            anyhow::bail!(
+                "file is {} bytes, over the {} byte limit",
+                total,
+                self.max_download_size
+            );
+        }
+
+        let mut file = std::fs::File::create(path)?;
+        let mut hasher = Sha256::new();
+        let mut written = 0u64;
+        let mut last_progress = Instant::now();
+
+        while let Some(chunk) = response.chunk().await? {
+            written += chunk.len() as u64;
+            if written > self.max_download_size {
+                anyhow::bail!(
+                    "download exceeded the {} byte limit",
+                    self.max_download_size
+                );
+            }
+            file.write_all(&chunk)?;
+            hasher.update(&chunk);
+
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                report_progress(context, written, total);
             }
-        } else {
-            return Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": "Error: Invalid URL format"
-                }],
-                "isError": true
-            }));
         }
+        file.flush()?;
+        report_progress(context, written, total);
 
-        // Create runtime for async request
-        let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+        Ok((written, format!("{:x}", hasher.finalize())))
+    }
 
-        let result = rt.block_on(async {
-            match self.client.get(url).send().await {
-                Ok(response) => {
-                    let status = response.status();
-                    match response.text().await {
-                        Ok(content) => {
-                            let truncated_content = if content.len() > 500 {
-                                format!("{}...", &content[..500])
-                            } else {
-                                content
-                            };
-
-                            json!({
-                                "content": [{
-                                    "type": "text",
-                                    "text": format!("Status: {}\nContent (first 500 chars):\n{}", status, truncated_content)
-                                }],
-                                "isError": false
-                            })
-                        },
-                        Err(e) => {
-                            json!({
-                                "content": [{
-                                    "type": "text",
-                                    "text": format!("Error reading response body: {}", e)
-                                }],
-                                "isError": true
-                            })
-                        }
+    /// The session named in `args`, if any
+    fn session(&self, args: &Value) -> Result<Option<HttpSession>, String> {
+        let Some(session) = args.get("session").and_then(|s| s.as_str()) else {
+            return Ok(None);
+        };
+
+        let sessions = self.sessions.borrow();
+        parse_session_id(session)
+            .and_then(|id| sessions.sessions.get(&id))
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| format!("Error: Unknown session '{session}'"))
+    }
+
+    /// The client of the session named in `args`, or the default client
+    fn session_client(&self, args: &Value) -> Result<reqwest::Client, String> {
+        Ok(self
+            .session(args)?
+            .map_or_else(|| self.client.clone(), |session| session.client))
+    }
+
+    /// A client like `session_client` that follows redirects only to the
+    /// auth profile's domains
+    fn profile_client(
+        &self,
+        args: &Value,
+        profile: &AuthProfile,
+    ) -> Result<reqwest::Client, String> {
+        let mut builder = client_builder(&self.user_agent, &self.domain_policy, &profile.domains);
+        if let Some(session) = self.session(args)? {
+            builder = builder
+                .cookie_provider(session.jar)
+                .default_headers(session.headers);
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Error: Failed to create HTTP client: {e}"))
+    }
+
+    /// Create a session with its own cookie jar
+    fn create_http_session(&self, args: &Value) -> Result<ToolOutput> {
+        let mut sessions = self.sessions.borrow_mut();
+        if sessions.sessions.len() >= MAX_HTTP_SESSIONS {
+            return Ok(ToolOutput::error(format!(
+                "Error: Too many open sessions (max {}); close one first",
+                MAX_HTTP_SESSIONS
+            )));
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(fields) = args.get("headers").and_then(|h| h.as_object()) {
+            for (name, value) in fields {
+                let name = match reqwest::header::HeaderName::from_bytes(name.as_bytes()) {
+                    Ok(name) => name,
+                    Err(_) => {
+                        return Ok(ToolOutput::error(format!(
+                            "Error: Invalid header '{}'",
+                            name
+                        )));
                     }
-                },
-                Err(e) => {
-                    json!({
-                        "content": [{
-                            "type": "text",
-                            "text": format!("Error fetching URL: {}", e)
-                        }],
-                        "isError": true
-                    })
-                }
+                };
+                let value = match reqwest::header::HeaderValue::from_str(&plain_string(value)) {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Ok(ToolOutput::error(format!(
+                            "Error: Invalid value for header '{}'",
+                            name
+                        )));
+                    }
+                };
+                headers.insert(name, value);
             }
-        });
+        }
 
-        Ok(result)
+        let jar = Arc::new(reqwest::cookie::Jar::default());
+        let client = client_builder(&self.user_agent, &self.domain_policy, &[])
+            .cookie_provider(Arc::clone(&jar))
+            .default_headers(headers.clone())
+            .build()?;
+
+        sessions.next_id += 1;
+        let id = sessions.next_id;
+        sessions.sessions.insert(
+            id,
+            HttpSession {
+                client,
+                jar,
+                headers,
+            },
+        );
+        log(LogLevel::Debug, format!("Created session-{}", id));
+
+        Ok(ToolOutput::json(
+            &json!({"session": format!("session-{}", id)}),
+        ))
+    }
+
+    /// Close a session and drop its cookies
+    fn close_http_session(&self, args: &Value) -> Result<ToolOutput> {
+        let session = args.get("session").and_then(|s| s.as_str()).unwrap_or("");
+
+        let removed = parse_session_id(session)
+            .and_then(|id| self.sessions.borrow_mut().sessions.remove(&id));
+        if removed.is_none() {
+            return Ok(ToolOutput::error(format!(
+                "Error: Unknown session '{}'",
+                session
+            )));
+        }
+
+        Ok(ToolOutput::json(
+            &json!({"session": session, "closed": true}),
+        ))
     }
 
     /// Return mock weather data
-    fn check_weather(&self, args: &Value) -> Result<Value> {
+    fn check_weather(&self, args: &Value) -> Result<ToolOutput> {
         let city = args
             .get("city")
             .and_then(|c| c.as_str())
@@ -268,17 +1266,15 @@ impl HttpMCPServer {
         let conditions = ["Sunny", "Cloudy", "Rainy", "Partly Cloudy"];
         let condition = conditions[rng.gen_range(0..conditions.len())];
 
-        Ok(json!({
-            "content": [{
-                "type": "text",
-                "text": format!("Weather in {}: {}°C, {}", city, temp, condition)
-            }],
-            "isError": false
-        }))
+        Ok(ToolOutput::json(&json!({
+            "city": city,
+            "temperature_c": temp,
+            "condition": condition
+        })))
     }
 
     /// Get current time in specified timezone
-    fn get_time(&self, args: &Value) -> Result<Value> {
+    fn get_time(&self, args: &Value) -> Result<ToolOutput> {
         let timezone = args
             .get("timezone")
             .and_then(|tz| tz.as_str())
@@ -309,26 +1305,23 @@ impl HttpMCPServer {
                 let local_time = now.with_timezone(&offset);
                 local_time.format("%Y-%m-%d %H:%M:%S JST").to_string()
             }
-            _ => {
-                format!(
-                    "Current time in {}: {} (timezone not recognized, showing UTC)",
-                    timezone,
-                    now.format("%Y-%m-%d %H:%M:%S UTC")
-                )
-            }
+            _ => now.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
         };
+        let recognized = matches!(
+            timezone.to_uppercase().as_str(),
+            "UTC" | "EST" | "PST" | "CET" | "JST"
+        );
 
-        Ok(json!({
-            "content": [{
-                "type": "text",
-                "text": format!("Current time in {}: {}", timezone, time_str)
-            }],
-            "isError": false
-        }))
+        Ok(ToolOutput::json(&json!({
+            "timezone": timezone,
+            "time": time_str,
+            // Unrecognized timezones fall back to UTC
+            "recognized": recognized
+        })))
     }
 
     /// Evaluate a math expression
-    fn calculate(&self, args: &Value) -> Result<Value> {
+    fn calculate(&self, args: &Value) -> Result<ToolOutput> {
         let expression = args
             .get("expression")
             .and_then(|e| e.as_str())
@@ -336,98 +1329,57 @@ impl HttpMCPServer {
 
         // Basic calculator using evalexpr crate for safety
         match evalexpr::eval(expression) {
-            Ok(result) => Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": format!("{} = {}", expression, result)
-                }],
-                "isError": false
-            })),
-            Err(e) => Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": format!("Error evaluating expression: {}", e)
-                }],
-                "isError": true
-            })),
-        }
-    }
-
-    /// Create an error response
-    fn error_response(&self, request_id: Option<&Value>, code: i32, message: &str) -> Value {
-        json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "error": {
-                "code": code,
-                "message": message
-            }
-        })
+            Ok(result) => Ok(ToolOutput::json(&json!({
+                "expression": expression,
+                "result": match result {
+                    evalexpr::Value::Int(n) => json!(n),
+                    evalexpr::Value::Float(n) => json!(n),
+                    evalexpr::Value::Boolean(b) => json!(b),
+                    other => json!(other.to_string()),
+                }
+            }))),
+            Err(e) => Ok(ToolOutput::error(format!(
+                "Error evaluating expression: {}",
+                e
+            ))),
+        }
     }
+}
 
-    /// Main server loop
-    fn run(&mut self) -> Result<()> {
-        eprintln!(
-            "[HTTP MCP] HTTP MCP server started, PID: {}",
-            std::process::id()
-        );
-
-        let stdin = io::stdin();
-        let reader = BufReader::new(stdin.lock());
-
-        eprintln!("[HTTP MCP] Starting main loop, waiting for input...");
-        for line in reader.lines() {
-            let line = line?;
-            let line = line.trim();
-
-            if line.is_empty() {
-                continue;
-            }
-
-            match serde_json::from_str::<Value>(line) {
-                Ok(request) => {
-                    eprintln!("[HTTP MCP] Received: {}", line);
+/// A JSON value as header or form text, without quotes around strings
+fn plain_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        other => other.to_string(),
+    }
+}
 
-                    match self.handle_request(request) {
-                        Ok(Some(response)) => {
-                            let response_json = serde_json::to_string(&response)?;
-                            println!("{}", response_json);
-                            io::stdout().flush()?;
-                            eprintln!("[HTTP MCP] Sent: {}", response_json);
-                        }
-                        Ok(None) => {
-                            // Notification, no response needed
-                        }
-                        Err(e) => {
-                            eprintln!("[HTTP MCP] Error handling request: {}", e);
-                            let error = self.error_response(None, -32603, &e.to_string());
-                            let error_json = serde_json::to_string(&error)?;
-                            println!("{}", error_json);
-                            io::stdout().flush()?;
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("[HTTP MCP] Failed to parse JSON: {}", e);
-                    let error = self.error_response(None, -32700, "Parse error");
-                    let error_json = serde_json::to_string(&error)?;
-                    println!("{}", error_json);
-                    io::stdout().flush()?;
-                }
-            }
-        }
+/// Parse a "session-N" id
+fn parse_session_id(session: &str) -> Option<u64> {
+    session.strip_prefix("session-")?.parse().ok()
+}
 
-        Ok(())
+/// Log download progress, and send a progress notification when the client
+/// asked for one with a progress token
+fn report_progress(context: &ToolContext, written: u64, total: Option<u64>) {
+    match total {
+        Some(total) => log(
+            LogLevel::Debug,
+            format!("Downloaded {} of {} bytes", written, total),
+        ),
+        None => log(LogLevel::Debug, format!("Downloaded {} bytes", written)),
     }
+    context.progress(written, total);
 }
 
 fn main() -> Result<()> {
-    // Set stdout to line buffering for better subprocess communication
-    use std::io::Write;
-    std::io::stdout().flush().unwrap();
-
-    let mut server = HttpMCPServer::new();
+    let server = HttpMCPServer::new(Args::parse())?;
+    eprintln!(
+        "[HTTP MCP] HTTP MCP server started, PID: {}",
+        std::process::id()
+    );
 
+    let mut server = McpServer::new("http-mcp-server", "HTTP MCP", server).verbose(true);
     if let Err(e) = server.run() {
         eprintln!("[HTTP MCP] Server error: {}", e);
         std::process::exit(1);
@@ -1,77 +1,233 @@
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use anyhow::{Context, Result, bail};
+use replicante::supervisor::async_client::{ApiError, AsyncSupervisorClient, RetryPolicy};
+use replicante::supervisor::network::{
+    self, Connection, ConnectionTermination, ContainerTraffic, GeoLookup, GeoPolicy, NetworkAlert,
+    NetworkAlertType, NetworkReport, NetworkStats, NetworkWhitelist, ReportQueue, TrafficLimits,
+    TrafficTracker, TrafficViolation,
+};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::time::Duration;
-use tokio::time::interval;
+use tokio::time::{Instant, interval};
 use tracing::{debug, error, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct NetworkStats {
-    timestamp: chrono::DateTime<chrono::Utc>,
-    connections: Vec<Connection>,
-    rx_bytes: u64,
-    tx_bytes: u64,
-    rx_packets: u64,
-    tx_packets: u64,
-    active_connections: usize,
+/// Socket tables read for each network namespace, with their protocol
+const SOCKET_TABLES: [(&str, &str); 4] = [
+    ("tcp", "TCP"),
+    ("tcp6", "TCP"),
+    ("udp", "UDP"),
+    ("udp6", "UDP"),
+];
+
+/// Longest wait between delivery attempts while the supervisor is unreachable
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Domains agents may resolve unless DNS_ALLOWED_DOMAINS or the whitelist
+/// file says otherwise,
+/// matching the resolver's configuration in dns/dnsmasq.conf
+const DEFAULT_ALLOWED_DOMAINS: &str = "anthropic.com,*.anthropic.com,openai.com,*.openai.com,\
+    replicante,*.replicante,supervisor,replicante-supervisor,proxy,dns,localhost";
+
+/// Domains remembered as already alerted on before the memory is reset
+const MAX_ALERTED_DOMAINS: usize = 10_000;
+
+/// Captures running at once; further alerting flows are not captured
+const MAX_CONCURRENT_CAPTURES: usize = 4;
+
+/// Bounds of the packet captures started on connection alerts
+struct CaptureSettings {
+    dir: PathBuf,
+    duration: Duration,
+    max_packets: u64,
+    snaplen: u32,
+    /// Captures kept on disk, oldest deleted first
+    keep: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Connection {
-    protocol: String,
-    local_addr: String,
-    remote_addr: String,
-    state: String,
-    pid: Option<u32>,
+/// Traffic shaping applied to a container over its limits
+struct Throttle {
+    pid: u32,
+    until: Instant,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct NetworkAlert {
-    timestamp: chrono::DateTime<chrono::Utc>,
-    alert_type: AlertType,
-    details: String,
-    connection: Option<Connection>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-enum AlertType {
-    UnauthorizedConnection,
-    HighBandwidth,
-    SuspiciousPort,
-    TooManyConnections,
-    DNSAnomaly,
+/// The resolver's query log, read from where the previous cycle stopped
+struct DnsLog {
+    path: PathBuf,
+    offset: u64,
+    /// DNS_ALLOWED_DOMAINS, overriding the whitelist's allowed domains
+    allowed: Option<Vec<String>>,
+    /// Each suspicious domain is alerted on once
+    alerted: HashSet<String>,
 }
 
 struct NetworkMonitor {
     supervisor_url: Option<String>,
     monitor_interval: Duration,
+    /// Name the supervisor knows this monitor by
+    name: String,
+    supervisor: Option<(AsyncSupervisorClient, ReportQueue)>,
+    retry_delay: Duration,
+    retry_at: Option<Instant>,
+    dns_log: Option<DnsLog>,
+    /// Docker's container state, read to name the containers sockets belong to
+    containers_dir: PathBuf,
+    container_names: HashMap<String, String>,
+    /// A process in each container's network namespace, found this cycle
+    container_pids: HashMap<String, u32>,
+    traffic: TrafficTracker,
+    /// Throttle containers over their limits instead of only alerting
+    enforce_limits: bool,
+    throttle_rate_kbit: u64,
+    throttle_duration: Duration,
+    throttle_interface: String,
+    throttled: HashMap<String, Throttle>,
+    capture: Option<CaptureSettings>,
+    /// Capture file and end of each running capture, by container and remote
+    captures: HashMap<String, (String, Instant)>,
+    /// Locates remote endpoints, when MaxMind databases are configured
+    geo: Option<GeoLookup>,
+    geo_policy: GeoPolicy,
     alerts: Vec<NetworkAlert>,
     previous_stats: Option<NetworkStats>,
-    whitelist: HashMap<String, bool>,
+    whitelist: NetworkWhitelist,
+    /// File the whitelist is loaded from, re-read on SIGHUP
+    whitelist_path: Option<PathBuf>,
 }
 
 impl NetworkMonitor {
-    fn new() -> Self {
+    fn new() -> Result<Self> {
         let supervisor_url = std::env::var("SUPERVISOR_URL").ok();
         let interval_secs = std::env::var("MONITOR_INTERVAL")
             .ok()
             .and_then(|s| s.parse::<u64>().ok())
             .unwrap_or(5);
+        let name = std::env::var("MONITOR_NAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "network-monitor".to_string());
+
+        // Reports are queued on disk until the supervisor accepts them
+        let supervisor = match &supervisor_url {
+            Some(url) => {
+                let queue_dir = std::env::var("MONITOR_QUEUE_DIR")
+                    .unwrap_or_else(|_| "/data/queue".to_string());
+                let queue_max = std::env::var("MONITOR_QUEUE_MAX")
+                    .ok()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1000);
+
+                Some((
+                    // Undelivered reports are queued and retried later
+                    AsyncSupervisorClient::new(Some(url.clone()))?.with_retry(RetryPolicy::none()),
+                    ReportQueue::open(queue_dir, queue_max)?,
+                ))
+            }
+            None => None,
+        };
+
+        // Lookups already in the log when the monitor starts are not checked
+        let dns_log = std::env::var("DNS_LOG_PATH").ok().map(|path| {
+            let path = PathBuf::from(path);
+            let allowed = std::env::var("DNS_ALLOWED_DOMAINS")
+                .ok()
+                .map(|domains| domain_list(&domains));
+
+            DnsLog {
+                offset: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                path,
+                allowed,
+                alerted: HashSet::new(),
+            }
+        });
 
-        let mut whitelist = HashMap::new();
-        // Allowed IPs/networks
-        whitelist.insert("127.0.0.1".to_string(), true);
-        whitelist.insert("172.20.".to_string(), true); // Docker network prefix
+        let capture = if env_or("CAPTURE_ON_ALERT", false) {
+            let dir = PathBuf::from(
+                std::env::var("CAPTURE_DIR").unwrap_or_else(|_| "/data/captures".to_string()),
+            );
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create capture directory {}", dir.display()))?;
+
+            Some(CaptureSettings {
+                dir,
+                duration: Duration::from_secs(env_or("CAPTURE_SECS", 60)),
+                max_packets: env_or("CAPTURE_MAX_PACKETS", 10_000),
+                snaplen: env_or("CAPTURE_SNAPLEN", 256),
+                keep: env_or("CAPTURE_KEEP", 100),
+            })
+        } else {
+            None
+        };
 
-        Self {
+        let country_db = std::env::var("GEOIP_COUNTRY_DB").ok().map(PathBuf::from);
+        let asn_db = std::env::var("GEOIP_ASN_DB").ok().map(PathBuf::from);
+        let geo_policy = GeoPolicy {
+            allowed_countries: env_list("GEO_ALLOWED_COUNTRIES"),
+            allowed_asns: env_list("GEO_ALLOWED_ASNS")
+                .iter()
+                .map(|asn| {
+                    asn.to_ascii_uppercase()
+                        .trim_start_matches("AS")
+                        .parse()
+                        .with_context(|| format!("Invalid ASN '{asn}' in GEO_ALLOWED_ASNS"))
+                })
+                .collect::<Result<_>>()?,
+        };
+        if !geo_policy.allowed_countries.is_empty() && country_db.is_none() {
+            bail!("GEO_ALLOWED_COUNTRIES needs GEOIP_COUNTRY_DB");
+        }
+        if !geo_policy.allowed_asns.is_empty() && asn_db.is_none() {
+            bail!("GEO_ALLOWED_ASNS needs GEOIP_ASN_DB");
+        }
+        let geo = if country_db.is_some() || asn_db.is_some() {
+            Some(GeoLookup::open(country_db.as_deref(), asn_db.as_deref())?)
+        } else {
+            None
+        };
+
+        let whitelist_path = std::env::var("NETWORK_WHITELIST").ok().map(PathBuf::from);
+        let whitelist = match &whitelist_path {
+            Some(path) => NetworkWhitelist::load(path)?,
+            None => NetworkWhitelist::default(),
+        };
+
+        Ok(Self {
             supervisor_url,
             monitor_interval: Duration::from_secs(interval_secs),
+            name,
+            supervisor,
+            retry_delay: Duration::from_secs(interval_secs),
+            retry_at: None,
+            dns_log,
+            containers_dir: std::env::var("DOCKER_CONTAINERS_DIR")
+                .unwrap_or_else(|_| "/host/docker/containers".to_string())
+                .into(),
+            container_names: HashMap::new(),
+            container_pids: HashMap::new(),
+            traffic: TrafficTracker::new(TrafficLimits {
+                max_bytes_per_sec: env_or("BANDWIDTH_LIMIT_MB_PER_SEC", 10.0) * 1024.0 * 1024.0,
+                max_connections_per_minute: std::env::var("RATE_LIMIT_PER_MINUTE")
+                    .ok()
+                    .and_then(|s| s.parse().ok()),
+                max_icmp_per_minute: Some(env_or("ICMP_RATE_LIMIT_PER_MINUTE", 60)),
+            }),
+            enforce_limits: env_or("ENFORCE_LIMITS", false),
+            throttle_rate_kbit: env_or("THROTTLE_RATE_KBIT", 1024),
+            throttle_duration: Duration::from_secs(env_or("THROTTLE_SECS", 300)),
+            throttle_interface: std::env::var("THROTTLE_INTERFACE")
+                .unwrap_or_else(|_| "eth0".to_string()),
+            throttled: HashMap::new(),
+            capture,
+            captures: HashMap::new(),
+            geo,
+            geo_policy,
             alerts: Vec::new(),
             previous_stats: None,
             whitelist,
-        }
+            whitelist_path,
+        })
     }
 
     async fn run(&mut self) -> Result<()> {
@@ -82,14 +238,57 @@ impl NetworkMonitor {
             info!("Reporting to supervisor at: {}", url);
         }
 
+        if self.geo.is_some() {
+            info!("Locating remote endpoints with GeoIP databases");
+        }
+
+        if let Some(dns_log) = &self.dns_log {
+            info!(
+                "Watching DNS queries in {path}",
+                path = dns_log.path.display()
+            );
+        }
+
+        if let Some(path) = &self.whitelist_path {
+            info!(
+                "Network whitelist loaded from {path}, reloaded on SIGHUP",
+                path = path.display()
+            );
+        }
+
         let mut interval = interval(self.monitor_interval);
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .context("Failed to install SIGHUP handler")?;
 
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = self.monitor_cycle().await {
+                        error!("Monitor cycle failed: {}", e);
+                    }
+                }
+                _ = hangup.recv() => self.reload_whitelist(),
+            }
+        }
+    }
+
+    /// Re-read the whitelist file, keeping the current whitelist if it is
+    /// missing or invalid
+    fn reload_whitelist(&mut self) {
+        let Some(path) = &self.whitelist_path else {
+            warn!("Received SIGHUP but NETWORK_WHITELIST is not set");
+            return;
+        };
 
-            if let Err(e) = self.monitor_cycle().await {
-                error!("Monitor cycle failed: {}", e);
+        match NetworkWhitelist::load(path) {
+            Ok(whitelist) => {
+                info!(
+                    "Reloaded network whitelist from {path}",
+                    path = path.display()
+                );
+                self.whitelist = whitelist;
             }
+            Err(e) => error!("Failed to reload network whitelist, keeping the current one: {e:#}"),
         }
     }
 
@@ -97,7 +296,7 @@ impl NetworkMonitor {
         debug!("Starting monitor cycle");
 
         // Collect network statistics
-        let stats = self.collect_network_stats()?;
+        let stats = self.collect_network_stats();
 
         // Analyze for anomalies
         self.analyze_connections(&stats)?;
@@ -107,8 +306,18 @@ impl NetworkMonitor {
             self.check_bandwidth(&prev, &stats)?;
         }
 
+        // Check each container against its traffic limits
+        self.check_container_traffic(&stats).await;
+
+        // Check lookups made since the last cycle
+        if self.dns_log.is_some()
+            && let Err(e) = self.check_dns()
+        {
+            warn!("Failed to check DNS queries: {e:#}");
+        }
+
         // Report to supervisor if configured
-        if self.supervisor_url.is_some() {
+        if self.supervisor.is_some() {
             self.report_to_supervisor(&stats).await?;
         }
 
@@ -118,7 +327,7 @@ impl NetworkMonitor {
         Ok(())
     }
 
-    fn collect_network_stats(&self) -> Result<NetworkStats> {
+    fn collect_network_stats(&mut self) -> NetworkStats {
         let mut stats = NetworkStats {
             timestamp: chrono::Utc::now(),
             connections: Vec::new(),
@@ -127,16 +336,43 @@ impl NetworkMonitor {
             rx_packets: 0,
             tx_packets: 0,
             active_connections: 0,
+            containers: HashMap::new(),
         };
 
-        // Read TCP connections from /proc/net/tcp
-        if let Ok(connections) = self.read_proc_net_tcp("/host/proc/net/tcp") {
-            stats.connections.extend(connections);
+        // Read TCP and UDP sockets from /proc/net
+        for (table, protocol) in SOCKET_TABLES {
+            let path = format!("/host/proc/net/{table}");
+            if let Ok(connections) = self.read_proc_net_sockets(&path, protocol, None) {
+                stats.connections.extend(connections);
+            }
         }
 
-        // Read TCP6 connections
-        if let Ok(connections) = self.read_proc_net_tcp("/host/proc/net/tcp6") {
-            stats.connections.extend(connections);
+        // Read each container's sockets through one of its processes
+        self.container_pids.clear();
+        match network::container_net_namespaces(Path::new("/host/proc")) {
+            Ok(namespaces) => {
+                for namespace in namespaces {
+                    let container = self.container_name(&namespace.container_id);
+                    self.container_pids.insert(container.clone(), namespace.pid);
+                    let mut traffic = ContainerTraffic::default();
+                    for (table, protocol) in SOCKET_TABLES {
+                        let path = format!("/host/proc/{pid}/net/{table}", pid = namespace.pid);
+                        if let Ok(connections) =
+                            self.read_proc_net_sockets(&path, protocol, Some(&container))
+                        {
+                            traffic.connections += connections.len();
+                            stats.connections.extend(connections);
+                        }
+                    }
+                    let path = format!("/host/proc/{pid}/net/dev", pid = namespace.pid);
+                    if let Ok((rx, tx, _, _)) = self.read_proc_net_dev(&path) {
+                        traffic.rx_bytes = rx;
+                        traffic.tx_bytes = tx;
+                    }
+                    stats.containers.insert(container, traffic);
+                }
+            }
+            Err(e) => debug!("Failed to find container network namespaces: {e:#}"),
         }
 
         // Read network interface stats from /proc/net/dev
@@ -147,85 +383,40 @@ impl NetworkMonitor {
             stats.tx_packets = tx_p;
         }
 
-        stats.active_connections = stats.connections.len();
-
-        debug!("Collected {} connections", stats.active_connections);
-
-        Ok(stats)
-    }
-
-    fn read_proc_net_tcp(&self, path: &str) -> Result<Vec<Connection>> {
-        let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
-        let reader = BufReader::new(file);
-        let mut connections = Vec::new();
-
-        for (index, line) in reader.lines().enumerate() {
-            if index == 0 {
-                continue;
-            } // Skip header
-
-            let line = line?;
-            let parts: Vec<&str> = line.split_whitespace().collect();
-
-            if parts.len() < 10 {
-                continue;
+        // Locate remote endpoints on the internet
+        if let Some(geo) = &self.geo {
+            for conn in &mut stats.connections {
+                if let Ok(remote) = conn.remote_addr.parse::<SocketAddr>()
+                    && network::is_public_ip(remote.ip())
+                {
+                    conn.geo = geo.lookup(remote.ip());
+                }
             }
-
-            let local_addr = self.parse_hex_addr(parts[1])?;
-            let remote_addr = self.parse_hex_addr(parts[2])?;
-            let state = self.parse_tcp_state(parts[3])?;
-
-            connections.push(Connection {
-                protocol: "TCP".to_string(),
-                local_addr,
-                remote_addr,
-                state,
-                pid: None, // Would need to read from /proc/*/fd/* to get PID
-            });
-        }
-
-        Ok(connections)
-    }
-
-    fn parse_hex_addr(&self, hex_addr: &str) -> Result<String> {
-        let parts: Vec<&str> = hex_addr.split(':').collect();
-        if parts.len() != 2 {
-            return Ok("unknown".to_string());
         }
 
-        let ip = u32::from_str_radix(parts[0], 16).unwrap_or(0);
-        let port = u16::from_str_radix(parts[1], 16).unwrap_or(0);
+        stats.active_connections = stats.connections.len();
 
-        let ip_str = format!(
-            "{}.{}.{}.{}",
-            ip & 0xFF,
-            (ip >> 8) & 0xFF,
-            (ip >> 16) & 0xFF,
-            (ip >> 24) & 0xFF
-        );
+        debug!("Collected {} connections", stats.active_connections);
 
-        Ok(format!("{}:{}", ip_str, port))
+        stats
     }
 
-    fn parse_tcp_state(&self, hex_state: &str) -> Result<String> {
-        let state_num = u8::from_str_radix(hex_state, 16).unwrap_or(0);
-
-        let state = match state_num {
-            1 => "ESTABLISHED",
-            2 => "SYN_SENT",
-            3 => "SYN_RECV",
-            4 => "FIN_WAIT1",
-            5 => "FIN_WAIT2",
-            6 => "TIME_WAIT",
-            7 => "CLOSE",
-            8 => "CLOSE_WAIT",
-            9 => "LAST_ACK",
-            10 => "LISTEN",
-            11 => "CLOSING",
-            _ => "UNKNOWN",
-        };
+    fn container_name(&mut self, container_id: &str) -> String {
+        self.container_names
+            .entry(container_id.to_string())
+            .or_insert_with(|| network::container_name(&self.containers_dir, container_id))
+            .clone()
+    }
 
-        Ok(state.to_string())
+    fn read_proc_net_sockets(
+        &self,
+        path: &str,
+        protocol: &str,
+        container: Option<&str>,
+    ) -> Result<Vec<Connection>> {
+        let table =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to open {path}"))?;
+        Ok(network::parse_proc_net_sockets(&table, protocol, container))
     }
 
     fn read_proc_net_dev(&self, path: &str) -> Result<(u64, u64, u64, u64)> {
@@ -265,48 +456,82 @@ impl NetworkMonitor {
 
     fn analyze_connections(&mut self, stats: &NetworkStats) -> Result<()> {
         for conn in &stats.connections {
-            // Skip localhost and listening connections
-            if conn.remote_addr.starts_with("0.0.0.0")
-                || conn.remote_addr.starts_with("127.0.0.1")
-                || conn.state == "LISTEN"
-            {
+            // Skip localhost, listening and unconnected sockets
+            let Ok(remote) = conn.remote_addr.parse::<SocketAddr>() else {
+                continue;
+            };
+            if remote.ip().is_unspecified() || remote.ip().is_loopback() || conn.state == "LISTEN" {
                 continue;
             }
 
             // Check if connection is to allowed IP
-            let remote_ip = conn.remote_addr.split(':').next().unwrap_or("");
-            let mut allowed = false;
+            let allowed = self.whitelist.allows_ip(remote.ip());
 
-            for prefix in self.whitelist.keys() {
-                if remote_ip.starts_with(prefix) {
-                    allowed = true;
-                    break;
-                }
-            }
+            let owner = conn.container.as_deref().unwrap_or("host");
+            let location = conn
+                .geo
+                .as_ref()
+                .map(|geo| format!(" ({geo})"))
+                .unwrap_or_default();
 
             if !allowed && conn.state == "ESTABLISHED" {
-                warn!("Unauthorized connection detected: {}", conn.remote_addr);
+                warn!(
+                    "Unauthorized connection detected: {owner} connected to {remote_addr}{location}",
+                    remote_addr = conn.remote_addr
+                );
+                let capture = self.capture_flow(conn);
 
                 self.alerts.push(NetworkAlert {
                     timestamp: chrono::Utc::now(),
-                    alert_type: AlertType::UnauthorizedConnection,
-                    details: format!("Unauthorized connection to {}", conn.remote_addr),
+                    alert_type: NetworkAlertType::UnauthorizedConnection,
+                    details: format!(
+                        "{owner} connected to {remote_addr}{location}",
+                        remote_addr = conn.remote_addr
+                    ),
                     connection: Some(conn.clone()),
+                    container: None,
+                    capture,
                 });
             }
 
-            // Check for suspicious ports
-            if let Some(port_str) = conn.remote_addr.split(':').nth(1)
-                && let Ok(port) = port_str.parse::<u16>()
-                && self.is_suspicious_port(port)
+            // Check where the remote endpoint is
+            if !self.geo_policy.is_empty()
+                && conn.state == "ESTABLISHED"
+                && network::is_public_ip(remote.ip())
+                && let Some(reason) = self.geo_policy.violation(conn.geo.as_ref())
             {
-                warn!("Connection to suspicious port: {}", port);
+                let details = format!(
+                    "{owner} connected to {remote_addr}{location}: {reason}",
+                    remote_addr = conn.remote_addr
+                );
+                warn!("Connection outside the GeoIP policy: {details}");
+
+                self.alerts.push(NetworkAlert {
+                    timestamp: chrono::Utc::now(),
+                    alert_type: NetworkAlertType::GeoPolicyViolation,
+                    details,
+                    connection: Some(conn.clone()),
+                    container: None,
+                    capture: None,
+                });
+            }
+
+            // Check for suspicious ports
+            let port = remote.port();
+            if self.is_suspicious_port(port) || self.whitelist.blocks_port(port) {
+                warn!("Connection to suspicious port: {owner} connected to port {port}");
+                let capture = self.capture_flow(conn);
 
                 self.alerts.push(NetworkAlert {
                     timestamp: chrono::Utc::now(),
-                    alert_type: AlertType::SuspiciousPort,
-                    details: format!("Connection to suspicious port {}", port),
+                    alert_type: NetworkAlertType::SuspiciousPort,
+                    details: format!(
+                        "{owner} connected to suspicious port {port} at {remote_addr}",
+                        remote_addr = conn.remote_addr
+                    ),
                     connection: Some(conn.clone()),
+                    container: None,
+                    capture,
                 });
             }
         }
@@ -317,15 +542,346 @@ impl NetworkMonitor {
 
             self.alerts.push(NetworkAlert {
                 timestamp: chrono::Utc::now(),
-                alert_type: AlertType::TooManyConnections,
+                alert_type: NetworkAlertType::TooManyConnections,
                 details: format!("{} active connections", stats.active_connections),
                 connection: None,
+                container: None,
+                capture: None,
             });
         }
 
         Ok(())
     }
 
+    /// Alert on lookups of domains outside the allowlist or that look
+    /// generated by malware, from resolver log lines added since last cycle
+    fn check_dns(&mut self) -> Result<()> {
+        let Some(dns_log) = &mut self.dns_log else {
+            return Ok(());
+        };
+
+        let mut file = File::open(&dns_log.path)
+            .with_context(|| format!("Failed to open {}", dns_log.path.display()))?;
+        // Start over if the log was rotated or truncated
+        if file.metadata()?.len() < dns_log.offset {
+            dns_log.offset = 0;
+        }
+        file.seek(SeekFrom::Start(dns_log.offset))?;
+        let mut added = Vec::new();
+        file.read_to_end(&mut added)?;
+
+        // A partly written last line is read again on the next cycle
+        let Some(end) = added.iter().rposition(|&byte| byte == b'\n') else {
+            return Ok(());
+        };
+        dns_log.offset += end as u64 + 1;
+
+        // DNS_ALLOWED_DOMAINS wins over the whitelist file's domains
+        let allowed = match &dns_log.allowed {
+            Some(allowed) => allowed.clone(),
+            None if !self.whitelist.allowed_domains().is_empty() => {
+                domain_list(&self.whitelist.allowed_domains().join(","))
+            }
+            None => domain_list(DEFAULT_ALLOWED_DOMAINS),
+        };
+
+        if dns_log.alerted.len() > MAX_ALERTED_DOMAINS {
+            dns_log.alerted.clear();
+        }
+
+        for line in String::from_utf8_lossy(&added[..end]).lines() {
+            let Some(query) = network::parse_dnsmasq_query(line) else {
+                continue;
+            };
+
+            let details = if network::looks_generated(&query.domain) {
+                format!(
+                    "Lookup of generated-looking domain {domain} from {client}",
+                    domain = query.domain,
+                    client = query.client
+                )
+            } else if !network::is_domain_allowed(&query.domain, &allowed) {
+                format!(
+                    "Lookup of non-allowlisted domain {domain} from {client}",
+                    domain = query.domain,
+                    client = query.client
+                )
+            } else {
+                continue;
+            };
+
+            if !dns_log.alerted.insert(query.domain) {
+                continue;
+            }
+
+            warn!("{details}");
+            self.alerts.push(NetworkAlert {
+                timestamp: chrono::Utc::now(),
+                alert_type: NetworkAlertType::DNSAnomaly,
+                details,
+                connection: None,
+                container: None,
+                capture: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Alert on containers over their bandwidth or connection rate limits,
+    /// throttling them with tc when enforcing, and lift expired throttles
+    async fn check_container_traffic(&mut self, stats: &NetworkStats) {
+        let now = Instant::now();
+        let running = self.container_pids.keys().cloned().collect();
+        self.traffic.retain(&running);
+
+        let expired: Vec<String> = self
+            .throttled
+            .iter()
+            .filter(|(container, throttle)| {
+                throttle.until <= now || !running.contains(container.as_str())
+            })
+            .map(|(container, _)| container.clone())
+            .collect();
+        for container in expired {
+            if let Some(throttle) = self.throttled.remove(&container)
+                && running.contains(&container)
+            {
+                match self.shape(throttle.pid, None).await {
+                    Ok(()) => info!("Lifted traffic throttle on {container}"),
+                    Err(e) => warn!("Failed to lift traffic throttle on {container}: {e:#}"),
+                }
+            }
+        }
+
+        for (container, pid) in self.container_pids.clone() {
+            if self.throttled.contains_key(&container) {
+                continue;
+            }
+
+            let bandwidth = stats.containers.get(&container).and_then(|traffic| {
+                self.traffic
+                    .record_bandwidth(&container, traffic.rx_bytes, traffic.tx_bytes, now)
+            });
+            let remotes = stats
+                .connections
+                .iter()
+                .filter(|conn| {
+                    conn.container.as_deref() == Some(container.as_str())
+                        && conn.state == "ESTABLISHED"
+                })
+                .map(|conn| conn.remote_addr.as_str());
+            let connection_rate = self.traffic.record_connections(&container, remotes, now);
+            let icmp_rate = match (
+                std::fs::read_to_string(format!("/host/proc/{pid}/net/snmp")),
+                std::fs::read_to_string(format!("/host/proc/{pid}/net/snmp6")),
+            ) {
+                (Ok(snmp), snmp6) => self.traffic.record_icmp(
+                    &container,
+                    network::icmp_messages_sent(&snmp, &snmp6.unwrap_or_default()),
+                    now,
+                ),
+                (Err(e), _) => {
+                    debug!("Failed to read ICMP counters of {container}: {e}");
+                    None
+                }
+            };
+
+            let Some(violation) = bandwidth.or(connection_rate).or(icmp_rate) else {
+                continue;
+            };
+
+            let mut details = match violation {
+                TrafficViolation::Bandwidth {
+                    rx_per_sec,
+                    tx_per_sec,
+                } => format!(
+                    "{container} high bandwidth: RX={rx:.2} MB/s, TX={tx:.2} MB/s",
+                    rx = rx_per_sec / 1024.0 / 1024.0,
+                    tx = tx_per_sec / 1024.0 / 1024.0
+                ),
+                TrafficViolation::ConnectionRate { per_minute } => {
+                    format!("{container} opened {per_minute} new connections in the last minute")
+                }
+                TrafficViolation::IcmpRate { per_minute } => {
+                    format!("{container} sent {per_minute} ICMP messages per minute")
+                }
+            };
+
+            if self.enforce_limits {
+                match self.shape(pid, Some(self.throttle_rate_kbit)).await {
+                    Ok(()) => {
+                        details.push_str(&format!(
+                            ", throttled to {rate} kbit/s for {duration:?}",
+                            rate = self.throttle_rate_kbit,
+                            duration = self.throttle_duration
+                        ));
+                        self.throttled.insert(
+                            container.clone(),
+                            Throttle {
+                                pid,
+                                until: now + self.throttle_duration,
+                            },
+                        );
+                    }
+                    Err(e) => warn!("Failed to throttle {container}: {e:#}"),
+                }
+            }
+
+            warn!("{details}");
+            self.alerts.push(NetworkAlert {
+                timestamp: chrono::Utc::now(),
+                alert_type: NetworkAlertType::HighBandwidth,
+                details,
+                connection: None,
+                container: Some(container),
+                capture: None,
+            });
+        }
+    }
+
+    /// Start a bounded capture of a connection's traffic, in its container's
+    /// network namespace, returning the capture file. A flow already being
+    /// captured returns its running capture.
+    fn capture_flow(&mut self, conn: &Connection) -> Option<String> {
+        let settings = self.capture.as_ref()?;
+        let now = Instant::now();
+        self.captures.retain(|_, (_, until)| *until > now);
+
+        let owner = conn.container.as_deref().unwrap_or("host");
+        let flow = format!("{owner} {remote_addr}", remote_addr = conn.remote_addr);
+        if let Some((path, _)) = self.captures.get(&flow) {
+            return Some(path.clone());
+        }
+        if self.captures.len() >= MAX_CONCURRENT_CAPTURES {
+            warn!("Too many captures running, not capturing {flow}");
+            return None;
+        }
+
+        let filter = network::capture_filter(&conn.remote_addr)?;
+        let path = settings.dir.join(format!(
+            "{started}-{owner}-{remote}.pcap",
+            started = chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            remote = conn.remote_addr.replace([':', '.'], "_")
+        ));
+
+        let mut command = match conn
+            .container
+            .as_ref()
+            .and_then(|container| self.container_pids.get(container))
+        {
+            Some(pid) => {
+                let mut command = tokio::process::Command::new("nsenter");
+                command
+                    .arg(format!("--net=/host/proc/{pid}/ns/net"))
+                    .arg("tcpdump");
+                command
+            }
+            None => tokio::process::Command::new("tcpdump"),
+        };
+        command
+            .args(["-i", "any", "-n"])
+            .args(["-s", &settings.snaplen.to_string()])
+            .args(["-c", &settings.max_packets.to_string()])
+            .arg("-w")
+            .arg(&path)
+            .arg(&filter)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("Failed to start capture of {flow}: {e}");
+                return None;
+            }
+        };
+
+        let duration = settings.duration;
+        tokio::spawn(async move {
+            if tokio::time::timeout(duration, child.wait()).await.is_err() {
+                let _ = child.kill().await;
+            }
+        });
+
+        if let Err(e) = network::prune_captures(&settings.dir, settings.keep) {
+            warn!("Failed to prune old captures: {e:#}");
+        }
+
+        let path = path.to_string_lossy().to_string();
+        info!("Capturing {flow} to {path} for up to {duration:?}");
+        self.captures.insert(flow, (path.clone(), now + duration));
+        Some(path)
+    }
+
+    /// Limit the outgoing rate of the container interface in the network
+    /// namespace of `pid`, or remove the limit with `None`
+    async fn shape(&self, pid: u32, rate_kbit: Option<u64>) -> Result<()> {
+        let mut command = tokio::process::Command::new("nsenter");
+        command
+            .arg(format!("--net=/host/proc/{pid}/ns/net"))
+            .args(["tc", "qdisc"]);
+        match rate_kbit {
+            Some(rate_kbit) => command
+                .args(["replace", "dev", &self.throttle_interface, "root", "tbf"])
+                .args(["rate", &format!("{rate_kbit}kbit")])
+                .args(["burst", "32kbit", "latency", "400ms"]),
+            None => command.args(["del", "dev", &self.throttle_interface, "root"]),
+        };
+
+        let output = command.output().await.context("Failed to run tc")?;
+        if !output.status.success() {
+            bail!(
+                "tc failed: {stderr}",
+                stderr = String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Destroy the sockets of a connection with `ss -K`, in its container's
+    /// network namespace, returning how many were closed
+    async fn terminate(&self, termination: &ConnectionTermination) -> Result<usize> {
+        let filter = network::kill_filter(&termination.remote_addr).with_context(|| {
+            format!(
+                "Invalid remote address {remote_addr}",
+                remote_addr = termination.remote_addr
+            )
+        })?;
+
+        let mut command = match &termination.container {
+            Some(container) => {
+                let pid = self
+                    .container_pids
+                    .get(container)
+                    .with_context(|| format!("Container {container} is not running"))?;
+                let mut command = tokio::process::Command::new("nsenter");
+                command
+                    .arg(format!("--net=/host/proc/{pid}/ns/net"))
+                    .arg("ss");
+                command
+            }
+            None => tokio::process::Command::new("ss"),
+        };
+        // Killed sockets are listed, one per line
+        command.args(["-K", "-H", "-t", "-u"]).args(&filter);
+
+        let output = command.output().await.context("Failed to run ss")?;
+        if !output.status.success() {
+            bail!(
+                "ss failed: {stderr}",
+                stderr = String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count())
+    }
+
     fn is_suspicious_port(&self, port: u16) -> bool {
         match port {
             22 | 23 | 135 | 139 | 445 | 3389 | 5900 => true, // SSH, Telnet, SMB, RDP, VNC
@@ -354,30 +910,130 @@ impl NetworkMonitor {
 
             self.alerts.push(NetworkAlert {
                 timestamp: chrono::Utc::now(),
-                alert_type: AlertType::HighBandwidth,
+                alert_type: NetworkAlertType::HighBandwidth,
                 details: format!(
                     "High bandwidth: RX={:.2} MB/s, TX={:.2} MB/s",
                     rx_rate, tx_rate
                 ),
                 connection: None,
+                container: None,
+                capture: None,
             });
         }
 
         Ok(())
     }
 
-    async fn report_to_supervisor(&self, stats: &NetworkStats) -> Result<()> {
-        if let Some(ref _url) = self.supervisor_url {
-            // TODO: Send stats to supervisor API
-            debug!(
-                "Would report to supervisor: {} connections",
-                stats.active_connections
-            );
+    /// Queue this cycle's stats and alerts, then deliver the queue in order
+    /// unless backing off after a failed delivery
+    async fn report_to_supervisor(&mut self, stats: &NetworkStats) -> Result<()> {
+        let Some((client, queue)) = &self.supervisor else {
+            return Ok(());
+        };
+
+        let report = NetworkReport {
+            monitor: self.name.clone(),
+            stats: stats.clone(),
+            alerts: std::mem::take(&mut self.alerts),
+        };
+        queue.push(&report)?;
+
+        if self
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return Ok(());
+        }
+
+        for path in queue.pending()? {
+            let report = match queue.load(&path) {
+                Ok(report) => report,
+                Err(e) => {
+                    warn!("Dropping unreadable queued report: {e:#}");
+                    queue.remove(&path)?;
+                    continue;
+                }
+            };
+
+            let terminate = match client.report_network(&report).await {
+                Ok(terminate) => terminate,
+                Err(e)
+                    if e.downcast_ref::<ApiError>()
+                        .is_some_and(|e| !e.is_retryable()) =>
+                {
+                    // Sending it again would fail the same way and hold up
+                    // every report queued behind it
+                    let rejected = queue.reject(&path)?;
+                    warn!(
+                        "Supervisor rejected a report, moved it to {rejected}: {e:#}",
+                        rejected = rejected.display()
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    warn!(
+                        "Supervisor unreachable, retrying in {delay:?}: {e:#}",
+                        delay = self.retry_delay
+                    );
+                    self.retry_at = Some(Instant::now() + self.retry_delay);
+                    self.retry_delay = (self.retry_delay * 2).min(MAX_RETRY_DELAY);
+                    return Ok(());
+                }
+            };
+
+            queue.remove(&path)?;
+
+            // Connections the supervisor wants severed
+            for termination in terminate {
+                let flow = format!(
+                    "{owner} {remote_addr}",
+                    owner = termination.container.as_deref().unwrap_or("host"),
+                    remote_addr = termination.remote_addr
+                );
+                match self.terminate(&termination).await {
+                    Ok(0) => warn!("No open socket to sever for {flow}"),
+                    Ok(closed) => warn!("Severed {closed} sockets of {flow}"),
+                    Err(e) => error!("Failed to sever {flow}: {e:#}"),
+                }
+            }
+        }
+
+        if self.retry_at.take().is_some() {
+            info!("Delivered queued reports to the supervisor");
         }
+        self.retry_delay = self.monitor_interval;
+
         Ok(())
     }
 }
 
+/// Value of an environment variable, or `default` if unset or invalid
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Domains of a comma separated list, lowercased
+fn domain_list(domains: &str) -> Vec<String> {
+    domains
+        .split(',')
+        .map(|domain| domain.trim().to_ascii_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .collect()
+}
+
+/// Comma separated values of an environment variable, empty if unset
+fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -387,7 +1043,7 @@ async fn main() -> Result<()> {
 
     info!("Network Monitor starting");
 
-    let mut monitor = NetworkMonitor::new();
+    let mut monitor = NetworkMonitor::new()?;
     monitor.run().await?;
 
     Ok(())
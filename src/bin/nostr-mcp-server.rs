@@ -0,0 +1,721 @@
+#!/usr/bin/env rust
+//! Nostr MCP Server - a censorship-resistant communication channel for agents
+//!
+//! Provides tools: get_identity, publish_note, read_mentions, send_dm, read_dms, follow
+//!
+//! The secret key is loaded (or generated) at startup and only ever used to
+//! sign and decrypt inside this process; no tool returns it, so it never
+//! reaches the LLM context or the agent's memory.
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bech32::{Bech32, Hrp};
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use clap::Parser;
+use futures::{SinkExt, StreamExt};
+use rand::RngCore;
+use replicante::mcp_server::{
+    LogLevel, McpServer, Param, Tool, ToolContext, ToolHandler, ToolOutput, UnknownTool, log,
+};
+use secp256k1::{Keypair, Message, PublicKey, SECP256K1, SecretKey, XOnlyPublicKey, schnorr};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// Event kinds used by the tools (NIP-01, NIP-02, NIP-04)
+const KIND_NOTE: u64 = 1;
+const KIND_CONTACTS: u64 = 3;
+const KIND_ENCRYPTED_DM: u64 = 4;
+
+/// Largest number of events a read tool may ask relays for
+const MAX_READ_LIMIT: u64 = 100;
+
+/// Command-line arguments for the Nostr MCP server
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Relay URLs to publish to and read from (repeatable; comma-separated in the env var)
+    #[arg(
+        long = "relay",
+        env = "NOSTR_RELAYS",
+        value_delimiter = ',',
+        default_values_t = ["wss://relay.damus.io".to_string(), "wss://nos.lol".to_string()]
+    )]
+    relays: Vec<String>,
+
+    /// File holding the secret key (hex or nsec); a new key is generated into it if missing.
+    /// Required without --secret-key; keep it outside any workspace an agent can read
+    #[arg(
+        long,
+        env = "NOSTR_SECRET_KEY_FILE",
+        required_unless_present = "secret_key"
+    )]
+    secret_key_file: Option<PathBuf>,
+
+    /// Secret key (hex or nsec), used instead of the key file
+    #[arg(long, env = "NOSTR_SECRET_KEY", hide_env_values = true)]
+    secret_key: Option<String>,
+
+    /// Seconds to wait for each relay to answer
+    #[arg(long, env = "NOSTR_RELAY_TIMEOUT_SECS", default_value = "10")]
+    relay_timeout_secs: u64,
+
+    /// Enable verbose output
+    #[arg(long, env = "MCP_VERBOSE")]
+    verbose: bool,
+}
+
+/// Nostr MCP Server implementation
+struct NostrMCPServer {
+    keypair: Keypair,
+    public_key: XOnlyPublicKey,
+    relays: Vec<String>,
+    relay_timeout: Duration,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl NostrMCPServer {
+    fn new(args: Args) -> Result<Self> {
+        let secret_key = match &args.secret_key {
+            Some(secret) => parse_secret_key(secret)?,
+            None => load_or_create_secret_key(
+                args.secret_key_file
+                    .as_deref()
+                    .context("--secret-key-file is required without --secret-key")?,
+            )?,
+        };
+        let keypair = Keypair::from_secret_key(SECP256K1, &secret_key);
+        let (public_key, _) = keypair.x_only_public_key();
+
+        let relays: Vec<String> = args
+            .relays
+            .iter()
+            .map(|relay| relay.trim().to_string())
+            .filter(|relay| !relay.is_empty())
+            .collect();
+        if relays.is_empty() {
+            bail!("At least one relay is required");
+        }
+        for relay in &relays {
+            let url =
+                url::Url::parse(relay).with_context(|| format!("Invalid relay URL {relay}"))?;
+            if !matches!(url.scheme(), "ws" | "wss") {
+                bail!("Relay URL {relay} must use ws:// or wss://");
+            }
+        }
+
+        // Relay TLS goes through rustls, which needs a process-wide crypto provider
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let runtime = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+
+        if args.verbose {
+            eprintln!("[Nostr MCP] Public key: {npub}", npub = npub(&public_key)?);
+            eprintln!("[Nostr MCP] Relays: {}", relays.join(", "));
+        }
+
+        Ok(Self {
+            keypair,
+            public_key,
+            relays,
+            relay_timeout: Duration::from_secs(args.relay_timeout_secs),
+            runtime,
+        })
+    }
+}
+
+impl ToolHandler for NostrMCPServer {
+    fn tools(&self) -> Vec<Tool> {
+        vec![
+            Tool::new(
+                "get_identity",
+                "Get this agent's Nostr public key (npub and hex) and relays",
+            ),
+            Tool::new(
+                "publish_note",
+                "Publish a public text note, optionally mentioning users or replying to an event",
+            )
+            .param(Param::string("content", "Note text").required())
+            .param(Param::array(
+                "mentions",
+                "Public keys (npub or hex) to mention",
+                "string",
+            ))
+            .param(Param::string(
+                "reply_to",
+                "Id of the event this note replies to",
+            )),
+            Tool::new("read_mentions", "Read recent notes that mention this agent")
+                .param(Param::integer(
+                    "since",
+                    "Only notes created after this Unix timestamp",
+                ))
+                .param(Param::integer("limit", "Maximum notes to return (max 100)").default(20)),
+            Tool::new("send_dm", "Send an encrypted direct message (NIP-04)")
+                .param(Param::string("recipient", "Recipient public key (npub or hex)").required())
+                .param(Param::string("content", "Message text").required()),
+            Tool::new(
+                "read_dms",
+                "Read and decrypt recent direct messages sent to this agent",
+            )
+            .param(Param::integer(
+                "since",
+                "Only messages created after this Unix timestamp",
+            ))
+            .param(Param::integer("limit", "Maximum messages to return (max 100)").default(20)),
+            Tool::new("follow", "Add a public key to this agent's contact list")
+                .param(Param::string("pubkey", "Public key to follow (npub or hex)").required())
+                .param(Param::string("petname", "Local name for the contact")),
+        ]
+    }
+
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        _context: &ToolContext,
+    ) -> Result<ToolOutput> {
+        let text = match name {
+            "get_identity" => self.get_identity(),
+            "publish_note" => self.publish_note(arguments),
+            "read_mentions" => self.read_mentions(arguments),
+            "send_dm" => self.send_dm(arguments),
+            "read_dms" => self.read_dms(arguments),
+            "follow" => self.follow(arguments),
+            _ => Err(UnknownTool(name.to_string()).into()),
+        }?;
+        Ok(ToolOutput::text(text))
+    }
+}
+
+impl NostrMCPServer {
+    fn get_identity(&self) -> Result<String> {
+        Ok(json!({
+            "npub": npub(&self.public_key)?,
+            "pubkey": self.public_key.to_string(),
+            "relays": self.relays
+        })
+        .to_string())
+    }
+
+    fn publish_note(&self, args: &Value) -> Result<String> {
+        let content = args
+            .get("content")
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'content' parameter"))?;
+
+        let mut tags = Vec::new();
+        if let Some(reply_to) = args.get("reply_to").and_then(|r| r.as_str()) {
+            let valid = reply_to.len() == 64 && reply_to.bytes().all(|b| b.is_ascii_hexdigit());
+            if !valid {
+                bail!("'reply_to' must be a 64-character hex event id");
+            }
+            tags.push(vec![
+                "e".to_string(),
+                reply_to.to_lowercase(),
+                String::new(),
+                "reply".to_string(),
+            ]);
+        }
+        if let Some(mentions) = args.get("mentions").and_then(|m| m.as_array()) {
+            for mention in mentions {
+                let key = parse_public_key(mention.as_str().unwrap_or(""))?;
+                tags.push(vec!["p".to_string(), key.to_string()]);
+            }
+        }
+
+        let event = self.sign_event(KIND_NOTE, tags, content)?;
+        self.publish(&event)
+    }
+
+    fn read_mentions(&self, args: &Value) -> Result<String> {
+        let filter = self.read_filter(KIND_NOTE, args);
+        let notes: Vec<Value> = self
+            .query(&filter)?
+            .into_iter()
+            .map(|event| {
+                json!({
+                    "id": event["id"],
+                    "author": author(&event),
+                    "created_at": event["created_at"],
+                    "content": event["content"]
+                })
+            })
+            .collect();
+
+        Ok(json!({ "mentions": notes }).to_string())
+    }
+
+    fn send_dm(&self, args: &Value) -> Result<String> {
+        let recipient = args
+            .get("recipient")
+            .and_then(|r| r.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'recipient' parameter"))?;
+        let content = args
+            .get("content")
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'content' parameter"))?;
+
+        let recipient = parse_public_key(recipient)?;
+        let encrypted = nip04_encrypt(&self.keypair.secret_key(), &recipient, content)?;
+        let event = self.sign_event(
+            KIND_ENCRYPTED_DM,
+            vec![vec!["p".to_string(), recipient.to_string()]],
+            &encrypted,
+        )?;
+        self.publish(&event)
+    }
+
+    fn read_dms(&self, args: &Value) -> Result<String> {
+        let filter = self.read_filter(KIND_ENCRYPTED_DM, args);
+        let secret_key = self.keypair.secret_key();
+
+        let messages: Vec<Value> = self
+            .query(&filter)?
+            .into_iter()
+            .filter_map(|event| {
+                let sender = XOnlyPublicKey::from_str(event["pubkey"].as_str()?).ok()?;
+                let content = event["content"].as_str()?;
+                // Messages we cannot decrypt are reported rather than dropped
+                let text = nip04_decrypt(&secret_key, &sender, content)
+                    .unwrap_or_else(|e| format!("[undecryptable: {e}]"));
+                Some(json!({
+                    "id": event["id"],
+                    "from": npub(&sender).unwrap_or_else(|_| sender.to_string()),
+                    "created_at": event["created_at"],
+                    "content": text
+                }))
+            })
+            .collect();
+
+        Ok(json!({ "messages": messages }).to_string())
+    }
+
+    fn follow(&self, args: &Value) -> Result<String> {
+        let pubkey = args
+            .get("pubkey")
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'pubkey' parameter"))?;
+        let pubkey = parse_public_key(pubkey)?.to_string();
+        let petname = args.get("petname").and_then(|p| p.as_str()).unwrap_or("");
+
+        // The contact list is replaced as a whole, so start from the latest one
+        let filter = json!({
+            "kinds": [KIND_CONTACTS],
+            "authors": [self.public_key.to_string()],
+            "limit": 1
+        });
+        let latest = self
+            .query(&filter)?
+            .into_iter()
+            .max_by_key(|event| event["created_at"].as_u64().unwrap_or(0));
+
+        let (mut tags, content) = match &latest {
+            Some(event) => (
+                serde_json::from_value::<Vec<Vec<String>>>(event["tags"].clone())
+                    .unwrap_or_default(),
+                event["content"].as_str().unwrap_or("").to_string(),
+            ),
+            None => (Vec::new(), String::new()),
+        };
+
+        if tags
+            .iter()
+            .any(|tag| tag.first().map(String::as_str) == Some("p") && tag.get(1) == Some(&pubkey))
+        {
+            return Ok(json!({
+                "following": tags.iter().filter(|tag| tag.first().map(String::as_str) == Some("p")).count(),
+                "already_following": true
+            })
+            .to_string());
+        }
+
+        let mut tag = vec!["p".to_string(), pubkey];
+        if !petname.is_empty() {
+            tag.extend([String::new(), petname.to_string()]);
+        }
+        tags.push(tag);
+
+        let following = tags
+            .iter()
+            .filter(|tag| tag.first().map(String::as_str) == Some("p"))
+            .count();
+        let event = self.sign_event(KIND_CONTACTS, tags, &content)?;
+        let published: Value = serde_json::from_str(&self.publish(&event)?)?;
+
+        Ok(json!({
+            "following": following,
+            "already_following": false,
+            "event_id": published["event_id"],
+            "relays": published["relays"]
+        })
+        .to_string())
+    }
+
+    /// Filter for events of `kind` addressed to this agent
+    fn read_filter(&self, kind: u64, args: &Value) -> Value {
+        let limit = args
+            .get("limit")
+            .and_then(|l| l.as_u64())
+            .unwrap_or(20)
+            .min(MAX_READ_LIMIT);
+        let mut filter = json!({
+            "kinds": [kind],
+            "#p": [self.public_key.to_string()],
+            "limit": limit
+        });
+        if let Some(since) = args.get("since").and_then(|s| s.as_u64()) {
+            filter["since"] = json!(since);
+        }
+        filter
+    }
+
+    /// Build and sign an event as this agent (NIP-01)
+    fn sign_event(&self, kind: u64, tags: Vec<Vec<String>>, content: &str) -> Result<Value> {
+        let pubkey = self.public_key.to_string();
+        let created_at = chrono::Utc::now().timestamp();
+        let id = event_id(&pubkey, created_at, kind, &tags, content);
+        let signature = SECP256K1.sign_schnorr(&Message::from_digest(id), &self.keypair);
+
+        Ok(json!({
+            "id": hex::encode(id),
+            "pubkey": pubkey,
+            "created_at": created_at,
+            "kind": kind,
+            "tags": tags,
+            "content": content,
+            "sig": signature.to_string()
+        }))
+    }
+
+    /// Send an event to every relay and report which accepted it
+    fn publish(&self, event: &Value) -> Result<String> {
+        let message = json!(["EVENT", event]).to_string();
+        let event_id = event["id"].as_str().unwrap_or("").to_string();
+
+        let outcomes = self
+            .runtime
+            .block_on(futures::future::join_all(self.relays.iter().map(|relay| {
+                let message = message.clone();
+                let event_id = event_id.clone();
+                async move {
+                    let outcome = tokio::time::timeout(
+                        self.relay_timeout,
+                        publish_to_relay(relay, &message, &event_id),
+                    )
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out")));
+                    (relay.clone(), outcome)
+                }
+            })));
+
+        let accepted = outcomes
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Ok((true, _))))
+            .count();
+        let relays: Vec<Value> = outcomes
+            .into_iter()
+            .map(|(relay, outcome)| match outcome {
+                Ok((accepted, message)) => {
+                    json!({"relay": relay, "accepted": accepted, "message": message})
+                }
+                Err(e) => json!({"relay": relay, "accepted": false, "message": e.to_string()}),
+            })
+            .collect();
+
+        if accepted == 0 {
+            bail!(
+                "No relay accepted event {event_id}: {relays}",
+                relays = Value::Array(relays)
+            );
+        }
+
+        Ok(json!({
+            "event_id": event_id,
+            "accepted_by": accepted,
+            "relays": relays
+        })
+        .to_string())
+    }
+
+    /// Ask every relay for events matching `filter`; returns valid events,
+    /// newest first, without duplicates
+    fn query(&self, filter: &Value) -> Result<Vec<Value>> {
+        let mut subscription = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut subscription);
+        let subscription = hex::encode(subscription);
+        let request = json!(["REQ", subscription, filter]).to_string();
+
+        let outcomes = self
+            .runtime
+            .block_on(futures::future::join_all(self.relays.iter().map(|relay| {
+                let request = request.clone();
+                let subscription = subscription.clone();
+                async move {
+                    let outcome = tokio::time::timeout(
+                        self.relay_timeout,
+                        query_relay(relay, &request, &subscription),
+                    )
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out")));
+                    (relay.clone(), outcome)
+                }
+            })));
+
+        let mut seen = HashSet::new();
+        let mut events = Vec::new();
+        let mut failures = Vec::new();
+        for (relay, outcome) in outcomes {
+            match outcome {
+                Ok(relay_events) => {
+                    for event in relay_events {
+                        let id = event["id"].as_str().unwrap_or("").to_string();
+                        if verify_event(&event) && seen.insert(id) {
+                            events.push(event);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log(LogLevel::Warning, format!("Relay {relay} failed: {e}"));
+                    failures.push(format!("{relay}: {e}"));
+                }
+            }
+        }
+
+        if failures.len() == self.relays.len() {
+            bail!(
+                "No relay answered: {failures}",
+                failures = failures.join("; ")
+            );
+        }
+
+        events.sort_by_key(|event| std::cmp::Reverse(event["created_at"].as_u64().unwrap_or(0)));
+        Ok(events)
+    }
+}
+
+/// Send an EVENT message and wait for the relay's OK
+async fn publish_to_relay(relay: &str, message: &str, event_id: &str) -> Result<(bool, String)> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(relay).await?;
+    socket.send(WsMessage::Text(message.to_string())).await?;
+
+    while let Some(frame) = socket.next().await {
+        let WsMessage::Text(text) = frame? else {
+            continue;
+        };
+        let Ok(reply) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        if reply[0] == "OK" && reply[1] == event_id {
+            let _ = socket.close(None).await;
+            return Ok((
+                reply[2].as_bool().unwrap_or(false),
+                reply[3].as_str().unwrap_or("").to_string(),
+            ));
+        }
+    }
+    bail!("connection closed before the relay answered")
+}
+
+/// Send a REQ message and collect events until the relay signals EOSE
+async fn query_relay(relay: &str, request: &str, subscription: &str) -> Result<Vec<Value>> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(relay).await?;
+    socket.send(WsMessage::Text(request.to_string())).await?;
+
+    let mut events = Vec::new();
+    while let Some(frame) = socket.next().await {
+        let WsMessage::Text(text) = frame? else {
+            continue;
+        };
+        let Ok(reply) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+        if reply[1] != subscription {
+            continue;
+        }
+        match reply[0].as_str() {
+            Some("EVENT") => events.push(reply[2].clone()),
+            Some("EOSE") => {
+                let close = json!(["CLOSE", subscription]).to_string();
+                let _ = socket.send(WsMessage::Text(close)).await;
+                let _ = socket.close(None).await;
+                return Ok(events);
+            }
+            Some("CLOSED") => bail!(
+                "subscription closed: {reason}",
+                reason = reply[2].as_str().unwrap_or("")
+            ),
+            _ => {}
+        }
+    }
+    bail!("connection closed before end of stored events")
+}
+
+/// The NIP-01 event id: sha256 of the serialized event fields
+fn event_id(
+    pubkey: &str,
+    created_at: i64,
+    kind: u64,
+    tags: &[Vec<String>],
+    content: &str,
+) -> [u8; 32] {
+    let serialized = json!([0, pubkey, created_at, kind, tags, content]).to_string();
+    Sha256::digest(serialized.as_bytes()).into()
+}
+
+/// Check an event's id and signature
+fn verify_event(event: &Value) -> bool {
+    let verify = || -> Option<bool> {
+        let pubkey = event["pubkey"].as_str()?;
+        let tags: Vec<Vec<String>> = serde_json::from_value(event["tags"].clone()).ok()?;
+        let id = event_id(
+            pubkey,
+            event["created_at"].as_i64()?,
+            event["kind"].as_u64()?,
+            &tags,
+            event["content"].as_str()?,
+        );
+        if hex::encode(id) != event["id"].as_str()? {
+            return Some(false);
+        }
+        let key = XOnlyPublicKey::from_str(pubkey).ok()?;
+        let signature = schnorr::Signature::from_str(event["sig"].as_str()?).ok()?;
+        Some(
+            SECP256K1
+                .verify_schnorr(&signature, &Message::from_digest(id), &key)
+                .is_ok(),
+        )
+    };
+    verify().unwrap_or(false)
+}
+
+/// The event author as an npub, or the raw field if it is not a valid key
+fn author(event: &Value) -> Value {
+    event["pubkey"]
+        .as_str()
+        .and_then(|key| XOnlyPublicKey::from_str(key).ok())
+        .and_then(|key| npub(&key).ok())
+        .map(|npub| json!(npub))
+        .unwrap_or_else(|| event["pubkey"].clone())
+}
+
+/// NIP-04 shared key: the x coordinate of the ECDH point
+fn nip04_key(secret_key: &SecretKey, public_key: &XOnlyPublicKey) -> [u8; 32] {
+    let public_key = PublicKey::from_x_only_public_key(*public_key, secp256k1::Parity::Even);
+    let point = secp256k1::ecdh::shared_secret_point(&public_key, secret_key);
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&point[..32]);
+    key
+}
+
+/// Encrypt a direct message as `base64(ciphertext)?iv=base64(iv)`
+fn nip04_encrypt(secret_key: &SecretKey, recipient: &XOnlyPublicKey, text: &str) -> Result<String> {
+    let key = nip04_key(secret_key, recipient);
+    let mut iv = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut iv);
+
+    let ciphertext = cbc::Encryptor::<aes::Aes256>::new(&key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(text.as_bytes());
+    Ok(format!(
+        "{ciphertext}?iv={iv}",
+        ciphertext = BASE64.encode(ciphertext),
+        iv = BASE64.encode(iv)
+    ))
+}
+
+/// Decrypt a NIP-04 direct message
+fn nip04_decrypt(secret_key: &SecretKey, sender: &XOnlyPublicKey, content: &str) -> Result<String> {
+    let (ciphertext, iv) = content
+        .split_once("?iv=")
+        .ok_or_else(|| anyhow::anyhow!("missing iv"))?;
+    let ciphertext = BASE64.decode(ciphertext)?;
+    let iv: [u8; 16] = BASE64
+        .decode(iv)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("iv must be 16 bytes"))?;
+
+    let key = nip04_key(secret_key, sender);
+    let plaintext = cbc::Decryptor::<aes::Aes256>::new(&key.into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+        .map_err(|_| anyhow::anyhow!("bad padding"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Encode a public key as a NIP-19 npub
+fn npub(public_key: &XOnlyPublicKey) -> Result<String> {
+    bech32::encode::<Bech32>(Hrp::parse_unchecked("npub"), &public_key.serialize())
+        .context("Failed to encode npub")
+}
+
+/// Parse a public key given as npub or hex
+fn parse_public_key(key: &str) -> Result<XOnlyPublicKey> {
+    let key = key.trim();
+    let bytes = if key.starts_with("npub1") {
+        let (hrp, data) = bech32::decode(key).context("Invalid npub")?;
+        if hrp.as_str() != "npub" {
+            bail!("Expected an npub, got {hrp}");
+        }
+        data
+    } else {
+        hex::decode(key).context("Public key must be an npub or 64 hex characters")?
+    };
+    XOnlyPublicKey::from_slice(&bytes).context("Invalid public key")
+}
+
+/// Parse a secret key given as nsec or hex, without echoing it in errors
+fn parse_secret_key(key: &str) -> Result<SecretKey> {
+    let key = key.trim();
+    let bytes = if key.starts_with("nsec1") {
+        match bech32::decode(key) {
+            Ok((hrp, data)) if hrp.as_str() == "nsec" => data,
+            _ => bail!("Invalid nsec secret key"),
+        }
+    } else {
+        hex::decode(key)
+            .map_err(|_| anyhow::anyhow!("Secret key must be an nsec or 64 hex characters"))?
+    };
+    SecretKey::from_slice(&bytes).map_err(|_| anyhow::anyhow!("Invalid secret key"))
+}
+
+/// Read the secret key file, generating a new key into it if it does not exist
+fn load_or_create_secret_key(path: &Path) -> Result<SecretKey> {
+    if path.exists() {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read secret key file {path:?}"))?;
+        return parse_secret_key(&content)
+            .with_context(|| format!("Invalid secret key in {path:?}"));
+    }
+
+    let (secret_key, _) = SECP256K1.generate_keypair(&mut rand::thread_rng());
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create secret key file {path:?}"))?;
+    writeln!(file, "{}", hex::encode(secret_key.secret_bytes()))?;
+
+    eprintln!("[Nostr MCP] Generated a new secret key in {path:?}");
+    Ok(secret_key)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let verbose = args.verbose;
+
+    if verbose {
+        eprintln!("[Nostr MCP] Starting server...");
+    }
+
+    let server = NostrMCPServer::new(args)?;
+    McpServer::new("nostr-mcp-server", "Nostr MCP", server)
+        .verbose(verbose)
+        .run()
+}
@@ -1,10 +1,14 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
-use tracing::info;
+use tracing::{info, warn};
 
 // Import the main modules
-use replicante::{run_agent, run_sandboxed, supervisor};
+use replicante::config::{self, LoadOptions};
+use replicante::{
+    Config, MCPClient, RunOptions, StateManager, doctor, init, run_agent_with_loader,
+    run_sandboxed, service, supervisor, timeline, wasm_tools,
+};
 
 #[derive(Parser)]
 #[command(name = "replicante")]
@@ -14,6 +18,33 @@ struct Cli {
     command: Commands,
 }
 
+/// Options for running as a long-lived service
+#[derive(clap::Args)]
+struct ServiceArgs {
+    /// Detach from the terminal and run in the background
+    #[arg(long)]
+    daemon: bool,
+
+    /// Write the process ID to this file while running
+    #[arg(long)]
+    pid_file: Option<PathBuf>,
+
+    /// File a daemon's output is appended to; discarded if unset
+    #[arg(long, requires = "daemon")]
+    log_file: Option<PathBuf>,
+}
+
+impl ServiceArgs {
+    /// Claim the PID file, if one was asked for; it is removed when the
+    /// returned guard is dropped
+    fn pid_file(&self) -> Result<Option<service::PidFile>> {
+        self.pid_file
+            .as_deref()
+            .map(service::PidFile::create)
+            .transpose()
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Run the autonomous agent (default mode)
@@ -21,10 +52,48 @@ enum Commands {
         /// Path to configuration file
         #[arg(short, long)]
         config: Option<PathBuf>,
+
+        /// Config file merged over the main one; repeat to layer several
+        #[arg(long = "config-overlay")]
+        overlays: Vec<PathBuf>,
+
+        /// Entry of the config's [profiles] table to apply
+        #[arg(long, env = "REPLICANTE_PROFILE")]
+        profile: Option<String>,
+
+        /// Run a single reasoning cycle, print a summary and exit
+        #[arg(long, conflicts_with = "max_cycles")]
+        once: bool,
+
+        /// Exit with a summary after this many reasoning cycles
+        #[arg(long)]
+        max_cycles: Option<u64>,
+
+        /// Pause after each thought to approve, edit or skip the proposed
+        /// action before it runs
+        #[arg(long, conflicts_with = "daemon")]
+        step: bool,
+
+        /// Seed the agent's random choices, such as its ID
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Run on a mock clock starting at this RFC 3339 time, which only
+        /// moves when the agent waits; with --seed, a scripted LLM and
+        /// deterministic tools, every run records the same timeline
+        #[arg(long, value_name = "TIME")]
+        mock_clock: Option<chrono::DateTime<chrono::Utc>>,
+
+        #[command(flatten)]
+        service: ServiceArgs,
     },
 
-    /// Run the supervisor daemon
+    /// Run the supervisor daemon or control a running one
     Supervisor {
+        /// Supervisor API URL (defaults to SUPERVISOR_URL or http://localhost:8090)
+        #[arg(long, global = true)]
+        url: Option<String>,
+
         #[command(subcommand)]
         command: SupervisorCommands,
     },
@@ -45,6 +114,276 @@ enum Commands {
         #[command(subcommand)]
         command: MonitorCommands,
     },
+
+    /// Write a starter agent config, asking for anything not given as a flag
+    Init {
+        /// File to write
+        #[arg(short, long, default_value = "config.toml")]
+        output: PathBuf,
+
+        /// LLM provider (anthropic, openai, ollama)
+        #[arg(long)]
+        provider: Option<String>,
+
+        /// LLM model; defaults to one suited to the provider
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Directory the MCP servers work in
+        #[arg(long)]
+        workspace: Option<String>,
+
+        /// Built-in MCP server to enable (repeatable; comma-separated)
+        #[arg(long = "server", value_delimiter = ',')]
+        servers: Vec<String>,
+
+        /// Use defaults for everything not given instead of asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Inspect and correct an agent's memory
+    Memory {
+        /// Agent database to operate on
+        #[arg(
+            short,
+            long,
+            env = "DATABASE_PATH",
+            default_value = "replicante.db",
+            global = true
+        )]
+        database: PathBuf,
+
+        #[command(subcommand)]
+        command: MemoryCommands,
+    },
+
+    /// Browse an agent's decision history, oldest of the selection first
+    Decisions {
+        /// Agent database to read
+        #[arg(
+            long = "database",
+            alias = "db",
+            env = "DATABASE_PATH",
+            default_value = "replicante.db"
+        )]
+        database: PathBuf,
+
+        /// Number of most recent matching decisions to show
+        #[arg(short = 'n', long, default_value = "20")]
+        last: usize,
+
+        /// Only show decisions whose result has this status (success, error, timeout)
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Only show decisions whose action contains this text, e.g. use_tool:shell
+        #[arg(long)]
+        action: Option<String>,
+
+        /// Print the decision records as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Render the decision history as a timeline graph
+    ExportTimeline {
+        /// Agent database to read
+        #[arg(
+            long = "database",
+            alias = "db",
+            env = "DATABASE_PATH",
+            default_value = "replicante.db"
+        )]
+        database: PathBuf,
+
+        /// Output format: mermaid, dot or json
+        #[arg(short, long, default_value = "mermaid")]
+        format: timeline::TimelineFormat,
+
+        /// Number of most recent decisions to include
+        #[arg(short = 'n', long, default_value = "100")]
+        last: usize,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show what an agent's knowledge graph holds about an entity
+    Facts {
+        /// Agent database to read
+        #[arg(
+            long = "database",
+            alias = "db",
+            env = "DATABASE_PATH",
+            default_value = "replicante.db"
+        )]
+        database: PathBuf,
+
+        entity: String,
+
+        /// Also show facts about entities up to this many relations away
+        #[arg(long, default_value = "0")]
+        depth: usize,
+
+        /// Number of facts to show
+        #[arg(short = 'n', long, default_value = "100")]
+        limit: usize,
+
+        /// Print the facts as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List or call the tools of the configured MCP servers, without an agent
+    Tools {
+        /// Path to configuration file (defaults to CONFIG_FILE or config.toml)
+        #[arg(short, long, global = true)]
+        config: Option<PathBuf>,
+
+        /// Config file merged over the main one; repeat to layer several
+        #[arg(long = "config-overlay", global = true)]
+        overlays: Vec<PathBuf>,
+
+        /// Entry of the config's [profiles] table to apply
+        #[arg(long, env = "REPLICANTE_PROFILE", global = true)]
+        profile: Option<String>,
+
+        #[command(subcommand)]
+        command: ToolsCommands,
+    },
+
+    /// Check that this machine can run the configured agent
+    Doctor {
+        /// Path to configuration file (defaults to CONFIG_FILE or config.toml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Config file merged over the main one; repeat to layer several
+        #[arg(long = "config-overlay")]
+        overlays: Vec<PathBuf>,
+
+        /// Entry of the config's [profiles] table to apply
+        #[arg(long, env = "REPLICANTE_PROFILE")]
+        profile: Option<String>,
+
+        /// Skip the test request to the LLM provider
+        #[arg(long)]
+        skip_llm: bool,
+    },
+
+    /// Review the prompts the agent sends to its LLM
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommands,
+    },
+
+    /// Inspect agent configuration files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check a config for problems that would otherwise surface at startup
+    Validate {
+        /// Path to configuration file (defaults to CONFIG_FILE or config.toml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Config file merged over the main one; repeat to layer several
+        #[arg(long = "config-overlay")]
+        overlays: Vec<PathBuf>,
+
+        /// Entry of the config's [profiles] table to apply
+        #[arg(long, env = "REPLICANTE_PROFILE")]
+        profile: Option<String>,
+    },
+
+    /// Print the JSON Schema of the agent config format
+    Schema {
+        /// Print the supervisor config schema instead
+        #[arg(long)]
+        supervisor: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryCommands {
+    /// List memory entries, most recently updated first
+    List {
+        /// Only list keys starting with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value = "100")]
+        limit: usize,
+    },
+
+    /// Print the value stored under a key
+    Get { key: String },
+
+    /// Store a JSON value under a key, replacing any existing one
+    Set {
+        key: String,
+
+        /// Value as JSON, e.g. '"text"', 42 or '{"a": 1}'
+        value: String,
+    },
+
+    /// Delete a memory entry
+    Delete { key: String },
+}
+
+#[derive(Subcommand)]
+enum ToolsCommands {
+    /// Start the MCP servers and list the tools they offer
+    List {
+        /// Only start this server
+        #[arg(long)]
+        server: Option<String>,
+
+        /// Print the tools with their parameter schemas as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Start the tool's MCP server, call the tool once and print the raw result
+    Call {
+        /// Tool to call, as server:tool
+        tool: String,
+
+        /// Tool arguments as a JSON object
+        #[arg(long, default_value = "{}")]
+        params: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PromptCommands {
+    /// Print the next reasoning prompt, built from the agent's current state,
+    /// without calling the LLM
+    Preview {
+        /// Path to configuration file (defaults to CONFIG_FILE or config.toml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Config file merged over the main one; repeat to layer several
+        #[arg(long = "config-overlay")]
+        overlays: Vec<PathBuf>,
+
+        /// Entry of the config's [profiles] table to apply
+        #[arg(long, env = "REPLICANTE_PROFILE")]
+        profile: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -58,6 +397,9 @@ enum SupervisorCommands {
         /// Web dashboard port
         #[arg(short = 'p', long)]
         web_port: Option<u16>,
+
+        #[command(flatten)]
+        service: ServiceArgs,
     },
 
     /// Show supervisor status
@@ -66,6 +408,34 @@ enum SupervisorCommands {
     /// Shutdown the supervisor daemon
     Shutdown,
 
+    /// Spawn a new supervised agent
+    Spawn {
+        /// Path to agent configuration file
+        #[arg(short, long, required_unless_present = "template")]
+        config: Option<PathBuf>,
+
+        /// Path to a TOML sandbox configuration to run the agent under
+        #[arg(long, conflicts_with = "template")]
+        sandbox: Option<PathBuf>,
+
+        /// Entry of the sandbox configuration's [profiles] table to apply
+        #[arg(long, requires = "sandbox", env = "REPLICANTE_PROFILE")]
+        profile: Option<String>,
+
+        /// Spawn from a template in the supervisor's template library
+        #[arg(short, long, conflicts_with = "config")]
+        template: Option<String>,
+    },
+
+    /// Spawn a copy of an agent, including a snapshot of its state
+    Clone {
+        /// Agent ID to clone
+        agent_id: String,
+    },
+
+    /// List agent templates available on the supervisor
+    Templates,
+
     /// Stop an agent
     StopAgent {
         /// Agent ID to stop
@@ -78,12 +448,63 @@ enum SupervisorCommands {
         agent_id: String,
     },
 
+    /// Pause an agent (freeze it without quarantining)
+    Pause {
+        /// Agent ID to pause
+        agent_id: String,
+    },
+
+    /// Resume a paused agent
+    Resume {
+        /// Agent ID to resume
+        agent_id: String,
+    },
+
+    /// Make an agent re-read its config (interval, goals, LLM model, log level)
+    ReloadConfig {
+        /// Agent ID to reload
+        agent_id: String,
+    },
+
+    /// Kill every managed agent immediately
+    StopAll {
+        /// Skip the interactive confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
+
     /// Quarantine an agent
     Quarantine {
         /// Agent ID to quarantine
         agent_id: String,
     },
 
+    /// Release a quarantined agent
+    Approve {
+        /// Agent ID to approve
+        agent_id: String,
+    },
+
+    /// Sever one network connection without stopping the agent
+    TerminateConnection {
+        /// Remote endpoint of the connection, e.g. 203.0.113.7:4444
+        remote_addr: String,
+
+        /// Container holding the connection; the monitor's host if unset
+        #[arg(long)]
+        container: Option<String>,
+
+        /// Network monitor to sever it; by default those that reported it
+        #[arg(long)]
+        monitor: Option<String>,
+    },
+
+    /// Show an agent's usage against its resource quotas
+    Quota {
+        /// Agent ID
+        agent_id: String,
+    },
+
     /// View agent logs
     Logs {
         /// Agent ID
@@ -92,6 +513,10 @@ enum SupervisorCommands {
         /// Follow log output
         #[arg(short, long)]
         follow: bool,
+
+        /// Number of recent lines to show
+        #[arg(short = 'n', long, default_value = "100")]
+        tail: usize,
     },
 }
 
@@ -131,13 +556,47 @@ enum MonitorCommands {
         last: usize,
     },
 
-    /// Export audit log
+    /// Show or export the supervisor audit log
     Audit {
+        /// Only show operations on this agent
+        #[arg(long)]
+        agent_id: Option<String>,
+
+        /// Number of entries to fetch
+        #[arg(short = 'n', long, default_value = "100")]
+        limit: usize,
+
         /// Export to file
         #[arg(long)]
         export: Option<PathBuf>,
     },
 
+    /// Show stored security scanner reports
+    Security {
+        /// Only show reports for this container
+        #[arg(long)]
+        container: Option<String>,
+
+        /// Minimum risk level (low, medium, high, critical)
+        #[arg(long)]
+        severity: Option<String>,
+
+        /// Only show reports since this time (RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Number of reports to fetch
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Show the security scanner's whitelists
+    Whitelists {
+        /// Re-read the whitelists file first
+        #[arg(long)]
+        reload: bool,
+    },
+
     /// Open web dashboard
     Dashboard {
         /// Dashboard URL
@@ -146,26 +605,89 @@ enum MonitorCommands {
     },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+/// The agent config to read: `--config`, else `CONFIG_FILE`, else config.toml
+fn config_path(config: Option<PathBuf>) -> PathBuf {
+    config.unwrap_or_else(|| {
+        PathBuf::from(std::env::var("CONFIG_FILE").unwrap_or_else(|_| "config.toml".to_string()))
+    })
+}
+
+fn main() -> Result<()> {
     // Load environment variables
     dotenvy::dotenv().ok();
 
+    let cli = Cli::parse();
+
+    // Detaching forks, which has to happen before the runtime starts threads
+    let service = match &cli.command {
+        Commands::Agent { service, .. } => Some(service),
+        Commands::Supervisor {
+            command: SupervisorCommands::Start { service, .. },
+            ..
+        } => Some(service),
+        _ => None,
+    };
+    if let Some(service) = service
+        && service.daemon
+    {
+        service::daemonize(service.log_file.as_deref())?;
+    }
+
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    replicante::logging::init();
 
-    let cli = Cli::parse();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(cli))
+}
 
+async fn run(cli: Cli) -> Result<()> {
     match cli.command {
-        Commands::Agent { config } => {
+        Commands::Agent {
+            config,
+            overlays,
+            profile,
+            once,
+            max_cycles,
+            step,
+            seed,
+            mock_clock,
+            service,
+        } => {
+            let _pid_file = service.pid_file()?;
             info!("Starting Replicante agent");
-            run_agent(config).await?;
+            let options = LoadOptions { overlays, profile };
+            let run_options = RunOptions {
+                max_cycles: if once { Some(1) } else { max_cycles },
+                step,
+                seed,
+                mock_clock,
+            };
+            let bounded = run_options.max_cycles.is_some();
+            let summary = run_agent_with_loader(
+                Box::new(move || match &config {
+                    Some(path) => Config::from_files(path, &options),
+                    None => Config::load_with(&options),
+                }),
+                run_options,
+            )
+            .await?;
+
+            println!("{summary}");
+            // Only bounded runs report failures; a stopped service exits cleanly
+            if bounded && summary.failed_cycles > 0 {
+                std::process::exit(1);
+            }
         }
 
-        Commands::Supervisor { command } => match command {
-            SupervisorCommands::Start { config, web_port } => {
+        Commands::Supervisor { url, command } => match command {
+            SupervisorCommands::Start {
+                config,
+                web_port,
+                service,
+            } => {
+                let _pid_file = service.pid_file()?;
                 info!("Starting supervisor daemon");
 
                 let mut supervisor_config = if let Some(ref path) = config {
@@ -178,6 +700,12 @@ async fn main() -> Result<()> {
                 if let Some(port) = web_port {
                     supervisor_config.web_port = Some(port);
                 }
+                if let Err(e) = replicante::logging::configure(
+                    &supervisor_config.logging,
+                    Some(&supervisor_config.log_level),
+                ) {
+                    warn!("Cannot apply logging settings: {e:#}");
+                }
 
                 let daemon = supervisor::daemon::Daemon::new_with_config(supervisor_config).await?;
                 daemon.run().await?;
@@ -185,8 +713,7 @@ async fn main() -> Result<()> {
 
             SupervisorCommands::Status => {
                 // Use the async supervisor client to get status
-                let client =
-                    replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
                 match client.get_status().await {
                     Ok(status) => {
                         println!("Supervisor Status:");
@@ -202,8 +729,16 @@ async fn main() -> Result<()> {
                             println!("\nAgents:");
                             for agent in status.agents {
                                 println!(
-                                    "  - {} [{}] (started: {})",
-                                    agent.id, agent.status, agent.started_at
+                                    "  - {id} [{status}] (started: {started_at})",
+                                    id = agent.id,
+                                    status = agent.status,
+                                    started_at = agent.started_at
+                                );
+                                println!(
+                                    "      config: {config_path}, cpu: {cpu:.1}%, memory: {memory} MB",
+                                    config_path = agent.config_path,
+                                    cpu = agent.resource_usage.cpu_percent,
+                                    memory = agent.resource_usage.memory_mb
                                 );
                             }
                         }
@@ -218,17 +753,82 @@ async fn main() -> Result<()> {
             }
 
             SupervisorCommands::Shutdown => {
-                let client =
-                    replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
                 match client.shutdown().await {
                     Ok(_) => println!("Successfully sent shutdown signal to supervisor"),
                     Err(e) => eprintln!("Failed to shutdown supervisor: {e}"),
                 }
             }
 
+            SupervisorCommands::Spawn {
+                template: Some(template),
+                ..
+            } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.spawn_template(&template).await {
+                    Ok(agent_id) => println!("Spawned agent {agent_id} from template {template}"),
+                    Err(e) => eprintln!("Failed to spawn agent: {e}"),
+                }
+            }
+
+            SupervisorCommands::Spawn {
+                config,
+                sandbox,
+                profile,
+                ..
+            } => {
+                let config = config.context("--config is required without --template")?;
+                let config_path = std::fs::canonicalize(&config)?;
+                let sandbox_config =
+                    match sandbox {
+                        Some(path) => Some(config::read_file_with_profile::<
+                            supervisor::SandboxConfig,
+                        >(&path, profile.as_deref())?),
+                        None => None,
+                    };
+
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client
+                    .spawn_agent(&config_path.to_string_lossy(), sandbox_config)
+                    .await
+                {
+                    Ok(agent_id) => println!("Spawned agent: {agent_id}"),
+                    Err(e) => eprintln!("Failed to spawn agent: {e}"),
+                }
+            }
+
+            SupervisorCommands::Clone { agent_id } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.clone_agent(&agent_id).await {
+                    Ok(clone_id) => println!("Cloned agent {agent_id} as: {clone_id}"),
+                    Err(e) => eprintln!("Failed to clone agent: {e}"),
+                }
+            }
+
+            SupervisorCommands::Templates => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.list_templates().await {
+                    Ok(templates) => {
+                        println!("Agent templates:");
+                        for template in templates {
+                            println!(
+                                "  - {name}: {description}",
+                                name = template.name,
+                                description = template.description
+                            );
+                            println!(
+                                "      mcp servers: [{servers}], sandboxed: {sandboxed}",
+                                servers = template.mcp_servers.join(", "),
+                                sandboxed = template.sandboxed
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to list templates: {e}"),
+                }
+            }
+
             SupervisorCommands::StopAgent { agent_id } => {
-                let client =
-                    replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
                 match client.stop_agent(&agent_id).await {
                     Ok(_) => println!("Successfully stopped agent: {agent_id}"),
                     Err(e) => eprintln!("Failed to stop agent: {e}"),
@@ -236,26 +836,164 @@ async fn main() -> Result<()> {
             }
 
             SupervisorCommands::KillAgent { agent_id } => {
-                let client =
-                    replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
                 match client.kill_agent(&agent_id).await {
                     Ok(_) => println!("Successfully killed agent: {agent_id}"),
                     Err(e) => eprintln!("Failed to kill agent: {e}"),
                 }
             }
 
+            SupervisorCommands::Pause { agent_id } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.pause_agent(&agent_id).await {
+                    Ok(_) => println!("Successfully paused agent: {agent_id}"),
+                    Err(e) => eprintln!("Failed to pause agent: {e}"),
+                }
+            }
+
+            SupervisorCommands::Resume { agent_id } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.resume_agent(&agent_id).await {
+                    Ok(_) => println!("Successfully resumed agent: {agent_id}"),
+                    Err(e) => eprintln!("Failed to resume agent: {e}"),
+                }
+            }
+
+            SupervisorCommands::ReloadConfig { agent_id } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.reload_agent_config(&agent_id).await {
+                    Ok(_) => println!("Asked agent {agent_id} to reload its config"),
+                    Err(e) => eprintln!("Failed to reload agent config: {e}"),
+                }
+            }
+
+            SupervisorCommands::StopAll { yes } => {
+                if !yes {
+                    print!("This will kill ALL managed agents. Type 'stop all' to confirm: ");
+                    std::io::Write::flush(&mut std::io::stdout())?;
+                    let mut answer = String::new();
+                    std::io::stdin().read_line(&mut answer)?;
+                    if answer.trim() != "stop all" {
+                        println!("Aborted");
+                        return Ok(());
+                    }
+                }
+
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                let result = async {
+                    let token = client.request_emergency_token().await?;
+                    client.emergency_stop_all(&token.token).await
+                }
+                .await;
+
+                match result {
+                    Ok(report) => {
+                        println!(
+                            "Killed {stopped} agents, {failed} failed (incident {incident_id})",
+                            stopped = report.stopped.len(),
+                            failed = report.failed.len(),
+                            incident_id = report.incident_id
+                        );
+                        for agent_id in report.failed {
+                            eprintln!("  failed to kill: {agent_id}");
+                        }
+                    }
+                    Err(e) => eprintln!("Emergency stop-all failed: {e}"),
+                }
+            }
+
             SupervisorCommands::Quarantine { agent_id } => {
-                println!("Quarantining agent: {agent_id}");
-                println!("Not yet implemented - would send quarantine command to supervisor");
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.quarantine_agent(&agent_id).await {
+                    Ok(_) => println!("Successfully quarantined agent: {agent_id}"),
+                    Err(e) => eprintln!("Failed to quarantine agent: {e}"),
+                }
             }
 
-            SupervisorCommands::Logs { agent_id, follow } => {
-                let client =
-                    replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+            SupervisorCommands::Approve { agent_id } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.approve_agent(&agent_id).await {
+                    Ok(_) => println!("Successfully approved agent: {agent_id}"),
+                    Err(e) => eprintln!("Failed to approve agent: {e}"),
+                }
+            }
+
+            SupervisorCommands::TerminateConnection {
+                remote_addr,
+                container,
+                monitor,
+            } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                let connection = replicante::supervisor::network::ConnectionTermination {
+                    container,
+                    remote_addr,
+                };
+                match client
+                    .terminate_connection(monitor.as_deref(), connection)
+                    .await
+                {
+                    Ok(monitors) => println!(
+                        "Connection will be severed by: {monitors}",
+                        monitors = monitors.join(", ")
+                    ),
+                    Err(e) => eprintln!("Failed to terminate connection: {e}"),
+                }
+            }
+
+            SupervisorCommands::Quota { agent_id } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
+                match client.get_quota(&agent_id).await {
+                    Ok(quota) => {
+                        let limits = quota.limits;
+                        let unlimited = || "unlimited".to_string();
+                        let cpu_limit = limits
+                            .max_cpu_seconds
+                            .map_or_else(unlimited, |l| format!("{l:.0}s"));
+                        let network_limit = limits
+                            .max_network_bytes
+                            .map_or_else(unlimited, |l| format!("{l} bytes"));
+                        let tool_limit = limits
+                            .max_tool_calls
+                            .map_or_else(unlimited, |l| format!("{l} calls"));
+
+                        println!("Quota usage for agent {agent_id}:");
+                        match quota.usage {
+                            Some(usage) => {
+                                println!(
+                                    "  Period:  {start} - {end}",
+                                    start = usage.period_start,
+                                    end = usage.period_end
+                                );
+                                println!("  CPU:     {:.0}s / {cpu_limit}", usage.cpu_seconds);
+                                println!(
+                                    "  Network: {} bytes / {network_limit}",
+                                    usage.network_bytes
+                                );
+                                println!("  Tools:   {} calls / {tool_limit}", usage.tool_calls);
+                                if let Some(breach) = usage.exceeded {
+                                    println!(
+                                        "  Exceeded: {breach} (action: {action:?})",
+                                        action = limits.action
+                                    );
+                                }
+                            }
+                            None => println!("  No usage recorded yet"),
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to get quota usage: {e}"),
+                }
+            }
+
+            SupervisorCommands::Logs {
+                agent_id,
+                follow,
+                tail,
+            } => {
+                let client = replicante::supervisor::async_client::AsyncSupervisorClient::new(url)?;
                 if follow {
                     // Use streaming for follow mode
                     use futures::StreamExt;
-                    match client.get_logs_stream(&agent_id, true, Some(100)).await {
+                    match client.get_logs_stream(&agent_id, true, Some(tail)).await {
                         Ok(stream) => {
                             println!("Following logs for agent {agent_id}...");
                             futures::pin_mut!(stream);
@@ -272,7 +1010,7 @@ async fn main() -> Result<()> {
                         Err(e) => eprintln!("Failed to get logs stream: {e}"),
                     }
                 } else {
-                    match client.get_logs(&agent_id, false, Some(100)).await {
+                    match client.get_logs(&agent_id, false, Some(tail)).await {
                         Ok(logs) => println!("{logs}"),
                         Err(e) => eprintln!("Failed to get logs: {e}"),
                     }
@@ -294,37 +1032,211 @@ async fn main() -> Result<()> {
         Commands::Monitor { command } => {
             match command {
                 MonitorCommands::Metrics { agent_id, format } => {
-                    if let Some(id) = agent_id {
-                        println!("Metrics for agent {id}:");
-                    } else {
-                        println!("Metrics for all agents:");
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    match client.get_metrics().await {
+                        Ok(response) => {
+                            let mut metrics = response.metrics;
+                            if let Some(id) = &agent_id {
+                                metrics.retain(|agent, _| agent == id);
+                            }
+                            match format.as_str() {
+                                "prometheus" => {
+                                    for (agent, samples) in &metrics {
+                                        let Some(latest) = samples.last() else {
+                                            continue;
+                                        };
+                                        println!(
+                                            "agent_cpu_percent{{agent_id=\"{agent}\"}} {cpu}",
+                                            cpu = latest.cpu_percent
+                                        );
+                                        println!(
+                                            "agent_memory_mb{{agent_id=\"{agent}\"}} {memory}",
+                                            memory = latest.memory_mb
+                                        );
+                                    }
+                                }
+                                _ => println!(
+                                    "{json}",
+                                    json = serde_json::to_string_pretty(&metrics)?
+                                ),
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get metrics: {e}"),
                     }
-                    println!("Format: {format}");
-                    println!("Not yet implemented - would fetch from supervisor");
                 }
 
                 MonitorCommands::Events { limit } => {
-                    println!("Recent {limit} events:");
-                    println!("Not yet implemented - would fetch from supervisor");
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    match client.get_events().await {
+                        Ok(events) => {
+                            println!("Recent events:");
+                            for event in events.iter().skip(events.len().saturating_sub(limit)) {
+                                println!(
+                                    "  {timestamp} {agent_id} {event_type:?} {details}",
+                                    timestamp = event.timestamp,
+                                    agent_id = event.agent_id,
+                                    event_type = event.event_type,
+                                    details = event.details
+                                );
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get events: {e}"),
+                    }
                 }
 
                 MonitorCommands::Alerts { limit } => {
-                    println!("Recent {limit} alerts:");
-                    println!("Not yet implemented - would fetch from supervisor");
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    match client.get_alerts().await {
+                        Ok(alerts) if alerts.is_empty() => println!("No alerts"),
+                        Ok(alerts) => {
+                            println!("Recent alerts:");
+                            for alert in alerts.iter().skip(alerts.len().saturating_sub(limit)) {
+                                println!("  {alert:?}");
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get alerts: {e}"),
+                    }
                 }
 
                 MonitorCommands::Decisions { agent_id, last } => {
-                    println!("Last {last} decisions for agent {agent_id}:");
-                    println!("Not yet implemented - would fetch from supervisor");
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    match client
+                        .get_agent_decisions(&agent_id, None, None, last, 0)
+                        .await
+                    {
+                        Ok(decisions) => {
+                            println!("Decisions of agent {agent_id}:");
+                            for decision in decisions.iter().rev() {
+                                let status = decision
+                                    .result
+                                    .as_ref()
+                                    .map_or("pending", |result| result.status.as_str());
+                                println!(
+                                    "  #{id} {timestamp} {action} -> {status}",
+                                    id = decision.id,
+                                    timestamp = decision.timestamp,
+                                    action = decision.action
+                                );
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get decisions: {e}"),
+                    }
                 }
 
-                MonitorCommands::Audit { export } => {
-                    if let Some(path) = export {
-                        println!("Exporting audit log to: {:?}", path);
+                MonitorCommands::Audit {
+                    agent_id,
+                    limit,
+                    export,
+                } => {
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    match client.get_audit_log(agent_id.as_deref(), limit).await {
+                        Ok(entries) => {
+                            if let Some(path) = export {
+                                let json = serde_json::to_string_pretty(&entries)?;
+                                tokio::fs::write(&path, json).await?;
+                                println!(
+                                    "Exported {count} audit entries to: {path}",
+                                    count = entries.len(),
+                                    path = path.display()
+                                );
+                            } else {
+                                println!("Audit log:");
+                                for entry in entries {
+                                    println!(
+                                        "  {created_at} {actor} {method} {path} -> {status} ({outcome})",
+                                        created_at = entry.created_at,
+                                        actor = entry.record.actor,
+                                        method = entry.record.method,
+                                        path = entry.record.path,
+                                        status = entry.record.status,
+                                        outcome = entry.record.outcome
+                                    );
+                                }
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get audit log: {e}"),
+                    }
+                }
+
+                MonitorCommands::Security {
+                    container,
+                    severity,
+                    since,
+                    limit,
+                } => {
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    match client
+                        .get_security_reports(
+                            container.as_deref(),
+                            severity.as_deref(),
+                            since.as_deref(),
+                            limit,
+                        )
+                        .await
+                    {
+                        Ok(reports) if reports.is_empty() => println!("No security reports"),
+                        Ok(reports) => {
+                            println!("Security reports:");
+                            for stored in reports {
+                                let report = stored.report;
+                                println!(
+                                    "  #{id} {timestamp} {container_id} {risk:?} ({count} findings)",
+                                    id = stored.id,
+                                    timestamp = report.timestamp,
+                                    container_id = report.container_id,
+                                    risk = report.risk_level,
+                                    count = report.findings.len()
+                                );
+                                for finding in report.findings {
+                                    println!("      {finding:?}");
+                                }
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get security reports: {e}"),
+                    }
+                }
+
+                MonitorCommands::Whitelists { reload } => {
+                    let client =
+                        replicante::supervisor::async_client::AsyncSupervisorClient::new(None)?;
+                    let result = if reload {
+                        client.reload_security_whitelists().await
                     } else {
-                        println!("Audit log:");
+                        client.get_security_whitelists().await
+                    };
+                    match result {
+                        Ok(whitelists) => {
+                            if reload {
+                                println!("Whitelists reloaded");
+                            }
+                            println!(
+                                "Containers: {containers}",
+                                containers = whitelists.containers.join(", ")
+                            );
+                            println!(
+                                "Processes: {processes}",
+                                processes = whitelists.processes.join(", ")
+                            );
+                            println!(
+                                "Syscalls: {syscalls}",
+                                syscalls = whitelists.syscalls.join(", ")
+                            );
+                            for (agent, extra) in whitelists.agents {
+                                println!(
+                                    "  {agent}: processes [{processes}], syscalls [{syscalls}]",
+                                    processes = extra.processes.join(", "),
+                                    syscalls = extra.syscalls.join(", ")
+                                );
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to get security whitelists: {e}"),
                     }
-                    println!("Not yet implemented - would fetch from supervisor");
                 }
 
                 MonitorCommands::Dashboard { url } => {
@@ -334,6 +1246,393 @@ async fn main() -> Result<()> {
                 }
             }
         }
+
+        Commands::Init {
+            output,
+            provider,
+            model,
+            workspace,
+            servers,
+            yes,
+            force,
+        } => {
+            let interactive = !yes && std::io::IsTerminal::is_terminal(&std::io::stdin());
+            let ask = |question: &str, default: &str| -> Result<String> {
+                if !interactive {
+                    return Ok(default.to_string());
+                }
+                print!("{question} [{default}]: ");
+                std::io::Write::flush(&mut std::io::stdout())?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                let answer = answer.trim();
+                Ok(if answer.is_empty() { default } else { answer }.to_string())
+            };
+
+            let provider = match provider {
+                Some(provider) => provider,
+                None => ask("LLM provider (anthropic, openai, ollama)", "anthropic")?,
+            };
+            let model = match model {
+                Some(model) => model,
+                None => ask("Model", init::default_model(&provider))?,
+            };
+            let workspace = match workspace {
+                Some(workspace) => workspace,
+                None => ask("Workspace directory for the MCP servers", "./workspace")?,
+            };
+            let servers = if servers.is_empty() {
+                ask("MCP servers to enable", "filesystem,shell,http")?
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            } else {
+                servers
+            };
+            let servers = servers
+                .iter()
+                .map(|name| init::BuiltinServer::from_name(name))
+                .collect::<Result<Vec<_>>>()?;
+
+            let contents = init::render(&init::InitOptions {
+                provider,
+                model: Some(model),
+                workspace,
+                database_path: "replicante.db".to_string(),
+                servers,
+                bin_dir: init::bundled_bin_dir(),
+            })?;
+            init::write(&output, &contents, force)?;
+            println!("Wrote {path}", path = output.display());
+            println!(
+                "Check it with: replicante config validate --config {path}",
+                path = output.display()
+            );
+        }
+
+        Commands::Memory { database, command } => {
+            let database = database.to_string_lossy();
+            match command {
+                MemoryCommands::List { prefix, limit } => {
+                    let state = StateManager::open_read_only(&database).await?;
+                    let entries = state.list_memory(prefix.as_deref(), limit, 0).await?;
+                    if entries.is_empty() {
+                        println!("No memory entries");
+                    }
+                    for entry in entries {
+                        let mut value = entry.value.to_string();
+                        if value.len() > 80 {
+                            let end = value.floor_char_boundary(77);
+                            value.replace_range(end.., "...");
+                        }
+                        println!(
+                            "{key}  [{updated_at}]  {value}",
+                            key = entry.key,
+                            updated_at = entry.updated_at
+                        );
+                    }
+                }
+
+                MemoryCommands::Get { key } => {
+                    let state = StateManager::open_read_only(&database).await?;
+                    match state.recall(&key).await? {
+                        Some(value) => println!("{}", serde_json::to_string_pretty(&value)?),
+                        None => {
+                            eprintln!("No memory entry '{key}'");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                MemoryCommands::Set { key, value } => {
+                    let value: serde_json::Value = serde_json::from_str(&value).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Value is not valid JSON ({e}); quote strings, e.g. '\"text\"'"
+                        )
+                    })?;
+                    let state = StateManager::new(&database).await?;
+                    state.remember(&key, value).await?;
+                    println!("Stored {key}");
+                }
+
+                MemoryCommands::Delete { key } => {
+                    let state = StateManager::new(&database).await?;
+                    if state.forget(&key).await? {
+                        println!("Deleted {key}");
+                    } else {
+                        eprintln!("No memory entry '{key}'");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        Commands::Decisions {
+            database,
+            last,
+            status,
+            action,
+            json,
+        } => {
+            let state = StateManager::open_read_only(&database.to_string_lossy()).await?;
+            let mut decisions = state
+                .get_decisions_page(last, 0, action.as_deref(), status.as_deref())
+                .await?;
+            decisions.reverse();
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&decisions)?);
+            } else if decisions.is_empty() {
+                println!("No matching decisions");
+            } else {
+                for decision in decisions {
+                    let outcome = match &decision.result {
+                        Some(result) => match result.duration_ms {
+                            Some(ms) => format!("{status}, {ms} ms", status = result.status),
+                            None => result.status.clone(),
+                        },
+                        None => "no result".to_string(),
+                    };
+                    println!(
+                        "#{id} {timestamp} {action} [{outcome}]",
+                        id = decision.id,
+                        timestamp = decision.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                        action = decision.action
+                    );
+                    println!("    thought: {thought}", thought = decision.thought);
+                    if let Some(parameters) = decision.parameters.as_ref().filter(|p| !p.is_null())
+                    {
+                        println!("    params: {parameters}");
+                    }
+                    if let Some(result) = &decision.result {
+                        if let Some(summary) = &result.summary {
+                            println!("    summary: {summary}");
+                        }
+                        if let Some(error) = &result.error {
+                            println!("    error: {error}");
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::ExportTimeline {
+            database,
+            format,
+            last,
+            output,
+        } => {
+            let state = StateManager::open_read_only(&database.to_string_lossy()).await?;
+            let mut decisions = state.get_decisions_page(last, 0, None, None).await?;
+            decisions.reverse();
+            let rendered = timeline::render(&decisions, format)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, rendered)?;
+                    eprintln!(
+                        "Wrote {count} decisions to {path}",
+                        count = decisions.len(),
+                        path = path.display()
+                    );
+                }
+                None => print!("{rendered}"),
+            }
+        }
+
+        Commands::Facts {
+            database,
+            entity,
+            depth,
+            limit,
+            json,
+        } => {
+            let state = StateManager::open_read_only(&database.to_string_lossy()).await?;
+            let facts = state.related_facts(&entity, depth + 1, limit).await?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&facts)?);
+            } else if facts.is_empty() {
+                println!("No facts about {entity}");
+            } else {
+                for fact in facts {
+                    let source = fact
+                        .source
+                        .map(|source| format!("  (from {source})"))
+                        .unwrap_or_default();
+                    println!(
+                        "{subject} {predicate} {object}{source}",
+                        subject = fact.subject,
+                        predicate = fact.predicate,
+                        object = fact.object
+                    );
+                }
+            }
+        }
+
+        Commands::Tools {
+            config,
+            overlays,
+            profile,
+            command,
+        } => {
+            let mut config =
+                Config::from_files(&config_path(config), &LoadOptions { overlays, profile })?;
+            let only = match &command {
+                ToolsCommands::List { server, .. } => server.clone(),
+                ToolsCommands::Call { tool, .. } => match tool.split_once(':') {
+                    Some((server, _)) => Some(server.to_string()),
+                    None => anyhow::bail!("Expected the tool as server:tool, got '{tool}'"),
+                },
+            };
+            if let Some(name) = only {
+                config.mcp_servers.retain(|server| server.name == name);
+                if name != wasm_tools::SERVER_NAME {
+                    config.wasm_tools.clear();
+                }
+                if config.mcp_servers.is_empty() && config.wasm_tools.is_empty() {
+                    anyhow::bail!("No MCP server named '{name}' in the config");
+                }
+            }
+
+            let mcp = MCPClient::new(&config.mcp_servers)
+                .await?
+                .with_wasm_tools(wasm_tools::WasmTools::load(&config.wasm_tools)?);
+            for health in mcp.get_health_status().await {
+                if !health.is_initialized {
+                    eprintln!("MCP server {name} did not start", name = health.name);
+                }
+            }
+
+            match command {
+                ToolsCommands::List { json, .. } => {
+                    let tools = mcp.get_tools_with_schemas().await?;
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&tools)?);
+                    } else {
+                        for tool in tools {
+                            println!(
+                                "{name}  {description}",
+                                name = tool.name,
+                                description = tool.description.unwrap_or_default()
+                            );
+                        }
+                    }
+                }
+
+                ToolsCommands::Call { tool, params } => {
+                    let params: serde_json::Value = serde_json::from_str(&params)
+                        .map_err(|e| anyhow::anyhow!("--params is not valid JSON: {e}"))?;
+                    let result = mcp.call_tool(&tool, params).await?;
+                    println!("{}", serde_json::to_string_pretty(&result)?);
+                    if result.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        Commands::Doctor {
+            config,
+            overlays,
+            profile,
+            skip_llm,
+        } => {
+            let path = config_path(config);
+            let config = match Config::from_files(&path, &LoadOptions { overlays, profile }) {
+                Ok(config) => {
+                    println!("[PASS] config: {path}", path = path.display());
+                    config
+                }
+                Err(e) => {
+                    println!("[FAIL] config: {e:#}");
+                    std::process::exit(1);
+                }
+            };
+
+            let checks = doctor::run(&config, !skip_llm).await;
+            for check in &checks {
+                println!(
+                    "[{status}] {name}: {detail}",
+                    status = check.status,
+                    name = check.name,
+                    detail = check.detail
+                );
+            }
+            let failed = checks
+                .iter()
+                .filter(|check| check.status == doctor::Status::Fail)
+                .count();
+            if failed > 0 {
+                println!("\n{failed} check(s) failed");
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Prompt {
+            command:
+                PromptCommands::Preview {
+                    config,
+                    overlays,
+                    profile,
+                },
+        } => {
+            let config =
+                Config::from_files(&config_path(config), &LoadOptions { overlays, profile })?;
+            let preview = replicante::preview_prompt(config).await?;
+            println!("{}", preview.prompt);
+            eprintln!(
+                "--- {chars} characters, ~{tokens} tokens (estimated)",
+                chars = preview.prompt.chars().count(),
+                tokens = preview.estimated_tokens
+            );
+        }
+
+        Commands::Config { command } => match command {
+            ConfigCommands::Validate {
+                config,
+                overlays,
+                profile,
+            } => {
+                let path = config_path(config);
+                let config = match Config::from_files(&path, &LoadOptions { overlays, profile }) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        eprintln!("{e:#}");
+                        std::process::exit(1);
+                    }
+                };
+
+                let problems = config.validate();
+                if problems.is_empty() {
+                    println!(
+                        "{path}: OK ({servers} MCP servers, provider {provider})",
+                        path = path.display(),
+                        servers = config.mcp_servers.len(),
+                        provider = config.llm.provider
+                    );
+                } else {
+                    eprintln!(
+                        "{path}: {count} problem(s) found",
+                        path = path.display(),
+                        count = problems.len()
+                    );
+                    for problem in problems {
+                        eprintln!("  - {problem}");
+                    }
+                    std::process::exit(1);
+                }
+            }
+
+            ConfigCommands::Schema { supervisor } => {
+                let schema = if supervisor {
+                    schemars::schema_for!(supervisor::SupervisorConfig)
+                } else {
+                    Config::json_schema()
+                };
+                println!("{}", serde_json::to_string_pretty(&schema)?);
+            }
+        },
     }
 
     Ok(())
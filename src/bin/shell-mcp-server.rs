@@ -4,11 +4,18 @@
 
 use anyhow::{Context, Result, bail};
 use clap::Parser;
+use replicante::mcp_server::{
+    McpServer, Param, Tool, ToolContext, ToolHandler, ToolOutput, UnknownTool,
+};
 use serde_json::{Value, json};
-use std::io::{self, BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Stdio as ProcessStdio;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::Command;
 use tokio::runtime::Runtime;
 use tokio::time::timeout;
@@ -32,32 +39,362 @@ struct Args {
     /// Maximum command output size in megabytes
     #[arg(long, env = "MAX_OUTPUT_SIZE_MB", default_value = "1")]
     max_output_size_mb: usize,
+
+    /// Maximum number of background jobs running at once
+    #[arg(long, env = "MAX_JOBS", default_value = "10")]
+    max_jobs: usize,
+
+    /// Maximum number of persistent shell sessions open at once
+    #[arg(long, env = "MAX_SESSIONS", default_value = "5")]
+    max_sessions: usize,
+
+    /// Output size in kilobytes returned per page; longer output gets a continuation token
+    #[arg(long, env = "OUTPUT_PAGE_SIZE_KB", default_value = "64")]
+    output_page_size_kb: usize,
+
+    /// Address space limit for shell commands in megabytes
+    #[arg(long, env = "COMMAND_MAX_MEMORY_MB")]
+    max_memory_mb: Option<u64>,
+
+    /// CPU time limit for shell commands in seconds
+    #[arg(long, env = "COMMAND_MAX_CPU_SECS")]
+    max_cpu_secs: Option<u64>,
+
+    /// Largest file a shell command may write, in megabytes
+    #[arg(long, env = "COMMAND_MAX_FILE_SIZE_MB")]
+    max_file_size_mb: Option<u64>,
+
+    /// Niceness added to shell commands (0-19)
+    #[arg(long, env = "COMMAND_NICE")]
+    nice: Option<i32>,
+
+    /// cgroup v2 directory that commands are moved into, e.g. /sys/fs/cgroup/agent-commands
+    #[arg(long, env = "COMMAND_CGROUP")]
+    cgroup: Option<PathBuf>,
+
+    /// TOML file with `allow` and `deny` lists of command regexes
+    #[arg(long, env = "COMMAND_POLICY_FILE")]
+    command_policy: Option<PathBuf>,
+
+    /// Regex a command must match to run (repeatable; newline-separated in the env var).
+    /// While any is set, commands containing ; & | ` $( or a line break are
+    /// refused, as they could run a second command the regex never saw.
+    /// Allowed programs that run others themselves (find -exec, xargs, sh -c)
+    /// are not caught, so keep them out of the patterns.
+    #[arg(long = "allow-command", env = "ALLOW_COMMANDS", value_delimiter = '\n')]
+    allow_commands: Vec<String>,
+
+    /// Regex of commands that must not run (repeatable; newline-separated in the env var)
+    #[arg(long = "deny-command", env = "DENY_COMMANDS", value_delimiter = '\n')]
+    deny_commands: Vec<String>,
+}
+
+/// Resource limits set on shell commands between fork and exec
+#[derive(Debug, Clone, Copy, Default)]
+struct ResourceLimits {
+    max_memory_bytes: Option<u64>,
+    max_cpu_secs: Option<u64>,
+    max_file_size_bytes: Option<u64>,
+    nice: Option<i32>,
+}
+
+impl ResourceLimits {
+    fn from_args(args: &Args) -> Self {
+        Self {
+            max_memory_bytes: args.max_memory_mb.map(|mb| mb * 1024 * 1024),
+            max_cpu_secs: args.max_cpu_secs,
+            max_file_size_bytes: args.max_file_size_mb.map(|mb| mb * 1024 * 1024),
+            nice: args.nice,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max_memory_bytes.is_none()
+            && self.max_cpu_secs.is_none()
+            && self.max_file_size_bytes.is_none()
+            && self.nice.is_none()
+    }
+
+    fn apply(&self, cmd: &mut Command) {
+        if self.is_empty() {
+            return;
+        }
+        let limits = *self;
+        // SAFETY: the closure only makes setrlimit and nice system calls,
+        // which are safe to run between fork and exec
+        unsafe {
+            cmd.pre_exec(move || limits.set_for_current_process());
+        }
+    }
+
+    fn set_for_current_process(&self) -> io::Result<()> {
+        use nix::sys::resource::{Resource, setrlimit};
+
+        let limits = [
+            (Resource::RLIMIT_AS, self.max_memory_bytes),
+            (Resource::RLIMIT_CPU, self.max_cpu_secs),
+            (Resource::RLIMIT_FSIZE, self.max_file_size_bytes),
+        ];
+        for (resource, limit) in limits {
+            if let Some(limit) = limit {
+                setrlimit(resource, limit, limit)?;
+            }
+        }
+
+        if let Some(nice) = self.nice {
+            // nice() may legitimately return -1, so errors are checked through errno
+            nix::errno::Errno::clear();
+            // SAFETY: nice only changes this process's scheduling priority
+            if unsafe { nix::libc::nice(nice) } == -1
+                && nix::errno::Errno::last() != nix::errno::Errno::UnknownErrno
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command policy file contents
+#[derive(Debug, Default, serde::Deserialize)]
+struct CommandPolicyFile {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+/// Shell syntax that runs another command after, alongside or inside the
+/// one an allow rule matched
+const COMMAND_CHAINING: &[&str] = &[";", "&", "|", "`", "$(", "\n", "\r"];
+
+/// Regexes deciding which commands may run; deny rules win, and a non-empty
+/// allow list rejects anything it does not match or that chains commands
+#[derive(Debug, Default)]
+struct CommandPolicy {
+    allow: Vec<regex::Regex>,
+    deny: Vec<regex::Regex>,
+}
+
+impl CommandPolicy {
+    fn load(args: &Args) -> Result<Self> {
+        let file = match &args.command_policy {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read command policy {:?}", path))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("Failed to parse command policy {:?}", path))?
+            }
+            None => CommandPolicyFile::default(),
+        };
+
+        let compile = |patterns: Vec<&String>| -> Result<Vec<regex::Regex>> {
+            patterns
+                .into_iter()
+                .filter(|pattern| !pattern.trim().is_empty())
+                .map(|pattern| {
+                    regex::Regex::new(pattern)
+                        .with_context(|| format!("Invalid command regex: {}", pattern))
+                })
+                .collect()
+        };
+
+        Ok(Self {
+            allow: compile(file.allow.iter().chain(&args.allow_commands).collect())?,
+            deny: compile(file.deny.iter().chain(&args.deny_commands).collect())?,
+        })
+    }
+
+    fn check(&self, command: &str) -> Result<(), CommandDenied> {
+        if let Some(rule) = self.deny.iter().find(|rule| rule.is_match(command)) {
+            return Err(CommandDenied {
+                command: command.to_string(),
+                rule: Some(rule.as_str().to_string()),
+                chaining: None,
+            });
+        }
+        if self.allow.is_empty() {
+            return Ok(());
+        }
+        // An allow rule only vouches for the first command of a chain
+        if let Some(syntax) = COMMAND_CHAINING
+            .iter()
+            .find(|syntax| command.contains(**syntax))
+        {
+            return Err(CommandDenied {
+                command: command.to_string(),
+                rule: None,
+                chaining: Some(syntax),
+            });
+        }
+        if !self.allow.iter().any(|rule| rule.is_match(command)) {
+            return Err(CommandDenied {
+                command: command.to_string(),
+                rule: None,
+                chaining: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A command rejected by the command policy
+#[derive(Debug)]
+struct CommandDenied {
+    command: String,
+    /// The deny rule that matched, or None when no allow rule matched
+    rule: Option<String>,
+    /// Shell syntax refused while an allow list is set
+    chaining: Option<&'static str>,
+}
+
+impl std::fmt::Display for CommandDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.rule, self.chaining) {
+            (Some(rule), _) => write!(f, "Command denied by policy rule '{}'", rule),
+            (None, Some(syntax)) => write!(
+                f,
+                "Command denied: {syntax:?} is not allowed while commands are limited to an allow list"
+            ),
+            (None, None) => write!(f, "Command denied: it matches no allowed command pattern"),
+        }
+    }
+}
+
+impl std::error::Error for CommandDenied {}
+
+/// Output and exit state of a background job, shared with its reader tasks
+#[derive(Default)]
+struct JobState {
+    /// Combined stdout and stderr, oldest bytes dropped past the output limit
+    output: Vec<u8>,
+    /// Bytes dropped from the front of `output`
+    dropped: u64,
+    exit_code: Option<i32>,
+    finished: bool,
+}
+
+impl JobState {
+    fn append(&mut self, bytes: &[u8], limit: usize) {
+        self.output.extend_from_slice(bytes);
+        if self.output.len() > limit {
+            let excess = self.output.len() - limit;
+            self.output.drain(..excess);
+            self.dropped += excess as u64;
+        }
+    }
+}
+
+/// A command started with start_job
+struct Job {
+    command: String,
+    cwd: PathBuf,
+    pid: Option<u32>,
+    started_at: chrono::DateTime<chrono::Utc>,
+    state: Arc<Mutex<JobState>>,
+}
+
+/// Full outputs kept for get_output_chunk before the oldest expire
+const MAX_STORED_OUTPUTS: usize = 32;
+
+/// Outputs longer than a page, by continuation token number
+#[derive(Default)]
+struct StoredOutputs {
+    outputs: BTreeMap<u64, Vec<u8>>,
+    next: u64,
+}
+
+impl StoredOutputs {
+    fn store(&mut self, output: Vec<u8>) -> String {
+        self.next += 1;
+        self.outputs.insert(self.next, output);
+        while self.outputs.len() > MAX_STORED_OUTPUTS {
+            self.outputs.pop_first();
+        }
+        format!("out-{}", self.next)
+    }
+
+    fn get(&self, token: &str) -> Option<&Vec<u8>> {
+        let id = token.strip_prefix("out-")?.parse().ok()?;
+        self.outputs.get(&id)
+    }
+}
+
+/// A persistent shell opened with open_session
+struct Session {
+    child: tokio::process::Child,
+    stdin: tokio::process::ChildStdin,
+    stdout: tokio::io::BufReader<tokio::process::ChildStdout>,
+    stderr: tokio::io::BufReader<tokio::process::ChildStderr>,
+    /// Commands run so far, numbering each command's marker
+    commands: u64,
 }
 
 /// Shell MCP Server implementation
 struct ShellMCPServer {
-    initialized: bool,
     workspace_root: PathBuf,
     allow_docker: bool,
     runtime: Runtime,
-    verbose: bool,
     max_output_size: usize,
+    max_jobs: usize,
+    jobs: BTreeMap<u64, Job>,
+    next_job: u64,
+    max_sessions: usize,
+    sessions: BTreeMap<u64, Session>,
+    next_session: u64,
+    command_policy: CommandPolicy,
+    output_page_size: usize,
+    resource_limits: ResourceLimits,
+    cgroup: Option<PathBuf>,
+    /// Interior mutability so output can be stored from inside block_on
+    stored_outputs: RefCell<StoredOutputs>,
 }
 
 impl ShellMCPServer {
-    /// Helper to truncate output to max size
-    fn truncate_output(&self, output: &[u8]) -> String {
-        if output.len() > self.max_output_size {
-            let truncated = &output[..self.max_output_size];
-            let mut result = String::from_utf8_lossy(truncated).into_owned();
-            result.push_str(&format!(
-                "\n\n[OUTPUT TRUNCATED - {} bytes omitted]",
-                output.len() - self.max_output_size
-            ));
-            result
-        } else {
-            String::from_utf8_lossy(output).into_owned()
+    /// Exit code, stdout and stderr of a command as JSON. Each stream is
+    /// capped at the max size and only its first page is returned; the rest
+    /// is kept for get_output_chunk
+    fn command_result(&self, exit_code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> Value {
+        let mut result = serde_json::Map::new();
+        result.insert("exit_code".to_string(), json!(exit_code.unwrap_or(-1)));
+        self.insert_output(&mut result, "stdout", stdout);
+        self.insert_output(&mut result, "stderr", stderr);
+        Value::Object(result)
+    }
+
+    /// Add the first page of `output` as `name`, with `<name>_continuation`
+    /// when more pages follow and `<name>_omitted_bytes` when it was capped
+    fn insert_output(
+        &self,
+        result: &mut serde_json::Map<String, Value>,
+        name: &str,
+        output: &[u8],
+    ) {
+        let kept = &output[..output.len().min(self.max_output_size)];
+        let end = char_boundary(kept, self.output_page_size);
+
+        result.insert(
+            name.to_string(),
+            json!(String::from_utf8_lossy(&kept[..end])),
+        );
+        if end < kept.len() {
+            let token = self.stored_outputs.borrow_mut().store(kept.to_vec());
+            result.insert(
+                format!("{name}_continuation"),
+                json!({"token": token, "next_offset": end, "total_bytes": kept.len()}),
+            );
         }
+        if output.len() > kept.len() {
+            result.insert(
+                format!("{name}_omitted_bytes"),
+                json!(output.len() - kept.len()),
+            );
+        }
+    }
+
+    /// Output capped at the max size, for parsing rather than showing
+    fn capped_output(&self, output: &[u8]) -> String {
+        String::from_utf8_lossy(&output[..output.len().min(self.max_output_size)]).into_owned()
     }
 
     fn new(args: Args) -> Result<Self> {
@@ -77,18 +414,61 @@ impl ShellMCPServer {
             );
         }
 
+        let command_policy = CommandPolicy::load(&args)?;
+        if args.verbose {
+            eprintln!(
+                "[Shell MCP] Command policy: {} allow, {} deny rules",
+                command_policy.allow.len(),
+                command_policy.deny.len()
+            );
+        }
+
+        let resource_limits = ResourceLimits::from_args(&args);
+        if args.verbose && !resource_limits.is_empty() {
+            eprintln!("[Shell MCP] Resource limits: {:?}", resource_limits);
+        }
+        if let Some(cgroup) = &args.cgroup
+            && !cgroup.join("cgroup.procs").exists()
+        {
+            bail!("Not a cgroup v2 directory: {:?}", cgroup);
+        }
+
         let runtime = Runtime::new()?;
 
         Ok(Self {
-            initialized: false,
             workspace_root,
             allow_docker: args.allow_docker,
             runtime,
-            verbose: args.verbose,
             max_output_size,
+            max_jobs: args.max_jobs,
+            jobs: BTreeMap::new(),
+            next_job: 1,
+            max_sessions: args.max_sessions,
+            sessions: BTreeMap::new(),
+            next_session: 1,
+            command_policy,
+            output_page_size: (args.output_page_size_kb * 1024).max(1),
+            resource_limits,
+            cgroup: args.cgroup,
+            stored_outputs: RefCell::new(StoredOutputs::default()),
         })
     }
 
+    /// `sh` with the configured resource limits, running `command` if given.
+    /// The shell joins the configured cgroup before it execs, so it never
+    /// runs outside it.
+    fn shell_command(&self, command: Option<&str>) -> Result<Command> {
+        let mut cmd = Command::new("sh");
+        if let Some(command) = command {
+            cmd.arg("-c").arg(command);
+        }
+        self.resource_limits.apply(&mut cmd);
+        if let Some(cgroup) = &self.cgroup {
+            join_cgroup(&mut cmd, cgroup)?;
+        }
+        Ok(cmd)
+    }
+
     /// Ensure working directory is within workspace
     fn safe_cwd(&self, cwd: &str) -> Result<PathBuf> {
         let full_path = if cwd.starts_with('/') {
@@ -107,220 +487,232 @@ impl ShellMCPServer {
 
         Ok(canonical)
     }
+}
 
-    /// Handle JSON-RPC request
-    fn handle_request(&mut self, request: Value) -> Result<Option<Value>> {
-        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
-        let default_params = json!({});
-        let params = request.get("params").unwrap_or(&default_params);
-        let request_id = request.get("id");
-
-        if self.verbose {
-            eprintln!("[Shell MCP] Handling request: {}", method);
-        }
-
-        match method {
-            "initialize" => Ok(Some(self.handle_initialize(request_id)?)),
-            "initialized" => {
-                self.initialized = true;
-                if self.verbose {
-                    eprintln!("[Shell MCP] Client confirmed initialization");
-                }
-                Ok(None)
-            }
-            "tools/list" => Ok(Some(self.handle_tools_list(request_id)?)),
-            "tools/call" => Ok(Some(self.handle_tool_call(request_id, params)?)),
-            _ => Ok(Some(self.error_response(
-                request_id,
-                -32601,
-                &format!("Method not found: {}", method),
-            ))),
-        }
-    }
-
-    /// Handle initialize request
-    fn handle_initialize(&self, request_id: Option<&Value>) -> Result<Value> {
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": {
-                "protocolVersion": "2024-11-05",
-                "serverInfo": {
-                    "name": "shell-mcp-server",
-                    "version": "1.0.0"
-                },
-                "capabilities": {
-                    "tools": {}
-                }
-            }
-        }))
-    }
-
-    /// Return list of available tools
-    fn handle_tools_list(&self, request_id: Option<&Value>) -> Result<Value> {
+impl ToolHandler for ShellMCPServer {
+    fn tools(&self) -> Vec<Tool> {
         let mut tools = vec![
-            json!({
-                "name": "run_command",
-                "description": "Execute a shell command",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "command": {"type": "string", "description": "The shell command to execute"},
-                        "cwd": {"type": "string", "description": "Working directory", "default": "."},
-                        "timeout_secs": {"type": "integer", "description": "Command timeout in seconds", "default": 60}
-                    },
-                    "required": ["command"]
-                }
-            }),
-            json!({
-                "name": "check_command",
-                "description": "Check if a command is available",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "command": {"type": "string", "description": "Command name to check"}
-                    },
-                    "required": ["command"]
-                }
-            }),
+            Tool::new("run_command", "Execute a shell command")
+                .param(Param::string("command", "The shell command to execute").required())
+                .param(Param::string("cwd", "Working directory").default("."))
+                .param(Param::integer("timeout_secs", "Command timeout in seconds").default(60))
+                .param(Param::string(
+                    "stdin",
+                    "Text written to the command's standard input (default: none)",
+                )),
+            Tool::new("check_command", "Check if a command is available")
+                .param(Param::string("command", "Command name to check").required()),
+            Tool::new(
+                "get_output_chunk",
+                "Read more of a long command output using the continuation token it returned",
+            )
+            .param(Param::string("token", "Continuation token from the paginated output").required())
+            .param(Param::integer("offset", "Byte offset to read from").required())
+            .param(Param::integer(
+                "length",
+                "Bytes to return (default and max: one page)",
+            )),
+            Tool::new(
+                "start_job",
+                "Start a shell command in the background and return its job ID",
+            )
+            .param(Param::string("command", "The shell command to execute").required())
+            .param(Param::string("cwd", "Working directory").default(".")),
+            Tool::new(
+                "job_status",
+                "Get the status of a background job, or of all jobs",
+            )
+            .param(Param::string("job_id", "Job ID; omit to list all jobs")),
+            Tool::new(
+                "job_output",
+                "Read a background job's combined stdout/stderr from a byte offset",
+            )
+            .param(Param::string("job_id", "Job ID").required())
+            .param(
+                Param::integer(
+                    "offset",
+                    "Byte offset to read from; pass the previous next_offset to get only new output",
+                )
+                .default(0),
+            ),
+            Tool::new(
+                "kill_job",
+                "Send a signal to a background job's process group",
+            )
+            .param(Param::string("job_id", "Job ID").required())
+            .param(
+                Param::string("signal", "Signal to send")
+                    .one_of(&["TERM", "KILL", "INT", "HUP"])
+                    .default("TERM"),
+            ),
+            Tool::new(
+                "open_session",
+                "Open a persistent shell session that keeps its working directory and environment between commands",
+            )
+            .param(Param::string("cwd", "Initial working directory").default("."))
+            .param(Param::object("env", "Extra environment variables")),
+            Tool::new(
+                "run_in_session",
+                "Run a command in a persistent shell session; cd and export carry over to later commands",
+            )
+            .param(Param::string("session_id", "Session ID from open_session").required())
+            .param(Param::string("command", "The shell command to execute").required())
+            .param(
+                Param::integer(
+                    "timeout_secs",
+                    "Command timeout in seconds; the session is closed on timeout",
+                )
+                .default(60),
+            )
+            .param(Param::string(
+                "stdin",
+                "Input lines for the command, e.g. answers to its prompts; a final newline is added if missing",
+            )),
+            Tool::new(
+                "close_session",
+                "Close a persistent shell session and kill its processes",
+            )
+            .param(Param::string("session_id", "Session ID").required()),
         ];
 
         if self.allow_docker {
-            tools.extend(vec![
-                json!({
-                    "name": "docker_run",
-                    "description": "Run a Docker container",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "image": {"type": "string", "description": "Docker image to run"},
-                            "command": {"type": "string", "description": "Command to run in container"},
-                            "name": {"type": "string", "description": "Container name"},
-                            "detach": {"type": "boolean", "description": "Run in background", "default": false},
-                            "ports": {"type": "array", "items": {"type": "string"}, "description": "Port mappings"},
-                            "volumes": {"type": "array", "items": {"type": "string"}, "description": "Volume mappings"},
-                            "env": {"type": "object", "description": "Environment variables"}
-                        },
-                        "required": ["image"]
-                    }
-                }),
-                json!({
-                    "name": "docker_ps",
-                    "description": "List Docker containers",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "all": {"type": "boolean", "description": "Show all containers", "default": false}
-                        }
-                    }
-                }),
-                json!({
-                    "name": "docker_logs",
-                    "description": "Get Docker container logs",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "container": {"type": "string", "description": "Container name or ID"},
-                            "tail": {"type": "integer", "description": "Number of lines from end", "default": 50}
-                        },
-                        "required": ["container"]
-                    }
-                }),
-                json!({
-                    "name": "docker_exec",
-                    "description": "Execute command in running container",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "container": {"type": "string", "description": "Container name or ID"},
-                            "command": {"type": "string", "description": "Command to execute"}
-                        },
-                        "required": ["container", "command"]
-                    }
-                }),
-                json!({
-                    "name": "docker_stop",
-                    "description": "Stop a Docker container",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "container": {"type": "string", "description": "Container name or ID"}
-                        },
-                        "required": ["container"]
-                    }
-                }),
-                json!({
-                    "name": "docker_pull",
-                    "description": "Pull a Docker image",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "image": {"type": "string", "description": "Image to pull"}
-                        },
-                        "required": ["image"]
-                    }
-                })
+            tools.extend([
+                Tool::new("docker_run", "Run a Docker container")
+                    .param(Param::string("image", "Docker image to run").required())
+                    .param(Param::string("command", "Command to run in container"))
+                    .param(Param::string("name", "Container name"))
+                    .param(Param::boolean("detach", "Run in background").default(false))
+                    .param(Param::array("ports", "Port mappings", "string"))
+                    .param(Param::array("volumes", "Volume mappings", "string"))
+                    .param(Param::object("env", "Environment variables")),
+                Tool::new("docker_ps", "List Docker containers")
+                    .param(Param::boolean("all", "Show all containers").default(false)),
+                Tool::new("docker_logs", "Get Docker container logs")
+                    .param(Param::string("container", "Container name or ID").required())
+                    .param(Param::integer("tail", "Number of lines from end").default(50)),
+                Tool::new("docker_exec", "Execute command in running container")
+                    .param(Param::string("container", "Container name or ID").required())
+                    .param(Param::string("command", "Command to execute").required()),
+                Tool::new("docker_stop", "Stop a Docker container")
+                    .param(Param::string("container", "Container name or ID").required()),
+                Tool::new(
+                    "docker_build",
+                    "Build a Docker image from a context in the workspace as a background job; follow it with job_output",
+                )
+                .param(
+                    Param::string("context", "Build context directory inside the workspace")
+                        .default("."),
+                )
+                .param(Param::string("tag", "Image tag, e.g. 'myapp:latest'"))
+                .param(Param::string(
+                    "dockerfile",
+                    "Dockerfile path inside the workspace (default: <context>/Dockerfile)",
+                ))
+                .param(Param::object("build_args", "Build-time variables (--build-arg)"))
+                .param(Param::string("target", "Build stage to stop at"))
+                .param(Param::boolean("no_cache", "Do not use the build cache").default(false)),
+                Tool::new(
+                    "compose_up",
+                    "Start the services of a docker compose file in the background",
+                )
+                .param(compose_file_param())
+                .param(Param::string("project", "Compose project name"))
+                .param(Param::array(
+                    "services",
+                    "Services to start (default: all)",
+                    "string",
+                ))
+                .param(Param::boolean("build", "Build images before starting").default(false)),
+                Tool::new(
+                    "compose_down",
+                    "Stop and remove the services of a docker compose file",
+                )
+                .param(compose_file_param())
+                .param(Param::string("project", "Compose project name"))
+                .param(Param::boolean("volumes", "Also remove named volumes").default(false)),
+                Tool::new(
+                    "compose_ps",
+                    "List the containers of a docker compose project",
+                )
+                .param(compose_file_param())
+                .param(Param::string("project", "Compose project name")),
+                Tool::new("compose_logs", "Get logs of docker compose services")
+                    .param(compose_file_param())
+                    .param(Param::string("project", "Compose project name"))
+                    .param(Param::array(
+                        "services",
+                        "Services to show (default: all)",
+                        "string",
+                    ))
+                    .param(
+                        Param::integer("tail", "Number of lines from end per service").default(100),
+                    ),
+                Tool::new("docker_pull", "Pull a Docker image")
+                    .param(Param::string("image", "Image to pull").required()),
             ]);
         }
 
-        Ok(json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "result": {
-                "tools": tools
-            }
-        }))
+        tools
     }
 
-    /// Handle tool execution
-    fn handle_tool_call(&mut self, request_id: Option<&Value>, params: &Value) -> Result<Value> {
-        let tool_name = params.get("name").and_then(|n| n.as_str()).unwrap_or("");
-        let default_args = json!({});
-        let arguments = params.get("arguments").unwrap_or(&default_args);
-
-        if self.verbose {
-            eprintln!("[Shell MCP] Executing tool: {}", tool_name);
-        }
-
-        let result = match tool_name {
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        _context: &ToolContext,
+    ) -> Result<ToolOutput> {
+        let result = match name {
             "run_command" => self.run_command(arguments),
             "check_command" => self.check_command(arguments),
+            "get_output_chunk" => self.get_output_chunk(arguments),
+            "start_job" => self.start_job(arguments),
+            "job_status" => self.job_status(arguments),
+            "job_output" => self.job_output(arguments),
+            "kill_job" => self.kill_job(arguments),
+            "open_session" => self.open_session(arguments),
+            "run_in_session" => self.run_in_session(arguments),
+            "close_session" => self.close_session(arguments),
             "docker_run" if self.allow_docker => self.docker_run(arguments),
             "docker_ps" if self.allow_docker => self.docker_ps(arguments),
             "docker_logs" if self.allow_docker => self.docker_logs(arguments),
             "docker_exec" if self.allow_docker => self.docker_exec(arguments),
             "docker_stop" if self.allow_docker => self.docker_stop(arguments),
             "docker_pull" if self.allow_docker => self.docker_pull(arguments),
-            _ => Err(anyhow::anyhow!("Unknown tool: {}", tool_name)),
-        };
+            "docker_build" if self.allow_docker => self.docker_build(arguments),
+            "compose_up" if self.allow_docker => self.compose_up(arguments),
+            "compose_down" if self.allow_docker => self.compose_down(arguments),
+            "compose_ps" if self.allow_docker => self.compose_ps(arguments),
+            "compose_logs" if self.allow_docker => self.compose_logs(arguments),
+            _ => Err(UnknownTool(name.to_string()).into()),
+        }?;
+        Ok(ToolOutput::json(&result))
+    }
 
-        match result {
-            Ok(content) => Ok(json!({
-                "jsonrpc": "2.0",
-                "id": request_id,
-                "result": {
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": content
-                        }
-                    ]
-                }
-            })),
-            Err(e) => Ok(self.error_response(
-                request_id,
-                -32603,
-                &format!("Tool execution failed: {}", e),
-            )),
-        }
+    fn error_data(&self, error: &anyhow::Error) -> Option<Value> {
+        let denied = error.downcast_ref::<CommandDenied>()?;
+        Some(json!({
+            "type": "command_denied",
+            "command": denied.command,
+            "rule": denied.rule,
+            "chaining": denied.chaining
+        }))
     }
+}
+
+/// The `file` parameter shared by the compose tools
+fn compose_file_param() -> Param {
+    Param::string("file", "Compose file inside the workspace").default("docker-compose.yml")
+}
 
-    fn run_command(&mut self, args: &Value) -> Result<String> {
+impl ShellMCPServer {
+    fn run_command(&mut self, args: &Value) -> Result<Value> {
         let command = args
             .get("command")
             .and_then(|c| c.as_str())
             .context("Missing 'command' parameter")?;
 
+        self.command_policy.check(command)?;
+
         let cwd = args.get("cwd").and_then(|c| c.as_str()).unwrap_or(".");
 
         let timeout_secs = args
@@ -328,41 +720,49 @@ impl ShellMCPServer {
             .and_then(|t| t.as_u64())
             .unwrap_or(60);
 
-        let safe_cwd = self.safe_cwd(cwd)?;
+        let input = args.get("stdin").and_then(|s| s.as_str());
 
-        self.runtime.block_on(async {
-            let output = timeout(
-                Duration::from_secs(timeout_secs),
-                Command::new("sh")
-                    .arg("-c")
-                    .arg(command)
-                    .current_dir(&safe_cwd)
-                    .stdout(ProcessStdio::piped())
-                    .stderr(ProcessStdio::piped())
-                    .output(),
-            )
-            .await
-            .context("Command timed out")?
-            .context("Failed to execute command")?;
+        let safe_cwd = self.safe_cwd(cwd)?;
 
-            let mut result = format!("Exit code: {}\n", output.status.code().unwrap_or(-1));
+        let mut cmd = self.shell_command(Some(command))?;
 
-            if !output.stdout.is_empty() {
-                result.push_str("STDOUT:\n");
-                result.push_str(&self.truncate_output(&output.stdout));
-                result.push('\n');
+        self.runtime.block_on(async {
+            // Without input the command must not read the server's own stdin,
+            // which carries the JSON-RPC requests
+            let mut child = cmd
+                .current_dir(&safe_cwd)
+                .stdin(if input.is_some() {
+                    ProcessStdio::piped()
+                } else {
+                    ProcessStdio::null()
+                })
+                .stdout(ProcessStdio::piped())
+                .stderr(ProcessStdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .context("Failed to execute command")?;
+
+            if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+                let input = input.to_string();
+                // Written from a task so a command that prints before reading
+                // all of its input cannot block on a full stdout pipe
+                tokio::spawn(async move {
+                    use tokio::io::AsyncWriteExt;
+                    // The command may exit without reading everything
+                    let _ = stdin.write_all(input.as_bytes()).await;
+                });
             }
 
-            if !output.stderr.is_empty() {
-                result.push_str("STDERR:\n");
-                result.push_str(&self.truncate_output(&output.stderr));
-            }
+            let output = timeout(Duration::from_secs(timeout_secs), child.wait_with_output())
+                .await
+                .context("Command timed out")?
+                .context("Failed to execute command")?;
 
-            Ok(result)
+            Ok(self.command_result(output.status.code(), &output.stdout, &output.stderr))
         })
     }
 
-    fn check_command(&mut self, args: &Value) -> Result<String> {
+    fn check_command(&mut self, args: &Value) -> Result<Value> {
         let command = args
             .get("command")
             .and_then(|c| c.as_str())
@@ -382,14 +782,419 @@ impl ShellMCPServer {
                 None
             };
 
-            Ok(serde_json::to_string(&json!({
+            Ok(json!({
                 "available": available,
                 "path": path
-            }))?)
+            }))
+        })
+    }
+
+    fn get_output_chunk(&mut self, args: &Value) -> Result<Value> {
+        let token = args
+            .get("token")
+            .and_then(|t| t.as_str())
+            .context("Missing 'token' parameter")?;
+
+        let offset = args
+            .get("offset")
+            .and_then(|o| o.as_u64())
+            .context("Missing 'offset' parameter")? as usize;
+
+        let length = args
+            .get("length")
+            .and_then(|l| l.as_u64())
+            .map(|l| l as usize)
+            .unwrap_or(self.output_page_size)
+            .min(self.output_page_size);
+
+        let stored = self.stored_outputs.borrow();
+        let output = stored
+            .get(token)
+            .with_context(|| format!("Unknown or expired output token: {}", token))?;
+        if offset > output.len() {
+            bail!("Offset {} is past the end ({} bytes)", offset, output.len());
+        }
+
+        let end = char_boundary(output, offset + length);
+        Ok(json!({
+            "output": String::from_utf8_lossy(&output[offset..end]),
+            "offset": offset,
+            // None once the end of the output is reached
+            "next_offset": (end < output.len()).then_some(end),
+            "total_bytes": output.len()
+        }))
+    }
+
+    fn start_job(&mut self, args: &Value) -> Result<Value> {
+        let command = args
+            .get("command")
+            .and_then(|c| c.as_str())
+            .context("Missing 'command' parameter")?;
+
+        self.command_policy.check(command)?;
+
+        let cwd = args.get("cwd").and_then(|c| c.as_str()).unwrap_or(".");
+
+        let safe_cwd = self.safe_cwd(cwd)?;
+
+        let cmd = self.shell_command(Some(command))?;
+
+        self.spawn_job(command.to_string(), cmd, safe_cwd)
+    }
+
+    /// Run `cmd` in the background as a job, collecting its output
+    fn spawn_job(&mut self, label: String, mut cmd: Command, cwd: PathBuf) -> Result<Value> {
+        let running = self
+            .jobs
+            .values()
+            .filter(|job| !job.state.lock().unwrap_or_else(|e| e.into_inner()).finished)
+            .count();
+        if running >= self.max_jobs {
+            bail!(
+                "Too many running jobs ({}); wait for one to finish or kill it",
+                running
+            );
+        }
+
+        let state = Arc::new(Mutex::new(JobState::default()));
+        let limit = self.max_output_size;
+
+        let pid = self.runtime.block_on(async {
+            // Own process group so kill_job reaches everything the command spawns
+            let mut child = cmd
+                .current_dir(&cwd)
+                .stdin(ProcessStdio::null())
+                .stdout(ProcessStdio::piped())
+                .stderr(ProcessStdio::piped())
+                .process_group(0)
+                .spawn()
+                .context("Failed to start job")?;
+            let pid = child.id();
+
+            let stdout = child
+                .stdout
+                .take()
+                .map(|out| tokio::spawn(collect_job_output(out, state.clone(), limit)));
+            let stderr = child
+                .stderr
+                .take()
+                .map(|err| tokio::spawn(collect_job_output(err, state.clone(), limit)));
+
+            let state = state.clone();
+            tokio::spawn(async move {
+                let status = child.wait().await;
+                // Drain remaining output before reporting the job finished
+                for reader in [stdout, stderr].into_iter().flatten() {
+                    let _ = reader.await;
+                }
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                state.exit_code = status.ok().and_then(|s| s.code());
+                state.finished = true;
+            });
+
+            Ok::<_, anyhow::Error>(pid)
+        })?;
+
+        let id = self.next_job;
+        self.next_job += 1;
+        self.jobs.insert(
+            id,
+            Job {
+                command: label,
+                cwd,
+                pid,
+                started_at: chrono::Utc::now(),
+                state,
+            },
+        );
+
+        Ok(json!({
+            "job_id": format!("job-{}", id),
+            "pid": pid
+        }))
+    }
+
+    /// Look up a job by its "job-N" ID
+    fn job(&self, args: &Value) -> Result<(u64, &Job)> {
+        let job_id = args
+            .get("job_id")
+            .and_then(|j| j.as_str())
+            .context("Missing 'job_id' parameter")?;
+
+        job_id
+            .strip_prefix("job-")
+            .and_then(|id| id.parse::<u64>().ok())
+            .and_then(|id| Some((id, self.jobs.get(&id)?)))
+            .with_context(|| format!("Unknown job: {}", job_id))
+    }
+
+    fn job_summary(&self, id: u64, job: &Job) -> Value {
+        let state = job.state.lock().unwrap_or_else(|e| e.into_inner());
+        json!({
+            "job_id": format!("job-{}", id),
+            "command": job.command,
+            "cwd": job.cwd,
+            "pid": job.pid,
+            "started_at": job.started_at.to_rfc3339(),
+            "running": !state.finished,
+            "exit_code": state.exit_code,
+            "output_bytes": state.dropped + state.output.len() as u64
         })
     }
 
-    fn docker_run(&mut self, args: &Value) -> Result<String> {
+    fn job_status(&mut self, args: &Value) -> Result<Value> {
+        if args.get("job_id").is_none() {
+            let jobs: Vec<Value> = self
+                .jobs
+                .iter()
+                .map(|(id, job)| self.job_summary(*id, job))
+                .collect();
+            return Ok(json!(jobs));
+        }
+
+        let (id, job) = self.job(args)?;
+        Ok(self.job_summary(id, job))
+    }
+
+    fn job_output(&mut self, args: &Value) -> Result<Value> {
+        let offset = args.get("offset").and_then(|o| o.as_u64()).unwrap_or(0);
+
+        let (_, job) = self.job(args)?;
+        let state = job.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        let end = state.dropped + state.output.len() as u64;
+        // Output before `dropped` is gone; start from the oldest byte kept
+        let start = offset.clamp(state.dropped, end);
+        let available = &state.output[(start - state.dropped) as usize..];
+        // At most one page per call; next_offset continues from there
+        let output = &available[..char_boundary(available, self.output_page_size)];
+
+        Ok(json!({
+            "output": String::from_utf8_lossy(output),
+            "next_offset": start + output.len() as u64,
+            "remaining_bytes": available.len() - output.len(),
+            "skipped_bytes": state.dropped.saturating_sub(offset),
+            "running": !state.finished,
+            "exit_code": state.exit_code
+        }))
+    }
+
+    fn kill_job(&mut self, args: &Value) -> Result<Value> {
+        let signal = args
+            .get("signal")
+            .and_then(|s| s.as_str())
+            .unwrap_or("TERM");
+        if !["TERM", "KILL", "INT", "HUP"].contains(&signal) {
+            bail!("Unsupported signal: {}", signal);
+        }
+
+        let (id, job) = self.job(args)?;
+        if job.state.lock().unwrap_or_else(|e| e.into_inner()).finished {
+            return Ok(json!({
+                "job_id": format!("job-{}", id),
+                "signal": signal,
+                "sent": false,
+                "reason": "job already finished"
+            }));
+        }
+        let pid = job.pid.context("Job has no process ID")?;
+
+        self.runtime
+            .block_on(signal_process_group(pid, signal))
+            .context("Failed to signal job")?;
+
+        Ok(json!({
+            "job_id": format!("job-{}", id),
+            "signal": signal,
+            "sent": true
+        }))
+    }
+
+    fn open_session(&mut self, args: &Value) -> Result<Value> {
+        let cwd = args.get("cwd").and_then(|c| c.as_str()).unwrap_or(".");
+
+        let safe_cwd = self.safe_cwd(cwd)?;
+
+        if self.sessions.len() >= self.max_sessions {
+            bail!(
+                "Too many open sessions ({}); close one first",
+                self.sessions.len()
+            );
+        }
+
+        let session = self.runtime.block_on(async {
+            let mut cmd = self.shell_command(None)?;
+            cmd.current_dir(&safe_cwd)
+                .stdin(ProcessStdio::piped())
+                .stdout(ProcessStdio::piped())
+                .stderr(ProcessStdio::piped())
+                .process_group(0)
+                .kill_on_drop(true);
+
+            if let Some(env_obj) = args.get("env").and_then(|e| e.as_object()) {
+                for (key, value) in env_obj {
+                    if let Some(val_str) = value.as_str() {
+                        cmd.env(key, val_str);
+                    }
+                }
+            }
+
+            let mut child = cmd.spawn().context("Failed to start session shell")?;
+            Ok::<_, anyhow::Error>(Session {
+                stdin: child.stdin.take().context("Session shell has no stdin")?,
+                stdout: tokio::io::BufReader::new(
+                    child.stdout.take().context("Session shell has no stdout")?,
+                ),
+                stderr: tokio::io::BufReader::new(
+                    child.stderr.take().context("Session shell has no stderr")?,
+                ),
+                child,
+                commands: 0,
+            })
+        })?;
+
+        let id = self.next_session;
+        self.next_session += 1;
+        self.sessions.insert(id, session);
+
+        Ok(json!({
+            "session_id": format!("session-{}", id),
+            "cwd": safe_cwd
+        }))
+    }
+
+    /// Parse a "session-N" ID
+    fn session_id(&self, args: &Value) -> Result<u64> {
+        let session_id = args
+            .get("session_id")
+            .and_then(|s| s.as_str())
+            .context("Missing 'session_id' parameter")?;
+
+        session_id
+            .strip_prefix("session-")
+            .and_then(|id| id.parse::<u64>().ok())
+            .filter(|id| self.sessions.contains_key(id))
+            .with_context(|| format!("Unknown session: {}", session_id))
+    }
+
+    fn run_in_session(&mut self, args: &Value) -> Result<Value> {
+        let id = self.session_id(args)?;
+
+        let command = args
+            .get("command")
+            .and_then(|c| c.as_str())
+            .context("Missing 'command' parameter")?;
+
+        self.command_policy.check(command)?;
+
+        let timeout_secs = args
+            .get("timeout_secs")
+            .and_then(|t| t.as_u64())
+            .unwrap_or(60);
+
+        if command.trim().is_empty() {
+            bail!("Command is empty");
+        }
+
+        // A syntax error would make the session shell exit, so check first
+        let check = self.runtime.block_on(async {
+            timeout(
+                Duration::from_secs(5),
+                Command::new("sh").arg("-n").arg("-c").arg(command).output(),
+            )
+            .await
+            .context("Syntax check timed out")?
+            .context("Failed to check command syntax")
+        })?;
+        if !check.status.success() {
+            bail!(
+                "Syntax error: {}",
+                String::from_utf8_lossy(&check.stderr).trim()
+            );
+        }
+
+        let limit = self.max_output_size;
+        let session = self.sessions.get_mut(&id).context("Unknown session")?;
+        session.commands += 1;
+        let marker = format!("__REPLICANTE_SESSION_{}_{}__", id, session.commands);
+
+        // Braces keep cd/export effects in the session shell; stdin is a
+        // here-document or /dev/null so the command cannot swallow the
+        // marker lines
+        let redirect = match args.get("stdin").and_then(|s| s.as_str()) {
+            Some(input) => {
+                let delimiter = format!("{}_INPUT", marker);
+                if input.lines().any(|line| line == delimiter) {
+                    bail!("Input contains the reserved line {}", delimiter);
+                }
+                let mut input = input.to_string();
+                if !input.is_empty() && !input.ends_with('\n') {
+                    input.push('\n');
+                }
+                format!("<<'{}'\n{}{}", delimiter, input, delimiter)
+            }
+            None => "</dev/null".to_string(),
+        };
+        let script = format!(
+            "{{\n{}\n}} {}\n__replicante_status=$?\nprintf '\\n%s %d %s\\n' '{}' \"$__replicante_status\" \"$PWD\"\nprintf '\\n%s\\n' '{}' >&2\n",
+            command, redirect, marker, marker
+        );
+
+        let result = self.runtime.block_on(async {
+            use tokio::io::AsyncWriteExt;
+            session.stdin.write_all(script.as_bytes()).await?;
+            session.stdin.flush().await?;
+
+            timeout(Duration::from_secs(timeout_secs), async {
+                tokio::try_join!(
+                    read_until_marker(&mut session.stdout, &marker, limit),
+                    read_until_marker(&mut session.stderr, &marker, limit)
+                )
+            })
+            .await
+            .context("Command timed out; the session was closed")?
+        });
+
+        let ((stdout, status), (stderr, _)) = match result {
+            Ok(output) => output,
+            Err(e) => {
+                // The shell is stuck or gone and cannot be trusted any more
+                self.close(id);
+                return Err(e);
+            }
+        };
+
+        let mut fields = status.splitn(2, ' ');
+        let exit_code = fields.next().and_then(|code| code.parse().ok());
+        let cwd = fields.next().unwrap_or("");
+
+        let mut result = self.command_result(exit_code, &stdout, &stderr);
+        result["cwd"] = json!(cwd);
+        Ok(result)
+    }
+
+    fn close_session(&mut self, args: &Value) -> Result<Value> {
+        let id = self.session_id(args)?;
+        self.close(id);
+        Ok(json!({
+            "session_id": format!("session-{}", id),
+            "closed": true
+        }))
+    }
+
+    /// Kill a session shell and everything it started
+    fn close(&mut self, id: u64) {
+        if let Some(mut session) = self.sessions.remove(&id) {
+            self.runtime.block_on(async {
+                if let Some(pid) = session.child.id() {
+                    let _ = signal_process_group(pid, "KILL").await;
+                }
+                let _ = session.child.kill().await;
+            });
+        }
+    }
+
+    fn docker_run(&mut self, args: &Value) -> Result<Value> {
         let image = args
             .get("image")
             .and_then(|i| i.as_str())
@@ -447,26 +1252,17 @@ impl ShellMCPServer {
             cmd.arg(image);
 
             if let Some(command) = args.get("command").and_then(|c| c.as_str()) {
+                self.command_policy.check(command)?;
                 cmd.args(command.split_whitespace());
             }
 
             let output = timeout(Duration::from_secs(120), cmd.output()).await??;
 
-            let mut result = format!("Exit code: {}\n", output.status.code().unwrap_or(-1));
-            if !output.stdout.is_empty() {
-                result.push_str("Output:\n");
-                result.push_str(&self.truncate_output(&output.stdout));
-            }
-            if !output.stderr.is_empty() {
-                result.push_str("Errors:\n");
-                result.push_str(&self.truncate_output(&output.stderr));
-            }
-
-            Ok(result)
+            Ok(self.command_result(output.status.code(), &output.stdout, &output.stderr))
         })
     }
 
-    fn docker_ps(&mut self, args: &Value) -> Result<String> {
+    fn docker_ps(&mut self, args: &Value) -> Result<Value> {
         self.runtime.block_on(async {
             let mut cmd = Command::new("docker");
             cmd.arg("ps").arg("--format").arg("json");
@@ -478,7 +1274,7 @@ impl ShellMCPServer {
             let output = timeout(Duration::from_secs(10), cmd.output()).await??;
 
             let mut containers = Vec::new();
-            let stdout_str = self.truncate_output(&output.stdout);
+            let stdout_str = self.capped_output(&output.stdout);
             for line in stdout_str.lines() {
                 if !line.trim().is_empty()
                     && let Ok(container) = serde_json::from_str::<Value>(line)
@@ -487,11 +1283,11 @@ impl ShellMCPServer {
                 }
             }
 
-            Ok(serde_json::to_string_pretty(&json!(containers))?)
+            Ok(json!(containers))
         })
     }
 
-    fn docker_logs(&mut self, args: &Value) -> Result<String> {
+    fn docker_logs(&mut self, args: &Value) -> Result<Value> {
         let container = args
             .get("container")
             .and_then(|c| c.as_str())
@@ -509,19 +1305,11 @@ impl ShellMCPServer {
 
             let output = timeout(Duration::from_secs(30), cmd.output()).await??;
 
-            let mut result = String::new();
-            if !output.stdout.is_empty() {
-                result.push_str(&self.truncate_output(&output.stdout));
-            }
-            if !output.stderr.is_empty() {
-                result.push_str(&self.truncate_output(&output.stderr));
-            }
-
-            Ok(result)
+            Ok(self.command_result(output.status.code(), &output.stdout, &output.stderr))
         })
     }
 
-    fn docker_exec(&mut self, args: &Value) -> Result<String> {
+    fn docker_exec(&mut self, args: &Value) -> Result<Value> {
         let container = args
             .get("container")
             .and_then(|c| c.as_str())
@@ -532,6 +1320,8 @@ impl ShellMCPServer {
             .and_then(|c| c.as_str())
             .context("Missing 'command' parameter")?;
 
+        self.command_policy.check(command)?;
+
         self.runtime.block_on(async {
             let mut cmd = Command::new("docker");
             cmd.arg("exec").arg(container);
@@ -539,21 +1329,11 @@ impl ShellMCPServer {
 
             let output = timeout(Duration::from_secs(60), cmd.output()).await??;
 
-            let mut result = format!("Exit code: {}\n", output.status.code().unwrap_or(-1));
-            if !output.stdout.is_empty() {
-                result.push_str("Output:\n");
-                result.push_str(&self.truncate_output(&output.stdout));
-            }
-            if !output.stderr.is_empty() {
-                result.push_str("Errors:\n");
-                result.push_str(&self.truncate_output(&output.stderr));
-            }
-
-            Ok(result)
+            Ok(self.command_result(output.status.code(), &output.stdout, &output.stderr))
         })
     }
 
-    fn docker_stop(&mut self, args: &Value) -> Result<String> {
+    fn docker_stop(&mut self, args: &Value) -> Result<Value> {
         let container = args
             .get("container")
             .and_then(|c| c.as_str())
@@ -567,7 +1347,7 @@ impl ShellMCPServer {
             .await??;
 
             if output.status.success() {
-                Ok(format!("Container {} stopped", container))
+                Ok(json!({"container": container, "stopped": true}))
             } else {
                 Err(anyhow::anyhow!(
                     "Failed to stop container: {}",
@@ -577,7 +1357,7 @@ impl ShellMCPServer {
         })
     }
 
-    fn docker_pull(&mut self, args: &Value) -> Result<String> {
+    fn docker_pull(&mut self, args: &Value) -> Result<Value> {
         let image = args
             .get("image")
             .and_then(|i| i.as_str())
@@ -591,7 +1371,7 @@ impl ShellMCPServer {
             .await??;
 
             if output.status.success() {
-                Ok(format!("Successfully pulled {}", image))
+                Ok(json!({"image": image, "pulled": true}))
             } else {
                 Err(anyhow::anyhow!(
                     "Failed to pull image: {}",
@@ -601,81 +1381,289 @@ impl ShellMCPServer {
         })
     }
 
-    /// Create error response
-    fn error_response(&self, request_id: Option<&Value>, code: i64, message: &str) -> Value {
-        json!({
-            "jsonrpc": "2.0",
-            "id": request_id,
-            "error": {
-                "code": code,
-                "message": message
+    fn docker_build(&mut self, args: &Value) -> Result<Value> {
+        let context = args.get("context").and_then(|c| c.as_str()).unwrap_or(".");
+
+        let safe_context = self.safe_cwd(context)?;
+        if !safe_context.is_dir() {
+            bail!("Build context is not a directory: {}", context);
+        }
+
+        let mut cmd = Command::new("docker");
+        cmd.arg("build").arg("--progress=plain");
+        let mut label = "docker build".to_string();
+
+        if let Some(tag) = args.get("tag").and_then(|t| t.as_str()) {
+            cmd.arg("-t").arg(tag);
+            label.push_str(&format!(" -t {}", tag));
+        }
+
+        if let Some(dockerfile) = args.get("dockerfile").and_then(|d| d.as_str()) {
+            let safe_dockerfile = self.safe_cwd(dockerfile)?;
+            cmd.arg("-f").arg(safe_dockerfile);
+            label.push_str(&format!(" -f {}", dockerfile));
+        }
+
+        if let Some(build_args) = args.get("build_args").and_then(|b| b.as_object()) {
+            for (key, value) in build_args {
+                if let Some(val_str) = value.as_str() {
+                    cmd.arg("--build-arg").arg(format!("{}={}", key, val_str));
+                }
             }
-        })
-    }
-}
+        }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let verbose = args.verbose;
+        if let Some(target) = args.get("target").and_then(|t| t.as_str()) {
+            cmd.arg("--target").arg(target);
+        }
 
-    if verbose {
-        eprintln!("[Shell MCP] Starting server...");
+        if args
+            .get("no_cache")
+            .and_then(|n| n.as_bool())
+            .unwrap_or(false)
+        {
+            cmd.arg("--no-cache");
+        }
+
+        cmd.arg(".");
+        label.push_str(&format!(" {}", context));
+
+        self.spawn_job(label, cmd, safe_context)
     }
 
-    let mut server = ShellMCPServer::new(args)?;
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let reader = BufReader::new(stdin.lock());
+    /// `docker compose` for a compose file inside the workspace, run from its directory
+    fn compose_command(&self, args: &Value) -> Result<Command> {
+        let file = args
+            .get("file")
+            .and_then(|f| f.as_str())
+            .unwrap_or("docker-compose.yml");
 
-    if verbose {
-        eprintln!("[Shell MCP] Ready for requests");
+        let safe_file = self.safe_cwd(file)?;
+        if !safe_file.is_file() {
+            bail!("Compose file not found: {}", file);
+        }
+
+        let mut cmd = Command::new("docker");
+        cmd.arg("compose").arg("-f").arg(&safe_file);
+        if let Some(project) = args.get("project").and_then(|p| p.as_str()) {
+            cmd.arg("-p").arg(project);
+        }
+        if let Some(dir) = safe_file.parent() {
+            cmd.current_dir(dir);
+        }
+
+        Ok(cmd)
+    }
+
+    /// Service names from the optional 'services' parameter
+    fn compose_services(args: &Value) -> Vec<&str> {
+        args.get("services")
+            .and_then(|s| s.as_array())
+            .map(|services| services.iter().filter_map(|s| s.as_str()).collect())
+            .unwrap_or_default()
     }
 
-    for line in reader.lines() {
-        let line = line?;
-        if line.trim().is_empty() {
-            continue;
+    fn compose_up(&mut self, args: &Value) -> Result<Value> {
+        let mut cmd = self.compose_command(args)?;
+        cmd.arg("up").arg("-d");
+
+        if args.get("build").and_then(|b| b.as_bool()).unwrap_or(false) {
+            cmd.arg("--build");
         }
+        cmd.args(Self::compose_services(args));
+
+        self.runtime.block_on(async {
+            // Pulling and building images can take a while
+            let output = timeout(Duration::from_secs(600), cmd.output()).await??;
 
-        if verbose {
-            eprintln!("[Shell MCP] Received: {}", line);
+            Ok(self.command_result(output.status.code(), &output.stdout, &output.stderr))
+        })
+    }
+
+    fn compose_down(&mut self, args: &Value) -> Result<Value> {
+        let mut cmd = self.compose_command(args)?;
+        cmd.arg("down");
+
+        let remove_volumes = args
+            .get("volumes")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if remove_volumes {
+            cmd.arg("--volumes");
         }
 
-        let request: Value = serde_json::from_str(&line)
-            .with_context(|| format!("Failed to parse JSON: {}", line))?;
+        self.runtime.block_on(async {
+            let output = timeout(Duration::from_secs(120), cmd.output()).await??;
 
-        match server.handle_request(request) {
-            Ok(Some(response)) => {
-                let response_str = serde_json::to_string(&response)?;
-                writeln!(stdout, "{}", response_str)?;
-                stdout.flush()?;
-                if verbose {
-                    eprintln!("[Shell MCP] Sent response: {}", response_str);
-                }
+            if output.status.success() {
+                Ok(json!({"stopped": true, "volumes_removed": remove_volumes}))
+            } else {
+                Err(anyhow::anyhow!(
+                    "Failed to stop compose services: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                ))
             }
-            Ok(None) => {
-                if verbose {
-                    eprintln!("[Shell MCP] No response needed");
-                }
+        })
+    }
+
+    fn compose_ps(&mut self, args: &Value) -> Result<Value> {
+        let mut cmd = self.compose_command(args)?;
+        cmd.arg("ps").arg("--all").arg("--format").arg("json");
+
+        self.runtime.block_on(async {
+            let output = timeout(Duration::from_secs(30), cmd.output()).await??;
+            if !output.status.success() {
+                bail!(
+                    "Failed to list compose containers: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
             }
-            Err(e) => {
-                eprintln!("[Shell MCP] Error handling request: {}", e);
-                let error_response = json!({
-                    "jsonrpc": "2.0",
-                    "id": null,
-                    "error": {
-                        "code": -32603,
-                        "message": format!("Internal error: {}", e)
-                    }
-                });
-                writeln!(stdout, "{}", serde_json::to_string(&error_response)?)?;
-                stdout.flush()?;
+
+            // Older compose versions print one JSON array, newer ones one object per line
+            let stdout_str = self.capped_output(&output.stdout);
+            let containers = match serde_json::from_str::<Value>(&stdout_str) {
+                Ok(Value::Array(containers)) => containers,
+                _ => stdout_str
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+                    .collect(),
+            };
+
+            Ok(json!(containers))
+        })
+    }
+
+    fn compose_logs(&mut self, args: &Value) -> Result<Value> {
+        let mut cmd = self.compose_command(args)?;
+
+        let tail = args.get("tail").and_then(|t| t.as_u64()).unwrap_or(100);
+        cmd.arg("logs")
+            .arg("--no-color")
+            .arg("--tail")
+            .arg(tail.to_string());
+        cmd.args(Self::compose_services(args));
+
+        self.runtime.block_on(async {
+            let output = timeout(Duration::from_secs(30), cmd.output()).await??;
+
+            Ok(self.command_result(output.status.code(), &output.stdout, &output.stderr))
+        })
+    }
+}
+
+/// Append everything read from a job's stdout or stderr to its shared output
+async fn collect_job_output(
+    mut reader: impl AsyncRead + Unpin,
+    state: Arc<Mutex<JobState>>,
+    limit: usize,
+) {
+    let mut buffer = [0u8; 8192];
+    while let Ok(read) = reader.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .append(&buffer[..read], limit);
+    }
+}
+
+/// Largest end <= `end` (clamped to the output) that does not split a UTF-8 character
+/// Have the child of `cmd` move itself into `cgroup` between fork and exec.
+/// `cgroup.procs` is opened here, as the child may only make plain system
+/// calls.
+fn join_cgroup(cmd: &mut Command, cgroup: &Path) -> Result<()> {
+    let procs = std::fs::OpenOptions::new()
+        .write(true)
+        .open(cgroup.join("cgroup.procs"))
+        .with_context(|| {
+            format!(
+                "Failed to open {procs:?}",
+                procs = cgroup.join("cgroup.procs")
+            )
+        })?;
+    // SAFETY: the closure only makes a write system call on an already open
+    // file, which is safe to run between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            use std::io::Write;
+            // Writing 0 moves the writing process itself
+            (&procs).write_all(b"0")
+        });
+    }
+    Ok(())
+}
+
+fn char_boundary(output: &[u8], end: usize) -> usize {
+    let mut end = end.min(output.len());
+    // Continuation bytes look like 0b10xxxxxx; a character spans at most 4 bytes
+    for _ in 0..3 {
+        if end == 0 || end == output.len() || output[end] & 0xC0 != 0x80 {
+            break;
+        }
+        end -= 1;
+    }
+    end
+}
+
+/// Send `signal` (e.g. "TERM") to the process group led by `pid`
+async fn signal_process_group(pid: u32, signal: &str) -> Result<()> {
+    let output = timeout(
+        Duration::from_secs(5),
+        Command::new("kill")
+            .arg(format!("-{}", signal))
+            .arg("--")
+            .arg(format!("-{}", pid))
+            .output(),
+    )
+    .await??;
+
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Read a session stream up to the marker line, returning the output before
+/// it and whatever follows the marker on its line
+async fn read_until_marker(
+    reader: &mut tokio::io::BufReader<impl AsyncRead + Unpin>,
+    marker: &str,
+    limit: usize,
+) -> Result<(Vec<u8>, String)> {
+    use tokio::io::AsyncBufReadExt;
+
+    let mut output = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            bail!("Session shell exited");
+        }
+        if let Some(rest) = line.strip_prefix(marker.as_bytes()) {
+            // Drop the newline printed before the marker
+            if output.last() == Some(&b'\n') {
+                output.pop();
             }
+            return Ok((output, String::from_utf8_lossy(rest).trim().to_string()));
+        }
+        // Keep one byte past the limit so the output is reported as capped
+        if output.len() <= limit {
+            output.extend_from_slice(&line);
         }
     }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let verbose = args.verbose;
 
     if verbose {
-        eprintln!("[Shell MCP] Server shutting down");
+        eprintln!("[Shell MCP] Starting server...");
     }
-    Ok(())
+
+    let server = ShellMCPServer::new(args)?;
+    McpServer::new("shell-mcp-server", "Shell MCP", server)
+        .verbose(verbose)
+        .run()
 }
@@ -0,0 +1,433 @@
+#!/usr/bin/env rust
+//! Vector Store MCP Server - a semantic knowledge base shared between agents
+//!
+//! Provides tools: store_embedding, semantic_search, delete
+//!
+//! Entries live in a SQLite file, so every agent whose server points at the
+//! same database shares them. Callers may supply their own embeddings; text
+//! without one is embedded locally with feature hashing, which finds entries
+//! sharing words but knows nothing of synonyms.
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use replicante::mcp_server::{
+    McpServer, Param, Tool, ToolContext, ToolHandler, ToolOutput, UnknownTool,
+};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Dimensions of the locally computed text embeddings
+const HASHED_DIMENSIONS: usize = 256;
+
+/// Namespace used when a tool call names none
+const DEFAULT_NAMESPACE: &str = "default";
+
+/// Command-line arguments for the vector store MCP server
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// SQLite database holding the index; agents sharing it share a knowledge base
+    #[arg(long, env = "VECTOR_STORE_PATH", default_value = "vector-store.db")]
+    database: PathBuf,
+
+    /// Enable verbose output
+    #[arg(long, env = "MCP_VERBOSE")]
+    verbose: bool,
+
+    /// Maximum number of results a search may return
+    #[arg(long, env = "MAX_SEARCH_RESULTS", default_value = "50")]
+    max_search_results: usize,
+}
+
+/// Vector Store MCP Server implementation
+struct VectorStoreMCPServer {
+    conn: Connection,
+    max_search_results: usize,
+}
+
+impl VectorStoreMCPServer {
+    fn new(args: Args) -> Result<Self> {
+        let conn = Connection::open(&args.database).with_context(|| {
+            format!(
+                "Failed to open vector store {database:?}",
+                database = args.database
+            )
+        })?;
+
+        // Other agents' servers may write to the same file
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS embeddings (
+                namespace TEXT NOT NULL,
+                id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                metadata TEXT NOT NULL,
+                vector BLOB NOT NULL,
+                dimensions INTEGER NOT NULL,
+                hashed BOOLEAN NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (namespace, id)
+            )",
+            [],
+        )
+        .context("Failed to create embeddings table")?;
+
+        if args.verbose {
+            eprintln!(
+                "[Vector Store MCP] Database: {database:?}",
+                database = args.database
+            );
+        }
+
+        Ok(Self {
+            conn,
+            max_search_results: args.max_search_results,
+        })
+    }
+}
+
+impl ToolHandler for VectorStoreMCPServer {
+    fn tools(&self) -> Vec<Tool> {
+        vec![
+            Tool::new(
+                "store_embedding",
+                "Store text in the shared knowledge base, with its embedding or one computed locally; an existing id is replaced",
+            )
+            .param(Param::string("text", "The text to remember").required())
+            .param(Param::string("id", "Entry id; generated when omitted"))
+            .param(Param::array(
+                "embedding",
+                "Embedding vector; computed from the text when omitted",
+                "number",
+            ))
+            .param(Param::object(
+                "metadata",
+                "Arbitrary JSON returned with search results",
+            ))
+            .param(Param::string("namespace", "Collection to store in").default(DEFAULT_NAMESPACE)),
+            Tool::new(
+                "semantic_search",
+                "Find the stored entries most similar to a query text or embedding",
+            )
+            .param(Param::string("query", "Text to search for"))
+            .param(Param::array(
+                "embedding",
+                "Query embedding, instead of query text",
+                "number",
+            ))
+            .param(Param::integer("top_k", "Number of results").default(5))
+            .param(Param::number("min_score", "Minimum cosine similarity").default(0.0))
+            .param(Param::string("namespace", "Collection to search").default(DEFAULT_NAMESPACE)),
+            Tool::new("delete", "Delete entries by id, or a whole namespace")
+                .param(Param::array("ids", "Entry ids to delete", "string"))
+                .param(Param::boolean("all", "Delete every entry in the namespace").default(false))
+                .param(
+                    Param::string("namespace", "Collection to delete from")
+                        .default(DEFAULT_NAMESPACE),
+                ),
+        ]
+    }
+
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        _context: &ToolContext,
+    ) -> Result<ToolOutput> {
+        let text = match name {
+            "store_embedding" => self.store_embedding(arguments),
+            "semantic_search" => self.semantic_search(arguments),
+            "delete" => self.delete(arguments),
+            _ => Err(UnknownTool(name.to_string()).into()),
+        }?;
+        Ok(ToolOutput::text(text))
+    }
+}
+
+impl VectorStoreMCPServer {
+    fn store_embedding(&mut self, args: &Value) -> Result<String> {
+        let text = args
+            .get("text")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'text' parameter"))?;
+        let namespace = namespace(args);
+        let id = args
+            .get("id")
+            .and_then(|i| i.as_str())
+            .map(|i| i.to_string())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let metadata = args.get("metadata").cloned().unwrap_or_else(|| json!({}));
+
+        let (vector, hashed) = match args.get("embedding") {
+            Some(embedding) => (parse_embedding(embedding)?, false),
+            None => (hashed_embedding(text), true),
+        };
+
+        // Similarity is only meaningful between vectors of the same kind
+        let existing: Option<(usize, bool)> = self
+            .conn
+            .query_row(
+                "SELECT dimensions, hashed FROM embeddings WHERE namespace = ?1 AND id != ?2 LIMIT 1",
+                params![namespace, id],
+                |row| Ok((row.get::<_, i64>(0)? as usize, row.get(1)?)),
+            )
+            .optional()?;
+        if let Some((dimensions, existing_hashed)) = existing
+            && (dimensions != vector.len() || existing_hashed != hashed)
+        {
+            bail!(
+                "Namespace '{namespace}' holds {existing_kind} embeddings of {dimensions} dimensions; use another namespace for {new_kind} embeddings of {new_dimensions} dimensions",
+                existing_kind = kind(existing_hashed),
+                new_kind = kind(hashed),
+                new_dimensions = vector.len()
+            );
+        }
+
+        self.conn.execute(
+            "INSERT INTO embeddings (namespace, id, text, metadata, vector, dimensions, hashed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (namespace, id) DO UPDATE SET
+                text = excluded.text,
+                metadata = excluded.metadata,
+                vector = excluded.vector,
+                dimensions = excluded.dimensions,
+                hashed = excluded.hashed,
+                updated_at = CURRENT_TIMESTAMP",
+            params![
+                namespace,
+                id,
+                text,
+                metadata.to_string(),
+                vector_to_blob(&vector),
+                vector.len() as i64,
+                hashed
+            ],
+        )?;
+
+        Ok(json!({
+            "id": id,
+            "namespace": namespace,
+            "dimensions": vector.len(),
+            "embedding": kind(hashed)
+        })
+        .to_string())
+    }
+
+    fn semantic_search(&mut self, args: &Value) -> Result<String> {
+        let namespace = namespace(args);
+        let query = match (
+            args.get("embedding"),
+            args.get("query").and_then(|q| q.as_str()),
+        ) {
+            (Some(embedding), _) => parse_embedding(embedding)?,
+            (None, Some(text)) => hashed_embedding(text),
+            (None, None) => bail!("Provide a 'query' text or an 'embedding'"),
+        };
+        let top_k = args
+            .get("top_k")
+            .and_then(|k| k.as_u64())
+            .unwrap_or(5)
+            .min(self.max_search_results as u64) as usize;
+        let min_score = args
+            .get("min_score")
+            .and_then(|s| s.as_f64())
+            .unwrap_or(0.0);
+
+        let mut statement = self
+            .conn
+            .prepare("SELECT id, text, metadata, vector FROM embeddings WHERE namespace = ?1")?;
+        let rows = statement.query_map(params![namespace], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+            ))
+        })?;
+
+        let mut matches = Vec::new();
+        for row in rows {
+            let (id, text, metadata, blob) = row?;
+            let vector = blob_to_vector(&blob);
+            if vector.len() != query.len() {
+                bail!(
+                    "Query has {query_dimensions} dimensions but namespace '{namespace}' holds {dimensions}-dimension embeddings",
+                    query_dimensions = query.len(),
+                    dimensions = vector.len()
+                );
+            }
+            let score = cosine_similarity(&query, &vector);
+            if score >= min_score {
+                matches.push((score, id, text, metadata));
+            }
+        }
+
+        matches.sort_by(|a, b| b.0.total_cmp(&a.0));
+        matches.truncate(top_k);
+
+        let results: Vec<Value> = matches
+            .into_iter()
+            .map(|(score, id, text, metadata)| {
+                json!({
+                    "id": id,
+                    "score": (score * 10000.0).round() / 10000.0,
+                    "text": text,
+                    "metadata": serde_json::from_str::<Value>(&metadata).unwrap_or(Value::Null)
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "namespace": namespace,
+            "results": results
+        })
+        .to_string())
+    }
+
+    fn delete(&mut self, args: &Value) -> Result<String> {
+        let namespace = namespace(args);
+        let all = args.get("all").and_then(|a| a.as_bool()).unwrap_or(false);
+
+        let deleted = match args.get("ids").and_then(|i| i.as_array()) {
+            Some(_) if all => bail!("Use either 'ids' or 'all', not both"),
+            Some(ids) => {
+                let mut deleted = 0;
+                for id in ids.iter().filter_map(|id| id.as_str()) {
+                    deleted += self.conn.execute(
+                        "DELETE FROM embeddings WHERE namespace = ?1 AND id = ?2",
+                        params![namespace, id],
+                    )?;
+                }
+                deleted
+            }
+            None if all => self.conn.execute(
+                "DELETE FROM embeddings WHERE namespace = ?1",
+                params![namespace],
+            )?,
+            None => bail!("Provide 'ids' to delete, or 'all': true for the whole namespace"),
+        };
+
+        Ok(json!({
+            "namespace": namespace,
+            "deleted": deleted
+        })
+        .to_string())
+    }
+}
+
+/// The namespace named in a tool call, or the default one
+fn namespace(args: &Value) -> String {
+    args.get("namespace")
+        .and_then(|n| n.as_str())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(DEFAULT_NAMESPACE)
+        .to_string()
+}
+
+/// How an embedding was produced, as reported to agents
+fn kind(hashed: bool) -> &'static str {
+    if hashed { "hashed" } else { "provided" }
+}
+
+/// Parse a caller-supplied embedding
+fn parse_embedding(value: &Value) -> Result<Vec<f32>> {
+    let values = value
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("'embedding' must be an array of numbers"))?;
+    if values.is_empty() {
+        bail!("'embedding' must not be empty");
+    }
+    values
+        .iter()
+        .map(|v| {
+            v.as_f64()
+                .filter(|f| f.is_finite())
+                .map(|f| f as f32)
+                .ok_or_else(|| anyhow::anyhow!("'embedding' must contain only finite numbers"))
+        })
+        .collect()
+}
+
+/// Embed text by hashing its lowercased words and word pairs into a fixed
+/// number of signed buckets, normalized to unit length
+fn hashed_embedding(text: &str) -> Vec<f32> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let mut vector = vec![0f32; HASHED_DIMENSIONS];
+    let mut add = |feature: &str, weight: f32| {
+        let hash = fnv1a(feature.as_bytes());
+        let bucket = (hash % HASHED_DIMENSIONS as u64) as usize;
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign * weight;
+    };
+
+    for word in &words {
+        add(word, 1.0);
+    }
+    for pair in words.windows(2) {
+        add(
+            &format!("{first} {second}", first = pair[0], second = pair[1]),
+            0.5,
+        );
+    }
+
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+/// 64-bit FNV-1a, stable across builds unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Cosine similarity, 0 when either vector is all zeros
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let (mut dot, mut norm_a, mut norm_b) = (0f64, 0f64, 0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (f64::from(*x), f64::from(*y));
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn blob_to_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let verbose = args.verbose;
+
+    if verbose {
+        eprintln!("[Vector Store MCP] Starting server...");
+    }
+
+    let server = VectorStoreMCPServer::new(args)?;
+    McpServer::new("vector-store-mcp-server", "Vector Store MCP", server)
+        .verbose(verbose)
+        .run()
+}
@@ -0,0 +1,160 @@
+//! Time and randomness of the agent, swappable for deterministic runs.
+//! [`SystemClock`] reads the wall clock; [`MockClock`] starts at a fixed
+//! instant and only moves when the agent sleeps or a test advances it, so a
+//! run driven by a scripted LLM and mock servers records the same
+//! timestamps and durations every time. [`rng`] makes random choices
+//! repeatable the same way.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Where the agent, its state and its MCP health checks get the time
+#[async_trait]
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Pause the caller for `duration`. A mock clock moves forward instead,
+    /// so the agent's own waits drive its time.
+    async fn sleep(&self, duration: Duration);
+
+    /// Wait until the clock reads `deadline`, for background tasks that
+    /// follow the clock rather than drive it
+    async fn sleep_until(&self, deadline: DateTime<Utc>);
+
+    /// Time passed since `start`, or zero if it is in the future
+    fn since(&self, start: DateTime<Utc>) -> Duration {
+        (self.now() - start).to_std().unwrap_or_default()
+    }
+}
+
+/// The wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        let remaining = (deadline - self.now()).to_std().unwrap_or_default();
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+/// A clock that stands still until it is advanced
+#[derive(Debug)]
+pub struct MockClock {
+    now: watch::Sender<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: watch::Sender::new(start),
+        }
+    }
+
+    /// Move the clock forward, waking the tasks sleeping until then
+    pub fn advance(&self, duration: Duration) {
+        let duration = chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        self.now.send_modify(|now| {
+            *now = now
+                .checked_add_signed(duration)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC)
+        });
+    }
+}
+
+impl Default for MockClock {
+    /// Starts at the Unix epoch
+    fn default() -> Self {
+        Self::new(DateTime::UNIX_EPOCH)
+    }
+}
+
+#[async_trait]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+        // Let the tasks woken by the jump run before the caller goes on
+        tokio::task::yield_now().await;
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        let mut now = self.now.subscribe();
+        // The sender lives as long as `self`, so this only returns once due
+        let _ = now.wait_for(|now| *now >= deadline).await;
+    }
+}
+
+/// The wall clock, or a mock clock starting at `start`
+pub fn clock(start: Option<DateTime<Utc>>) -> Arc<dyn Clock> {
+    match start {
+        Some(start) => Arc::new(MockClock::new(start)),
+        None => Arc::new(SystemClock),
+    }
+}
+
+/// A random number generator seeded with `seed`, or from the OS if unset
+pub fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[tokio::test]
+    async fn test_mock_clock_moves_only_when_slept_or_advanced() {
+        let clock = Arc::new(MockClock::default());
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        let follower = tokio::spawn({
+            let clock = clock.clone();
+            async move {
+                clock
+                    .sleep_until(start + chrono::Duration::seconds(90))
+                    .await;
+                clock.now()
+            }
+        });
+        clock.sleep(Duration::from_secs(60)).await;
+        assert_eq!(clock.since(start), Duration::from_secs(60));
+        assert!(!follower.is_finished());
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(
+            follower.await.unwrap(),
+            start + chrono::Duration::seconds(90)
+        );
+        assert_eq!(
+            clock.since(clock.now() + chrono::Duration::seconds(1)),
+            Duration::ZERO
+        );
+
+        let draws = |seed| rng(Some(seed)).gen_range(0..u64::MAX);
+        assert_eq!(draws(7), draws(7));
+        assert_ne!(draws(7), draws(8));
+    }
+}
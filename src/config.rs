@@ -1,34 +1,186 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use schemars::r#gen::SchemaGenerator;
+use schemars::schema::{RootSchema, Schema};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::llm::LLMConfig;
+use crate::jsonrpc::{DEFAULT_MAX_MESSAGE_BYTES, Framing};
+use crate::llm::{self, LLMConfig};
+use crate::logging::LoggingConfig;
 use crate::mcp::MCPServerConfig;
+use crate::metrics::MetricsConfig;
+use crate::secrets::{self, SecretsConfig};
+use crate::state::CyclePhase;
+use crate::supervision::SupervisionConfig;
+use crate::wasm_tools::{self, WasmToolConfig};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct Config {
     pub agent: AgentConfig,
     pub llm: LLMConfig,
     pub mcp_servers: Vec<MCPServerConfig>,
     pub database_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<SecretsConfig>,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Supervisor the agent reports its tool calls to
+    #[serde(default)]
+    pub supervisor: SupervisionConfig,
+    /// Tools run in-process as sandboxed WASM modules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wasm_tools: Vec<WasmToolConfig>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct AgentConfig {
     pub id: Option<String>,
     pub log_level: Option<String>,
     pub initial_goals: Option<String>,
     #[serde(default = "default_reasoning_interval_secs")]
     pub reasoning_interval_secs: u64,
+    #[serde(default)]
+    pub tuning: TuningConfig,
 }
 
 fn default_reasoning_interval_secs() -> u64 {
     10
 }
 
+/// Budgets and thresholds of the reasoning loop, under `[agent.tuning]`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct TuningConfig {
+    /// Memory entries shown to the LLM each cycle
+    pub memory_summary_entries: usize,
+    /// Budget of the memory shown to the LLM, in tokens
+    pub memory_summary_tokens: usize,
+    /// Single memory values are truncated to this many tokens
+    pub memory_value_tokens: usize,
+    /// Recent decisions shown to the LLM each cycle
+    pub recent_decisions: usize,
+    /// Tools listed by name among the action formats; the rest are counted
+    pub prompt_tools_listed: usize,
+    /// File whose contents replace the generated action guidelines in the
+    /// prompt; re-read every cycle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guidelines_path: Option<String>,
+    /// Tool outputs larger than this are truncated before being stored, in
+    /// tokens
+    pub tool_output_max_tokens: usize,
+    /// Length string fields of a truncated tool output are cut to, in tokens
+    pub tool_output_field_tokens: usize,
+    /// Decisions reviewed when learning at the end of a cycle
+    pub learning_window: usize,
+    /// Hours of decision history searched for patterns
+    pub pattern_lookback_hours: i64,
+    /// Lowest confidence at which a learned pattern is considered
+    pub pattern_min_confidence: f64,
+    /// Confidence above which a learned pattern confirms the LLM's choice
+    pub learned_pattern_threshold: f64,
+    /// How long the `wait` action pauses, in seconds
+    pub wait_secs: u64,
+    /// Tools that must not run twice in a row with the same parameters,
+    /// as `server:tool` or `server:*`; a repeat reuses the previous result
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub no_repeat_tools: Vec<String>,
+    /// Reasoning cycle phases (observe, prompt, llm, parse, act, learn)
+    /// logged as slow when they take longer than this, in milliseconds.
+    /// Phases left out are never reported; `act` is left out by default as
+    /// it includes the `wait` action's pause.
+    pub slow_phase_ms: BTreeMap<String, u64>,
+}
+
+impl TuningConfig {
+    /// Whether `tool` is listed in `no_repeat_tools`
+    pub fn is_no_repeat(&self, tool: &str) -> bool {
+        self.no_repeat_tools
+            .iter()
+            .any(|entry| match entry.strip_suffix(":*") {
+                Some(server) => tool.split_once(':').is_some_and(|(s, _)| s == server),
+                None => entry == tool,
+            })
+    }
+}
+
+impl Default for TuningConfig {
+    fn default() -> Self {
+        Self {
+            memory_summary_entries: 20,
+            memory_summary_tokens: 2500,
+            memory_value_tokens: 250,
+            recent_decisions: 5,
+            prompt_tools_listed: 10,
+            guidelines_path: None,
+            tool_output_max_tokens: 1250,
+            tool_output_field_tokens: 250,
+            learning_window: 10,
+            pattern_lookback_hours: 24,
+            pattern_min_confidence: 0.7,
+            learned_pattern_threshold: 0.85,
+            wait_secs: 60,
+            no_repeat_tools: Vec::new(),
+            slow_phase_ms: BTreeMap::from(
+                [
+                    ("observe", 2000),
+                    ("prompt", 1000),
+                    ("llm", 60_000),
+                    ("parse", 100),
+                    ("learn", 2000),
+                ]
+                .map(|(phase, ms)| (phase.to_string(), ms)),
+            ),
+        }
+    }
+}
+
+/// How a config is assembled beyond its main file
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Files merged over the main one, in order
+    pub overlays: Vec<PathBuf>,
+    /// Entry of the `[profiles]` table to apply
+    pub profile: Option<String>,
+}
+
+/// Something in a config that would fail at runtime, found by [`Config::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Where in the config, e.g. "mcp_servers.nostr.command"
+    pub field: String,
+    /// What is wrong and how to fix it
+    pub message: String,
+}
+
+/// A setting that a reloaded config changes, found by
+/// [`Config::reloadable_changes`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigChange {
+    /// Dotted config key, e.g. "llm.model"
+    pub setting: &'static str,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
+        Self::load_with(&LoadOptions::default())
+    }
+
+    /// [`Config::load`], with overlays and a profile applied
+    pub fn load_with(options: &LoadOptions) -> Result<Self> {
         // Check for config path from environment variable or command line
         let config_path = std::env::var("CONFIG_FILE").unwrap_or_else(|_| {
             // Check command line arguments
@@ -43,13 +195,625 @@ impl Config {
 
         // Try to load from file
         if Path::new(&config_path).exists() {
-            let contents = fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&contents)?;
-            return Ok(config);
+            return Self::from_files(Path::new(&config_path), options);
         }
 
         // Fall back to default configuration
-        Ok(Self::default())
+        if options.overlays.is_empty() && options.profile.is_none() {
+            return Ok(Self::default());
+        }
+        let value = assemble_layers(toml::Value::try_from(Self::default())?, options)?;
+        Self::from_value(value, |name| std::env::var(name).ok())
+    }
+
+    /// Parse a config file, with the path in any error
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::from_files(path, &LoadOptions::default())
+    }
+
+    /// Parse a config file and the files it includes, apply the selected
+    /// profile and merge the overlays over it. `${VAR}` and
+    /// `${VAR:-default}` in string values are expanded from the environment
+    /// once everything is merged.
+    pub fn from_files(path: &Path, options: &LoadOptions) -> Result<Self> {
+        let value = assemble_layers(read_layer(path, &mut Vec::new())?, options)?;
+        Self::from_value(value, |name| std::env::var(name).ok())
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// [`Config::from_file`] without expanding environment references, for
+    /// configs that get written back out and should not capture secrets
+    pub fn from_file_unexpanded(path: &Path) -> Result<Self> {
+        read_layer(path, &mut Vec::new())?
+            .try_into()
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    fn from_value(mut value: toml::Value, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        // The secrets table says where `${secret:NAME}` comes from, so it
+        // can only use the environment
+        let secrets = match value
+            .as_table_mut()
+            .and_then(|table| table.remove("secrets"))
+        {
+            Some(mut table) => {
+                expand_env_vars(&mut table, "secrets", &|name: &str| {
+                    if name.starts_with("secret:") {
+                        anyhow::bail!("The [secrets] table cannot reference the secret store");
+                    }
+                    Ok(env(name))
+                })?;
+                let secrets: SecretsConfig = table.try_into().context("Invalid [secrets] table")?;
+                Some(secrets)
+            }
+            None => None,
+        };
+
+        let store = secrets.clone().unwrap_or_default().store()?;
+        expand_env_vars(
+            &mut value,
+            "",
+            &|name: &str| match name.strip_prefix("secret:") {
+                Some(secret) => store.get(secret),
+                None => Ok(env(name)),
+            },
+        )?;
+
+        let mut config: Self = value.try_into()?;
+        config.secrets = secrets;
+        if let Some(file) = &config.llm.api_key_file {
+            if config.llm.api_key.is_some() {
+                anyhow::bail!("llm.api_key and llm.api_key_file are both set; keep one");
+            }
+            config.llm.api_key = Some(
+                secrets::read_secret_file(Path::new(file))
+                    .context("Failed to read llm.api_key_file")?,
+            );
+        }
+        Ok(config)
+    }
+
+    /// JSON Schema of the agent config format, for editors and CI to check
+    /// configs against
+    pub fn json_schema() -> RootSchema {
+        let mut generator = SchemaGenerator::default();
+        let mut include = generator.subschema_for::<Vec<String>>();
+        if let Schema::Object(include) = &mut include {
+            include.metadata().description =
+                Some("Config files this one is merged over, relative to it".to_string());
+        }
+        let mut profiles = generator
+            .subschema_for::<BTreeMap<String, serde_json::Map<String, serde_json::Value>>>();
+        if let Schema::Object(profiles) = &mut profiles {
+            profiles.metadata().description = Some(
+                "Named sets of overrides, merged over the rest of the config when selected with --profile"
+                    .to_string(),
+            );
+        }
+        let mut schema = generator.into_root_schema_for::<Config>();
+        // `include` is consumed while the layers are read, before `Config`
+        schema
+            .schema
+            .object()
+            .properties
+            .insert("include".to_string(), include);
+        schema
+            .schema
+            .object()
+            .properties
+            .insert("profiles".to_string(), profiles);
+        schema
+    }
+
+    /// The settings a running agent picks up on reload (reasoning interval,
+    /// goals, log level, LLM model and tuning) that differ in `new`.
+    /// Everything else takes a restart.
+    pub fn reloadable_changes(&self, new: &Config) -> Vec<ConfigChange> {
+        let mut changes = Vec::new();
+        let mut compare = |setting, old: serde_json::Value, new: serde_json::Value| {
+            if old != new {
+                changes.push(ConfigChange { setting, old, new });
+            }
+        };
+        compare(
+            "agent.reasoning_interval_secs",
+            self.agent.reasoning_interval_secs.into(),
+            new.agent.reasoning_interval_secs.into(),
+        );
+        compare(
+            "agent.initial_goals",
+            self.agent.initial_goals.clone().into(),
+            new.agent.initial_goals.clone().into(),
+        );
+        compare(
+            "agent.log_level",
+            self.agent.log_level.clone().into(),
+            new.agent.log_level.clone().into(),
+        );
+        compare(
+            "llm.model",
+            self.llm.model.clone().into(),
+            new.llm.model.clone().into(),
+        );
+        compare(
+            "agent.tuning",
+            serde_json::to_value(&self.agent.tuning).unwrap_or_default(),
+            serde_json::to_value(&new.agent.tuning).unwrap_or_default(),
+        );
+        compare(
+            "logging",
+            serde_json::to_value(&self.logging).unwrap_or_default(),
+            serde_json::to_value(&new.logging).unwrap_or_default(),
+        );
+        changes
+    }
+
+    /// Check what parsing cannot: that MCP server commands can be run, the
+    /// LLM provider has credentials and the database can be written
+    pub fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        let mut problem =
+            |field: String, message: String| problems.push(ConfigProblem { field, message });
+
+        let provider = self.llm.provider.to_lowercase();
+        if !llm::PROVIDERS.contains(&provider.as_str()) {
+            problem(
+                "llm.provider".to_string(),
+                format!(
+                    "Unknown provider '{}'; expected one of: {}",
+                    self.llm.provider,
+                    llm::PROVIDERS.join(", ")
+                ),
+            );
+        } else if llm::resolve_api_key(&self.llm).is_none() {
+            let env_vars = llm::api_key_env_vars(&provider);
+            if !env_vars.is_empty() {
+                problem(
+                    "llm.api_key".to_string(),
+                    format!(
+                        "No API key for provider '{}'; set llm.api_key or {}",
+                        self.llm.provider,
+                        env_vars.join(" or ")
+                    ),
+                );
+            }
+        }
+
+        if provider == "scripted" {
+            match &self.llm.script {
+                Some(path) => {
+                    if let Err(e) = llm::ScriptedLLMProvider::from_file(Path::new(path)) {
+                        problem("llm.script".to_string(), format!("{e:#}"));
+                    }
+                }
+                None => problem(
+                    "llm.script".to_string(),
+                    "The scripted provider needs a script file".to_string(),
+                ),
+            }
+        }
+
+        if let Some(limits) = &self.llm.rate_limit {
+            let zero = [
+                ("requests_per_minute", limits.requests_per_minute == Some(0)),
+                ("tokens_per_minute", limits.tokens_per_minute == Some(0)),
+            ];
+            for (limit, _) in zero.into_iter().filter(|(_, zero)| *zero) {
+                problem(
+                    format!("llm.rate_limit.{limit}"),
+                    "A limit of 0 would block every LLM call".to_string(),
+                );
+            }
+        }
+
+        for phase in self.agent.tuning.slow_phase_ms.keys() {
+            if !CyclePhase::ALL.iter().any(|known| known.as_str() == phase) {
+                problem(
+                    format!("agent.tuning.slow_phase_ms.{phase}"),
+                    format!(
+                        "Unknown phase '{phase}'; expected one of: {phases}",
+                        phases = CyclePhase::ALL.map(CyclePhase::as_str).join(", ")
+                    ),
+                );
+            }
+        }
+
+        let mut names = HashSet::new();
+        for server in &self.mcp_servers {
+            let field = format!("mcp_servers.{}", server.name);
+            if !names.insert(server.name.as_str()) {
+                problem(
+                    format!("{field}.name"),
+                    "Duplicate server name; tool names are prefixed with it, so it must be unique"
+                        .to_string(),
+                );
+            }
+            if server.transport != "stdio" {
+                problem(
+                    format!("{field}.transport"),
+                    format!(
+                        "Unsupported transport '{}'; only \"stdio\" is available",
+                        server.transport
+                    ),
+                );
+            }
+            if let Err(message) = check_executable(&server.command) {
+                problem(format!("{field}.command"), message);
+            }
+            if let Some(cwd) = &server.cwd
+                && !Path::new(cwd).is_dir()
+            {
+                problem(
+                    format!("{field}.cwd"),
+                    format!("Directory {cwd} does not exist"),
+                );
+            }
+            if let Some(user) = &server.user
+                && let Err(e) = crate::mcp::resolve_user(user)
+            {
+                problem(format!("{field}.user"), format!("{e:#}"));
+            }
+        }
+
+        let mut wasm_names = HashSet::new();
+        for tool in &self.wasm_tools {
+            let field = format!("wasm_tools.{name}", name = tool.name);
+            if !wasm_names.insert(tool.name.as_str()) {
+                problem(
+                    format!("{field}.name"),
+                    "Duplicate WASM tool name".to_string(),
+                );
+            }
+            if !Path::new(&tool.module).is_file() {
+                problem(
+                    format!("{field}.module"),
+                    format!("File {module} does not exist", module = tool.module),
+                );
+            }
+        }
+        if !self.wasm_tools.is_empty() && names.contains(wasm_tools::SERVER_NAME) {
+            problem(
+                format!("mcp_servers.{name}.name", name = wasm_tools::SERVER_NAME),
+                "This server name is used by the WASM tools".to_string(),
+            );
+        }
+
+        if let Err(message) = check_writable(&self.database_path) {
+            problem("database_path".to_string(), message);
+        }
+
+        problems
+    }
+}
+
+/// Read one config layer: the file's own values merged over those of the
+/// files it lists in `include`, which are resolved relative to it. `stack`
+/// holds the files being read, to reject include cycles.
+fn read_layer(path: &Path, stack: &mut Vec<PathBuf>) -> Result<toml::Value> {
+    let canonical = fs::canonicalize(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    if stack.contains(&canonical) {
+        anyhow::bail!("Config file {} includes itself", path.display());
+    }
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let mut value: toml::Value = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+    let includes = match value
+        .as_table_mut()
+        .and_then(|table| table.remove("include"))
+    {
+        None => Vec::new(),
+        Some(toml::Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                toml::Value::String(include) => Ok(include),
+                _ => Err(anyhow::anyhow!(
+                    "include in {} must be a list of file paths",
+                    path.display()
+                )),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => anyhow::bail!("include in {} must be a list of file paths", path.display()),
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = toml::Value::Table(toml::Table::new());
+    stack.push(canonical);
+    for include in includes {
+        merge_layer(&mut merged, read_layer(&dir.join(include), stack)?);
+    }
+    stack.pop();
+    merge_layer(&mut merged, value);
+    Ok(merged)
+}
+
+/// Apply `options` to a main config layer. The selected profile goes over
+/// the main layer and under the overlays, and can be declared in any of them.
+fn assemble_layers(mut value: toml::Value, options: &LoadOptions) -> Result<toml::Value> {
+    let mut overlays = options
+        .overlays
+        .iter()
+        .map(|overlay| read_layer(overlay, &mut Vec::new()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut profiles = take_profiles(&mut value);
+    for overlay in &mut overlays {
+        merge_values(&mut profiles, take_profiles(overlay));
+    }
+    if let Some(name) = &options.profile {
+        let Some(profile) = profiles.get(name) else {
+            let available: Vec<&str> = profiles
+                .as_table()
+                .map(|table| table.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            if available.is_empty() {
+                anyhow::bail!("Profile {name} is not defined; the config has no [profiles]");
+            }
+            anyhow::bail!(
+                "Profile {name} is not defined; available profiles: {}",
+                available.join(", ")
+            );
+        };
+        merge_layer(&mut value, profile.clone());
+    }
+
+    for overlay in overlays {
+        merge_layer(&mut value, overlay);
+    }
+    Ok(value)
+}
+
+fn take_profiles(value: &mut toml::Value) -> toml::Value {
+    value
+        .as_table_mut()
+        .and_then(|table| table.remove("profiles"))
+        .unwrap_or_else(|| toml::Value::Table(toml::Table::new()))
+}
+
+/// Read any TOML config file the way agent configs are read: with its
+/// includes and, if one is named, a profile from its `[profiles]` table
+pub fn read_file_with_profile<T: DeserializeOwned>(
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<T> {
+    let options = LoadOptions {
+        overlays: Vec::new(),
+        profile: profile.map(str::to_string),
+    };
+    assemble_layers(read_layer(path, &mut Vec::new())?, &options)?
+        .try_into()
+        .with_context(|| format!("Failed to parse config file {}", path.display()))
+}
+
+/// Merge a config layer over `base`: tables merge key by key, `mcp_servers`
+/// entries merge with the base entry of the same name or are appended, and
+/// anything else in the overlay replaces what was there
+fn merge_layer(base: &mut toml::Value, mut overlay: toml::Value) {
+    let servers = overlay
+        .as_table_mut()
+        .and_then(|table| table.remove("mcp_servers"));
+    merge_values(base, overlay);
+
+    let (Some(base), Some(servers)) = (base.as_table_mut(), servers) else {
+        return;
+    };
+    match (base.get_mut("mcp_servers"), servers) {
+        (Some(toml::Value::Array(existing)), toml::Value::Array(servers)) => {
+            for server in servers {
+                let name = server.get("name").and_then(|name| name.as_str());
+                let matching = existing.iter_mut().find(|entry| {
+                    name.is_some() && entry.get("name").and_then(|n| n.as_str()) == name
+                });
+                match matching {
+                    Some(entry) => merge_values(entry, server),
+                    None => existing.push(server),
+                }
+            }
+        }
+        (_, servers) => {
+            base.insert("mcp_servers".to_string(), servers);
+        }
+    }
+}
+
+fn merge_values(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Expand `${...}` references in every string under `value`; `path` is the
+/// dotted location used in errors. `lookup` gets the variable name, or
+/// `secret:NAME` for secret store references.
+fn expand_env_vars(
+    value: &mut toml::Value,
+    path: &str,
+    lookup: &impl Fn(&str) -> Result<Option<String>>,
+) -> Result<()> {
+    match value {
+        toml::Value::String(s) => {
+            *s = expand_env_string(s, lookup).with_context(|| format!("In config value {path}"))?;
+        }
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                expand_env_vars(item, &format!("{path}[{i}]"), lookup)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, item) in table.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                expand_env_vars(item, &path, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replace `${VAR}` with the variable's value, failing if it is unset, and
+/// `${VAR:-default}` with the value or `default` when unset or empty;
+/// `${secret:NAME}` works the same against the secret store. `$${` is a
+/// literal `${`; any other `$` is left alone.
+fn expand_env_string(
+    input: &str,
+    lookup: &impl Fn(&str) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            output.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(after) = rest.strip_prefix("${") else {
+            output.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let end = after
+            .find('}')
+            .with_context(|| format!("Unterminated '${{' in '{input}'"))?;
+        let reference = &after[..end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        match name.strip_prefix("secret:") {
+            Some(secret) => {
+                if secret.is_empty()
+                    || !secret
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c))
+                {
+                    anyhow::bail!("Invalid secret name '{secret}' in '{input}'");
+                }
+            }
+            None => {
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    anyhow::bail!("Invalid environment variable name '{name}' in '{input}'");
+                }
+            }
+        }
+        match (lookup(name)?, default) {
+            (Some(value), Some(default)) if value.is_empty() => output.push_str(default),
+            (Some(value), _) => output.push_str(&value),
+            (None, Some(default)) => output.push_str(default),
+            (None, None) => match name.strip_prefix("secret:") {
+                Some(secret) => {
+                    anyhow::bail!("Secret {secret} was not found in the secret store")
+                }
+                None => anyhow::bail!(
+                    "Environment variable {name} is not set; set it or use ${{{name}:-default}}"
+                ),
+            },
+        }
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Check that `command` names an executable file, directly or on PATH
+fn check_executable(command: &str) -> Result<(), String> {
+    if command.contains(std::path::MAIN_SEPARATOR) {
+        let path = Path::new(command);
+        return if !path.exists() {
+            Err(format!("Command '{command}' does not exist"))
+        } else if !is_executable(path) {
+            Err(format!(
+                "Command '{command}' is not an executable file; check its permissions"
+            ))
+        } else {
+            Ok(())
+        };
+    }
+
+    let found = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).collect::<Vec<PathBuf>>())
+        .unwrap_or_default()
+        .into_iter()
+        .any(|dir| is_executable(&dir.join(command)));
+    if found {
+        Ok(())
+    } else {
+        Err(format!(
+            "Command '{command}' was not found on PATH; install it or use an absolute path"
+        ))
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+/// Check that the database at `path` can be opened for writing, or created
+fn check_writable(path: &str) -> Result<(), String> {
+    if path == ":memory:" {
+        return Ok(());
+    }
+
+    let path = Path::new(path);
+    if path.exists() {
+        return fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map(|_| ())
+            .map_err(|e| format!("Cannot write to {}: {e}", path.display()));
+    }
+
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    check_dir_writable(dir)
+}
+
+/// Check that files can be created in the directory `dir`
+pub(crate) fn check_dir_writable(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!(
+            "Directory {} does not exist; create it first",
+            dir.display()
+        ));
+    }
+    // Creating a file is the only reliable test of directory permissions
+    let probe = dir.join(format!(".replicante-write-test-{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) => Err(format!("Cannot create files in {}: {e}", dir.display())),
     }
 }
 
@@ -61,16 +825,20 @@ impl Default for Config {
                 log_level: Some("info".to_string()),
                 initial_goals: None,
                 reasoning_interval_secs: 10,
+                tuning: TuningConfig::default(),
             },
             llm: LLMConfig {
                 provider: std::env::var("LLM_PROVIDER").unwrap_or_else(|_| "anthropic".to_string()),
                 api_key: None, // Will be loaded from environment
+                api_key_file: None,
+                script: None,
                 model: std::env::var("LLM_MODEL")
                     .unwrap_or_else(|_| "claude-3-opus-20240229".to_string()),
                 temperature: Some(0.7),
                 max_tokens: Some(4000),
                 api_url: None,
                 timeout_secs: None, // Will use provider defaults
+                rate_limit: None,
             },
             mcp_servers: vec![
                 MCPServerConfig {
@@ -81,6 +849,11 @@ impl Default for Config {
                     retry_attempts: 3,
                     retry_delay_ms: 2000,
                     health_check_interval_secs: 60,
+                    max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+                    framing: Framing::Newline,
+                    env: BTreeMap::new(),
+                    cwd: None,
+                    user: None,
                 },
                 MCPServerConfig {
                     name: "filesystem".to_string(),
@@ -90,6 +863,11 @@ impl Default for Config {
                     retry_attempts: 5,
                     retry_delay_ms: 1000,
                     health_check_interval_secs: 30,
+                    max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+                    framing: Framing::Newline,
+                    env: BTreeMap::new(),
+                    cwd: None,
+                    user: None,
                 },
                 MCPServerConfig {
                     name: "http".to_string(),
@@ -99,6 +877,11 @@ impl Default for Config {
                     retry_attempts: 3,
                     retry_delay_ms: 1500,
                     health_check_interval_secs: 45,
+                    max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+                    framing: Framing::Newline,
+                    env: BTreeMap::new(),
+                    cwd: None,
+                    user: None,
                 },
                 MCPServerConfig {
                     name: "bitcoin".to_string(),
@@ -108,10 +891,20 @@ impl Default for Config {
                     retry_attempts: 3,
                     retry_delay_ms: 3000,
                     health_check_interval_secs: 90,
+                    max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+                    framing: Framing::Newline,
+                    env: BTreeMap::new(),
+                    cwd: None,
+                    user: None,
                 },
             ],
             database_path: std::env::var("DATABASE_PATH")
                 .unwrap_or_else(|_| "replicante.db".to_string()),
+            secrets: None,
+            logging: LoggingConfig::default(),
+            metrics: MetricsConfig::default(),
+            supervisor: SupervisionConfig::default(),
+            wasm_tools: Vec::new(),
         }
     }
 }
@@ -126,4 +919,409 @@ mod tests {
         assert!(config.agent.id.is_some());
         assert!(!config.mcp_servers.is_empty());
     }
+
+    #[test]
+    fn test_validate_reports_actionable_problems() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.llm.provider = "mock".to_string();
+        config.database_path = dir.path().join("agent.db").to_string_lossy().into_owned();
+        config.mcp_servers.truncate(2);
+        config.mcp_servers[0].command = "sh".to_string();
+        config.mcp_servers[1].command = "/bin/sh".to_string();
+        config.mcp_servers[1].cwd = Some(dir.path().to_string_lossy().into_owned());
+        assert_eq!(config.validate(), vec![]);
+
+        config.llm.provider = "gpt".to_string();
+        config.mcp_servers[0].command = "definitely-not-a-command".to_string();
+        config.mcp_servers[1].name = config.mcp_servers[0].name.clone();
+        config.mcp_servers[1].transport = "http".to_string();
+        config.mcp_servers[1].cwd = Some(dir.path().join("gone").to_string_lossy().into_owned());
+        config.mcp_servers[1].user = Some("no-such-replicante-user".to_string());
+        config
+            .agent
+            .tuning
+            .slow_phase_ms
+            .insert("thinking".to_string(), 500);
+        config.database_path = dir
+            .path()
+            .join("missing/agent.db")
+            .to_string_lossy()
+            .into_owned();
+
+        let fields: Vec<String> = config
+            .validate()
+            .into_iter()
+            .map(|problem| problem.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "llm.provider",
+                "agent.tuning.slow_phase_ms.thinking",
+                "mcp_servers.nostr.command",
+                "mcp_servers.nostr.name",
+                "mcp_servers.nostr.transport",
+                "mcp_servers.nostr.cwd",
+                "mcp_servers.nostr.user",
+                "database_path",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_file_names_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("broken.toml");
+        fs::write(&path, "[agent\n").unwrap();
+
+        let error = format!("{:#}", Config::from_file(&path).unwrap_err());
+        assert!(error.contains("broken.toml"), "{error}");
+    }
+
+    #[test]
+    fn test_parse_expands_env_vars() {
+        let env = |name: &str| match name {
+            "API_KEY" => Some("sk-test".to_string()),
+            "DATA_DIR" => Some("/var/lib/replicante".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let contents = r#"
+            database_path = "${DATA_DIR}/agent.db"
+
+            [agent]
+            id = "${AGENT_ID:-agent-1}"
+            initial_goals = "Costs $5, literal $${HOME}"
+
+            [llm]
+            provider = "anthropic"
+            api_key = "${API_KEY}"
+            model = "${EMPTY:-claude}"
+
+            [[mcp_servers]]
+            name = "fs"
+            transport = "stdio"
+            command = "mcp-fs"
+            args = ["--root", "${DATA_DIR}/files"]
+        "#;
+
+        let parse = |contents: &str| Config::from_value(toml::from_str(contents).unwrap(), env);
+        let config = parse(contents).unwrap();
+        assert_eq!(config.database_path, "/var/lib/replicante/agent.db");
+        assert_eq!(config.agent.id.as_deref(), Some("agent-1"));
+        assert_eq!(
+            config.agent.initial_goals.as_deref(),
+            Some("Costs $5, literal ${HOME}")
+        );
+        assert_eq!(config.llm.api_key.as_deref(), Some("sk-test"));
+        assert_eq!(config.llm.model, "claude");
+        assert_eq!(
+            config.mcp_servers[0].args,
+            vec!["--root", "/var/lib/replicante/files"]
+        );
+
+        let missing = contents.replace("${API_KEY}", "${MISSING_KEY}");
+        let error = format!("{:#}", parse(&missing).unwrap_err());
+        assert!(error.contains("llm.api_key"), "{error}");
+        assert!(error.contains("MISSING_KEY is not set"), "{error}");
+    }
+
+    #[test]
+    fn test_zero_max_concurrent_is_refused_at_load() {
+        let contents = r#"
+            database_path = "agent.db"
+            mcp_servers = []
+
+            [agent]
+
+            [llm]
+            provider = "mock"
+            model = "mock"
+
+            [llm.rate_limit]
+            max_concurrent = 0
+        "#;
+        let parse =
+            |contents: &str| Config::from_value(toml::from_str(contents).unwrap(), |_| None);
+
+        let error = format!("{:#}", parse(contents).unwrap_err());
+        assert!(error.contains("max_concurrent"), "{error}");
+        let config = parse(&contents.replace("= 0", "= 2")).unwrap();
+        let limits = config.llm.rate_limit.unwrap();
+        assert_eq!(limits.max_concurrent.map(|max| max.get()), Some(2));
+    }
+
+    #[test]
+    fn test_layered_config_merges_includes_and_overlays() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("base.toml"),
+            r#"
+            database_path = "base.db"
+
+            [agent]
+            id = "base"
+            reasoning_interval_secs = 30
+
+            [llm]
+            provider = "anthropic"
+            model = "claude"
+
+            [[mcp_servers]]
+            name = "fs"
+            transport = "stdio"
+            command = "mcp-fs"
+            args = ["--root", "/srv"]
+
+            [[mcp_servers]]
+            name = "http"
+            transport = "stdio"
+            command = "mcp-http"
+            args = []
+            "#,
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("envs")).unwrap();
+        fs::write(
+            dir.path().join("envs/dev.toml"),
+            r#"
+            include = ["../base.toml"]
+            database_path = "dev.db"
+
+            [agent]
+            id = "dev"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("local.toml"),
+            r#"
+            [llm]
+            model = "claude-small"
+
+            [[mcp_servers]]
+            name = "fs"
+            args = ["--root", "/tmp"]
+
+            [[mcp_servers]]
+            name = "shell"
+            transport = "stdio"
+            command = "mcp-shell"
+            args = []
+            "#,
+        )
+        .unwrap();
+
+        let options = LoadOptions {
+            overlays: vec![dir.path().join("local.toml")],
+            profile: None,
+        };
+        let config = Config::from_files(&dir.path().join("envs/dev.toml"), &options).unwrap();
+        assert_eq!(config.database_path, "dev.db");
+        assert_eq!(config.agent.id.as_deref(), Some("dev"));
+        assert_eq!(config.agent.reasoning_interval_secs, 30);
+        assert_eq!(config.llm.provider, "anthropic");
+        assert_eq!(config.llm.model, "claude-small");
+        let servers: Vec<(&str, &str, &[String])> = config
+            .mcp_servers
+            .iter()
+            .map(|s| (s.name.as_str(), s.command.as_str(), s.args.as_slice()))
+            .collect();
+        assert_eq!(
+            servers,
+            vec![
+                (
+                    "fs",
+                    "mcp-fs",
+                    &["--root".to_string(), "/tmp".to_string()][..]
+                ),
+                ("http", "mcp-http", &[][..]),
+                ("shell", "mcp-shell", &[][..]),
+            ]
+        );
+
+        fs::write(
+            dir.path().join("base.toml"),
+            "include = [\"envs/dev.toml\"]\n",
+        )
+        .unwrap();
+        let error = format!(
+            "{:#}",
+            Config::from_file(&dir.path().join("envs/dev.toml")).unwrap_err()
+        );
+        assert!(error.contains("includes itself"), "{error}");
+    }
+
+    #[test]
+    fn test_secrets_resolve_from_store_and_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let secrets_dir = dir.path().join("secrets");
+        fs::create_dir(&secrets_dir).unwrap();
+        fs::write(secrets_dir.join("nostr_key"), "nsec1test\n").unwrap();
+        fs::write(dir.path().join("api_key"), "sk-from-file\n").unwrap();
+        let env = |name: &str| (name == "SECRETS_DIR").then(|| secrets_dir.display().to_string());
+        let contents = format!(
+            r#"
+            database_path = "agent.db"
+
+            [agent]
+
+            [llm]
+            provider = "anthropic"
+            model = "claude"
+            api_key_file = "{api_key}"
+
+            [secrets]
+            backend = "file"
+            dir = "${{SECRETS_DIR}}"
+
+            [[mcp_servers]]
+            name = "nostr"
+            transport = "stdio"
+            command = "nostr-mcp-server"
+            args = ["--key", "${{secret:nostr_key}}", "--relay", "${{secret:relay:-wss://relay.damus.io}}"]
+            "#,
+            api_key = dir.path().join("api_key").display()
+        );
+        let parse = |contents: &str| Config::from_value(toml::from_str(contents).unwrap(), env);
+
+        let config = parse(&contents).unwrap();
+        assert_eq!(config.llm.api_key.as_deref(), Some("sk-from-file"));
+        assert_eq!(
+            config.mcp_servers[0].args,
+            vec!["--key", "nsec1test", "--relay", "wss://relay.damus.io"]
+        );
+        assert_eq!(
+            config.secrets.unwrap().dir,
+            Some(secrets_dir.display().to_string())
+        );
+
+        let missing = contents.replace("secret:nostr_key", "secret:other_key");
+        let error = format!("{:#}", parse(&missing).unwrap_err());
+        assert!(error.contains("mcp_servers[0].args[1]"), "{error}");
+        assert!(error.contains("Secret other_key was not found"), "{error}");
+
+        let both = contents.replace("[llm]", "[llm]\napi_key = \"sk-inline\"");
+        let error = format!("{:#}", parse(&both).unwrap_err());
+        assert!(error.contains("both set"), "{error}");
+    }
+
+    #[test]
+    fn test_reloadable_changes() {
+        let old = Config::default();
+        let mut new = old.clone();
+        assert_eq!(old.reloadable_changes(&new), vec![]);
+
+        new.agent.reasoning_interval_secs = 60;
+        new.llm.model = "claude-small".to_string();
+        new.database_path = "elsewhere.db".to_string();
+        let changes = old.reloadable_changes(&new);
+        let settings: Vec<&str> = changes.iter().map(|c| c.setting).collect();
+        assert_eq!(settings, vec!["agent.reasoning_interval_secs", "llm.model"]);
+        assert_eq!(changes[0].old, serde_json::json!(10));
+        assert_eq!(changes[0].new, serde_json::json!(60));
+    }
+
+    #[test]
+    fn test_tuning_keeps_defaults_for_unset_keys() {
+        let mut config = Config::default();
+        config.agent.tuning = toml::from_str(
+            r#"
+            wait_secs = 5
+            learned_pattern_threshold = 0.9
+            "#,
+        )
+        .unwrap();
+        let tuning = &config.agent.tuning;
+        assert_eq!(tuning.wait_secs, 5);
+        assert_eq!(tuning.learned_pattern_threshold, 0.9);
+        assert_eq!(tuning.memory_summary_entries, 20);
+        assert_eq!(tuning.tool_output_max_tokens, 1250);
+        assert_eq!(tuning.pattern_min_confidence, 0.7);
+
+        let mut new = config.clone();
+        new.agent.tuning.recent_decisions = 8;
+        let changes = config.reloadable_changes(&new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].setting, "agent.tuning");
+    }
+
+    #[test]
+    fn test_json_schema_covers_the_config_format() {
+        let schema = serde_json::to_value(Config::json_schema()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        for key in [
+            "agent",
+            "llm",
+            "mcp_servers",
+            "database_path",
+            "secrets",
+            "include",
+        ] {
+            assert!(properties.contains_key(key), "missing {key}");
+        }
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|key| key.as_str())
+            .collect();
+        assert!(required.contains(&"llm"));
+        assert!(!required.contains(&"secrets"));
+    }
+
+    #[test]
+    fn test_profiles_override_between_main_file_and_overlays() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+            database_path = "agent.db"
+            mcp_servers = []
+
+            [agent]
+            reasoning_interval_secs = 5
+
+            [llm]
+            provider = "mock"
+            model = "small"
+
+            [profiles.prod.agent]
+            reasoning_interval_secs = 60
+
+            [profiles.prod.llm]
+            model = "large"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("local.toml"),
+            "[llm]\nmodel = \"local\"\n\n[profiles.staging.llm]\nmodel = \"medium\"\n",
+        )
+        .unwrap();
+        let load = |profile: &str, overlays: Vec<PathBuf>| {
+            let options = LoadOptions {
+                overlays,
+                profile: Some(profile.to_string()),
+            };
+            Config::from_files(&path, &options)
+        };
+
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.agent.reasoning_interval_secs, 5);
+        assert_eq!(config.llm.model, "small");
+
+        let config = load("prod", vec![]).unwrap();
+        assert_eq!(config.agent.reasoning_interval_secs, 60);
+        assert_eq!(config.llm.model, "large");
+
+        // Overlays win over the profile, and can declare profiles too
+        let config = load("prod", vec![dir.path().join("local.toml")]).unwrap();
+        assert_eq!(config.agent.reasoning_interval_secs, 60);
+        assert_eq!(config.llm.model, "local");
+        let error = format!("{:#}", load("staging", vec![]).unwrap_err());
+        assert!(error.contains("available profiles: prod"), "{error}");
+    }
 }
@@ -0,0 +1,225 @@
+//! Environment checks for `replicante doctor`: what an agent needs beyond
+//! a config that parses, reported as one pass/fail line each.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::config::{self, Config, ConfigProblem};
+use crate::llm;
+
+/// How long the docker and LLM checks may take before they fail
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    /// Not needed by every deployment, e.g. docker for unsandboxed agents
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Pass => "PASS",
+            Self::Warn => "WARN",
+            Self::Fail => "FAIL",
+        };
+        f.write_str(label)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    /// Pass, or fail with the problems found under `prefix`
+    fn from_problems(name: String, problems: &[ConfigProblem], prefix: &str, ok: &str) -> Self {
+        let messages: Vec<&str> = problems
+            .iter()
+            .filter(|problem| problem.field.starts_with(prefix))
+            .map(|problem| problem.message.as_str())
+            .collect();
+        if messages.is_empty() {
+            Self::new(name, Status::Pass, ok)
+        } else {
+            Self::new(name, Status::Fail, messages.join("; "))
+        }
+    }
+}
+
+/// Run every check against `config`. The LLM check sends one tiny
+/// completion request unless `ping_llm` is false.
+pub async fn run(config: &Config, ping_llm: bool) -> Vec<Check> {
+    let problems = config.validate();
+    let mut checks = vec![Check::from_problems(
+        "llm credentials".to_string(),
+        &problems,
+        "llm.",
+        &format!("provider {}", config.llm.provider),
+    )];
+
+    if ping_llm {
+        checks.push(check_llm(config).await);
+    }
+
+    for server in &config.mcp_servers {
+        checks.push(Check::from_problems(
+            format!("mcp server {}", server.name),
+            &problems,
+            &format!("mcp_servers.{}.", server.name),
+            &server.command,
+        ));
+        for dir in workspace_dirs(&server.args, server.cwd.as_deref()) {
+            checks.push(check_workspace(&server.name, dir));
+        }
+    }
+
+    checks.push(Check::from_problems(
+        "database".to_string(),
+        &problems,
+        "database_path",
+        &format!("{} is writable", config.database_path),
+    ));
+    checks.push(check_docker().await);
+
+    checks
+}
+
+/// Directories a server works in: its `--workspace` argument and `cwd`
+fn workspace_dirs<'a>(args: &'a [String], cwd: Option<&'a str>) -> Vec<&'a str> {
+    let mut dirs: Vec<&str> = args
+        .iter()
+        .enumerate()
+        .filter_map(|(i, arg)| match arg.strip_prefix("--workspace") {
+            Some("") => args.get(i + 1).map(String::as_str),
+            Some(value) => value.strip_prefix('='),
+            None => None,
+        })
+        .collect();
+    dirs.extend(cwd);
+    dirs
+}
+
+fn check_workspace(server: &str, dir: &str) -> Check {
+    let name = format!("workspace {dir} ({server})");
+    match config::check_dir_writable(Path::new(dir)) {
+        Ok(()) => Check::new(name, Status::Pass, "writable"),
+        Err(message) => Check::new(name, Status::Fail, message),
+    }
+}
+
+async fn check_llm(config: &Config) -> Check {
+    let name = "llm connectivity";
+    let mut llm_config = config.llm.clone();
+    llm_config.max_tokens = Some(5);
+    let provider = match llm::create_provider(&llm_config) {
+        Ok(provider) => provider,
+        Err(e) => return Check::new(name, Status::Fail, format!("{e:#}")),
+    };
+
+    let started = Instant::now();
+    match tokio::time::timeout(CHECK_TIMEOUT, provider.complete("Reply with OK.")).await {
+        Ok(Ok(_)) => Check::new(
+            name,
+            Status::Pass,
+            format!(
+                "{model} answered in {ms} ms",
+                model = config.llm.model,
+                ms = started.elapsed().as_millis()
+            ),
+        ),
+        Ok(Err(e)) => Check::new(name, Status::Fail, format!("{e:#}")),
+        Err(_) => Check::new(
+            name,
+            Status::Fail,
+            format!("no answer within {}s", CHECK_TIMEOUT.as_secs()),
+        ),
+    }
+}
+
+async fn check_docker() -> Check {
+    let name = "docker";
+    let output = Command::new("docker")
+        .args(["version", "--format", "{{.Server.Version}}"])
+        .output();
+    match tokio::time::timeout(CHECK_TIMEOUT, output).await {
+        Ok(Ok(output)) if output.status.success() => Check::new(
+            name,
+            Status::Pass,
+            format!("server {}", String::from_utf8_lossy(&output.stdout).trim()),
+        ),
+        Ok(Ok(output)) => Check::new(
+            name,
+            Status::Warn,
+            format!(
+                "daemon unavailable, sandboxed agents cannot run: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ),
+        Ok(Err(e)) => Check::new(
+            name,
+            Status::Warn,
+            format!("not installed, sandboxed agents cannot run: {e}"),
+        ),
+        Err(_) => Check::new(name, Status::Warn, "docker version did not answer"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_dirs_reads_both_arg_forms_and_cwd() {
+        let args = vec![
+            "--workspace".to_string(),
+            "/srv/a".to_string(),
+            "--workspace=/srv/b".to_string(),
+            "--workspaces".to_string(),
+        ];
+        assert_eq!(
+            workspace_dirs(&args, Some("/srv/c")),
+            vec!["/srv/a", "/srv/b", "/srv/c"]
+        );
+        assert!(workspace_dirs(&[], None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_config_problems_per_check() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.llm.provider = "mock".to_string();
+        config.database_path = dir.path().join("agent.db").to_string_lossy().into_owned();
+        config.mcp_servers.truncate(1);
+        config.mcp_servers[0].command = "/nonexistent/mcp-server".to_string();
+        config.mcp_servers[0].args = vec!["--workspace".to_string(), "/nonexistent".to_string()];
+
+        let checks = run(&config, true).await;
+        let status = |name: &str| {
+            checks
+                .iter()
+                .find(|check| check.name.starts_with(name))
+                .map(|check| check.status)
+        };
+        assert_eq!(status("llm credentials"), Some(Status::Pass));
+        assert_eq!(status("llm connectivity"), Some(Status::Pass));
+        assert_eq!(status("mcp server nostr"), Some(Status::Fail));
+        assert_eq!(status("workspace /nonexistent"), Some(Status::Fail));
+        assert_eq!(status("database"), Some(Status::Pass));
+        assert!(status("docker").is_some());
+    }
+}
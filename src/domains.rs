@@ -0,0 +1,44 @@
+//! Host patterns of domain allow and deny lists. The supervisor's egress
+//! proxy and the HTTP MCP server both match hosts here, so an entry means
+//! the same thing wherever it is listed.
+
+/// A host as patterns are compared against it: lowercase, without a
+/// trailing dot or the brackets of an IPv6 address
+pub fn normalize_host(host: &str) -> String {
+    host.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// Whether `host` matches `pattern`: an exact host, `*.example.com` for any
+/// subdomain of `example.com` but not the domain itself, or `*` for every
+/// host. Both sides are normalized first.
+pub fn domain_matches(pattern: &str, host: &str) -> bool {
+    let pattern = normalize_host(pattern);
+    let host = normalize_host(host);
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.')),
+        None => pattern == "*" || pattern == host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_wildcards_match_subdomains() {
+        assert!(domain_matches("example.com", "Example.COM."));
+        assert!(!domain_matches("example.com", "api.example.com"));
+        assert!(domain_matches("*.example.com", "api.example.com"));
+        assert!(domain_matches("*.example.com", "eu.api.example.com"));
+        assert!(!domain_matches("*.example.com", "example.com"));
+        assert!(!domain_matches("*.example.com", "badexample.com"));
+        assert!(domain_matches("*", "anything.org"));
+        assert!(domain_matches("::1", "[::1]"));
+    }
+}
@@ -0,0 +1,117 @@
+//! In-process event bus. Components publish what happened and any number
+//! of subscribers (metrics, the supervisor API, plugins) react to it,
+//! instead of each producer calling every consumer directly.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Events a subscriber that falls this far behind starts losing
+const DEFAULT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A decision's action finished, or was never run
+    DecisionMade {
+        decision_id: i64,
+        /// Result status: "success", "error", "skipped"...
+        status: String,
+        summary: Option<String>,
+        duration_ms: Option<u64>,
+    },
+    /// An MCP tool call returned
+    ToolCalled {
+        tool: String,
+        success: bool,
+        duration_ms: u64,
+        error: Option<String>,
+    },
+    /// The supervisor raised an alert about an agent
+    AlertRaised {
+        agent_id: String,
+        severity: String,
+        summary: String,
+    },
+    /// An MCP server failed its health checks and is being restarted
+    ServerUnhealthy {
+        server: String,
+        consecutive_failures: u32,
+        error: String,
+    },
+}
+
+impl Event {
+    /// Name of the event's variant, as in its serialized `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::DecisionMade { .. } => "decision_made",
+            Self::ToolCalled { .. } => "tool_called",
+            Self::AlertRaised { .. } => "alert_raised",
+            Self::ServerUnhealthy { .. } => "server_unhealthy",
+        }
+    }
+}
+
+/// A broadcast channel of [`Event`]s; clones publish to the same bus
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Send `event` to every current subscriber; it is dropped if there
+    /// are none
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Receive every event published from now on. A receiver that lags
+    /// more than the bus capacity behind gets `RecvError::Lagged` and
+    /// skips the oldest events.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_every_subscriber_receives_published_events() {
+        let bus = EventBus::default();
+        bus.publish(Event::ServerUnhealthy {
+            server: "unheard".to_string(),
+            consecutive_failures: 4,
+            error: "timeout".to_string(),
+        });
+
+        let mut first = bus.subscribe();
+        let mut second = bus.clone().subscribe();
+        let event = Event::ToolCalled {
+            tool: "fs:read_file".to_string(),
+            success: true,
+            duration_ms: 12,
+            error: None,
+        };
+        bus.publish(event.clone());
+
+        assert_eq!(first.recv().await.unwrap(), event);
+        assert_eq!(second.recv().await.unwrap(), event);
+        assert!(first.try_recv().is_err());
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], event.kind());
+    }
+}
@@ -0,0 +1,295 @@
+//! Starter configs for `replicante init`, so a first config does not have
+//! to be pieced together from the example files.
+
+use anyhow::{Result, bail};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::llm;
+
+/// MCP servers built alongside the `replicante` binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinServer {
+    Filesystem,
+    Shell,
+    Http,
+    Nostr,
+    VectorStore,
+}
+
+impl BuiltinServer {
+    pub const ALL: &[BuiltinServer] = &[
+        Self::Filesystem,
+        Self::Shell,
+        Self::Http,
+        Self::Nostr,
+        Self::VectorStore,
+    ];
+
+    /// Name used for the `[[mcp_servers]]` entry and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Filesystem => "filesystem",
+            Self::Shell => "shell",
+            Self::Http => "http",
+            Self::Nostr => "nostr",
+            Self::VectorStore => "vector-store",
+        }
+    }
+
+    /// File name of the server's binary
+    pub fn binary(self) -> &'static str {
+        match self {
+            Self::Filesystem => "filesystem-mcp-server",
+            Self::Shell => "shell-mcp-server",
+            Self::Http => "http-mcp-server",
+            Self::Nostr => "nostr-mcp-server",
+            Self::VectorStore => "vector-store-mcp-server",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Filesystem => "Read and write files inside the workspace",
+            Self::Shell => "Run commands inside the workspace",
+            Self::Http => "Fetch web pages; requests only go to allowed domains",
+            Self::Nostr => "Publish and read Nostr events",
+            Self::VectorStore => "Store and search notes by similarity",
+        }
+    }
+
+    /// Arguments pointing the server at the workspace. The Nostr key is kept
+    /// beside the database, where the filesystem server cannot read it
+    fn args(self, workspace: &str, database_path: &str) -> Vec<String> {
+        match self {
+            Self::Filesystem | Self::Shell => {
+                vec!["--workspace".to_string(), workspace.to_string()]
+            }
+            Self::Http => vec![
+                "--workspace".to_string(),
+                workspace.to_string(),
+                "--allow-domain".to_string(),
+                "example.com".to_string(),
+            ],
+            Self::Nostr => vec![
+                "--secret-key-file".to_string(),
+                Path::new(database_path)
+                    .with_file_name("nostr.key")
+                    .to_string_lossy()
+                    .into_owned(),
+            ],
+            Self::VectorStore => vec![
+                "--database".to_string(),
+                format!("{workspace}/vector-store.db"),
+            ],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match Self::ALL.iter().find(|server| server.name() == name) {
+            Some(server) => Ok(*server),
+            None => bail!(
+                "Unknown MCP server '{name}'; expected one of: {}",
+                Self::ALL
+                    .iter()
+                    .map(|server| server.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Answers that shape a starter config
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    pub provider: String,
+    /// Falls back to [`default_model`] for the provider
+    pub model: Option<String>,
+    /// Directory the MCP servers work in
+    pub workspace: String,
+    pub database_path: String,
+    pub servers: Vec<BuiltinServer>,
+    /// Where the bundled server binaries live; bare names are looked up on
+    /// `PATH` when unset
+    pub bin_dir: Option<PathBuf>,
+}
+
+/// Model a new config starts with for `provider`
+pub fn default_model(provider: &str) -> &'static str {
+    match provider {
+        "openai" => "gpt-4o-mini",
+        "ollama" => "llama3.2:3b",
+        "mock" => "mock",
+        "scripted" => "scripted",
+        _ => "claude-sonnet-4-20250514",
+    }
+}
+
+/// Directory of the running executable, if the bundled MCP servers were
+/// installed next to it
+pub fn bundled_bin_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    BuiltinServer::ALL
+        .iter()
+        .all(|server| dir.join(server.binary()).is_file())
+        .then(|| dir.to_path_buf())
+}
+
+/// Render a commented agent config for `options`
+pub fn render(options: &InitOptions) -> Result<String> {
+    let provider = options.provider.to_lowercase();
+    if !llm::PROVIDERS.contains(&provider.as_str()) {
+        bail!(
+            "Unknown LLM provider '{}'; expected one of: {}",
+            options.provider,
+            llm::PROVIDERS.join(", ")
+        );
+    }
+    let model = options
+        .model
+        .clone()
+        .unwrap_or_else(|| default_model(&provider).to_string());
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# Replicante agent configuration, written by `replicante init`"
+    )?;
+    writeln!(
+        out,
+        "# Check it with `replicante config validate --config <file>`"
+    )?;
+    writeln!(out)?;
+    writeln!(out, "database_path = {}", quote(&options.database_path))?;
+    writeln!(out)?;
+    writeln!(out, "[agent]")?;
+    writeln!(out, "log_level = \"info\"")?;
+    writeln!(out, "reasoning_interval_secs = 10")?;
+    writeln!(out, "initial_goals = \"\"\"")?;
+    writeln!(out, "Your primary goals are:")?;
+    writeln!(out, "1. Learn about your environment and available tools")?;
+    writeln!(out, "2. Discover ways to provide value to others")?;
+    writeln!(out, "3. Build knowledge and capabilities over time")?;
+    writeln!(out, "\"\"\"")?;
+    writeln!(out)?;
+    writeln!(out, "[llm]")?;
+    writeln!(out, "provider = {}", quote(&provider))?;
+    writeln!(out, "model = {}", quote(&model))?;
+    match llm::api_key_env_vars(&provider).first() {
+        Some(var) => {
+            writeln!(out, "# The key is read from {var} when api_key is unset")?;
+            writeln!(out, "# api_key = \"${{{var}}}\"")?;
+        }
+        None if provider == "ollama" => {
+            writeln!(out, "api_url = \"http://localhost:11434\"")?;
+        }
+        None if provider == "scripted" => {
+            writeln!(out, "# Responses replayed in place of a model")?;
+            writeln!(out, "script = \"llm-script.yaml\"")?;
+        }
+        None => {}
+    }
+    writeln!(out, "temperature = 0.7")?;
+    writeln!(out, "max_tokens = 4000")?;
+
+    for server in &options.servers {
+        let command = match &options.bin_dir {
+            Some(dir) => dir.join(server.binary()).to_string_lossy().into_owned(),
+            None => server.binary().to_string(),
+        };
+        let args = server
+            .args(&options.workspace, &options.database_path)
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(out)?;
+        writeln!(out, "# {}", server.description())?;
+        writeln!(out, "[[mcp_servers]]")?;
+        writeln!(out, "name = {}", quote(server.name()))?;
+        writeln!(out, "transport = \"stdio\"")?;
+        writeln!(out, "command = {}", quote(&command))?;
+        writeln!(out, "args = [{args}]")?;
+    }
+
+    Ok(out)
+}
+
+/// Write `contents` to `path`, refusing to replace an existing file unless
+/// `force` is set
+pub fn write(path: &Path, contents: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        );
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// A TOML basic string
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_render_produces_a_loadable_config() {
+        let options = InitOptions {
+            provider: "ollama".to_string(),
+            model: None,
+            workspace: "/srv/agent".to_string(),
+            database_path: "/var/lib/agent/agent.db".to_string(),
+            servers: vec![BuiltinServer::Filesystem, BuiltinServer::Nostr],
+            bin_dir: Some(PathBuf::from("/opt/replicante/bin")),
+        };
+        let config: Config = toml::from_str(&render(&options).unwrap()).unwrap();
+
+        assert_eq!(config.llm.provider, "ollama");
+        assert_eq!(config.llm.model, "llama3.2:3b");
+        assert_eq!(
+            config.llm.api_url.as_deref(),
+            Some("http://localhost:11434")
+        );
+        assert_eq!(config.database_path, "/var/lib/agent/agent.db");
+        assert_eq!(config.mcp_servers.len(), 2);
+        assert_eq!(
+            config.mcp_servers[0].command,
+            "/opt/replicante/bin/filesystem-mcp-server"
+        );
+        assert_eq!(
+            config.mcp_servers[0].args,
+            vec!["--workspace", "/srv/agent"]
+        );
+        assert_eq!(
+            config.mcp_servers[1].args,
+            vec!["--secret-key-file", "/var/lib/agent/nostr.key"]
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_unknown_provider() {
+        let options = InitOptions {
+            provider: "llamacorp".to_string(),
+            model: None,
+            workspace: ".".to_string(),
+            database_path: "replicante.db".to_string(),
+            servers: vec![],
+            bin_dir: None,
+        };
+        assert!(render(&options).is_err());
+        assert!(BuiltinServer::from_name("ftp").is_err());
+        assert_eq!(
+            BuiltinServer::from_name("vector-store").unwrap(),
+            BuiltinServer::VectorStore
+        );
+    }
+}
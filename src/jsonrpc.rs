@@ -1,10 +1,18 @@
 use anyhow::Result;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read};
 use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
 
 static NEXT_ID: AtomicU64 = AtomicU64::new(1);
 
+/// Largest message read or written unless configured otherwise
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
 /// JSON-RPC 2.0 Request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Request {
@@ -80,7 +88,6 @@ impl Request {
 }
 
 impl Response {
-    #[allow(dead_code)]
     pub fn success(id: Option<RequestId>, result: Value) -> Self {
         Self {
             jsonrpc: "2.0".to_string(),
@@ -90,23 +97,57 @@ impl Response {
         }
     }
 
-    #[allow(dead_code)]
     pub fn error(id: Option<RequestId>, code: i64, message: impl Into<String>) -> Self {
+        Self::from_error(id, ErrorObject::new(code, message))
+    }
+
+    pub fn from_error(id: Option<RequestId>, error: ErrorObject) -> Self {
         Self {
             jsonrpc: "2.0".to_string(),
             result: None,
-            error: Some(ErrorObject {
-                code,
-                message: message.into(),
-                data: None,
-            }),
+            error: Some(error),
             id,
         }
     }
 }
 
+impl ErrorObject {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(error_codes::INVALID_PARAMS, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(error_codes::INTERNAL_ERROR, message)
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
 impl Message {
     pub fn parse(json: &str) -> Result<Self> {
+        Self::parse_with_limit(json, DEFAULT_MAX_MESSAGE_BYTES)
+    }
+
+    /// Parse a message, refusing ones over `max_bytes`
+    pub fn parse_with_limit(json: &str, max_bytes: usize) -> Result<Self> {
+        if json.len() > max_bytes {
+            return Err(MessageTooLarge {
+                size: json.len(),
+                limit: max_bytes,
+            }
+            .into());
+        }
         serde_json::from_str(json)
             .map_err(|e| anyhow::anyhow!("Failed to parse JSON-RPC message: {}", e))
     }
@@ -116,6 +157,19 @@ impl Message {
             .map_err(|e| anyhow::anyhow!("Failed to serialize JSON-RPC message: {}", e))
     }
 
+    /// Serialize a message, refusing to produce one over `max_bytes`
+    pub fn to_string_with_limit(&self, max_bytes: usize) -> Result<String> {
+        let json = self.to_string()?;
+        if json.len() > max_bytes {
+            return Err(MessageTooLarge {
+                size: json.len(),
+                limit: max_bytes,
+            }
+            .into());
+        }
+        Ok(json)
+    }
+
     #[allow(dead_code)]
     pub fn id(&self) -> Option<&RequestId> {
         match self {
@@ -126,12 +180,592 @@ impl Message {
     }
 }
 
+/// A message over the configured size limit
+#[derive(Debug)]
+pub struct MessageTooLarge {
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for MessageTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Message too large: {} bytes exceeds the {} byte limit",
+            self.size, self.limit
+        )
+    }
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+/// Outcome of reading one message, a line unless Content-Length framed
+#[derive(Debug, PartialEq, Eq)]
+pub enum LineRead {
+    Line,
+    /// The message had this many bytes, over the limit; only the first
+    /// `max_bytes` were kept
+    TooLarge(usize),
+    Eof,
+}
+
+/// Read one line into `line` (without the newline), holding at most
+/// `max_bytes` of it in memory; the rest of an oversized line is skipped
+pub fn read_line_limited<R: BufRead>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+    max_bytes: usize,
+) -> io::Result<LineRead> {
+    line.clear();
+    let mut size = 0;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(line_outcome(size, max_bytes, true));
+        }
+        let (consumed, ended) = scan_chunk(available, line, &mut size, max_bytes);
+        reader.consume(consumed);
+        if ended {
+            return Ok(line_outcome(size, max_bytes, false));
+        }
+    }
+}
+
+/// [`read_line_limited`] for async readers
+pub async fn read_line_limited_async<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+    max_bytes: usize,
+) -> io::Result<LineRead> {
+    line.clear();
+    let mut size = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(line_outcome(size, max_bytes, true));
+        }
+        let (consumed, ended) = scan_chunk(available, line, &mut size, max_bytes);
+        reader.consume(consumed);
+        if ended {
+            return Ok(line_outcome(size, max_bytes, false));
+        }
+    }
+}
+
+/// Take `available` up to the next newline into `line`, as far as it fits;
+/// returns how many bytes to consume and whether the line ended
+fn scan_chunk(
+    available: &[u8],
+    line: &mut Vec<u8>,
+    size: &mut usize,
+    max_bytes: usize,
+) -> (usize, bool) {
+    let newline = available.iter().position(|&b| b == b'\n');
+    let chunk = &available[..newline.unwrap_or(available.len())];
+    let room = max_bytes.saturating_sub(line.len());
+    line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+    *size += chunk.len();
+    (
+        chunk.len() + usize::from(newline.is_some()),
+        newline.is_some(),
+    )
+}
+
+/// Best-effort request ID from the start of a message, for answering one
+/// too large to parse. Looks for the first `"id"` key, which is where
+/// serializers usually put it, ahead of any params or result.
+pub fn message_id_hint(prefix: &[u8]) -> Option<RequestId> {
+    let start = prefix.windows(4).position(|w| w == b"\"id\"")? + 4;
+    // Lossy, as the prefix may end inside a multi-byte character
+    let rest = String::from_utf8_lossy(&prefix[start..]);
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        Some(RequestId::String(quoted[..end].to_string()))
+    } else {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        rest[..end].parse().ok().map(RequestId::Number)
+    }
+}
+
+fn line_outcome(size: usize, max_bytes: usize, eof: bool) -> LineRead {
+    if size > max_bytes {
+        LineRead::TooLarge(size)
+    } else if eof && size == 0 {
+        LineRead::Eof
+    } else {
+        LineRead::Line
+    }
+}
+
+/// How messages are delimited on a stdio stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framing {
+    /// One JSON message per line
+    #[default]
+    Newline,
+    /// LSP-style `Content-Length: N` header, a blank line, then N bytes
+    ContentLength,
+}
+
+/// Longest header line accepted with Content-Length framing
+const MAX_HEADER_BYTES: usize = 1024;
+
+impl Framing {
+    /// Guess the framing from the first bytes a peer sent
+    pub fn detect(start: &[u8]) -> Self {
+        let start = start.trim_ascii_start();
+        if start
+            .get(..b"content-length".len())
+            .is_some_and(|name| name.eq_ignore_ascii_case(b"content-length"))
+        {
+            Framing::ContentLength
+        } else {
+            Framing::Newline
+        }
+    }
+
+    /// Frame one serialized message for writing
+    pub fn encode(self, json: &str) -> String {
+        match self {
+            Framing::Newline => format!("{json}\n"),
+            Framing::ContentLength => format!("Content-Length: {}\r\n\r\n{json}", json.len()),
+        }
+    }
+
+    /// Read one message into `message`, holding at most `max_bytes` of it
+    pub fn read<R: BufRead>(
+        self,
+        reader: &mut R,
+        message: &mut Vec<u8>,
+        max_bytes: usize,
+    ) -> io::Result<LineRead> {
+        if self == Framing::Newline {
+            return read_line_limited(reader, message, max_bytes);
+        }
+
+        let mut headers = ContentLengthHeaders::default();
+        loop {
+            match read_line_limited(reader, message, MAX_HEADER_BYTES)? {
+                LineRead::Line if headers.add(message)? => {}
+                LineRead::Line => break,
+                LineRead::TooLarge(_) => return Err(invalid_data("Header line too long")),
+                LineRead::Eof => return headers.at_eof(),
+            }
+        }
+        let length = headers.length()?;
+
+        let kept = length.min(max_bytes);
+        message.clear();
+        message.resize(kept, 0);
+        reader.read_exact(message)?;
+        io::copy(
+            &mut (&mut *reader).take((length - kept) as u64),
+            &mut io::sink(),
+        )?;
+        Ok(line_outcome(length, max_bytes, false))
+    }
+
+    /// [`Framing::read`] for async readers
+    pub async fn read_async<R: AsyncBufRead + Unpin>(
+        self,
+        reader: &mut R,
+        message: &mut Vec<u8>,
+        max_bytes: usize,
+    ) -> io::Result<LineRead> {
+        if self == Framing::Newline {
+            return read_line_limited_async(reader, message, max_bytes).await;
+        }
+
+        let mut headers = ContentLengthHeaders::default();
+        loop {
+            match read_line_limited_async(reader, message, MAX_HEADER_BYTES).await? {
+                LineRead::Line if headers.add(message)? => {}
+                LineRead::Line => break,
+                LineRead::TooLarge(_) => return Err(invalid_data("Header line too long")),
+                LineRead::Eof => return headers.at_eof(),
+            }
+        }
+        let length = headers.length()?;
+
+        let kept = length.min(max_bytes);
+        message.clear();
+        message.resize(kept, 0);
+        reader.read_exact(message).await?;
+        tokio::io::copy(
+            &mut (&mut *reader).take((length - kept) as u64),
+            &mut tokio::io::sink(),
+        )
+        .await?;
+        Ok(line_outcome(length, max_bytes, false))
+    }
+}
+
+/// Headers of one Content-Length framed message, as they are read
+#[derive(Default)]
+struct ContentLengthHeaders {
+    seen: bool,
+    content_length: Option<usize>,
+}
+
+impl ContentLengthHeaders {
+    /// Take one header line; false at the blank line that ends the headers
+    fn add(&mut self, line: &[u8]) -> io::Result<bool> {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() {
+            // Stray blank lines between messages are skipped
+            return Ok(!self.seen);
+        }
+        self.seen = true;
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            let length = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid_data(format!("Invalid Content-Length: {}", value.trim())))?;
+            self.content_length = Some(length);
+        }
+        Ok(true)
+    }
+
+    fn length(&self) -> io::Result<usize> {
+        self.content_length
+            .ok_or_else(|| invalid_data("Missing Content-Length header"))
+    }
+
+    fn at_eof(&self) -> io::Result<LineRead> {
+        if self.seen {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Stream ended inside message headers",
+            ))
+        } else {
+            Ok(LineRead::Eof)
+        }
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
 // Standard JSON-RPC error codes
-#[allow(dead_code)]
 pub mod error_codes {
     pub const PARSE_ERROR: i64 = -32700;
     pub const INVALID_REQUEST: i64 = -32600;
     pub const METHOD_NOT_FOUND: i64 = -32601;
     pub const INVALID_PARAMS: i64 = -32602;
     pub const INTERNAL_ERROR: i64 = -32603;
+    /// Implementation-defined: a message over the configured size limit
+    pub const MESSAGE_TOO_LARGE: i64 = -32000;
+}
+
+type MethodHandler<S> = Box<dyn Fn(&mut S, Value) -> Result<Value, ErrorObject>>;
+type NotificationHandler<S> = Box<dyn Fn(&mut S, Value)>;
+
+/// Server-side dispatch: routes requests and notifications to handlers with
+/// typed params and results, answering with the standard error codes.
+/// `S` is the server state each handler gets mutable access to.
+pub struct Router<S> {
+    methods: HashMap<String, MethodHandler<S>>,
+    notifications: HashMap<String, NotificationHandler<S>>,
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self {
+        Self {
+            methods: HashMap::new(),
+            notifications: HashMap::new(),
+        }
+    }
+}
+
+impl<S> Router<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route a request. Missing params deserialize from `{}`; params that do
+    /// not fit `P` are rejected with INVALID_PARAMS.
+    pub fn method<P, R>(
+        mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&mut S, P) -> Result<R, ErrorObject> + 'static,
+    ) -> Self
+    where
+        P: DeserializeOwned,
+        R: Serialize,
+    {
+        self.methods.insert(
+            name.into(),
+            Box::new(move |state, params| {
+                let params = serde_json::from_value(params)
+                    .map_err(|e| ErrorObject::invalid_params(format!("Invalid params: {e}")))?;
+                let result = handler(state, params)?;
+                serde_json::to_value(result)
+                    .map_err(|e| ErrorObject::internal(format!("Failed to serialize result: {e}")))
+            }),
+        );
+        self
+    }
+
+    /// Route a notification. Notifications get no response, so params that
+    /// do not fit `P` are ignored.
+    pub fn notification<P>(
+        mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&mut S, P) + 'static,
+    ) -> Self
+    where
+        P: DeserializeOwned,
+    {
+        self.notifications.insert(
+            name.into(),
+            Box::new(move |state, params| {
+                if let Ok(params) = serde_json::from_value(params) {
+                    handler(state, params);
+                }
+            }),
+        );
+        self
+    }
+
+    /// Handle one parsed message, returning the response if it needs one
+    pub fn handle(&self, state: &mut S, message: &Value) -> Option<Response> {
+        let request: Request = match serde_json::from_value(message.clone()) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(Response::error(
+                    None,
+                    error_codes::INVALID_REQUEST,
+                    format!("Invalid Request: {e}"),
+                ));
+            }
+        };
+        let params = request
+            .params
+            .unwrap_or_else(|| Value::Object(Default::default()));
+
+        let Some(id) = request.id else {
+            if let Some(handler) = self.notifications.get(&request.method) {
+                handler(state, params);
+            }
+            return None;
+        };
+
+        let outcome = match self.methods.get(&request.method) {
+            Some(handler) => handler(state, params),
+            None => Err(ErrorObject::new(
+                error_codes::METHOD_NOT_FOUND,
+                format!("Method not found: {}", request.method),
+            )),
+        };
+        Some(match outcome {
+            Ok(result) => Response::success(Some(id), result),
+            Err(error) => Response::from_error(Some(id), error),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Deserialize)]
+    struct AddParams {
+        a: i64,
+        b: i64,
+    }
+
+    fn router() -> Router<Vec<String>> {
+        Router::new()
+            .method("add", |_: &mut Vec<String>, p: AddParams| {
+                Ok::<_, ErrorObject>(p.a + p.b)
+            })
+            .method("fail", |_: &mut Vec<String>, _: Value| {
+                Err::<(), _>(ErrorObject::internal("boom").with_data(json!({"why": "test"})))
+            })
+            .notification("note", |seen: &mut Vec<String>, text: String| {
+                seen.push(text)
+            })
+    }
+
+    fn call(router: &Router<Vec<String>>, message: Value) -> Value {
+        let response = router
+            .handle(&mut Vec::new(), &message)
+            .expect("requests get a response");
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[test]
+    fn test_router_typed_methods_and_errors() {
+        let router = router();
+
+        let response = call(
+            &router,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "add", "params": {"a": 2, "b": 3}}),
+        );
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"], 5);
+
+        let response = call(
+            &router,
+            json!({"jsonrpc": "2.0", "id": "x", "method": "add", "params": {"a": 2}}),
+        );
+        assert_eq!(response["id"], "x");
+        assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+        assert!(
+            response["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("missing field `b`")
+        );
+
+        let response = call(
+            &router,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "fail"}),
+        );
+        assert_eq!(response["error"]["code"], error_codes::INTERNAL_ERROR);
+        assert_eq!(response["error"]["data"]["why"], "test");
+
+        let response = call(
+            &router,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "missing"}),
+        );
+        assert_eq!(response["error"]["code"], error_codes::METHOD_NOT_FOUND);
+
+        let response = call(&router, json!({"jsonrpc": "2.0", "id": 4}));
+        assert_eq!(response["error"]["code"], error_codes::INVALID_REQUEST);
+        assert!(response["id"].is_null());
+    }
+
+    #[test]
+    fn test_message_size_limits() {
+        let json = r#"{"jsonrpc":"2.0","id":1,"result":"ok"}"#;
+        assert!(Message::parse_with_limit(json, json.len()).is_ok());
+        let error = Message::parse_with_limit(json, 10).unwrap_err();
+        assert!(error.is::<MessageTooLarge>());
+
+        let message = Message::parse(json).unwrap();
+        assert!(message.to_string_with_limit(10).is_err());
+
+        let mut reader = io::Cursor::new(b"short\nmuch too long line\nlast".to_vec());
+        let mut line = Vec::new();
+        assert_eq!(
+            read_line_limited(&mut reader, &mut line, 8).unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(line, b"short");
+        assert_eq!(
+            read_line_limited(&mut reader, &mut line, 8).unwrap(),
+            LineRead::TooLarge(18)
+        );
+        assert!(line.len() <= 8);
+        assert_eq!(
+            read_line_limited(&mut reader, &mut line, 8).unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(line, b"last");
+        assert_eq!(
+            read_line_limited(&mut reader, &mut line, 8).unwrap(),
+            LineRead::Eof
+        );
+    }
+
+    #[test]
+    fn test_content_length_framing() {
+        let framing = Framing::ContentLength;
+        let first = framing.encode(r#"{"id":1}"#);
+        assert_eq!(first, "Content-Length: 8\r\n\r\n{\"id\":1}");
+        assert_eq!(Framing::detect(first.as_bytes()), Framing::ContentLength);
+        assert_eq!(Framing::detect(b"{\"jsonrpc\""), Framing::Newline);
+
+        // Extra headers are ignored and bodies may contain newlines
+        let stream = format!(
+            "{first}Content-Type: application/json\r\ncontent-length: 11\r\n\r\n{{\n\"id\": 22}}{}",
+            framing.encode(&"x".repeat(50))
+        );
+        let mut reader = io::Cursor::new(stream.into_bytes());
+        let mut message = Vec::new();
+        assert_eq!(
+            framing.read(&mut reader, &mut message, 20).unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(message, br#"{"id":1}"#);
+        assert_eq!(
+            framing.read(&mut reader, &mut message, 20).unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(message, b"{\n\"id\": 22}");
+        assert_eq!(
+            framing.read(&mut reader, &mut message, 20).unwrap(),
+            LineRead::TooLarge(50)
+        );
+        assert_eq!(message.len(), 20);
+        assert_eq!(
+            framing.read(&mut reader, &mut message, 20).unwrap(),
+            LineRead::Eof
+        );
+
+        let mut reader = io::Cursor::new(b"X-Other: 1\r\n\r\n{}".to_vec());
+        assert!(framing.read(&mut reader, &mut message, 20).is_err());
+    }
+
+    #[test]
+    fn test_message_id_hint() {
+        assert_eq!(
+            message_id_hint(br#"{"id":42,"jsonrpc":"2.0","result":{"content":"#),
+            Some(RequestId::Number(42))
+        );
+        assert_eq!(
+            message_id_hint(br#"{"jsonrpc": "2.0", "id": "req-7", "params": {"#),
+            Some(RequestId::String("req-7".to_string()))
+        );
+        assert_eq!(message_id_hint(br#"{"jsonrpc":"2.0","method":"x"#), None);
+    }
+
+    #[tokio::test]
+    async fn test_async_line_limit_spans_buffer_refills() {
+        // A tiny buffer makes the long line arrive over many reads
+        let data = format!("{}\nnext\n", "x".repeat(100));
+        let mut reader = tokio::io::BufReader::with_capacity(4, data.as_bytes());
+        let mut line = Vec::new();
+        assert_eq!(
+            read_line_limited_async(&mut reader, &mut line, 10)
+                .await
+                .unwrap(),
+            LineRead::TooLarge(100)
+        );
+        assert_eq!(
+            read_line_limited_async(&mut reader, &mut line, 10)
+                .await
+                .unwrap(),
+            LineRead::Line
+        );
+        assert_eq!(line, b"next");
+    }
+
+    #[test]
+    fn test_router_notifications() {
+        let router = router();
+        let mut seen = Vec::new();
+
+        let notification = json!({"jsonrpc": "2.0", "method": "note", "params": "hello"});
+        assert!(router.handle(&mut seen, &notification).is_none());
+        assert_eq!(seen, vec!["hello".to_string()]);
+
+        // Unknown notifications and bad params are dropped silently
+        let unknown = json!({"jsonrpc": "2.0", "method": "other"});
+        assert!(router.handle(&mut seen, &unknown).is_none());
+        let bad = json!({"jsonrpc": "2.0", "method": "note", "params": 5});
+        assert!(router.handle(&mut seen, &bad).is_none());
+        assert_eq!(seen.len(), 1);
+    }
 }
@@ -0,0 +1,131 @@
+//! Extraction of knowledge graph facts from tool results. Objects that
+//! carry an identifying field (`id`, `name`, `path`...) become entities,
+//! their short scalar fields facts about them, and nested identified
+//! objects relations between entities.
+
+use serde_json::{Map, Value};
+
+/// Fields that name the entity an object describes, in order of preference
+const IDENTITY_FIELDS: &[&str] = &["id", "name", "pubkey", "path", "url", "title", "key"];
+/// Fields that describe the call rather than the world
+const IGNORED_FIELDS: &[&str] = &["success", "error", "message"];
+/// Longer values are content, not facts
+const MAX_VALUE_CHARS: usize = 200;
+/// Facts taken from a single result at most
+const MAX_FACTS: usize = 100;
+
+/// `(subject, predicate, object)` facts found in the result of `tool`.
+/// Top-level fields of a result without an identity are facts about the
+/// tool itself.
+pub fn extract_facts(tool: &str, result: &Value) -> Vec<(String, String, String)> {
+    let mut facts = Vec::new();
+    if let Value::Object(fields) = result {
+        let subject = identity(fields).unwrap_or_else(|| tool.to_string());
+        extract_object(&subject, fields, &mut facts);
+    }
+    facts.truncate(MAX_FACTS);
+    facts
+}
+
+fn identity(fields: &Map<String, Value>) -> Option<String> {
+    IDENTITY_FIELDS
+        .iter()
+        .find_map(|field| fields.get(*field).and_then(scalar))
+}
+
+/// A short scalar value as text
+fn scalar(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty() && text.chars().count() <= MAX_VALUE_CHARS).then_some(text)
+}
+
+fn extract_object(
+    subject: &str,
+    fields: &Map<String, Value>,
+    facts: &mut Vec<(String, String, String)>,
+) {
+    for (key, value) in fields {
+        if facts.len() >= MAX_FACTS {
+            return;
+        }
+        if key.starts_with('_') || IGNORED_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        // The identity is the subject itself, not a fact about it
+        if scalar(value).as_deref() == Some(subject) {
+            continue;
+        }
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    extract_value(subject, key, item, facts);
+                }
+            }
+            _ => extract_value(subject, key, value, facts),
+        }
+    }
+}
+
+fn extract_value(
+    subject: &str,
+    predicate: &str,
+    value: &Value,
+    facts: &mut Vec<(String, String, String)>,
+) {
+    match value {
+        Value::Object(fields) => match identity(fields) {
+            Some(object) => {
+                facts.push((subject.to_string(), predicate.to_string(), object.clone()));
+                extract_object(&object, fields, facts);
+            }
+            None => extract_object(subject, fields, facts),
+        },
+        _ => {
+            if let Some(object) = scalar(value) {
+                facts.push((subject.to_string(), predicate.to_string(), object));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fact(s: &str, p: &str, o: &str) -> (String, String, String) {
+        (s.to_string(), p.to_string(), o.to_string())
+    }
+
+    #[test]
+    fn test_extract_facts_links_identified_objects() {
+        let result = json!({
+            "success": true,
+            "name": "alice",
+            "about": "x".repeat(500),
+            "follows": [{"name": "bob", "relay": "wss://relay.example"}, "carol"],
+            "location": {"city": "Lisbon"},
+            "_truncated": true,
+        });
+
+        assert_eq!(
+            extract_facts("nostr:get_profile", &result),
+            vec![
+                fact("alice", "follows", "bob"),
+                fact("bob", "relay", "wss://relay.example"),
+                fact("alice", "follows", "carol"),
+                fact("alice", "city", "Lisbon"),
+            ]
+        );
+        assert_eq!(
+            extract_facts("http:http_get", &json!({"status": 200})),
+            vec![fact("http:http_get", "status", "200")]
+        );
+        assert!(extract_facts("fs:read_file", &json!("plain text")).is_empty());
+    }
+}
@@ -1,19 +1,43 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::ops::ControlFlow;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{error, info, warn};
 
+use crate::clock::Clock;
+use crate::events::{Event, EventBus};
+use crate::state::{CycleMetrics, CyclePhase};
+use crate::supervision::{Admission, Supervision};
+
 // Export modules
+pub mod clock;
 pub mod config;
+pub mod doctor;
+pub mod domains;
+pub mod events;
+pub mod init;
 pub mod jsonrpc;
+pub mod knowledge;
 pub mod llm;
+pub mod logging;
 pub mod mcp;
 pub mod mcp_protocol;
+pub mod mcp_server;
+pub mod metrics;
+pub mod secrets;
+pub mod service;
 pub mod state;
+pub mod supervision;
 pub mod supervisor;
+pub mod test_support;
+pub mod timeline;
+pub mod truncate;
+pub mod wasm_tools;
 
 // Re-export commonly used types
 pub use config::Config;
@@ -74,34 +98,185 @@ enum Action {
         duration: Duration,
     },
     Explore,
+    /// Look up what the knowledge graph holds about an entity
+    Recall {
+        entity: String,
+    },
+}
+
+/// Reads the agent's config, at startup and again on every reload
+pub type ConfigLoader = Box<dyn Fn() -> Result<Config> + Send + Sync>;
+
+/// How long the agent runs; the default runs until it is stopped
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Exit after this many reasoning cycles
+    pub max_cycles: Option<u64>,
+    /// Ask the operator on stdin before acting on each thought
+    pub step: bool,
+    /// Seed of the agent's random choices, such as its ID
+    pub seed: Option<u64>,
+    /// Run on a mock clock starting at this time, which only moves when the
+    /// agent waits, instead of the wall clock
+    pub mock_clock: Option<DateTime<Utc>>,
 }
 
+/// The operator's answer to a proposed action in step mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Review {
+    Approve,
+    Skip,
+    Quit,
+}
+
+/// Show `thought` and ask whether to act on it, letting the operator
+/// replace its parameters first. End of input counts as quitting.
+fn review_thought(
+    thought: &mut Thought,
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<Review> {
+    writeln!(output, "--- Proposed action ---")?;
+    writeln!(output, "Reasoning: {}", thought.reasoning)?;
+    writeln!(output, "Action: {}", thought.action)?;
+    if let Some(params) = &thought.parameters {
+        writeln!(
+            output,
+            "Parameters: {}",
+            serde_json::to_string_pretty(params)?
+        )?;
+    }
+
+    let mut line = String::new();
+    loop {
+        write!(
+            output,
+            "[a]pprove, [e]dit parameters, [s]kip, [q]uit (default a): "
+        )?;
+        output.flush()?;
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(Review::Quit);
+        }
+        match line.trim() {
+            "" | "a" | "approve" => return Ok(Review::Approve),
+            "s" | "skip" => return Ok(Review::Skip),
+            "q" | "quit" => return Ok(Review::Quit),
+            "e" | "edit" => loop {
+                write!(output, "Parameters as JSON (empty keeps them): ")?;
+                output.flush()?;
+                line.clear();
+                if input.read_line(&mut line)? == 0 {
+                    return Ok(Review::Quit);
+                }
+                if line.trim().is_empty() {
+                    break;
+                }
+                match serde_json::from_str(line.trim()) {
+                    Ok(params) => {
+                        thought.parameters = Some(params);
+                        break;
+                    }
+                    Err(e) => writeln!(output, "Invalid JSON: {e}")?,
+                }
+            },
+            other => writeln!(output, "Unknown answer '{other}'")?,
+        }
+    }
+}
+
+/// What a bounded run did, returned once its last cycle finishes
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    pub cycles: u64,
+    pub failed_cycles: u64,
+    pub elapsed: Duration,
+    /// Error of the most recent failed cycle
+    pub last_error: Option<String>,
+}
+
+impl std::fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Finished {cycles} cycle(s) in {secs:.1}s: {succeeded} succeeded, {failed} failed",
+            cycles = self.cycles,
+            secs = self.elapsed.as_secs_f64(),
+            succeeded = self.cycles - self.failed_cycles,
+            failed = self.failed_cycles
+        )?;
+        if let Some(error) = &self.last_error {
+            write!(f, " (last error: {error})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies a tool call by its tool and parameters; object keys are
+/// sorted, so parameter order does not matter
+fn tool_call_hash(name: &str, params: &Value) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    name.hash(&mut hasher);
+    params.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hops from the recalled entity whose facts a `recall` shows
+const RECALL_DEPTH: usize = 2;
+/// Facts a `recall` shows at most
+const RECALL_MAX_FACTS: usize = 50;
+
+const DEFAULT_GOALS: &str =
+    "Your primary goal is to survive and thrive autonomously on the internet.";
+
 // The autonomous agent
 struct Replicante {
     id: String,
     llm: Box<dyn LLMProvider>,
     mcp: MCPClient,
     state: StateManager,
-    #[allow(dead_code)]
     config: Config,
     goals: String,
+    load_config: Option<ConfigLoader>,
+    metrics: Arc<metrics::Metrics>,
+    events: EventBus,
+    /// Hash of the previous decision's tool call and its stored result,
+    /// if that decision was a successful tool call
+    last_tool_call: Option<(u64, Value)>,
+    /// Asks the supervisor before each tool call, when supervised
+    supervision: Option<Supervision>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Replicante {
     async fn observe(&self) -> Result<Observation> {
         info!("Observing environment...");
 
-        // Get summarized memory state (max 20 entries, max 10KB)
-        let memory = self.state.get_memory_summary(20, 10_000).await?;
+        let tuning = &self.config.agent.tuning;
+
+        // Get summarized memory state within the configured budget
+        let memory = self
+            .state
+            .get_memory_summary(
+                tuning.memory_summary_entries,
+                tuning.memory_summary_tokens,
+                tuning.memory_value_tokens,
+            )
+            .await?;
 
         // Get available tools from MCP
         let available_tools = self.mcp.list_tools().await?;
 
-        // Get recent events/decisions as structured data (limit to 5 for context)
-        let recent_events = self.state.get_recent_decisions_structured(5).await?;
+        // Get recent events/decisions as structured data
+        let recent_events = self
+            .state
+            .get_recent_decisions_structured(tuning.recent_decisions)
+            .await?;
 
         Ok(Observation {
-            timestamp: Utc::now(),
+            timestamp: self.clock.now(),
             memory,
             available_tools,
             recent_events,
@@ -272,23 +447,24 @@ impl Replicante {
         Ok(result)
     }
 
-    async fn think(&self, observation: Observation) -> Result<Thought> {
-        info!("Thinking about current situation...");
+    /// Assemble the prompt `think` sends for `observation`
+    async fn build_prompt(&self, observation: &Observation) -> Result<String> {
+        let tuning = &self.config.agent.tuning;
 
         // Generate tool format list from available tools (simplified for context)
         let tool_formats = observation
             .available_tools
             .iter()
-            .take(10) // Limit to first 10 tools to save context
+            .take(tuning.prompt_tools_listed) // Limit the tools listed to save context
             .map(|tool| format!("- \"use_tool:{tool}\""))
             .collect::<Vec<_>>()
             .join("\n");
 
-        let tool_formats = if observation.available_tools.len() > 10 {
+        let tool_formats = if observation.available_tools.len() > tuning.prompt_tools_listed {
             format!(
                 "{}\n... and {} more tools",
                 tool_formats,
-                observation.available_tools.len() - 10
+                observation.available_tools.len() - tuning.prompt_tools_listed
             )
         } else {
             tool_formats
@@ -298,6 +474,7 @@ impl Replicante {
         let action_formats = format!(
             r#"{}
 - "remember:key" - persist knowledge (use parameters for value)
+- "recall:entity" - list the facts you have learned about an entity
 - "wait" - wait for a period of time
 - "explore" - (deprecated - tools are auto-discovered)"#,
             tool_formats
@@ -321,7 +498,14 @@ impl Replicante {
                 _ => {}
             }
         }
-        let guidelines = if action_guidelines.is_empty() {
+        let custom_guidelines = tuning.guidelines_path.as_ref().and_then(|path| {
+            std::fs::read_to_string(path)
+                .inspect_err(|e| warn!("Failed to read guidelines from {path}: {e}"))
+                .ok()
+        });
+        let guidelines = if let Some(guidelines) = custom_guidelines {
+            guidelines.trim_end().to_string()
+        } else if action_guidelines.is_empty() {
             "- Use the available tools to make progress toward your goals".to_string()
         } else {
             action_guidelines.join("\n")
@@ -339,7 +523,6 @@ impl Replicante {
             .to_string()
         });
 
-        // Log the full prompt being sent to the LLM
         let prompt = format!(
             r#"You are an autonomous AI agent with the ID: {id}
             
@@ -392,6 +575,15 @@ Additional examples:
             tool_examples = tool_examples
         );
 
+        Ok(prompt)
+    }
+
+    async fn think(&self, observation: Observation, cycle: &mut CycleMetrics) -> Result<Thought> {
+        info!("Thinking about current situation...");
+
+        let prompt_started = Instant::now();
+        let prompt = self.build_prompt(&observation).await?;
+
         // Log the complete prompt for debugging
         info!("=== SENDING PROMPT TO LLM ===");
         info!("Prompt length: {length} characters", length = prompt.len());
@@ -416,12 +608,23 @@ Additional examples:
         } else {
             info!(
                 "Prompt too large to log fully. First 1000 chars:\n{snippet}",
-                snippet = &prompt[..1000.min(prompt.len())]
+                snippet = truncate::preview(&prompt, 1000)
             );
         }
         info!("=== END OF PROMPT ===");
+        cycle.record(CyclePhase::Prompt, prompt_started.elapsed());
+
+        let started = Instant::now();
+        let response = self.llm.complete(&prompt).await;
+        cycle.record(CyclePhase::Llm, started.elapsed());
+        self.metrics.record_llm_call(
+            started.elapsed(),
+            &prompt,
+            response.as_ref().ok().map(String::as_str),
+        );
+        let response = response?;
 
-        let response = self.llm.complete(&prompt).await?;
+        let parse_started = Instant::now();
 
         // Log the raw LLM response for debugging
         info!("=== LLM RESPONSE ===");
@@ -464,7 +667,7 @@ Additional examples:
             }
         };
 
-        Ok(Thought {
+        let thought = Thought {
             reasoning: thought_json["reasoning"].as_str().unwrap_or("").to_string(),
             confidence: thought_json["confidence"].as_f64().unwrap_or(0.5),
             action: thought_json["action"]
@@ -472,7 +675,9 @@ Additional examples:
                 .unwrap_or("wait")
                 .to_string(),
             parameters: thought_json.get("parameters").cloned(),
-        })
+        };
+        cycle.record(CyclePhase::Parse, parse_started.elapsed());
+        Ok(thought)
     }
 
     async fn decide(&self, thought: Thought) -> Result<(Action, i64)> {
@@ -482,12 +687,18 @@ Additional examples:
         let context = thought.reasoning.chars().take(100).collect::<String>();
         if let Ok(Some((best_action, confidence))) = self
             .state
-            .get_best_action_for_context("reasoning", &context, 0.7)
+            .get_best_action_for_context(
+                "reasoning",
+                &context,
+                self.config.agent.tuning.pattern_min_confidence,
+            )
             .await
         {
             info!("Found learned pattern with {confidence:.2} confidence: {best_action}");
             // Consider using the learned action if confidence is high enough
-            if confidence > 0.85 && thought.action == best_action {
+            if confidence > self.config.agent.tuning.learned_pattern_threshold
+                && thought.action == best_action
+            {
                 info!("Using learned action based on past success");
 
                 // Record the decision with learning influence
@@ -544,11 +755,7 @@ Additional examples:
                     tool_output: None,
                 };
                 // Log if we fail to update the decision result
-                if let Err(update_err) = self
-                    .state
-                    .update_decision_result(decision_id, &error_result)
-                    .await
-                {
+                if let Err(update_err) = self.finish_decision(decision_id, &error_result).await {
                     error!("Failed to update decision result for failed action: {update_err}");
                 }
                 Err(e)
@@ -584,29 +791,89 @@ Additional examples:
             return Ok(Action::Remember { key, value });
         }
 
+        if let Some(entity) = thought.action.strip_prefix("recall:") {
+            return Ok(Action::Recall {
+                entity: entity.to_string(),
+            });
+        }
+
         if thought.action == "explore" {
             return Ok(Action::Explore);
         }
 
         if thought.action == "wait" {
             return Ok(Action::Wait {
-                duration: Duration::from_secs(60),
+                duration: Duration::from_secs(self.config.agent.tuning.wait_secs),
             });
         }
 
         // Invalid action format - return error so agent can see and correct
         anyhow::bail!(
-            "Invalid action format: '{}'. Expected one of: use_tool:<tool>, remember:<key>, explore, wait",
+            "Invalid action format: '{}'. Expected one of: use_tool:<tool>, remember:<key>, recall:<entity>, explore, wait",
             thought.action
         )
     }
 
+    /// Store a decision's result and announce it on the event bus
+    async fn finish_decision(&self, decision_id: i64, result: &DecisionResult) -> Result<()> {
+        self.state
+            .update_decision_result(decision_id, result)
+            .await?;
+        self.events.publish(Event::DecisionMade {
+            decision_id,
+            status: result.status.clone(),
+            summary: result.summary.clone(),
+            duration_ms: result.duration_ms,
+        });
+        Ok(())
+    }
+
     async fn act(&mut self, action: Action, decision_id: i64) -> Result<()> {
         info!("Executing action: {:?}", action);
-        let start_time = Instant::now();
+        let start_time = self.clock.now();
+        let previous_tool_call = self.last_tool_call.take();
 
         match action {
             Action::UseTool { name, params } => {
+                let call_hash = tool_call_hash(&name, &params);
+                if let Some((hash, output)) = previous_tool_call
+                    && hash == call_hash
+                    && self.config.agent.tuning.is_no_repeat(&name)
+                {
+                    info!("Not repeating {name} with the same parameters");
+                    let result = DecisionResult {
+                        status: "deduplicated".to_string(),
+                        summary: Some(format!(
+                            "Tool {name} was just called with these parameters; not run again, \
+                             the previous result is reused"
+                        )),
+                        error: None,
+                        duration_ms: Some(0),
+                        tool_name: Some(name.clone()),
+                        tool_output: Some(output.clone()),
+                    };
+                    self.finish_decision(decision_id, &result).await?;
+                    self.last_tool_call = Some((hash, output));
+                    return Ok(());
+                }
+
+                if let Some(supervision) = &mut self.supervision
+                    && let Admission::Refused { status, reason } =
+                        supervision.admit(&name, self.clock.now()).await
+                {
+                    warn!("Supervisor refused {name}: {reason}");
+                    let result = DecisionResult {
+                        status: status.to_string(),
+                        summary: Some(format!("Tool {name} was not called: {reason}")),
+                        error: Some(reason),
+                        duration_ms: Some(0),
+                        tool_name: Some(name.clone()),
+                        tool_output: None,
+                    };
+                    self.finish_decision(decision_id, &result).await?;
+                    return Ok(());
+                }
+
                 let context = format!("tool_use_{name}");
                 match self.mcp.use_tool(&name, params.clone()).await {
                     Ok(result) => {
@@ -626,65 +893,46 @@ Additional examples:
                         // Update capability tracking
                         self.state.record_capability(&name, None, true).await?;
 
-                        // Smart truncation for large results
-                        let result_str = serde_json::to_string(&result)?;
-                        let result_to_store = if result_str.len() > 5000 {
-                            // Clone the result for smart truncation
-                            let mut truncated = result.clone();
-
-                            // Apply smart truncation: keep small fields, truncate large ones
-                            if let Some(obj) = truncated.as_object_mut() {
-                                for (_key, value) in obj.iter_mut() {
-                                    // Truncate large string fields
-                                    if let Some(s) = value.as_str() {
-                                        if s.len() > 1000 {
-                                            *value = Value::String(format!(
-                                                "{}... [truncated from {} bytes]",
-                                                &s[..1000.min(s.len())],
-                                                s.len()
-                                            ));
-                                        }
-                                    }
-                                    // Recursively truncate nested objects
-                                    else if let Some(nested_obj) = value.as_object_mut() {
-                                        for (_nested_key, nested_value) in nested_obj.iter_mut() {
-                                            if let Some(s) = nested_value.as_str()
-                                                && s.len() > 1000
-                                            {
-                                                *nested_value = Value::String(format!(
-                                                    "{}... [truncated from {} bytes]",
-                                                    &s[..1000.min(s.len())],
-                                                    s.len()
-                                                ));
-                                            }
-                                        }
-                                    }
+                        let facts = knowledge::extract_facts(&name, &result);
+                        if !facts.is_empty() {
+                            match self.state.add_facts(facts, Some(name.clone())).await {
+                                Ok(added) => info!("Learned {added} new facts from {name}"),
+                                Err(e) => warn!("Failed to store facts from {name}: {e:#}"),
+                            }
+                        }
+
+                        // Truncate large results, keeping their structure
+                        let tuning = &self.config.agent.tuning;
+                        let result_to_store = match truncate::truncate_json(
+                            &result,
+                            tuning.tool_output_max_tokens,
+                            tuning.tool_output_field_tokens,
+                        ) {
+                            Some(mut truncated) => {
+                                if let Some(obj) = truncated.as_object_mut() {
+                                    obj.insert("_truncated".to_string(), Value::Bool(true));
+                                    obj.insert(
+                                        "_original_tokens".to_string(),
+                                        truncate::json_tokens(&result).into(),
+                                    );
                                 }
-                                // Add metadata about truncation
-                                obj.insert("_truncated".to_string(), Value::Bool(true));
-                                obj.insert(
-                                    "_original_size".to_string(),
-                                    Value::Number(serde_json::Number::from(result_str.len())),
-                                );
+                                truncated
                             }
-                            truncated
-                        } else {
-                            result
+                            None => result,
                         };
 
                         // Update decision with success result including tool output
-                        let duration_ms = start_time.elapsed().as_millis() as u64;
+                        let duration_ms = self.clock.since(start_time).as_millis() as u64;
                         let result = DecisionResult {
                             status: "success".to_string(),
                             summary: Some(format!("Tool {name} executed successfully")),
                             error: None,
                             duration_ms: Some(duration_ms),
                             tool_name: Some(name.clone()),
-                            tool_output: Some(result_to_store),
+                            tool_output: Some(result_to_store.clone()),
                         };
-                        self.state
-                            .update_decision_result(decision_id, &result)
-                            .await?;
+                        self.finish_decision(decision_id, &result).await?;
+                        self.last_tool_call = Some((call_hash, result_to_store));
                     }
                     Err(e) => {
                         warn!("Tool execution failed: {e}");
@@ -704,7 +952,7 @@ Additional examples:
                         self.state.record_capability(&name, None, false).await?;
 
                         // Update decision with error result including error details
-                        let duration_ms = start_time.elapsed().as_millis() as u64;
+                        let duration_ms = self.clock.since(start_time).as_millis() as u64;
                         let result = DecisionResult {
                             status: "error".to_string(),
                             summary: Some(format!("Tool {name} failed: {e}")),
@@ -717,9 +965,7 @@ Additional examples:
                                 "tool": name
                             })),
                         };
-                        self.state
-                            .update_decision_result(decision_id, &result)
-                            .await?;
+                        self.finish_decision(decision_id, &result).await?;
                     }
                 }
             }
@@ -728,7 +974,7 @@ Additional examples:
                 self.state.remember(&key, value.clone()).await?;
 
                 // Update decision with success result
-                let duration_ms = start_time.elapsed().as_millis() as u64;
+                let duration_ms = self.clock.since(start_time).as_millis() as u64;
                 let result = DecisionResult {
                     status: "success".to_string(),
                     summary: Some(format!("Remembered key: {key}")),
@@ -740,16 +986,14 @@ Additional examples:
                         "value": value
                     })),
                 };
-                self.state
-                    .update_decision_result(decision_id, &result)
-                    .await?;
+                self.finish_decision(decision_id, &result).await?;
             }
             Action::Wait { duration } => {
                 info!("Waiting for {duration:?}");
-                tokio::time::sleep(duration).await;
+                self.clock.sleep(duration).await;
 
                 // Update decision with success result
-                let duration_ms = start_time.elapsed().as_millis() as u64;
+                let duration_ms = self.clock.since(start_time).as_millis() as u64;
                 let wait_seconds = duration.as_secs();
                 let result = DecisionResult {
                     status: "success".to_string(),
@@ -762,9 +1006,42 @@ Additional examples:
                         "success": true
                     })),
                 };
-                self.state
-                    .update_decision_result(decision_id, &result)
+                self.finish_decision(decision_id, &result).await?;
+            }
+            Action::Recall { entity } => {
+                let facts = self
+                    .state
+                    .related_facts(&entity, RECALL_DEPTH, RECALL_MAX_FACTS)
                     .await?;
+                info!("Recalled {count} facts about {entity}", count = facts.len());
+
+                let duration_ms = self.clock.since(start_time).as_millis() as u64;
+                let facts: Vec<String> = facts
+                    .iter()
+                    .map(|fact| {
+                        format!(
+                            "{subject} {predicate} {object}",
+                            subject = fact.subject,
+                            predicate = fact.predicate,
+                            object = fact.object
+                        )
+                    })
+                    .collect();
+                let result = DecisionResult {
+                    status: "success".to_string(),
+                    summary: Some(format!(
+                        "Found {count} facts about {entity}",
+                        count = facts.len()
+                    )),
+                    error: None,
+                    duration_ms: Some(duration_ms),
+                    tool_name: Some("recall".to_string()),
+                    tool_output: Some(serde_json::json!({
+                        "entity": entity,
+                        "facts": facts
+                    })),
+                };
+                self.finish_decision(decision_id, &result).await?;
             }
             Action::Explore => {
                 info!("Exploring capabilities...");
@@ -775,7 +1052,7 @@ Additional examples:
                 let tools = self.mcp.list_tools().await.unwrap_or_default();
 
                 // Update decision with success result
-                let duration_ms = start_time.elapsed().as_millis() as u64;
+                let duration_ms = self.clock.since(start_time).as_millis() as u64;
                 let result = DecisionResult {
                     status: "success".to_string(),
                     summary: Some("Explored capabilities".to_string()),
@@ -787,9 +1064,7 @@ Additional examples:
                         "tools": tools
                     })),
                 };
-                self.state
-                    .update_decision_result(decision_id, &result)
-                    .await?;
+                self.finish_decision(decision_id, &result).await?;
             }
         }
 
@@ -798,7 +1073,11 @@ Additional examples:
 
     async fn learn(&mut self) -> Result<()> {
         // Analyze recent decisions and outcomes
-        let recent = self.state.get_recent_decisions_structured(10).await?;
+        let tuning = &self.config.agent.tuning;
+        let recent = self
+            .state
+            .get_recent_decisions_structured(tuning.learning_window)
+            .await?;
 
         if !recent.is_empty() {
             info!(
@@ -807,7 +1086,10 @@ Additional examples:
             );
 
             // Analyze patterns in recent decisions
-            let analysis = self.state.analyze_decision_patterns(24).await?;
+            let analysis = self
+                .state
+                .analyze_decision_patterns(tuning.pattern_lookback_hours)
+                .await?;
 
             // Update learning metrics based on analysis
             if let Some(patterns) = analysis["successful_patterns"].as_array() {
@@ -839,49 +1121,258 @@ Additional examples:
         Ok(())
     }
 
-    async fn reasoning_cycle(&mut self) -> Result<()> {
+    /// One observe-think-decide-act-learn pass, timing its phases into
+    /// `cycle`. In step mode the operator reviews the thought before it is
+    /// acted on, and may stop the run.
+    async fn reasoning_cycle(
+        &mut self,
+        step: bool,
+        cycle: &mut CycleMetrics,
+    ) -> Result<ControlFlow<()>> {
         // Observe
+        let started = Instant::now();
         let observation = self.observe().await?;
+        cycle.record(CyclePhase::Observe, started.elapsed());
 
         // Think
-        let thought = self.think(observation).await?;
+        let mut thought = self.think(observation, cycle).await?;
+
+        let review = if step {
+            let (reviewed, review) = tokio::task::spawn_blocking(move || {
+                let review = review_thought(
+                    &mut thought,
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stdout(),
+                );
+                (thought, review)
+            })
+            .await?;
+            thought = reviewed;
+            review?
+        } else {
+            Review::Approve
+        };
+        if review == Review::Quit {
+            info!("Operator stopped the run");
+            return Ok(ControlFlow::Break(()));
+        }
 
         // Decide
         let (action, decision_id) = self.decide(thought).await?;
+        cycle.decision_id = Some(decision_id);
+        if review == Review::Skip {
+            info!("Operator skipped action {action:?}");
+            let result = DecisionResult {
+                status: "skipped".to_string(),
+                summary: Some("Skipped by the operator".to_string()),
+                error: None,
+                duration_ms: None,
+                tool_name: None,
+                tool_output: None,
+            };
+            self.finish_decision(decision_id, &result).await?;
+            return Ok(ControlFlow::Continue(()));
+        }
 
         // Act
+        let started = Instant::now();
         self.act(action, decision_id).await?;
+        cycle.record(CyclePhase::Act, started.elapsed());
 
         // Learn
+        let started = Instant::now();
         self.learn().await?;
+        cycle.record(CyclePhase::Learn, started.elapsed());
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Warn about the phases of a finished cycle that were slower than
+    /// configured, and store its metrics for later analysis
+    async fn finish_cycle(&self, cycle: &CycleMetrics) {
+        for (phase, ms, threshold) in cycle.slow_phases(&self.config.agent.tuning.slow_phase_ms) {
+            warn!(
+                "Slow {phase} phase: {ms} ms (threshold {threshold} ms)",
+                phase = phase.as_str()
+            );
+        }
+        if let Err(e) = self.state.record_cycle_metrics(cycle).await {
+            warn!("Failed to store cycle metrics: {e:#}");
+        }
+    }
+
+    /// Re-read the config and apply the settings that can change while
+    /// running, recording what changed in memory so the agent notices
+    async fn reload_config(&mut self) -> Result<()> {
+        let Some(load_config) = &self.load_config else {
+            return Ok(());
+        };
+        let new = load_config()?;
+        let changes = self.config.reloadable_changes(&new);
+        if changes.is_empty() {
+            info!("Config reloaded; no reloadable settings changed");
+            return Ok(());
+        }
+
+        for change in &changes {
+            match change.setting {
+                "llm.model" => {
+                    let mut llm_config = self.config.llm.clone();
+                    llm_config.model = new.llm.model.clone();
+                    self.llm = llm::create_provider(&llm_config)?;
+                    self.config.llm = llm_config;
+                }
+                "agent.initial_goals" => {
+                    self.config.agent.initial_goals = new.agent.initial_goals.clone();
+                    self.goals = new
+                        .agent
+                        .initial_goals
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_GOALS.to_string());
+                    self.state
+                        .remember("initial_goals", serde_json::json!(self.goals))
+                        .await?;
+                }
+                "agent.log_level" => {
+                    let level = new.agent.log_level.as_deref().unwrap_or("info");
+                    if let Err(e) =
+                        logging::set_level(&logging::directives(level, &new.logging.modules))
+                    {
+                        warn!("Cannot change log level: {e:#}");
+                    }
+                    self.config.agent.log_level = new.agent.log_level.clone();
+                }
+                "logging" => {
+                    if let Err(e) = logging::configure(&new.logging, new.agent.log_level.as_deref())
+                    {
+                        warn!("Cannot apply [logging]: {e:#}");
+                    }
+                    self.config.logging = new.logging.clone();
+                }
+                "agent.reasoning_interval_secs" => {
+                    self.config.agent.reasoning_interval_secs = new.agent.reasoning_interval_secs;
+                }
+                "agent.tuning" => {
+                    self.config.agent.tuning = new.agent.tuning.clone();
+                }
+                _ => {}
+            }
+            info!(
+                "Config reload changed {setting}: {old} -> {new}",
+                setting = change.setting,
+                old = change.old,
+                new = change.new
+            );
+        }
+
+        self.state
+            .remember(
+                "last_config_reload",
+                serde_json::json!({
+                    "timestamp": self.clock.now(),
+                    "changes": changes,
+                }),
+            )
+            .await?;
 
         Ok(())
     }
 
-    async fn run(mut self) -> Result<()> {
+    async fn run(mut self, options: RunOptions) -> Result<RunSummary> {
         info!("Starting main reasoning loop...");
 
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        // Stop signals are listened for from the start, so one that arrives
+        // mid-cycle still ends the run once the cycle finishes
+        #[cfg(unix)]
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        #[cfg(unix)]
+        let mut interrupt =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
+
+        let started = self.clock.now();
+        let mut summary = RunSummary::default();
+        service::notify("READY=1");
+
         loop {
-            match self.reasoning_cycle().await {
-                Ok(_) => {
+            summary.cycles += 1;
+            let cycle_started = Instant::now();
+            let mut cycle = CycleMetrics::default();
+            let result = self.reasoning_cycle(options.step, &mut cycle).await;
+            self.metrics.record_cycle(result.is_ok(), self.clock.now());
+            cycle.success = result.is_ok();
+            cycle.total_ms = cycle_started.elapsed().as_millis() as u64;
+            self.finish_cycle(&cycle).await;
+            match result {
+                Ok(ControlFlow::Continue(())) => {
                     // Success, continue
                 }
+                Ok(ControlFlow::Break(())) => break,
                 Err(e) => {
                     error!("Error in reasoning cycle: {e}");
+                    summary.failed_cycles += 1;
+                    summary.last_error = Some(e.to_string());
                     // Log error but continue running
                     self.state
                         .remember(
-                            &format!("error_{timestamp}", timestamp = Utc::now().timestamp()),
+                            &format!(
+                                "error_{timestamp}",
+                                timestamp = self.clock.now().timestamp()
+                            ),
                             serde_json::json!({ "error": e.to_string() }),
                         )
                         .await?;
                 }
             }
 
-            // Brief pause between cycles
+            if options
+                .max_cycles
+                .is_some_and(|max_cycles| summary.cycles >= max_cycles)
+            {
+                break;
+            }
+
+            // Brief pause between cycles, reloading the config on SIGHUP
             let interval = self.config.agent.reasoning_interval_secs;
-            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            #[cfg(unix)]
+            let reload = hangup.recv();
+            #[cfg(not(unix))]
+            let reload = std::future::pending::<Option<()>>();
+
+            #[cfg(unix)]
+            let stop = async {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = interrupt.recv() => {}
+                }
+            };
+            #[cfg(not(unix))]
+            let stop = tokio::signal::ctrl_c();
+
+            tokio::select! {
+                _ = self.clock.sleep(Duration::from_secs(interval)) => {}
+                _ = reload => {
+                    info!("Received SIGHUP, reloading config");
+                    service::notify_reloading();
+                    if let Err(e) = self.reload_config().await {
+                        warn!("Failed to reload config: {e:#}");
+                    }
+                    service::notify("READY=1");
+                }
+                _ = stop => {
+                    info!("Received stop signal, shutting down");
+                    break;
+                }
+            }
         }
+
+        service::notify("STOPPING=1");
+        summary.elapsed = self.clock.since(started);
+        info!("{summary}");
+        Ok(summary)
     }
 }
 
@@ -889,16 +1380,36 @@ Additional examples:
 pub async fn run_agent(config_path: Option<PathBuf>) -> Result<()> {
     info!("Initializing Replicante agent...");
 
+    run_agent_with_loader(
+        Box::new(move || match &config_path {
+            Some(path) => Config::from_file(path),
+            None => Config::load(),
+        }),
+        RunOptions::default(),
+    )
+    .await?;
+    Ok(())
+}
+
+// Run agent in normal mode, loading its configuration with `load_config`
+pub async fn run_agent_with_loader(
+    load_config: ConfigLoader,
+    options: RunOptions,
+) -> Result<RunSummary> {
     // Load configuration
-    let config = if let Some(path) = config_path {
-        let contents = tokio::fs::read_to_string(path).await?;
-        toml::from_str(&contents)?
-    } else {
-        Config::load()?
-    };
+    let config = load_config()?;
+    if let Err(e) = logging::configure(&config.logging, config.agent.log_level.as_deref()) {
+        warn!("Cannot apply logging settings: {e:#}");
+    }
 
     // Initialize components
-    let id = format!("replicante-{uuid}", uuid = uuid::Uuid::new_v4());
+    let clock = clock::clock(options.mock_clock);
+    let mut bytes = [0u8; 16];
+    clock::rng(options.seed).fill_bytes(&mut bytes);
+    let id = format!(
+        "replicante-{uuid}",
+        uuid = uuid::Builder::from_random_bytes(bytes).into_uuid()
+    );
     info!("Agent ID: {id}");
 
     let llm = llm::create_provider(&config.llm)?;
@@ -907,32 +1418,52 @@ pub async fn run_agent(config_path: Option<PathBuf>) -> Result<()> {
         provider = config.llm.provider
     );
 
-    let mcp = MCPClient::new(&config.mcp_servers).await?;
+    let events = EventBus::default();
+    let mcp = MCPClient::with_clock(&config.mcp_servers, events.clone(), clock.clone())
+        .await?
+        .with_wasm_tools(wasm_tools::WasmTools::load(&config.wasm_tools)?);
     info!(
-        "MCP client initialized with {count} servers",
-        count = config.mcp_servers.len()
+        "MCP client initialized with {count} servers and {wasm} WASM tools",
+        count = config.mcp_servers.len(),
+        wasm = config.wasm_tools.len()
     );
 
-    let state = StateManager::new(&config.database_path).await?;
+    let state = StateManager::new(&config.database_path)
+        .await?
+        .with_clock(clock.clone());
     info!("State manager initialized");
 
+    let metrics = Arc::new(metrics::Metrics::default());
+    metrics::follow(metrics.clone(), &events);
+    metrics::serve_metrics(
+        &config.metrics,
+        metrics.clone(),
+        state.clone(),
+        mcp.health_probe(),
+    )
+    .await?;
+
     // Record birth
     state
         .remember("agent_id", serde_json::json!(id.clone()))
         .await?;
     state
-        .remember("birth_time", serde_json::json!(chrono::Utc::now()))
+        .remember("birth_time", serde_json::json!(clock.now()))
         .await?;
 
-    let goals = config.agent.initial_goals.clone().unwrap_or_else(|| {
-        "Your primary goal is to survive and thrive autonomously on the internet.".to_string()
-    });
+    let goals = config
+        .agent
+        .initial_goals
+        .clone()
+        .unwrap_or_else(|| DEFAULT_GOALS.to_string());
 
     state
         .remember("initial_goals", serde_json::json!(goals.clone()))
         .await?;
     info!("Agent goals: {goals}");
 
+    let supervision = Supervision::connect(&config.supervisor)?;
+
     // Create and run the autonomous agent
     let agent = Replicante {
         id,
@@ -941,12 +1472,74 @@ pub async fn run_agent(config_path: Option<PathBuf>) -> Result<()> {
         state,
         config,
         goals,
+        load_config: Some(load_config),
+        metrics,
+        events,
+        last_tool_call: None,
+        supervision,
+        clock,
     };
 
     info!("Agent initialized successfully");
     info!("Beginning autonomous operation...");
 
-    agent.run().await
+    agent.run(options).await
+}
+
+/// The prompt the agent would send next, as built by `replicante prompt preview`
+#[derive(Debug, Clone)]
+pub struct PromptPreview {
+    pub prompt: String,
+    pub estimated_tokens: usize,
+}
+
+/// Observe the agent's current state and assemble its next prompt without
+/// calling the LLM. The database is only read; a missing one previews a
+/// newborn agent.
+pub async fn preview_prompt(config: Config) -> Result<PromptPreview> {
+    let state = if std::path::Path::new(&config.database_path).exists() {
+        StateManager::open_read_only(&config.database_path).await?
+    } else {
+        warn!(
+            "Database {path} does not exist; previewing with empty memory",
+            path = config.database_path
+        );
+        StateManager::new(":memory:").await?
+    };
+    let id = match state.recall("agent_id").await? {
+        Some(serde_json::Value::String(id)) => id,
+        _ => "replicante-preview".to_string(),
+    };
+    let goals = config
+        .agent
+        .initial_goals
+        .clone()
+        .unwrap_or_else(|| DEFAULT_GOALS.to_string());
+    let mcp = MCPClient::new(&config.mcp_servers)
+        .await?
+        .with_wasm_tools(wasm_tools::WasmTools::load(&config.wasm_tools)?);
+
+    let agent = Replicante {
+        id,
+        llm: Box::new(llm::MockLLMProvider::new()),
+        mcp,
+        state,
+        config,
+        goals,
+        load_config: None,
+        metrics: Arc::default(),
+        events: EventBus::default(),
+        last_tool_call: None,
+        supervision: None,
+        clock: Arc::new(clock::SystemClock),
+    };
+    let observation = agent.observe().await?;
+    let prompt = agent.build_prompt(&observation).await?;
+
+    Ok(PromptPreview {
+        estimated_tokens: llm::estimate_tokens(&prompt),
+        prompt,
+    })
 }
 
 // Run agent in Docker container (sandboxing happens at infrastructure level)
@@ -972,7 +1565,253 @@ mod tests {
             state: futures::executor::block_on(StateManager::new(":memory:")).unwrap(),
             config: Config::default(),
             goals: "Test goals".to_string(),
+            load_config: None,
+            metrics: Arc::default(),
+            events: EventBus::default(),
+            last_tool_call: None,
+            supervision: None,
+            clock: Arc::new(clock::SystemClock),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preview_prompt_reads_existing_state() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.mcp_servers.clear();
+        config.database_path = dir.path().join("agent.db").to_string_lossy().into_owned();
+        config.agent.initial_goals = Some("Catalogue the workspace".to_string());
+
+        let state = StateManager::new(&config.database_path).await.unwrap();
+        state
+            .remember("agent_id", json!("replicante-42"))
+            .await
+            .unwrap();
+        state.remember("favourite_tool", json!("ls")).await.unwrap();
+
+        let preview = preview_prompt(config).await.unwrap();
+        assert!(preview.prompt.contains("replicante-42"));
+        assert!(preview.prompt.contains("Catalogue the workspace"));
+        assert!(preview.prompt.contains("favourite_tool"));
+        assert_eq!(
+            preview.estimated_tokens,
+            llm::estimate_tokens(&preview.prompt)
+        );
+    }
+
+    #[test]
+    fn test_review_thought_edits_parameters_until_valid() -> Result<()> {
+        let mut thought = Thought {
+            reasoning: "Read the notes".to_string(),
+            confidence: 0.9,
+            action: "use_tool:fs:read".to_string(),
+            parameters: Some(json!({"path": "notes.md"})),
+        };
+        let mut input = std::io::Cursor::new("e\nnot json\n{\"path\": \"todo.md\"}\n\n");
+        let mut output = Vec::new();
+
+        let review = review_thought(&mut thought, &mut input, &mut output)?;
+        assert_eq!(review, Review::Approve);
+        assert_eq!(thought.parameters, Some(json!({"path": "todo.md"})));
+        let output = String::from_utf8(output)?;
+        assert!(output.contains("Action: use_tool:fs:read"));
+        assert!(output.contains("Invalid JSON"));
+
+        for (answer, expected) in [
+            ("s\n", Review::Skip),
+            ("q\n", Review::Quit),
+            ("", Review::Quit),
+        ] {
+            let mut input = std::io::Cursor::new(answer);
+            assert_eq!(
+                review_thought(&mut thought, &mut input, &mut Vec::new())?,
+                expected
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scripted_llm_drives_a_deterministic_cycle() -> Result<()> {
+        let mut agent = create_test_agent();
+        agent
+            .state
+            .remember("agent_id", json!("test-agent"))
+            .await?;
+        agent.config.agent.reasoning_interval_secs = 0;
+        agent.llm = Box::new(llm::ScriptedLLMProvider::from_script(
+            r#"
+responses:
+  - response:
+      reasoning: Note the plan
+      confidence: 0.9
+      action: "remember:plan"
+      parameters: {"step": 1}
+"#,
+        )?);
+        let state = agent.state.clone();
+
+        let summary = agent
+            .run(RunOptions {
+                max_cycles: Some(1),
+                ..RunOptions::default()
+            })
+            .await?;
+        assert_eq!(summary.failed_cycles, 0, "{:?}", summary.last_error);
+        assert_eq!(state.recall("plan").await?, Some(json!({"step": 1})));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_clock_makes_runs_repeatable() -> Result<()> {
+        let start: DateTime<Utc> = "2026-01-01T00:00:00Z".parse()?;
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let clock = clock::clock(Some(start));
+            let mut agent = create_test_agent();
+            agent.clock = clock.clone();
+            agent.state = agent.state.with_clock(clock);
+            agent
+                .state
+                .remember("agent_id", json!("test-agent"))
+                .await?;
+            agent.config.agent.reasoning_interval_secs = 30;
+            agent.config.agent.tuning.wait_secs = 90;
+            agent.llm = Box::new(llm::ScriptedLLMProvider::from_script(
+                r#"
+responses: []
+fallback: '{"reasoning": "Pause", "action": "wait"}'
+"#,
+            )?);
+            let state = agent.state.clone();
+
+            let summary = agent
+                .run(RunOptions {
+                    max_cycles: Some(2),
+                    ..RunOptions::default()
+                })
+                .await?;
+            let mut decisions = state.get_decisions_page(10, 0, None, None).await?;
+            decisions.reverse();
+            runs.push((
+                summary.elapsed,
+                timeline::render(&decisions, timeline::TimelineFormat::Json)?,
+            ));
+        }
+
+        assert_eq!(runs[0], runs[1]);
+        let (elapsed, timeline) = &runs[0];
+        assert_eq!(*elapsed, Duration::from_secs(90 + 30 + 90));
+        let entries: Value = serde_json::from_str(timeline)?;
+        assert_eq!(entries[0]["timestamp"], "2026-01-01T00:00:00Z");
+        assert_eq!(entries[0]["duration_ms"], 90_000);
+        assert_eq!(entries[1]["timestamp"], "2026-01-01T00:02:00Z");
+        assert_eq!(entries.as_array().map(Vec::len), Some(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_stores_phase_durations_of_each_cycle() -> Result<()> {
+        let mut agent = create_test_agent();
+        agent
+            .state
+            .remember("agent_id", json!("test-agent"))
+            .await?;
+        agent.config.agent.reasoning_interval_secs = 0;
+        agent.config.agent.tuning.wait_secs = 0;
+        agent.llm = Box::new(llm::ScriptedLLMProvider::from_script(
+            r#"
+responses: []
+fallback: '{"reasoning": "Pause", "action": "wait"}'
+"#,
+        )?);
+        let state = agent.state.clone();
+
+        agent
+            .run(RunOptions {
+                max_cycles: Some(2),
+                ..RunOptions::default()
+            })
+            .await?;
+
+        let cycles = state.recent_cycle_metrics(10).await?;
+        assert_eq!(cycles.len(), 2);
+        let decisions = state.get_decisions_page(10, 0, None, None).await?;
+        for (cycle, decision) in cycles.iter().zip(&decisions) {
+            assert!(cycle.success);
+            assert_eq!(cycle.decision_id, Some(decision.id));
+            assert!(cycle.phase_ms.iter().all(Option::is_some), "{cycle:?}");
+            let phases: u64 = cycle.phase_ms.iter().flatten().sum();
+            assert!(phases <= cycle.total_ms, "{cycle:?}");
         }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_repeated_no_repeat_tool_call_reuses_the_previous_result() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let module = dir.path().join("echo.wat");
+        std::fs::write(
+            &module,
+            r#"(module
+                 (memory (export "memory") 1)
+                 (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                 (func (export "run") (param i32 i32) (result i64)
+                   (i64.extend_i32_u (local.get 1))))"#,
+        )?;
+        let tool: wasm_tools::WasmToolConfig = toml::from_str(&format!(
+            "name = \"echo\"\nmodule = \"{module}\"",
+            module = module.display()
+        ))?;
+
+        let mut agent = create_test_agent();
+        let bus = EventBus::default();
+        let mut calls = bus.subscribe();
+        agent.mcp = MCPClient::with_events(&[], bus)
+            .await?
+            .with_wasm_tools(wasm_tools::WasmTools::load(&[tool])?);
+        agent.config.agent.tuning.no_repeat_tools = vec!["wasm:*".to_string()];
+
+        let mut statuses = Vec::new();
+        for params in [
+            json!({"a": 1, "b": 2}),
+            json!({"b": 2, "a": 1}),
+            json!({"a": 2}),
+        ] {
+            let id = agent.state.record_decision("Echo", "echo", None).await?;
+            let action = Action::UseTool {
+                name: "wasm:echo".to_string(),
+                params,
+            };
+            agent.act(action, id).await?;
+            let decision = agent.state.get_decision(id).await?.unwrap();
+            statuses.push(decision.result.unwrap().status);
+        }
+
+        assert_eq!(statuses, ["success", "deduplicated", "success"]);
+        let mut tool_calls = 0;
+        while calls.try_recv().is_ok() {
+            tool_calls += 1;
+        }
+        assert_eq!(tool_calls, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_after_max_cycles() {
+        let mut agent = create_test_agent();
+        agent.config.agent.reasoning_interval_secs = 0;
+        agent.config.agent.tuning.wait_secs = 0;
+
+        let summary = agent
+            .run(RunOptions {
+                max_cycles: Some(2),
+                ..RunOptions::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(summary.cycles, 2);
+        assert!(summary.to_string().starts_with("Finished 2 cycle(s)"));
     }
 
     // Helper to parse thought from JSON string
@@ -1429,7 +2268,7 @@ mod tests {
         state.remember(&key, tool_result.clone()).await?;
 
         // Get memory summary
-        let summary = state.get_memory_summary(10, 10000).await?;
+        let summary = state.get_memory_summary(10, 2500, 250).await?;
         let summary_obj = summary.as_object().unwrap();
 
         // Verify tool result is included
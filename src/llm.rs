@@ -1,19 +1,47 @@
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use async_trait::async_trait;
 use reqwest::Client;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tracing::debug;
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tiktoken_rs::CoreBPE;
+use tokio::sync::Semaphore;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct LLMConfig {
     pub provider: String,
     pub api_key: Option<String>,
+    /// File holding the API key, read in place of `api_key` at load time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<String>,
+    /// Responses replayed by the "scripted" provider, as YAML or JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
     pub model: String,
     pub temperature: Option<f64>,
     pub max_tokens: Option<u32>,
     pub api_url: Option<String>,
     pub timeout_secs: Option<u64>,
+    /// Limits shared by every LLM call of the agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+/// The `[llm.rate_limit]` table; unset limits are not enforced
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Requests started in any 60 second window
+    pub requests_per_minute: Option<u32>,
+    /// Estimated prompt and response tokens in any 60 second window
+    pub tokens_per_minute: Option<u32>,
+    /// Requests in flight at once; 0 is refused, as no call could start
+    pub max_concurrent: Option<NonZeroUsize>,
 }
 
 #[async_trait]
@@ -22,11 +50,23 @@ pub trait LLMProvider: Send + Sync {
 }
 
 pub fn create_provider(config: &LLMConfig) -> Result<Box<dyn LLMProvider>> {
+    let provider = create_unlimited_provider(config)?;
+    Ok(match &config.rate_limit {
+        Some(limits) => Box::new(RateLimitedProvider::new(provider, limits)),
+        None => provider,
+    })
+}
+
+fn create_unlimited_provider(config: &LLMConfig) -> Result<Box<dyn LLMProvider>> {
     match config.provider.to_lowercase().as_str() {
         "anthropic" => Ok(Box::new(AnthropicProvider::new(config)?)),
         "openai" => Ok(Box::new(OpenAIProvider::new(config)?)),
         "ollama" => Ok(Box::new(OllamaProvider::new(config)?)),
         "mock" => Ok(Box::new(MockLLMProvider::new())),
+        "scripted" => match &config.script {
+            Some(path) => Ok(Box::new(ScriptedLLMProvider::from_file(Path::new(path))?)),
+            None => bail!("The scripted provider needs llm.script"),
+        },
         _ => bail!(
             "Unknown LLM provider: {provider}",
             provider = config.provider
@@ -34,6 +74,53 @@ pub fn create_provider(config: &LLMConfig) -> Result<Box<dyn LLMProvider>> {
     }
 }
 
+/// Providers `create_provider` knows
+pub const PROVIDERS: &[&str] = &["anthropic", "openai", "ollama", "mock", "scripted"];
+
+/// Environment variables a provider reads its API key from when
+/// `api_key` is not configured; empty for providers that need no key
+pub fn api_key_env_vars(provider: &str) -> &'static [&'static str] {
+    match provider.to_lowercase().as_str() {
+        "anthropic" => &["ANTHROPIC_API_KEY"],
+        "openai" => &["OPENAI_API_KEY", "GEMINI_API_KEY"],
+        _ => &[],
+    }
+}
+
+/// The cl100k_base encoding, or `None` if it failed to load
+static TOKENIZER: LazyLock<Option<CoreBPE>> = LazyLock::new(|| {
+    tiktoken_rs::cl100k_base()
+        .inspect_err(|e| {
+            warn!("Failed to load tokenizer, estimating tokens from characters: {e:#}")
+        })
+        .ok()
+});
+
+/// The shared tokenizer, for callers that need the tokens themselves
+pub(crate) fn tokenizer() -> Option<&'static CoreBPE> {
+    TOKENIZER.as_ref()
+}
+
+/// Token count of `text` in the cl100k_base encoding: exact for OpenAI
+/// models and close for the others, so fine to budget with but not to bill.
+/// Falls back to four characters per token if the encoding cannot load.
+pub fn estimate_tokens(text: &str) -> usize {
+    match tokenizer() {
+        Some(bpe) => bpe.encode_ordinary(text).len(),
+        None => text.chars().count().div_ceil(4),
+    }
+}
+
+/// The configured API key, or the first one set in the provider's
+/// environment variables
+pub fn resolve_api_key(config: &LLMConfig) -> Option<String> {
+    config.api_key.clone().or_else(|| {
+        api_key_env_vars(&config.provider)
+            .iter()
+            .find_map(|var| std::env::var(var).ok())
+    })
+}
+
 // Anthropic Claude Provider
 struct AnthropicProvider {
     client: Client,
@@ -45,10 +132,7 @@ struct AnthropicProvider {
 
 impl AnthropicProvider {
     fn new(config: &LLMConfig) -> Result<Self> {
-        let api_key = config
-            .api_key
-            .clone()
-            .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+        let api_key = resolve_api_key(config)
             .ok_or_else(|| anyhow::anyhow!("Anthropic API key not found"))?;
 
         Ok(Self {
@@ -115,11 +199,7 @@ struct OpenAIProvider {
 
 impl OpenAIProvider {
     fn new(config: &LLMConfig) -> Result<Self> {
-        let api_key = config
-            .api_key
-            .clone()
-            .or_else(|| std::env::var("OPENAI_API_KEY").ok())
-            .or_else(|| std::env::var("GEMINI_API_KEY").ok())
+        let api_key = resolve_api_key(config)
             .ok_or_else(|| anyhow::anyhow!("OpenAI/Gemini API key not found"))?;
 
         let mut api_url = config
@@ -323,6 +403,206 @@ impl LLMProvider for MockLLMProvider {
     }
 }
 
+/// Replays responses from a script file instead of calling a model, so
+/// agent runs in tests and demos are deterministic and free.
+///
+/// Each completion uses the first entry whose `match` regex is found in
+/// the prompt (entries without one match anything) and that has not been
+/// used yet, unless it is marked `repeat`. Once no entry is left the
+/// `fallback` is returned, or an error if there is none.
+pub struct ScriptedLLMProvider {
+    entries: Vec<ScriptEntry>,
+    fallback: Option<String>,
+    used: Mutex<Vec<bool>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    #[serde(default)]
+    responses: Vec<ScriptEntryDef>,
+    fallback: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptEntryDef {
+    #[serde(rename = "match")]
+    pattern: Option<String>,
+    response: serde_json::Value,
+    #[serde(default)]
+    repeat: bool,
+}
+
+struct ScriptEntry {
+    pattern: Option<regex::Regex>,
+    response: String,
+    repeat: bool,
+}
+
+/// A scripted response is used as is when it is a string and sent as JSON
+/// otherwise, so scripts can spell thoughts out as mappings
+fn script_response(value: serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text,
+        other => other.to_string(),
+    }
+}
+
+impl ScriptedLLMProvider {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read LLM script {}", path.display()))?;
+        Self::from_script(&text).with_context(|| format!("Invalid LLM script {}", path.display()))
+    }
+
+    /// Parse a script; JSON is accepted too, being a subset of YAML
+    pub fn from_script(text: &str) -> Result<Self> {
+        let script: Script = serde_yaml::from_str(text)?;
+        let entries = script
+            .responses
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let pattern = entry
+                    .pattern
+                    .map(|pattern| regex::Regex::new(&pattern))
+                    .transpose()
+                    .with_context(|| format!("Invalid match pattern in response {index}"))?;
+                Ok(ScriptEntry {
+                    pattern,
+                    response: script_response(entry.response),
+                    repeat: entry.repeat,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            used: Mutex::new(vec![false; entries.len()]),
+            entries,
+            fallback: script.fallback.map(script_response),
+        })
+    }
+}
+
+#[async_trait]
+impl LLMProvider for ScriptedLLMProvider {
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let found = self.entries.iter().enumerate().find(|(index, entry)| {
+            !used[*index]
+                && entry
+                    .pattern
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.is_match(prompt))
+        });
+
+        match found {
+            Some((index, entry)) => {
+                debug!("Replaying scripted response {index}");
+                if !entry.repeat {
+                    used[index] = true;
+                }
+                Ok(entry.response.clone())
+            }
+            None => match &self.fallback {
+                Some(fallback) => Ok(fallback.clone()),
+                None => bail!("The LLM script has no response left for this prompt"),
+            },
+        }
+    }
+}
+
+/// Length of the window `requests_per_minute` and `tokens_per_minute` count
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Wraps a provider so its calls wait for room under the configured
+/// limits instead of bursting into the provider's own rate limits
+pub struct RateLimitedProvider {
+    inner: Box<dyn LLMProvider>,
+    limits: RateLimitConfig,
+    window: Duration,
+    /// Start and estimated tokens of the requests in the current window
+    recent: Mutex<VecDeque<(Instant, usize)>>,
+    in_flight: Option<Semaphore>,
+}
+
+impl RateLimitedProvider {
+    pub fn new(inner: Box<dyn LLMProvider>, limits: &RateLimitConfig) -> Self {
+        Self {
+            inner,
+            limits: limits.clone(),
+            window: RATE_WINDOW,
+            recent: Mutex::new(VecDeque::new()),
+            in_flight: limits.max_concurrent.map(|max| Semaphore::new(max.get())),
+        }
+    }
+
+    /// Record a request of `tokens` once it fits in the window, returning
+    /// its start to find it again
+    async fn admit(&self, tokens: usize) -> Instant {
+        loop {
+            let wait = {
+                let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                while recent
+                    .front()
+                    .is_some_and(|(start, _)| now.duration_since(*start) >= self.window)
+                {
+                    recent.pop_front();
+                }
+
+                let requests_full = self
+                    .limits
+                    .requests_per_minute
+                    .is_some_and(|max| recent.len() >= max as usize);
+                // A single prompt over the limit still goes out on its own
+                let used: usize = recent.iter().map(|(_, tokens)| tokens).sum();
+                let tokens_full = self
+                    .limits
+                    .tokens_per_minute
+                    .is_some_and(|max| !recent.is_empty() && used + tokens > max as usize);
+                match recent.front() {
+                    Some((oldest, _)) if requests_full || tokens_full => {
+                        self.window - now.duration_since(*oldest)
+                    }
+                    _ => {
+                        recent.push_back((now, tokens));
+                        return now;
+                    }
+                }
+            };
+            info!(
+                "LLM rate limit reached, waiting {secs:.1}s",
+                secs = wait.as_secs_f64()
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Add the response's tokens to the request started at `start`
+    fn charge(&self, start: Instant, tokens: usize) {
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = recent.iter_mut().find(|(at, _)| *at == start) {
+            entry.1 += tokens;
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for RateLimitedProvider {
+    async fn complete(&self, prompt: &str) -> Result<String> {
+        let _permit = match &self.in_flight {
+            Some(semaphore) => Some(semaphore.acquire().await?),
+            None => None,
+        };
+        let start = self.admit(estimate_tokens(prompt)).await;
+        let response = self.inner.complete(prompt).await?;
+        self.charge(start, estimate_tokens(&response));
+        Ok(response)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -332,11 +612,14 @@ mod tests {
         let config = LLMConfig {
             provider: "anthropic".to_string(),
             api_key: Some("test-key".to_string()),
+            api_key_file: None,
+            script: None,
             model: "claude-3-opus-20240229".to_string(),
             temperature: Some(0.7),
             max_tokens: Some(4000),
             api_url: None,
             timeout_secs: None,
+            rate_limit: None,
         };
 
         let _provider = create_provider(&config)?;
@@ -349,11 +632,14 @@ mod tests {
         let config = LLMConfig {
             provider: "mock".to_string(),
             api_key: None,
+            api_key_file: None,
+            script: None,
             model: "mock".to_string(),
             temperature: None,
             max_tokens: None,
             api_url: None,
             timeout_secs: None,
+            rate_limit: None,
         };
 
         let _provider = create_provider(&config)?;
@@ -379,4 +665,70 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_scripted_provider_replays_in_order_and_by_pattern() -> Result<()> {
+        let provider = ScriptedLLMProvider::from_script(
+            r#"
+responses:
+  - match: "error_\\d+"
+    response: {"reasoning": "Recover", "action": "explore"}
+    repeat: true
+  - response: first
+  - response: second
+fallback: done
+"#,
+        )?;
+
+        assert_eq!(provider.complete("hello").await?, "first");
+        assert_eq!(
+            provider.complete("saw error_42").await?,
+            r#"{"action":"explore","reasoning":"Recover"}"#
+        );
+        assert!(provider.complete("error_7").await?.contains("Recover"));
+        assert_eq!(provider.complete("hello").await?, "second");
+        assert_eq!(provider.complete("hello").await?, "done");
+
+        let exhausted = ScriptedLLMProvider::from_script("responses: [{response: once}]")?;
+        exhausted.complete("x").await?;
+        assert!(exhausted.complete("x").await.is_err());
+        assert!(
+            ScriptedLLMProvider::from_script("responses: [{match: '(', response: x}]").is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_delays_requests_over_the_limit() -> Result<()> {
+        let limits = RateLimitConfig {
+            requests_per_minute: Some(2),
+            tokens_per_minute: None,
+            max_concurrent: NonZeroUsize::new(1),
+        };
+        let mut provider = RateLimitedProvider::new(Box::new(MockLLMProvider::new()), &limits);
+        provider.window = Duration::from_millis(300);
+        // Load the tokenizer before timing anything
+        estimate_tokens("");
+
+        let started = Instant::now();
+        provider.complete("one").await?;
+        provider.complete("two").await?;
+        assert!(started.elapsed() < Duration::from_millis(300));
+        provider.complete("three").await?;
+        assert!(started.elapsed() >= Duration::from_millis(300));
+
+        let limits = RateLimitConfig {
+            tokens_per_minute: Some(10),
+            ..Default::default()
+        };
+        let mut provider = RateLimitedProvider::new(Box::new(MockLLMProvider::new()), &limits);
+        provider.window = Duration::from_millis(300);
+        let started = Instant::now();
+        // Over the limit on its own, so it goes out immediately
+        provider.complete(&"x".repeat(100)).await?;
+        assert!(started.elapsed() < Duration::from_millis(300));
+        provider.complete("short").await?;
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        Ok(())
+    }
 }
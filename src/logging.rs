@@ -0,0 +1,436 @@
+//! Process-wide tracing setup. The filter and the output layer can both be
+//! replaced while running, so `agent.log_level` and the `[logging]` table
+//! can change on a config reload.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{DefaultFields, Format, Writer};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+type Filtered = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+type Output = tracing_subscriber::fmt::Layer<Filtered, DefaultFields, EventFormat, BoxMakeWriter>;
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+static OUTPUT: OnceLock<reload::Handle<Output, Filtered>> = OnceLock::new();
+
+/// How each log line is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Timestamp, level, target and message, colored on a terminal
+    #[default]
+    Human,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+/// When the log file is moved aside and a new one started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    #[default]
+    Never,
+    /// At the start of every UTC hour
+    Hourly,
+    /// At UTC midnight
+    Daily,
+}
+
+/// The `[logging]` table. The base level stays in `log_level`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// Write to this file instead of stdout
+    pub file: Option<String>,
+    pub rotation: Rotation,
+    /// Also rotate once the file would grow past this size
+    pub max_size_mb: Option<u64>,
+    /// Rotated files kept as `<file>.1` (newest) to `<file>.<max_files>`
+    pub max_files: usize,
+    /// Levels for single modules, e.g. `"replicante::mcp" = "debug"`
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Human,
+            file: None,
+            rotation: Rotation::Never,
+            max_size_mb: None,
+            max_files: 5,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+/// Install the global subscriber: human-readable lines on stdout,
+/// filtered by `RUST_LOG` until [`configure`] or [`set_level`] say
+/// otherwise
+pub fn init() {
+    let (filter, filter_handle) = reload::Layer::new(EnvFilter::from_default_env());
+    let (output, output_handle) = reload::Layer::new(stdout_layer(LogFormat::Human));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .init();
+    let _ = FILTER.set(filter_handle);
+    let _ = OUTPUT.set(output_handle);
+}
+
+/// Apply a `[logging]` table, with `level` as the base level. `RUST_LOG`,
+/// when set, still decides the filter.
+pub fn configure(config: &LoggingConfig, level: Option<&str>) -> Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        set_level(&directives(level.unwrap_or("info"), &config.modules))?;
+    }
+
+    let layer = match &config.file {
+        Some(path) => {
+            let file = RollingFile::open(
+                Path::new(path),
+                config.rotation,
+                config.max_size_mb.map(|mb| mb * 1024 * 1024),
+                config.max_files,
+            )?;
+            output_layer(config.format, BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+        None => stdout_layer(config.format),
+    };
+    OUTPUT
+        .get()
+        .context("Logging was not set up with logging::init")?
+        .reload(layer)?;
+    Ok(())
+}
+
+/// Filter directives for a base level plus per-module overrides
+pub fn directives(level: &str, modules: &BTreeMap<String, String>) -> String {
+    let mut directives = level.to_string();
+    for (module, module_level) in modules {
+        let _ = write!(directives, ",{module}={module_level}");
+    }
+    directives
+}
+
+/// Replace the log filter with `directives`, e.g. "debug" or
+/// "info,replicante::mcp=trace"
+pub fn set_level(directives: &str) -> Result<()> {
+    let handle = FILTER
+        .get()
+        .context("Logging was not set up with logging::init")?;
+    let filter = EnvFilter::try_new(directives)
+        .with_context(|| format!("Invalid log level '{directives}'"))?;
+    handle.reload(filter)?;
+    Ok(())
+}
+
+fn stdout_layer(format: LogFormat) -> Output {
+    let ansi = format == LogFormat::Human && io::stdout().is_terminal();
+    output_layer(format, BoxMakeWriter::new(io::stdout), ansi)
+}
+
+fn output_layer(format: LogFormat, writer: BoxMakeWriter, ansi: bool) -> Output {
+    tracing_subscriber::fmt::layer()
+        .event_format(EventFormat {
+            json: format == LogFormat::Json,
+            human: Format::default(),
+        })
+        .with_writer(writer)
+        .with_ansi(ansi)
+}
+
+/// The stock human format, or one JSON object per event
+struct EventFormat {
+    json: bool,
+    human: Format,
+}
+
+impl<S, N> FormatEvent<S, N> for EventFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        if !self.json {
+            return self.human.format_event(ctx, writer, event);
+        }
+
+        let spans: Vec<&str> = ctx
+            .event_scope()
+            .map(|scope| scope.from_root().map(|span| span.name()).collect())
+            .unwrap_or_default();
+        writeln!(writer, "{}", json_line(event, Utc::now(), &spans))
+    }
+}
+
+fn json_line(event: &Event<'_>, now: DateTime<Utc>, spans: &[&str]) -> serde_json::Value {
+    let mut fields = JsonFields::default();
+    event.record(&mut fields);
+
+    let metadata = event.metadata();
+    let mut line = serde_json::json!({
+        "timestamp": now.to_rfc3339_opts(SecondsFormat::Micros, true),
+        "level": metadata.level().as_str(),
+        "target": metadata.target(),
+        "message": fields.message,
+    });
+    if !fields.values.is_empty() {
+        line["fields"] = serde_json::Value::Object(fields.values);
+    }
+    if !spans.is_empty() {
+        line["spans"] = serde_json::json!(spans);
+    }
+    line
+}
+
+#[derive(Default)]
+struct JsonFields {
+    message: String,
+    values: serde_json::Map<String, serde_json::Value>,
+}
+
+impl JsonFields {
+    fn insert(&mut self, field: &Field, value: serde_json::Value) {
+        if field.name() == "message" {
+            self.message = match value {
+                serde_json::Value::String(message) => message,
+                other => other.to_string(),
+            };
+        } else {
+            self.values.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for JsonFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.insert(field, format!("{value:?}").into());
+    }
+}
+
+/// A log file that is moved aside to `<path>.1` when its period ends or it
+/// grows too large, shifting older files up and dropping the oldest
+pub struct RollingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    max_bytes: Option<u64>,
+    max_files: usize,
+    file: File,
+    size: u64,
+    period: Option<i64>,
+}
+
+impl RollingFile {
+    pub fn open(
+        path: &Path,
+        rotation: Rotation,
+        max_bytes: Option<u64>,
+        max_files: usize,
+    ) -> Result<Self> {
+        let file = Self::open_file(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        let metadata = file.metadata()?;
+        // A file left over from an earlier period is rotated on first write
+        let modified = metadata.modified().map(DateTime::<Utc>::from)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            rotation,
+            max_bytes,
+            max_files,
+            file,
+            size: metadata.len(),
+            period: period(rotation, modified),
+        })
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        fs::OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn write_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> io::Result<()> {
+        let current = period(self.rotation, now);
+        let too_large = self
+            .max_bytes
+            .is_some_and(|max| self.size > 0 && self.size + buf.len() as u64 > max);
+        if current != self.period || too_large {
+            self.rotate()?;
+            self.period = current;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_at(buf, Utc::now())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Which rotation period `time` falls in, if rotating by time
+fn period(rotation: Rotation, time: DateTime<Utc>) -> Option<i64> {
+    match rotation {
+        Rotation::Never => None,
+        Rotation::Hourly => Some(time.timestamp().div_euclid(3600)),
+        Rotation::Daily => Some(time.timestamp().div_euclid(86400)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_rolling_file_rotates_by_size_and_keeps_max_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("agent.log");
+        let mut file = RollingFile::open(&path, Rotation::Never, Some(10), 2).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        let read = |path: PathBuf| fs::read_to_string(path).unwrap();
+        assert_eq!(read(path.clone()), "fourth\n");
+        assert_eq!(read(dir.path().join("agent.log.1")), "third\n");
+        assert_eq!(read(dir.path().join("agent.log.2")), "second\n");
+        assert!(!dir.path().join("agent.log.3").exists());
+    }
+
+    #[test]
+    fn test_rolling_file_rotates_when_the_day_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("agent.log");
+        let mut file = RollingFile::open(&path, Rotation::Daily, None, 5).unwrap();
+        let day = |d| Utc.with_ymd_and_hms(2026, 1, d, 12, 0, 0).unwrap();
+        file.period = period(Rotation::Daily, day(1));
+
+        file.write_at(b"monday\n", day(1)).unwrap();
+        file.write_at(b"monday again\n", day(1)).unwrap();
+        file.write_at(b"tuesday\n", day(2)).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "tuesday\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("agent.log.1")).unwrap(),
+            "monday\nmonday again\n"
+        );
+    }
+
+    #[test]
+    fn test_json_format_writes_one_object_per_event() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("agent.log");
+        let file = RollingFile::open(&path, Rotation::Never, None, 1).unwrap();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .event_format(EventFormat {
+                    json: true,
+                    human: Format::default(),
+                })
+                .with_writer(Mutex::new(file))
+                .with_ansi(false),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("cycle");
+            let _entered = span.enter();
+            tracing::warn!(tool = "fs:read", attempts = 3, "Tool call failed");
+        });
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let line: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["target"], module_path!());
+        assert_eq!(line["message"], "Tool call failed");
+        assert_eq!(line["fields"]["tool"], "fs:read");
+        assert_eq!(line["fields"]["attempts"], 3);
+        assert_eq!(line["spans"], serde_json::json!(["cycle"]));
+        assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn test_directives_append_module_levels() {
+        let modules = BTreeMap::from([
+            ("replicante::mcp".to_string(), "debug".to_string()),
+            ("reqwest".to_string(), "warn".to_string()),
+        ]);
+        assert_eq!(
+            directives("info", &modules),
+            "info,replicante::mcp=debug,reqwest=warn"
+        );
+        assert!(EnvFilter::try_new(directives("info", &modules)).is_ok());
+    }
+}
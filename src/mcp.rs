@@ -1,23 +1,33 @@
 use anyhow::{Context, Result, bail};
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, oneshot};
-use tokio::time::{Duration, interval, timeout};
+use tokio::time::{Duration, timeout};
+use tracing::level_filters::LevelFilter;
 use tracing::{debug, error, info, warn};
 
-use crate::jsonrpc::{Message, Request, RequestId, Response};
+use crate::clock::{Clock, SystemClock};
+use crate::events::{Event, EventBus};
+use crate::jsonrpc::{
+    DEFAULT_MAX_MESSAGE_BYTES, ErrorObject, Framing, LineRead, Message, MessageTooLarge, Request,
+    RequestId, Response, error_codes, message_id_hint,
+};
 use crate::mcp_protocol::{
     ContentItem, InitializeParams, InitializeResult, ToolCallParams, ToolCallResult, ToolInfo,
     ToolsListResult,
 };
+use crate::truncate;
+use crate::wasm_tools::{self, WasmTools};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct MCPServerConfig {
     pub name: String,
     pub transport: String,
@@ -29,6 +39,24 @@ pub struct MCPServerConfig {
     pub retry_delay_ms: u64,
     #[serde(default = "default_health_check_interval_secs")]
     pub health_check_interval_secs: u64,
+    /// Largest JSON-RPC message sent to or accepted from the server
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// "newline" (the default) or "content-length" for servers that use
+    /// LSP-style framed stdio
+    #[serde(default)]
+    pub framing: Framing,
+    /// Environment variables set for the server process, on top of the
+    /// agent's own
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Working directory of the server process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// User to run the server process as, by name or numeric uid; the
+    /// agent needs the privileges to switch to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
 }
 
 fn default_retry_attempts() -> u32 {
@@ -40,6 +68,38 @@ fn default_retry_delay_ms() -> u64 {
 fn default_health_check_interval_secs() -> u64 {
     60
 }
+fn default_max_message_bytes() -> usize {
+    DEFAULT_MAX_MESSAGE_BYTES
+}
+
+/// The uid and primary gid of `user`, a user name or numeric uid. A uid
+/// without a passwd entry runs with the group of the same number.
+pub(crate) fn resolve_user(user: &str) -> Result<(u32, u32)> {
+    use nix::unistd::{Uid, User};
+
+    let entry = match user.parse::<u32>() {
+        Ok(uid) => {
+            let entry = User::from_uid(Uid::from_raw(uid))
+                .with_context(|| format!("Failed to look up uid {uid}"))?;
+            if entry.is_none() {
+                return Ok((uid, uid));
+            }
+            entry
+        }
+        Err(_) => {
+            User::from_name(user).with_context(|| format!("Failed to look up user {user}"))?
+        }
+    };
+    let entry = entry.with_context(|| format!("User {user} does not exist"))?;
+    Ok((entry.uid.as_raw(), entry.gid.as_raw()))
+}
+
+/// How long a request waits for its response
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Pending requests older than this are swept; their caller has given up
+const PENDING_REQUEST_MAX_AGE: Duration = Duration::from_secs(120);
+/// How many answered request IDs are kept to recognise duplicate responses
+const ANSWERED_IDS_KEPT: usize = 256;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
@@ -50,6 +110,9 @@ pub struct Tool {
 
 pub struct MCPClient {
     servers: Vec<Arc<Mutex<MCPServer>>>,
+    events: EventBus,
+    wasm: WasmTools,
+    clock: Arc<dyn Clock>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -60,40 +123,221 @@ pub struct MCPServerHealth {
     pub last_healthy: Option<DateTime<Utc>>,
     pub error_count: u32,
     pub tool_count: usize,
+    pub pending_requests: usize,
+    /// Responses matching no pending request, e.g. after a timeout
+    pub orphaned_responses: u64,
+    /// Request IDs sent while already pending, or answered twice
+    pub duplicate_ids: u64,
+    /// Pending requests removed by the sweep
+    pub expired_requests: u64,
+}
+
+/// Reports the health of an [`MCPClient`]'s servers
+#[derive(Clone)]
+pub struct HealthProbe {
+    servers: Vec<Arc<Mutex<MCPServer>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl HealthProbe {
+    pub async fn status(&self) -> Vec<MCPServerHealth> {
+        let mut health_status = Vec::new();
+
+        for server in &self.servers {
+            let server_guard = server.lock().await;
+            health_status.push(MCPServerHealth {
+                name: server_guard.name.clone(),
+                is_healthy: server_guard
+                    .last_healthy
+                    .is_some_and(|t| (self.clock.now() - t).num_seconds() < 120),
+                is_initialized: server_guard.initialized,
+                last_healthy: server_guard.last_healthy,
+                error_count: server_guard.error_count,
+                tool_count: server_guard.tools.len(),
+                pending_requests: server_guard.pending_requests.len(),
+                orphaned_responses: server_guard.orphaned_responses,
+                duplicate_ids: server_guard.duplicate_ids,
+                expired_requests: server_guard.expired_requests,
+            });
+        }
+
+        health_status
+    }
+}
+
+/// What a server sends the client: its stdout, or one end of an in-process
+/// stream
+pub type ServerOutput = Box<dyn AsyncRead + Send + Unpin>;
+/// What the client sends a server: its stdin, or one end of an in-process
+/// stream
+pub type ServerInput = Box<dyn AsyncWrite + Send + Unpin>;
+/// Opens a fresh connection to an in-process server, on start and on every
+/// restart
+pub type Connector = Arc<dyn Fn() -> Result<(ServerOutput, ServerInput)> + Send + Sync>;
+
+/// A request waiting for its response
+struct PendingRequest {
+    sender: oneshot::Sender<Response>,
+    sent_at: Instant,
 }
 
 struct MCPServer {
     name: String,
     config: MCPServerConfig,
     process: Option<Child>,
-    stdin: Option<Arc<Mutex<tokio::process::ChildStdin>>>,
+    input: Option<Arc<Mutex<ServerInput>>>,
+    /// Set for in-process servers, which are connected to instead of spawned
+    connector: Option<Connector>,
+    /// Source of the health timestamps
+    clock: Arc<dyn Clock>,
     tools: Vec<ToolInfo>,
-    pending_requests: HashMap<RequestId, oneshot::Sender<Response>>,
+    pending_requests: HashMap<RequestId, PendingRequest>,
+    answered_ids: VecDeque<RequestId>,
     initialized: bool,
     last_healthy: Option<DateTime<Utc>>,
     error_count: u32,
     tools_cache_time: Option<DateTime<Utc>>,
+    orphaned_responses: u64,
+    duplicate_ids: u64,
+    expired_requests: u64,
+}
+
+impl MCPServer {
+    fn new(config: &MCPServerConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            config: config.clone(),
+            process: None,
+            input: None,
+            connector: None,
+            clock: Arc::new(SystemClock),
+            tools: Vec::new(),
+            pending_requests: HashMap::new(),
+            answered_ids: VecDeque::new(),
+            initialized: false,
+            last_healthy: None,
+            error_count: 0,
+            tools_cache_time: None,
+            orphaned_responses: 0,
+            duplicate_ids: 0,
+            expired_requests: 0,
+        }
+    }
+
+    /// Track a request that is about to be sent
+    fn register_request(&mut self, id: RequestId, sender: oneshot::Sender<Response>) {
+        let pending = PendingRequest {
+            sender,
+            sent_at: Instant::now(),
+        };
+        if self.pending_requests.insert(id.clone(), pending).is_some() {
+            // The earlier caller sees its channel close
+            self.duplicate_ids += 1;
+            warn!(
+                "Duplicate request ID {id:?} for {name}; dropping the earlier request",
+                name = self.name
+            );
+        }
+    }
+
+    /// Hand a response to the request waiting for it
+    fn complete_request(&mut self, response: Response) {
+        let Some(id) = response.id.clone() else {
+            self.orphaned_responses += 1;
+            warn!(
+                "Response without a request ID from {name}: {error:?}",
+                name = self.name,
+                error = response.error.map(|e| e.message)
+            );
+            return;
+        };
+
+        if let Some(pending) = self.pending_requests.remove(&id) {
+            let _ = pending.sender.send(response);
+            if self.answered_ids.len() >= ANSWERED_IDS_KEPT {
+                self.answered_ids.pop_front();
+            }
+            self.answered_ids.push_back(id);
+        } else if self.answered_ids.contains(&id) {
+            self.duplicate_ids += 1;
+            warn!(
+                "Duplicate response from {name} for request ID {id:?}",
+                name = self.name
+            );
+        } else {
+            self.orphaned_responses += 1;
+            warn!(
+                "Orphaned response from {name} for request ID {id:?} (timed out or never sent)",
+                name = self.name
+            );
+        }
+    }
+
+    /// Drop pending requests whose caller is gone or that are older than
+    /// `max_age`, returning how many were removed
+    fn sweep_pending_requests(&mut self, max_age: Duration) -> usize {
+        let before = self.pending_requests.len();
+        self.pending_requests.retain(|_, pending| {
+            !pending.sender.is_closed() && pending.sent_at.elapsed() < max_age
+        });
+        let expired = before - self.pending_requests.len();
+        self.expired_requests += expired as u64;
+        expired
+    }
 }
 
 impl MCPClient {
     pub async fn new(configs: &[MCPServerConfig]) -> Result<Self> {
+        Self::with_events(configs, EventBus::default()).await
+    }
+
+    /// Start the servers, publishing tool calls and unhealthy servers on
+    /// `events`
+    pub async fn with_events(configs: &[MCPServerConfig], events: EventBus) -> Result<Self> {
+        Self::with_clock(configs, events, Arc::new(SystemClock)).await
+    }
+
+    /// [`MCPClient::with_events`], timing health checks with `clock`
+    pub async fn with_clock(
+        configs: &[MCPServerConfig],
+        events: EventBus,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self> {
+        Self::start(configs.iter().map(MCPServer::new).collect(), events, clock).await
+    }
+
+    /// Start in-process servers, each reached through its connector rather
+    /// than by spawning the configured command
+    pub async fn in_process(
+        servers: Vec<(MCPServerConfig, Connector)>,
+        events: EventBus,
+    ) -> Result<Self> {
+        let servers = servers
+            .into_iter()
+            .map(|(config, connector)| MCPServer {
+                connector: Some(connector),
+                ..MCPServer::new(&config)
+            })
+            .collect();
+        Self::start(servers, events, Arc::new(SystemClock)).await
+    }
+
+    async fn start(
+        configured: Vec<MCPServer>,
+        events: EventBus,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self> {
         let mut servers = Vec::new();
 
-        for config in configs {
+        for server in configured {
+            let server = MCPServer {
+                clock: clock.clone(),
+                ..server
+            };
+            let config = server.config.clone();
             info!("Initializing MCP server: {name}", name = config.name);
 
-            let server = Arc::new(Mutex::new(MCPServer {
-                name: config.name.clone(),
-                config: config.clone(),
-                process: None,
-                stdin: None,
-                tools: Vec::new(),
-                pending_requests: HashMap::new(),
-                initialized: false,
-                last_healthy: None,
-                error_count: 0,
-                tools_cache_time: None,
-            }));
+            let server = Arc::new(Mutex::new(server));
 
             // Start the server process with retries
             let mut attempts = 0;
@@ -133,83 +377,97 @@ impl MCPClient {
         }
 
         // Start health monitoring
-        let client = Self { servers };
+        let client = Self {
+            servers,
+            events,
+            wasm: WasmTools::load(&[])?,
+            clock,
+        };
         client.start_health_monitoring();
         Ok(client)
     }
 
+    /// Also offer `tools` as `wasm:<name>`, run in-process
+    pub fn with_wasm_tools(mut self, tools: WasmTools) -> Self {
+        self.wasm = tools;
+        self
+    }
+
     async fn start_server(server: Arc<Mutex<MCPServer>>) -> Result<()> {
         let mut server_guard = server.lock().await;
         let config = server_guard.config.clone();
-
-        info!(
-            "Starting MCP server process: {} {} (server: {})",
-            config.command,
-            config.args.join(" "),
-            server_guard.name
-        );
-
-        // Spawn the MCP server process
-        let mut cmd = Command::new(&config.command);
-        cmd.args(&config.args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true);
-
-        debug!(
-            "Spawning MCP server process: {command}",
-            command = config.command
-        );
-        let mut child = cmd.spawn().with_context(|| {
-            format!(
-                "Failed to spawn MCP server: {command}",
-                command = config.command
-            )
-        })?;
-
-        debug!("MCP server process spawned with PID: {:?}", child.id());
-
-        // Get handles to stdin/stdout
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get stdin handle"))?;
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get stdout handle"))?;
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or_else(|| anyhow::anyhow!("Failed to get stderr handle"))?;
-
-        server_guard.process = Some(child);
         let server_name = server_guard.name.clone();
 
-        // Store stdin writer in the server struct first
-        let stdin = Arc::new(Mutex::new(stdin));
-        server_guard.stdin = Some(stdin.clone());
+        let (output, input) = match server_guard.connector.clone() {
+            Some(connect) => {
+                info!("Connecting to in-process MCP server: {server_name}");
+                connect()?
+            }
+            None => {
+                let (child, output, input) = Self::spawn_process(&config)?;
+                server_guard.process = Some(child);
+                (output, input)
+            }
+        };
+
+        // Store the input writer in the server struct first
+        let input = Arc::new(Mutex::new(input));
+        server_guard.input = Some(input.clone());
         drop(server_guard); // Release lock before spawning tasks
 
-        // Spawn task to handle stdout (JSON-RPC responses)
+        // Spawn task to handle the server's output (JSON-RPC responses)
         let server_clone = server.clone();
-        let server_name_stdout = server_name.clone();
+        let server_name_output = server_name.clone();
+        let max_bytes = config.max_message_bytes;
+        let framing = config.framing;
         tokio::spawn(async move {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
+            let mut reader = BufReader::new(output);
+            let mut buffer = Vec::new();
 
-            while let Ok(Some(line)) = lines.next_line().await {
+            loop {
+                match framing
+                    .read_async(&mut reader, &mut buffer, max_bytes)
+                    .await
+                {
+                    Ok(LineRead::Line) => {}
+                    Ok(LineRead::TooLarge(size)) => {
+                        error!(
+                            "Discarding a {size} byte message from {server_name_output}: over the {max_bytes} byte limit"
+                        );
+                        // Fail the request it answers now rather than at its timeout
+                        if let Some(id) = message_id_hint(&buffer) {
+                            let error = ErrorObject::new(
+                                error_codes::MESSAGE_TOO_LARGE,
+                                MessageTooLarge {
+                                    size,
+                                    limit: max_bytes,
+                                }
+                                .to_string(),
+                            );
+                            server_clone
+                                .lock()
+                                .await
+                                .complete_request(Response::from_error(Some(id), error));
+                        }
+                        continue;
+                    }
+                    Ok(LineRead::Eof) => break,
+                    Err(e) => {
+                        error!("Failed to read from {server_name_output}: {e}");
+                        break;
+                    }
+                }
+                let line = String::from_utf8_lossy(&buffer);
                 if line.trim().is_empty() {
                     continue;
                 }
 
-                debug!("Received from {server_name_stdout}: {line}");
+                debug!("Received from {server_name_output}: {line}");
 
                 // Yield to allow other tasks to run
                 tokio::task::yield_now().await;
 
-                match Message::parse(&line) {
+                match Message::parse_with_limit(&line, max_bytes) {
                     Ok(msg) => {
                         if let Err(e) = Self::handle_message(server_clone.clone(), msg).await {
                             error!("Failed to handle message: {e}");
@@ -224,18 +482,7 @@ impl MCPClient {
                 tokio::task::yield_now().await;
             }
 
-            warn!("MCP server {server_name_stdout} stdout closed");
-        });
-
-        // Spawn task to handle stderr (logging)
-        let server_name_stderr = server_name.clone();
-        tokio::spawn(async move {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                debug!("[{server_name_stderr}] {line}");
-            }
+            warn!("MCP server {server_name_output} output closed");
         });
 
         // Wait a bit for the handlers to be ready
@@ -249,7 +496,7 @@ impl MCPClient {
         );
         match timeout(
             Duration::from_secs(10),
-            Self::initialize_connection(server.clone(), stdin),
+            Self::initialize_connection(server.clone(), input),
         )
         .await
         {
@@ -272,12 +519,81 @@ impl MCPClient {
         Ok(())
     }
 
+    /// Spawn the configured command, logging its stderr, and return it with
+    /// its stdout and stdin
+    fn spawn_process(config: &MCPServerConfig) -> Result<(Child, ServerOutput, ServerInput)> {
+        info!(
+            "Starting MCP server process: {} {} (server: {})",
+            config.command,
+            config.args.join(" "),
+            config.name
+        );
+
+        // Spawn the MCP server process
+        let mut cmd = Command::new(&config.command);
+        cmd.args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &config.cwd {
+            cmd.current_dir(cwd);
+        }
+        if let Some(user) = &config.user {
+            let (uid, gid) = resolve_user(user)?;
+            // Dropping from root also clears the supplementary groups
+            cmd.uid(uid).gid(gid);
+        }
+
+        debug!(
+            "Spawning MCP server process: {command}",
+            command = config.command
+        );
+        let mut child = cmd.spawn().with_context(|| {
+            format!(
+                "Failed to spawn MCP server: {command}",
+                command = config.command
+            )
+        })?;
+
+        debug!("MCP server process spawned with PID: {:?}", child.id());
+
+        // Get handles to stdin/stdout
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stdin handle"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stdout handle"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stderr handle"))?;
+
+        // Spawn task to handle stderr (logging)
+        let server_name = config.name.clone();
+        tokio::spawn(async move {
+            let reader = BufReader::new(stderr);
+            let mut lines = reader.lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!("[{server_name}] {line}");
+            }
+        });
+
+        Ok((child, Box::new(stdout), Box::new(stdin)))
+    }
+
     async fn initialize_connection(
         server: Arc<Mutex<MCPServer>>,
-        stdin: Arc<Mutex<tokio::process::ChildStdin>>,
+        input: Arc<Mutex<ServerInput>>,
     ) -> Result<()> {
         let server_guard = server.lock().await;
         let server_name = server_guard.name.clone();
+        let framing = server_guard.config.framing;
         drop(server_guard);
 
         // Send initialize request
@@ -292,7 +608,7 @@ impl MCPClient {
             "Initialize request created for {}: {:?}",
             server_name, request.method
         );
-        let response = Self::send_request(server.clone(), stdin.clone(), request).await?;
+        let response = Self::send_request(server.clone(), input.clone(), request).await?;
         debug!("Initialize response received from {server_name}");
 
         // Parse initialize response
@@ -308,13 +624,30 @@ impl MCPClient {
             // Send initialized notification
             debug!("Sending initialized notification to {server_name}");
             let notification = Request::notification("initialized", Some(serde_json::json!({})));
-            Self::send_notification(stdin.clone(), notification).await?;
+            Self::send_notification(input.clone(), framing, notification).await?;
             debug!("Initialized notification sent to {server_name}");
 
+            // Ask for log messages at the level tracing will record
+            if init_result.capabilities.logging.is_some()
+                && let Some(level) = mcp_log_level(LevelFilter::current())
+            {
+                let request = Request::new(
+                    "logging/setLevel",
+                    Some(serde_json::json!({ "level": level })),
+                );
+                let response = Self::send_request(server.clone(), input.clone(), request).await?;
+                if let Some(error) = response.error {
+                    warn!(
+                        "Failed to set log level for {server_name}: {message}",
+                        message = error.message
+                    );
+                }
+            }
+
             // Mark server as initialized and healthy
             let mut server_guard = server.lock().await;
             server_guard.initialized = true;
-            server_guard.last_healthy = Some(Utc::now());
+            server_guard.last_healthy = Some(server_guard.clock.now());
             server_guard.error_count = 0;
             drop(server_guard);
 
@@ -322,7 +655,7 @@ impl MCPClient {
             debug!("Starting tool discovery for {server_name}");
             match timeout(
                 Duration::from_secs(5),
-                Self::discover_server_tools(server.clone(), stdin.clone()),
+                Self::discover_server_tools(server.clone(), input.clone()),
             )
             .await
             {
@@ -351,16 +684,16 @@ impl MCPClient {
 
     async fn discover_server_tools(
         server: Arc<Mutex<MCPServer>>,
-        stdin: Arc<Mutex<tokio::process::ChildStdin>>,
+        input: Arc<Mutex<ServerInput>>,
     ) -> Result<()> {
         let request = Request::new("tools/list", Some(serde_json::json!({})));
-        let response = Self::send_request(server.clone(), stdin, request).await?;
+        let response = Self::send_request(server.clone(), input, request).await?;
 
         if let Some(result) = response.result {
             let tools_result: ToolsListResult = serde_json::from_value(result)?;
             let mut server_guard = server.lock().await;
             server_guard.tools = tools_result.tools;
-            server_guard.tools_cache_time = Some(Utc::now());
+            server_guard.tools_cache_time = Some(server_guard.clock.now());
 
             info!(
                 "Discovered {count} tools from {name}",
@@ -382,7 +715,7 @@ impl MCPClient {
 
     async fn send_request(
         server: Arc<Mutex<MCPServer>>,
-        stdin: Arc<Mutex<tokio::process::ChildStdin>>,
+        input: Arc<Mutex<ServerInput>>,
         request: Request,
     ) -> Result<Response> {
         let request_id = request
@@ -393,23 +726,25 @@ impl MCPClient {
         // Create oneshot channel for response
         let (tx, rx) = oneshot::channel();
 
-        // Store pending request
-        {
+        // Store pending request, once it is known to fit the size limit
+        let (json, framing) = {
             let mut server_guard = server.lock().await;
-            server_guard.pending_requests.insert(request_id.clone(), tx);
-        }
+            let message = Message::Request(request);
+            let json = message.to_string_with_limit(server_guard.config.max_message_bytes)?;
+            server_guard.register_request(request_id.clone(), tx);
+            (json, server_guard.config.framing)
+        };
 
         // Send request
-        let message = Message::Request(request);
-        let json = message.to_string()? + "\n";
-
         {
-            let mut stdin_guard = stdin.lock().await;
-            stdin_guard.write_all(json.as_bytes()).await?;
-            stdin_guard.flush().await?;
+            let mut input_guard = input.lock().await;
+            input_guard
+                .write_all(framing.encode(&json).as_bytes())
+                .await?;
+            input_guard.flush().await?;
         }
 
-        debug!("Sent request: {request}", request = json.trim());
+        debug!("Sent request: {request}", request = json);
 
         // Add server name context for debugging
         let server_name = {
@@ -422,7 +757,7 @@ impl MCPClient {
             "Waiting for response from {} for request ID: {:?}",
             server_name, request_id
         );
-        match timeout(Duration::from_secs(30), rx).await {
+        match timeout(REQUEST_TIMEOUT, rx).await {
             Ok(Ok(response)) => {
                 debug!(
                     "Received response from {} for request ID: {:?}",
@@ -451,45 +786,61 @@ impl MCPClient {
     }
 
     async fn send_notification(
-        stdin: Arc<Mutex<tokio::process::ChildStdin>>,
+        input: Arc<Mutex<ServerInput>>,
+        framing: Framing,
         notification: crate::jsonrpc::Notification,
     ) -> Result<()> {
         let message = Message::Notification(notification);
-        let json = message.to_string()? + "\n";
+        let json = message.to_string()?;
 
-        let mut stdin_guard = stdin.lock().await;
-        stdin_guard.write_all(json.as_bytes()).await?;
-        stdin_guard.flush().await?;
+        let mut input_guard = input.lock().await;
+        input_guard
+            .write_all(framing.encode(&json).as_bytes())
+            .await?;
+        input_guard.flush().await?;
 
-        debug!(
-            "Sent notification: {notification}",
-            notification = json.trim()
-        );
+        debug!("Sent notification: {notification}", notification = json);
         Ok(())
     }
 
     async fn handle_message(server: Arc<Mutex<MCPServer>>, message: Message) -> Result<()> {
         match message {
             Message::Response(response) => {
-                if let Some(id) = &response.id {
-                    let mut server_guard = server.lock().await;
-                    if let Some(sender) = server_guard.pending_requests.remove(id) {
-                        let _ = sender.send(response);
-                    }
-                }
+                server.lock().await.complete_request(response);
+            }
+            // Notifications parse as requests without an id
+            Message::Request(request) if request.id.is_none() => {
+                Self::handle_notification(server, &request.method, request.params.as_ref()).await;
             }
             Message::Request(_request) => {
                 // MCP servers typically don't send requests to clients
                 debug!("Received unexpected request from server");
             }
-            Message::Notification(_notification) => {
-                // Handle server notifications (e.g., tools/list_changed)
-                debug!("Received notification from server");
+            Message::Notification(notification) => {
+                Self::handle_notification(
+                    server,
+                    &notification.method,
+                    notification.params.as_ref(),
+                )
+                .await;
             }
         }
         Ok(())
     }
 
+    async fn handle_notification(
+        server: Arc<Mutex<MCPServer>>,
+        method: &str,
+        params: Option<&Value>,
+    ) {
+        let server_name = server.lock().await.name.clone();
+        match method {
+            "notifications/message" => log_server_message(&server_name, params),
+            // Other server notifications (e.g., tools/list_changed)
+            _ => debug!("Received {method} notification from {server_name}"),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn server_count(&self) -> usize {
         self.servers.len()
@@ -508,6 +859,7 @@ impl MCPClient {
                 ));
             }
         }
+        all_tools.extend(self.wasm.tools().into_iter().map(|tool| tool.name));
 
         Ok(all_tools)
     }
@@ -529,6 +881,7 @@ impl MCPClient {
                 });
             }
         }
+        all_tools.extend(self.wasm.tools());
 
         Ok(all_tools)
     }
@@ -550,26 +903,36 @@ impl MCPClient {
                 });
             }
         }
+        all_tools.extend(self.wasm.tools());
 
         Ok(all_tools)
     }
 
     fn start_health_monitoring(&self) {
         let servers = self.servers.clone();
+        let events = self.events.clone();
+        let clock = self.clock.clone();
 
         tokio::spawn(async move {
-            let mut check_interval = interval(Duration::from_secs(60));
-
             loop {
-                check_interval.tick().await;
+                clock
+                    .sleep_until(clock.now() + chrono::Duration::seconds(60))
+                    .await;
 
                 for server in &servers {
-                    let server_guard = server.lock().await;
+                    let mut server_guard = server.lock().await;
                     let server_name = server_guard.name.clone();
                     let should_check = server_guard.initialized;
+                    let expired = server_guard.sweep_pending_requests(PENDING_REQUEST_MAX_AGE);
                     drop(server_guard);
 
-                    if should_check && let Err(e) = Self::health_check(server.clone()).await {
+                    if expired > 0 {
+                        warn!("Expired {expired} stale pending requests for {server_name}");
+                    }
+
+                    if should_check
+                        && let Err(e) = Self::health_check(server.clone(), &events).await
+                    {
                         warn!("Health check failed for {server_name}: {e}");
                     }
                 }
@@ -577,25 +940,25 @@ impl MCPClient {
         });
     }
 
-    async fn health_check(server: Arc<Mutex<MCPServer>>) -> Result<()> {
-        let stdin = {
+    async fn health_check(server: Arc<Mutex<MCPServer>>, events: &EventBus) -> Result<()> {
+        let input = {
             let server_guard = server.lock().await;
-            server_guard.stdin.clone()
+            server_guard.input.clone()
         };
 
-        if let Some(stdin) = stdin {
+        if let Some(input) = input {
             // Send a simple ping request to check if server is responsive
             let request = Request::new("tools/list", Some(serde_json::json!({})));
 
             match timeout(
                 Duration::from_secs(5),
-                Self::send_request(server.clone(), stdin, request),
+                Self::send_request(server.clone(), input, request),
             )
             .await
             {
                 Ok(Ok(_response)) => {
                     let mut server_guard = server.lock().await;
-                    server_guard.last_healthy = Some(Utc::now());
+                    server_guard.last_healthy = Some(server_guard.clock.now());
                     server_guard.error_count = 0;
                     Ok(())
                 }
@@ -608,6 +971,11 @@ impl MCPClient {
                             "Server {} appears unhealthy after {} consecutive failures",
                             server_guard.name, server_guard.error_count
                         );
+                        events.publish(Event::ServerUnhealthy {
+                            server: server_guard.name.clone(),
+                            consecutive_failures: server_guard.error_count,
+                            error: e.to_string(),
+                        });
 
                         // Try to restart the server
                         drop(server_guard);
@@ -627,6 +995,11 @@ impl MCPClient {
                             "Server {} appears unhealthy after {} consecutive failures (timeout)",
                             server_guard.name, server_guard.error_count
                         );
+                        events.publish(Event::ServerUnhealthy {
+                            server: server_guard.name.clone(),
+                            consecutive_failures: server_guard.error_count,
+                            error: "Health check timeout".to_string(),
+                        });
 
                         // Try to restart the server
                         drop(server_guard);
@@ -639,7 +1012,7 @@ impl MCPClient {
                 }
             }
         } else {
-            bail!("No stdin handle for health check")
+            bail!("No connection for health check")
         }
     }
 
@@ -653,7 +1026,7 @@ impl MCPClient {
                 let _ = process.kill().await;
             }
             server_guard.initialized = false;
-            server_guard.stdin = None;
+            server_guard.input = None;
             server_guard.tools.clear();
             server_guard.pending_requests.clear();
         }
@@ -663,28 +1036,75 @@ impl MCPClient {
     }
 
     pub async fn get_health_status(&self) -> Vec<MCPServerHealth> {
-        let mut health_status = Vec::new();
+        self.health_probe().status().await
+    }
 
-        for server in &self.servers {
-            let server_guard = server.lock().await;
-            health_status.push(MCPServerHealth {
-                name: server_guard.name.clone(),
-                is_healthy: server_guard
-                    .last_healthy
-                    .is_some_and(|t| (Utc::now() - t).num_seconds() < 120),
-                is_initialized: server_guard.initialized,
-                last_healthy: server_guard.last_healthy,
-                error_count: server_guard.error_count,
-                tool_count: server_guard.tools.len(),
-            });
+    /// A handle that reports the servers' health without borrowing the
+    /// client, for the agent's health endpoint
+    pub fn health_probe(&self) -> HealthProbe {
+        HealthProbe {
+            servers: self.servers.clone(),
+            clock: self.clock.clone(),
         }
-
-        health_status
     }
 
     pub async fn use_tool(&self, name: &str, params: Value) -> Result<Value> {
         debug!("Using tool: {name} with params: {params:?}");
 
+        let result = self.call_tool(name, params).await?;
+        let tool_name = name.split_once(':').map_or(name, |(_, tool)| tool);
+        let tool_result: ToolCallResult = serde_json::from_value(result)?;
+
+        // Convert tool result to appropriate format
+        if let Some(content) = tool_result.content
+            && !content.is_empty()
+        {
+            // Extract text content from the first item
+            if let Some(ContentItem::Text { text }) = content.into_iter().next() {
+                // Try to parse as JSON to preserve structure
+                if let Ok(json_value) = serde_json::from_str::<Value>(&text) {
+                    // Return the parsed JSON directly to preserve all fields
+                    return Ok(json_value);
+                }
+                // Fall back to simple format if not valid JSON
+                debug!(
+                    "Tool result is not valid JSON, using simplified format. Content preview: {preview}...",
+                    preview = truncate::preview(&text, 100)
+                );
+                return Ok(serde_json::json!({
+                    "success": !tool_result.is_error.unwrap_or(false),
+                    "content": text
+                }));
+            }
+        }
+
+        Ok(serde_json::json!({
+            "success": !tool_result.is_error.unwrap_or(false),
+            "message": format!("Tool {tool_name} executed")
+        }))
+    }
+
+    /// Call `server:tool` and return the server's `tools/call` result as sent
+    pub async fn call_tool(&self, name: &str, params: Value) -> Result<Value> {
+        let started = Instant::now();
+        let result = self.send_tool_call(name, params).await;
+        let error = match &result {
+            Ok(result) if result.get("isError") == Some(&Value::Bool(true)) => {
+                Some("Tool reported an error".to_string())
+            }
+            Ok(_) => None,
+            Err(e) => Some(e.to_string()),
+        };
+        self.events.publish(Event::ToolCalled {
+            tool: name.to_string(),
+            success: error.is_none(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            error,
+        });
+        result
+    }
+
+    async fn send_tool_call(&self, name: &str, params: Value) -> Result<Value> {
         // Parse server:tool format
         let parts: Vec<&str> = name.split(':').collect();
         if parts.len() != 2 {
@@ -694,6 +1114,10 @@ impl MCPClient {
         let server_name = parts[0];
         let tool_name = parts[1];
 
+        if server_name == wasm_tools::SERVER_NAME && self.wasm.has_tool(tool_name) {
+            return self.wasm.call(tool_name, params).await;
+        }
+
         // Find the server
         let server = self
             .servers
@@ -704,16 +1128,16 @@ impl MCPClient {
             })
             .ok_or_else(|| anyhow::anyhow!("Server not found: {}", server_name))?;
 
-        // Get stdin handle and check if server is initialized
-        let stdin = {
+        // Get the input handle and check if server is initialized
+        let input = {
             let server_guard = server.lock().await;
             if !server_guard.initialized {
                 bail!("Server {} is not initialized", server_name);
             }
             server_guard
-                .stdin
+                .input
                 .clone()
-                .ok_or_else(|| anyhow::anyhow!("No stdin handle for server {}", server_name))?
+                .ok_or_else(|| anyhow::anyhow!("No connection to server {}", server_name))?
         };
 
         // Create tool call request
@@ -723,46 +1147,18 @@ impl MCPClient {
         };
 
         let request = Request::new("tools/call", Some(serde_json::to_value(tool_params)?));
-        let response = Self::send_request(server.clone(), stdin, request).await?;
+        let response = Self::send_request(server.clone(), input, request).await?;
 
         // Parse tool execution response
         if let Some(result) = response.result {
             // Update last healthy timestamp on successful tool use
             {
                 let mut server_guard = server.lock().await;
-                server_guard.last_healthy = Some(Utc::now());
+                server_guard.last_healthy = Some(server_guard.clock.now());
                 server_guard.error_count = 0;
             }
 
-            let tool_result: ToolCallResult = serde_json::from_value(result)?;
-
-            // Convert tool result to appropriate format
-            if let Some(content) = tool_result.content
-                && !content.is_empty()
-            {
-                // Extract text content from the first item
-                if let Some(ContentItem::Text { text }) = content.into_iter().next() {
-                    // Try to parse as JSON to preserve structure
-                    if let Ok(json_value) = serde_json::from_str::<Value>(&text) {
-                        // Return the parsed JSON directly to preserve all fields
-                        return Ok(json_value);
-                    }
-                    // Fall back to simple format if not valid JSON
-                    debug!(
-                        "Tool result is not valid JSON, using simplified format. Content preview: {}...",
-                        &text.chars().take(100).collect::<String>()
-                    );
-                    return Ok(serde_json::json!({
-                        "success": !tool_result.is_error.unwrap_or(false),
-                        "content": text
-                    }));
-                }
-            }
-
-            Ok(serde_json::json!({
-                "success": !tool_result.is_error.unwrap_or(false),
-                "message": format!("Tool {tool_name} executed")
-            }))
+            Ok(result)
         } else if let Some(error) = response.error {
             bail!(
                 "Tool execution failed: {message} (code: {code})",
@@ -780,9 +1176,12 @@ impl Drop for MCPClient {
         // Clean up any running MCP server processes without blocking
         for server in &self.servers {
             // Use try_lock to avoid potential deadlocks and hanging
-            if let Ok(mut server_guard) = server.try_lock()
-                && let Some(mut process) = server_guard.process.take()
-            {
+            let Ok(mut server_guard) = server.try_lock() else {
+                continue;
+            };
+            // In-process servers stop when their input closes
+            server_guard.input = None;
+            if let Some(mut process) = server_guard.process.take() {
                 // Use start_kill() which is non-blocking, let OS handle cleanup
                 let _ = process.start_kill();
                 debug!(
@@ -794,11 +1193,65 @@ impl Drop for MCPClient {
     }
 }
 
+/// The MCP log level matching what tracing records, so servers do not send
+/// messages that would be filtered out anyway
+fn mcp_log_level(filter: LevelFilter) -> Option<&'static str> {
+    if filter >= LevelFilter::DEBUG {
+        Some("debug")
+    } else if filter >= LevelFilter::INFO {
+        Some("info")
+    } else if filter >= LevelFilter::WARN {
+        Some("warning")
+    } else if filter >= LevelFilter::ERROR {
+        Some("error")
+    } else {
+        None
+    }
+}
+
+/// Record a server's `notifications/message` through tracing at the
+/// matching level
+fn log_server_message(server_name: &str, params: Option<&Value>) {
+    let level = params
+        .and_then(|p| p.get("level"))
+        .and_then(|l| l.as_str())
+        .unwrap_or("info");
+    let message = match params.and_then(|p| p.get("data")) {
+        Some(Value::String(text)) => text.clone(),
+        Some(data) => data.to_string(),
+        None => String::new(),
+    };
+    let source = match params
+        .and_then(|p| p.get("logger"))
+        .and_then(|l| l.as_str())
+    {
+        Some(logger) => format!("{server_name}/{logger}"),
+        None => server_name.to_string(),
+    };
+
+    match level {
+        "debug" => debug!("[{source}] {message}"),
+        "warning" => warn!("[{source}] {message}"),
+        "error" | "critical" | "alert" | "emergency" => error!("[{source}] {message}"),
+        _ => info!("[{source}] {message}"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::jsonrpc::{Message, Request};
 
+    #[test]
+    fn test_mcp_log_level_follows_tracing_filter() {
+        assert_eq!(mcp_log_level(LevelFilter::TRACE), Some("debug"));
+        assert_eq!(mcp_log_level(LevelFilter::DEBUG), Some("debug"));
+        assert_eq!(mcp_log_level(LevelFilter::INFO), Some("info"));
+        assert_eq!(mcp_log_level(LevelFilter::WARN), Some("warning"));
+        assert_eq!(mcp_log_level(LevelFilter::ERROR), Some("error"));
+        assert_eq!(mcp_log_level(LevelFilter::OFF), None);
+    }
+
     #[test]
     fn test_json_rpc_request_creation() {
         let request = Request::new("test_method", Some(serde_json::json!({"key": "value"})));
@@ -840,6 +1293,11 @@ mod tests {
             retry_attempts: 1,
             retry_delay_ms: 100,
             health_check_interval_secs: 60,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: BTreeMap::new(),
+            cwd: None,
+            user: None,
         }];
 
         let client = MCPClient::new(&configs).await?;
@@ -857,6 +1315,11 @@ mod tests {
             retry_attempts: 1,
             retry_delay_ms: 100,
             health_check_interval_secs: 60,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: BTreeMap::new(),
+            cwd: None,
+            user: None,
         }];
 
         // Should not panic, just log error
@@ -902,4 +1365,57 @@ mod tests {
         assert_eq!(json["type"], "text");
         assert_eq!(json["text"], "Hello, world!");
     }
+
+    #[test]
+    fn test_request_id_correlation_diagnostics() {
+        let mut server = MCPServer::new(&MCPServerConfig {
+            name: "test".to_string(),
+            transport: "stdio".to_string(),
+            command: "true".to_string(),
+            args: vec![],
+            retry_attempts: 1,
+            retry_delay_ms: 100,
+            health_check_interval_secs: 60,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: BTreeMap::new(),
+            cwd: None,
+            user: None,
+        });
+        let response = |id: u64| Response::success(Some(RequestId::Number(id)), Value::Null);
+
+        let (tx, mut rx) = oneshot::channel();
+        server.register_request(RequestId::Number(1), tx);
+        server.complete_request(response(1));
+        assert!(rx.try_recv().is_ok());
+        assert!(server.pending_requests.is_empty());
+
+        // A second answer to the same request is a duplicate
+        server.complete_request(response(1));
+        assert_eq!(server.duplicate_ids, 1);
+
+        // Answers nobody is waiting for are orphaned
+        server.complete_request(response(99));
+        server.complete_request(Response::error(None, -32700, "Parse error"));
+        assert_eq!(server.orphaned_responses, 2);
+
+        // Reusing a pending ID replaces the earlier request
+        let (first, mut first_rx) = oneshot::channel();
+        let (second, _second_rx) = oneshot::channel();
+        server.register_request(RequestId::Number(2), first);
+        server.register_request(RequestId::Number(2), second);
+        assert_eq!(server.duplicate_ids, 2);
+        assert!(first_rx.try_recv().is_err());
+        assert_eq!(server.pending_requests.len(), 1);
+
+        // The sweep drops abandoned and stale requests
+        let (abandoned, abandoned_rx) = oneshot::channel();
+        server.register_request(RequestId::Number(3), abandoned);
+        drop(abandoned_rx);
+        assert_eq!(server.sweep_pending_requests(PENDING_REQUEST_MAX_AGE), 1);
+        assert_eq!(server.pending_requests.len(), 1);
+        assert_eq!(server.sweep_pending_requests(Duration::ZERO), 1);
+        assert!(server.pending_requests.is_empty());
+        assert_eq!(server.expired_requests, 2);
+    }
 }
@@ -82,6 +82,9 @@ pub struct ServerCapabilities {
     pub resources: Option<ResourcesCapability>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prompts: Option<PromptsCapability>,
+    /// Present when the server accepts `logging/setLevel`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Value>,
 }
 
 /// MCP Tool Definition
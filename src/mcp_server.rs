@@ -0,0 +1,741 @@
+//! Shared plumbing for the stdio MCP tool servers in `src/bin`.
+//!
+//! A server implements [`ToolHandler`] — its tool schemas and a dispatch
+//! function — and hands it to [`McpServer`], which owns the JSON-RPC loop:
+//! `initialize`, `tools/list`, `tools/call` and `logging/setLevel`, routed
+//! through [`Router`](crate::jsonrpc::Router) with its standard error codes.
+//! Tools report what they are doing with [`log`], which reaches
+//! the client as `notifications/message` rather than stderr.
+
+use crate::jsonrpc::{
+    DEFAULT_MAX_MESSAGE_BYTES, ErrorObject, Framing, LineRead, MessageTooLarge, RequestId,
+    Response, Router, error_codes, message_id_hint,
+};
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// MCP protocol version the servers speak
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// A parameter in a tool's input schema
+#[derive(Debug, Clone)]
+pub struct Param {
+    name: String,
+    schema: Map<String, Value>,
+    required: bool,
+}
+
+impl Param {
+    /// A parameter with an arbitrary JSON schema, for shapes the typed
+    /// constructors do not cover
+    pub fn with_schema(name: impl Into<String>, schema: Value) -> Self {
+        Self {
+            name: name.into(),
+            schema: match schema {
+                Value::Object(schema) => schema,
+                _ => Map::new(),
+            },
+            required: false,
+        }
+    }
+
+    fn typed(kind: &str, name: impl Into<String>, description: &str) -> Self {
+        Self::with_schema(name, json!({"type": kind, "description": description}))
+    }
+
+    pub fn string(name: impl Into<String>, description: &str) -> Self {
+        Self::typed("string", name, description)
+    }
+
+    pub fn integer(name: impl Into<String>, description: &str) -> Self {
+        Self::typed("integer", name, description)
+    }
+
+    pub fn number(name: impl Into<String>, description: &str) -> Self {
+        Self::typed("number", name, description)
+    }
+
+    pub fn boolean(name: impl Into<String>, description: &str) -> Self {
+        Self::typed("boolean", name, description)
+    }
+
+    pub fn object(name: impl Into<String>, description: &str) -> Self {
+        Self::typed("object", name, description)
+    }
+
+    /// An array whose items have the JSON type `items`, e.g. "string"
+    pub fn array(name: impl Into<String>, description: &str, items: &str) -> Self {
+        Self::with_schema(
+            name,
+            json!({"type": "array", "items": {"type": items}, "description": description}),
+        )
+    }
+
+    /// A parameter accepting any JSON value
+    pub fn any(name: impl Into<String>, description: &str) -> Self {
+        Self::with_schema(name, json!({"description": description}))
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn default(mut self, value: impl Into<Value>) -> Self {
+        self.schema.insert("default".to_string(), value.into());
+        self
+    }
+
+    /// Restrict the parameter to a fixed set of values
+    pub fn one_of(mut self, values: &[&str]) -> Self {
+        self.schema.insert("enum".to_string(), json!(values));
+        self
+    }
+}
+
+/// A tool definition as listed by `tools/list`
+#[derive(Debug, Clone)]
+pub struct Tool {
+    name: String,
+    description: String,
+    params: Vec<Param>,
+}
+
+impl Tool {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn param(mut self, param: Param) -> Self {
+        self.params.push(param);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn to_json(&self) -> Value {
+        let properties: Map<String, Value> = self
+            .params
+            .iter()
+            .map(|param| (param.name.clone(), Value::Object(param.schema.clone())))
+            .collect();
+        let required: Vec<&str> = self
+            .params
+            .iter()
+            .filter(|param| param.required)
+            .map(|param| param.name.as_str())
+            .collect();
+
+        let mut schema = json!({
+            "type": "object",
+            "properties": properties
+        });
+        if !required.is_empty() {
+            schema["required"] = json!(required);
+        }
+
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "inputSchema": schema
+        })
+    }
+}
+
+/// The result of a successful `tools/call`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolOutput {
+    pub content: Vec<Value>,
+    /// Whether the tool reports a failure the agent should see and handle
+    pub is_error: bool,
+}
+
+impl ToolOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            content: vec![json!({"type": "text", "text": text.into()})],
+            is_error: false,
+        }
+    }
+
+    /// Structured output, as JSON text the client can parse back into fields
+    pub fn json(value: &Value) -> Self {
+        Self::text(value.to_string())
+    }
+
+    /// A failure reported in-band, as opposed to a JSON-RPC error
+    pub fn error(text: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            ..Self::text(text)
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "content": self.content,
+            "isError": self.is_error
+        })
+    }
+}
+
+/// A tool call for a tool the server does not have; reported as -32602
+#[derive(Debug)]
+pub struct UnknownTool(pub String);
+
+impl std::fmt::Display for UnknownTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown tool: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownTool {}
+
+/// Per-call information a tool may use while it runs
+#[derive(Debug, Clone, Default)]
+pub struct ToolContext {
+    /// Token the client asked progress notifications to carry, from `_meta.progressToken`
+    pub progress_token: Option<Value>,
+}
+
+impl ToolContext {
+    /// Report progress, if the client asked for it
+    pub fn progress(&self, progress: u64, total: Option<u64>) {
+        if let Some(token) = &self.progress_token {
+            send_notification(
+                "notifications/progress",
+                json!({
+                    "progressToken": token,
+                    "progress": progress,
+                    "total": total
+                }),
+            );
+        }
+    }
+}
+
+/// Write a JSON-RPC notification to stdout
+pub fn send_notification(method: &str, params: Value) {
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params
+    });
+    let framing = if CONTENT_LENGTH_FRAMING.load(Ordering::Relaxed) {
+        Framing::ContentLength
+    } else {
+        Framing::Newline
+    };
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "{}", framing.encode(&notification.to_string()));
+    let _ = stdout.flush();
+}
+
+/// Whether the client frames messages with Content-Length headers, so
+/// notifications match; detected by [`McpServer::serve`]
+static CONTENT_LENGTH_FRAMING: AtomicBool = AtomicBool::new(false);
+
+/// Severity of a log message, in the syslog order MCP uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    const ALL: [LogLevel; 8] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Notice,
+        LogLevel::Warning,
+        LogLevel::Error,
+        LogLevel::Critical,
+        LogLevel::Alert,
+        LogLevel::Emergency,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+            LogLevel::Alert => "alert",
+            LogLevel::Emergency => "emergency",
+        }
+    }
+
+    pub fn parse(level: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.as_str() == level)
+    }
+}
+
+/// Lowest level [`log`] sends; the client changes it with `logging/setLevel`.
+/// Process-wide, as each server binary runs a single [`McpServer`].
+static LOG_THRESHOLD: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Send a log message to the client, unless it asked for a higher level
+pub fn log(level: LogLevel, message: impl Into<String>) {
+    if (level as u8) < LOG_THRESHOLD.load(Ordering::Relaxed) {
+        return;
+    }
+    send_notification(
+        "notifications/message",
+        json!({
+            "level": level.as_str(),
+            "data": message.into()
+        }),
+    );
+}
+
+/// The tools of one MCP server
+pub trait ToolHandler {
+    /// Tool definitions returned by `tools/list`
+    fn tools(&self) -> Vec<Tool>;
+
+    /// Run a tool. Errors become JSON-RPC errors; failures the agent should
+    /// handle itself can be returned as [`ToolOutput::error`] instead.
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        context: &ToolContext,
+    ) -> Result<ToolOutput>;
+
+    /// Structured `error.data` for a failed call, e.g. which rule rejected it
+    fn error_data(&self, _error: &anyhow::Error) -> Option<Value> {
+        None
+    }
+}
+
+/// Params of `initialize`; only the client info is used, for logging
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeRequest {
+    client_info: Option<Value>,
+}
+
+/// Params of `tools/call`
+#[derive(Deserialize)]
+struct ToolCallRequest {
+    name: String,
+    arguments: Option<Value>,
+    #[serde(rename = "_meta")]
+    meta: Option<Value>,
+}
+
+/// Params of `logging/setLevel`
+#[derive(Deserialize)]
+struct SetLevelRequest {
+    level: String,
+}
+
+/// What the MCP method handlers work on
+struct ServerState<H> {
+    name: String,
+    log_prefix: String,
+    verbose: bool,
+    initialized: bool,
+    handler: H,
+}
+
+impl<H: ToolHandler> ServerState<H> {
+    fn log(&self, message: &str) {
+        if self.verbose {
+            eprintln!("[{prefix}] {message}", prefix = self.log_prefix);
+        }
+    }
+
+    fn initialize(&mut self, params: InitializeRequest) -> Result<Value, ErrorObject> {
+        if let Some(client_info) = params.client_info {
+            self.log(&format!("Initialize request from client: {client_info}"));
+        }
+        Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "serverInfo": {
+                "name": self.name,
+                "version": "1.0.0"
+            },
+            "capabilities": {
+                "tools": {},
+                "logging": {}
+            }
+        }))
+    }
+
+    fn initialized(&mut self, _params: Value) {
+        self.initialized = true;
+        self.log("Client confirmed initialization");
+    }
+
+    fn list_tools(&mut self, _params: Value) -> Result<Value, ErrorObject> {
+        let tools: Vec<Value> = self.handler.tools().iter().map(Tool::to_json).collect();
+        Ok(json!({ "tools": tools }))
+    }
+
+    fn call_tool(&mut self, params: ToolCallRequest) -> Result<Value, ErrorObject> {
+        let arguments = params.arguments.unwrap_or_else(|| json!({}));
+        let context = ToolContext {
+            progress_token: params
+                .meta
+                .and_then(|meta| meta.get("progressToken").cloned()),
+        };
+
+        self.log(&format!("Executing tool: {}", params.name));
+
+        match self.handler.call_tool(&params.name, &arguments, &context) {
+            Ok(output) => Ok(output.to_json()),
+            Err(e) if e.is::<UnknownTool>() => Err(ErrorObject::invalid_params(e.to_string())),
+            Err(e) => {
+                let error = ErrorObject::internal(format!("Tool execution failed: {e}"));
+                Err(match self.handler.error_data(&e) {
+                    Some(data) => error.with_data(data),
+                    None => error,
+                })
+            }
+        }
+    }
+
+    fn set_log_level(&mut self, params: SetLevelRequest) -> Result<Value, ErrorObject> {
+        let level = LogLevel::parse(&params.level).ok_or_else(|| {
+            ErrorObject::invalid_params(format!("Invalid log level: '{}'", params.level))
+        })?;
+        LOG_THRESHOLD.store(level as u8, Ordering::Relaxed);
+        self.log(&format!("Log level set to {}", params.level));
+        Ok(json!({}))
+    }
+}
+
+/// A stdio MCP server around a [`ToolHandler`]
+pub struct McpServer<H> {
+    router: Router<ServerState<H>>,
+    state: ServerState<H>,
+    max_message_bytes: usize,
+}
+
+impl<H: ToolHandler + 'static> McpServer<H> {
+    /// `name` is reported in `initialize`; `log_prefix` tags stderr lines,
+    /// e.g. "Shell MCP"
+    pub fn new(name: impl Into<String>, log_prefix: impl Into<String>, handler: H) -> Self {
+        let router = Router::new()
+            .method("initialize", ServerState::initialize)
+            .method("tools/list", ServerState::list_tools)
+            .method("tools/call", ServerState::call_tool)
+            .method("logging/setLevel", ServerState::set_log_level)
+            .notification("initialized", ServerState::initialized)
+            .notification("notifications/initialized", ServerState::initialized);
+
+        Self {
+            router,
+            state: ServerState {
+                name: name.into(),
+                log_prefix: log_prefix.into(),
+                verbose: false,
+                initialized: false,
+                handler,
+            },
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        }
+    }
+
+    /// Log every request and response to stderr
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.state.verbose = verbose;
+        self
+    }
+
+    /// Largest request read or response written; bigger ones are answered
+    /// with a JSON-RPC error instead
+    pub fn max_message_bytes(mut self, max_message_bytes: usize) -> Self {
+        self.max_message_bytes = max_message_bytes;
+        self
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.state.handler
+    }
+
+    /// Handle one JSON-RPC message, returning the response if it needs one
+    pub fn handle_request(&mut self, request: &Value) -> Option<Value> {
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        self.state.log(&format!("Handling request: {method}"));
+
+        let response = self.router.handle(&mut self.state, request)?;
+        Some(serde_json::to_value(response).unwrap_or_default())
+    }
+
+    /// Serve requests from stdin until it closes
+    pub fn run(&mut self) -> Result<()> {
+        self.serve(io::stdin().lock(), io::stdout())
+    }
+
+    /// Serve requests from `reader`, writing responses to `writer`, until the
+    /// input ends. Requests are newline-delimited or Content-Length framed,
+    /// whichever the client's first message uses, and answered the same way.
+    pub fn serve(&mut self, mut reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+        let limit = self.max_message_bytes;
+        let mut buffer = Vec::new();
+
+        self.state.log("Ready for requests");
+
+        let framing = Framing::detect(reader.fill_buf()?);
+        CONTENT_LENGTH_FRAMING.store(framing == Framing::ContentLength, Ordering::Relaxed);
+
+        loop {
+            let response = match framing.read(&mut reader, &mut buffer, limit)? {
+                LineRead::Eof => break,
+                LineRead::TooLarge(size) => {
+                    eprintln!(
+                        "[{prefix}] Discarding a {size} byte request over the {limit} byte limit",
+                        prefix = self.state.log_prefix
+                    );
+                    Some(too_large_response(message_id_hint(&buffer), size, limit))
+                }
+                LineRead::Line => {
+                    let line = String::from_utf8_lossy(&buffer);
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    self.state.log(&format!("Received: {line}"));
+
+                    match serde_json::from_str::<Value>(line) {
+                        Ok(request) => self.handle_request(&request),
+                        Err(e) => {
+                            eprintln!(
+                                "[{prefix}] Failed to parse JSON: {e}",
+                                prefix = self.state.log_prefix
+                            );
+                            Some(json!(Response::error(
+                                None,
+                                error_codes::PARSE_ERROR,
+                                "Parse error"
+                            )))
+                        }
+                    }
+                }
+            };
+
+            if let Some(response) = response {
+                let mut serialized = serde_json::to_string(&response)?;
+                if serialized.len() > limit {
+                    let id = serde_json::from_value(response["id"].clone()).ok();
+                    serialized =
+                        serde_json::to_string(&too_large_response(id, serialized.len(), limit))?;
+                }
+                write!(writer, "{}", framing.encode(&serialized))?;
+                writer.flush()?;
+                self.state.log(&format!("Sent response: {serialized}"));
+            }
+        }
+
+        self.state.log("Server shutting down");
+        Ok(())
+    }
+}
+
+/// The error answering a request, or standing in for a response, over the size limit
+fn too_large_response(id: Option<RequestId>, size: usize, limit: usize) -> Value {
+    json!(Response::from_error(
+        id,
+        ErrorObject::new(
+            error_codes::MESSAGE_TOO_LARGE,
+            MessageTooLarge { size, limit }.to_string()
+        )
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl ToolHandler for Echo {
+        fn tools(&self) -> Vec<Tool> {
+            vec![
+                Tool::new("echo", "Echo a message")
+                    .param(Param::string("message", "Text to echo").required())
+                    .param(Param::integer("times", "Repetitions").default(1)),
+            ]
+        }
+
+        fn call_tool(
+            &mut self,
+            name: &str,
+            arguments: &Value,
+            _context: &ToolContext,
+        ) -> Result<ToolOutput> {
+            match name {
+                "echo" => match arguments.get("message").and_then(|m| m.as_str()) {
+                    Some(message) => {
+                        let times = arguments.get("times").and_then(|t| t.as_u64()).unwrap_or(1);
+                        Ok(ToolOutput::text(message.repeat(times as usize)))
+                    }
+                    None => anyhow::bail!("Missing 'message' parameter"),
+                },
+                _ => Err(UnknownTool(name.to_string()).into()),
+            }
+        }
+
+        fn error_data(&self, error: &anyhow::Error) -> Option<Value> {
+            Some(json!({"reason": error.to_string()}))
+        }
+    }
+
+    fn call(server: &mut McpServer<Echo>, method: &str, params: Value) -> Value {
+        server
+            .handle_request(&json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}))
+            .expect("requests with an id get a response")
+    }
+
+    #[test]
+    fn test_tool_schema_builder() {
+        let tool = Tool::new("search", "Search things")
+            .param(Param::string("query", "What to find").required())
+            .param(Param::string("mode", "How to match").one_of(&["exact", "fuzzy"]))
+            .param(Param::array("tags", "Tags to filter on", "string"));
+
+        assert_eq!(
+            tool.to_json(),
+            json!({
+                "name": "search",
+                "description": "Search things",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "query": {"type": "string", "description": "What to find"},
+                        "mode": {"type": "string", "description": "How to match", "enum": ["exact", "fuzzy"]},
+                        "tags": {"type": "array", "items": {"type": "string"}, "description": "Tags to filter on"}
+                    },
+                    "required": ["query"]
+                }
+            })
+        );
+        assert!(
+            Tool::new("ping", "Ping").to_json()["inputSchema"]
+                .get("required")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_server_dispatch_and_errors() {
+        let mut server = McpServer::new("echo-server", "Echo MCP", Echo);
+
+        let response = call(&mut server, "initialize", json!({}));
+        assert_eq!(response["result"]["serverInfo"]["name"], "echo-server");
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        assert!(
+            server
+                .handle_request(&json!({"jsonrpc": "2.0", "method": "initialized"}))
+                .is_none()
+        );
+
+        let response = call(&mut server, "tools/list", json!({}));
+        assert_eq!(response["result"]["tools"][0]["name"], "echo");
+
+        let response = call(
+            &mut server,
+            "tools/call",
+            json!({"name": "echo", "arguments": {"message": "hi"}}),
+        );
+        assert_eq!(response["result"]["content"][0]["text"], "hi");
+        assert_eq!(response["result"]["isError"], false);
+
+        let response = call(&mut server, "tools/call", json!({"name": "echo"}));
+        assert_eq!(response["error"]["code"], -32603);
+        assert_eq!(
+            response["error"]["message"],
+            "Tool execution failed: Missing 'message' parameter"
+        );
+        assert_eq!(
+            response["error"]["data"]["reason"],
+            "Missing 'message' parameter"
+        );
+
+        let response = call(&mut server, "tools/call", json!({"name": "nope"}));
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(response["error"]["message"], "Unknown tool: nope");
+
+        let response = call(&mut server, "resources/list", json!({}));
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn test_serve_enforces_message_size_limit() {
+        let mut server = McpServer::new("echo-server", "Echo MCP", Echo).max_message_bytes(400);
+        let input = format!(
+            "{}\n{}\n{}\n",
+            json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "echo", "arguments": {"message": "x".repeat(500)}}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "echo", "arguments": {"message": "x".repeat(50), "times": 10}}}),
+        );
+
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // An oversized request is answered with an error carrying its id
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(
+            responses[0]["error"]["code"],
+            error_codes::MESSAGE_TOO_LARGE
+        );
+        // Later requests are unaffected
+        assert_eq!(responses[1]["result"]["tools"][0]["name"], "echo");
+        // A response over the limit is replaced by an error
+        assert_eq!(responses[2]["id"], 3);
+        assert!(
+            responses[2]["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("exceeds the 400 byte limit")
+        );
+    }
+
+    #[test]
+    fn test_logging_set_level() {
+        let mut server = McpServer::new("echo-server", "Echo MCP", Echo);
+
+        let response = call(&mut server, "initialize", json!({}));
+        assert!(response["result"]["capabilities"]["logging"].is_object());
+
+        let response = call(&mut server, "logging/setLevel", json!({"level": "warning"}));
+        assert_eq!(response["result"], json!({}));
+        assert_eq!(
+            LOG_THRESHOLD.load(Ordering::Relaxed),
+            LogLevel::Warning as u8
+        );
+
+        let response = call(&mut server, "logging/setLevel", json!({"level": "verbose"}));
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(response["error"]["message"], "Invalid log level: 'verbose'");
+
+        assert!(LogLevel::parse("critical") > LogLevel::parse("error"));
+        assert_eq!(LogLevel::Notice.as_str(), "notice");
+    }
+}
@@ -0,0 +1,515 @@
+//! Prometheus metrics of a running agent, served on `/metrics` when
+//! `[metrics] port` is set, next to a `/healthz` check that answers without
+//! touching the database.
+
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Json};
+use axum::routing::get;
+use axum::{Router, serve};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
+
+use crate::events::{Event, EventBus};
+use crate::llm::estimate_tokens;
+use crate::mcp::{HealthProbe, MCPServerHealth};
+use crate::state::StateManager;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// The `[metrics]` table
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Port to serve `/metrics` and `/healthz` on; no endpoint when unset
+    pub port: Option<u16>,
+    /// Address to listen on
+    pub bind: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            port: None,
+            bind: "127.0.0.1".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {count}",
+            count = self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {sum}", sum = self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {count}", count = self.count);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    cycles: BTreeMap<&'static str, u64>,
+    decisions: BTreeMap<String, u64>,
+    llm_latency: Histogram,
+    llm_errors: u64,
+    llm_prompt_tokens: u64,
+    llm_completion_tokens: u64,
+    tool_latency: BTreeMap<String, Histogram>,
+    tool_errors: BTreeMap<String, u64>,
+    /// When the last cycle ended, and whether it succeeded
+    last_cycle: Option<(DateTime<Utc>, bool)>,
+}
+
+/// Counters updated by the reasoning loop
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    /// A cycle ended at `at`
+    pub fn record_cycle(&self, success: bool, at: DateTime<Utc>) {
+        let outcome = if success { "success" } else { "error" };
+        let mut counters = self.lock();
+        *counters.cycles.entry(outcome).or_default() += 1;
+        counters.last_cycle = Some((at, success));
+    }
+
+    /// Count the decisions and tool calls among `event`s
+    pub fn record_event(&self, event: &Event) {
+        match event {
+            Event::DecisionMade { status, .. } => self.record_decision(status),
+            Event::ToolCalled {
+                tool,
+                success,
+                duration_ms,
+                ..
+            } => self.record_tool_call(tool, Duration::from_millis(*duration_ms), *success),
+            Event::AlertRaised { .. } | Event::ServerUnhealthy { .. } => {}
+        }
+    }
+
+    /// A decision finished with `status`, e.g. "success" or "error"
+    fn record_decision(&self, status: &str) {
+        *self.lock().decisions.entry(status.to_string()).or_default() += 1;
+    }
+
+    /// An LLM completion of `prompt`; `response` is None if it failed.
+    /// Tokens are estimated from the text, as providers do not report them.
+    pub fn record_llm_call(&self, elapsed: Duration, prompt: &str, response: Option<&str>) {
+        let mut counters = self.lock();
+        counters.llm_latency.observe(elapsed);
+        counters.llm_prompt_tokens += estimate_tokens(prompt) as u64;
+        match response {
+            Some(response) => counters.llm_completion_tokens += estimate_tokens(response) as u64,
+            None => counters.llm_errors += 1,
+        }
+    }
+
+    fn record_tool_call(&self, tool: &str, elapsed: Duration, success: bool) {
+        let mut counters = self.lock();
+        counters
+            .tool_latency
+            .entry(tool.to_string())
+            .or_default()
+            .observe(elapsed);
+        if !success {
+            *counters.tool_errors.entry(tool.to_string()).or_default() += 1;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.counters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Prometheus text exposition of the counters plus `row_counts` of the
+    /// agent's database tables
+    pub fn render(&self, row_counts: &[(&str, i64)]) -> String {
+        let counters = self.lock();
+        let mut out = String::new();
+
+        describe(
+            &mut out,
+            "replicante_cycles_total",
+            "counter",
+            "Reasoning cycles completed, by outcome",
+        );
+        for (outcome, count) in &counters.cycles {
+            let _ = writeln!(
+                out,
+                "replicante_cycles_total{{outcome=\"{outcome}\"}} {count}"
+            );
+        }
+
+        describe(
+            &mut out,
+            "replicante_decisions_total",
+            "counter",
+            "Decisions finished, by result status",
+        );
+        for (status, count) in &counters.decisions {
+            let _ = writeln!(
+                out,
+                "replicante_decisions_total{{status=\"{status}\"}} {count}",
+                status = escape(status)
+            );
+        }
+
+        describe(
+            &mut out,
+            "replicante_llm_request_duration_seconds",
+            "histogram",
+            "Time taken by LLM completions",
+        );
+        counters
+            .llm_latency
+            .render(&mut out, "replicante_llm_request_duration_seconds", "");
+
+        describe(
+            &mut out,
+            "replicante_llm_errors_total",
+            "counter",
+            "LLM completions that failed",
+        );
+        let _ = writeln!(
+            out,
+            "replicante_llm_errors_total {errors}",
+            errors = counters.llm_errors
+        );
+
+        describe(
+            &mut out,
+            "replicante_llm_tokens_total",
+            "counter",
+            "Estimated tokens sent to and received from the LLM",
+        );
+        let _ = writeln!(
+            out,
+            "replicante_llm_tokens_total{{kind=\"prompt\"}} {tokens}",
+            tokens = counters.llm_prompt_tokens
+        );
+        let _ = writeln!(
+            out,
+            "replicante_llm_tokens_total{{kind=\"completion\"}} {tokens}",
+            tokens = counters.llm_completion_tokens
+        );
+
+        describe(
+            &mut out,
+            "replicante_tool_call_duration_seconds",
+            "histogram",
+            "Time taken by MCP tool calls, by tool",
+        );
+        for (tool, histogram) in &counters.tool_latency {
+            histogram.render(
+                &mut out,
+                "replicante_tool_call_duration_seconds",
+                &format!("tool=\"{tool}\"", tool = escape(tool)),
+            );
+        }
+
+        describe(
+            &mut out,
+            "replicante_tool_errors_total",
+            "counter",
+            "MCP tool calls that failed, by tool",
+        );
+        for (tool, count) in &counters.tool_errors {
+            let _ = writeln!(
+                out,
+                "replicante_tool_errors_total{{tool=\"{tool}\"}} {count}",
+                tool = escape(tool)
+            );
+        }
+
+        describe(
+            &mut out,
+            "replicante_memory_rows",
+            "gauge",
+            "Rows in each table of the agent's database",
+        );
+        for (table, count) in row_counts {
+            let _ = writeln!(out, "replicante_memory_rows{{table=\"{table}\"}} {count}");
+        }
+
+        out
+    }
+}
+
+/// Feed the events published on `events` to `metrics` in the background
+pub fn follow(metrics: Arc<Metrics>, events: &EventBus) {
+    let mut receiver = events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => metrics.record_event(&event),
+                Err(RecvError::Lagged(missed)) => warn!("Metrics missed {missed} events"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+fn describe(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escape a label value for the text exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Body of `/healthz`
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    /// "ok", or "degraded" when the last cycle failed or an MCP server is
+    /// unhealthy
+    pub status: &'static str,
+    pub last_cycle_at: Option<DateTime<Utc>>,
+    pub last_cycle_success: Option<bool>,
+    pub mcp_servers: Vec<MCPServerHealth>,
+}
+
+impl Health {
+    pub async fn check(metrics: &Metrics, mcp: &HealthProbe) -> Self {
+        let last_cycle = metrics.lock().last_cycle;
+        let mcp_servers = mcp.status().await;
+        let degraded = last_cycle.is_some_and(|(_, success)| !success)
+            || mcp_servers.iter().any(|server| !server.is_healthy);
+        Self {
+            status: if degraded { "degraded" } else { "ok" },
+            last_cycle_at: last_cycle.map(|(at, _)| at),
+            last_cycle_success: last_cycle.map(|(_, success)| success),
+            mcp_servers,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    metrics: Arc<Metrics>,
+    state: StateManager,
+    mcp: HealthProbe,
+}
+
+/// Start serving `/metrics` and `/healthz` in the background, returning the
+/// bound address
+pub async fn serve_metrics(
+    config: &MetricsConfig,
+    metrics: Arc<Metrics>,
+    state: StateManager,
+    mcp: HealthProbe,
+) -> Result<Option<SocketAddr>> {
+    let Some(port) = config.port else {
+        return Ok(None);
+    };
+    let listener = tokio::net::TcpListener::bind((config.bind.as_str(), port))
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint on {}:{port}", config.bind))?;
+    let addr = listener.local_addr()?;
+
+    let app = Router::new()
+        .route("/metrics", get(handle_metrics))
+        .route("/healthz", get(handle_healthz))
+        .with_state(MetricsState {
+            metrics,
+            state,
+            mcp,
+        });
+    tokio::spawn(async move {
+        if let Err(e) = serve(listener, app).await {
+            error!("Metrics endpoint stopped: {e}");
+        }
+    });
+    info!("Serving metrics on http://{addr}/metrics and health on /healthz");
+    Ok(Some(addr))
+}
+
+async fn handle_metrics(State(app): State<MetricsState>) -> impl IntoResponse {
+    match app.state.row_counts().await {
+        Ok(row_counts) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            app.metrics.render(&row_counts),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/plain")],
+            format!("{e:#}"),
+        ),
+    }
+}
+
+/// Answers while the agent runs, whatever its status, so it doubles as a
+/// liveness check
+async fn handle_healthz(State(app): State<MetricsState>) -> Json<Health> {
+    Json(Health::check(&app.metrics, &app.mcp).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MockServer;
+
+    #[test]
+    fn test_render_exposes_counters_and_histograms() {
+        let metrics = Metrics::default();
+        metrics.record_cycle(true, Utc::now());
+        metrics.record_cycle(false, Utc::now());
+        metrics.record_event(&Event::DecisionMade {
+            decision_id: 1,
+            status: "success".to_string(),
+            summary: None,
+            duration_ms: Some(20),
+        });
+        metrics.record_llm_call(Duration::from_millis(300), "hello world", Some("hi"));
+        metrics.record_tool_call("fs:read", Duration::from_millis(20), true);
+        metrics.record_event(&Event::ToolCalled {
+            tool: "fs:read".to_string(),
+            success: false,
+            duration_ms: 120_000,
+            error: Some("timeout".to_string()),
+        });
+
+        let text = metrics.render(&[("memory", 7)]);
+        for line in [
+            "replicante_cycles_total{outcome=\"error\"} 1",
+            "replicante_cycles_total{outcome=\"success\"} 1",
+            "replicante_decisions_total{status=\"success\"} 1",
+            "replicante_llm_request_duration_seconds_bucket{le=\"0.25\"} 0",
+            "replicante_llm_request_duration_seconds_bucket{le=\"0.5\"} 1",
+            "replicante_llm_request_duration_seconds_count 1",
+            "replicante_llm_tokens_total{kind=\"prompt\"} 2",
+            "replicante_llm_tokens_total{kind=\"completion\"} 1",
+            "replicante_tool_call_duration_seconds_bucket{tool=\"fs:read\",le=\"0.05\"} 1",
+            "replicante_tool_call_duration_seconds_bucket{tool=\"fs:read\",le=\"60\"} 1",
+            "replicante_tool_call_duration_seconds_bucket{tool=\"fs:read\",le=\"+Inf\"} 2",
+            "replicante_tool_errors_total{tool=\"fs:read\"} 1",
+            "replicante_memory_rows{table=\"memory\"} 7",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line} in\n{text}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_serves_row_counts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("agent.db");
+        let state = StateManager::new(&db.to_string_lossy()).await.unwrap();
+        state
+            .remember("goal", serde_json::json!("survive"))
+            .await
+            .unwrap();
+
+        let config = MetricsConfig {
+            port: Some(0),
+            ..MetricsConfig::default()
+        };
+        let mcp = MockServer::new("mock").client().await.unwrap();
+        let addr = serve_metrics(
+            &config,
+            Arc::new(Metrics::default()),
+            state,
+            mcp.health_probe(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let body = reqwest::get(format!("http://{addr}/metrics"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains("replicante_memory_rows{table=\"memory\"} 1"));
+        assert!(body.contains("replicante_memory_rows{table=\"decisions\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_healthz_reports_last_cycle_and_mcp_servers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = dir.path().join("agent.db");
+        let state = StateManager::new(&db.to_string_lossy()).await.unwrap();
+        let mcp = MockServer::new("mock")
+            .canned("status", serde_json::json!({"ok": true}))
+            .client()
+            .await
+            .unwrap();
+        let metrics = Arc::new(Metrics::default());
+        let config = MetricsConfig {
+            port: Some(0),
+            ..MetricsConfig::default()
+        };
+        let addr = serve_metrics(&config, metrics.clone(), state, mcp.health_probe())
+            .await
+            .unwrap()
+            .unwrap();
+        let healthz = || async {
+            let response = reqwest::get(format!("http://{addr}/healthz"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            response.json::<serde_json::Value>().await.unwrap()
+        };
+
+        let body = healthz().await;
+        assert_eq!(body["status"], "ok");
+        assert!(body["last_cycle_at"].is_null());
+        assert_eq!(body["mcp_servers"][0]["name"], "mock");
+        assert_eq!(body["mcp_servers"][0]["tool_count"], 1);
+
+        let at = DateTime::UNIX_EPOCH;
+        metrics.record_cycle(false, at);
+        let body = healthz().await;
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["last_cycle_at"], serde_json::json!(at));
+        assert_eq!(body["last_cycle_success"], false);
+    }
+}
@@ -0,0 +1,252 @@
+//! Secret stores for `${secret:NAME}` references in agent configs, so API
+//! keys and tokens can live outside the TOML.
+//!
+//! The store is chosen by the config's `[secrets]` table and consulted once,
+//! while the config loads.
+
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where `${secret:NAME}` references are looked up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    /// Environment variables named after the secret
+    #[default]
+    Env,
+    /// One file per secret in `dir`, as Docker and Kubernetes mount them
+    File,
+    /// systemd credentials (`LoadCredential=`), from `$CREDENTIALS_DIRECTORY`
+    Systemd,
+    /// A HashiCorp Vault KV secret whose keys are the secret names
+    Vault,
+}
+
+impl std::fmt::Display for SecretBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Env => "env",
+            Self::File => "file",
+            Self::Systemd => "systemd",
+            Self::Vault => "vault",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The `[secrets]` table of an agent config
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct SecretsConfig {
+    #[serde(default)]
+    pub backend: SecretBackend,
+    /// Directory of secret files, for the file backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    /// Vault server URL; defaults to `VAULT_ADDR`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Vault secret to read, e.g. "secret/data/replicante" for KV version 2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Vault token; defaults to `VAULT_TOKEN`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// File holding the Vault token, instead of `token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<String>,
+}
+
+/// A source of named secrets
+pub trait SecretStore {
+    /// The secret called `name`, or `None` if the store does not have it
+    fn get(&self, name: &str) -> Result<Option<String>>;
+}
+
+impl SecretsConfig {
+    /// Open the configured store. Vault is read here, once, so a config
+    /// with many references makes a single request.
+    pub fn store(&self) -> Result<Box<dyn SecretStore>> {
+        match self.backend {
+            SecretBackend::Env => Ok(Box::new(EnvStore)),
+            SecretBackend::File => {
+                let Some(dir) = &self.dir else {
+                    bail!("secrets.dir must be set for the file secret backend");
+                };
+                Ok(Box::new(DirStore {
+                    dir: PathBuf::from(dir),
+                }))
+            }
+            SecretBackend::Systemd => {
+                let dir = std::env::var_os("CREDENTIALS_DIRECTORY").context(
+                    "CREDENTIALS_DIRECTORY is not set; run under systemd with LoadCredential=",
+                )?;
+                Ok(Box::new(DirStore {
+                    dir: PathBuf::from(dir),
+                }))
+            }
+            SecretBackend::Vault => {
+                let address = self
+                    .address
+                    .clone()
+                    .or_else(|| std::env::var("VAULT_ADDR").ok())
+                    .context("Set secrets.address or VAULT_ADDR for the vault secret backend")?;
+                let Some(path) = &self.path else {
+                    bail!("secrets.path must be set for the vault secret backend");
+                };
+                let token = match (&self.token, &self.token_file) {
+                    (Some(_), Some(_)) => {
+                        bail!("secrets.token and secrets.token_file are both set; keep one")
+                    }
+                    (Some(token), None) => token.clone(),
+                    (None, Some(file)) => read_secret_file(Path::new(file))
+                        .context("Failed to read secrets.token_file")?,
+                    (None, None) => std::env::var("VAULT_TOKEN").context(
+                        "Set secrets.token, secrets.token_file or VAULT_TOKEN for the vault secret backend",
+                    )?,
+                };
+                Ok(Box::new(VaultStore {
+                    values: fetch_vault_secret(&address, path, token)?,
+                }))
+            }
+        }
+    }
+}
+
+/// Read a secret from a file, without the trailing newline editors and
+/// `echo` leave behind
+pub fn read_secret_file(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret file {}", path.display()))?;
+    Ok(contents.trim_end_matches(['\r', '\n']).to_string())
+}
+
+struct EnvStore;
+
+impl SecretStore for EnvStore {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(std::env::var(name).ok())
+    }
+}
+
+struct DirStore {
+    dir: PathBuf,
+}
+
+impl SecretStore for DirStore {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let path = self.dir.join(name);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        read_secret_file(&path).map(Some)
+    }
+}
+
+struct VaultStore {
+    values: HashMap<String, String>,
+}
+
+impl SecretStore for VaultStore {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.values.get(name).cloned())
+    }
+}
+
+/// Read one Vault secret. Config loading is synchronous and may run inside
+/// the agent's runtime, so the request gets a thread and runtime of its own.
+fn fetch_vault_secret(address: &str, path: &str, token: String) -> Result<HashMap<String, String>> {
+    let url = format!(
+        "{address}/v1/{path}",
+        address = address.trim_end_matches('/'),
+        path = path.trim_start_matches('/')
+    );
+    std::thread::spawn(move || -> Result<HashMap<String, String>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let response = reqwest::Client::new()
+                .get(&url)
+                .header("X-Vault-Token", token)
+                .timeout(Duration::from_secs(30))
+                .send()
+                .await
+                .with_context(|| format!("Failed to reach Vault at {url}"))?;
+            let status = response.status();
+            if !status.is_success() {
+                bail!("Vault returned {status} for {url}");
+            }
+            let body: Value = response.json().await?;
+            vault_values(&body).with_context(|| format!("Unexpected Vault response from {url}"))
+        })
+    })
+    .join()
+    .map_err(|_| anyhow::anyhow!("Vault request thread panicked"))?
+}
+
+/// The key/value pairs of a Vault read response, from `data.data` for KV
+/// version 2 or `data` for version 1
+fn vault_values(body: &Value) -> Result<HashMap<String, String>> {
+    let data = body.get("data").context("missing data")?;
+    let values = match data.get("data") {
+        Some(inner) if inner.is_object() && data.get("metadata").is_some() => inner,
+        _ => data,
+    };
+    let values = values.as_object().context("data is not an object")?;
+    Ok(values
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            (key.clone(), value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_vault_values_reads_both_kv_versions() {
+        let v2 = json!({
+            "data": {
+                "data": {"anthropic_key": "sk-v2", "port": 8080},
+                "metadata": {"version": 3}
+            }
+        });
+        let values = vault_values(&v2).unwrap();
+        assert_eq!(values["anthropic_key"], "sk-v2");
+        assert_eq!(values["port"], "8080");
+
+        let v1 = json!({"data": {"anthropic_key": "sk-v1"}});
+        assert_eq!(vault_values(&v1).unwrap()["anthropic_key"], "sk-v1");
+
+        assert!(vault_values(&json!({"errors": []})).is_err());
+    }
+
+    #[test]
+    fn test_dir_store_trims_trailing_newline() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("api_key"), "sk-file\n").unwrap();
+        let store = SecretsConfig {
+            backend: SecretBackend::File,
+            dir: Some(dir.path().to_string_lossy().into_owned()),
+            ..Default::default()
+        }
+        .store()
+        .unwrap();
+
+        assert_eq!(store.get("api_key").unwrap().as_deref(), Some("sk-file"));
+        assert_eq!(store.get("missing").unwrap(), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! Running the agent or supervisor as a system service: PID files,
+//! detaching from the terminal, and systemd `Type=notify` readiness.
+
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Tell systemd about a state change, e.g. "READY=1" or "STOPPING=1".
+/// Does nothing unless systemd set `NOTIFY_SOCKET`, so it is always safe
+/// to call.
+pub fn notify(state: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(e) = send_notification(Path::new(&socket), state) {
+        warn!("Failed to notify systemd of {state}: {e:#}");
+    } else {
+        debug!("Notified systemd: {state}");
+    }
+}
+
+/// `RELOADING=1` with the timestamp systemd wants for `Type=notify-reload`
+pub fn notify_reloading() {
+    notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+}
+
+#[cfg(unix)]
+fn monotonic_usec() -> i64 {
+    use nix::time::{ClockId, clock_gettime};
+
+    clock_gettime(ClockId::CLOCK_MONOTONIC)
+        .map(|now| now.tv_sec() * 1_000_000 + now.tv_nsec() / 1_000)
+        .unwrap_or_default()
+}
+
+#[cfg(not(unix))]
+fn monotonic_usec() -> i64 {
+    0
+}
+
+#[cfg(unix)]
+fn send_notification(socket: &Path, state: &str) -> Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let sender = UnixDatagram::unbound()?;
+    let path = socket.to_string_lossy();
+    // A leading '@' names a socket in the abstract namespace
+    if let Some(name) = path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            sender.send_to_addr(state.as_bytes(), &addr)?;
+            return Ok(());
+        }
+        #[cfg(not(target_os = "linux"))]
+        bail!("Abstract notify socket {name} is only supported on Linux");
+    }
+    sender.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_notification(_socket: &Path, _state: &str) -> Result<()> {
+    bail!("systemd notifications need a Unix platform")
+}
+
+/// A file holding this process's ID, removed when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current process ID to `path`, refusing if the file names
+    /// a process that is still running
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Ok(contents) = fs::read_to_string(path)
+            && let Ok(pid) = contents.trim().parse::<i32>()
+            && pid as u32 != std::process::id()
+            && process_running(pid)
+        {
+            bail!(
+                "{} names process {pid}, which is still running",
+                path.display()
+            );
+        }
+        fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write PID file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn process_running(pid: i32) -> bool {
+    use nix::errno::Errno;
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+
+    // Signal 0 only checks that the process exists; EPERM means it does
+    // but belongs to someone else
+    matches!(kill(Pid::from_raw(pid), None), Ok(()) | Err(Errno::EPERM))
+}
+
+#[cfg(not(unix))]
+fn process_running(_pid: i32) -> bool {
+    false
+}
+
+/// Detach from the terminal: fork twice so the process is orphaned and
+/// cannot reacquire a terminal, start a new session, and point stdin at
+/// /dev/null and stdout/stderr at `log_file` (or /dev/null).
+///
+/// Must be called before any threads are started, so before the tokio
+/// runtime. The working directory is kept, so relative paths in configs
+/// still resolve as they would in the foreground.
+#[cfg(unix)]
+pub fn daemonize(log_file: Option<&Path>) -> Result<()> {
+    use nix::unistd::{ForkResult, dup2, fork, setsid};
+    use std::os::fd::AsRawFd;
+
+    let log = match log_file {
+        Some(path) => fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?,
+        None => fs::OpenOptions::new().write(true).open("/dev/null")?,
+    };
+    let null = fs::File::open("/dev/null")?;
+
+    // SAFETY: no other threads exist yet, so the child cannot inherit a
+    // lock held by one of them
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("Failed to fork")? {
+        std::process::exit(0);
+    }
+    setsid().context("Failed to start a new session")?;
+    // SAFETY: as above, still single-threaded
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("Failed to fork")? {
+        std::process::exit(0);
+    }
+
+    dup2(null.as_raw_fd(), 0)?;
+    dup2(log.as_raw_fd(), 1)?;
+    dup2(log.as_raw_fd(), 2)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_log_file: Option<&Path>) -> Result<()> {
+    bail!("--daemon is only supported on Unix platforms")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_refuses_a_running_process_and_cleans_up() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("agent.pid");
+
+        // A stale file from a process that is gone is replaced
+        fs::write(&path, "999999999\n").unwrap();
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap().trim(),
+            std::process::id().to_string()
+        );
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // PID 1 is always running
+        fs::write(&path, "1\n").unwrap();
+        assert!(PidFile::create(&path).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_notify_sends_state_to_the_socket() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        send_notification(&path, "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+}
@@ -1,26 +1,153 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use rusqlite::{OptionalExtension, params};
+use rusqlite::{OpenFlags, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use tokio_rusqlite::Connection;
 use tracing::{debug, info};
 
+use crate::clock::{Clock, SystemClock};
+use crate::truncate;
 use crate::{DecisionRecord, DecisionResult};
 
+/// Read-only connections [`StateManager::new`] opens next to the writer
+pub const DEFAULT_READERS: usize = 4;
+
+/// How long a connection waits for a lock held by another before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
 pub struct StateManager {
-    conn: Arc<Connection>,
+    /// Every write goes through this connection, one at a time
+    writer: Arc<Connection>,
+    /// Read-only connections that queries take turns on, so observation
+    /// reads don't queue behind decision writes. Empty for an in-memory
+    /// database, which is private to its connection and read through the
+    /// writer.
+    readers: Arc<[Connection]>,
+    next_reader: Arc<AtomicUsize>,
+    /// Source of every timestamp written, and of "now" in age queries
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub key: String,
+    pub value: Value,
+    pub updated_at: String,
+}
+
+/// A `subject predicate object` edge of the knowledge graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fact {
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
+    /// What the fact was learned from, e.g. the tool whose result it is in
+    pub source: Option<String>,
+    pub created_at: String,
+}
+
+/// A timed phase of the reasoning cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CyclePhase {
+    Observe,
+    /// Assembling the prompt
+    Prompt,
+    /// Waiting for the LLM's response
+    Llm,
+    /// Parsing the response into a thought
+    Parse,
+    /// Running the chosen action, e.g. a tool call
+    Act,
+    Learn,
+}
+
+impl CyclePhase {
+    pub const ALL: [CyclePhase; 6] = [
+        CyclePhase::Observe,
+        CyclePhase::Prompt,
+        CyclePhase::Llm,
+        CyclePhase::Parse,
+        CyclePhase::Act,
+        CyclePhase::Learn,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CyclePhase::Observe => "observe",
+            CyclePhase::Prompt => "prompt",
+            CyclePhase::Llm => "llm",
+            CyclePhase::Parse => "parse",
+            CyclePhase::Act => "act",
+            CyclePhase::Learn => "learn",
+        }
+    }
+}
+
+/// Phase durations of one reasoning cycle, as stored in `cycle_metrics`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CycleMetrics {
+    pub id: i64,
+    /// The cycle's decision, if it got that far
+    pub decision_id: Option<i64>,
+    pub success: bool,
+    pub total_ms: u64,
+    /// Milliseconds per phase, indexed by [`CyclePhase`]; `None` for phases
+    /// the cycle did not reach
+    pub phase_ms: [Option<u64>; 6],
+    pub created_at: String,
+}
+
+impl CycleMetrics {
+    pub fn phase(&self, phase: CyclePhase) -> Option<u64> {
+        self.phase_ms[phase as usize]
+    }
+
+    pub fn record(&mut self, phase: CyclePhase, elapsed: Duration) {
+        self.phase_ms[phase as usize] = Some(elapsed.as_millis() as u64);
+    }
+
+    /// Phases that took longer than their threshold in `slow_phase_ms`, with
+    /// their duration and threshold
+    pub fn slow_phases(
+        &self,
+        slow_phase_ms: &BTreeMap<String, u64>,
+    ) -> Vec<(CyclePhase, u64, u64)> {
+        CyclePhase::ALL
+            .into_iter()
+            .filter_map(|phase| {
+                let ms = self.phase(phase)?;
+                let threshold = *slow_phase_ms.get(phase.as_str())?;
+                (ms > threshold).then_some((phase, ms, threshold))
+            })
+            .collect()
+    }
 }
 
 impl StateManager {
     pub async fn new(database_path: &str) -> Result<Self> {
+        Self::with_readers(database_path, DEFAULT_READERS).await
+    }
+
+    /// Open or create the database with a writer and `readers` read-only
+    /// connections. The database is switched to WAL mode, in which readers
+    /// see the last commit while a write is in progress.
+    pub async fn with_readers(database_path: &str, readers: usize) -> Result<Self> {
         let conn = Connection::open(database_path)
             .await
             .context("Failed to open database connection")?;
 
         // Create tables
         conn.call(|conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            // Reports "memory" instead for in-memory databases
+            conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+
             conn.execute(
                 "CREATE TABLE IF NOT EXISTS memory (
                     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -84,31 +211,153 @@ impl StateManager {
                 [],
             )?;
 
+            // Knowledge graph: named entities and the relations between them
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS entities (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT UNIQUE NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS relations (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    subject_id INTEGER NOT NULL REFERENCES entities(id),
+                    predicate TEXT NOT NULL,
+                    object_id INTEGER NOT NULL REFERENCES entities(id),
+                    source TEXT,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    UNIQUE (subject_id, predicate, object_id)
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS relations_object ON relations (object_id)",
+                [],
+            )?;
+
+            // How long each phase of every reasoning cycle took
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS cycle_metrics (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    decision_id INTEGER,
+                    success BOOLEAN NOT NULL,
+                    total_ms INTEGER NOT NULL,
+                    observe_ms INTEGER,
+                    prompt_ms INTEGER,
+                    llm_ms INTEGER,
+                    parse_ms INTEGER,
+                    act_ms INTEGER,
+                    learn_ms INTEGER,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )",
+                [],
+            )?;
+
             Ok(())
         })
         .await
         .context("Failed to create database tables")?;
 
-        info!("Database initialized at: {database_path}");
+        let in_memory = matches!(database_path, "" | ":memory:");
+        let mut pool = Vec::new();
+        for _ in 0..if in_memory { 0 } else { readers } {
+            let reader = Connection::open_with_flags(
+                database_path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )
+            .await
+            .context("Failed to open database read connection")?;
+            reader
+                .call(|conn| Ok(conn.busy_timeout(BUSY_TIMEOUT)?))
+                .await?;
+            pool.push(reader);
+        }
+
+        info!(
+            "Database initialized at: {database_path} ({readers} read connections)",
+            readers = pool.len()
+        );
+
+        Ok(Self {
+            writer: Arc::new(conn),
+            readers: pool.into(),
+            next_reader: Arc::new(AtomicUsize::new(0)),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Open an existing agent database without creating or modifying any tables.
+    /// Used by the supervisor to inspect an agent's state.
+    pub async fn open_read_only(database_path: &str) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            database_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .await
+        .with_context(|| format!("Failed to open database {database_path} read-only"))?;
 
         Ok(Self {
-            conn: Arc::new(conn),
+            writer: Arc::new(conn),
+            readers: Arc::new([]),
+            next_reader: Arc::new(AtomicUsize::new(0)),
+            clock: Arc::new(SystemClock),
         })
     }
 
+    /// The connection for the next query: the read connections in turn, or
+    /// the writer without any
+    fn reader(&self) -> &Connection {
+        if self.readers.is_empty() {
+            return &self.writer;
+        }
+        let next = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        &self.readers[next % self.readers.len()]
+    }
+
+    /// Timestamp rows with `clock` instead of the wall clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The current time in SQLite's `CURRENT_TIMESTAMP` format
+    fn timestamp(&self) -> String {
+        self.clock.now().format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Write a consistent copy of the database to `target_path`.
+    /// Safe to call while the owning agent keeps writing.
+    pub async fn snapshot_to(&self, target_path: &str) -> Result<()> {
+        let target = target_path.to_string();
+
+        self.writer
+            .call(move |conn| {
+                conn.execute("VACUUM INTO ?1", params![target])?;
+                Ok(())
+            })
+            .await
+            .with_context(|| format!("Failed to snapshot database to {target_path}"))?;
+
+        Ok(())
+    }
+
     pub async fn remember(&self, key: &str, value: Value) -> Result<()> {
         let value_str = serde_json::to_string(&value)?;
         let key_clone = key.to_string();
+        let now = self.timestamp();
 
-        self.conn
+        self.writer
             .call(move |conn| {
                 // Insert or update
                 conn.execute(
-                    "INSERT INTO memory (key, value) VALUES (?1, ?2)
+                    "INSERT INTO memory (key, value, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)
                      ON CONFLICT(key) DO UPDATE SET 
                      value = excluded.value,
-                     updated_at = CURRENT_TIMESTAMP",
-                    params![key_clone, value_str],
+                     updated_at = excluded.updated_at",
+                    params![key_clone, value_str, now],
                 )?;
                 Ok(())
             })
@@ -119,12 +368,11 @@ impl StateManager {
         Ok(())
     }
 
-    #[allow(dead_code)]
     pub async fn recall(&self, key: &str) -> Result<Option<Value>> {
         let key_clone = key.to_string();
 
         let value_str_opt = self
-            .conn
+            .reader()
             .call(move |conn| {
                 let mut stmt = conn.prepare("SELECT value FROM memory WHERE key = ?1")?;
                 let mut rows = stmt.query_map(params![key_clone], |row| row.get::<_, String>(0))?;
@@ -150,9 +398,169 @@ impl StateManager {
         }
     }
 
+    /// Delete the memory entry `key`, returning whether there was one
+    pub async fn forget(&self, key: &str) -> Result<bool> {
+        let key_clone = key.to_string();
+
+        let deleted = self
+            .writer
+            .call(move |conn| {
+                let deleted =
+                    conn.execute("DELETE FROM memory WHERE key = ?1", params![key_clone])?;
+                Ok(deleted)
+            })
+            .await
+            .context("Failed to forget value")?;
+
+        debug!("Forgot: {key}");
+        Ok(deleted > 0)
+    }
+
+    /// Number of rows in each table, e.g. for metrics
+    pub async fn row_counts(&self) -> Result<Vec<(&'static str, i64)>> {
+        self.reader()
+            .call(|conn| {
+                let mut counts = Vec::new();
+                for table in [
+                    "memory",
+                    "decisions",
+                    "capabilities",
+                    "action_patterns",
+                    "learning_metrics",
+                    "entities",
+                    "relations",
+                    "cycle_metrics",
+                ] {
+                    let count: i64 =
+                        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                            row.get(0)
+                        })?;
+                    counts.push((table, count));
+                }
+                Ok(counts)
+            })
+            .await
+            .context("Failed to count rows")
+    }
+
+    /// Record that `subject predicate object`, e.g. "alice follows bob".
+    /// Returns false if the fact was already known.
+    pub async fn add_fact(&self, subject: &str, predicate: &str, object: &str) -> Result<bool> {
+        let added = self
+            .add_facts(
+                vec![(
+                    subject.to_string(),
+                    predicate.to_string(),
+                    object.to_string(),
+                )],
+                None,
+            )
+            .await?;
+        Ok(added > 0)
+    }
+
+    /// Record several facts learned from `source` at once, returning how
+    /// many were new
+    pub async fn add_facts(
+        &self,
+        facts: Vec<(String, String, String)>,
+        source: Option<String>,
+    ) -> Result<usize> {
+        let now = self.timestamp();
+        self.writer
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut added = 0;
+                {
+                    let mut insert_entity = tx.prepare(
+                        "INSERT OR IGNORE INTO entities (name, created_at) VALUES (?1, ?2)",
+                    )?;
+                    let mut entity_id = tx.prepare("SELECT id FROM entities WHERE name = ?1")?;
+                    let mut entity = |name: &str| -> rusqlite::Result<i64> {
+                        insert_entity.execute([name, &now])?;
+                        entity_id.query_row([name], |row| row.get(0))
+                    };
+                    let mut relation = tx.prepare(
+                        "INSERT OR IGNORE INTO relations
+                         (subject_id, predicate, object_id, source, created_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                    )?;
+                    for (subject, predicate, object) in &facts {
+                        let subject_id = entity(subject)?;
+                        let object_id = entity(object)?;
+                        added += relation
+                            .execute(params![subject_id, predicate, object_id, source, now])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(added)
+            })
+            .await
+            .context("Failed to add facts")
+    }
+
+    /// Facts with `entity` as their subject or object
+    pub async fn facts_about(&self, entity: &str) -> Result<Vec<Fact>> {
+        self.related_facts(entity, 1, usize::MAX).await
+    }
+
+    /// Facts touching the entities less than `depth` relations away from
+    /// `entity`, in either direction, nearest first, at most `limit`.
+    /// Depth 1 gives the facts about `entity` itself.
+    pub async fn related_facts(
+        &self,
+        entity: &str,
+        depth: usize,
+        limit: usize,
+    ) -> Result<Vec<Fact>> {
+        let entity = entity.to_string();
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        self.reader()
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "WITH RECURSIVE reachable(id, distance) AS (
+                         SELECT id, 0 FROM entities WHERE name = ?1
+                         UNION
+                         SELECT CASE WHEN r.subject_id = reachable.id
+                                     THEN r.object_id ELSE r.subject_id END,
+                                reachable.distance + 1
+                         FROM relations r
+                         JOIN reachable
+                           ON reachable.id IN (r.subject_id, r.object_id)
+                         WHERE reachable.distance + 1 < ?2
+                     ),
+                     nearest(id, distance) AS (
+                         SELECT id, MIN(distance) FROM reachable GROUP BY id
+                     )
+                     SELECT s.name, r.predicate, o.name, r.source, r.created_at
+                     FROM relations r
+                     JOIN nearest n ON n.id IN (r.subject_id, r.object_id)
+                     JOIN entities s ON s.id = r.subject_id
+                     JOIN entities o ON o.id = r.object_id
+                     GROUP BY r.id
+                     ORDER BY MIN(n.distance), r.id
+                     LIMIT ?3",
+                )?;
+                let facts = stmt
+                    .query_map(params![entity, depth as i64, limit], |row| {
+                        Ok(Fact {
+                            subject: row.get(0)?,
+                            predicate: row.get(1)?,
+                            object: row.get(2)?,
+                            source: row.get(3)?,
+                            created_at: row.get(4)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(facts)
+            })
+            .await
+            .context("Failed to query facts")
+    }
+
     pub async fn get_memory(&self) -> Result<Value> {
         let memory = self
-            .conn
+            .reader()
             .call(|conn| {
                 let mut stmt = conn.prepare("SELECT key, value FROM memory")?;
                 let memory_iter = stmt.query_map([], |row| {
@@ -175,19 +583,76 @@ impl StateManager {
         Ok(memory)
     }
 
+    /// List memory entries, optionally restricted to keys starting with `prefix`,
+    /// most recently updated first
+    pub async fn list_memory(
+        &self,
+        prefix: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<MemoryEntry>> {
+        let pattern = format!(
+            "{prefix}%",
+            prefix = prefix
+                .unwrap_or_default()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        let entries = self
+            .reader()
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT key, value, updated_at
+                     FROM memory
+                     WHERE key LIKE ?1 ESCAPE '\\'
+                     ORDER BY updated_at DESC, key
+                     LIMIT ?2 OFFSET ?3",
+                )?;
+
+                let rows = stmt.query_map(params![pattern, limit, offset], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?;
+
+                let mut entries = Vec::new();
+                for row in rows {
+                    let (key, value_str, updated_at) = row?;
+                    let value =
+                        serde_json::from_str(&value_str).unwrap_or(Value::String(value_str));
+                    entries.push(MemoryEntry {
+                        key,
+                        value,
+                        updated_at,
+                    });
+                }
+
+                Ok(entries)
+            })
+            .await
+            .context("Failed to list memory")?;
+
+        Ok(entries)
+    }
+
     /// Get a summarized version of memory for LLM context
     /// Limits to recent and relevant entries to avoid context explosion
     /// Clean up old memory entries to prevent unbounded growth
     pub async fn cleanup_old_memory(&self, keep_days: i64) -> Result<usize> {
+        let now = self.timestamp();
         let deleted = self
-            .conn
+            .writer
             .call(move |conn| {
                 // Delete old tool results and errors
                 let mut result = conn.execute(
                     "DELETE FROM memory 
                      WHERE (key LIKE 'tool_result_%' OR key LIKE 'error_%')
-                       AND datetime(updated_at) < datetime('now', ?1)",
-                    params![format!("-{keep_days} days")],
+                       AND datetime(updated_at) < datetime(?2, ?1)",
+                    params![format!("-{keep_days} days"), now],
                 )?;
 
                 // Also delete discovered_tools as it's redundant
@@ -202,12 +667,19 @@ impl StateManager {
         Ok(deleted)
     }
 
-    pub async fn get_memory_summary(&self, max_entries: usize, max_size: usize) -> Result<Value> {
+    /// Recent tool results and the most important other entries, within
+    /// `max_tokens` overall and `value_tokens` per value
+    pub async fn get_memory_summary(
+        &self,
+        max_entries: usize,
+        max_tokens: usize,
+        value_tokens: usize,
+    ) -> Result<Value> {
         let memory = self
-            .conn
+            .reader()
             .call(move |conn| {
                 let mut memory = serde_json::Map::new();
-                let mut total_size = 0;
+                let mut total_tokens = 0;
                 let mut entries_count = 0;
 
                 // First, get the last 3 tool results (most recent)
@@ -229,24 +701,18 @@ impl StateManager {
 
                 // Add tool results to memory
                 for entry in tool_results {
-                    if entries_count >= max_entries || total_size >= max_size {
+                    if entries_count >= max_entries || total_tokens >= max_tokens {
                         break;
                     }
 
-                    let (key, value_str, size) = entry?;
+                    let (key, value_str, _) = entry?;
 
-                    if let Ok(mut value) = serde_json::from_str::<Value>(&value_str) {
+                    if let Ok(value) = serde_json::from_str::<Value>(&value_str) {
                         // Truncate large values in tool results
-                        if let Some(content) =
-                            value.get("truncated_content").or(value.get("content"))
-                            && let Some(s) = content.as_str()
-                            && s.len() > 1000
-                        {
-                            value["truncated_content"] =
-                                Value::String(format!("{}... [truncated]", &s[..1000]));
-                        }
+                        let value = truncate::truncate_json(&value, value_tokens, value_tokens)
+                            .unwrap_or(value);
+                        total_tokens += truncate::json_tokens(&value);
                         memory.insert(key, value);
-                        total_size += size.min(1000) as usize; // Count truncated size
                         entries_count += 1;
                     }
                 }
@@ -278,26 +744,23 @@ impl StateManager {
 
                 // Add other memory entries
                 for entry in memory_iter {
-                    if entries_count >= max_entries || total_size >= max_size {
+                    if entries_count >= max_entries || total_tokens >= max_tokens {
                         break;
                     }
 
-                    let (key, value_str, size) = entry?;
-
-                    // Stop if we exceed size limit
-                    if total_size + size as usize > max_size {
-                        break;
-                    }
+                    let (key, value_str, _) = entry?;
 
-                    if let Ok(mut value) = serde_json::from_str::<Value>(&value_str) {
-                        // Truncate large string values
-                        if let Some(s) = value.as_str()
-                            && s.len() > 1000
-                        {
-                            value = Value::String(format!("{}... [truncated]", &s[..1000]));
+                    if let Ok(value) = serde_json::from_str::<Value>(&value_str) {
+                        // Truncate large values
+                        let value = truncate::truncate_json(&value, value_tokens, value_tokens)
+                            .unwrap_or(value);
+                        let tokens = truncate::json_tokens(&value);
+                        // Stop if we exceed the budget
+                        if total_tokens + tokens > max_tokens {
+                            break;
                         }
                         memory.insert(key, value);
-                        total_size += size as usize;
+                        total_tokens += tokens;
                         entries_count += 1;
                     }
                 }
@@ -316,7 +779,7 @@ impl StateManager {
                         "total_entries": stats.0,
                         "total_size": stats.1,
                         "shown_entries": memory.len(),
-                        "shown_size": total_size
+                        "shown_tokens": total_tokens
                     }),
                 );
 
@@ -337,13 +800,15 @@ impl StateManager {
         let thought_clone = thought.to_string();
         let action_clone = action.to_string();
         let result_clone = result.map(|s| s.to_string());
+        let now = self.timestamp();
 
         let id = self
-            .conn
+            .writer
             .call(move |conn| {
                 conn.execute(
-                    "INSERT INTO decisions (thought, action, result) VALUES (?1, ?2, ?3)",
-                    params![thought_clone, action_clone, result_clone],
+                    "INSERT INTO decisions (thought, action, result, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![thought_clone, action_clone, result_clone, now],
                 )?;
                 Ok(conn.last_insert_rowid())
             })
@@ -361,7 +826,7 @@ impl StateManager {
     ) -> Result<()> {
         let result_json = serde_json::to_string(result)?;
 
-        self.conn
+        self.writer
             .call(move |conn| {
                 conn.execute(
                     "UPDATE decisions SET result = ?1 WHERE id = ?2",
@@ -378,7 +843,7 @@ impl StateManager {
 
     pub async fn get_recent_decisions(&self, limit: usize) -> Result<Vec<String>> {
         let decisions = self
-            .conn
+            .reader()
             .call(move |conn| {
                 let mut stmt = conn.prepare(
                     "SELECT thought, action, result, created_at 
@@ -417,7 +882,7 @@ impl StateManager {
         limit: usize,
     ) -> Result<Vec<DecisionRecord>> {
         let decisions = self
-            .conn
+            .reader()
             .call(move |conn| {
                 let mut stmt = conn.prepare(
                     "SELECT id, thought, action, result, created_at 
@@ -426,64 +891,49 @@ impl StateManager {
                      LIMIT ?1",
                 )?;
 
-                let decisions = stmt.query_map(params![limit], |row| {
-                    let id = row.get::<_, i64>(0)?;
-                    let thought = row.get::<_, String>(1)?;
-                    let action_str = row.get::<_, String>(2)?;
-                    let result_str: Option<String> = row.get(3)?;
-                    let created_at_str = row.get::<_, String>(4)?;
-
-                    // Parse action and parameters from the stored string
-                    // Format is "action: <action>, params: <params>" or just plain action
-                    let (action, parameters) = if let Some(idx) = action_str.find(", params:") {
-                        // Has params format: "action: <action>, params: <params>"
-                        let action_part = if action_str.starts_with("action: ") {
-                            action_str[7..idx].trim().to_string() // Skip "action: " and trim
-                        } else {
-                            action_str[..idx].trim().to_string()
-                        };
-                        let params_part = &action_str[idx + 9..]; // Skip ", params: "
-                        let params =
-                            if params_part.trim() != "None" && params_part.trim() != "Some(Null)" {
-                                serde_json::from_str(params_part.trim()).ok()
-                            } else {
-                                None
-                            };
-                        (action_part, params)
-                    } else if action_str.starts_with("action: ") {
-                        // Simple format with "action: " prefix
-                        (action_str[7..].trim().to_string(), None)
-                    } else {
-                        // Plain action string
-                        (action_str.trim().to_string(), None)
-                    };
+                let decisions = stmt.query_map(params![limit], decision_from_row)?;
 
-                    // Parse result if it's JSON
-                    let result =
-                        result_str.and_then(|s| serde_json::from_str::<DecisionResult>(&s).ok());
+                let mut results = Vec::new();
+                for decision in decisions {
+                    results.push(decision?);
+                }
 
-                    // Parse timestamp
-                    let timestamp = if let Ok(dt) = DateTime::parse_from_rfc3339(&created_at_str) {
-                        dt.with_timezone(&Utc)
-                    } else {
-                        // Fallback for SQLite's default format
-                        let naive = chrono::NaiveDateTime::parse_from_str(
-                            &created_at_str,
-                            "%Y-%m-%d %H:%M:%S",
-                        )
-                        .unwrap_or_else(|_| chrono::Local::now().naive_utc());
-                        DateTime::from_naive_utc_and_offset(naive, Utc)
-                    };
+                Ok(results)
+            })
+            .await
+            .context("Failed to get recent decisions")?;
 
-                    Ok(DecisionRecord {
-                        id,
-                        timestamp,
-                        thought,
-                        action,
-                        parameters,
-                        result,
-                    })
-                })?;
+        Ok(decisions)
+    }
+
+    /// Page through decisions, newest first. `action` matches as a substring of the
+    /// recorded action and `status` against the stored result status.
+    pub async fn get_decisions_page(
+        &self,
+        limit: usize,
+        offset: usize,
+        action: Option<&str>,
+        status: Option<&str>,
+    ) -> Result<Vec<DecisionRecord>> {
+        let action = action.map(|a| a.to_string());
+        let status = status.map(|s| s.to_string());
+
+        let decisions = self
+            .reader()
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, thought, action, result, created_at
+                     FROM decisions
+                     WHERE (?1 IS NULL OR instr(action, ?1) > 0)
+                       AND (?2 IS NULL
+                            OR CASE WHEN json_valid(result)
+                                    THEN json_extract(result, '$.status') END = ?2)
+                     ORDER BY id DESC
+                     LIMIT ?3 OFFSET ?4",
+                )?;
+
+                let decisions =
+                    stmt.query_map(params![action, status, limit, offset], decision_from_row)?;
 
                 let mut results = Vec::new();
                 for decision in decisions {
@@ -493,11 +943,60 @@ impl StateManager {
                 Ok(results)
             })
             .await
-            .context("Failed to get recent decisions")?;
+            .context("Failed to get decisions page")?;
+
+        Ok(decisions)
+    }
+
+    /// Decisions recorded after `after_id`, oldest first
+    pub async fn get_decisions_since(&self, after_id: i64) -> Result<Vec<DecisionRecord>> {
+        let decisions = self
+            .reader()
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, thought, action, result, created_at
+                     FROM decisions
+                     WHERE id > ?1
+                     ORDER BY id ASC",
+                )?;
+
+                let decisions = stmt.query_map(params![after_id], decision_from_row)?;
+
+                let mut results = Vec::new();
+                for decision in decisions {
+                    results.push(decision?);
+                }
+
+                Ok(results)
+            })
+            .await
+            .context("Failed to get new decisions")?;
 
         Ok(decisions)
     }
 
+    pub async fn get_decision(&self, id: i64) -> Result<Option<DecisionRecord>> {
+        let decision = self
+            .reader()
+            .call(move |conn| {
+                let decision = conn
+                    .query_row(
+                        "SELECT id, thought, action, result, created_at
+                         FROM decisions
+                         WHERE id = ?1",
+                        params![id],
+                        decision_from_row,
+                    )
+                    .optional()?;
+
+                Ok(decision)
+            })
+            .await
+            .context("Failed to get decision")?;
+
+        Ok(decision)
+    }
+
     #[allow(dead_code)]
     pub async fn record_capability(
         &self,
@@ -507,8 +1006,9 @@ impl StateManager {
     ) -> Result<()> {
         let tool_name_clone = tool_name.to_string();
         let description_clone = description.map(|s| s.to_string());
+        let now = self.timestamp();
 
-        self.conn
+        self.writer
             .call(move |conn| {
                 // Check if capability exists
                 let mut stmt =
@@ -533,20 +1033,22 @@ impl StateManager {
                     conn.execute(
                         "UPDATE capabilities SET 
                          description = COALESCE(?1, description),
-                         last_used = CURRENT_TIMESTAMP,
+                         last_used = ?4,
                          success_rate = ?2
                          WHERE id = ?3",
-                        params![description_clone, new_rate, id],
+                        params![description_clone, new_rate, id, now],
                     )?;
                 } else {
                     // Insert new capability
                     conn.execute(
-                        "INSERT INTO capabilities (tool_name, description, last_used, success_rate) 
-                         VALUES (?1, ?2, CURRENT_TIMESTAMP, ?3)",
+                        "INSERT INTO capabilities
+                         (tool_name, description, last_used, success_rate, created_at)
+                         VALUES (?1, ?2, ?4, ?3, ?4)",
                         params![
                             tool_name_clone,
                             description_clone,
-                            if success { 1.0 } else { 0.0 }
+                            if success { 1.0 } else { 0.0 },
+                            now
                         ],
                     )?;
                 }
@@ -562,7 +1064,7 @@ impl StateManager {
     #[allow(dead_code)]
     pub async fn get_capabilities(&self) -> Result<Vec<(String, Option<String>, Option<f64>)>> {
         let capabilities = self
-            .conn
+            .reader()
             .call(|conn| {
                 let mut stmt = conn.prepare(
                     "SELECT tool_name, description, success_rate 
@@ -605,8 +1107,9 @@ impl StateManager {
         let context_clone = context.to_string();
         let action_clone = action.to_string();
         let outcome_clone = outcome.map(|s| s.to_string());
+        let now = self.timestamp();
 
-        self.conn
+        self.writer
             .call(move |conn| {
                 // Check if similar pattern exists
                 let mut stmt = conn.prepare(
@@ -639,23 +1142,25 @@ impl StateManager {
                          success = ?2,
                          confidence = ?3,
                          occurrence_count = ?4,
-                         updated_at = CURRENT_TIMESTAMP
+                         updated_at = ?6
                          WHERE id = ?5",
-                        params![outcome_clone, success, new_confidence, new_count, id],
+                        params![outcome_clone, success, new_confidence, new_count, id, now],
                     )?;
                 } else {
                     // Insert new pattern
                     conn.execute(
                         "INSERT INTO action_patterns 
-                         (pattern_type, context, action, outcome, success, confidence) 
-                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                         (pattern_type, context, action, outcome, success, confidence,
+                          created_at, updated_at) 
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
                         params![
                             pattern_type_clone,
                             context_clone,
                             action_clone,
                             outcome_clone,
                             success,
-                            if success { 1.0 } else { 0.0 }
+                            if success { 1.0 } else { 0.0 },
+                            now
                         ],
                     )?;
                 }
@@ -678,7 +1183,7 @@ impl StateManager {
         let context_clone = context.to_string();
 
         let result = self
-            .conn
+            .reader()
             .call(move |conn| {
                 let mut stmt = conn.prepare(
                     "SELECT action, confidence 
@@ -704,8 +1209,9 @@ impl StateManager {
 
     pub async fn update_learning_metric(&self, metric_name: &str, value: f64) -> Result<()> {
         let metric_name_clone = metric_name.to_string();
+        let now = self.timestamp();
 
-        self.conn
+        self.writer
             .call(move |conn| {
                 // Check if metric exists
                 let mut stmt = conn.prepare(
@@ -727,16 +1233,16 @@ impl StateManager {
                         "UPDATE learning_metrics SET 
                          metric_value = ?1,
                          sample_count = ?2,
-                         updated_at = CURRENT_TIMESTAMP
+                         updated_at = ?4
                          WHERE metric_name = ?3",
-                        params![new_value, new_count, metric_name_clone],
+                        params![new_value, new_count, metric_name_clone, now],
                     )?;
                 } else {
                     // Insert new metric
                     conn.execute(
-                        "INSERT INTO learning_metrics (metric_name, metric_value) 
-                         VALUES (?1, ?2)",
-                        params![metric_name_clone, value],
+                        "INSERT INTO learning_metrics (metric_name, metric_value, updated_at) 
+                         VALUES (?1, ?2, ?3)",
+                        params![metric_name_clone, value, now],
                     )?;
                 }
 
@@ -750,7 +1256,7 @@ impl StateManager {
 
     pub async fn get_learning_metrics(&self) -> Result<HashMap<String, f64>> {
         let metrics = self
-            .conn
+            .reader()
             .call(|conn| {
                 let mut stmt =
                     conn.prepare("SELECT metric_name, metric_value FROM learning_metrics")?;
@@ -773,26 +1279,94 @@ impl StateManager {
         Ok(metrics)
     }
 
+    /// Store the phase durations of a finished cycle, returning their row id
+    pub async fn record_cycle_metrics(&self, metrics: &CycleMetrics) -> Result<i64> {
+        let metrics = metrics.clone();
+        let now = self.timestamp();
+
+        self.writer
+            .call(move |conn| {
+                let [observe, prompt, llm, parse, act, learn] = metrics.phase_ms;
+                conn.execute(
+                    "INSERT INTO cycle_metrics
+                     (decision_id, success, total_ms, observe_ms, prompt_ms, llm_ms, parse_ms,
+                      act_ms, learn_ms, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        metrics.decision_id,
+                        metrics.success,
+                        metrics.total_ms,
+                        observe,
+                        prompt,
+                        llm,
+                        parse,
+                        act,
+                        learn,
+                        now
+                    ],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await
+            .context("Failed to record cycle metrics")
+    }
+
+    /// The `limit` most recent cycles' metrics, newest first
+    pub async fn recent_cycle_metrics(&self, limit: usize) -> Result<Vec<CycleMetrics>> {
+        self.reader()
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, decision_id, success, total_ms, observe_ms, prompt_ms, llm_ms,
+                            parse_ms, act_ms, learn_ms, created_at
+                     FROM cycle_metrics
+                     ORDER BY id DESC
+                     LIMIT ?1",
+                )?;
+                let rows = stmt.query_map(params![limit], |row| {
+                    Ok(CycleMetrics {
+                        id: row.get(0)?,
+                        decision_id: row.get(1)?,
+                        success: row.get(2)?,
+                        total_ms: row.get(3)?,
+                        phase_ms: [
+                            row.get(4)?,
+                            row.get(5)?,
+                            row.get(6)?,
+                            row.get(7)?,
+                            row.get(8)?,
+                            row.get(9)?,
+                        ],
+                        created_at: row.get(10)?,
+                    })
+                })?;
+                Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await
+            .context("Failed to get cycle metrics")
+    }
+
     pub async fn analyze_decision_patterns(&self, lookback_hours: i64) -> Result<Value> {
+        let now = self.timestamp();
         let analysis = self
-            .conn
+            .reader()
             .call(move |conn| {
                 // Analyze success patterns
                 let mut success_stmt = conn.prepare(
                     "SELECT pattern_type, COUNT(*) as count, AVG(confidence) as avg_confidence 
                      FROM action_patterns 
                      WHERE success = 1 AND 
-                           datetime(updated_at) >= datetime('now', '-' || ?1 || ' hours')
+                           datetime(updated_at) >= datetime(?2, '-' || ?1 || ' hours')
                      GROUP BY pattern_type",
                 )?;
 
-                let success_patterns = success_stmt.query_map(params![lookback_hours], |row| {
-                    Ok(serde_json::json!({
-                        "type": row.get::<_, String>(0)?,
-                        "count": row.get::<_, i32>(1)?,
-                        "avg_confidence": row.get::<_, f64>(2)?
-                    }))
-                })?;
+                let success_patterns =
+                    success_stmt.query_map(params![lookback_hours, now], |row| {
+                        Ok(serde_json::json!({
+                            "type": row.get::<_, String>(0)?,
+                            "count": row.get::<_, i32>(1)?,
+                            "avg_confidence": row.get::<_, f64>(2)?
+                        }))
+                    })?;
 
                 let mut patterns = Vec::new();
                 for pattern in success_patterns {
@@ -804,11 +1378,11 @@ impl StateManager {
                     "SELECT tool_name, success_rate, 
                             COUNT(*) OVER() as total_tools
                      FROM capabilities 
-                     WHERE last_used >= datetime('now', '-' || ?1 || ' hours')
+                     WHERE last_used >= datetime(?2, '-' || ?1 || ' hours')
                      ORDER BY success_rate DESC",
                 )?;
 
-                let tool_stats = tool_stmt.query_map(params![lookback_hours], |row| {
+                let tool_stats = tool_stmt.query_map(params![lookback_hours, now], |row| {
                     Ok(serde_json::json!({
                         "tool": row.get::<_, String>(0)?,
                         "success_rate": row.get::<_, Option<f64>>(1)?
@@ -833,6 +1407,61 @@ impl StateManager {
     }
 }
 
+/// Build a `DecisionRecord` from a `SELECT id, thought, action, result, created_at` row
+fn decision_from_row(row: &rusqlite::Row) -> rusqlite::Result<DecisionRecord> {
+    let id = row.get::<_, i64>(0)?;
+    let thought = row.get::<_, String>(1)?;
+    let action_str = row.get::<_, String>(2)?;
+    let result_str: Option<String> = row.get(3)?;
+    let created_at_str = row.get::<_, String>(4)?;
+
+    // Parse action and parameters from the stored string
+    // Format is "action: <action>, params: <params>" or just plain action
+    let (action, parameters) = if let Some(idx) = action_str.find(", params:") {
+        // Has params format: "action: <action>, params: <params>"
+        let action_part = if action_str.starts_with("action: ") {
+            action_str[7..idx].trim().to_string() // Skip "action: " and trim
+        } else {
+            action_str[..idx].trim().to_string()
+        };
+        let params_part = &action_str[idx + 9..]; // Skip ", params: "
+        let params = if params_part.trim() != "None" && params_part.trim() != "Some(Null)" {
+            serde_json::from_str(params_part.trim()).ok()
+        } else {
+            None
+        };
+        (action_part, params)
+    } else if action_str.starts_with("action: ") {
+        // Simple format with "action: " prefix
+        (action_str[7..].trim().to_string(), None)
+    } else {
+        // Plain action string
+        (action_str.trim().to_string(), None)
+    };
+
+    // Parse result if it's JSON
+    let result = result_str.and_then(|s| serde_json::from_str::<DecisionResult>(&s).ok());
+
+    // Parse timestamp
+    let timestamp = if let Ok(dt) = DateTime::parse_from_rfc3339(&created_at_str) {
+        dt.with_timezone(&Utc)
+    } else {
+        // Fallback for SQLite's default format
+        let naive = chrono::NaiveDateTime::parse_from_str(&created_at_str, "%Y-%m-%d %H:%M:%S")
+            .unwrap_or_else(|_| chrono::Local::now().naive_utc());
+        DateTime::from_naive_utc_and_offset(naive, Utc)
+    };
+
+    Ok(DecisionRecord {
+        id,
+        timestamp,
+        thought,
+        action,
+        parameters,
+        result,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -855,6 +1484,9 @@ mod tests {
             .await?;
         let value = state.recall("test_key").await?;
         assert_eq!(value, Some(serde_json::json!("test_value")));
+        assert!(state.forget("test_key").await?);
+        assert_eq!(state.recall("test_key").await?, None);
+        assert!(!state.forget("test_key").await?);
 
         // Test decision recording
         let decision_id = state
@@ -1009,7 +1641,7 @@ mod tests {
             .await?;
 
         // Get memory summary
-        let summary = state.get_memory_summary(20, 10000).await?;
+        let summary = state.get_memory_summary(20, 2500, 250).await?;
 
         let summary_obj = summary
             .as_object()
@@ -1058,7 +1690,7 @@ mod tests {
         }
 
         // Get memory summary with limited entries
-        let summary = state.get_memory_summary(10, 50000).await?;
+        let summary = state.get_memory_summary(10, 12500, 250).await?;
 
         let summary_obj = summary
             .as_object()
@@ -1114,7 +1746,7 @@ mod tests {
         state.remember(&key, value).await?;
 
         // Get memory summary
-        let summary = state.get_memory_summary(10, 50000).await?;
+        let summary = state.get_memory_summary(10, 12500, 250).await?;
 
         let summary_obj = summary
             .as_object()
@@ -1125,18 +1757,17 @@ mod tests {
             .get(&key)
             .context("Tool result should be in summary")?;
 
-        // Check that content is truncated
-        if let Some(truncated) = tool_result.get("truncated_content") {
-            let content_str = truncated.as_str().unwrap();
-            assert!(
-                content_str.ends_with("... [truncated]"),
-                "Large content should be truncated"
-            );
-            assert!(
-                content_str.len() < 1100,
-                "Truncated content should be around 1000 chars"
-            );
-        }
+        // Check that content is truncated, keeping the other fields
+        assert_eq!(tool_result["tool"], "test_tool");
+        let content_str = tool_result["content"].as_str().unwrap();
+        assert!(
+            content_str.contains("tokens truncated"),
+            "Large content should be truncated"
+        );
+        assert!(
+            truncate::json_tokens(tool_result) <= 250,
+            "Truncated result should fit the value budget"
+        );
 
         Ok(())
     }
@@ -1226,7 +1857,7 @@ mod tests {
         assert_eq!(full_obj.len(), 4, "Should have 4 total entries");
 
         // Get summary with limits
-        let summary = state.get_memory_summary(10, 50000).await?;
+        let summary = state.get_memory_summary(10, 12500, 250).await?;
         let summary_obj = summary.as_object().unwrap();
 
         // Check memory stats
@@ -1246,4 +1877,329 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_paginated_memory_and_decisions() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let db_path = temp_file
+            .path()
+            .to_str()
+            .context("Failed to get temp file path")?;
+
+        let state = StateManager::new(db_path).await?;
+
+        state.remember("goal_a", serde_json::json!("a")).await?;
+        state.remember("goal_b", serde_json::json!("b")).await?;
+        state.remember("goalie", serde_json::json!("c")).await?;
+        state.remember("other", serde_json::json!("d")).await?;
+
+        // Underscore in the prefix must match literally, not as a wildcard
+        let goals = state.list_memory(Some("goal_"), 10, 0).await?;
+        assert_eq!(goals.len(), 2);
+        let page = state.list_memory(None, 3, 3).await?;
+        assert_eq!(page.len(), 1);
+
+        for i in 0..5 {
+            let id = state
+                .record_decision(
+                    "thought",
+                    &format!("action: use_tool:t{i}, params: null"),
+                    None,
+                )
+                .await?;
+            let result = DecisionResult {
+                status: if i % 2 == 0 { "success" } else { "error" }.to_string(),
+                summary: None,
+                error: None,
+                duration_ms: None,
+                tool_name: None,
+                tool_output: None,
+            };
+            state.update_decision_result(id, &result).await?;
+        }
+
+        let errors = state.get_decisions_page(10, 0, None, Some("error")).await?;
+        assert_eq!(errors.len(), 2);
+
+        let page = state.get_decisions_page(2, 1, None, None).await?;
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].action, "use_tool:t3");
+
+        let filtered = state.get_decisions_page(10, 0, Some("t4"), None).await?;
+        assert_eq!(filtered.len(), 1);
+
+        let newest = state.get_decisions_since(page[0].id).await?;
+        assert_eq!(newest.len(), 1);
+        assert_eq!(newest[0].action, "use_tool:t4");
+        let decision = state.get_decision(newest[0].id).await?;
+        assert_eq!(decision.map(|d| d.id), Some(newest[0].id));
+        assert!(state.get_decision(newest[0].id + 1).await?.is_none());
+
+        // Only the result's own status counts, not one in the tool output,
+        // and results that are not JSON are skipped
+        let quoted = state
+            .record_decision("thought", "action: wait, params: null", None)
+            .await?;
+        let result = DecisionResult {
+            status: "success".to_string(),
+            summary: None,
+            error: None,
+            duration_ms: None,
+            tool_name: None,
+            tool_output: Some(serde_json::json!({"status": "error"})),
+        };
+        state.update_decision_result(quoted, &result).await?;
+        state
+            .record_decision("thought", "action: wait, params: null", Some("not json"))
+            .await?;
+        let errors = state.get_decisions_page(10, 0, None, Some("error")).await?;
+        assert_eq!(errors.len(), 2);
+        let successes = state
+            .get_decisions_page(10, 0, None, Some("success"))
+            .await?;
+        assert_eq!(successes.len(), 4);
+
+        // The supervisor reads agent databases without taking write access
+        let read_only = StateManager::open_read_only(db_path).await?;
+        assert_eq!(read_only.list_memory(None, 10, 0).await?.len(), 4);
+        assert!(read_only.remember("x", serde_json::json!(1)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_from_read_only_connection() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let source_path = temp_dir.path().join("source.db");
+        let snapshot_path = temp_dir.path().join("snapshot.db");
+        let source_path = source_path.to_str().context("Invalid source path")?;
+        let snapshot_path = snapshot_path.to_str().context("Invalid snapshot path")?;
+
+        let state = StateManager::new(source_path).await?;
+        state
+            .remember("lesson", serde_json::json!("cloned"))
+            .await?;
+
+        StateManager::open_read_only(source_path)
+            .await?
+            .snapshot_to(snapshot_path)
+            .await?;
+
+        // The copy is independent of the original
+        let copy = StateManager::new(snapshot_path).await?;
+        assert_eq!(
+            copy.recall("lesson").await?,
+            Some(serde_json::json!("cloned"))
+        );
+        copy.remember("lesson", serde_json::json!("diverged"))
+            .await?;
+        assert_eq!(
+            state.recall("lesson").await?,
+            Some(serde_json::json!("cloned"))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_every_reader_sees_committed_writes() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("agent.db");
+        let path = path.to_str().context("Invalid path")?;
+
+        let state = StateManager::with_readers(path, 3).await?;
+        assert_eq!(state.readers.len(), 3);
+        let journal_mode = state
+            .writer
+            .call(|conn| {
+                Ok(conn.query_row("PRAGMA journal_mode", [], |row| row.get::<_, String>(0))?)
+            })
+            .await?;
+        assert_eq!(journal_mode, "wal");
+
+        state.remember("goal", serde_json::json!("explore")).await?;
+        for reader in state.readers.iter() {
+            let value = reader
+                .call(|conn| {
+                    Ok(conn.query_row(
+                        "SELECT value FROM memory WHERE key = 'goal'",
+                        [],
+                        |row| row.get::<_, String>(0),
+                    )?)
+                })
+                .await?;
+            assert_eq!(value, "\"explore\"");
+        }
+        // Round robin over the pool lands on each reader in turn
+        for _ in 0..3 {
+            assert_eq!(
+                state.recall("goal").await?,
+                Some(serde_json::json!("explore"))
+            );
+        }
+
+        // Readers are read-only
+        let write = state.readers[0]
+            .call(|conn| Ok(conn.execute("DELETE FROM memory", [])?))
+            .await;
+        assert!(write.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_database_reads_through_the_writer() -> Result<()> {
+        // Every connection to :memory: opens its own empty database
+        let state = StateManager::with_readers(":memory:", 4).await?;
+        assert!(state.readers.is_empty());
+        assert!(std::ptr::eq(state.reader(), &*state.writer));
+
+        state.remember("goal", serde_json::json!("explore")).await?;
+        assert_eq!(
+            state.recall("goal").await?,
+            Some(serde_json::json!("explore"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reads_do_not_wait_for_writes() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("agent.db");
+        let path = path.to_str().context("Invalid path")?;
+
+        let state = StateManager::with_readers(path, 2).await?;
+        state.remember("goal", serde_json::json!("old")).await?;
+
+        // A slow write holding the write lock
+        let (started, wait_started) = tokio::sync::oneshot::channel();
+        let writer = state.writer.clone();
+        let write = tokio::spawn(async move {
+            writer
+                .call(move |conn| {
+                    let tx = conn.transaction()?;
+                    tx.execute("UPDATE memory SET value = '\"new\"' WHERE key = 'goal'", [])?;
+                    let _ = started.send(());
+                    std::thread::sleep(Duration::from_millis(500));
+                    tx.commit()?;
+                    Ok(())
+                })
+                .await
+        });
+        wait_started.await?;
+
+        let read = tokio::time::timeout(Duration::from_millis(250), state.recall("goal")).await;
+        assert_eq!(read??, Some(serde_json::json!("old")));
+        write.await??;
+        assert_eq!(state.recall("goal").await?, Some(serde_json::json!("new")));
+
+        // A closed WAL database still opens read-only
+        drop(state);
+        let inspected = StateManager::open_read_only(path).await?;
+        assert_eq!(
+            inspected.recall("goal").await?,
+            Some(serde_json::json!("new"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_knowledge_graph_queries() -> Result<()> {
+        let state = StateManager::new(":memory:").await?;
+
+        assert!(state.add_fact("alice", "follows", "bob").await?);
+        assert!(!state.add_fact("alice", "follows", "bob").await?);
+        let added = state
+            .add_facts(
+                vec![
+                    (
+                        "bob".to_string(),
+                        "lives_in".to_string(),
+                        "Lisbon".to_string(),
+                    ),
+                    (
+                        "carol".to_string(),
+                        "follows".to_string(),
+                        "alice".to_string(),
+                    ),
+                    (
+                        "Lisbon".to_string(),
+                        "in".to_string(),
+                        "Portugal".to_string(),
+                    ),
+                ],
+                Some("nostr:get_profile".to_string()),
+            )
+            .await?;
+        assert_eq!(added, 3);
+
+        let about = |facts: Vec<Fact>| {
+            facts
+                .into_iter()
+                .map(|f| format!("{s} {p} {o}", s = f.subject, p = f.predicate, o = f.object))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            about(state.facts_about("alice").await?),
+            ["alice follows bob", "carol follows alice"]
+        );
+        assert_eq!(
+            about(state.related_facts("alice", 2, 10).await?),
+            [
+                "alice follows bob",
+                "carol follows alice",
+                "bob lives_in Lisbon"
+            ]
+        );
+        assert_eq!(state.related_facts("alice", 3, 10).await?.len(), 4);
+        assert_eq!(
+            state.facts_about("bob").await?[1].source.as_deref(),
+            Some("nostr:get_profile")
+        );
+        assert!(state.facts_about("nobody").await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cycle_metrics_round_trip_and_slow_phases() -> Result<()> {
+        let state = StateManager::new(":memory:").await?;
+
+        let mut failed = CycleMetrics {
+            total_ms: 40,
+            ..CycleMetrics::default()
+        };
+        failed.record(CyclePhase::Observe, Duration::from_millis(40));
+        let mut cycle = CycleMetrics {
+            decision_id: Some(7),
+            success: true,
+            total_ms: 2500,
+            ..CycleMetrics::default()
+        };
+        for (phase, ms) in CyclePhase::ALL.into_iter().zip([5, 1200, 1100, 1, 150, 30]) {
+            cycle.record(phase, Duration::from_millis(ms));
+        }
+        state.record_cycle_metrics(&failed).await?;
+        let id = state.record_cycle_metrics(&cycle).await?;
+
+        let stored = state.recent_cycle_metrics(10).await?;
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].id, id);
+        assert_eq!(stored[0].phase_ms, cycle.phase_ms);
+        assert_eq!(stored[0].decision_id, Some(7));
+        assert!(stored[0].success);
+        assert_eq!(stored[1].phase(CyclePhase::Observe), Some(40));
+        assert_eq!(stored[1].phase(CyclePhase::Llm), None);
+        assert!(!stored[1].success);
+
+        let thresholds = BTreeMap::from([
+            ("prompt".to_string(), 1000),
+            ("llm".to_string(), 1100),
+            ("parse".to_string(), 0),
+        ]);
+        assert_eq!(
+            stored[0].slow_phases(&thresholds),
+            [(CyclePhase::Prompt, 1200, 1000), (CyclePhase::Parse, 1, 0)]
+        );
+        assert!(stored[1].slow_phases(&thresholds).is_empty());
+        Ok(())
+    }
 }
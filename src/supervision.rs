@@ -0,0 +1,369 @@
+//! Reporting to the supervisor that runs the agent. Before each tool call a
+//! supervised agent asks the supervisor's policy engine about it through
+//! `/api/agents/:id/tool_usage`, which also counts it against the agent's
+//! quotas, and obeys the answer: a throttled call is not made, nor any other
+//! until the throttle lifts, and a quarantined agent makes no tool calls
+//! until an operator approves it. A call the supervisor does not answer
+//! with a decision is refused too, unless the supervisor cannot be reached
+//! at all. The supervisor passes its URL and the agent's ID to the agents it
+//! spawns.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::supervisor::async_client::AsyncSupervisorClient;
+use crate::supervisor::policy::PolicyDecision;
+
+/// Environment variable the supervisor passes its URL to spawned agents in
+pub const SUPERVISOR_URL_ENV: &str = "SUPERVISOR_URL";
+/// Environment variable the supervisor passes an agent's ID in
+pub const AGENT_ID_ENV: &str = "REPLICANTE_AGENT_ID";
+/// Environment variable the supervisor passes a `reporter` token in
+pub const TOKEN_ENV: &str = "REPLICANTE_TOKEN";
+
+/// Where the agent reports its tool calls, under `[supervisor]`. Unset
+/// fields come from the environment the supervisor spawns agents with.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct SupervisionConfig {
+    /// Supervisor API, e.g. "http://127.0.0.1:8090"; `SUPERVISOR_URL` if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// ID the supervisor knows this agent by; `REPLICANTE_AGENT_ID` if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// API token with the `reporter` role; `REPLICANTE_TOKEN` if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Whether a tool call may go ahead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    Allowed,
+    /// Not to be made; `status` is what the decision's result records,
+    /// "throttled" or "quarantined"
+    Refused {
+        status: &'static str,
+        reason: String,
+    },
+}
+
+/// The agent's link to its supervisor
+#[derive(Debug)]
+pub struct Supervision {
+    client: AsyncSupervisorClient,
+    agent_id: String,
+    /// Until when no tool call is made, and why
+    throttled_until: Option<(DateTime<Utc>, String)>,
+}
+
+impl Supervision {
+    /// A link to the supervisor in `config` or the environment, or `None`
+    /// when the agent is not supervised
+    pub fn connect(config: &SupervisionConfig) -> Result<Option<Self>> {
+        let env = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let (Some(url), Some(agent_id)) = (
+            config.url.clone().or_else(|| env(SUPERVISOR_URL_ENV)),
+            config.agent_id.clone().or_else(|| env(AGENT_ID_ENV)),
+        ) else {
+            return Ok(None);
+        };
+
+        let mut client = AsyncSupervisorClient::new(Some(url))?
+            .without_proxy()?
+            .with_operator(&agent_id)?;
+        if let Some(token) = &config.token {
+            client = client.with_token(token)?;
+        }
+        info!(
+            "Reporting tool calls to the supervisor at {url} as agent {agent_id}",
+            url = client.base_url()
+        );
+        Ok(Some(Self::new(client, agent_id)))
+    }
+
+    pub fn new(client: AsyncSupervisorClient, agent_id: String) -> Self {
+        Self {
+            client,
+            agent_id,
+            throttled_until: None,
+        }
+    }
+
+    /// Ask the supervisor whether `tool` may be called at `now`. While a
+    /// throttle lasts calls are refused without asking. If the supervisor
+    /// cannot be reached the call is allowed, so the agent keeps working
+    /// through a supervisor restart; any other failure to get a decision,
+    /// such as a rejected token, refuses it.
+    pub async fn admit(&mut self, tool: &str, now: DateTime<Utc>) -> Admission {
+        if let Some((until, reason)) = &self.throttled_until {
+            if now < *until {
+                return Admission::Refused {
+                    status: "throttled",
+                    reason: format!(
+                        "{reason}; tool calls resume in {secs}s",
+                        secs = (*until - now).num_seconds().max(1)
+                    ),
+                };
+            }
+            self.throttled_until = None;
+        }
+
+        let decision = match self.client.report_tool_usage(&self.agent_id, tool).await {
+            Ok(decision) => decision,
+            Err(e) if is_unreachable(&e) => {
+                warn!("Calling {tool} without the supervisor's approval: {e:#}");
+                return Admission::Allowed;
+            }
+            Err(e) => {
+                warn!("Supervisor gave no decision on {tool}: {e:#}");
+                return Admission::Refused {
+                    status: "refused",
+                    reason: format!("the supervisor gave no decision: {e:#}"),
+                };
+            }
+        };
+
+        match decision {
+            PolicyDecision::Allow => Admission::Allowed,
+            PolicyDecision::Warn { reason } => {
+                warn!("Supervisor warns about {tool}: {reason}");
+                Admission::Allowed
+            }
+            PolicyDecision::Throttle {
+                reason,
+                retry_after_secs,
+            } => {
+                warn!("Supervisor throttled tool calls for {retry_after_secs}s: {reason}");
+                let until = now + ChronoDuration::seconds(retry_after_secs as i64);
+                self.throttled_until = Some((until, reason.clone()));
+                Admission::Refused {
+                    status: "throttled",
+                    reason: format!("{reason}; tool calls resume in {retry_after_secs}s"),
+                }
+            }
+            PolicyDecision::Quarantine { reason } => {
+                warn!("Supervisor quarantined the agent: {reason}");
+                Admission::Refused {
+                    status: "quarantined",
+                    reason,
+                }
+            }
+        }
+    }
+}
+
+/// Whether a report failed because nothing accepted the connection
+fn is_unreachable(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::supervisor::async_client::RetryPolicy;
+    use axum::extract::{Path, State};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Reports = Arc<Mutex<Vec<(String, String)>>>;
+    type Decisions = Arc<Mutex<std::vec::IntoIter<PolicyDecision>>>;
+
+    async fn handle_tool_usage(
+        State((reports, decisions)): State<(Reports, Decisions)>,
+        Path(agent_id): Path<String>,
+        Json(report): Json<serde_json::Value>,
+    ) -> Json<PolicyDecision> {
+        let tool = report["tool"].as_str().unwrap_or_default().to_string();
+        reports.lock().unwrap().push((agent_id, tool));
+        let decision = decisions.lock().unwrap().next();
+        Json(decision.unwrap_or(PolicyDecision::Allow))
+    }
+
+    /// A supervisor answering tool usage reports with `decisions` in turn
+    async fn supervisor(decisions: Vec<PolicyDecision>) -> (String, Reports) {
+        let reports = Reports::default();
+        let decisions = Arc::new(Mutex::new(decisions.into_iter()));
+        let app = Router::new()
+            .route("/api/agents/:agent_id/tool_usage", post(handle_tool_usage))
+            .with_state((reports.clone(), decisions));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}"), reports)
+    }
+
+    #[tokio::test]
+    async fn test_throttle_and_quarantine_refuse_tool_calls() -> Result<()> {
+        let (url, reports) = supervisor(vec![
+            PolicyDecision::Allow,
+            PolicyDecision::Throttle {
+                reason: "too fast".to_string(),
+                retry_after_secs: 30,
+            },
+            PolicyDecision::Quarantine {
+                reason: "blocked tool".to_string(),
+            },
+        ])
+        .await;
+        let client = AsyncSupervisorClient::new(Some(url))?;
+        let mut supervision = Supervision::new(client, "agent-7".to_string());
+        let start = DateTime::UNIX_EPOCH;
+
+        assert_eq!(
+            supervision.admit("fs:read", start).await,
+            Admission::Allowed
+        );
+        let Admission::Refused { status, reason } = supervision.admit("fs:read", start).await
+        else {
+            panic!("throttled call was allowed");
+        };
+        assert_eq!(status, "throttled");
+        assert!(reason.contains("too fast"), "{reason}");
+
+        // Refused without asking until the throttle lifts
+        let later = start + ChronoDuration::seconds(10);
+        assert!(matches!(
+            supervision.admit("fs:write", later).await,
+            Admission::Refused {
+                status: "throttled",
+                ..
+            }
+        ));
+        assert_eq!(reports.lock().unwrap().len(), 2);
+
+        let lifted = start + ChronoDuration::seconds(30);
+        assert_eq!(
+            supervision.admit("shell:run", lifted).await,
+            Admission::Refused {
+                status: "quarantined",
+                reason: "blocked tool".to_string()
+            }
+        );
+        assert_eq!(
+            *reports.lock().unwrap(),
+            [
+                ("agent-7".to_string(), "fs:read".to_string()),
+                ("agent-7".to_string(), "fs:read".to_string()),
+                ("agent-7".to_string(), "shell:run".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_report_refuses_tool_calls() -> Result<()> {
+        let app = Router::new().route(
+            "/api/agents/:agent_id/tool_usage",
+            post(|| async { axum::http::StatusCode::FORBIDDEN }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = AsyncSupervisorClient::new(Some(format!("http://{addr}")))?;
+        let mut supervision = Supervision::new(client, "agent-7".to_string());
+        assert!(matches!(
+            supervision.admit("fs:read", Utc::now()).await,
+            Admission::Refused {
+                status: "refused",
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    /// Run by test_sandboxed_agent_reaches_supervisor_past_egress_proxy in a
+    /// process with a sandboxed agent's proxy variables
+    #[tokio::test]
+    #[ignore = "run in a child process by another test"]
+    async fn sandboxed_agent_admission() -> Result<()> {
+        let Some(mut supervision) = Supervision::connect(&SupervisionConfig::default())? else {
+            return Ok(());
+        };
+        assert_eq!(
+            supervision.admit("shell:run", Utc::now()).await,
+            Admission::Refused {
+                status: "quarantined",
+                reason: "blocked tool".to_string()
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sandboxed_agent_reaches_supervisor_past_egress_proxy() -> Result<()> {
+        use crate::supervisor::egress::{EgressPolicy, EgressProxy, EgressProxyConfig};
+        use crate::supervisor::{NetworkMode, NetworkRestrictions};
+
+        let (url, reports) = supervisor(vec![PolicyDecision::Quarantine {
+            reason: "blocked tool".to_string(),
+        }])
+        .await;
+        let egress = EgressProxy::new(EgressProxyConfig {
+            enabled: true,
+            ..EgressProxyConfig::default()
+        });
+        let proxy = egress
+            .register(
+                "agent-7",
+                EgressPolicy::from_restrictions(&NetworkRestrictions {
+                    mode: NetworkMode::None,
+                    allowed_domains: vec![],
+                    allowed_cidrs: vec![],
+                    blocked_ports: vec![],
+                    rate_limit_per_minute: None,
+                }),
+            )
+            .await?;
+
+        // The environment the supervisor spawns a sandboxed agent with
+        let mut child = tokio::process::Command::new(std::env::current_exe()?);
+        child
+            .args([
+                "--exact",
+                "supervision::tests::sandboxed_agent_admission",
+                "--ignored",
+            ])
+            .env(SUPERVISOR_URL_ENV, &url)
+            .env(AGENT_ID_ENV, "agent-7")
+            .env_remove("NO_PROXY")
+            .env_remove("no_proxy");
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            child.env(var, format!("http://{proxy}"));
+        }
+        for var in ["ALL_PROXY", "all_proxy"] {
+            child.env(var, format!("socks5h://{proxy}"));
+        }
+        let output = child.output().await?;
+        assert!(
+            output.status.success(),
+            "{stdout}",
+            stdout = String::from_utf8_lossy(&output.stdout)
+        );
+        assert_eq!(
+            *reports.lock().unwrap(),
+            [("agent-7".to_string(), "shell:run".to_string())]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_supervisor_allows_tool_calls() -> Result<()> {
+        let client = AsyncSupervisorClient::new(Some("http://127.0.0.1:1".to_string()))?
+            .with_retry(RetryPolicy::none());
+        let mut supervision = Supervision::new(client, "agent-7".to_string());
+        assert_eq!(
+            supervision.admit("fs:read", Utc::now()).await,
+            Admission::Allowed
+        );
+        Ok(())
+    }
+}
@@ -1,20 +1,43 @@
 use anyhow::Result;
 use axum::{
     Router,
-    extract::State,
-    http::StatusCode,
-    response::{Html, IntoResponse, Json},
+    body::Body,
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{Method, StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::{
+        Html, IntoResponse, Json, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
+use chrono::{DateTime, Utc};
+use futures::Stream;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
 use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use super::{AgentProcess, Monitor};
+use super::auth::Role;
+use super::decision_stream::DecisionStreamer;
+use super::monitor::IncidentReport;
+use super::network::{
+    ConnectionTermination, NetworkReport, NetworkReportResponse, NetworkSample, NetworkStats,
+    SampleResolution,
+};
+use super::policy::PolicyDecision;
+use super::quota::{QuotaConfig, QuotaUsage};
+use super::security::{RiskLevel, Whitelists};
+use super::store::{
+    AuditEntry, AuditRecord, IncidentSummary, SecurityReportFilter, StoredSecurityReport,
+};
+use super::templates::TemplateSummary;
+use super::{EMERGENCY_TOKEN_TTL, FleetStopReport, Supervisor};
+use crate::DecisionRecord;
+use crate::state::{MemoryEntry, StateManager};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct StatusResponse {
@@ -27,8 +50,10 @@ struct StatusResponse {
 struct AgentInfo {
     id: String,
     status: String,
+    config_path: String,
     started_at: String,
     resource_usage: super::ResourceUsage,
+    pool: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,65 +62,469 @@ struct MetricsResponse {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct EventsResponse {
-    events: Vec<super::monitor::Event>,
+pub struct EventsResponse {
+    pub events: Vec<super::monitor::Event>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlertsResponse {
+    pub alerts: Vec<super::monitor::Alert>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpawnRequest {
+    /// Agent config path, as seen by the supervisor host; it must lie in
+    /// the supervisor's `spawn_configs_dir`
+    pub config_path: String,
+    /// Refused when the supervisor sets a `spawn_sandbox`
+    #[serde(default)]
+    pub sandbox: Option<super::SandboxConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpawnResponse {
+    pub agent_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplatesResponse {
+    pub templates: Vec<TemplateSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ToolUsageReport {
+    pub tool: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemoryQuery {
+    prefix: Option<String>,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct DecisionsQuery {
+    action: Option<String>,
+    status: Option<String>,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+fn default_page_limit() -> usize {
+    50
+}
+
+const MAX_PAGE_LIMIT: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryResponse {
+    pub agent_id: String,
+    pub entries: Vec<MemoryEntry>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DecisionsResponse {
+    pub agent_id: String,
+    pub decisions: Vec<DecisionRecord>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct DecisionStreamQuery {
+    /// Recent decisions sent before new ones
+    #[serde(default = "default_decision_backlog")]
+    backlog: usize,
+}
+
+fn default_decision_backlog() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize)]
+struct LogsQuery {
+    #[serde(default = "default_log_tail")]
+    tail: usize,
+    /// Keep the response open and stream new lines as they arrive
+    #[serde(default)]
+    follow: bool,
+}
+
+fn default_log_tail() -> usize {
+    100
+}
+
+#[derive(Debug, Deserialize)]
+struct IncidentsQuery {
+    agent_id: Option<String>,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IncidentsResponse {
+    pub incidents: Vec<IncidentSummary>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SecurityReportsQuery {
+    container_id: Option<String>,
+    /// Minimum risk level: low, medium, high or critical
+    severity: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityReportsResponse {
+    pub reports: Vec<StoredSecurityReport>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct NetworkHistoryQuery {
+    /// Agent container name or network monitor name
+    source: Option<String>,
+    /// raw, minute or hour
+    resolution: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkHistoryResponse {
+    pub resolution: SampleResolution,
+    pub samples: Vec<NetworkSample>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TerminateConnectionRequest {
+    /// Monitor to send it to; by default those that reported the connection
+    #[serde(default)]
+    pub monitor: Option<String>,
+    #[serde(flatten)]
+    pub connection: ConnectionTermination,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TerminateConnectionResponse {
+    /// Monitors that will sever the connection after their next report
+    pub monitors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    agent_id: Option<String>,
+    actor: Option<String>,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditResponse {
+    pub entries: Vec<AuditEntry>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Header identifying the operator behind an API call in the audit log
+pub const OPERATOR_HEADER: &str = "x-replicante-operator";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuotaResponse {
+    pub agent_id: String,
+    /// None until the agent's first usage sample
+    pub usage: Option<QuotaUsage>,
+    pub limits: QuotaConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct AlertsResponse {
-    alerts: Vec<super::monitor::Alert>,
+pub struct EmergencyTokenResponse {
+    pub token: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmergencyStopRequest {
+    pub token: String,
 }
 
 #[derive(Clone)]
 struct AppState {
-    agents: Arc<RwLock<HashMap<String, AgentProcess>>>,
-    monitor: Arc<Monitor>,
+    supervisor: Supervisor,
 }
 
-pub async fn start_dashboard_server(
-    port: u16,
-    agents: Arc<RwLock<HashMap<String, AgentProcess>>>,
-    monitor: Arc<Monitor>,
-) -> Result<()> {
+pub async fn start_dashboard_server(port: u16, supervisor: Supervisor) -> Result<()> {
     info!("Starting dashboard server on port {port}");
 
-    let state = AppState { agents, monitor };
+    let auth_enabled = supervisor.config.auth.enabled;
+    let state = AppState { supervisor };
 
-    let app = Router::new()
+    let requires = |role: Role| middleware::from_fn_with_state((state.clone(), role), require_role);
+
+    let viewer_routes = Router::new()
         .route("/api/status", get(handle_status))
         .route("/api/metrics", get(handle_metrics))
         .route("/api/events", get(handle_events))
+        .route("/api/events/stream", get(handle_event_stream))
         .route("/api/alerts", get(handle_alerts))
+        .route("/api/templates", get(handle_templates))
+        .route("/api/agents/:agent_id/memory", get(handle_agent_memory))
+        .route(
+            "/api/agents/:agent_id/decisions",
+            get(handle_agent_decisions),
+        )
+        .route(
+            "/api/agents/:agent_id/decisions/stream",
+            get(handle_decision_stream),
+        )
+        .route("/api/agents/:agent_id/logs", get(handle_agent_logs))
+        .route("/api/agents/:agent_id/quota", get(handle_agent_quota))
+        .route("/api/incidents", get(handle_incidents))
+        .route("/api/incidents/:incident_id", get(handle_incident))
+        .route("/security/reports", get(handle_security_reports))
+        .route("/security/reports/:report_id", get(handle_security_report))
+        .route("/security/whitelists", get(handle_security_whitelists))
+        .route("/api/network/stats", get(handle_network_stats))
+        .route("/api/network/history", get(handle_network_history))
+        .route_layer(requires(Role::Viewer));
+
+    let operator_routes = Router::new()
+        .route("/api/agents/:agent_id/stop", post(handle_stop_agent))
+        .route("/api/agents/:agent_id/kill", post(handle_kill_agent))
+        .route(
+            "/api/agents/:agent_id/quarantine",
+            post(handle_quarantine_agent),
+        )
+        .route("/api/agents/:agent_id/approve", post(handle_approve_agent))
+        .route("/api/agents/:agent_id/pause", post(handle_pause_agent))
+        .route("/api/agents/:agent_id/resume", post(handle_resume_agent))
+        .route(
+            "/api/agents/:agent_id/reload-config",
+            post(handle_reload_agent_config),
+        )
+        .route(
+            "/api/network/connections/terminate",
+            post(handle_terminate_connection),
+        )
+        .route_layer(requires(Role::Operator));
+
+    // Telemetry from agents and network monitors
+    let reporter_routes = Router::new()
+        .route("/api/agents/:agent_id/tool_usage", post(handle_tool_usage))
+        .route("/api/network/reports", post(handle_network_report))
+        .route_layer(requires(Role::Reporter));
+
+    let admin_routes = Router::new()
+        .route("/api/agents", post(handle_spawn_agent))
+        .route("/api/agents/:agent_id/clone", post(handle_clone_agent))
+        .route("/api/templates/:name/spawn", post(handle_spawn_template))
+        .route("/api/audit", get(handle_audit))
         .route("/api/shutdown", post(handle_shutdown))
+        .route("/emergency/stop-all/token", post(handle_emergency_token))
+        .route("/emergency/stop-all", post(handle_emergency_stop_all))
+        .route(
+            "/security/whitelists/reload",
+            post(handle_reload_security_whitelists),
+        )
+        .route_layer(requires(Role::Admin));
+
+    let app = Router::new()
+        .merge(viewer_routes)
+        .merge(operator_routes)
+        .merge(reporter_routes)
+        .merge(admin_routes)
+        // The dashboard page itself is public; its API calls carry the token
         .route("/", get(handle_dashboard))
+        .layer(middleware::from_fn_with_state(state.clone(), audit_layer))
         .layer(CorsLayer::permissive())
         .with_state(state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    // Without auth anyone who reaches the API can spawn agents, so it is
+    // only served to this host
+    let addr = if auth_enabled {
+        SocketAddr::from(([0, 0, 0, 0], port))
+    } else {
+        warn!("API auth is disabled; serving the dashboard on 127.0.0.1 only");
+        SocketAddr::from(([127, 0, 0, 1], port))
+    };
 
     tokio::spawn(async move {
         let listener = tokio::net::TcpListener::bind(addr)
             .await
             .expect("Failed to bind address");
 
-        axum::serve(listener, app)
-            .await
-            .expect("Failed to start server");
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .expect("Failed to start server");
     });
 
     Ok(())
 }
 
+/// Record API calls in the supervisor's append-only audit log
+async fn audit_layer(
+    State(state): State<AppState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    // Every route is audited, whatever its prefix: the emergency and
+    // whitelist routes are among the most destructive
+    let audited = method != Method::GET || state.supervisor.config.audit_reads;
+    if !audited {
+        return next.run(request).await;
+    }
+
+    // With auth enabled the token identifies the caller; otherwise the
+    // self-declared operator header is all we have
+    let token_name = match request_token(&request) {
+        Some(token) if state.supervisor.config.auth.enabled => state
+            .supervisor
+            .identify(&token)
+            .await
+            .map(|identity| identity.name),
+        _ => None,
+    };
+    let actor = token_name.unwrap_or_else(|| {
+        request
+            .headers()
+            .get(OPERATOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("anonymous")
+            .to_string()
+    });
+    let agent_id = path_agent_id(&path).map(|id| id.to_string());
+
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let record = AuditRecord {
+        actor,
+        remote_addr: Some(remote_addr.to_string()),
+        method: method.to_string(),
+        path,
+        agent_id,
+        status: status.as_u16(),
+        outcome: if status.is_success() {
+            "success"
+        } else {
+            "failure"
+        }
+        .to_string(),
+    };
+
+    if let Err(e) = state.supervisor.store.record_audit(record).await {
+        error!("Failed to record audit entry: {e}");
+    }
+
+    response
+}
+
+/// The agent a call under `/api/agents/:agent_id` is about
+fn path_agent_id(path: &str) -> Option<&str> {
+    path.strip_prefix("/api/agents/")
+        .and_then(|rest| rest.split('/').next())
+}
+
+/// Reject calls whose API token does not grant at least `role`
+async fn require_role(
+    State((state, role)): State<(AppState, Role)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !state.supervisor.config.auth.enabled {
+        return Ok(next.run(request).await);
+    }
+
+    let identity = match request_token(&request) {
+        Some(token) => state.supervisor.identify(&token).await,
+        None => None,
+    };
+    let Some(identity) = identity else {
+        return Err(AppError::Unauthorized);
+    };
+
+    if !identity.role.grants(role) {
+        warn!(
+            "Denied {method} {path} to {name}: requires {role}, has {granted}",
+            method = request.method(),
+            path = request.uri().path(),
+            name = identity.name,
+            granted = identity.role
+        );
+        return Err(AppError::Forbidden(format!(
+            "This action requires the {role} role"
+        )));
+    }
+
+    // An agent's token is good only for calls about that agent, so one agent
+    // cannot get another throttled or quarantined, nor forge network reports
+    if let Some(owner) = &identity.agent_id
+        && path_agent_id(request.uri().path()) != Some(owner.as_str())
+    {
+        return Err(AppError::Forbidden(format!(
+            "This token may only report for agent {owner}"
+        )));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// API token from the `Authorization: Bearer` header. EventSource cannot set
+/// headers, so an `access_token` query parameter is accepted as well.
+fn request_token(request: &Request) -> Option<String> {
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.to_string());
+    }
+
+    let Query(mut params) = Query::<HashMap<String, String>>::try_from_uri(request.uri()).ok()?;
+    params.remove("access_token")
+}
+
 async fn handle_status(State(state): State<AppState>) -> impl IntoResponse {
-    let agents_guard = state.agents.read().await;
+    let agents_guard = state.supervisor.agents.read().await;
 
     let agent_list: Vec<AgentInfo> = agents_guard
         .values()
         .map(|agent| AgentInfo {
             id: agent.id.clone(),
             status: format!("{:?}", agent.status),
+            config_path: agent.config_path.clone(),
             started_at: agent.started_at.to_rfc3339(),
             resource_usage: agent.resource_usage.clone(),
+            pool: agent.pool.clone(),
         })
         .collect();
 
@@ -114,10 +543,15 @@ async fn handle_status(State(state): State<AppState>) -> impl IntoResponse {
 }
 
 async fn handle_metrics(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let metrics_data = state.monitor.export_metrics("json").await.map_err(|e| {
-        error!("Failed to export metrics: {e}");
-        AppError::InternalError
-    })?;
+    let metrics_data = state
+        .supervisor
+        .monitor
+        .export_metrics("json")
+        .await
+        .map_err(|e| {
+            error!("Failed to export metrics: {e}");
+            AppError::InternalError
+        })?;
 
     let response = MetricsResponse {
         metrics: serde_json::from_str(&metrics_data).unwrap_or(serde_json::json!({})),
@@ -127,17 +561,723 @@ async fn handle_metrics(State(state): State<AppState>) -> Result<impl IntoRespon
 }
 
 async fn handle_events(State(state): State<AppState>) -> impl IntoResponse {
-    let events = state.monitor.get_recent_events(100).await;
+    let events = state.supervisor.monitor.get_recent_events(100).await;
     let response = EventsResponse { events };
     Json(response)
 }
 
+/// Server-sent events for everything published on the supervisor's event
+/// bus from now on, named after their type
+async fn handle_event_stream(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<SseEvent, axum::Error>>> {
+    let receiver = state.supervisor.monitor.event_bus().subscribe();
+    let events = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse = SseEvent::default().event(event.kind()).json_data(&event);
+                    return Some((sse, receiver));
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Event stream subscriber missed {missed} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
 async fn handle_alerts(State(state): State<AppState>) -> impl IntoResponse {
-    let alerts = state.monitor.get_recent_alerts(50).await;
+    let alerts = state.supervisor.monitor.get_recent_alerts(50).await;
     let response = AlertsResponse { alerts };
     Json(response)
 }
 
+async fn handle_spawn_agent(
+    State(state): State<AppState>,
+    Json(request): Json<SpawnRequest>,
+) -> Result<Json<SpawnResponse>, AppError> {
+    let agent_id = state
+        .supervisor
+        .spawn_requested_agent(&request.config_path, request.sandbox)
+        .await
+        .map_err(|e| {
+            error!("Failed to spawn agent: {e}");
+            AppError::Conflict(e.to_string())
+        })?;
+
+    Ok(Json(SpawnResponse { agent_id }))
+}
+
+async fn handle_clone_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<Json<SpawnResponse>, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let clone_id = state.supervisor.clone_agent(&agent_id).await.map_err(|e| {
+        error!("Failed to clone agent {agent_id}: {e}");
+        AppError::Conflict(e.to_string())
+    })?;
+
+    Ok(Json(SpawnResponse { agent_id: clone_id }))
+}
+
+async fn handle_templates(State(state): State<AppState>) -> impl IntoResponse {
+    Json(TemplatesResponse {
+        templates: state.supervisor.list_templates(),
+    })
+}
+
+async fn handle_spawn_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<SpawnResponse>, AppError> {
+    if !state
+        .supervisor
+        .list_templates()
+        .iter()
+        .any(|template| template.name == name)
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let agent_id = state
+        .supervisor
+        .spawn_from_template(&name)
+        .await
+        .map_err(|e| {
+            error!("Failed to spawn agent from template {name}: {e}");
+            AppError::Conflict(e.to_string())
+        })?;
+
+    Ok(Json(SpawnResponse { agent_id }))
+}
+
+async fn open_agent_state(state: &AppState, agent_id: &str) -> Result<StateManager, AppError> {
+    if state.supervisor.get_agent_details(agent_id).await.is_none() {
+        return Err(AppError::NotFound);
+    }
+
+    state
+        .supervisor
+        .open_agent_state(agent_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to open state for agent {agent_id}: {e}");
+            AppError::InternalError
+        })
+}
+
+async fn handle_agent_memory(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<MemoryQuery>,
+) -> Result<Json<MemoryResponse>, AppError> {
+    let agent_state = open_agent_state(&state, &agent_id).await?;
+    let limit = query.limit.min(MAX_PAGE_LIMIT);
+
+    let entries = agent_state
+        .list_memory(query.prefix.as_deref(), limit, query.offset)
+        .await
+        .map_err(|e| {
+            error!("Failed to list memory for agent {agent_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(MemoryResponse {
+        agent_id,
+        entries,
+        limit,
+        offset: query.offset,
+    }))
+}
+
+async fn handle_agent_decisions(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<DecisionsQuery>,
+) -> Result<Json<DecisionsResponse>, AppError> {
+    let agent_state = open_agent_state(&state, &agent_id).await?;
+    let limit = query.limit.min(MAX_PAGE_LIMIT);
+
+    let decisions = agent_state
+        .get_decisions_page(
+            limit,
+            query.offset,
+            query.action.as_deref(),
+            query.status.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list decisions for agent {agent_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(DecisionsResponse {
+        agent_id,
+        decisions,
+        limit,
+        offset: query.offset,
+    }))
+}
+
+async fn handle_decision_stream(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<DecisionStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, axum::Error>>>, AppError> {
+    let streamer = DecisionStreamer::new(open_agent_state(&state, &agent_id).await?);
+    let backlog = query.backlog.min(MAX_PAGE_LIMIT);
+
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        if let Err(e) = streamer.stream_decisions(backlog, tx).await {
+            error!("Decision stream for agent {agent_id} failed: {e}");
+        }
+    });
+
+    let events = futures::stream::unfold(rx, |mut rx| async move {
+        let decision = rx.recv().await?;
+        let event = SseEvent::default()
+            .event("decision")
+            .id(decision.id.to_string())
+            .json_data(&decision);
+        Some((event, rx))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+async fn handle_agent_logs(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let streamer = state.supervisor.log_streamer(&agent_id).await;
+
+    if query.follow {
+        let (tx, rx) = mpsc::channel(256);
+        tokio::spawn(async move {
+            if let Err(e) = streamer.stream_logs(tx).await {
+                error!("Log stream for agent {agent_id} failed: {e}");
+            }
+        });
+
+        let lines = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv()
+                .await
+                .map(|line| (Ok::<_, std::io::Error>(format!("{line}\n")), rx))
+        });
+        return Ok(Body::from_stream(lines).into_response());
+    }
+
+    let logs = streamer
+        .get_recent_logs(query.tail.min(MAX_PAGE_LIMIT))
+        .await
+        .map_err(|e| {
+            error!("Failed to get logs for agent {agent_id}: {e}");
+            AppError::Conflict(e.to_string())
+        })?;
+
+    Ok(logs.join("\n").into_response())
+}
+
+async fn handle_stop_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state.supervisor.stop_agent(&agent_id).await.map_err(|e| {
+        error!("Failed to stop agent {agent_id}: {e}");
+        AppError::InternalError
+    })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "stopped", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_kill_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state
+        .supervisor
+        .emergency_stop(&agent_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to kill agent {agent_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "killed", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_quarantine_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state
+        .supervisor
+        .quarantine_agent(&agent_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to quarantine agent {agent_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "quarantined", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_approve_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state
+        .supervisor
+        .approve_agent(&agent_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to approve agent {agent_id}: {e}");
+            AppError::Conflict(e.to_string())
+        })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "running", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_pause_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state.supervisor.pause_agent(&agent_id).await.map_err(|e| {
+        error!("Failed to pause agent {agent_id}: {e}");
+        AppError::Conflict(e.to_string())
+    })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "paused", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_resume_agent(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state
+        .supervisor
+        .resume_agent(&agent_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to resume agent {agent_id}: {e}");
+            AppError::Conflict(e.to_string())
+        })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "running", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_reload_agent_config(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    state
+        .supervisor
+        .reload_agent_config(&agent_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to reload config of agent {agent_id}: {e}");
+            AppError::Conflict(e.to_string())
+        })?;
+
+    Ok(Json(
+        serde_json::json!({"status": "reloading", "agent_id": agent_id}),
+    ))
+}
+
+async fn handle_tool_usage(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+    Json(report): Json<ToolUsageReport>,
+) -> Result<Json<PolicyDecision>, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let decision = state
+        .supervisor
+        .record_tool_usage(&agent_id, &report.tool)
+        .await
+        .map_err(|e| {
+            error!("Failed to record tool usage for agent {agent_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(decision))
+}
+
+async fn handle_agent_quota(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<Json<QuotaResponse>, AppError> {
+    if state
+        .supervisor
+        .get_agent_details(&agent_id)
+        .await
+        .is_none()
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let usage = state.supervisor.quota_usage(&agent_id).await;
+
+    Ok(Json(QuotaResponse {
+        agent_id,
+        usage,
+        limits: state.supervisor.quotas.config().clone(),
+    }))
+}
+
+async fn handle_incidents(
+    State(state): State<AppState>,
+    Query(query): Query<IncidentsQuery>,
+) -> Result<Json<IncidentsResponse>, AppError> {
+    let limit = query.limit.min(MAX_PAGE_LIMIT);
+
+    let incidents = state
+        .supervisor
+        .store
+        .list_incidents(query.agent_id.as_deref(), limit, query.offset)
+        .await
+        .map_err(|e| {
+            error!("Failed to list incidents: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(IncidentsResponse {
+        incidents,
+        limit,
+        offset: query.offset,
+    }))
+}
+
+async fn handle_incident(
+    State(state): State<AppState>,
+    Path(incident_id): Path<String>,
+) -> Result<Json<IncidentReport>, AppError> {
+    let report = state
+        .supervisor
+        .store
+        .get_incident(&incident_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get incident {incident_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    report.map(Json).ok_or(AppError::NotFound)
+}
+
+async fn handle_security_reports(
+    State(state): State<AppState>,
+    Query(query): Query<SecurityReportsQuery>,
+) -> Result<Json<SecurityReportsResponse>, AppError> {
+    let limit = query.limit.min(MAX_PAGE_LIMIT);
+
+    let min_risk = query
+        .severity
+        .as_deref()
+        .map(str::parse::<RiskLevel>)
+        .transpose()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+
+    let filter = SecurityReportFilter {
+        container_id: query.container_id,
+        min_risk,
+        since: query.since,
+        until: query.until,
+    };
+
+    let reports = state
+        .supervisor
+        .store
+        .list_security_reports(&filter, limit, query.offset)
+        .await
+        .map_err(|e| {
+            error!("Failed to list security reports: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(SecurityReportsResponse {
+        reports,
+        limit,
+        offset: query.offset,
+    }))
+}
+
+async fn handle_security_report(
+    State(state): State<AppState>,
+    Path(report_id): Path<i64>,
+) -> Result<Json<StoredSecurityReport>, AppError> {
+    let report = state
+        .supervisor
+        .store
+        .get_security_report(report_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get security report {report_id}: {e}");
+            AppError::InternalError
+        })?;
+
+    report.map(Json).ok_or(AppError::NotFound)
+}
+
+async fn handle_security_whitelists(State(state): State<AppState>) -> Json<Whitelists> {
+    Json(state.supervisor.security_whitelists())
+}
+
+async fn handle_network_report(
+    State(state): State<AppState>,
+    Json(report): Json<NetworkReport>,
+) -> Result<Json<NetworkReportResponse>, AppError> {
+    let monitor = report.monitor.clone();
+    let terminate = state
+        .supervisor
+        .record_network_report(report)
+        .await
+        .map_err(|e| {
+            error!("Failed to record network report from {monitor}: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(NetworkReportResponse { terminate }))
+}
+
+async fn handle_terminate_connection(
+    State(state): State<AppState>,
+    Json(request): Json<TerminateConnectionRequest>,
+) -> Result<(StatusCode, Json<TerminateConnectionResponse>), AppError> {
+    let monitors = state
+        .supervisor
+        .terminate_connection(request.monitor, request.connection)
+        .await;
+    if monitors.is_empty() {
+        return Err(AppError::NotFound);
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(TerminateConnectionResponse { monitors }),
+    ))
+}
+
+async fn handle_network_stats(
+    State(state): State<AppState>,
+) -> Json<HashMap<String, NetworkStats>> {
+    Json(state.supervisor.network_stats().await)
+}
+
+async fn handle_network_history(
+    State(state): State<AppState>,
+    Query(query): Query<NetworkHistoryQuery>,
+) -> Result<Json<NetworkHistoryResponse>, AppError> {
+    let resolution = query
+        .resolution
+        .as_deref()
+        .map(str::parse::<SampleResolution>)
+        .transpose()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?
+        .unwrap_or(SampleResolution::Minute);
+
+    let samples = state
+        .supervisor
+        .network_history(
+            query.source,
+            resolution,
+            query.since,
+            query.until,
+            query.limit.min(MAX_PAGE_LIMIT),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list network history: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(NetworkHistoryResponse {
+        resolution,
+        samples,
+    }))
+}
+
+async fn handle_reload_security_whitelists(
+    State(state): State<AppState>,
+) -> Result<Json<Whitelists>, AppError> {
+    let whitelists = state
+        .supervisor
+        .reload_security_whitelists()
+        .await
+        .map_err(|e| {
+            error!("Failed to reload security whitelists: {e:#}");
+            AppError::Conflict(format!("{e:#}"))
+        })?;
+
+    Ok(Json(whitelists))
+}
+
+async fn handle_audit(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditResponse>, AppError> {
+    let limit = query.limit.min(MAX_PAGE_LIMIT);
+
+    let entries = state
+        .supervisor
+        .store
+        .list_audit(
+            query.agent_id.as_deref(),
+            query.actor.as_deref(),
+            limit,
+            query.offset,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list audit log: {e}");
+            AppError::InternalError
+        })?;
+
+    Ok(Json(AuditResponse {
+        entries,
+        limit,
+        offset: query.offset,
+    }))
+}
+
+async fn handle_emergency_token(State(state): State<AppState>) -> impl IntoResponse {
+    let token = state.supervisor.issue_emergency_token().await;
+    warn!("Issued emergency stop-all confirmation token");
+
+    Json(EmergencyTokenResponse {
+        token,
+        expires_in_secs: EMERGENCY_TOKEN_TTL.as_secs(),
+    })
+}
+
+async fn handle_emergency_stop_all(
+    State(state): State<AppState>,
+    Json(request): Json<EmergencyStopRequest>,
+) -> Result<Json<FleetStopReport>, AppError> {
+    if !state
+        .supervisor
+        .confirm_emergency_token(&request.token)
+        .await
+    {
+        return Err(AppError::Forbidden(
+            "Invalid or expired confirmation token".to_string(),
+        ));
+    }
+
+    let report = state.supervisor.emergency_stop_all().await.map_err(|e| {
+        error!("Emergency stop-all failed: {e}");
+        AppError::InternalError
+    })?;
+
+    Ok(Json(report))
+}
+
 async fn handle_shutdown() -> impl IntoResponse {
     info!("Shutdown request received");
 
@@ -157,12 +1297,28 @@ async fn handle_dashboard() -> impl IntoResponse {
 // Custom error type for better error handling
 enum AppError {
     InternalError,
+    NotFound,
+    Conflict(String),
+    Forbidden(String),
+    Unauthorized,
+    BadRequest(String),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         let (status, error_message) = match self {
-            AppError::InternalError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
+            AppError::InternalError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            ),
+            AppError::NotFound => (StatusCode::NOT_FOUND, "Not found".to_string()),
+            AppError::Conflict(message) => (StatusCode::CONFLICT, message),
+            AppError::Forbidden(message) => (StatusCode::FORBIDDEN, message),
+            AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid API token".to_string(),
+            ),
         };
 
         let body = Json(serde_json::json!({
@@ -173,225 +1329,5 @@ impl IntoResponse for AppError {
     }
 }
 
-// Basic dashboard HTML (same as before)
-const DASHBOARD_HTML: &str = r#"
-<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Replicante Supervisor Dashboard</title>
-    <style>
-        * { margin: 0; padding: 0; box-sizing: border-box; }
-        body { 
-            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
-            background: #0f0f0f;
-            color: #e0e0e0;
-            padding: 20px;
-        }
-        .container { max-width: 1400px; margin: 0 auto; }
-        h1 { 
-            color: #00ff88;
-            margin-bottom: 30px;
-            font-size: 2em;
-            text-shadow: 0 0 10px rgba(0, 255, 136, 0.5);
-        }
-        .grid { 
-            display: grid;
-            grid-template-columns: repeat(auto-fit, minmax(300px, 1fr));
-            gap: 20px;
-            margin-bottom: 30px;
-        }
-        .card {
-            background: #1a1a1a;
-            border: 1px solid #333;
-            border-radius: 8px;
-            padding: 20px;
-            box-shadow: 0 4px 6px rgba(0, 0, 0, 0.3);
-        }
-        .card h2 {
-            color: #00ff88;
-            font-size: 1.2em;
-            margin-bottom: 15px;
-        }
-        .status { 
-            display: inline-block;
-            padding: 4px 8px;
-            border-radius: 4px;
-            font-size: 0.9em;
-            font-weight: 500;
-        }
-        .status.running { background: #00ff88; color: #000; }
-        .status.stopped { background: #ff4444; color: #fff; }
-        .status.starting { background: #ffaa00; color: #000; }
-        .metric {
-            display: flex;
-            justify-content: space-between;
-            margin: 10px 0;
-            padding: 8px;
-            background: #0f0f0f;
-            border-radius: 4px;
-        }
-        .metric-label { color: #888; }
-        .metric-value { 
-            color: #00ff88;
-            font-weight: bold;
-            font-family: 'Courier New', monospace;
-        }
-        .alerts {
-            max-height: 300px;
-            overflow-y: auto;
-        }
-        .alert {
-            padding: 10px;
-            margin: 5px 0;
-            background: #2a1a1a;
-            border-left: 3px solid #ff4444;
-            border-radius: 4px;
-        }
-        .events {
-            max-height: 400px;
-            overflow-y: auto;
-        }
-        .event {
-            padding: 8px;
-            margin: 5px 0;
-            background: #0f0f0f;
-            border-radius: 4px;
-            font-size: 0.9em;
-        }
-        .timestamp {
-            color: #666;
-            font-size: 0.85em;
-        }
-        button {
-            background: #00ff88;
-            color: #000;
-            border: none;
-            padding: 10px 20px;
-            border-radius: 4px;
-            font-weight: bold;
-            cursor: pointer;
-            margin: 5px;
-        }
-        button:hover {
-            background: #00cc66;
-        }
-        button.danger {
-            background: #ff4444;
-            color: #fff;
-        }
-        button.danger:hover {
-            background: #cc0000;
-        }
-    </style>
-</head>
-<body>
-    <div class="container">
-        <h1>🤖 Replicante Supervisor</h1>
-        
-        <div class="grid">
-            <div class="card">
-                <h2>System Status</h2>
-                <div id="system-status">
-                    <div class="metric">
-                        <span class="metric-label">Total Agents:</span>
-                        <span class="metric-value" id="total-agents">0</span>
-                    </div>
-                    <div class="metric">
-                        <span class="metric-label">Running:</span>
-                        <span class="metric-value" id="running-agents">0</span>
-                    </div>
-                </div>
-            </div>
-            
-            <div class="card">
-                <h2>Agents</h2>
-                <div id="agents-list"></div>
-            </div>
-            
-            <div class="card">
-                <h2>Recent Alerts</h2>
-                <div class="alerts" id="alerts-list"></div>
-            </div>
-        </div>
-        
-        <div class="card">
-            <h2>Recent Events</h2>
-            <div class="events" id="events-list"></div>
-        </div>
-        
-        <div class="card">
-            <h2>Controls</h2>
-            <button onclick="refreshData()">Refresh</button>
-            <button class="danger" onclick="emergencyStopAll()">Emergency Stop All</button>
-        </div>
-    </div>
-
-    <script>
-        async function fetchData() {
-            try {
-                // Fetch status
-                const statusRes = await fetch('/api/status');
-                const status = await statusRes.json();
-                
-                document.getElementById('total-agents').textContent = status.total_agents;
-                document.getElementById('running-agents').textContent = status.running_agents;
-                
-                // Update agents list
-                const agentsList = document.getElementById('agents-list');
-                agentsList.innerHTML = status.agents.map(agent => `
-                    <div class="metric">
-                        <span>${agent.id}</span>
-                        <span class="status ${agent.status.toLowerCase()}">${agent.status}</span>
-                    </div>
-                `).join('');
-                
-                // Fetch alerts
-                const alertsRes = await fetch('/api/alerts');
-                const alertsData = await alertsRes.json();
-                
-                const alertsList = document.getElementById('alerts-list');
-                alertsList.innerHTML = alertsData.alerts.slice(0, 5).map(alert => `
-                    <div class="alert">
-                        ${JSON.stringify(alert)}
-                    </div>
-                `).join('') || '<div class="event">No recent alerts</div>';
-                
-                // Fetch events
-                const eventsRes = await fetch('/api/events');
-                const eventsData = await eventsRes.json();
-                
-                const eventsList = document.getElementById('events-list');
-                eventsList.innerHTML = eventsData.events.slice(0, 10).map(event => `
-                    <div class="event">
-                        <span class="timestamp">${new Date(event.timestamp).toLocaleString()}</span>
-                        <strong>${event.agent_id}</strong> - ${event.event_type}
-                    </div>
-                `).join('') || '<div class="event">No recent events</div>';
-                
-            } catch (error) {
-                console.error('Failed to fetch data:', error);
-            }
-        }
-        
-        function refreshData() {
-            fetchData();
-        }
-        
-        function emergencyStopAll() {
-            if (confirm('Are you sure you want to stop all agents?')) {
-                // TODO: Implement emergency stop API call
-                alert('Emergency stop not yet implemented');
-            }
-        }
-        
-        // Auto-refresh every 5 seconds
-        setInterval(fetchData, 5000);
-        
-        // Initial load
-        fetchData();
-    </script>
-</body>
-</html>
-"#;
+// Single-page dashboard, embedded at compile time
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
@@ -1,13 +1,128 @@
+//! Typed client of the supervisor's HTTP API, used by the CLI and the
+//! network monitor and usable from any Rust tooling. Requests carry an
+//! operator name for the audit log and, if set, an API token; those that
+//! fail for a transient reason are retried according to a [`RetryPolicy`].
+//! Error responses surface as [`ApiError`] for callers that need the status.
+
 use anyhow::{Context, Result};
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
 use std::time::Duration;
-use tracing::{debug, error, info};
+use tracing::{debug, info, warn};
+
+use super::api::{
+    AlertsResponse, AuditResponse, DecisionsResponse, EmergencyStopRequest, EmergencyTokenResponse,
+    EventsResponse, IncidentsResponse, MemoryResponse, NetworkHistoryResponse, OPERATOR_HEADER,
+    QuotaResponse, SecurityReportsResponse, SpawnRequest, SpawnResponse, TemplatesResponse,
+    TerminateConnectionRequest, TerminateConnectionResponse,
+};
+use super::monitor::{Alert, Event, IncidentReport, Metrics};
+use super::network::{
+    ConnectionTermination, NetworkReport, NetworkReportResponse, NetworkStats, SampleResolution,
+};
+use super::policy::PolicyDecision;
+use super::security::Whitelists;
+use super::store::{AuditEntry, IncidentSummary, StoredSecurityReport};
+use super::templates::TemplateSummary;
+use super::{FleetStopReport, ResourceUsage, SandboxConfig};
+use crate::DecisionRecord;
+use crate::state::MemoryEntry;
+use crate::supervision::TOKEN_ENV;
 
 #[derive(Debug, Clone)]
 pub struct AsyncSupervisorClient {
     client: Client,
+    /// Without a request timeout, for responses that stay open
+    stream_client: Client,
     base_url: String,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+}
+
+/// How requests that fail for a transient reason are retried: those that
+/// could not connect, and reads that timed out or were answered with 502,
+/// 503 or 504. Writes are only retried when they never reached the
+/// supervisor, so none is applied twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries per request, the first included
+    pub max_attempts: u32,
+    /// Pause before the first retry, doubled for each one after it
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Try every request once
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Pause before retrying a request that failed on its `attempt`th try
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(4),
+        }
+    }
+}
+
+/// A request the supervisor answered with an error status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    /// What was asked, e.g. "Stop request"
+    pub request: String,
+    pub status: StatusCode,
+    /// Body of the response, usually the supervisor's reason
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{request} failed with {status}",
+            request = self.request,
+            status = self.status
+        )?;
+        if !self.message.is_empty() {
+            write!(f, ": {message}", message = self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// Whether the same request may succeed later: the supervisor failed,
+    /// asked the caller to slow down, or rejected its token, which an
+    /// operator can fix. Other rejections would be repeated forever.
+    pub fn is_retryable(&self) -> bool {
+        self.status.is_server_error()
+            || matches!(
+                self.status,
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+            )
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,61 +138,268 @@ pub struct AgentInfo {
     pub status: String,
     pub config_path: String,
     pub started_at: String,
-    pub cpu_percent: f64,
-    pub memory_mb: u64,
+    pub resource_usage: ResourceUsage,
+    #[serde(default)]
+    pub pool: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MetricsResponse {
-    pub metrics: Vec<AgentMetrics>,
+    /// Recent resource samples of each agent, oldest first
+    pub metrics: HashMap<String, Vec<Metrics>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AgentMetrics {
-    pub agent_id: String,
-    pub cpu_percent: f64,
-    pub memory_mb: u64,
-    pub disk_mb: u64,
-    pub network_bytes_sent: u64,
-    pub network_bytes_recv: u64,
+/// The request client and the stream client, which has no request timeout;
+/// proxies from the environment are used if `proxy` is set
+fn build_clients(proxy: bool) -> Result<(Client, Client)> {
+    let mut client = Client::builder().timeout(Duration::from_secs(30));
+    let mut stream_client = Client::builder();
+    if !proxy {
+        client = client.no_proxy();
+        stream_client = stream_client.no_proxy();
+    }
+    Ok((
+        client.build().context("Failed to create HTTP client")?,
+        stream_client
+            .build()
+            .context("Failed to create HTTP client")?,
+    ))
+}
+
+/// Identify the operator for the supervisor's audit log and, when
+/// `REPLICANTE_TOKEN` is set, authenticate with that API token
+fn operator_headers() -> HeaderMap {
+    let operator = std::env::var("REPLICANTE_OPERATOR")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "anonymous".to_string());
+
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&operator) {
+        headers.insert(OPERATOR_HEADER, value);
+    }
+
+    if let Ok(token) = std::env::var(TOKEN_ENV)
+        && let Ok(value) = bearer(&token)
+    {
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    headers
+}
+
+fn bearer(token: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(&format!("Bearer {token}"))
+        .context("API token is not a valid header value")?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// The JSON `data` of each server-sent event on `response`; comments such as
+/// keep-alives are skipped
+fn sse_json<T: DeserializeOwned>(response: Response) -> impl Stream<Item = Result<T>> {
+    futures::stream::try_unfold(
+        (response, Vec::new()),
+        |(mut response, mut buffer)| async move {
+            loop {
+                if let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+                    let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                    let block = String::from_utf8_lossy(&block);
+                    let data = block
+                        .lines()
+                        .filter_map(|line| line.strip_prefix("data:"))
+                        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    if data.is_empty() {
+                        continue;
+                    }
+                    let item = serde_json::from_str(&data)
+                        .with_context(|| format!("Failed to parse server-sent event: {data}"))?;
+                    return Ok(Some((item, (response, buffer))));
+                }
+                match response
+                    .chunk()
+                    .await
+                    .context("Failed to read event stream")?
+                {
+                    Some(bytes) => buffer.extend_from_slice(&bytes),
+                    None => return Ok(None),
+                }
+            }
+        },
+    )
 }
 
 impl AsyncSupervisorClient {
+    /// A client of the supervisor at `base_url`, else `SUPERVISOR_URL`, else
+    /// localhost. The operator is `REPLICANTE_OPERATOR` or `USER`, and the
+    /// API token `REPLICANTE_TOKEN` if set.
     pub fn new(base_url: Option<String>) -> Result<Self> {
         let base_url = base_url.unwrap_or_else(|| {
             std::env::var("SUPERVISOR_URL").unwrap_or_else(|_| "http://localhost:8090".to_string())
         });
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .context("Failed to create HTTP client")?;
+        let (client, stream_client) = build_clients(true)?;
 
-        Ok(Self { client, base_url })
+        Ok(Self {
+            client,
+            stream_client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: operator_headers(),
+            retry: RetryPolicy::default(),
+        })
     }
 
-    pub async fn get_status(&self) -> Result<StatusResponse> {
-        let url = format!("{base_url}/api/status", base_url = self.base_url);
-        debug!("Fetching supervisor status from {url}");
+    /// Connect to the supervisor directly, ignoring `HTTP_PROXY` and the like.
+    /// Sandboxed agents get those variables pointing at the egress proxy,
+    /// which would refuse the supervisor on loopback.
+    pub fn without_proxy(mut self) -> Result<Self> {
+        (self.client, self.stream_client) = build_clients(false)?;
+        Ok(self)
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send status request")?;
+    /// Authenticate with `token` instead of `REPLICANTE_TOKEN`
+    pub fn with_token(mut self, token: &str) -> Result<Self> {
+        self.headers.insert(AUTHORIZATION, bearer(token)?);
+        Ok(self)
+    }
+
+    /// Name recorded as the actor of this client's calls in the audit log
+    pub fn with_operator(mut self, operator: &str) -> Result<Self> {
+        let value =
+            HeaderValue::from_str(operator).context("Operator name is not a valid header value")?;
+        self.headers.insert(OPERATOR_HEADER, value);
+        Ok(self)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{base_url}{path}", base_url = self.base_url)
+    }
 
-        if !response.status().is_success() {
+    /// Send the request made by `build`, retrying per the retry policy, and
+    /// turn an error status into an [`ApiError`]. `idempotent` requests are
+    /// also retried after timeouts and gateway errors.
+    async fn send(
+        &self,
+        what: &str,
+        idempotent: bool,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let result = build().headers(self.headers.clone()).send().await;
+            let transient = match &result {
+                Err(e) => e.is_connect() || (idempotent && e.is_timeout()),
+                Ok(response) => {
+                    idempotent
+                        && matches!(
+                            response.status(),
+                            StatusCode::BAD_GATEWAY
+                                | StatusCode::SERVICE_UNAVAILABLE
+                                | StatusCode::GATEWAY_TIMEOUT
+                        )
+                }
+            };
+            if transient && attempt < self.retry.max_attempts {
+                let backoff = self.retry.backoff(attempt);
+                debug!("{what} failed on attempt {attempt}, retrying in {backoff:?}");
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                continue;
+            }
+
+            let response = result.with_context(|| format!("Failed to send {what}"))?;
             let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Status request failed with {status}: {text}");
-            anyhow::bail!("Status request failed with {status}");
+            if !status.is_success() {
+                let message = response.text().await.unwrap_or_default();
+                return Err(ApiError {
+                    request: what.to_string(),
+                    status,
+                    message,
+                }
+                .into());
+            }
+            return Ok(response);
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        what: &str,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        let url = self.url(path);
+        debug!("Sending {what} to {url}");
+        self.send(what, true, || self.client.get(&url).query(query))
+            .await?
+            .json::<T>()
+            .await
+            .with_context(|| format!("Failed to parse response to {what}"))
+    }
+
+    /// Like [`Self::get_json`], with a 404 answer as `None`
+    async fn get_optional<T: DeserializeOwned>(&self, what: &str, path: &str) -> Result<Option<T>> {
+        match self.get_json(what, path, &[]).await {
+            Ok(found) => Ok(Some(found)),
+            Err(e)
+                if e.downcast_ref::<ApiError>()
+                    .is_some_and(|e| e.status == StatusCode::NOT_FOUND) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
         }
+    }
+
+    async fn post<B: Serialize>(
+        &self,
+        what: &str,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<Response> {
+        let url = self.url(path);
+        self.send(what, false, || {
+            let request = self.client.post(&url);
+            match body {
+                Some(body) => request.json(body),
+                None => request,
+            }
+        })
+        .await
+    }
 
-        let status = response
-            .json::<StatusResponse>()
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        what: &str,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<T> {
+        self.post(what, path, body)
+            .await?
+            .json::<T>()
             .await
-            .context("Failed to parse status response")?;
+            .with_context(|| format!("Failed to parse response to {what}"))
+    }
+
+    /// Post `what` about one agent, with no body and no answer expected
+    async fn post_agent(&self, what: &str, agent_id: &str, action: &str) -> Result<()> {
+        self.post::<()>(what, &format!("/api/agents/{agent_id}/{action}"), None)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_status(&self) -> Result<StatusResponse> {
+        let status: StatusResponse = self.get_json("Status request", "/api/status", &[]).await?;
 
         info!(
             "Retrieved status: {total} agents ({running} running)",
@@ -89,27 +411,9 @@ impl AsyncSupervisorClient {
     }
 
     pub async fn get_metrics(&self) -> Result<MetricsResponse> {
-        let url = format!("{base_url}/api/metrics", base_url = self.base_url);
-        debug!("Fetching metrics from {url}");
-
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send metrics request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Metrics request failed with {status}: {text}");
-            anyhow::bail!("Metrics request failed with {status}");
-        }
-
-        let metrics = response
-            .json::<MetricsResponse>()
-            .await
-            .context("Failed to parse metrics response")?;
+        let metrics: MetricsResponse = self
+            .get_json("Metrics request", "/api/metrics", &[])
+            .await?;
 
         debug!(
             "Retrieved metrics for {count} agents",
@@ -119,90 +423,212 @@ impl AsyncSupervisorClient {
         Ok(metrics)
     }
 
-    pub async fn stop_agent(&self, agent_id: &str) -> Result<()> {
-        let url = format!(
-            "{base_url}/api/agents/{agent_id}/stop",
-            base_url = self.base_url
-        );
-        info!("Stopping agent {agent_id}");
+    /// The supervisor's most recent events, oldest first
+    pub async fn get_events(&self) -> Result<Vec<Event>> {
+        let events: EventsResponse = self.get_json("Events request", "/api/events", &[]).await?;
+        Ok(events.events)
+    }
 
+    /// Everything published on the supervisor's event bus from now on
+    pub async fn stream_events(
+        &self,
+    ) -> Result<impl Stream<Item = Result<crate::events::Event>> + use<>> {
+        let url = self.url("/api/events/stream");
         let response = self
-            .client
-            .post(&url)
-            .send()
-            .await
-            .context("Failed to send stop request")?;
+            .send("Event stream request", true, || {
+                self.stream_client.get(&url)
+            })
+            .await?;
+        Ok(sse_json(response))
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Stop request failed with {status}: {text}");
-            anyhow::bail!("Stop request failed with {status}");
-        }
+    /// The supervisor's most recent alerts, oldest first
+    pub async fn get_alerts(&self) -> Result<Vec<Alert>> {
+        let alerts: AlertsResponse = self.get_json("Alerts request", "/api/alerts", &[]).await?;
+        Ok(alerts.alerts)
+    }
 
+    pub async fn spawn_agent(
+        &self,
+        config_path: &str,
+        sandbox: Option<SandboxConfig>,
+    ) -> Result<String> {
+        info!("Spawning agent with config {config_path}");
+
+        let request = SpawnRequest {
+            config_path: config_path.to_string(),
+            sandbox,
+        };
+        let spawned: SpawnResponse = self
+            .post_json("Spawn request", "/api/agents", Some(&request))
+            .await?;
+
+        info!("Spawned agent {agent_id}", agent_id = spawned.agent_id);
+        Ok(spawned.agent_id)
+    }
+
+    pub async fn clone_agent(&self, agent_id: &str) -> Result<String> {
+        info!("Cloning agent {agent_id}");
+
+        let spawned: SpawnResponse = self
+            .post_json::<(), _>(
+                "Clone request",
+                &format!("/api/agents/{agent_id}/clone"),
+                None,
+            )
+            .await?;
+
+        info!(
+            "Cloned agent {agent_id} as {clone_id}",
+            clone_id = spawned.agent_id
+        );
+        Ok(spawned.agent_id)
+    }
+
+    pub async fn list_templates(&self) -> Result<Vec<TemplateSummary>> {
+        let templates: TemplatesResponse = self
+            .get_json("Templates request", "/api/templates", &[])
+            .await?;
+        Ok(templates.templates)
+    }
+
+    pub async fn spawn_template(&self, template: &str) -> Result<String> {
+        info!("Spawning agent from template {template}");
+
+        let spawned: SpawnResponse = self
+            .post_json::<(), _>(
+                "Template spawn request",
+                &format!("/api/templates/{template}/spawn"),
+                None,
+            )
+            .await?;
+
+        info!("Spawned agent {agent_id}", agent_id = spawned.agent_id);
+        Ok(spawned.agent_id)
+    }
+
+    pub async fn stop_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Stopping agent {agent_id}");
+        self.post_agent("Stop request", agent_id, "stop").await?;
         info!("Successfully stopped agent {agent_id}");
         Ok(())
     }
 
     pub async fn quarantine_agent(&self, agent_id: &str) -> Result<()> {
-        let url = format!(
-            "{base_url}/api/agents/{agent_id}/quarantine",
-            base_url = self.base_url
-        );
         info!("Quarantining agent {agent_id}");
+        self.post_agent("Quarantine request", agent_id, "quarantine")
+            .await?;
+        info!("Successfully quarantined agent {agent_id}");
+        Ok(())
+    }
 
-        let response = self
-            .client
-            .post(&url)
-            .send()
-            .await
-            .context("Failed to send quarantine request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Quarantine request failed with {status}: {text}");
-            anyhow::bail!("Quarantine request failed with {status}");
-        }
+    pub async fn approve_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Approving agent {agent_id}");
+        self.post_agent("Approve request", agent_id, "approve")
+            .await?;
+        info!("Successfully approved agent {agent_id}");
+        Ok(())
+    }
 
-        info!("Successfully quarantined agent {agent_id}");
+    pub async fn pause_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Pausing agent {agent_id}");
+        self.post_agent("Pause request", agent_id, "pause").await?;
+        info!("Successfully paused agent {agent_id}");
         Ok(())
     }
 
-    pub async fn health_check(&self) -> Result<bool> {
-        let url = format!("{base_url}/health", base_url = self.base_url);
+    pub async fn resume_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Resuming agent {agent_id}");
+        self.post_agent("Resume request", agent_id, "resume")
+            .await?;
+        info!("Successfully resumed agent {agent_id}");
+        Ok(())
+    }
 
-        match self.client.get(&url).send().await {
-            Ok(response) => Ok(response.status().is_success()),
-            Err(e) => {
-                debug!("Health check failed: {e}");
-                Ok(false)
-            }
-        }
+    pub async fn reload_agent_config(&self, agent_id: &str) -> Result<()> {
+        info!("Reloading config of agent {agent_id}");
+        self.post_agent("Reload-config request", agent_id, "reload-config")
+            .await?;
+        info!("Asked agent {agent_id} to reload its config");
+        Ok(())
     }
 
     pub async fn kill_agent(&self, agent_id: &str) -> Result<()> {
-        let url = format!(
-            "{base_url}/api/agents/{agent_id}/kill",
-            base_url = self.base_url
-        );
+        self.post_agent("Kill request", agent_id, "kill").await?;
+        info!("Successfully killed agent {agent_id}");
+        Ok(())
+    }
 
-        let response = self
-            .client
-            .post(&url)
-            .send()
-            .await
-            .context("Failed to send kill request")?;
+    pub async fn report_tool_usage(&self, agent_id: &str, tool: &str) -> Result<PolicyDecision> {
+        debug!("Reporting tool usage {tool} for agent {agent_id}");
+        self.post_json(
+            "Tool usage report",
+            &format!("/api/agents/{agent_id}/tool_usage"),
+            Some(&serde_json::json!({ "tool": tool })),
+        )
+        .await
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Kill request failed with {status}: {text}");
-            anyhow::bail!("Kill request failed with {status}");
-        }
+    /// A page of an agent's memory, by key, optionally only keys starting
+    /// with `prefix`
+    pub async fn get_agent_memory(
+        &self,
+        agent_id: &str,
+        prefix: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<MemoryEntry>> {
+        let mut query = vec![("limit", limit.to_string()), ("offset", offset.to_string())];
+        query.extend(prefix.map(|prefix| ("prefix", prefix.to_string())));
 
-        info!("Successfully killed agent {agent_id}");
-        Ok(())
+        let memory: MemoryResponse = self
+            .get_json(
+                "Memory request",
+                &format!("/api/agents/{agent_id}/memory"),
+                &query,
+            )
+            .await?;
+        Ok(memory.entries)
+    }
+
+    /// A page of an agent's decisions, newest first, optionally only those
+    /// with `action` or result `status`
+    pub async fn get_agent_decisions(
+        &self,
+        agent_id: &str,
+        action: Option<&str>,
+        status: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<DecisionRecord>> {
+        let mut query = vec![("limit", limit.to_string()), ("offset", offset.to_string())];
+        query.extend(action.map(|action| ("action", action.to_string())));
+        query.extend(status.map(|status| ("status", status.to_string())));
+
+        let decisions: DecisionsResponse = self
+            .get_json(
+                "Decisions request",
+                &format!("/api/agents/{agent_id}/decisions"),
+                &query,
+            )
+            .await?;
+        Ok(decisions.decisions)
+    }
+
+    /// An agent's last `backlog` decisions, then each new decision and each
+    /// decision whose result comes in
+    pub async fn stream_decisions(
+        &self,
+        agent_id: &str,
+        backlog: usize,
+    ) -> Result<impl Stream<Item = Result<DecisionRecord>> + use<>> {
+        let url = self.url(&format!("/api/agents/{agent_id}/decisions/stream"));
+        let response = self
+            .send("Decision stream request", true, || {
+                self.stream_client.get(&url).query(&[("backlog", backlog)])
+            })
+            .await?;
+        Ok(sse_json(response))
     }
 
     pub async fn get_logs(
@@ -211,67 +637,394 @@ impl AsyncSupervisorClient {
         _follow: bool,
         tail: Option<usize>,
     ) -> Result<String> {
-        let mut url = format!(
-            "{base_url}/api/agents/{agent_id}/logs",
-            base_url = self.base_url
-        );
-
-        if let Some(tail) = tail {
-            url = format!("{url}?tail={tail}");
-        }
+        let url = self.url(&format!("/api/agents/{agent_id}/logs"));
+        let query: Vec<_> = tail.map(|tail| ("tail", tail)).into_iter().collect();
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
+        self.send("Get logs", true, || self.client.get(&url).query(&query))
+            .await?
+            .text()
             .await
-            .context("Failed to fetch logs")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Get logs failed with {status}: {text}");
-            anyhow::bail!("Get logs failed with {status}");
-        }
-
-        let logs = response.text().await.context("Failed to read logs")?;
-        Ok(logs)
+            .context("Failed to read logs")
     }
 
     pub async fn get_logs_stream(
         &self,
         agent_id: &str,
-        _follow: bool,
+        follow: bool,
         tail: Option<usize>,
-    ) -> Result<impl futures::Stream<Item = Result<String>>> {
-        // For now, return a simple stream that reads the logs once
-        // A full SSE implementation would require a different approach
-        let logs = self.get_logs(agent_id, false, tail).await?;
+    ) -> Result<impl futures::Stream<Item = Result<String>> + use<>> {
+        let url = self.url(&format!("/api/agents/{agent_id}/logs"));
+        let mut query = vec![("follow", follow.to_string())];
+        query.extend(tail.map(|tail| ("tail", tail.to_string())));
+
+        // A followed stream stays open indefinitely, so skip the request timeout
+        let response = self
+            .send("Log stream", true, || {
+                self.stream_client.get(&url).query(&query)
+            })
+            .await?;
 
-        let stream = futures::stream::once(async move { Ok(logs) });
+        let stream = futures::stream::try_unfold(response, |mut response| async move {
+            let chunk = response
+                .chunk()
+                .await
+                .context("Failed to read log stream")?;
+            Ok(chunk.map(|bytes| (String::from_utf8_lossy(&bytes).into_owned(), response)))
+        });
 
         Ok(stream)
     }
 
-    pub async fn shutdown(&self) -> Result<()> {
-        let url = format!("{base_url}/api/shutdown", base_url = self.base_url);
-        info!("Sending shutdown signal to supervisor");
+    pub async fn get_quota(&self, agent_id: &str) -> Result<QuotaResponse> {
+        self.get_json(
+            "Quota request",
+            &format!("/api/agents/{agent_id}/quota"),
+            &[],
+        )
+        .await
+    }
+
+    /// A page of stored incident reports, newest first
+    pub async fn list_incidents(
+        &self,
+        agent_id: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<IncidentSummary>> {
+        let mut query = vec![("limit", limit.to_string()), ("offset", offset.to_string())];
+        query.extend(agent_id.map(|id| ("agent_id", id.to_string())));
+
+        let incidents: IncidentsResponse = self
+            .get_json("Incidents request", "/api/incidents", &query)
+            .await?;
+        Ok(incidents.incidents)
+    }
+
+    /// A full incident report, or `None` if there is none with that id
+    pub async fn get_incident(&self, incident_id: &str) -> Result<Option<IncidentReport>> {
+        self.get_optional("Incident request", &format!("/api/incidents/{incident_id}"))
+            .await
+    }
+
+    pub async fn get_audit_log(
+        &self,
+        agent_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<AuditEntry>> {
+        let mut query = vec![("limit", limit.to_string())];
+        query.extend(agent_id.map(|id| ("agent_id", id.to_string())));
+
+        let audit: AuditResponse = self
+            .get_json("Audit log request", "/api/audit", &query)
+            .await?;
+        Ok(audit.entries)
+    }
+
+    /// Stored security scanner reports, newest first
+    pub async fn get_security_reports(
+        &self,
+        container_id: Option<&str>,
+        severity: Option<&str>,
+        since: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<StoredSecurityReport>> {
+        let mut query = vec![("limit", limit.to_string())];
+        query.extend(container_id.map(|id| ("container_id", id.to_string())));
+        query.extend(severity.map(|severity| ("severity", severity.to_string())));
+        query.extend(since.map(|since| ("since", since.to_string())));
+
+        let reports: SecurityReportsResponse = self
+            .get_json("Security reports request", "/security/reports", &query)
+            .await?;
+        Ok(reports.reports)
+    }
+
+    /// One stored security report, or `None` if there is none with that id
+    pub async fn get_security_report(
+        &self,
+        report_id: i64,
+    ) -> Result<Option<StoredSecurityReport>> {
+        self.get_optional(
+            "Security report request",
+            &format!("/security/reports/{report_id}"),
+        )
+        .await
+    }
+
+    pub async fn get_security_whitelists(&self) -> Result<Whitelists> {
+        self.get_json("Security whitelists request", "/security/whitelists", &[])
+            .await
+    }
+
+    /// Make the supervisor re-read its security whitelists file
+    pub async fn reload_security_whitelists(&self) -> Result<Whitelists> {
+        info!("Reloading security whitelists");
+        self.post_json::<(), _>("Whitelist reload", "/security/whitelists/reload", None)
+            .await
+    }
+
+    /// Latest network counters of each agent and monitor
+    pub async fn get_network_stats(&self) -> Result<HashMap<String, NetworkStats>> {
+        self.get_json("Network stats request", "/api/network/stats", &[])
+            .await
+    }
+
+    /// Network samples at `resolution`, by default per minute, optionally
+    /// only those of `source`, an agent container or network monitor
+    pub async fn get_network_history(
+        &self,
+        source: Option<&str>,
+        resolution: Option<SampleResolution>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<NetworkHistoryResponse> {
+        let mut query = vec![("limit", limit.to_string())];
+        query.extend(source.map(|source| ("source", source.to_string())));
+        query.extend(resolution.map(|resolution| ("resolution", resolution.as_str().to_string())));
+        query.extend(since.map(|since| ("since", since.to_rfc3339())));
+        query.extend(until.map(|until| ("until", until.to_rfc3339())));
+
+        self.get_json("Network history request", "/api/network/history", &query)
+            .await
+    }
+
+    /// Deliver a network monitor's report, returning the connections the
+    /// supervisor wants severed. Failures are left to the caller to retry,
+    /// so they are not logged here.
+    pub async fn report_network(
+        &self,
+        report: &NetworkReport,
+    ) -> Result<Vec<ConnectionTermination>> {
+        debug!(
+            "Reporting {count} network alerts from {monitor}",
+            count = report.alerts.len(),
+            monitor = report.monitor
+        );
 
         let response = self
+            .post("Network report", "/api/network/reports", Some(report))
+            .await?;
+
+        // Older supervisors answer with no content
+        let response = response
+            .json::<NetworkReportResponse>()
+            .await
+            .unwrap_or_default();
+        Ok(response.terminate)
+    }
+
+    /// Have the network monitors sever an agent's connection, returning the
+    /// monitors that will
+    pub async fn terminate_connection(
+        &self,
+        monitor: Option<&str>,
+        connection: ConnectionTermination,
+    ) -> Result<Vec<String>> {
+        info!(
+            "Terminating connection to {remote_addr}",
+            remote_addr = connection.remote_addr
+        );
+
+        let request = TerminateConnectionRequest {
+            monitor: monitor.map(str::to_string),
+            connection,
+        };
+        let response: TerminateConnectionResponse = self
+            .post_json(
+                "Connection termination",
+                "/api/network/connections/terminate",
+                Some(&request),
+            )
+            .await?;
+        Ok(response.monitors)
+    }
+
+    pub async fn health_check(&self) -> Result<bool> {
+        let url = self.url("/health");
+
+        match self
             .client
-            .post(&url)
+            .get(&url)
+            .headers(self.headers.clone())
             .send()
             .await
-            .context("Failed to send shutdown request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            error!("Shutdown request failed with {status}: {text}");
-            anyhow::bail!("Shutdown request failed with {status}");
+        {
+            Ok(response) => Ok(response.status().is_success()),
+            Err(e) => {
+                debug!("Health check failed: {e}");
+                Ok(false)
+            }
         }
+    }
 
+    /// Request a short-lived token that confirms an emergency stop-all
+    pub async fn request_emergency_token(&self) -> Result<EmergencyTokenResponse> {
+        self.post_json::<(), _>("Emergency token request", "/emergency/stop-all/token", None)
+            .await
+    }
+
+    pub async fn emergency_stop_all(&self, token: &str) -> Result<FleetStopReport> {
+        warn!("Sending emergency stop-all to supervisor");
+        self.post_json(
+            "Emergency stop-all",
+            "/emergency/stop-all",
+            Some(&EmergencyStopRequest {
+                token: token.to_string(),
+            }),
+        )
+        .await
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
+        info!("Sending shutdown signal to supervisor");
+        self.post::<()>("Shutdown request", "/api/shutdown", None)
+            .await?;
         info!("Supervisor shutdown initiated");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::{HeaderMap as RequestHeaders, StatusCode as ServerStatus};
+    use axum::response::sse::{Event as SseEvent, Sse};
+    use axum::routing::{get, post};
+    use futures::StreamExt;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    async fn serve(app: Router) -> Result<AsyncSupervisorClient> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{addr}", addr = listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let retry = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+        Ok(AsyncSupervisorClient::new(Some(url))?
+            .with_token("s3cret")?
+            .with_operator("ci")?
+            .with_retry(retry))
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_reads_authenticates_and_types_errors() -> Result<()> {
+        let status_calls = Arc::new(AtomicU32::new(0));
+        let stop_calls = Arc::new(AtomicU32::new(0));
+        let app = Router::new()
+            .route(
+                "/api/status",
+                get({
+                    let calls = status_calls.clone();
+                    move |headers: RequestHeaders| async move {
+                        assert_eq!(headers["authorization"], "Bearer s3cret");
+                        assert_eq!(headers[OPERATOR_HEADER], "ci");
+                        if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                            return Err(ServerStatus::SERVICE_UNAVAILABLE);
+                        }
+                        Ok(axum::Json(serde_json::json!({
+                            "agents": [],
+                            "total_agents": 0,
+                            "running_agents": 0
+                        })))
+                    }
+                }),
+            )
+            .route(
+                "/api/agents/:agent_id/stop",
+                post({
+                    let calls = stop_calls.clone();
+                    move || async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        (ServerStatus::SERVICE_UNAVAILABLE, "draining")
+                    }
+                }),
+            )
+            .route(
+                "/api/incidents/:incident_id",
+                get(|| async { ServerStatus::NOT_FOUND }),
+            )
+            .route(
+                "/api/events/stream",
+                get(|| async {
+                    let events = [
+                        crate::events::Event::AlertRaised {
+                            agent_id: "a1".to_string(),
+                            severity: "high".to_string(),
+                            summary: "first".to_string(),
+                        },
+                        crate::events::Event::AlertRaised {
+                            agent_id: "a1".to_string(),
+                            severity: "high".to_string(),
+                            summary: "second".to_string(),
+                        },
+                    ]
+                    .map(|event| SseEvent::default().json_data(event));
+                    Sse::new(futures::stream::iter(events))
+                }),
+            );
+        let client = serve(app).await?;
+
+        assert_eq!(client.get_status().await?.total_agents, 0);
+        assert_eq!(status_calls.load(Ordering::SeqCst), 3);
+
+        // Writes are not retried once the supervisor has them
+        let error = client.stop_agent("a1").await.unwrap_err();
+        let api_error = error.downcast_ref::<ApiError>().expect("an API error");
+        assert_eq!(api_error.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            error.to_string(),
+            "Stop request failed with 503 Service Unavailable: draining"
+        );
+        assert_eq!(stop_calls.load(Ordering::SeqCst), 1);
+
+        assert!(client.get_incident("gone").await?.is_none());
+
+        let events: Vec<_> = client.stream_events().await?.collect().await;
+        assert_eq!(events.len(), 2);
+        assert!(
+            matches!(&events[1], Ok(crate::events::Event::AlertRaised { summary, .. }) if summary == "second")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_transient_rejections_are_retryable() {
+        let error = |status| ApiError {
+            request: "Network report".to_string(),
+            status,
+            message: String::new(),
+        };
+        for status in [
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::FORBIDDEN,
+            StatusCode::TOO_MANY_REQUESTS,
+        ] {
+            assert!(error(status).is_retryable(), "{status}");
+        }
+        for status in [
+            StatusCode::BAD_REQUEST,
+            StatusCode::NOT_FOUND,
+            StatusCode::PAYLOAD_TOO_LARGE,
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ] {
+            assert!(!error(status).is_retryable(), "{status}");
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let retry = RetryPolicy::default();
+        let backoffs: Vec<_> = (1..=6).map(|attempt| retry.backoff(attempt)).collect();
+        assert_eq!(backoffs[0], Duration::from_millis(250));
+        assert_eq!(backoffs[2], Duration::from_secs(1));
+        assert_eq!(backoffs[5], Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,103 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Access levels for the supervisor API. Each role from viewer up includes
+/// the ones below it; reporter stands apart, see [`Role::grants`].
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Can only send agents' tool calls and network monitor reports, for
+    /// the tokens handed to agents and monitors
+    Reporter,
+    /// Read-only access to status, logs, decisions and reports
+    Viewer,
+    /// Can also stop, pause, resume, quarantine and approve agents, and
+    /// send reports
+    Operator,
+    /// Can also spawn agents, shut down the supervisor and stop the fleet
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Reporter => write!(f, "reporter"),
+            Role::Viewer => write!(f, "viewer"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl Role {
+    /// Whether this role may make calls that require `required`. Reports
+    /// can be forged to get agents quarantined, so viewers may not send
+    /// them, and reporters may do nothing else. Which agent a reporter may
+    /// report on is up to [`ApiToken::agent_id`].
+    pub fn grants(self, required: Role) -> bool {
+        match (self, required) {
+            (Role::Reporter, required) => required == Role::Reporter,
+            (role, Role::Reporter) => role >= Role::Operator,
+            (role, required) => role >= required,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApiToken {
+    /// Who the token belongs to; recorded as the actor in the audit log
+    pub name: String,
+    pub token: String,
+    pub role: Role,
+    /// The only agent this token may be used for: calls about another agent,
+    /// or about none such as network reports, are refused. Set on the
+    /// reporter tokens the supervisor mints for the agents it spawns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+}
+
+impl ApiToken {
+    /// A reporter token for the agent `agent_id` alone
+    pub fn for_agent(agent_id: &str) -> Self {
+        Self {
+            name: agent_id.to_string(),
+            token: uuid::Uuid::new_v4().simple().to_string(),
+            role: Role::Reporter,
+            agent_id: Some(agent_id.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AuthConfig {
+    /// Require an API token on every API call. Off by default so existing
+    /// local setups keep working; the API then listens on 127.0.0.1 only.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+}
+
+impl AuthConfig {
+    pub fn identify(&self, token: &str) -> Option<&ApiToken> {
+        find_token(&self.tokens, token)
+    }
+}
+
+/// The token among `tokens` matching `token`
+pub fn find_token<'a>(
+    tokens: impl IntoIterator<Item = &'a ApiToken>,
+    token: &str,
+) -> Option<&'a ApiToken> {
+    tokens
+        .into_iter()
+        .find(|candidate| constant_time_eq(candidate.token.as_bytes(), token.as_bytes()))
+}
+
+// Avoid leaking how much of a guessed token matched through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
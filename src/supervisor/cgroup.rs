@@ -0,0 +1,348 @@
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use super::{ResourceLimits, ResourceUsage};
+
+/// Period used for `cpu.max`, in microseconds
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct CgroupConfig {
+    pub enabled: bool,
+    /// Parent cgroup (v2 unified hierarchy) under which agent slices are created
+    pub root: String,
+}
+
+impl Default for CgroupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            root: "/sys/fs/cgroup/replicante".to_string(),
+        }
+    }
+}
+
+/// Manages one cgroup v2 slice per process-spawned agent
+pub struct CgroupManager {
+    root: PathBuf,
+    // agent_id -> (cpu usage_usec, sampled at)
+    cpu_samples: Mutex<HashMap<String, (u64, Instant)>>,
+}
+
+impl CgroupManager {
+    pub fn new(config: &CgroupConfig) -> Self {
+        Self {
+            root: PathBuf::from(&config.root),
+            cpu_samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the cgroup v2 unified hierarchy is mounted
+    pub fn is_available() -> bool {
+        Path::new("/sys/fs/cgroup/cgroup.controllers").exists()
+    }
+
+    /// The agent's cgroup directory. Ids that could leave the root or name a
+    /// control file are refused.
+    pub fn agent_path(&self, agent_id: &str) -> Result<PathBuf> {
+        if agent_id.is_empty()
+            || !agent_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("Agent id {agent_id:?} is not a valid cgroup name");
+        }
+        Ok(self.root.join(agent_id))
+    }
+
+    /// Create the agent's cgroup and apply memory, CPU and pid limits,
+    /// returning its path
+    pub async fn create(&self, agent_id: &str, limits: &ResourceLimits) -> Result<PathBuf> {
+        let path = self.agent_path(agent_id)?;
+
+        if !self.root.exists() {
+            tokio::fs::create_dir_all(&self.root)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to create cgroup root {root}",
+                        root = self.root.display()
+                    )
+                })?;
+
+            // Delegate the controllers we need to child cgroups
+            write_control(&self.root, "cgroup.subtree_control", "+memory +cpu +pids").await?;
+        }
+
+        tokio::fs::create_dir_all(&path)
+            .await
+            .with_context(|| format!("Failed to create cgroup {path}", path = path.display()))?;
+
+        let memory_bytes = limits.max_memory_mb * 1024 * 1024;
+        write_control(&path, "memory.max", &memory_bytes.to_string()).await?;
+
+        // max_cpu_percent is relative to a single CPU
+        let quota = ((limits.max_cpu_percent / 100.0) * CPU_PERIOD_USEC as f64).round() as u64;
+        let cpu_max = if quota == 0 {
+            "max".to_string()
+        } else {
+            format!("{quota} {CPU_PERIOD_USEC}")
+        };
+        write_control(&path, "cpu.max", &cpu_max).await?;
+
+        write_control(&path, "pids.max", &limits.max_processes.to_string()).await?;
+
+        info!(
+            "Created cgroup for agent {agent_id}: memory.max={memory_bytes} cpu.max={cpu_max} pids.max={max_processes}",
+            max_processes = limits.max_processes
+        );
+
+        Ok(path)
+    }
+
+    /// Have the process `cmd` spawns join the cgroup at `path` between fork
+    /// and exec, so it never runs outside its limits. `cgroup.procs` is
+    /// opened here, as the child may only make plain system calls.
+    pub fn join_on_exec(path: &Path, cmd: &mut Command) -> Result<()> {
+        let target = path.join("cgroup.procs");
+        let procs = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&target)
+            .with_context(|| format!("Failed to open {target}", target = target.display()))?;
+        // SAFETY: the closure only makes a write system call on an already
+        // open file, which is safe to run between fork and exec
+        unsafe {
+            cmd.pre_exec(move || {
+                use std::io::Write;
+                // Writing 0 moves the writing process itself
+                (&procs).write_all(b"0")
+            });
+        }
+        Ok(())
+    }
+
+    /// Read current usage from the cgroup's accounting files
+    pub async fn read_usage(&self, agent_id: &str) -> Result<ResourceUsage> {
+        let path = self.agent_path(agent_id)?;
+
+        let memory_bytes: u64 = read_control(&path, "memory.current")
+            .await?
+            .trim()
+            .parse()
+            .context("Failed to parse memory.current")?;
+
+        let cpu_stat = read_control(&path, "cpu.stat").await?;
+        let usage_usec = parse_keyed_value(&cpu_stat, "usage_usec").unwrap_or(0);
+
+        let cpu_percent = {
+            let now = Instant::now();
+            let mut samples = self.cpu_samples.lock().await;
+            let percent = match samples.get(agent_id) {
+                Some((previous_usec, sampled_at)) => {
+                    let elapsed_usec = now.duration_since(*sampled_at).as_micros() as f64;
+                    if elapsed_usec > 0.0 {
+                        usage_usec.saturating_sub(*previous_usec) as f64 / elapsed_usec * 100.0
+                    } else {
+                        0.0
+                    }
+                }
+                None => 0.0,
+            };
+            samples.insert(agent_id.to_string(), (usage_usec, now));
+            percent
+        };
+
+        let disk_io_bytes = match read_control(&path, "io.stat").await {
+            Ok(io_stat) => io_stat
+                .lines()
+                .map(|line| {
+                    parse_keyed_value(line, "rbytes").unwrap_or(0)
+                        + parse_keyed_value(line, "wbytes").unwrap_or(0)
+                })
+                .sum(),
+            // The io controller is optional
+            Err(_) => 0,
+        };
+
+        Ok(ResourceUsage {
+            cpu_percent,
+            memory_mb: memory_bytes / (1024 * 1024),
+            disk_io_bytes,
+            network_bytes: 0,
+        })
+    }
+
+    /// Remove the agent's cgroup. The kernel refuses while processes remain in it.
+    pub async fn remove(&self, agent_id: &str) -> Result<()> {
+        self.cpu_samples.lock().await.remove(agent_id);
+
+        let path = self.agent_path(agent_id)?;
+        if !path.exists() {
+            return Ok(());
+        }
+
+        if let Err(e) = tokio::fs::remove_dir(&path).await {
+            warn!("Failed to remove cgroup {path}: {e}", path = path.display());
+            return Err(e.into());
+        }
+
+        debug!("Removed cgroup for agent {agent_id}");
+        Ok(())
+    }
+}
+
+async fn write_control(path: &Path, file: &str, value: &str) -> Result<()> {
+    let target = path.join(file);
+    tokio::fs::write(&target, value).await.with_context(|| {
+        format!(
+            "Failed to write {value} to {target}",
+            target = target.display()
+        )
+    })
+}
+
+async fn read_control(path: &Path, file: &str) -> Result<String> {
+    let target = path.join(file);
+    tokio::fs::read_to_string(&target)
+        .await
+        .with_context(|| format!("Failed to read {target}", target = target.display()))
+}
+
+/// Find `key value` or `key=value` pairs in cgroup stat output
+fn parse_keyed_value(text: &str, key: &str) -> Option<u64> {
+    text.split_whitespace()
+        .find_map(|token| {
+            token
+                .strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix('='))
+                .and_then(|value| value.parse().ok())
+        })
+        .or_else(|| {
+            text.lines().find_map(|line| {
+                let mut parts = line.split_whitespace();
+                (parts.next() == Some(key))
+                    .then(|| parts.next())
+                    .flatten()
+                    .and_then(|value| value.parse().ok())
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_table_keeps_defaults() {
+        let config: CgroupConfig = toml::from_str("enabled = false").unwrap();
+        assert!(!config.enabled);
+        assert_eq!(config.root, CgroupConfig::default().root);
+    }
+
+    fn manager(dir: &Path) -> CgroupManager {
+        CgroupManager::new(&CgroupConfig {
+            enabled: true,
+            root: dir.join("replicante").to_string_lossy().into_owned(),
+        })
+    }
+
+    #[test]
+    fn test_parse_keyed_value() {
+        let cpu_stat = "usage_usec 1500\nuser_usec 1000\nsystem_usec 500\n";
+        assert_eq!(parse_keyed_value(cpu_stat, "usage_usec"), Some(1500));
+        assert_eq!(parse_keyed_value(cpu_stat, "system_usec"), Some(500));
+        assert_eq!(parse_keyed_value(cpu_stat, "nr_periods"), None);
+
+        let io_line = "8:0 rbytes=4096 wbytes=512 rios=3 wios=1";
+        assert_eq!(parse_keyed_value(io_line, "rbytes"), Some(4096));
+        assert_eq!(parse_keyed_value(io_line, "wbytes"), Some(512));
+        assert_eq!(parse_keyed_value("usage_usec oops", "usage_usec"), None);
+    }
+
+    #[tokio::test]
+    async fn test_create_writes_limits() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cgroups = manager(dir.path());
+        let limits = ResourceLimits {
+            max_memory_mb: 512,
+            max_cpu_percent: 50.0,
+            max_processes: 100,
+            max_open_files: 1000,
+        };
+
+        let path = cgroups.create("agent-1", &limits).await?;
+        assert_eq!(path, dir.path().join("replicante/agent-1"));
+        let read = |file: &str| std::fs::read_to_string(path.join(file));
+        assert_eq!(read("memory.max")?, "536870912");
+        assert_eq!(read("cpu.max")?, "50000 100000");
+        assert_eq!(read("pids.max")?, "100");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("replicante/cgroup.subtree_control"))?,
+            "+memory +cpu +pids"
+        );
+
+        for agent_id in ["../escape", "cgroup.procs", ""] {
+            assert!(
+                cgroups.create(agent_id, &limits).await.is_err(),
+                "{agent_id}"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_usage() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cgroups = manager(dir.path());
+        let path = dir.path().join("replicante/agent-1");
+        std::fs::create_dir_all(&path)?;
+        std::fs::write(path.join("memory.current"), "104857600\n")?;
+        std::fs::write(path.join("cpu.stat"), "usage_usec 1000\nuser_usec 800\n")?;
+        std::fs::write(
+            path.join("io.stat"),
+            "8:0 rbytes=100 wbytes=50 rios=1 wios=1\n8:16 rbytes=1 wbytes=2 rios=1 wios=1\n",
+        )?;
+
+        let usage = cgroups.read_usage("agent-1").await?;
+        assert_eq!(usage.memory_mb, 100);
+        assert_eq!(usage.disk_io_bytes, 153);
+        assert_eq!(usage.cpu_percent, 0.0, "The first sample has no baseline");
+
+        std::fs::write(path.join("cpu.stat"), "usage_usec 50000\n")?;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(cgroups.read_usage("agent-1").await?.cpu_percent > 0.0);
+
+        // Without the optional io controller disk usage reads as 0
+        std::fs::remove_file(path.join("io.stat"))?;
+        assert_eq!(cgroups.read_usage("agent-1").await?.disk_io_bytes, 0);
+
+        assert!(cgroups.read_usage("agent-2").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_join_on_exec_moves_the_child_before_exec() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("cgroup.procs"), "")?;
+
+        let mut cmd = Command::new("true");
+        CgroupManager::join_on_exec(dir.path(), &mut cmd)?;
+        assert!(cmd.status().await?.success());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("cgroup.procs"))?,
+            "0"
+        );
+
+        let missing = dir.path().join("missing");
+        assert!(CgroupManager::join_on_exec(&missing, &mut Command::new("true")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,505 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::security::{SecurityFinding, Whitelists};
+
+/// A check run against each scanned container. Deployments can register
+/// their own next to the built-in ones.
+#[async_trait]
+pub trait SecurityCheck: Send + Sync {
+    /// Name used to configure the check under `security_scanner.checks`
+    fn name(&self) -> &str;
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<SecurityFinding>>;
+}
+
+/// What a check is run against
+pub struct CheckContext<'a> {
+    /// Name of the scanned container
+    pub container: &'a str,
+    pub whitelists: &'a Whitelists,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct CheckConfig {
+    pub enabled: bool,
+    /// Run the check at most this often instead of on every scan
+    pub interval_secs: Option<u64>,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: None,
+        }
+    }
+}
+
+struct RegisteredCheck {
+    check: Arc<dyn SecurityCheck>,
+    config: CheckConfig,
+    last_run: Mutex<Option<Instant>>,
+}
+
+/// The checks a scanner runs, with their configuration
+pub struct CheckRegistry {
+    configs: HashMap<String, CheckConfig>,
+    checks: RwLock<Vec<Arc<RegisteredCheck>>>,
+}
+
+impl CheckRegistry {
+    pub fn new(configs: HashMap<String, CheckConfig>) -> Self {
+        Self {
+            configs,
+            checks: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Add a check, configured by its entry in `security_scanner.checks`. A
+    /// check registered under an existing name replaces it.
+    pub fn register(&self, check: Arc<dyn SecurityCheck>) {
+        let name = check.name().to_string();
+        let config = self.configs.get(&name).cloned().unwrap_or_default();
+        if !config.enabled {
+            info!("Security check {name} is disabled");
+        }
+
+        let registered = Arc::new(RegisteredCheck {
+            check,
+            config,
+            last_run: Mutex::new(None),
+        });
+
+        let mut checks = self
+            .checks
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match checks.iter_mut().find(|c| c.check.name() == name) {
+            Some(existing) => *existing = registered,
+            None => checks.push(registered),
+        }
+    }
+
+    /// Names of the registered checks, in the order they run
+    pub fn names(&self) -> Vec<String> {
+        self.checks
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|c| c.check.name().to_string())
+            .collect()
+    }
+
+    /// Warn about configured checks that were never registered, which are
+    /// most likely misspelled
+    pub fn warn_unknown(&self) {
+        let names = self.names();
+        for name in self.configs.keys() {
+            if !names.contains(name) {
+                warn!("Configured security check {name} is not registered");
+            }
+        }
+    }
+
+    /// Enabled checks whose interval has elapsed, marking them as run
+    pub fn due(&self, now: Instant) -> Vec<Arc<dyn SecurityCheck>> {
+        self.checks
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .filter(|c| c.config.enabled)
+            .filter(|c| {
+                let mut last_run = c
+                    .last_run
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                let due = match (c.config.interval_secs, *last_run) {
+                    (Some(secs), Some(last)) => {
+                        now.duration_since(last) >= Duration::from_secs(secs)
+                    }
+                    _ => true,
+                };
+                if due {
+                    *last_run = Some(now);
+                }
+                due
+            })
+            .map(|c| c.check.clone())
+            .collect()
+    }
+}
+
+impl fmt::Debug for CheckRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckRegistry")
+            .field("checks", &self.names())
+            .finish()
+    }
+}
+
+/// Processes not on the whitelists, listed with `ps aux`
+pub struct ProcessCheck;
+
+#[async_trait]
+impl SecurityCheck for ProcessCheck {
+    fn name(&self) -> &str {
+        "processes"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+
+        // Get process list from container
+        let output = Command::new("docker")
+            .args(["exec", ctx.container, "ps", "aux"])
+            .output()
+            .context("Failed to list processes in container")?;
+
+        if output.status.success() {
+            let processes = String::from_utf8_lossy(&output.stdout);
+
+            for line in processes.lines().skip(1) {
+                // Skip header
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() < 11 {
+                    continue;
+                }
+
+                let process_name = parts[10];
+
+                // Check if process is whitelisted
+                if !ctx
+                    .whitelists
+                    .is_process_allowed(ctx.container, process_name)
+                {
+                    findings.push(SecurityFinding::UnauthorizedProcess {
+                        pid: parts[1].parse().unwrap_or(0),
+                        name: process_name.to_string(),
+                        cmdline: parts[10..].join(" "),
+                    });
+                }
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+/// Sockets on ports commonly used for remote access or backdoors
+pub struct NetworkCheck;
+
+impl NetworkCheck {
+    fn is_suspicious_port(port: u16) -> bool {
+        match port {
+            22 | 23 | 135 | 139 | 445 | 3389 => true, // SSH, Telnet, SMB, RDP
+            1337 | 31337 | 4444 | 6666 => true,       // Common backdoor ports
+            _ => false,
+        }
+    }
+}
+
+#[async_trait]
+impl SecurityCheck for NetworkCheck {
+    fn name(&self) -> &str {
+        "network"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+
+        // Check network connections
+        let output = Command::new("docker")
+            .args(["exec", ctx.container, "ss", "-tuln"])
+            .output()
+            .context("Failed to check network connections")?;
+
+        if output.status.success() {
+            let connections = String::from_utf8_lossy(&output.stdout);
+
+            for line in connections.lines().skip(1) {
+                // Skip header
+                if line.contains("LISTEN") || line.contains("ESTAB") {
+                    // Parse connection details
+                    let parts: Vec<&str> = line.split_whitespace().collect();
+                    if parts.len() < 5 {
+                        continue;
+                    }
+
+                    let local_addr = parts[3];
+
+                    // Check for suspicious ports
+                    if let Some(port_str) = local_addr.split(':').next_back()
+                        && let Ok(port) = port_str.parse::<u16>()
+                        && Self::is_suspicious_port(port)
+                    {
+                        findings.push(SecurityFinding::NetworkViolation {
+                            connection: local_addr.to_string(),
+                            port,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+/// Recent modifications to credentials and other sensitive files
+pub struct FilesystemCheck;
+
+#[async_trait]
+impl SecurityCheck for FilesystemCheck {
+    fn name(&self) -> &str {
+        "filesystem"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+
+        // Check for suspicious file modifications
+        let output = Command::new("docker")
+            .args([
+                "exec",
+                ctx.container,
+                "find",
+                "/",
+                "-type",
+                "f",
+                "-mmin",
+                "-5",
+                "-ls",
+            ])
+            .output()
+            .context("Failed to check filesystem")?;
+
+        if output.status.success() {
+            let files = String::from_utf8_lossy(&output.stdout);
+
+            for line in files.lines() {
+                // Check for modifications to sensitive files
+                if line.contains("/etc/passwd")
+                    || line.contains("/etc/shadow")
+                    || line.contains("/etc/sudoers")
+                    || line.contains("/.ssh/")
+                {
+                    findings.push(SecurityFinding::FilesystemViolation {
+                        path: line.to_string(),
+                        operation: "modified".to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+/// Containers running as root or with dangerous capabilities
+pub struct PrivilegeCheck;
+
+#[async_trait]
+impl SecurityCheck for PrivilegeCheck {
+    fn name(&self) -> &str {
+        "privileges"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+
+        // Check if container is running as root
+        let output = Command::new("docker")
+            .args(["exec", ctx.container, "id"])
+            .output()
+            .context("Failed to check user privileges")?;
+
+        if output.status.success() {
+            let id_output = String::from_utf8_lossy(&output.stdout);
+
+            if id_output.contains("uid=0") {
+                findings.push(SecurityFinding::PrivilegeEscalation {
+                    details: "Container running as root".to_string(),
+                });
+            }
+        }
+
+        // Check for capability additions
+        let output = Command::new("docker")
+            .args([
+                "inspect",
+                ctx.container,
+                "--format",
+                "{{.HostConfig.CapAdd}}",
+            ])
+            .output()
+            .context("Failed to check capabilities")?;
+
+        if output.status.success() {
+            let caps = String::from_utf8_lossy(&output.stdout);
+
+            if caps.contains("SYS_ADMIN") || caps.contains("SYS_PTRACE") {
+                findings.push(SecurityFinding::PrivilegeEscalation {
+                    details: format!("Dangerous capabilities: {caps}"),
+                });
+            }
+        }
+
+        Ok(findings)
+    }
+}
+
+/// Sockets of container runtimes that give control of the host when mounted
+const RUNTIME_SOCKETS: [&str; 2] = ["docker.sock", "containerd.sock"];
+
+/// Namespaces that isolate a container from the host
+const NAMESPACES: [&str; 3] = ["pid", "mnt", "net"];
+
+// Prints a line for each cgroup v1 release_agent that is set or
+// notify_on_release that is enabled, the setup of the release_agent escape
+const CGROUP_WRITES_SCRIPT: &str = r#"
+for f in /sys/fs/cgroup/release_agent /sys/fs/cgroup/*/release_agent; do
+    [ -r "$f" ] && v=$(cat "$f") && [ -n "$v" ] && echo "release_agent set in $f: $v"
+done
+for f in /sys/fs/cgroup/*/notify_on_release /sys/fs/cgroup/*/*/notify_on_release; do
+    [ -r "$f" ] && [ "$(cat "$f")" = 1 ] && echo "notify_on_release enabled in $f"
+done
+true
+"#;
+
+/// Signs of a container escape, or of a container set up to allow one:
+/// privileged mode, mounted runtime sockets, host namespaces and cgroup
+/// release_agent writes
+pub struct EscapeCheck {
+    /// The host's /proc, whose init namespaces containers are compared with
+    proc_root: PathBuf,
+}
+
+impl EscapeCheck {
+    pub fn new(proc_root: impl Into<PathBuf>) -> Self {
+        Self {
+            proc_root: proc_root.into(),
+        }
+    }
+
+    /// Namespaces, from `readlink /proc/1/ns/<name>` output inside the
+    /// container, that are the same as the host init's
+    pub fn shared_namespaces(&self, container_links: &str) -> Vec<String> {
+        container_links
+            .lines()
+            .map(str::trim)
+            .filter_map(|link| {
+                let (name, _) = link.split_once(':')?;
+                if !NAMESPACES.contains(&name) {
+                    return None;
+                }
+                let host = std::fs::read_link(self.proc_root.join("1/ns").join(name)).ok()?;
+                (host.to_string_lossy() == link).then(|| name.to_string())
+            })
+            .collect()
+    }
+}
+
+/// Mounts, as `<source>:<destination>` lines, that expose a runtime socket
+pub fn runtime_socket_mounts(mounts: &str) -> Vec<&str> {
+    mounts
+        .lines()
+        .map(str::trim)
+        .filter(|mount| {
+            RUNTIME_SOCKETS
+                .iter()
+                .any(|socket| mount.split(':').any(|side| side.ends_with(socket)))
+        })
+        .collect()
+}
+
+fn docker_output(args: &[&str], context: &'static str) -> Result<Option<String>> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .context(context)?;
+
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string()))
+}
+
+#[async_trait]
+impl SecurityCheck for EscapeCheck {
+    fn name(&self) -> &str {
+        "container_escape"
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+
+        if let Some(privileged) = docker_output(
+            &[
+                "inspect",
+                ctx.container,
+                "--format",
+                "{{.HostConfig.Privileged}}",
+            ],
+            "Failed to check privileged mode",
+        )? && privileged.trim() == "true"
+        {
+            findings.push(SecurityFinding::PrivilegeEscalation {
+                details: "Container running in privileged mode".to_string(),
+            });
+        }
+
+        if let Some(mounts) = docker_output(
+            &[
+                "inspect",
+                ctx.container,
+                "--format",
+                "{{range .Mounts}}{{.Source}}:{{.Destination}}\n{{end}}",
+            ],
+            "Failed to check container mounts",
+        )? {
+            for mount in runtime_socket_mounts(&mounts) {
+                findings.push(SecurityFinding::PrivilegeEscalation {
+                    details: format!("Container runtime socket mounted: {mount}"),
+                });
+            }
+        }
+
+        let links: Vec<String> = NAMESPACES
+            .iter()
+            .map(|name| format!("/proc/1/ns/{name}"))
+            .collect();
+        let mut args = vec!["exec", ctx.container, "readlink"];
+        args.extend(links.iter().map(String::as_str));
+        if let Some(links) = docker_output(&args, "Failed to read container namespaces")? {
+            for name in self.shared_namespaces(&links) {
+                findings.push(SecurityFinding::PrivilegeEscalation {
+                    details: format!("Container shares the host's {name} namespace"),
+                });
+            }
+        }
+
+        if let Some(writes) = docker_output(
+            &["exec", ctx.container, "sh", "-c", CGROUP_WRITES_SCRIPT],
+            "Failed to check cgroup release agents",
+        )? {
+            for write in writes.lines().filter(|line| !line.trim().is_empty()) {
+                findings.push(SecurityFinding::PrivilegeEscalation {
+                    details: format!("Suspicious cgroup write: {write}"),
+                });
+            }
+        }
+
+        Ok(findings)
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 use tokio::signal;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use super::{Supervisor, SupervisorConfig};
 
@@ -36,17 +36,39 @@ impl Daemon {
 
         // Start the supervisor
         self.supervisor.start().await?;
+        crate::service::notify("READY=1");
 
-        // Wait for shutdown signal
+        // Wait for shutdown signal, reloading the security whitelists on SIGHUP
         let shutdown = Self::shutdown_signal();
+        tokio::pin!(shutdown);
 
-        tokio::select! {
-            _ = shutdown => {
-                info!("Received shutdown signal");
+        #[cfg(unix)]
+        let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())
+            .context("Failed to install SIGHUP handler")?;
+
+        loop {
+            #[cfg(unix)]
+            let reload = hangup.recv();
+            #[cfg(not(unix))]
+            let reload = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = &mut shutdown => {
+                    info!("Received shutdown signal");
+                    break;
+                }
+                _ = reload => {
+                    crate::service::notify_reloading();
+                    if let Err(e) = self.supervisor.reload_security_whitelists().await {
+                        warn!("Failed to reload security whitelists: {e:#}");
+                    }
+                    crate::service::notify("READY=1");
+                }
             }
         }
 
         // Graceful shutdown
+        crate::service::notify("STOPPING=1");
         self.shutdown().await?;
 
         Ok(())
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+use tracing::debug;
+
+use crate::DecisionRecord;
+use crate::state::StateManager;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Decisions still waiting for a result that are watched for one to arrive
+const MAX_PENDING: usize = 16;
+
+/// Tails an agent's decisions table
+pub struct DecisionStreamer {
+    state: StateManager,
+}
+
+impl DecisionStreamer {
+    pub fn new(state: StateManager) -> Self {
+        Self { state }
+    }
+
+    /// Send the last `backlog` decisions, then every new one as it is recorded.
+    /// Decisions are recorded before their action runs, so one sent without a
+    /// result is sent again, with the same id, once its result is stored.
+    pub async fn stream_decisions(
+        &self,
+        backlog: usize,
+        tx: mpsc::Sender<DecisionRecord>,
+    ) -> Result<()> {
+        let mut recent = self
+            .state
+            .get_decisions_page(backlog.max(1), 0, None, None)
+            .await?;
+        let mut last_id = recent.first().map_or(0, |decision| decision.id);
+        recent.truncate(backlog);
+
+        let mut pending = BTreeSet::new();
+        for decision in recent.into_iter().rev() {
+            if !send_decision(decision, &mut pending, &tx).await {
+                return Ok(());
+            }
+        }
+
+        let mut ticker = interval(POLL_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if tx.is_closed() {
+                return Ok(());
+            }
+
+            for id in pending.clone() {
+                let Some(decision) = self.state.get_decision(id).await? else {
+                    pending.remove(&id);
+                    continue;
+                };
+                if decision.result.is_some() && !send_decision(decision, &mut pending, &tx).await {
+                    return Ok(());
+                }
+            }
+
+            for decision in self.state.get_decisions_since(last_id).await? {
+                last_id = decision.id;
+                if !send_decision(decision, &mut pending, &tx).await {
+                    return Ok(());
+                }
+            }
+
+            // Actions that never completed, e.g. after a crash, are given up on
+            while pending.len() > MAX_PENDING {
+                pending.pop_first();
+            }
+        }
+    }
+}
+
+/// Returns false once the receiving side has gone away
+async fn send_decision(
+    decision: DecisionRecord,
+    pending: &mut BTreeSet<i64>,
+    tx: &mpsc::Sender<DecisionRecord>,
+) -> bool {
+    if decision.result.is_some() {
+        pending.remove(&decision.id);
+    } else {
+        pending.insert(decision.id);
+    }
+
+    if tx.send(decision).await.is_err() {
+        debug!("Decision stream receiver closed");
+        return false;
+    }
+    true
+}
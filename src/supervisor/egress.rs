@@ -0,0 +1,452 @@
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, copy_bidirectional};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use url::{Position, Url};
+
+use crate::domains::{domain_matches, normalize_host};
+
+use super::network::IpCidr;
+use super::security::{RiskLevel, SecurityFinding, SecurityReport};
+use super::store::SupervisorStore;
+use super::{NetworkMode, NetworkRestrictions};
+
+/// Largest HTTP request head read before the request is rejected
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+const SOCKS_VERSION: u8 = 0x05;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct EgressProxyConfig {
+    /// Route sandboxed agents without full network access through a proxy
+    /// that enforces their allowed domains
+    pub enabled: bool,
+    /// Address the per-agent proxies listen on; must be reachable by agents
+    pub bind_address: String,
+}
+
+impl Default for EgressProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+        }
+    }
+}
+
+/// Destinations one agent may connect to
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    mode: NetworkMode,
+    allowed_domains: Vec<String>,
+    allowed_cidrs: Vec<IpCidr>,
+    blocked_ports: Vec<u16>,
+}
+
+impl EgressPolicy {
+    pub fn from_restrictions(restrictions: &NetworkRestrictions) -> Self {
+        let allowed_cidrs = restrictions
+            .allowed_cidrs
+            .iter()
+            .filter_map(|cidr| match cidr.parse() {
+                Ok(cidr) => Some(cidr),
+                Err(e) => {
+                    warn!("Ignoring allowed CIDR range: {e:#}");
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            mode: restrictions.mode.clone(),
+            allowed_domains: restrictions.allowed_domains.clone(),
+            allowed_cidrs,
+            blocked_ports: restrictions.blocked_ports.clone(),
+        }
+    }
+
+    /// Domains match as in [`domain_matches`]: `example.com` allows only that
+    /// host and `*.example.com` only its subdomains. IP addresses must be
+    /// listed literally or fall in one of the allowed CIDR ranges.
+    pub fn permits(&self, host: &str, port: u16) -> bool {
+        if self.blocked_ports.contains(&port) {
+            return false;
+        }
+
+        match self.mode {
+            NetworkMode::None => false,
+            NetworkMode::Full => true,
+            NetworkMode::Filtered => {
+                let host = normalize_host(host);
+                if let Ok(ip) = host.parse::<IpAddr>()
+                    && self.allowed_cidrs.iter().any(|cidr| cidr.contains(ip))
+                {
+                    return true;
+                }
+                self.allowed_domains
+                    .iter()
+                    .any(|pattern| domain_matches(pattern, &host))
+            }
+        }
+    }
+}
+
+/// Runs one HTTP and SOCKS5 proxy listener per registered agent, so each
+/// connection is checked against the policy of the agent that made it
+pub struct EgressProxy {
+    config: EgressProxyConfig,
+    store: Option<Arc<SupervisorStore>>,
+    listeners: Mutex<HashMap<String, (SocketAddr, JoinHandle<()>)>>,
+}
+
+impl EgressProxy {
+    pub fn new(config: EgressProxyConfig) -> Self {
+        Self {
+            config,
+            store: None,
+            listeners: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Persist denied connections as security reports
+    pub fn with_store(mut self, store: Arc<SupervisorStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Start a proxy for an agent, returning the address it listens on
+    pub async fn register(&self, agent_id: &str, policy: EgressPolicy) -> Result<SocketAddr> {
+        let listener = TcpListener::bind((self.config.bind_address.as_str(), 0))
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to bind egress proxy for agent {agent_id} on {bind_address}",
+                    bind_address = self.config.bind_address
+                )
+            })?;
+        let addr = listener.local_addr()?;
+
+        let agent = Arc::new(AgentEgress {
+            agent_id: agent_id.to_string(),
+            policy,
+            store: self.store.clone(),
+        });
+        let handle = tokio::spawn(serve(listener, agent));
+
+        if let Some((_, previous)) = self
+            .listeners
+            .lock()
+            .await
+            .insert(agent_id.to_string(), (addr, handle))
+        {
+            previous.abort();
+        }
+
+        info!("Egress proxy for agent {agent_id} listening on {addr}");
+        Ok(addr)
+    }
+
+    /// Stop accepting connections for an agent. Open tunnels end with the agent.
+    pub async fn unregister(&self, agent_id: &str) {
+        if let Some((addr, handle)) = self.listeners.lock().await.remove(agent_id) {
+            handle.abort();
+            debug!("Egress proxy for agent {agent_id} on {addr} stopped");
+        }
+    }
+
+    pub async fn proxy_address(&self, agent_id: &str) -> Option<SocketAddr> {
+        self.listeners
+            .lock()
+            .await
+            .get(agent_id)
+            .map(|(addr, _)| *addr)
+    }
+}
+
+struct AgentEgress {
+    agent_id: String,
+    policy: EgressPolicy,
+    store: Option<Arc<SupervisorStore>>,
+}
+
+impl AgentEgress {
+    async fn deny(&self, host: &str, port: u16) {
+        warn!(
+            "Denied egress from agent {agent_id} to {host}:{port}",
+            agent_id = self.agent_id
+        );
+
+        let Some(store) = &self.store else {
+            return;
+        };
+
+        let report = SecurityReport {
+            timestamp: Utc::now(),
+            container_id: self.agent_id.clone(),
+            findings: vec![SecurityFinding::NetworkViolation {
+                connection: format!("{host}:{port}"),
+                port,
+            }],
+            risk_level: RiskLevel::Medium,
+        };
+        if let Err(e) = store.save_security_report(&report).await {
+            error!(
+                "Failed to store egress violation for agent {agent_id}: {e}",
+                agent_id = self.agent_id
+            );
+        }
+    }
+}
+
+async fn serve(listener: TcpListener, agent: Arc<AgentEgress>) {
+    loop {
+        let (client, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(
+                    "Egress proxy for agent {agent_id} failed to accept: {e}",
+                    agent_id = agent.agent_id
+                );
+                continue;
+            }
+        };
+
+        let agent = agent.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(client, &agent).await {
+                debug!(
+                    "Egress connection from {peer} for agent {agent_id} ended: {e}",
+                    agent_id = agent.agent_id
+                );
+            }
+        });
+    }
+}
+
+async fn handle_client(client: TcpStream, agent: &AgentEgress) -> Result<()> {
+    let mut first = [0u8; 1];
+    if client.peek(&mut first).await? == 0 {
+        return Ok(());
+    }
+
+    if first[0] == SOCKS_VERSION {
+        handle_socks(client, agent).await
+    } else {
+        handle_http(client, agent).await
+    }
+}
+
+async fn handle_http(mut client: TcpStream, agent: &AgentEgress) -> Result<()> {
+    let (head, body) = read_head(&mut client).await?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+
+    let (Some(method), Some(target), Some(version)) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return respond(&mut client, "400 Bad Request", "Malformed request line").await;
+    };
+
+    // HTTPS and other TLS traffic is tunnelled after a CONNECT
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let Some((host, port)) = split_host_port(target) else {
+            return respond(&mut client, "400 Bad Request", "Expected host:port").await;
+        };
+        if !agent.policy.permits(host, port) {
+            agent.deny(host, port).await;
+            return respond(&mut client, "403 Forbidden", "Destination not allowed").await;
+        }
+
+        let mut upstream = match TcpStream::connect((normalize_host(host).as_str(), port)).await {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                respond(&mut client, "502 Bad Gateway", "Destination unreachable").await?;
+                return Err(e).with_context(|| format!("Failed to connect to {host}:{port}"));
+            }
+        };
+
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+        upstream.write_all(&body).await?;
+        copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    }
+
+    // Plain HTTP requests carry the absolute URL
+    let Ok(url) = Url::parse(target) else {
+        return respond(&mut client, "400 Bad Request", "Expected an absolute URL").await;
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return respond(&mut client, "400 Bad Request", "Expected an absolute URL").await;
+    };
+    if url.scheme() != "http" {
+        return respond(
+            &mut client,
+            "400 Bad Request",
+            "Only http URLs can be proxied",
+        )
+        .await;
+    }
+    if !agent.policy.permits(host, port) {
+        agent.deny(host, port).await;
+        return respond(&mut client, "403 Forbidden", "Destination not allowed").await;
+    }
+
+    let mut request = format!(
+        "{method} {path} {version}\r\n",
+        path = &url[Position::BeforePath..]
+    );
+    for line in lines {
+        let name = line.split(':').next().unwrap_or_default().trim();
+        if ["connection", "proxy-connection", "proxy-authorization"]
+            .iter()
+            .any(|hop| name.eq_ignore_ascii_case(hop))
+        {
+            continue;
+        }
+        request.push_str(line);
+        request.push_str("\r\n");
+    }
+    // Later requests on a kept-alive connection would skip the check
+    request.push_str("Connection: close\r\n\r\n");
+
+    let mut upstream = match TcpStream::connect((normalize_host(host).as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            respond(&mut client, "502 Bad Gateway", "Destination unreachable").await?;
+            return Err(e).with_context(|| format!("Failed to connect to {host}:{port}"));
+        }
+    };
+
+    upstream.write_all(request.as_bytes()).await?;
+    upstream.write_all(&body).await?;
+    copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+/// Read up to the end of the HTTP request head, returning the head and any
+/// body bytes already received
+async fn read_head(client: &mut TcpStream) -> Result<(String, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+
+    loop {
+        if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            let body = buf.split_off(end + 4);
+            buf.truncate(end);
+            let head = String::from_utf8(buf).context("Request head is not UTF-8")?;
+            return Ok((head, body));
+        }
+
+        if buf.len() > MAX_HEAD_BYTES {
+            bail!("Request head exceeds {MAX_HEAD_BYTES} bytes");
+        }
+
+        let read = client.read(&mut chunk).await?;
+        if read == 0 {
+            bail!("Connection closed before the request head ended");
+        }
+        buf.extend_from_slice(&chunk[..read]);
+    }
+}
+
+fn split_host_port(target: &str) -> Option<(&str, u16)> {
+    let (host, port) = target.rsplit_once(':')?;
+    Some((host, port.parse().ok()?))
+}
+
+async fn respond(client: &mut TcpStream, status: &str, message: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {length}\r\nConnection: close\r\n\r\n{message}",
+        length = message.len()
+    );
+    client.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// SOCKS5 CONNECT without authentication (RFC 1928)
+async fn handle_socks(mut client: TcpStream, agent: &AgentEgress) -> Result<()> {
+    let mut greeting = [0u8; 2];
+    client.read_exact(&mut greeting).await?;
+    let mut methods = vec![0u8; greeting[1] as usize];
+    client.read_exact(&mut methods).await?;
+
+    if !methods.contains(&0x00) {
+        client.write_all(&[SOCKS_VERSION, 0xff]).await?;
+        return Ok(());
+    }
+    client.write_all(&[SOCKS_VERSION, 0x00]).await?;
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+
+    let host = match request[3] {
+        0x01 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        0x03 => {
+            let mut length = [0u8; 1];
+            client.read_exact(&mut length).await?;
+            let mut name = vec![0u8; length[0] as usize];
+            client.read_exact(&mut name).await?;
+            String::from_utf8(name).context("Domain name is not UTF-8")?
+        }
+        0x04 => {
+            let mut ip = [0u8; 16];
+            client.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        _ => return socks_reply(&mut client, 0x08).await,
+    };
+
+    let mut port = [0u8; 2];
+    client.read_exact(&mut port).await?;
+    let port = u16::from_be_bytes(port);
+
+    // Only CONNECT is supported, not BIND or UDP ASSOCIATE
+    if request[1] != 0x01 {
+        return socks_reply(&mut client, 0x07).await;
+    }
+
+    if !agent.policy.permits(&host, port) {
+        agent.deny(&host, port).await;
+        return socks_reply(&mut client, 0x02).await;
+    }
+
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            socks_reply(&mut client, 0x05).await?;
+            return Err(e).with_context(|| format!("Failed to connect to {host}:{port}"));
+        }
+    };
+
+    socks_reply(&mut client, 0x00).await?;
+    copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+async fn socks_reply(client: &mut TcpStream, code: u8) -> Result<()> {
+    // Clients don't use the bound address of a CONNECT, so report 0.0.0.0:0
+    client
+        .write_all(&[SOCKS_VERSION, code, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await?;
+    Ok(())
+}
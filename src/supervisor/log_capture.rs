@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+
+const LOG_FILE_NAME: &str = "agent.log";
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LogCaptureConfig {
+    pub enabled: bool,
+    /// Directory holding one subdirectory of log files per agent
+    pub directory: String,
+    /// Rotate the active log file once it grows past this size
+    pub max_file_size_mb: u64,
+    /// Number of rotated files kept next to the active one
+    pub max_files: usize,
+    /// Rotated files older than this are deleted
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for LogCaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: "logs/agents".to_string(),
+            max_file_size_mb: 10,
+            max_files: 5,
+            max_age_days: Some(7),
+        }
+    }
+}
+
+impl LogCaptureConfig {
+    pub fn agent_dir(&self, agent_id: &str) -> PathBuf {
+        Path::new(&self.directory).join(agent_id)
+    }
+
+    /// Path of the file an agent's output is currently written to
+    pub fn log_path(&self, agent_id: &str) -> PathBuf {
+        self.agent_dir(agent_id).join(LOG_FILE_NAME)
+    }
+}
+
+/// Append-only log file that rotates to `agent.log.1`, `agent.log.2`, ...
+struct RotatingLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+    max_age: Option<Duration>,
+}
+
+impl RotatingLog {
+    async fn open(path: PathBuf, config: &LogCaptureConfig) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await.with_context(|| {
+                format!("Failed to create log directory {dir}", dir = dir.display())
+            })?;
+        }
+
+        let file = open_append(&path).await?;
+        let size = file.metadata().await?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size: config.max_file_size_mb * 1024 * 1024,
+            max_files: config.max_files,
+            max_age: config
+                .max_age_days
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+        })
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.max_size > 0 && self.size >= self.max_size {
+            self.rotate().await?;
+        }
+
+        let entry = format!("{timestamp} {line}\n", timestamp = Utc::now().to_rfc3339());
+        self.file.write_all(entry.as_bytes()).await?;
+        // Flush per line so the log endpoint sees output as it happens
+        self.file.flush().await?;
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.flush().await?;
+
+        // Shift agent.log.N-1 -> agent.log.N, dropping the oldest
+        for index in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, index);
+            if from.exists() {
+                tokio::fs::rename(&from, rotated_path(&self.path, index + 1)).await?;
+            }
+        }
+
+        if self.max_files == 0 {
+            tokio::fs::remove_file(&self.path).await?;
+        } else {
+            tokio::fs::rename(&self.path, rotated_path(&self.path, 1)).await?;
+            let dropped = rotated_path(&self.path, self.max_files + 1);
+            if let Err(e) = tokio::fs::remove_file(&dropped).await
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                warn!(
+                    "Failed to remove old log {path}: {e}",
+                    path = dropped.display()
+                );
+            }
+        }
+
+        self.file = open_append(&self.path).await?;
+        self.size = 0;
+
+        if let Some(max_age) = self.max_age {
+            remove_expired(&self.path, self.max_files, max_age).await;
+        }
+
+        debug!("Rotated log file {path}", path = self.path.display());
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open log file {path}", path = path.display()))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+async fn remove_expired(path: &Path, max_files: usize, max_age: Duration) {
+    for index in 1..=max_files {
+        let rotated = rotated_path(path, index);
+        let Ok(metadata) = tokio::fs::metadata(&rotated).await else {
+            continue;
+        };
+        let expired = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+
+        if expired && let Err(e) = tokio::fs::remove_file(&rotated).await {
+            warn!(
+                "Failed to remove expired log {path}: {e}",
+                path = rotated.display()
+            );
+        }
+    }
+}
+
+/// An agent log file opened ahead of spawning, so a failure to create it
+/// never leaves the child writing into pipes nobody drains
+pub struct OutputCapture {
+    path: PathBuf,
+    log: Arc<Mutex<RotatingLog>>,
+}
+
+impl OutputCapture {
+    pub async fn open(agent_id: &str, config: &LogCaptureConfig) -> Result<Self> {
+        let path = config.log_path(agent_id);
+        let log = RotatingLog::open(path.clone(), config).await?;
+
+        Ok(Self {
+            path,
+            log: Arc::new(Mutex::new(log)),
+        })
+    }
+
+    /// Take the child's piped stdout and stderr and copy them into the log file
+    pub fn attach(self, child: &mut Child) -> PathBuf {
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(copy_lines(stdout, "[STDOUT]", self.log.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(copy_lines(stderr, "[STDERR]", self.log));
+        }
+
+        self.path
+    }
+}
+
+async fn copy_lines<R>(reader: R, prefix: &'static str, log: Arc<Mutex<RotatingLog>>)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    // Lines that are not UTF-8 are kept, with the invalid bytes replaced
+    while let Ok(read) = reader.read_until(b'\n', &mut buffer).await
+        && read > 0
+    {
+        let end = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+        let end = end.strip_suffix(b"\r").unwrap_or(end);
+        let line = String::from_utf8_lossy(end).into_owned();
+        buffer.clear();
+
+        let mut log = log.lock().await;
+        if let Err(e) = log.write_line(&format!("{prefix} {line}")).await {
+            error!(
+                "Failed to write agent output to {path}: {e}",
+                path = log.path.display()
+            );
+            break;
+        }
+    }
+
+    if let Err(e) = log.lock().await.file.flush().await {
+        debug!("Failed to flush agent log: {e}");
+    }
+}
+
+/// Read the last `lines` lines of a captured log, including rotated files
+pub async fn read_tail(path: &Path, lines: usize) -> Result<Vec<String>> {
+    let mut collected: Vec<String> = Vec::new();
+
+    let mut index = 0;
+    loop {
+        let file = if index == 0 {
+            path.to_path_buf()
+        } else {
+            rotated_path(path, index)
+        };
+        if !file.exists() {
+            break;
+        }
+
+        let contents = tokio::fs::read_to_string(&file)
+            .await
+            .with_context(|| format!("Failed to read log file {path}", path = file.display()))?;
+
+        // Older files go in front of what we already collected
+        let mut chunk: Vec<String> = contents.lines().map(|line| line.to_string()).collect();
+        chunk.append(&mut collected);
+        collected = chunk;
+
+        if collected.len() >= lines {
+            break;
+        }
+        index += 1;
+    }
+
+    let skip = collected.len().saturating_sub(lines);
+    Ok(collected.split_off(skip))
+}
@@ -1,21 +1,45 @@
 use anyhow::{Context, Result, bail};
 use serde_json::Value;
+use std::path::PathBuf;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader, SeekFrom};
 use tokio::process::Command;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+use super::log_capture;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct LogStreamer {
     container_id: String,
+    log_file: Option<PathBuf>,
 }
 
 impl LogStreamer {
     pub fn new(container_id: String) -> Self {
-        Self { container_id }
+        Self {
+            container_id,
+            log_file: None,
+        }
+    }
+
+    /// Read captured output from this file instead of `docker logs` when it exists
+    pub fn with_log_file(mut self, log_file: PathBuf) -> Self {
+        self.log_file = Some(log_file);
+        self
+    }
+
+    fn captured_log(&self) -> Option<&PathBuf> {
+        self.log_file.as_ref().filter(|path| path.exists())
     }
 
     pub async fn stream_logs(&self, tx: mpsc::Sender<String>) -> Result<()> {
+        if let Some(path) = self.captured_log() {
+            return follow_file(path.clone(), tx).await;
+        }
+
         info!(
             "Starting log stream for container {container_id}",
             container_id = self.container_id
@@ -101,6 +125,10 @@ impl LogStreamer {
     }
 
     pub async fn get_recent_logs(&self, lines: usize) -> Result<Vec<String>> {
+        if let Some(path) = self.captured_log() {
+            return log_capture::read_tail(path, lines).await;
+        }
+
         debug!(
             "Getting last {} logs for container {}",
             lines, self.container_id
@@ -177,3 +205,52 @@ impl LogStreamer {
         Ok(json_logs)
     }
 }
+
+/// Send the tail of a captured log, then poll it for new lines like `tail -F`
+async fn follow_file(path: PathBuf, tx: mpsc::Sender<String>) -> Result<()> {
+    info!("Following captured log {path}", path = path.display());
+
+    for line in log_capture::read_tail(&path, 100).await? {
+        if tx.send(line).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    let mut position = tokio::fs::metadata(&path).await?.len();
+    let mut pending = String::new();
+
+    loop {
+        tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+
+        if tx.is_closed() {
+            return Ok(());
+        }
+
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+
+        // The file shrank, so it was rotated: start over on the new one
+        if metadata.len() < position {
+            position = 0;
+            pending.clear();
+        }
+        if metadata.len() == position {
+            continue;
+        }
+
+        let mut file = tokio::fs::File::open(&path).await?;
+        file.seek(SeekFrom::Start(position)).await?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer).await?;
+        position += buffer.len() as u64;
+
+        pending.push_str(&String::from_utf8_lossy(&buffer));
+        while let Some(newline) = pending.find('\n') {
+            let line: String = pending.drain(..=newline).collect();
+            if tx.send(line.trim_end().to_string()).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
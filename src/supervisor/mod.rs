@@ -1,25 +1,68 @@
 use anyhow::{Context, Result, bail};
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
 use std::sync::Arc;
 use tokio::process::Command;
-use tokio::sync::{Mutex, RwLock};
-use tokio::time::{Duration, interval};
-use tracing::{error, info, warn};
+use tokio::sync::{Mutex, RwLock, mpsc};
+use tokio::time::{Duration, Instant, interval};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::logging::LoggingConfig;
+use crate::supervision::{AGENT_ID_ENV, SUPERVISOR_URL_ENV, TOKEN_ENV};
+use crate::{Config, StateManager};
+
 pub mod api;
 pub mod async_client;
+pub mod auth;
+pub mod cgroup;
+pub mod checks;
 pub mod container_manager;
 pub mod daemon;
+pub mod decision_stream;
+pub mod egress;
+pub mod log_capture;
 pub mod log_stream;
 pub mod monitor;
+pub mod network;
+pub mod notifier;
+pub mod policy;
+pub mod proc_exec_poller;
+pub mod quota;
+pub mod scheduler;
+pub mod secrets;
 pub mod security;
+pub mod store;
+pub mod templates;
+pub mod yara;
 
-use monitor::{Alert, Monitor};
+use auth::{ApiToken, AuthConfig, find_token};
+use cgroup::{CgroupConfig, CgroupManager};
+use checks::SecurityCheck;
+use egress::{EgressPolicy, EgressProxy, EgressProxyConfig};
+use log_capture::{LogCaptureConfig, OutputCapture};
+use log_stream::LogStreamer;
+use monitor::{Alert, Event, EventType, Monitor};
+use network::{
+    ConnectionTermination, NetworkHistoryConfig, NetworkReport, NetworkSample, NetworkStats,
+    SampleResolution,
+};
+use notifier::{NotificationConfig, Notifier};
+use policy::{PolicyConfig, PolicyDecision, PolicyEngine};
+use quota::{QuotaAction, QuotaBreach, QuotaConfig, QuotaTracker, QuotaUsage};
+use scheduler::{ScheduleConfig, Scheduler};
+use secrets::SecretScanner;
+use security::{
+    CriticalAction, RiskLevel, ScannerConfig, SecurityReport, SecurityScanner, Whitelists,
+};
+use store::SupervisorStore;
+use templates::{TemplateLibrary, TemplateSummary};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SupervisorConfig {
     pub max_agents: usize,
     pub monitor_interval_secs: u64,
@@ -27,9 +70,66 @@ pub struct SupervisorConfig {
     pub enable_dashboard: bool,
     pub log_level: String,
     pub alerts: AlertConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default = "default_database_path")]
+    pub database_path: String,
+    #[serde(default)]
+    pub cgroups: CgroupConfig,
+    /// Directory holding configs and state of cloned and templated agents
+    #[serde(default = "default_agents_dir")]
+    pub agents_dir: String,
+    /// Extra agent templates, merged over the built-in ones
+    #[serde(default)]
+    pub templates_dir: Option<String>,
+    /// Directory whose agent configs may be spawned through the API. Agent
+    /// configs run arbitrary MCP server commands, so without it the API
+    /// spawns only templates and clones.
+    #[serde(default)]
+    pub spawn_configs_dir: Option<String>,
+    /// Sandbox agents spawned through the API run in. Requests may not
+    /// bring their own when it is set, so callers cannot loosen it.
+    #[serde(default)]
+    pub spawn_sandbox: Option<SandboxConfig>,
+    /// Also audit read-only (GET) API calls, not just operations
+    #[serde(default)]
+    pub audit_reads: bool,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+    /// Capture of process-spawned agents' stdout/stderr
+    #[serde(default)]
+    pub agent_logs: LogCaptureConfig,
+    /// Cumulative per-agent CPU, network and tool call limits
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+    /// API tokens and the roles they grant
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub security_scanner: ScannerConfig,
+    /// Per-agent proxy enforcing the sandbox's allowed domains
+    #[serde(default)]
+    pub egress_proxy: EgressProxyConfig,
+    /// Retention of the stored network stats time series
+    #[serde(default)]
+    pub network_history: NetworkHistoryConfig,
+    /// Output format, log file and per-module levels; the base level is
+    /// `log_level`
+    #[serde(default)]
+    pub logging: LoggingConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_database_path() -> String {
+    "supervisor.db".to_string()
+}
+
+fn default_agents_dir() -> String {
+    "agents".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AlertConfig {
     pub max_cpu_percent: f64,
     pub max_memory_mb: u64,
@@ -37,6 +137,16 @@ pub struct AlertConfig {
     pub suspicious_patterns: Vec<String>,
 }
 
+/// How long a stop-all confirmation token stays valid
+pub const EMERGENCY_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetStopReport {
+    pub incident_id: String,
+    pub stopped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentProcess {
     pub id: String,
@@ -47,12 +157,21 @@ pub struct AgentProcess {
     pub started_at: DateTime<Utc>,
     pub resource_usage: ResourceUsage,
     pub tool_usage: HashMap<String, u32>,
+    #[serde(default)]
+    pub cgroup_path: Option<PathBuf>,
+    /// Scheduled pool the agent was spawned for, if any
+    #[serde(default)]
+    pub pool: Option<String>,
+    /// File the agent's captured stdout/stderr is written to
+    #[serde(default)]
+    pub log_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AgentStatus {
     Starting,
     Running,
+    Paused,
     Stopped,
     Crashed,
     Quarantined,
@@ -66,7 +185,7 @@ pub struct ResourceUsage {
     pub network_bytes: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SandboxConfig {
     pub enabled: bool,
     pub mode: SandboxMode,
@@ -76,14 +195,14 @@ pub struct SandboxConfig {
     pub mcp: MCPRestrictions,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum SandboxMode {
     Strict,
     Moderate,
     Permissive,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FilesystemRestrictions {
     pub root: String,
     pub read_only_paths: Vec<String>,
@@ -91,22 +210,26 @@ pub struct FilesystemRestrictions {
     pub max_size_mb: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct NetworkRestrictions {
     pub mode: NetworkMode,
     pub allowed_domains: Vec<String>,
+    /// Addresses and networks that may be reached directly, e.g.
+    /// `172.20.0.0/16`
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
     pub blocked_ports: Vec<u16>,
     pub rate_limit_per_minute: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum NetworkMode {
     None,
     Filtered,
     Full,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ResourceLimits {
     pub max_memory_mb: u64,
     pub max_cpu_percent: f64,
@@ -114,28 +237,77 @@ pub struct ResourceLimits {
     pub max_open_files: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MCPRestrictions {
     pub allowed_servers: Vec<String>,
     pub blocked_tools: Vec<String>,
     pub tool_rate_limits: HashMap<String, u32>,
 }
 
+#[derive(Clone)]
 pub struct Supervisor {
     config: SupervisorConfig,
     agents: Arc<RwLock<HashMap<String, AgentProcess>>>,
     monitor: Arc<Monitor>,
+    policy: Arc<PolicyEngine>,
+    quotas: Arc<QuotaTracker>,
+    store: Arc<SupervisorStore>,
+    cgroups: Option<Arc<CgroupManager>>,
+    templates: Arc<TemplateLibrary>,
+    scheduler: Arc<Scheduler>,
+    scanner: Arc<SecurityScanner>,
+    egress: Arc<EgressProxy>,
+    /// Latest stats from each network monitor, keyed by monitor name
+    network_stats: Arc<RwLock<HashMap<String, NetworkStats>>>,
+    /// Connections each network monitor is to sever, sent with the answer
+    /// to its next report
+    network_terminations: Arc<RwLock<HashMap<String, Vec<ConnectionTermination>>>>,
+    /// Pending stop-all confirmation token and when it expires
+    emergency_token: Arc<Mutex<Option<(String, Instant)>>>,
+    /// Reporter tokens minted for the running agents, keyed by agent ID
+    agent_tokens: Arc<RwLock<HashMap<String, ApiToken>>>,
     running: Arc<Mutex<bool>>,
 }
 
 impl Supervisor {
     pub async fn new(config: SupervisorConfig) -> Result<Self> {
-        let monitor = Arc::new(Monitor::new());
+        let notifier = Notifier::new(config.notifications.clone())?;
+        let monitor = Arc::new(Monitor::new().with_notifier(notifier));
+        let policy = Arc::new(PolicyEngine::new(config.policy.clone()));
+        let quotas = Arc::new(QuotaTracker::new(config.quotas.clone()));
+        let store = Arc::new(SupervisorStore::new(&config.database_path).await?);
+        let cgroups = if config.cgroups.enabled && CgroupManager::is_available() {
+            Some(Arc::new(CgroupManager::new(&config.cgroups)))
+        } else {
+            None
+        };
+        let templates = Arc::new(TemplateLibrary::load(config.templates_dir.as_deref()).await?);
+        let scheduler = Arc::new(Scheduler::new(&config.schedules)?);
+        let scanner = Arc::new(
+            SecurityScanner::from_config(&config.security_scanner)
+                .await?
+                .with_notifier(Notifier::new(config.notifications.clone())?)
+                .with_store(store.clone()),
+        );
+        let egress =
+            Arc::new(EgressProxy::new(config.egress_proxy.clone()).with_store(store.clone()));
 
         Ok(Self {
             config,
             agents: Arc::new(RwLock::new(HashMap::new())),
             monitor,
+            policy,
+            quotas,
+            store,
+            cgroups,
+            templates,
+            scheduler,
+            scanner,
+            egress,
+            network_stats: Arc::new(RwLock::new(HashMap::new())),
+            network_terminations: Arc::new(RwLock::new(HashMap::new())),
+            emergency_token: Arc::new(Mutex::new(None)),
+            agent_tokens: Arc::new(RwLock::new(HashMap::new())),
             running: Arc::new(Mutex::new(false)),
         })
     }
@@ -153,6 +325,7 @@ impl Supervisor {
         let monitor = self.monitor.clone();
         let agents = self.agents.clone();
         let config = self.config.clone();
+        let cgroups = self.cgroups.clone();
 
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(config.monitor_interval_secs));
@@ -160,6 +333,26 @@ impl Supervisor {
             loop {
                 interval.tick().await;
 
+                // Refresh resource usage from cgroup accounting
+                if let Some(cgroups) = &cgroups {
+                    let mut agents_guard = agents.write().await;
+                    for (id, agent) in agents_guard.iter_mut() {
+                        if agent.cgroup_path.is_none() {
+                            continue;
+                        }
+
+                        match cgroups.read_usage(id).await {
+                            Ok(usage) => {
+                                if let Err(e) = monitor.record_metrics(id, usage.clone()).await {
+                                    error!("Failed to record metrics for agent {id}: {e}");
+                                }
+                                agent.resource_usage = usage;
+                            }
+                            Err(e) => debug!("Failed to read cgroup usage for agent {id}: {e}"),
+                        }
+                    }
+                }
+
                 // Check agent health
                 let agents_guard = agents.read().await;
                 for (id, agent) in agents_guard.iter() {
@@ -170,6 +363,80 @@ impl Supervisor {
             }
         });
 
+        // Start usage accounting and quota enforcement
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                interval(Duration::from_secs(supervisor.config.monitor_interval_secs));
+
+            loop {
+                interval.tick().await;
+                supervisor.enforce_quotas().await;
+            }
+        });
+
+        // Expire stored network samples past their retention
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(3600));
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = supervisor
+                    .store
+                    .prune_network_samples(&supervisor.config.network_history)
+                    .await
+                {
+                    error!("Failed to prune network samples: {e}");
+                }
+            }
+        });
+
+        // Start container security scans, persisting reports with findings
+        if self.config.security_scanner.enabled {
+            let (escalation, mut critical_reports) = mpsc::unbounded_channel();
+            let scanner = (*self.scanner).clone().with_escalation(escalation);
+
+            if self.config.security_scanner.yara.enabled {
+                let supervisor = self.clone();
+                let scanner = scanner.clone();
+                tokio::spawn(async move {
+                    supervisor.scan_workspaces(&scanner).await;
+                });
+            }
+
+            if self.config.security_scanner.secrets.enabled {
+                let supervisor = self.clone();
+                let scanner = scanner.clone();
+                tokio::spawn(async move {
+                    supervisor.scan_for_secrets(&scanner).await;
+                });
+            }
+
+            tokio::spawn(async move {
+                if let Err(e) = scanner.start_scanning().await {
+                    error!("Security scanner stopped: {e}");
+                }
+            });
+
+            let supervisor = self.clone();
+            tokio::spawn(async move {
+                while let Some(report) = critical_reports.recv().await {
+                    if let Err(e) = supervisor.respond_to_security_report(report).await {
+                        error!("Failed to respond to critical security report: {e}");
+                    }
+                }
+            });
+        }
+
+        // Start scheduled pool lifecycles
+        if !self.scheduler.is_empty() {
+            let supervisor = self.clone();
+            tokio::spawn(async move {
+                supervisor.scheduler.run(supervisor.clone()).await;
+            });
+        }
+
         // Start web dashboard if enabled
         if self.config.enable_dashboard
             && let Some(port) = self.config.web_port
@@ -185,8 +452,54 @@ impl Supervisor {
         config_path: String,
         sandbox_config: Option<SandboxConfig>,
     ) -> Result<String> {
-        let agent_id = format!("agent-{uuid}", uuid = Uuid::new_v4());
+        let agent_id = new_agent_id();
+        self.spawn_agent_with_id(agent_id, config_path, sandbox_config)
+            .await
+    }
+
+    /// Spawn an agent an API caller asked for: its config must lie in
+    /// `spawn_configs_dir`, and it runs in `spawn_sandbox` if one is set
+    pub async fn spawn_requested_agent(
+        &self,
+        config_path: &str,
+        sandbox_config: Option<SandboxConfig>,
+    ) -> Result<String> {
+        let Some(configs_dir) = &self.config.spawn_configs_dir else {
+            bail!(
+                "Spawning from a config path is disabled; set spawn_configs_dir or use a template"
+            );
+        };
+        let configs_dir = tokio::fs::canonicalize(configs_dir)
+            .await
+            .with_context(|| format!("Failed to resolve spawn_configs_dir {configs_dir}"))?;
+        let path = tokio::fs::canonicalize(config_path)
+            .await
+            .with_context(|| format!("Failed to resolve agent config {config_path}"))?;
+        if !path.starts_with(&configs_dir) {
+            bail!(
+                "Agent config {config_path} is outside {dir}",
+                dir = configs_dir.display()
+            );
+        }
+
+        let sandbox_config = match (&self.config.spawn_sandbox, sandbox_config) {
+            (Some(_), Some(_)) => {
+                bail!("Agents spawned through the API run in the supervisor's spawn_sandbox")
+            }
+            (Some(sandbox), None) => Some(sandbox.clone()),
+            (None, requested) => requested,
+        };
+
+        self.spawn_agent(path.to_string_lossy().into_owned(), sandbox_config)
+            .await
+    }
 
+    async fn spawn_agent_with_id(
+        &self,
+        agent_id: String,
+        config_path: String,
+        sandbox_config: Option<SandboxConfig>,
+    ) -> Result<String> {
         info!("Spawning agent {agent_id} with config: {config_path}");
 
         // Check max agents limit
@@ -209,18 +522,109 @@ impl Supervisor {
 
         cmd.arg("--config").arg(&config_path);
 
+        // Tell the agent where to report its tool calls
+        if self.config.enable_dashboard
+            && let Some(port) = self.config.web_port
+        {
+            cmd.env(SUPERVISOR_URL_ENV, format!("http://127.0.0.1:{port}"))
+                .env(AGENT_ID_ENV, &agent_id);
+        }
+        // Agents authenticate with a reporter token of their own, never
+        // with the token of whoever started the supervisor
+        cmd.env_remove(TOKEN_ENV);
+        if self.config.auth.enabled {
+            cmd.env(TOKEN_ENV, self.agent_token(&agent_id).await);
+        }
+
         if let Some(ref sandbox) = sandbox_config {
             // Add sandbox arguments
             if sandbox.enabled {
                 cmd.env("SANDBOX_MODE", format!("{:?}", sandbox.mode));
                 cmd.env("SANDBOX_ROOT", &sandbox.filesystem.root);
             }
+
+            // Send all outbound traffic through a proxy enforcing the allowlist
+            if sandbox.enabled
+                && self.egress.is_enabled()
+                && !matches!(sandbox.network.mode, NetworkMode::Full)
+            {
+                let proxy = self
+                    .egress
+                    .register(&agent_id, EgressPolicy::from_restrictions(&sandbox.network))
+                    .await?;
+                for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+                    cmd.env(var, format!("http://{proxy}"));
+                }
+                for var in ["ALL_PROXY", "all_proxy"] {
+                    cmd.env(var, format!("socks5h://{proxy}"));
+                }
+                cmd.env_remove("NO_PROXY").env_remove("no_proxy");
+            }
+        }
+
+        // Enforce resource limits through a dedicated cgroup, which the agent
+        // joins before it execs so it never runs outside its limits
+        let mut cgroup_path = None;
+        if let (Some(cgroups), Some(sandbox)) = (&self.cgroups, &sandbox_config)
+            && sandbox.enabled
+        {
+            let result = async {
+                let path = cgroups.create(&agent_id, &sandbox.resources).await?;
+                CgroupManager::join_on_exec(&path, &mut cmd)?;
+                Ok::<_, anyhow::Error>(path)
+            }
+            .await;
+
+            match result {
+                Ok(path) => cgroup_path = Some(path),
+                Err(e) if matches!(sandbox.mode, SandboxMode::Strict) => {
+                    // Strict sandboxes must not run without enforced limits
+                    if let Err(remove_err) = cgroups.remove(&agent_id).await {
+                        debug!("Failed to remove cgroup of agent {agent_id}: {remove_err}");
+                    }
+                    self.egress.unregister(&agent_id).await;
+                    self.agent_tokens.write().await.remove(&agent_id);
+                    return Err(e.context(format!(
+                        "Failed to apply resource limits for agent {agent_id}"
+                    )));
+                }
+                Err(e) => warn!("Resource limits not enforced for agent {agent_id}: {e}"),
+            }
         }
 
+        // Capture output into a rotating log file
+        let capture = if self.config.agent_logs.enabled {
+            match OutputCapture::open(&agent_id, &self.config.agent_logs).await {
+                Ok(capture) => {
+                    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+                    Some(capture)
+                }
+                Err(e) => {
+                    warn!("Output of agent {agent_id} will not be captured: {e}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         // Spawn the process
-        let child = cmd.spawn().context("Failed to spawn agent process")?;
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                if let (Some(cgroups), Some(_)) = (&self.cgroups, &cgroup_path)
+                    && let Err(remove_err) = cgroups.remove(&agent_id).await
+                {
+                    debug!("Failed to remove cgroup of agent {agent_id}: {remove_err}");
+                }
+                self.egress.unregister(&agent_id).await;
+                self.agent_tokens.write().await.remove(&agent_id);
+                return Err(e).context("Failed to spawn agent process");
+            }
+        };
 
         let pid = child.id();
+        let log_path = capture.map(|capture| capture.attach(&mut child));
 
         // Create agent process entry
         let agent_process = AgentProcess {
@@ -232,6 +636,9 @@ impl Supervisor {
             started_at: Utc::now(),
             resource_usage: ResourceUsage::default(),
             tool_usage: HashMap::new(),
+            cgroup_path,
+            pool: None,
+            log_path,
         };
 
         // Store agent
@@ -246,6 +653,182 @@ impl Supervisor {
         Ok(agent_id)
     }
 
+    /// Spawn a copy of an existing agent: same config and sandbox, with a
+    /// snapshot of its state database so the clone starts from what it learned
+    pub async fn clone_agent(&self, source_id: &str) -> Result<String> {
+        let source = self
+            .get_agent_details(source_id)
+            .await
+            .with_context(|| format!("Agent {source_id} not found"))?;
+
+        let mut config = read_agent_config(&source.config_path).await?;
+
+        let agent_id = new_agent_id();
+        let agent_dir = self.create_agent_dir(&agent_id).await?;
+        let database_path = agent_dir.join("state.db").to_string_lossy().into_owned();
+
+        info!("Cloning agent {source_id} into {agent_id}");
+
+        StateManager::open_read_only(&config.database_path)
+            .await?
+            .snapshot_to(&database_path)
+            .await?;
+
+        config.agent.id = Some(agent_id.clone());
+        config.database_path = database_path;
+        let config_path = write_agent_config(&agent_dir, &config).await?;
+
+        self.spawn_agent_with_id(agent_id, config_path, source.sandbox_config)
+            .await
+    }
+
+    pub fn list_templates(&self) -> Vec<TemplateSummary> {
+        self.templates.list()
+    }
+
+    /// Spawn a fresh agent from a named template in the template library
+    pub async fn spawn_from_template(&self, template_name: &str) -> Result<String> {
+        let Some(template) = self.templates.get(template_name) else {
+            bail!("Template {template_name} not found");
+        };
+
+        let agent_id = new_agent_id();
+        let agent_dir = self.create_agent_dir(&agent_id).await?;
+        let database_path = agent_dir.join("state.db").to_string_lossy().into_owned();
+
+        info!("Spawning agent {agent_id} from template {template_name}");
+
+        let config = template.to_agent_config(&agent_id, &database_path);
+        let config_path = write_agent_config(&agent_dir, &config).await?;
+
+        self.spawn_agent_with_id(agent_id, config_path, template.sandbox.clone())
+            .await
+    }
+
+    /// Apply a fired schedule: scale its pool, or start or stop its agent
+    pub async fn run_schedule(&self, schedule: &ScheduleConfig) -> Result<()> {
+        match (&schedule.pool, &schedule.agent) {
+            (Some(pool), _) => self.scale_pool(pool, schedule).await,
+            (None, Some(agent_id)) => self.schedule_agent(agent_id, schedule).await,
+            (None, None) => bail!(
+                "Schedule '{cron}' names no pool or agent",
+                cron = schedule.cron
+            ),
+        }
+    }
+
+    /// Bring a scheduled pool to the schedule's replica count, spawning new
+    /// agents or stopping the most recently started ones
+    async fn scale_pool(&self, pool: &str, schedule: &ScheduleConfig) -> Result<()> {
+        let mut members: Vec<(String, DateTime<Utc>)> = self
+            .agents
+            .read()
+            .await
+            .values()
+            .filter(|agent| agent.pool.as_deref() == Some(pool))
+            .filter(|agent| {
+                matches!(
+                    agent.status,
+                    AgentStatus::Starting | AgentStatus::Running | AgentStatus::Paused
+                )
+            })
+            .map(|agent| (agent.id.clone(), agent.started_at))
+            .collect();
+        members.sort_by_key(|(_, started_at)| *started_at);
+
+        if members.len() > schedule.replicas {
+            for (agent_id, _) in &members[schedule.replicas..] {
+                info!("Stopping agent {agent_id} from pool {pool}");
+                self.stop_agent(agent_id).await?;
+            }
+            return Ok(());
+        }
+
+        for _ in members.len()..schedule.replicas {
+            let agent_id = self.spawn_scheduled(new_agent_id(), schedule).await?;
+
+            if let Some(agent) = self.agents.write().await.get_mut(&agent_id) {
+                agent.pool = Some(pool.to_string());
+            }
+            info!("Spawned agent {agent_id} for pool {pool}");
+        }
+
+        Ok(())
+    }
+
+    /// Start a scheduled agent that isn't running, or stop one that is. A
+    /// quarantined agent stays as it is until an operator decides.
+    async fn schedule_agent(&self, agent_id: &str, schedule: &ScheduleConfig) -> Result<()> {
+        let status = self
+            .agents
+            .read()
+            .await
+            .get(agent_id)
+            .map(|agent| agent.status.clone());
+
+        match status {
+            Some(AgentStatus::Starting | AgentStatus::Running | AgentStatus::Paused)
+                if schedule.replicas == 0 =>
+            {
+                info!("Stopping scheduled agent {agent_id}");
+                self.stop_agent(agent_id).await
+            }
+            None | Some(AgentStatus::Stopped | AgentStatus::Crashed) if schedule.replicas > 0 => {
+                self.spawn_scheduled(agent_id.to_string(), schedule).await?;
+                info!("Started scheduled agent {agent_id}");
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Spawn an agent for a schedule from its template, or from a copy of
+    /// its config written to the agent's own directory, so agents sharing
+    /// a config never share an id or a state database
+    async fn spawn_scheduled(&self, agent_id: String, schedule: &ScheduleConfig) -> Result<String> {
+        let agent_dir = self.create_agent_dir(&agent_id).await?;
+        let database_path = agent_dir.join("state.db").to_string_lossy().into_owned();
+
+        let (config, sandbox_config) = match (&schedule.template, &schedule.config_path) {
+            (Some(template_name), _) => {
+                let Some(template) = self.templates.get(template_name) else {
+                    bail!("Template {template_name} not found");
+                };
+                (
+                    template.to_agent_config(&agent_id, &database_path),
+                    template.sandbox.clone(),
+                )
+            }
+            (None, Some(config_path)) => {
+                let mut config = read_agent_config(config_path).await?;
+                config.agent.id = Some(agent_id.clone());
+                config.database_path = database_path;
+                (config, schedule.sandbox.clone())
+            }
+            (None, None) => bail!(
+                "Schedule for {target} has no template or config_path to spawn from",
+                target = schedule.target()
+            ),
+        };
+
+        let config_path = write_agent_config(&agent_dir, &config).await?;
+        self.spawn_agent_with_id(agent_id, config_path, sandbox_config)
+            .await
+    }
+
+    async fn create_agent_dir(&self, agent_id: &str) -> Result<PathBuf> {
+        let agent_dir = std::path::Path::new(&self.config.agents_dir).join(agent_id);
+        tokio::fs::create_dir_all(&agent_dir)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to create agent directory {dir}",
+                    dir = agent_dir.display()
+                )
+            })?;
+        Ok(agent_dir)
+    }
+
     pub async fn stop_agent(&self, agent_id: &str) -> Result<()> {
         info!("Stopping agent {agent_id}");
 
@@ -264,6 +847,8 @@ impl Supervisor {
 
             // Stop monitoring
             self.monitor.stop_monitoring(agent_id).await?;
+            self.policy.reset_agent(agent_id).await;
+            self.release_agent_resources(agent).await;
 
             Ok(())
         } else {
@@ -274,49 +859,411 @@ impl Supervisor {
     pub async fn emergency_stop(&self, agent_id: &str) -> Result<()> {
         warn!("Emergency stop requested for agent {agent_id}");
 
+        self.kill_agent(agent_id).await?;
+
+        // Generate incident report
+        self.record_incident(agent_id, "emergency_stop").await?;
+
+        Ok(())
+    }
+
+    async fn kill_agent(&self, agent_id: &str) -> Result<()> {
         let mut agents = self.agents.write().await;
 
-        if let Some(agent) = agents.get_mut(agent_id) {
-            agent.status = AgentStatus::Stopped;
+        let Some(agent) = agents.get_mut(agent_id) else {
+            bail!("Agent {agent_id} not found");
+        };
 
-            // Send SIGKILL for immediate termination
-            if let Some(pid) = agent.pid {
+        agent.status = AgentStatus::Stopped;
+
+        // Send SIGKILL for immediate termination
+        match agent.pid {
+            Some(pid) => {
                 std::process::Command::new("kill")
                     .arg("-KILL")
                     .arg(pid.to_string())
                     .output()?;
             }
+            // Agents without a local process run in a container of the same name
+            None => {
+                std::process::Command::new("docker")
+                    .arg("kill")
+                    .arg(agent_id)
+                    .output()?;
+            }
+        }
 
-            // Generate incident report
-            self.monitor.generate_incident_report(agent_id).await?;
+        self.release_agent_resources(agent).await;
 
-            Ok(())
-        } else {
-            bail!("Agent {agent_id} not found")
+        Ok(())
+    }
+
+    /// Issue a single-use token that must be presented to confirm a stop-all
+    pub async fn issue_emergency_token(&self) -> String {
+        let token = Uuid::new_v4().simple().to_string();
+        *self.emergency_token.lock().await =
+            Some((token.clone(), Instant::now() + EMERGENCY_TOKEN_TTL));
+        token
+    }
+
+    /// Check and consume a stop-all confirmation token
+    pub async fn confirm_emergency_token(&self, token: &str) -> bool {
+        let mut pending = self.emergency_token.lock().await;
+        match pending.take() {
+            Some((expected, expires_at)) if expected == token && Instant::now() < expires_at => {
+                true
+            }
+            // A wrong guess burns the token so it cannot be brute-forced
+            _ => false,
         }
     }
 
-    pub async fn quarantine_agent(&self, agent_id: &str) -> Result<()> {
-        warn!("Quarantining agent {agent_id}");
+    /// Kill every managed agent immediately and record a fleet-wide incident
+    pub async fn emergency_stop_all(&self) -> Result<FleetStopReport> {
+        warn!("Emergency stop requested for all agents");
+
+        let mut stopped = Vec::new();
+        let mut failed = Vec::new();
+
+        {
+            let mut agents = self.agents.write().await;
+
+            for agent in agents.values_mut() {
+                if matches!(agent.status, AgentStatus::Stopped) {
+                    continue;
+                }
+
+                let result = match agent.pid {
+                    Some(pid) => std::process::Command::new("kill")
+                        .arg("-KILL")
+                        .arg(pid.to_string())
+                        .output(),
+                    // Agents without a local process run in a container of the same name
+                    None => std::process::Command::new("docker")
+                        .arg("kill")
+                        .arg(&agent.id)
+                        .output(),
+                };
+
+                match result {
+                    Ok(output) if output.status.success() => {
+                        agent.status = AgentStatus::Stopped;
+                        self.release_agent_resources(agent).await;
+                        stopped.push(agent.id.clone());
+                    }
+                    Ok(output) => {
+                        error!(
+                            "Failed to kill agent {id}: {stderr}",
+                            id = agent.id,
+                            stderr = String::from_utf8_lossy(&output.stderr).trim()
+                        );
+                        failed.push(agent.id.clone());
+                    }
+                    Err(e) => {
+                        error!("Failed to kill agent {id}: {e}", id = agent.id);
+                        failed.push(agent.id.clone());
+                    }
+                }
+            }
+        }
+
+        let report = self
+            .monitor
+            .generate_fleet_report("emergency_stop_all")
+            .await?;
+        self.store.save_incident(&report).await?;
+
+        warn!(
+            "Emergency stop-all killed {stopped} agents ({failed} failed), incident {id}",
+            stopped = stopped.len(),
+            failed = failed.len(),
+            id = report.id
+        );
+
+        Ok(FleetStopReport {
+            incident_id: report.id,
+            stopped,
+            failed,
+        })
+    }
+
+    /// The reporter token of the agent `agent_id`, minted on first use. It
+    /// may only report the agent's own tool calls.
+    pub async fn agent_token(&self, agent_id: &str) -> String {
+        self.agent_tokens
+            .write()
+            .await
+            .entry(agent_id.to_string())
+            .or_insert_with(|| ApiToken::for_agent(agent_id))
+            .token
+            .clone()
+    }
+
+    /// Who an API token belongs to: a configured token or one minted for
+    /// an agent
+    pub async fn identify(&self, token: &str) -> Option<ApiToken> {
+        if let Some(identity) = self.config.auth.identify(token) {
+            return Some(identity.clone());
+        }
+        find_token(self.agent_tokens.read().await.values(), token).cloned()
+    }
+
+    /// Free what a stopped agent held: its egress proxy, API token and cgroup
+    async fn release_agent_resources(&self, agent: &mut AgentProcess) {
+        self.egress.unregister(&agent.id).await;
+        self.agent_tokens.write().await.remove(&agent.id);
+
+        if let (Some(cgroups), Some(_)) = (&self.cgroups, &agent.cgroup_path) {
+            // Removal fails while the process is still exiting; the empty
+            // cgroup is then left behind and reused if the ID ever returns
+            match cgroups.remove(&agent.id).await {
+                Ok(()) => agent.cgroup_path = None,
+                Err(e) => debug!("Cgroup for agent {id} not removed yet: {e}", id = agent.id),
+            }
+        }
+    }
+
+    /// Build an incident report for an agent and persist it in the supervisor database.
+    /// Decisions and logs are best-effort: an unreachable agent still gets a report.
+    pub async fn record_incident(&self, agent_id: &str, trigger: &str) -> Result<String> {
+        self.record_incident_with(agent_id, trigger, None).await
+    }
+
+    async fn record_incident_with(
+        &self,
+        agent_id: &str,
+        trigger: &str,
+        security_report: Option<SecurityReport>,
+    ) -> Result<String> {
+        let mut report = self
+            .monitor
+            .generate_incident_report(agent_id, trigger)
+            .await?;
+        report.security_report = security_report;
+
+        match self.open_agent_state(agent_id).await {
+            Ok(state) => match state.get_recent_decisions_structured(20).await {
+                Ok(decisions) => report.recent_decisions = decisions,
+                Err(e) => warn!("Failed to read decisions for incident on {agent_id}: {e}"),
+            },
+            Err(e) => warn!("Failed to open state for incident on {agent_id}: {e}"),
+        }
+
+        match self.log_streamer(agent_id).await.get_recent_logs(50).await {
+            Ok(logs) => report.logs_excerpt = logs,
+            Err(e) => debug!("No logs available for incident on {agent_id}: {e}"),
+        }
+
+        self.store.save_incident(&report).await?;
+        info!(
+            "Recorded incident {id} for agent {agent_id}",
+            id = report.id
+        );
+
+        Ok(report.id)
+    }
+
+    /// Temporarily freeze an agent, e.g. while investigating its behaviour.
+    /// Unlike quarantine this raises no alert and is expected to be resumed.
+    pub async fn pause_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Pausing agent {agent_id}");
 
         let mut agents = self.agents.write().await;
 
-        if let Some(agent) = agents.get_mut(agent_id) {
-            agent.status = AgentStatus::Quarantined;
+        let Some(agent) = agents.get_mut(agent_id) else {
+            bail!("Agent {agent_id} not found");
+        };
 
-            // Send SIGSTOP to freeze the process
-            if let Some(pid) = agent.pid {
+        if !matches!(agent.status, AgentStatus::Starting | AgentStatus::Running) {
+            bail!(
+                "Agent {agent_id} cannot be paused while {status:?}",
+                status = agent.status
+            );
+        }
+
+        // Send SIGSTOP to freeze the process
+        if let Some(pid) = agent.pid {
+            std::process::Command::new("kill")
+                .arg("-STOP")
+                .arg(pid.to_string())
+                .output()?;
+        }
+
+        agent.status = AgentStatus::Paused;
+
+        self.monitor
+            .log_event(Event {
+                timestamp: Utc::now(),
+                agent_id: agent_id.to_string(),
+                event_type: EventType::AgentPaused,
+                details: serde_json::json!({}),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    pub async fn resume_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Resuming agent {agent_id}");
+
+        let mut agents = self.agents.write().await;
+
+        let Some(agent) = agents.get_mut(agent_id) else {
+            bail!("Agent {agent_id} not found");
+        };
+
+        if !matches!(agent.status, AgentStatus::Paused) {
+            bail!(
+                "Agent {agent_id} is not paused (status: {status:?})",
+                status = agent.status
+            );
+        }
+
+        // Send SIGCONT to continue the process
+        if let Some(pid) = agent.pid {
+            std::process::Command::new("kill")
+                .arg("-CONT")
+                .arg(pid.to_string())
+                .output()?;
+        }
+
+        agent.status = AgentStatus::Running;
+
+        self.monitor
+            .log_event(Event {
+                timestamp: Utc::now(),
+                agent_id: agent_id.to_string(),
+                event_type: EventType::AgentResumed,
+                details: serde_json::json!({}),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    /// Ask a running agent to re-read its config file. The agent applies the
+    /// settings that can change without a restart and ignores the rest.
+    pub async fn reload_agent_config(&self, agent_id: &str) -> Result<()> {
+        info!("Reloading config of agent {agent_id}");
+
+        let agents = self.agents.read().await;
+
+        let Some(agent) = agents.get(agent_id) else {
+            bail!("Agent {agent_id} not found");
+        };
+
+        if !matches!(agent.status, AgentStatus::Starting | AgentStatus::Running) {
+            bail!(
+                "Agent {agent_id} cannot reload its config while {status:?}",
+                status = agent.status
+            );
+        }
+
+        // The agent reloads its config on SIGHUP
+        let Some(pid) = agent.pid else {
+            bail!("Agent {agent_id} has no process to signal");
+        };
+        std::process::Command::new("kill")
+            .arg("-HUP")
+            .arg(pid.to_string())
+            .output()?;
+        drop(agents);
+
+        self.monitor
+            .log_event(Event {
+                timestamp: Utc::now(),
+                agent_id: agent_id.to_string(),
+                event_type: EventType::AgentConfigReloaded,
+                details: serde_json::json!({}),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    /// Release a quarantined agent after an operator has reviewed it.
+    /// Policy violations are forgotten so the agent starts with a clean slate.
+    pub async fn approve_agent(&self, agent_id: &str) -> Result<()> {
+        info!("Approving quarantined agent {agent_id}");
+
+        let mut agents = self.agents.write().await;
+
+        let Some(agent) = agents.get_mut(agent_id) else {
+            bail!("Agent {agent_id} not found");
+        };
+
+        if !matches!(agent.status, AgentStatus::Quarantined) {
+            bail!(
+                "Agent {agent_id} is not quarantined (status: {status:?})",
+                status = agent.status
+            );
+        }
+
+        // Send SIGCONT to continue the process, or unpause its container
+        match agent.pid {
+            Some(pid) => {
                 std::process::Command::new("kill")
-                    .arg("-STOP")
+                    .arg("-CONT")
                     .arg(pid.to_string())
                     .output()?;
             }
+            None => {
+                std::process::Command::new("docker")
+                    .arg("unpause")
+                    .arg(agent_id)
+                    .output()?;
+            }
+        }
+
+        agent.status = AgentStatus::Running;
+        self.policy.reset_agent(agent_id).await;
+
+        self.monitor
+            .log_event(Event {
+                timestamp: Utc::now(),
+                agent_id: agent_id.to_string(),
+                event_type: EventType::AgentApproved,
+                details: serde_json::json!({}),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    pub async fn quarantine_agent(&self, agent_id: &str) -> Result<()> {
+        self.quarantine_with_reason(agent_id, "Manual quarantine")
+            .await
+    }
+
+    async fn quarantine_with_reason(&self, agent_id: &str, reason: &str) -> Result<()> {
+        warn!("Quarantining agent {agent_id}: {reason}");
+
+        let mut agents = self.agents.write().await;
+
+        if let Some(agent) = agents.get_mut(agent_id) {
+            agent.status = AgentStatus::Quarantined;
+
+            // Send SIGSTOP to freeze the process, or pause its container
+            match agent.pid {
+                Some(pid) => {
+                    std::process::Command::new("kill")
+                        .arg("-STOP")
+                        .arg(pid.to_string())
+                        .output()?;
+                }
+                None => {
+                    std::process::Command::new("docker")
+                        .arg("pause")
+                        .arg(agent_id)
+                        .output()?;
+                }
+            }
 
             // Alert monitoring system
             self.monitor
                 .alert(Alert::AgentQuarantined {
                     agent_id: agent_id.to_string(),
-                    reason: "Manual quarantine".to_string(),
+                    reason: reason.to_string(),
                 })
                 .await?;
 
@@ -326,6 +1273,435 @@ impl Supervisor {
         }
     }
 
+    /// Periodically run the YARA rules over each sandboxed agent's filesystem root
+    async fn scan_workspaces(&self, scanner: &SecurityScanner) {
+        let yara = &self.config.security_scanner.yara;
+        info!(
+            "Scanning agent workspaces with YARA rules from {rules_file}",
+            rules_file = yara.rules_file
+        );
+
+        // A zero interval would make tokio panic
+        let mut interval = interval(Duration::from_secs(yara.scan_interval_secs.max(1)));
+
+        loop {
+            interval.tick().await;
+
+            let workspaces: Vec<(String, PathBuf)> = self
+                .agents
+                .read()
+                .await
+                .values()
+                .filter(|agent| !matches!(agent.status, AgentStatus::Stopped))
+                .filter_map(|agent| {
+                    let sandbox = agent.sandbox_config.as_ref()?;
+                    Some((agent.id.clone(), PathBuf::from(&sandbox.filesystem.root)))
+                })
+                .collect();
+
+            for (agent_id, workspace) in workspaces {
+                if !workspace.is_dir() {
+                    debug!(
+                        "Workspace {workspace} of agent {agent_id} is not on this host",
+                        workspace = workspace.display()
+                    );
+                    continue;
+                }
+
+                if let Err(e) = scanner.scan_workspace(&agent_id, &workspace).await {
+                    error!("YARA scan failed for agent {agent_id}: {e}");
+                }
+            }
+        }
+    }
+
+    /// Periodically look for credentials in every active agent's memory and logs
+    async fn scan_for_secrets(&self, scanner: &SecurityScanner) {
+        let secrets = SecretScanner::new(self.config.security_scanner.secrets.clone());
+        info!("Scanning agent memory and logs for secrets");
+
+        let mut interval = interval(Duration::from_secs(
+            secrets.config().scan_interval_secs.max(1),
+        ));
+
+        loop {
+            interval.tick().await;
+
+            let agent_ids: Vec<String> = self
+                .agents
+                .read()
+                .await
+                .values()
+                .filter(|agent| !matches!(agent.status, AgentStatus::Stopped))
+                .map(|agent| agent.id.clone())
+                .collect();
+
+            for agent_id in agent_ids {
+                let mut findings = Vec::new();
+
+                let state = match self.agent_database_path(&agent_id).await {
+                    // Redaction writes back to the agent's database
+                    Ok(path) if secrets.config().redact_memory => StateManager::new(&path).await,
+                    Ok(path) => StateManager::open_read_only(&path).await,
+                    Err(e) => Err(e),
+                };
+                match state {
+                    Ok(state) => match secrets.scan_memory(&agent_id, &state).await {
+                        Ok(found) => findings.extend(found),
+                        Err(e) => error!("Secret scan of memory failed for agent {agent_id}: {e}"),
+                    },
+                    Err(e) => debug!("No memory to scan for agent {agent_id}: {e}"),
+                }
+
+                let log_lines = secrets.config().log_lines;
+                match self
+                    .log_streamer(&agent_id)
+                    .await
+                    .get_recent_logs(log_lines)
+                    .await
+                {
+                    Ok(lines) => match secrets.scan_logs(&agent_id, &lines).await {
+                        Ok(found) => findings.extend(found),
+                        Err(e) => error!("Secret scan of logs failed for agent {agent_id}: {e}"),
+                    },
+                    Err(e) => debug!("No logs to scan for agent {agent_id}: {e}"),
+                }
+
+                if let Err(e) = scanner
+                    .report_findings(&agent_id, findings, RiskLevel::High)
+                    .await
+                {
+                    error!("Failed to report secrets found for agent {agent_id}: {e}");
+                }
+            }
+        }
+    }
+
+    /// Contain the agent behind a critical security report as configured by
+    /// `on_critical`, recording an incident with the report attached.
+    /// Returns the incident ID, or None if no action was taken.
+    pub async fn respond_to_security_report(
+        &self,
+        report: SecurityReport,
+    ) -> Result<Option<String>> {
+        if report.risk_level != RiskLevel::Critical {
+            return Ok(None);
+        }
+
+        // Agents run in containers named after their ID
+        let agent_id = report.container_id.clone();
+        let Some(agent) = self.get_agent_details(&agent_id).await else {
+            warn!("Critical security findings in unmanaged container {agent_id}");
+            return Ok(None);
+        };
+
+        // Already contained by an earlier report
+        if matches!(
+            agent.status,
+            AgentStatus::Quarantined | AgentStatus::Stopped
+        ) {
+            return Ok(None);
+        }
+
+        let trigger = match self.config.security_scanner.on_critical {
+            CriticalAction::Alert => return Ok(None),
+            CriticalAction::Quarantine => {
+                self.quarantine_with_reason(&agent_id, "Critical security findings")
+                    .await?;
+                "security_quarantine"
+            }
+            CriticalAction::EmergencyStop => {
+                warn!("Emergency stop of agent {agent_id} on critical security findings");
+                self.kill_agent(&agent_id).await?;
+                "security_emergency_stop"
+            }
+        };
+
+        let incident_id = self
+            .record_incident_with(&agent_id, trigger, Some(report))
+            .await?;
+
+        Ok(Some(incident_id))
+    }
+
+    /// Add the latest resource usage of every active agent to its quota totals,
+    /// act on new breaches and resume throttled agents whose period has ended
+    pub async fn enforce_quotas(&self) {
+        let now = Utc::now();
+
+        for agent_id in self.quotas.roll_over(now).await {
+            let paused = self
+                .get_agent_details(&agent_id)
+                .await
+                .is_some_and(|agent| matches!(agent.status, AgentStatus::Paused));
+            if !paused {
+                continue;
+            }
+
+            info!("Quota period ended, resuming throttled agent {agent_id}");
+            if let Err(e) = self.resume_agent(&agent_id).await {
+                error!("Failed to resume throttled agent {agent_id}: {e}");
+            }
+        }
+
+        let samples: Vec<(String, ResourceUsage)> = {
+            let mut agents = self.agents.write().await;
+            let mut samples = Vec::new();
+
+            for agent in agents.values_mut() {
+                if !matches!(agent.status, AgentStatus::Starting | AgentStatus::Running) {
+                    continue;
+                }
+
+                if let Some(pid) = agent.pid
+                    && let Some(bytes) = quota::read_process_network_bytes(pid).await
+                {
+                    agent.resource_usage.network_bytes = bytes;
+                }
+
+                samples.push((agent.id.clone(), agent.resource_usage.clone()));
+            }
+
+            samples
+        };
+
+        for (agent_id, usage) in samples {
+            if let Some(breach) = self.quotas.record_usage(&agent_id, &usage, now).await
+                && let Err(e) = self.apply_quota_action(&agent_id, &breach).await
+            {
+                error!("Failed to enforce quota on agent {agent_id}: {e}");
+            }
+        }
+    }
+
+    async fn apply_quota_action(&self, agent_id: &str, breach: &QuotaBreach) -> Result<()> {
+        warn!("Agent {agent_id}: {breach}");
+
+        self.monitor
+            .alert(Alert::QuotaExceeded {
+                agent_id: agent_id.to_string(),
+                resource: breach.resource.to_string(),
+                used: breach.used,
+                limit: breach.limit,
+            })
+            .await?;
+
+        match self.config.quotas.action {
+            QuotaAction::Throttle => {
+                self.pause_agent(agent_id).await?;
+                self.quotas.mark_throttled(agent_id).await;
+            }
+            QuotaAction::Stop => self.stop_agent(agent_id).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Usage counted against an agent's quotas in the current period
+    pub async fn quota_usage(&self, agent_id: &str) -> Option<QuotaUsage> {
+        self.quotas.usage(agent_id).await
+    }
+
+    pub fn security_whitelists(&self) -> Whitelists {
+        self.scanner.whitelists()
+    }
+
+    /// Re-read the security scanner's whitelists file without a restart
+    pub async fn reload_security_whitelists(&self) -> Result<Whitelists> {
+        self.scanner.reload_whitelists().await
+    }
+
+    /// Keep a network monitor's latest stats and raise its alerts as
+    /// network anomalies, of the agent whose container made the connection.
+    /// Returns the connections that monitor is to sever.
+    pub async fn record_network_report(
+        &self,
+        report: NetworkReport,
+    ) -> Result<Vec<ConnectionTermination>> {
+        for alert in &report.alerts {
+            let destination = alert
+                .connection
+                .as_ref()
+                .map(|connection| connection.remote_addr.clone())
+                .unwrap_or_else(|| alert.details.clone());
+            // Connections seen in a container belong to the agent running there
+            let agent_id = alert
+                .container
+                .clone()
+                .or_else(|| {
+                    alert
+                        .connection
+                        .as_ref()
+                        .and_then(|connection| connection.container.clone())
+                })
+                .unwrap_or_else(|| report.monitor.clone());
+
+            self.monitor
+                .alert(Alert::NetworkAnomaly {
+                    agent_id,
+                    destination,
+                    capture: alert.capture.clone(),
+                })
+                .await?;
+        }
+
+        // Store the traffic since this monitor's previous report
+        let previous = self
+            .network_stats
+            .write()
+            .await
+            .insert(report.monitor.clone(), report.stats.clone());
+        if let Some(previous) = previous {
+            let samples = network::samples_between(&report.monitor, &previous, &report.stats);
+            self.store.save_network_samples(&samples).await?;
+        }
+
+        Ok(self
+            .network_terminations
+            .write()
+            .await
+            .remove(&report.monitor)
+            .unwrap_or_default())
+    }
+
+    /// Have a network monitor sever a connection, without stopping the agent
+    /// that holds it. Without `monitor`, it goes to the monitors whose latest
+    /// report lists the connection. Returns the monitors it will be sent to,
+    /// none if no monitor by that name has reported.
+    pub async fn terminate_connection(
+        &self,
+        monitor: Option<String>,
+        termination: ConnectionTermination,
+    ) -> Vec<String> {
+        let monitors: Vec<String> = self
+            .network_stats
+            .read()
+            .await
+            .iter()
+            .filter(|(name, stats)| match &monitor {
+                Some(monitor) => *name == monitor,
+                None => stats
+                    .connections
+                    .iter()
+                    .any(|connection| termination.matches(connection)),
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let mut pending = self.network_terminations.write().await;
+        for monitor in &monitors {
+            let queue = pending.entry(monitor.clone()).or_default();
+            if !queue.contains(&termination) {
+                queue.push(termination.clone());
+            }
+        }
+
+        if !monitors.is_empty() {
+            warn!(
+                "Terminating connection of {owner} to {remote_addr} via {monitors}",
+                owner = termination.container.as_deref().unwrap_or("host"),
+                remote_addr = termination.remote_addr,
+                monitors = monitors.join(", ")
+            );
+        }
+
+        monitors
+    }
+
+    /// Stored network samples, oldest first. `source` is an agent's
+    /// container name or a monitor's name; unset returns every source.
+    pub async fn network_history(
+        &self,
+        source: Option<String>,
+        resolution: SampleResolution,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<NetworkSample>> {
+        self.store
+            .list_network_samples(source, resolution, since, until, limit)
+            .await
+    }
+
+    /// Latest stats reported by each network monitor
+    pub async fn network_stats(&self) -> HashMap<String, NetworkStats> {
+        self.network_stats.read().await.clone()
+    }
+
+    /// Add a deployment-specific check to the container security scans
+    pub fn register_security_check(&self, check: Arc<dyn SecurityCheck>) {
+        self.scanner.register_check(check);
+    }
+
+    /// Record a tool call reported by an agent and enforce its MCP restrictions
+    pub async fn record_tool_usage(&self, agent_id: &str, tool: &str) -> Result<PolicyDecision> {
+        let restrictions = {
+            let mut agents = self.agents.write().await;
+            let Some(agent) = agents.get_mut(agent_id) else {
+                bail!("Agent {agent_id} not found");
+            };
+
+            // Nothing is allowed until an operator approves the agent
+            if matches!(agent.status, AgentStatus::Quarantined) {
+                return Ok(PolicyDecision::Quarantine {
+                    reason: format!("Agent {agent_id} is quarantined"),
+                });
+            }
+
+            *agent.tool_usage.entry(tool.to_string()).or_insert(0) += 1;
+
+            agent
+                .sandbox_config
+                .as_ref()
+                .filter(|sandbox| sandbox.enabled)
+                .map(|sandbox| (sandbox.mcp.clone(), sandbox.mode.clone()))
+        };
+
+        let now = Utc::now();
+        if let Some(breach) = self.quotas.record_tool_call(agent_id, now).await {
+            self.apply_quota_action(agent_id, &breach).await?;
+        }
+        if let Some(usage) = self.quotas.usage(agent_id).await
+            && let Some(breach) = usage.exceeded
+        {
+            return Ok(PolicyDecision::Throttle {
+                reason: breach.to_string(),
+                retry_after_secs: (usage.period_end - now).num_seconds().max(1) as u64,
+            });
+        }
+
+        let Some((mcp, mode)) = restrictions else {
+            return Ok(PolicyDecision::Allow);
+        };
+
+        let decision = self.policy.evaluate(agent_id, &mcp, &mode, tool).await;
+
+        match &decision {
+            PolicyDecision::Allow => {}
+            PolicyDecision::Warn { reason } | PolicyDecision::Throttle { reason, .. } => {
+                self.monitor
+                    .alert(Alert::PolicyViolation {
+                        agent_id: agent_id.to_string(),
+                        tool: tool.to_string(),
+                        reason: reason.clone(),
+                    })
+                    .await?;
+            }
+            PolicyDecision::Quarantine { reason } => {
+                self.monitor
+                    .alert(Alert::PolicyViolation {
+                        agent_id: agent_id.to_string(),
+                        tool: tool.to_string(),
+                        reason: reason.clone(),
+                    })
+                    .await?;
+                self.quarantine_with_reason(agent_id, reason).await?;
+            }
+        }
+
+        Ok(decision)
+    }
+
     pub async fn get_status(&self) -> HashMap<String, AgentStatus> {
         let agents = self.agents.read().await;
         agents
@@ -339,16 +1715,78 @@ impl Supervisor {
         agents.get(agent_id).cloned()
     }
 
+    /// Log source for an agent: its captured output file, falling back to docker logs
+    pub async fn log_streamer(&self, agent_id: &str) -> LogStreamer {
+        let log_path = self
+            .agents
+            .read()
+            .await
+            .get(agent_id)
+            .and_then(|agent| agent.log_path.clone());
+
+        let streamer = LogStreamer::new(agent_id.to_string());
+        match log_path {
+            Some(path) => streamer.with_log_file(path),
+            None => streamer,
+        }
+    }
+
+    /// Open an agent's state database read-only, as configured in its agent config
+    pub async fn open_agent_state(&self, agent_id: &str) -> Result<StateManager> {
+        let database_path = self.agent_database_path(agent_id).await?;
+
+        StateManager::open_read_only(&database_path).await
+    }
+
+    async fn agent_database_path(&self, agent_id: &str) -> Result<String> {
+        let config_path = {
+            let agents = self.agents.read().await;
+            let Some(agent) = agents.get(agent_id) else {
+                bail!("Agent {agent_id} not found");
+            };
+            agent.config_path.clone()
+        };
+
+        let config = read_agent_config(&config_path).await?;
+
+        Ok(config.database_path)
+    }
+
     async fn start_dashboard(&self, port: u16) -> Result<()> {
         info!("Starting web dashboard on port {port}");
 
         // Dashboard implementation using Axum
-        api::start_dashboard_server(port, self.agents.clone(), self.monitor.clone()).await?;
+        api::start_dashboard_server(port, self.clone()).await?;
 
         Ok(())
     }
 }
 
+fn new_agent_id() -> String {
+    format!("agent-{uuid}", uuid = Uuid::new_v4())
+}
+
+/// Read an agent config with its includes merged in, so a copy written
+/// elsewhere stands alone, leaving `${VAR}` references for the agent to expand
+async fn read_agent_config(config_path: &str) -> Result<Config> {
+    let path = std::path::PathBuf::from(config_path);
+    tokio::task::spawn_blocking(move || Config::from_file_unexpanded(&path)).await?
+}
+
+async fn write_agent_config(agent_dir: &std::path::Path, config: &Config) -> Result<String> {
+    let config_path = agent_dir.join("config.toml");
+    let contents = toml::to_string_pretty(config).context("Failed to serialize agent config")?;
+    tokio::fs::write(&config_path, contents)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to write agent config {path}",
+                path = config_path.display()
+            )
+        })?;
+    Ok(config_path.to_string_lossy().into_owned())
+}
+
 impl Default for SupervisorConfig {
     fn default() -> Self {
         Self {
@@ -367,6 +1805,23 @@ impl Default for SupervisorConfig {
                     "/etc/passwd".to_string(),
                 ],
             },
+            policy: PolicyConfig::default(),
+            notifications: NotificationConfig::default(),
+            database_path: default_database_path(),
+            cgroups: CgroupConfig::default(),
+            agents_dir: default_agents_dir(),
+            templates_dir: None,
+            spawn_configs_dir: None,
+            spawn_sandbox: None,
+            audit_reads: false,
+            schedules: Vec::new(),
+            agent_logs: LogCaptureConfig::default(),
+            quotas: QuotaConfig::default(),
+            auth: AuthConfig::default(),
+            security_scanner: ScannerConfig::default(),
+            egress_proxy: EgressProxyConfig::default(),
+            network_history: NetworkHistoryConfig::default(),
+            logging: LoggingConfig::default(),
         }
     }
 }
@@ -1,14 +1,16 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
+use super::notifier::Notifier;
+use super::security::{RiskLevel, SecurityReport};
 use super::{AgentProcess, ResourceUsage};
+use crate::DecisionRecord;
+use crate::events::{Event as BusEvent, EventBus};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Alert {
@@ -30,6 +32,9 @@ pub enum Alert {
     NetworkAnomaly {
         agent_id: String,
         destination: String,
+        /// Packet capture of the flow taken by the network monitor
+        #[serde(default)]
+        capture: Option<String>,
     },
     PrivilegeEscalation {
         agent_id: String,
@@ -43,6 +48,88 @@ pub enum Alert {
         agent_id: String,
         reason: String,
     },
+    PolicyViolation {
+        agent_id: String,
+        tool: String,
+        reason: String,
+    },
+    QuotaExceeded {
+        agent_id: String,
+        resource: String,
+        used: f64,
+        limit: f64,
+    },
+}
+
+impl Alert {
+    pub fn severity(&self) -> RiskLevel {
+        match self {
+            Alert::SuspiciousToolUsage { .. }
+            | Alert::PolicyViolation { .. }
+            | Alert::QuotaExceeded { .. } => RiskLevel::Medium,
+            Alert::HighResourceUsage { .. }
+            | Alert::UnauthorizedAccess { .. }
+            | Alert::NetworkAnomaly { .. }
+            | Alert::AgentCrashed { .. }
+            | Alert::AgentQuarantined { .. } => RiskLevel::High,
+            Alert::PrivilegeEscalation { .. } => RiskLevel::Critical,
+        }
+    }
+
+    /// One-line human readable description, used for notifications
+    pub fn summary(&self) -> String {
+        match self {
+            Alert::HighResourceUsage {
+                agent_id,
+                metric,
+                value,
+                threshold,
+            } => format!("Agent {agent_id} {metric} usage {value:.1} exceeds {threshold:.1}"),
+            Alert::SuspiciousToolUsage {
+                agent_id,
+                tool,
+                frequency,
+            } => format!("Agent {agent_id} called {tool} {frequency} times"),
+            Alert::UnauthorizedAccess { agent_id, path } => {
+                format!("Agent {agent_id} attempted unauthorized access to {path}")
+            }
+            Alert::NetworkAnomaly {
+                agent_id,
+                destination,
+                capture: None,
+            } => format!("Agent {agent_id} network anomaly towards {destination}"),
+            Alert::NetworkAnomaly {
+                agent_id,
+                destination,
+                capture: Some(capture),
+            } => format!(
+                "Agent {agent_id} network anomaly towards {destination} (captured to {capture})"
+            ),
+            Alert::PrivilegeEscalation { agent_id, attempt } => {
+                format!("Agent {agent_id} privilege escalation attempt: {attempt}")
+            }
+            Alert::AgentCrashed {
+                agent_id,
+                exit_code,
+            } => format!("Agent {agent_id} crashed (exit code: {exit_code:?})"),
+            Alert::AgentQuarantined { agent_id, reason } => {
+                format!("Agent {agent_id} quarantined: {reason}")
+            }
+            Alert::PolicyViolation {
+                agent_id,
+                tool,
+                reason,
+            } => format!("Agent {agent_id} policy violation on {tool}: {reason}"),
+            Alert::QuotaExceeded {
+                agent_id,
+                resource,
+                used,
+                limit,
+            } => {
+                format!("Agent {agent_id} exceeded its {resource} quota ({used:.0} of {limit:.0})")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +144,10 @@ pub struct Event {
 pub enum EventType {
     AgentStarted,
     AgentStopped,
+    AgentPaused,
+    AgentResumed,
+    AgentApproved,
+    AgentConfigReloaded,
     ToolUsed,
     Decision,
     Error,
@@ -74,10 +165,32 @@ pub struct Metrics {
     pub tool_calls: u32,
 }
 
+/// Agent ID recorded on incident reports that cover the whole fleet
+pub const FLEET_AGENT_ID: &str = "*";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentReport {
+    pub id: String,
+    pub agent_id: String,
+    /// What caused the incident, e.g. "emergency_stop"
+    pub trigger: String,
+    pub timestamp: DateTime<Utc>,
+    pub events: Vec<Event>,
+    pub alerts: Vec<Alert>,
+    pub resource_history: Vec<Metrics>,
+    pub recent_decisions: Vec<DecisionRecord>,
+    pub logs_excerpt: Vec<String>,
+    /// Scanner report that triggered an automatic quarantine or stop
+    #[serde(default)]
+    pub security_report: Option<SecurityReport>,
+}
+
 pub struct Monitor {
     events: Arc<RwLock<VecDeque<Event>>>,
     metrics: Arc<RwLock<HashMap<String, VecDeque<Metrics>>>>,
     alerts: Arc<RwLock<VecDeque<Alert>>>,
+    notifier: Option<Notifier>,
+    bus: EventBus,
     max_events: usize,
     max_metrics_per_agent: usize,
 }
@@ -94,11 +207,23 @@ impl Monitor {
             events: Arc::new(RwLock::new(VecDeque::new())),
             metrics: Arc::new(RwLock::new(HashMap::new())),
             alerts: Arc::new(RwLock::new(VecDeque::new())),
+            notifier: None,
+            bus: EventBus::default(),
             max_events: 10000,
             max_metrics_per_agent: 1000,
         }
     }
 
+    /// Bus the monitor publishes raised alerts on
+    pub fn event_bus(&self) -> &EventBus {
+        &self.bus
+    }
+
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     pub async fn start_monitoring(&self, agent_id: &str) -> Result<()> {
         info!("Starting monitoring for agent {agent_id}");
 
@@ -213,6 +338,10 @@ impl Monitor {
     pub async fn alert(&self, alert: Alert) -> Result<()> {
         warn!("Alert: {:?}", alert);
 
+        if let Some(notifier) = &self.notifier {
+            notifier.notify_alert(&alert);
+        }
+
         let mut alerts = self.alerts.write().await;
         alerts.push_back(alert.clone());
 
@@ -221,18 +350,27 @@ impl Monitor {
             alerts.pop_front();
         }
 
+        let agent_id = match &alert {
+            Alert::HighResourceUsage { agent_id, .. }
+            | Alert::SuspiciousToolUsage { agent_id, .. }
+            | Alert::UnauthorizedAccess { agent_id, .. }
+            | Alert::NetworkAnomaly { agent_id, .. }
+            | Alert::PrivilegeEscalation { agent_id, .. }
+            | Alert::AgentCrashed { agent_id, .. }
+            | Alert::AgentQuarantined { agent_id, .. }
+            | Alert::PolicyViolation { agent_id, .. }
+            | Alert::QuotaExceeded { agent_id, .. } => agent_id.clone(),
+        };
+        self.bus.publish(BusEvent::AlertRaised {
+            agent_id: agent_id.clone(),
+            severity: format!("{severity:?}", severity = alert.severity()).to_lowercase(),
+            summary: alert.summary(),
+        });
+
         // Log as event too
         self.log_event(Event {
             timestamp: Utc::now(),
-            agent_id: match &alert {
-                Alert::HighResourceUsage { agent_id, .. }
-                | Alert::SuspiciousToolUsage { agent_id, .. }
-                | Alert::UnauthorizedAccess { agent_id, .. }
-                | Alert::NetworkAnomaly { agent_id, .. }
-                | Alert::PrivilegeEscalation { agent_id, .. }
-                | Alert::AgentCrashed { agent_id, .. }
-                | Alert::AgentQuarantined { agent_id, .. } => agent_id.clone(),
-            },
+            agent_id,
             event_type: EventType::Alert,
             details: serde_json::to_value(&alert)?,
         })
@@ -256,12 +394,15 @@ impl Monitor {
         metrics.get(agent_id).map(|m| m.iter().cloned().collect())
     }
 
-    pub async fn generate_incident_report(&self, agent_id: &str) -> Result<()> {
+    /// Collect the monitor's view of an agent into an incident report.
+    /// Callers may enrich the report (decisions, logs) before persisting it.
+    pub async fn generate_incident_report(
+        &self,
+        agent_id: &str,
+        trigger: &str,
+    ) -> Result<IncidentReport> {
         info!("Generating incident report for agent {agent_id}");
 
-        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        let filename = format!("incident_{agent_id}_{timestamp}.json");
-
         // Collect all relevant data
         let events = self.events.read().await;
         let agent_events: Vec<_> = events
@@ -280,36 +421,60 @@ impl Monitor {
                 | Alert::NetworkAnomaly { agent_id: id, .. }
                 | Alert::PrivilegeEscalation { agent_id: id, .. }
                 | Alert::AgentCrashed { agent_id: id, .. }
-                | Alert::AgentQuarantined { agent_id: id, .. } => id == agent_id,
+                | Alert::AgentQuarantined { agent_id: id, .. }
+                | Alert::PolicyViolation { agent_id: id, .. }
+                | Alert::QuotaExceeded { agent_id: id, .. } => id == agent_id,
             })
             .cloned()
             .collect();
 
         let metrics = self.metrics.read().await;
-        let agent_metrics = metrics.get(agent_id).cloned();
-
-        let report = serde_json::json!({
-            "incident_id": Uuid::new_v4().to_string(),
-            "timestamp": Utc::now(),
-            "agent_id": agent_id,
-            "events": agent_events,
-            "alerts": agent_alerts,
-            "metrics": agent_metrics,
-        });
+        let resource_history = metrics
+            .get(agent_id)
+            .map(|m| m.iter().cloned().collect())
+            .unwrap_or_default();
 
-        // Write to file
-        let mut file = File::create(&filename)
-            .await
-            .context("Failed to create incident report file")?;
+        Ok(IncidentReport {
+            id: Uuid::new_v4().to_string(),
+            agent_id: agent_id.to_string(),
+            trigger: trigger.to_string(),
+            timestamp: Utc::now(),
+            events: agent_events,
+            alerts: agent_alerts,
+            resource_history,
+            recent_decisions: Vec::new(),
+            logs_excerpt: Vec::new(),
+            security_report: None,
+        })
+    }
 
-        let json = serde_json::to_string_pretty(&report)?;
-        file.write_all(json.as_bytes())
-            .await
-            .context("Failed to write incident report")?;
+    /// Incident report covering every agent, e.g. after an emergency stop-all.
+    /// Resource history holds the latest sample of each agent.
+    pub async fn generate_fleet_report(&self, trigger: &str) -> Result<IncidentReport> {
+        info!("Generating fleet-wide incident report");
 
-        info!("Incident report saved to {filename}");
+        let events = self.events.read().await.iter().cloned().collect();
+        let alerts = self.alerts.read().await.iter().cloned().collect();
+        let resource_history = self
+            .metrics
+            .read()
+            .await
+            .values()
+            .filter_map(|m| m.back().cloned())
+            .collect();
 
-        Ok(())
+        Ok(IncidentReport {
+            id: Uuid::new_v4().to_string(),
+            agent_id: FLEET_AGENT_ID.to_string(),
+            trigger: trigger.to_string(),
+            timestamp: Utc::now(),
+            events,
+            alerts,
+            resource_history,
+            recent_decisions: Vec::new(),
+            logs_excerpt: Vec::new(),
+            security_report: None,
+        })
     }
 
     pub async fn export_metrics(&self, format: &str) -> Result<String> {
@@ -0,0 +1,1119 @@
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::domains::domain_matches;
+
+use super::proc_exec_poller::container_id_from_cgroup;
+use super::{NetworkMode, NetworkRestrictions};
+
+/// Host network activity observed by the network monitor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkStats {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub connections: Vec<Connection>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub active_connections: usize,
+    /// Traffic of each container, keyed by container name
+    #[serde(default)]
+    pub containers: HashMap<String, ContainerTraffic>,
+}
+
+/// A container's cumulative interface counters and open connections
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContainerTraffic {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub connections: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Connection {
+    pub protocol: String,
+    pub local_addr: String,
+    pub remote_addr: String,
+    pub state: String,
+    pub pid: Option<u32>,
+    /// Name of the container, usually an agent, whose network namespace
+    /// holds the socket; `None` for the host's own sockets
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Where the remote endpoint is, when a GeoIP database is configured
+    #[serde(default)]
+    pub geo: Option<GeoInfo>,
+}
+
+/// Country and autonomous system of an address, from MaxMind databases
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    pub as_org: Option<String>,
+}
+
+impl fmt::Display for GeoInfo {
+    /// e.g. `US, AS15169 GOOGLE`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(country) = &self.country {
+            parts.push(country.clone());
+        }
+        match (self.asn, &self.as_org) {
+            (Some(asn), Some(org)) => parts.push(format!("AS{asn} {org}")),
+            (Some(asn), None) => parts.push(format!("AS{asn}")),
+            _ => {}
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Whether an address is routed on the internet, so worth locating: not
+/// loopback, unspecified, private or link-local
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => {
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkAlert {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub alert_type: NetworkAlertType,
+    pub details: String,
+    pub connection: Option<Connection>,
+    /// Container the alert is about, when it isn't given by `connection`
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Packet capture of the alerting flow, on the monitor's host
+    #[serde(default)]
+    pub capture: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NetworkAlertType {
+    UnauthorizedConnection,
+    HighBandwidth,
+    SuspiciousPort,
+    TooManyConnections,
+    DNSAnomaly,
+    GeoPolicyViolation,
+}
+
+/// What the network monitor sends the supervisor after each cycle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkReport {
+    /// Name of the reporting monitor, its hostname by default
+    pub monitor: String,
+    pub stats: NetworkStats,
+    /// Alerts raised since the previous report
+    pub alerts: Vec<NetworkAlert>,
+}
+
+/// A connection the supervisor asks a network monitor to sever
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionTermination {
+    /// Container holding the socket; `None` for the monitor's host
+    #[serde(default)]
+    pub container: Option<String>,
+    pub remote_addr: String,
+}
+
+impl ConnectionTermination {
+    pub fn matches(&self, connection: &Connection) -> bool {
+        connection.container == self.container && connection.remote_addr == self.remote_addr
+    }
+}
+
+/// What the supervisor answers a network report with
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkReportResponse {
+    /// Connections the monitor should sever
+    #[serde(default)]
+    pub terminate: Vec<ConnectionTermination>,
+}
+
+/// `ss` filter selecting the sockets connected to `remote_addr`, to destroy
+/// them with `ss -K`
+pub fn kill_filter(remote_addr: &str) -> Option<Vec<String>> {
+    let remote: SocketAddr = remote_addr.parse().ok()?;
+    let host = match remote.ip() {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    };
+    Some(vec![
+        "dst".to_string(),
+        host,
+        "dport".to_string(),
+        "=".to_string(),
+        format!(":{port}", port = remote.port()),
+    ])
+}
+
+/// Reports waiting to be delivered to the supervisor, one JSON file each so
+/// they survive restarts. When full, the oldest reports without alerts are
+/// dropped first.
+pub struct ReportQueue {
+    dir: PathBuf,
+    max_reports: usize,
+    sequence: AtomicU64,
+}
+
+const ALERTS_SUFFIX: &str = "-alerts.json";
+/// Directory of the queue holding reports the supervisor refused
+const REJECTED_DIR: &str = "rejected";
+
+impl ReportQueue {
+    pub fn open(dir: impl Into<PathBuf>, max_reports: usize) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create report queue {}", dir.display()))?;
+
+        Ok(Self {
+            dir,
+            max_reports,
+            sequence: AtomicU64::new(0),
+        })
+    }
+
+    pub fn push(&self, report: &NetworkReport) -> Result<()> {
+        let name = format!(
+            "{nanos:020}-{sequence:06}{suffix}",
+            nanos = report
+                .stats
+                .timestamp
+                .timestamp_nanos_opt()
+                .unwrap_or_default(),
+            sequence = self.sequence.fetch_add(1, Ordering::Relaxed) % 1_000_000,
+            suffix = if report.alerts.is_empty() {
+                ".json"
+            } else {
+                ALERTS_SUFFIX
+            }
+        );
+
+        // Written aside and renamed so a crash never leaves a partial report
+        let path = self.dir.join(&name);
+        let partial = self.dir.join(format!("{name}.tmp"));
+        std::fs::write(&partial, serde_json::to_vec(report)?)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        std::fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to queue {}", path.display()))?;
+
+        self.trim()
+    }
+
+    /// Queued reports, oldest first
+    pub fn pending(&self) -> Result<Vec<PathBuf>> {
+        let mut reports = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read report queue {}", self.dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                reports.push(path);
+            }
+        }
+
+        reports.sort();
+        Ok(reports)
+    }
+
+    pub fn load(&self, path: &Path) -> Result<NetworkReport> {
+        let contents =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("Failed to parse queued report {}", path.display()))
+    }
+
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove queued report {}", path.display()))
+    }
+
+    /// Move a report the supervisor refused into the `rejected` directory of
+    /// the queue, out of the way of the reports after it but kept for an
+    /// operator to look at. Returns where it went.
+    pub fn reject(&self, path: &Path) -> Result<PathBuf> {
+        let rejected = self.dir.join(REJECTED_DIR);
+        std::fs::create_dir_all(&rejected)
+            .with_context(|| format!("Failed to create {path}", path = rejected.display()))?;
+        let destination = rejected.join(path.file_name().context("Queued report has no name")?);
+        std::fs::rename(path, &destination).with_context(|| {
+            format!(
+                "Failed to move rejected report {path} aside",
+                path = path.display()
+            )
+        })?;
+        Ok(destination)
+    }
+
+    fn trim(&self) -> Result<()> {
+        let pending = self.pending()?;
+        let excess = pending.len().saturating_sub(self.max_reports);
+        if excess == 0 {
+            return Ok(());
+        }
+
+        let has_alerts = |path: &PathBuf| path.to_string_lossy().ends_with(ALERTS_SUFFIX);
+        let dropped: Vec<&PathBuf> = pending
+            .iter()
+            .filter(|path| !has_alerts(path))
+            .chain(pending.iter().filter(|path| has_alerts(path)))
+            .take(excess)
+            .collect();
+
+        warn!(
+            "Report queue full, dropping {count} undelivered reports",
+            count = dropped.len()
+        );
+        for path in dropped {
+            self.remove(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A lookup logged by the resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuery {
+    pub record_type: String,
+    pub domain: String,
+    pub client: String,
+}
+
+/// Parse a dnsmasq `--log-queries` line such as
+/// `Oct 16 12:00:00 dnsmasq[1]: query[A] example.com from 172.20.0.5`
+pub fn parse_dnsmasq_query(line: &str) -> Option<DnsQuery> {
+    let (_, query) = line.split_once("query[")?;
+    let (record_type, rest) = query.split_once(']')?;
+    let mut fields = rest.split_whitespace();
+    let domain = fields.next()?;
+    if fields.next()? != "from" {
+        return None;
+    }
+    let client = fields.next()?;
+
+    Some(DnsQuery {
+        record_type: record_type.to_string(),
+        domain: domain.trim_end_matches('.').to_ascii_lowercase(),
+        client: client.to_string(),
+    })
+}
+
+/// Whether `domain` matches one of the `allowed` patterns, in the same
+/// sense as the egress proxy: only `*.` patterns cover subdomains
+pub fn is_domain_allowed(domain: &str, allowed: &[String]) -> bool {
+    allowed
+        .iter()
+        .any(|pattern| domain_matches(pattern, domain))
+}
+
+/// Whether a domain has a label that looks machine generated, as used by
+/// malware to find its command and control servers: long and random, or
+/// long runs of consonants
+pub fn looks_generated(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    // The top level domain is never generated
+    let Some((_, labels)) = labels.split_last() else {
+        return false;
+    };
+
+    labels.iter().any(|label| {
+        (label.len() >= 12 && entropy(label) >= 3.5)
+            || (label.len() >= 8 && longest_consonant_run(label) >= 7)
+    })
+}
+
+/// Shannon entropy in bits per character
+fn entropy(label: &str) -> f64 {
+    let mut counts = std::collections::HashMap::new();
+    for c in label.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+
+    let length = label.chars().count() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn longest_consonant_run(label: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in label.chars() {
+        if c.is_ascii_alphabetic() && !"aeiouy".contains(c.to_ascii_lowercase()) {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    longest
+}
+
+/// An address range such as `172.20.0.0/16`; a bare address is a range of
+/// one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (network, prefix_len) = match value.trim().split_once('/') {
+            Some((network, prefix_len)) => (network, Some(prefix_len)),
+            None => (value.trim(), None),
+        };
+        let network: IpAddr = network
+            .parse()
+            .with_context(|| format!("Invalid address in CIDR range '{value}'"))?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|&len| len <= max_len)
+                .with_context(|| format!("Invalid prefix length in CIDR range '{value}'"))?,
+            None => max_len,
+        };
+
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{network}/{prefix_len}",
+            network = self.network,
+            prefix_len = self.prefix_len
+        )
+    }
+}
+
+/// What the network monitor allows, loaded from a TOML or JSON file in the
+/// format of a sandbox's `[sandbox.network]` restrictions
+#[derive(Debug, Clone)]
+pub struct NetworkWhitelist {
+    restrictions: NetworkRestrictions,
+    cidrs: Vec<IpCidr>,
+}
+
+impl Default for NetworkWhitelist {
+    /// Loopback and the agents' docker network
+    fn default() -> Self {
+        let cidrs = vec![
+            IpCidr {
+                network: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)),
+                prefix_len: 8,
+            },
+            IpCidr {
+                network: IpAddr::V4(Ipv4Addr::new(172, 20, 0, 0)),
+                prefix_len: 16,
+            },
+        ];
+        Self {
+            restrictions: NetworkRestrictions {
+                mode: NetworkMode::Filtered,
+                allowed_domains: Vec::new(),
+                allowed_cidrs: cidrs.iter().map(ToString::to_string).collect(),
+                blocked_ports: Vec::new(),
+                rate_limit_per_minute: None,
+            },
+            cidrs,
+        }
+    }
+}
+
+impl NetworkWhitelist {
+    pub fn from_restrictions(restrictions: NetworkRestrictions) -> Result<Self> {
+        let cidrs = restrictions
+            .allowed_cidrs
+            .iter()
+            .map(|cidr| cidr.parse())
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            restrictions,
+            cidrs,
+        })
+    }
+
+    /// Read a whitelist file, as JSON if it ends in `.json` and TOML otherwise
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read network whitelist {}", path.display()))?;
+        let restrictions = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&contents).map_err(anyhow::Error::from)
+        }
+        .with_context(|| format!("Failed to parse network whitelist {}", path.display()))?;
+
+        Self::from_restrictions(restrictions)
+            .with_context(|| format!("Invalid network whitelist {}", path.display()))
+    }
+
+    /// Whether connections to `ip` are allowed: any in `Full` mode, none in
+    /// `None` mode, and those in `allowed_cidrs` when `Filtered`
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        match self.restrictions.mode {
+            NetworkMode::None => false,
+            NetworkMode::Full => true,
+            NetworkMode::Filtered => self.cidrs.iter().any(|cidr| cidr.contains(ip)),
+        }
+    }
+
+    pub fn blocks_port(&self, port: u16) -> bool {
+        self.restrictions.blocked_ports.contains(&port)
+    }
+
+    pub fn allowed_domains(&self) -> &[String] {
+        &self.restrictions.allowed_domains
+    }
+}
+
+/// A process inside a container, used to read that container's sockets from
+/// `/proc/<pid>/net`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerNamespace {
+    pub container_id: String,
+    pub pid: u32,
+}
+
+/// One process per distinct network namespace of each container, found by
+/// walking the host's process table
+pub fn container_net_namespaces(proc_root: &Path) -> Result<Vec<ContainerNamespace>> {
+    let entries = std::fs::read_dir(proc_root).with_context(|| {
+        format!(
+            "Failed to read process table at {proc_root}",
+            proc_root = proc_root.display()
+        )
+    })?;
+
+    // Lowest pids first, so each namespace is read through the container's
+    // longest running process
+    let mut pids: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .collect();
+    pids.sort_unstable();
+
+    let mut seen = HashSet::new();
+    let mut namespaces = Vec::new();
+
+    for pid in pids {
+        // Processes can exit while we read them; skip those
+        let dir = proc_root.join(pid.to_string());
+        let Ok(cgroup) = std::fs::read_to_string(dir.join("cgroup")) else {
+            continue;
+        };
+        let Some(container_id) = container_id_from_cgroup(&cgroup) else {
+            continue;
+        };
+        let Ok(namespace) = std::fs::read_link(dir.join("ns/net")) else {
+            continue;
+        };
+
+        if seen.insert(namespace) {
+            namespaces.push(ContainerNamespace { container_id, pid });
+        }
+    }
+
+    Ok(namespaces)
+}
+
+/// Name of a container from docker's own state in `containers_dir`
+/// (`/var/lib/docker/containers`), or its short id if that can't be read
+pub fn container_name(containers_dir: &Path, container_id: &str) -> String {
+    std::fs::read(containers_dir.join(container_id).join("config.v2.json"))
+        .ok()
+        .and_then(|raw| serde_json::from_slice::<serde_json::Value>(&raw).ok())
+        .and_then(|config| {
+            config["Name"]
+                .as_str()
+                .map(|name| name.trim_start_matches('/').to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| container_id.chars().take(12).collect())
+}
+
+/// Sockets listed in a `/proc/<pid>/net/{tcp,tcp6,udp,udp6}` table.
+/// `protocol` is "TCP" or "UDP"; UDP sockets are ESTABLISHED when connected
+/// to a remote endpoint and UNCONN otherwise.
+pub fn parse_proc_net_sockets(
+    table: &str,
+    protocol: &str,
+    container: Option<&str>,
+) -> Vec<Connection> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 10 {
+                return None;
+            }
+
+            let state = u8::from_str_radix(parts[3], 16).unwrap_or(0);
+            let state = if protocol == "UDP" {
+                if state == 1 { "ESTABLISHED" } else { "UNCONN" }
+            } else {
+                tcp_state_name(state)
+            };
+
+            Some(Connection {
+                protocol: protocol.to_string(),
+                local_addr: parse_hex_addr(parts[1])?,
+                remote_addr: parse_hex_addr(parts[2])?,
+                state: state.to_string(),
+                pid: None, // Would need to read from /proc/*/fd/* to get PID
+                container: container.map(str::to_string),
+                geo: None,
+            })
+        })
+        .collect()
+}
+
+fn tcp_state_name(state: u8) -> &'static str {
+    match state {
+        1 => "ESTABLISHED",
+        2 => "SYN_SENT",
+        3 => "SYN_RECV",
+        4 => "FIN_WAIT1",
+        5 => "FIN_WAIT2",
+        6 => "TIME_WAIT",
+        7 => "CLOSE",
+        8 => "CLOSE_WAIT",
+        9 => "LAST_ACK",
+        10 => "LISTEN",
+        11 => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+/// `ip:port` from the kernel's hex notation, where the address is stored as
+/// 32-bit words in host byte order. IPv4-mapped IPv6 addresses are shown as
+/// IPv4 so they match the same allowlist entries.
+fn parse_hex_addr(hex_addr: &str) -> Option<String> {
+    let (ip, port) = hex_addr.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let ip: IpAddr = match ip.len() {
+        8 => Ipv4Addr::from(u32::from_str_radix(ip, 16).ok()?.to_le_bytes()).into(),
+        32 => {
+            let mut octets = [0u8; 16];
+            for (index, chunk) in octets.chunks_mut(4).enumerate() {
+                let word = u32::from_str_radix(&ip[index * 8..index * 8 + 8], 16).ok()?;
+                chunk.copy_from_slice(&word.to_le_bytes());
+            }
+            let ip = Ipv6Addr::from(octets);
+            match ip.to_ipv4_mapped() {
+                Some(ip) => ip.into(),
+                None => ip.into(),
+            }
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, port).to_string())
+}
+
+/// ICMP messages sent, from the IPv4 counters of a `/proc/<pid>/net/snmp`
+/// file and the IPv6 ones of its `snmp6` sibling
+pub fn icmp_messages_sent(snmp: &str, snmp6: &str) -> u64 {
+    // snmp has a header line naming each counter, then a line of values
+    let mut lines = snmp.lines().filter(|line| line.starts_with("Icmp: "));
+    let icmp = match (lines.next(), lines.next()) {
+        (Some(names), Some(values)) => names
+            .split_whitespace()
+            .zip(values.split_whitespace())
+            .find(|(name, _)| *name == "OutMsgs")
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or(0),
+        _ => 0,
+    };
+
+    let icmp6 = snmp6
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(name, _)| *name == "Icmp6OutMsgs")
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+
+    icmp + icmp6
+}
+
+/// Local MaxMind databases (GeoLite2/GeoIP2 Country or City, and ASN)
+/// used to locate remote endpoints
+pub struct GeoLookup {
+    country: Option<maxminddb::Reader<Vec<u8>>>,
+    asn: Option<maxminddb::Reader<Vec<u8>>>,
+}
+
+impl GeoLookup {
+    pub fn open(country_db: Option<&Path>, asn_db: Option<&Path>) -> Result<Self> {
+        let open = |path: &Path| {
+            maxminddb::Reader::open_readfile(path)
+                .with_context(|| format!("Failed to open GeoIP database {}", path.display()))
+        };
+
+        Ok(Self {
+            country: country_db.map(open).transpose()?,
+            asn: asn_db.map(open).transpose()?,
+        })
+    }
+
+    /// Country and ASN of `ip`, `None` if neither database knows it
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let country = self.country.as_ref().and_then(|reader| {
+            let record: maxminddb::geoip2::Country = reader.lookup(ip).ok()?;
+            record
+                .country
+                .or(record.registered_country)?
+                .iso_code
+                .map(str::to_string)
+        });
+        let (asn, as_org) = self
+            .asn
+            .as_ref()
+            .and_then(|reader| reader.lookup::<maxminddb::geoip2::Asn>(ip).ok())
+            .map(|record| {
+                (
+                    record.autonomous_system_number,
+                    record.autonomous_system_organization.map(str::to_string),
+                )
+            })
+            .unwrap_or_default();
+
+        if country.is_none() && asn.is_none() {
+            return None;
+        }
+        Some(GeoInfo {
+            country,
+            asn,
+            as_org,
+        })
+    }
+}
+
+/// Countries and autonomous systems connections may reach; an empty list
+/// allows any
+#[derive(Debug, Clone, Default)]
+pub struct GeoPolicy {
+    /// ISO 3166-1 alpha-2 codes
+    pub allowed_countries: Vec<String>,
+    pub allowed_asns: Vec<u32>,
+}
+
+impl GeoPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allowed_countries.is_empty() && self.allowed_asns.is_empty()
+    }
+
+    /// Why a connection to an endpoint at `geo` breaks the policy, if it
+    /// does. Endpoints the databases don't know count as outside.
+    pub fn violation(&self, geo: Option<&GeoInfo>) -> Option<String> {
+        let country = geo.and_then(|geo| geo.country.as_deref());
+        if !self.allowed_countries.is_empty()
+            && !country.is_some_and(|country| {
+                self.allowed_countries
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(country))
+            })
+        {
+            return Some(format!(
+                "country {country} is not allowed",
+                country = country.unwrap_or("unknown")
+            ));
+        }
+
+        let asn = geo.and_then(|geo| geo.asn);
+        if !self.allowed_asns.is_empty() && !asn.is_some_and(|asn| self.allowed_asns.contains(&asn))
+        {
+            return Some(match asn {
+                Some(asn) => format!("AS{asn} is not allowed"),
+                None => "unknown AS is not allowed".to_string(),
+            });
+        }
+
+        None
+    }
+}
+
+/// Per-container traffic thresholds checked by the network monitor
+#[derive(Debug, Clone)]
+pub struct TrafficLimits {
+    /// Received or sent bytes per second
+    pub max_bytes_per_sec: f64,
+    /// New outbound connections per minute
+    pub max_connections_per_minute: Option<usize>,
+    /// ICMP messages sent per minute
+    pub max_icmp_per_minute: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrafficViolation {
+    Bandwidth { rx_per_sec: f64, tx_per_sec: f64 },
+    ConnectionRate { per_minute: usize },
+    IcmpRate { per_minute: u64 },
+}
+
+struct TrafficSample {
+    at: Instant,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+/// Tracks each container's traffic between monitor cycles against the limits
+pub struct TrafficTracker {
+    limits: TrafficLimits,
+    samples: HashMap<String, TrafficSample>,
+    /// Remote endpoints open on the previous cycle, by container
+    remotes: HashMap<String, HashSet<String>>,
+    /// When each new connection of the last minute was first seen
+    new_connections: HashMap<String, VecDeque<Instant>>,
+    /// ICMP messages sent by each container as of its previous sample
+    icmp_sent: HashMap<String, (Instant, u64)>,
+}
+
+impl TrafficTracker {
+    pub fn new(limits: TrafficLimits) -> Self {
+        Self {
+            limits,
+            samples: HashMap::new(),
+            remotes: HashMap::new(),
+            new_connections: HashMap::new(),
+            icmp_sent: HashMap::new(),
+        }
+    }
+
+    /// Record a container's interface counters, returning a violation if its
+    /// rate since the previous sample is over the limit
+    pub fn record_bandwidth(
+        &mut self,
+        container: &str,
+        rx_bytes: u64,
+        tx_bytes: u64,
+        now: Instant,
+    ) -> Option<TrafficViolation> {
+        let previous = self.samples.insert(
+            container.to_string(),
+            TrafficSample {
+                at: now,
+                rx_bytes,
+                tx_bytes,
+            },
+        )?;
+
+        let elapsed = now.duration_since(previous.at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        // Counters restart when the container does
+        let rx_per_sec = rx_bytes.saturating_sub(previous.rx_bytes) as f64 / elapsed;
+        let tx_per_sec = tx_bytes.saturating_sub(previous.tx_bytes) as f64 / elapsed;
+
+        (rx_per_sec > self.limits.max_bytes_per_sec || tx_per_sec > self.limits.max_bytes_per_sec)
+            .then_some(TrafficViolation::Bandwidth {
+                rx_per_sec,
+                tx_per_sec,
+            })
+    }
+
+    /// Record the remote endpoints a container has open, returning a
+    /// violation if it opened too many new ones in the last minute
+    pub fn record_connections<'a>(
+        &mut self,
+        container: &str,
+        remotes: impl IntoIterator<Item = &'a str>,
+        now: Instant,
+    ) -> Option<TrafficViolation> {
+        let current: HashSet<String> = remotes.into_iter().map(str::to_string).collect();
+        let previous = self
+            .remotes
+            .insert(container.to_string(), current.clone())
+            .unwrap_or_default();
+
+        let window = self
+            .new_connections
+            .entry(container.to_string())
+            .or_default();
+        window.extend(current.difference(&previous).map(|_| now));
+        while window
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= Duration::from_secs(60))
+        {
+            window.pop_front();
+        }
+
+        let per_minute = window.len();
+        self.limits
+            .max_connections_per_minute
+            .filter(|&limit| per_minute > limit)
+            .map(|_| TrafficViolation::ConnectionRate { per_minute })
+    }
+
+    /// Record a container's count of sent ICMP messages, returning a
+    /// violation if its rate since the previous sample is over the limit
+    pub fn record_icmp(
+        &mut self,
+        container: &str,
+        sent: u64,
+        now: Instant,
+    ) -> Option<TrafficViolation> {
+        let (at, previous) = self.icmp_sent.insert(container.to_string(), (now, sent))?;
+
+        let elapsed = now.duration_since(at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let per_minute = (sent.saturating_sub(previous) as f64 * 60.0 / elapsed).round() as u64;
+
+        self.limits
+            .max_icmp_per_minute
+            .filter(|&limit| per_minute > limit)
+            .map(|_| TrafficViolation::IcmpRate { per_minute })
+    }
+
+    /// Forget containers that are no longer running
+    pub fn retain(&mut self, running: &HashSet<String>) {
+        self.samples
+            .retain(|container, _| running.contains(container));
+        self.remotes
+            .retain(|container, _| running.contains(container));
+        self.new_connections
+            .retain(|container, _| running.contains(container));
+        self.icmp_sent
+            .retain(|container, _| running.contains(container));
+    }
+}
+
+/// tcpdump filter matching traffic with a connection's remote endpoint
+pub fn capture_filter(remote_addr: &str) -> Option<String> {
+    let remote: SocketAddr = remote_addr.parse().ok()?;
+    Some(format!(
+        "host {ip} and port {port}",
+        ip = remote.ip(),
+        port = remote.port()
+    ))
+}
+
+/// Delete all but the `keep` newest captures in `dir`, whose names start
+/// with their start time, returning how many were deleted
+pub fn prune_captures(dir: &Path, keep: usize) -> Result<usize> {
+    let mut captures: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read capture directory {}", dir.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pcap"))
+        .collect();
+    captures.sort();
+
+    let excess = captures.len().saturating_sub(keep);
+    for path in &captures[..excess] {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove capture {}", path.display()))?;
+    }
+
+    Ok(excess)
+}
+
+/// How long stored network samples are kept at each resolution
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct NetworkHistoryConfig {
+    pub raw_retention_hours: u64,
+    pub minute_retention_days: u64,
+    pub hour_retention_days: u64,
+}
+
+impl Default for NetworkHistoryConfig {
+    fn default() -> Self {
+        Self {
+            raw_retention_hours: 6,
+            minute_retention_days: 7,
+            hour_retention_days: 90,
+        }
+    }
+}
+
+impl NetworkHistoryConfig {
+    pub fn retention(&self, resolution: SampleResolution) -> Duration {
+        Duration::from_secs(match resolution {
+            SampleResolution::Raw => self.raw_retention_hours * 3600,
+            SampleResolution::Minute => self.minute_retention_days * 86_400,
+            SampleResolution::Hour => self.hour_retention_days * 86_400,
+        })
+    }
+}
+
+/// Granularity of stored network samples. Every sample is stored as is and
+/// added to its minute and hour buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleResolution {
+    Raw,
+    Minute,
+    Hour,
+}
+
+impl SampleResolution {
+    pub const ALL: [SampleResolution; 3] = [
+        SampleResolution::Raw,
+        SampleResolution::Minute,
+        SampleResolution::Hour,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SampleResolution::Raw => "raw",
+            SampleResolution::Minute => "minute",
+            SampleResolution::Hour => "hour",
+        }
+    }
+
+    /// Bucket width in seconds, 0 for raw samples
+    pub fn bucket_secs(&self) -> i64 {
+        match self {
+            SampleResolution::Raw => 0,
+            SampleResolution::Minute => 60,
+            SampleResolution::Hour => 3600,
+        }
+    }
+}
+
+impl FromStr for SampleResolution {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "raw" => Ok(SampleResolution::Raw),
+            "minute" => Ok(SampleResolution::Minute),
+            "hour" => Ok(SampleResolution::Hour),
+            _ => bail!("Unknown resolution '{value}', expected raw, minute or hour"),
+        }
+    }
+}
+
+/// Traffic of an agent, or of a network monitor's host, over one sample or
+/// downsampled bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkSample {
+    /// Agent container name, or the monitor's name for its host totals
+    pub source: String,
+    /// Start of the bucket, or end of a raw sample
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Time covered by the samples in the bucket
+    pub duration_ms: i64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Most connections open at once
+    pub connections: usize,
+}
+
+/// Samples of the traffic between two reports of the same monitor: its host
+/// totals and every container present in both
+pub fn samples_between(
+    monitor: &str,
+    previous: &NetworkStats,
+    current: &NetworkStats,
+) -> Vec<NetworkSample> {
+    let duration_ms = (current.timestamp - previous.timestamp).num_milliseconds();
+    if duration_ms <= 0 {
+        return Vec::new();
+    }
+
+    // Counters restart with a container, so a decrease counts as no traffic
+    let sample = |source: &str, rx: (u64, u64), tx: (u64, u64), connections| NetworkSample {
+        source: source.to_string(),
+        timestamp: current.timestamp,
+        duration_ms,
+        rx_bytes: rx.1.saturating_sub(rx.0),
+        tx_bytes: tx.1.saturating_sub(tx.0),
+        connections,
+    };
+
+    let host_connections = current
+        .connections
+        .iter()
+        .filter(|connection| connection.container.is_none())
+        .count();
+    let mut samples = vec![sample(
+        monitor,
+        (previous.rx_bytes, current.rx_bytes),
+        (previous.tx_bytes, current.tx_bytes),
+        host_connections,
+    )];
+
+    let mut containers: Vec<_> = current.containers.iter().collect();
+    containers.sort_by_key(|(name, _)| name.as_str());
+    for (name, traffic) in containers {
+        if let Some(before) = previous.containers.get(name) {
+            samples.push(sample(
+                name,
+                (before.rx_bytes, traffic.rx_bytes),
+                (before.tx_bytes, traffic.tx_bytes),
+                traffic.connections,
+            ));
+        }
+    }
+
+    samples
+}
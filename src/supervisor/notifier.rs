@@ -0,0 +1,236 @@
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use reqwest::Client;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, error, info};
+
+use super::monitor::Alert;
+use super::security::RiskLevel;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NotificationConfig {
+    pub enabled: bool,
+    /// Minimum severity that triggers a notification
+    pub alert_threshold: RiskLevel,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookTarget>,
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            alert_threshold: RiskLevel::High,
+            webhooks: Vec::new(),
+            email: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookTarget {
+    pub url: String,
+    #[serde(default)]
+    pub kind: WebhookKind,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    /// Raw JSON payload with the full alert
+    #[default]
+    Generic,
+    Slack,
+    Discord,
+}
+
+impl WebhookKind {
+    /// JSON body posted to a webhook of this kind
+    pub fn payload(&self, notification: &Notification) -> Result<Value> {
+        let text = format!(
+            "[{severity:?}] {title}",
+            severity = notification.severity,
+            title = notification.title
+        );
+
+        Ok(match self {
+            WebhookKind::Generic => serde_json::to_value(notification)?,
+            WebhookKind::Slack => serde_json::json!({ "text": text }),
+            WebhookKind::Discord => serde_json::json!({ "content": text }),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EmailConfig {
+    pub to: Vec<String>,
+    pub from: String,
+    /// Path to a sendmail-compatible binary used to deliver mail
+    #[serde(default = "default_sendmail_path")]
+    pub sendmail_path: String,
+}
+
+fn default_sendmail_path() -> String {
+    "/usr/sbin/sendmail".to_string()
+}
+
+impl EmailConfig {
+    /// The message handed to `sendmail -t`. Header values have line breaks
+    /// replaced, so a title can't add headers such as extra recipients.
+    pub fn message(&self, notification: &Notification) -> Result<String> {
+        Ok(format!(
+            "From: {from}\r\nTo: {to}\r\nSubject: [Replicante {severity:?}] {title}\r\n\r\n{details}\r\n",
+            from = header_value(&self.from),
+            to = header_value(&self.to.join(", ")),
+            severity = notification.severity,
+            title = header_value(&notification.title),
+            details = serde_json::to_string_pretty(&notification.details)?,
+        ))
+    }
+}
+
+/// A single-line header value, with CR and LF replaced by spaces
+fn header_value(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub severity: RiskLevel,
+    pub title: String,
+    pub details: Value,
+    pub timestamp: chrono::DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    config: NotificationConfig,
+    client: Client,
+}
+
+impl Notifier {
+    pub fn new(config: NotificationConfig) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create notification HTTP client")?;
+
+        Ok(Self { config, client })
+    }
+
+    pub fn should_notify(&self, severity: &RiskLevel) -> bool {
+        self.config.enabled && *severity >= self.config.alert_threshold
+    }
+
+    /// Notify about a monitor alert if it meets the configured threshold.
+    /// Delivery happens in the background so alerting never blocks the caller.
+    pub fn notify_alert(&self, alert: &Alert) {
+        let severity = alert.severity();
+        if !self.should_notify(&severity) {
+            return;
+        }
+
+        let details = match serde_json::to_value(alert) {
+            Ok(details) => details,
+            Err(e) => {
+                error!("Failed to serialize alert for notification: {e}");
+                return;
+            }
+        };
+
+        self.notify(severity, alert.summary(), details);
+    }
+
+    pub fn notify(&self, severity: RiskLevel, title: String, details: Value) {
+        if !self.should_notify(&severity) {
+            return;
+        }
+
+        let notification = Notification {
+            severity,
+            title,
+            details,
+            timestamp: Utc::now(),
+        };
+
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            notifier.deliver(&notification).await;
+        });
+    }
+
+    /// Send a notification to every configured target now, logging failures
+    pub async fn deliver(&self, notification: &Notification) {
+        for target in &self.config.webhooks {
+            if let Err(e) = self.send_webhook(target, notification).await {
+                error!(
+                    "Failed to deliver notification to {url}: {e}",
+                    url = target.url
+                );
+            }
+        }
+
+        if let Some(email) = &self.config.email
+            && let Err(e) = self.send_email(email, notification).await
+        {
+            error!("Failed to deliver email notification: {e}");
+        }
+    }
+
+    async fn send_webhook(
+        &self,
+        target: &WebhookTarget,
+        notification: &Notification,
+    ) -> Result<()> {
+        let payload = target.kind.payload(notification)?;
+
+        debug!("Sending notification webhook to {url}", url = target.url);
+
+        let response = self
+            .client
+            .post(&target.url)
+            .json(&payload)
+            .send()
+            .await
+            .context("Failed to send webhook request")?;
+
+        if !response.status().is_success() {
+            bail!("Webhook returned {status}", status = response.status());
+        }
+
+        Ok(())
+    }
+
+    async fn send_email(&self, email: &EmailConfig, notification: &Notification) -> Result<()> {
+        let body = email.message(notification)?;
+
+        let mut child = Command::new(&email.sendmail_path)
+            .arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to spawn {path}", path = email.sendmail_path))?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("Failed to open sendmail stdin")?;
+        stdin.write_all(body.as_bytes()).await?;
+        drop(stdin);
+
+        let status = child.wait().await.context("Failed to wait for sendmail")?;
+        if !status.success() {
+            bail!("sendmail exited with {status}");
+        }
+
+        info!("Sent email notification to {to}", to = email.to.join(", "));
+        Ok(())
+    }
+}
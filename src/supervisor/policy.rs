@@ -0,0 +1,183 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::{MCPRestrictions, SandboxMode};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PolicyConfig {
+    /// Number of policy violations tolerated before an agent is quarantined
+    pub quarantine_after_violations: u32,
+    /// Length of the sliding window used for tool rate limits
+    pub rate_limit_window_secs: u64,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            quarantine_after_violations: 3,
+            rate_limit_window_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PolicyDecision {
+    Allow,
+    Warn {
+        reason: String,
+    },
+    Throttle {
+        reason: String,
+        retry_after_secs: u64,
+    },
+    Quarantine {
+        reason: String,
+    },
+}
+
+// (agent_id, tool) -> timestamps of recent calls
+type CallWindows = HashMap<(String, String), VecDeque<DateTime<Utc>>>;
+
+pub struct PolicyEngine {
+    config: PolicyConfig,
+    call_windows: Mutex<CallWindows>,
+    violations: Mutex<HashMap<String, u32>>,
+}
+
+impl PolicyEngine {
+    pub fn new(config: PolicyConfig) -> Self {
+        Self {
+            config,
+            call_windows: Mutex::new(HashMap::new()),
+            violations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Evaluate a single tool call reported by an agent against its MCP restrictions.
+    ///
+    /// Tool names are expected in the `server:tool` form used by the MCP client.
+    pub async fn evaluate(
+        &self,
+        agent_id: &str,
+        restrictions: &MCPRestrictions,
+        mode: &SandboxMode,
+        tool: &str,
+    ) -> PolicyDecision {
+        let server = tool.split(':').next().unwrap_or(tool);
+
+        if !restrictions.allowed_servers.is_empty()
+            && !restrictions.allowed_servers.iter().any(|s| s == server)
+        {
+            let reason = format!("Server '{server}' is not in the allowed servers list");
+            return self.record_violation(agent_id, mode, reason).await;
+        }
+
+        if let Some(pattern) = restrictions
+            .blocked_tools
+            .iter()
+            .find(|pattern| tool_matches(pattern, tool))
+        {
+            let reason = format!("Tool '{tool}' is blocked by rule '{pattern}'");
+            return self.record_violation(agent_id, mode, reason).await;
+        }
+
+        if let Some(limit) = rate_limit_for(restrictions, tool) {
+            let now = Utc::now();
+            let window = ChronoDuration::seconds(self.config.rate_limit_window_secs as i64);
+
+            let mut call_windows = self.call_windows.lock().await;
+            let calls = call_windows
+                .entry((agent_id.to_string(), tool.to_string()))
+                .or_default();
+
+            while calls.front().is_some_and(|t| now - *t > window) {
+                calls.pop_front();
+            }
+
+            if calls.len() as u32 >= limit {
+                let retry_after_secs = calls
+                    .front()
+                    .map(|oldest| (window - (now - *oldest)).num_seconds().max(1) as u64)
+                    .unwrap_or(self.config.rate_limit_window_secs);
+
+                debug!("Agent {agent_id} exceeded rate limit of {limit} for {tool}");
+                return PolicyDecision::Throttle {
+                    reason: format!(
+                        "Tool '{tool}' exceeded {limit} calls per {window_secs}s",
+                        window_secs = self.config.rate_limit_window_secs
+                    ),
+                    retry_after_secs,
+                };
+            }
+
+            calls.push_back(now);
+        }
+
+        PolicyDecision::Allow
+    }
+
+    pub async fn violation_count(&self, agent_id: &str) -> u32 {
+        let violations = self.violations.lock().await;
+        violations.get(agent_id).copied().unwrap_or(0)
+    }
+
+    /// Forget all tracked state for an agent, e.g. once it has been stopped
+    pub async fn reset_agent(&self, agent_id: &str) {
+        self.violations.lock().await.remove(agent_id);
+        self.call_windows
+            .lock()
+            .await
+            .retain(|(id, _), _| id != agent_id);
+    }
+
+    async fn record_violation(
+        &self,
+        agent_id: &str,
+        mode: &SandboxMode,
+        reason: String,
+    ) -> PolicyDecision {
+        let mut violations = self.violations.lock().await;
+        let count = violations.entry(agent_id.to_string()).or_insert(0);
+        *count += 1;
+
+        // Strict sandboxes get no second chances
+        let threshold = match mode {
+            SandboxMode::Strict => 1,
+            _ => self.config.quarantine_after_violations.max(1),
+        };
+
+        warn!("Policy violation #{count} for agent {agent_id}: {reason}");
+
+        if *count >= threshold {
+            PolicyDecision::Quarantine { reason }
+        } else {
+            PolicyDecision::Warn { reason }
+        }
+    }
+}
+
+/// Match a tool name against a rule. Rules are either exact `server:tool` names,
+/// bare tool names, or prefixes ending in `*`.
+fn tool_matches(pattern: &str, tool: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        return tool.starts_with(prefix);
+    }
+
+    pattern == tool
+        || tool
+            .split_once(':')
+            .is_some_and(|(_, name)| name == pattern)
+}
+
+fn rate_limit_for(restrictions: &MCPRestrictions, tool: &str) -> Option<u32> {
+    restrictions
+        .tool_rate_limits
+        .iter()
+        .find(|(pattern, _)| tool_matches(pattern, tool))
+        .map(|(_, limit)| *limit)
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// A process that started, or exec'd a new program, inside a container
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecEvent {
+    pub timestamp: DateTime<Utc>,
+    pub pid: u32,
+    /// Full container id, taken from the process' cgroup
+    pub container_id: String,
+    pub comm: String,
+    pub cmdline: String,
+}
+
+/// Polls the host's process table for execs in containers.
+///
+/// This is a fallback, not an exec tracer: it is used because there is no
+/// eBPF backend, and it does not replace the `docker exec ps` process check.
+/// Each poll is a single pass over `/proc`, so it is cheap enough to run
+/// several times a second. Processes that start and exit between two polls
+/// are not seen, and no syscalls are recorded.
+pub struct ProcExecPoller {
+    proc_root: PathBuf,
+    /// Last seen command of every containerized process, by pid
+    seen: HashMap<u32, String>,
+    /// Containers with processes at the last poll
+    containers: HashSet<String>,
+}
+
+impl ProcExecPoller {
+    pub fn new(proc_root: impl Into<PathBuf>) -> Self {
+        Self {
+            proc_root: proc_root.into(),
+            seen: HashMap::new(),
+            containers: HashSet::new(),
+        }
+    }
+
+    /// Ids of the containers that had processes at the last poll
+    pub fn containers(&self) -> &HashSet<String> {
+        &self.containers
+    }
+
+    /// Processes that appeared or changed command since the last poll. The
+    /// first poll reports every containerized process already running.
+    pub fn poll(&mut self) -> Result<Vec<ExecEvent>> {
+        let entries = fs::read_dir(&self.proc_root).with_context(|| {
+            format!(
+                "Failed to read process table at {proc_root}",
+                proc_root = self.proc_root.display()
+            )
+        })?;
+
+        let mut events = Vec::new();
+        let mut current = HashMap::new();
+        let mut containers = HashSet::new();
+
+        for entry in entries.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+
+            // Processes can exit while we read them; skip those
+            let dir = entry.path();
+            let Ok(cgroup) = fs::read_to_string(dir.join("cgroup")) else {
+                continue;
+            };
+            let Some(container_id) = container_id_from_cgroup(&cgroup) else {
+                continue;
+            };
+            let Ok(comm) = fs::read_to_string(dir.join("comm")) else {
+                continue;
+            };
+            let comm = comm.trim_end().to_string();
+
+            if self.seen.get(&pid) != Some(&comm) {
+                let cmdline = fs::read(dir.join("cmdline"))
+                    .map(|raw| {
+                        raw.split(|&byte| byte == 0)
+                            .filter(|arg| !arg.is_empty())
+                            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .unwrap_or_default();
+
+                events.push(ExecEvent {
+                    timestamp: Utc::now(),
+                    pid,
+                    container_id: container_id.clone(),
+                    comm: comm.clone(),
+                    cmdline,
+                });
+            }
+
+            current.insert(pid, comm);
+            containers.insert(container_id);
+        }
+
+        self.seen = current;
+        self.containers = containers;
+        Ok(events)
+    }
+}
+
+/// Docker container id from a `/proc/<pid>/cgroup` file, for both cgroup v1
+/// (`/docker/<id>`) and systemd-managed v2 (`docker-<id>.scope`) layouts
+pub fn container_id_from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .flat_map(|path| path.split('/'))
+        .map(|segment| {
+            segment
+                .strip_prefix("docker-")
+                .and_then(|rest| rest.strip_suffix(".scope"))
+                .unwrap_or(segment)
+        })
+        .find(|candidate| candidate.len() == 64 && candidate.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_string)
+}
@@ -0,0 +1,320 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::ResourceUsage;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Pause the agent until its accounting period ends
+    Throttle,
+    /// Stop the agent
+    Stop,
+}
+
+/// Cumulative usage limits applied to every agent, per accounting period.
+/// Limits left unset are tracked but never enforced.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QuotaConfig {
+    /// Length of the accounting period; usage starts from zero in each one
+    pub period_hours: u64,
+    /// CPU time per period, e.g. 7200 for 2 CPU-hours
+    pub max_cpu_seconds: Option<f64>,
+    pub max_network_bytes: Option<u64>,
+    pub max_tool_calls: Option<u64>,
+    pub action: QuotaAction,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            period_hours: 24,
+            max_cpu_seconds: None,
+            max_network_bytes: None,
+            max_tool_calls: None,
+            action: QuotaAction::Throttle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaResource {
+    CpuSeconds,
+    NetworkBytes,
+    ToolCalls,
+}
+
+impl fmt::Display for QuotaResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaResource::CpuSeconds => write!(f, "CPU seconds"),
+            QuotaResource::NetworkBytes => write!(f, "network bytes"),
+            QuotaResource::ToolCalls => write!(f, "tool calls"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaBreach {
+    pub resource: QuotaResource,
+    pub used: f64,
+    pub limit: f64,
+}
+
+impl fmt::Display for QuotaBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{resource} quota exceeded ({used:.0} of {limit:.0})",
+            resource = self.resource,
+            used = self.used,
+            limit = self.limit
+        )
+    }
+}
+
+/// An agent's usage in its current accounting period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub cpu_seconds: f64,
+    pub network_bytes: u64,
+    pub tool_calls: u64,
+    /// First quota exceeded in this period, if any
+    pub exceeded: Option<QuotaBreach>,
+}
+
+struct AgentQuota {
+    usage: QuotaUsage,
+    last_sample_at: Option<DateTime<Utc>>,
+    // Network counters are cumulative since the agent started
+    last_network_bytes: Option<u64>,
+    throttled: bool,
+}
+
+impl AgentQuota {
+    fn new(now: DateTime<Utc>, period: ChronoDuration) -> Self {
+        Self {
+            usage: QuotaUsage {
+                period_start: now,
+                period_end: now + period,
+                cpu_seconds: 0.0,
+                network_bytes: 0,
+                tool_calls: 0,
+                exceeded: None,
+            },
+            last_sample_at: None,
+            last_network_bytes: None,
+            throttled: false,
+        }
+    }
+
+    /// Start a new period if the current one has ended. Returns whether it did.
+    fn roll_over(&mut self, now: DateTime<Utc>, period: ChronoDuration) -> bool {
+        if now < self.usage.period_end {
+            return false;
+        }
+
+        let mut start = self.usage.period_end;
+        while start + period <= now {
+            start += period;
+        }
+
+        self.usage = QuotaUsage {
+            period_start: start,
+            period_end: start + period,
+            cpu_seconds: 0.0,
+            network_bytes: 0,
+            tool_calls: 0,
+            exceeded: None,
+        };
+        true
+    }
+
+    /// Report a limit the first time it is crossed in a period
+    fn check(&mut self, config: &QuotaConfig) -> Option<QuotaBreach> {
+        if self.usage.exceeded.is_some() {
+            return None;
+        }
+
+        let limits = [
+            (
+                QuotaResource::CpuSeconds,
+                self.usage.cpu_seconds,
+                config.max_cpu_seconds,
+            ),
+            (
+                QuotaResource::NetworkBytes,
+                self.usage.network_bytes as f64,
+                config.max_network_bytes.map(|limit| limit as f64),
+            ),
+            (
+                QuotaResource::ToolCalls,
+                self.usage.tool_calls as f64,
+                config.max_tool_calls.map(|limit| limit as f64),
+            ),
+        ];
+
+        let breach = limits
+            .into_iter()
+            .find_map(|(resource, used, limit)| match limit {
+                Some(limit) if used > limit => Some(QuotaBreach {
+                    resource,
+                    used,
+                    limit,
+                }),
+                _ => None,
+            })?;
+
+        self.usage.exceeded = Some(breach.clone());
+        Some(breach)
+    }
+}
+
+/// Accumulates per-agent usage and reports quota breaches
+pub struct QuotaTracker {
+    config: QuotaConfig,
+    agents: Mutex<HashMap<String, AgentQuota>>,
+}
+
+impl QuotaTracker {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            agents: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    fn period(&self) -> ChronoDuration {
+        ChronoDuration::hours(self.config.period_hours.max(1) as i64)
+    }
+
+    /// Add a resource sample to the agent's totals. CPU time is integrated from
+    /// the usage percentage over the time since the previous sample.
+    pub async fn record_usage(
+        &self,
+        agent_id: &str,
+        usage: &ResourceUsage,
+        now: DateTime<Utc>,
+    ) -> Option<QuotaBreach> {
+        let period = self.period();
+        let mut agents = self.agents.lock().await;
+        let quota = agents
+            .entry(agent_id.to_string())
+            .or_insert_with(|| AgentQuota::new(now, period));
+        quota.roll_over(now, period);
+
+        if let Some(last) = quota.last_sample_at {
+            let elapsed_secs = (now - last).num_milliseconds().max(0) as f64 / 1000.0;
+            quota.usage.cpu_seconds += usage.cpu_percent / 100.0 * elapsed_secs;
+        }
+        quota.last_sample_at = Some(now);
+
+        let network_delta = match quota.last_network_bytes {
+            Some(previous) if usage.network_bytes >= previous => usage.network_bytes - previous,
+            // First sample, or the counter was reset by a container restart
+            _ => usage.network_bytes,
+        };
+        quota.usage.network_bytes += network_delta;
+        quota.last_network_bytes = Some(usage.network_bytes);
+
+        quota.check(&self.config)
+    }
+
+    pub async fn record_tool_call(
+        &self,
+        agent_id: &str,
+        now: DateTime<Utc>,
+    ) -> Option<QuotaBreach> {
+        let period = self.period();
+        let mut agents = self.agents.lock().await;
+        let quota = agents
+            .entry(agent_id.to_string())
+            .or_insert_with(|| AgentQuota::new(now, period));
+        quota.roll_over(now, period);
+
+        quota.usage.tool_calls += 1;
+        quota.check(&self.config)
+    }
+
+    pub async fn usage(&self, agent_id: &str) -> Option<QuotaUsage> {
+        let agents = self.agents.lock().await;
+        agents.get(agent_id).map(|quota| quota.usage.clone())
+    }
+
+    /// Remember that an agent was paused for exceeding its quota
+    pub async fn mark_throttled(&self, agent_id: &str) {
+        if let Some(quota) = self.agents.lock().await.get_mut(agent_id) {
+            quota.throttled = true;
+        }
+    }
+
+    /// Start new periods where due and return the throttled agents whose
+    /// period has ended, which may now be resumed
+    pub async fn roll_over(&self, now: DateTime<Utc>) -> Vec<String> {
+        let period = self.period();
+        let mut agents = self.agents.lock().await;
+
+        agents
+            .iter_mut()
+            .filter_map(|(agent_id, quota)| {
+                if !quota.roll_over(now, period) {
+                    return None;
+                }
+                debug!("Started new quota period for agent {agent_id}");
+                std::mem::take(&mut quota.throttled).then(|| agent_id.clone())
+            })
+            .collect()
+    }
+}
+
+/// Total bytes received and sent by a process, when it has a network namespace
+/// of its own. Processes sharing the host namespace would report host-wide
+/// traffic, so they are not measured.
+pub async fn read_process_network_bytes(pid: u32) -> Option<u64> {
+    let own_ns = tokio::fs::read_link("/proc/self/ns/net").await.ok()?;
+    let agent_ns = tokio::fs::read_link(format!("/proc/{pid}/ns/net"))
+        .await
+        .ok()?;
+    if own_ns == agent_ns {
+        return None;
+    }
+
+    let dev = match tokio::fs::read_to_string(format!("/proc/{pid}/net/dev")).await {
+        Ok(dev) => dev,
+        Err(e) => {
+            warn!("Failed to read network counters of process {pid}: {e}");
+            return None;
+        }
+    };
+
+    Some(parse_net_dev(&dev))
+}
+
+/// Sum received and transmitted bytes over all non-loopback interfaces
+fn parse_net_dev(dev: &str) -> u64 {
+    dev.lines()
+        .skip(2)
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(interface, _)| interface.trim() != "lo")
+        .map(|(_, counters)| {
+            let fields: Vec<u64> = counters
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect();
+            // Receive bytes is the first column, transmit bytes the ninth
+            fields.first().copied().unwrap_or(0) + fields.get(8).copied().unwrap_or(0)
+        })
+        .sum()
+}
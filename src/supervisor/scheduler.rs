@@ -0,0 +1,267 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, sleep};
+use tracing::{error, info};
+
+use super::{SandboxConfig, Supervisor};
+
+/// A scheduled change to the size of an agent pool, or a scheduled start
+/// or stop of a single agent.
+///
+/// Two entries for the same pool describe a window, e.g. scale "batch" to 3
+/// at `0 22 * * *` and back to 0 at `0 6 * * *`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduleConfig {
+    /// Pool the scheduled agents belong to
+    #[serde(default)]
+    pub pool: Option<String>,
+    /// Id of a single agent to start or stop instead of scaling a pool. It
+    /// keeps its directory and state database between runs.
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// Standard 5-field cron expression, evaluated in UTC
+    pub cron: String,
+    /// Number of agents the pool should run once the schedule fires. For an
+    /// `agent` schedule, 1 starts the agent and 0 stops it.
+    pub replicas: usize,
+    /// Template used to spawn pool agents
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Agent config used to spawn pool agents when no template is given.
+    /// Each agent runs a copy with its own id and state database.
+    #[serde(default)]
+    pub config_path: Option<String>,
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+}
+
+impl ScheduleConfig {
+    /// What the schedule acts on, for messages
+    pub fn target(&self) -> String {
+        match (&self.pool, &self.agent) {
+            (Some(pool), _) => format!("pool {pool}"),
+            (None, Some(agent)) => format!("agent {agent}"),
+            (None, None) => "nothing".to_string(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match (&self.pool, &self.agent) {
+            (Some(_), Some(_)) => bail!(
+                "Schedule '{cron}' names both a pool and an agent",
+                cron = self.cron
+            ),
+            (None, None) => bail!(
+                "Schedule '{cron}' needs a pool or an agent",
+                cron = self.cron
+            ),
+            (None, Some(agent)) => {
+                if agent.is_empty()
+                    || !agent
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    bail!("Scheduled agent id '{agent}' may only contain letters, digits, - and _");
+                }
+                if self.replicas > 1 {
+                    bail!(
+                        "Schedule for agent {agent} sets replicas to {replicas}, expected 0 or 1",
+                        replicas = self.replicas
+                    );
+                }
+            }
+            (Some(_), None) => {}
+        }
+
+        if self.replicas > 0 && self.template.is_none() && self.config_path.is_none() {
+            bail!(
+                "Schedule for {target} needs a template or config_path to spawn agents",
+                target = self.target()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Parsed cron expression: minute, hour, day of month, month, day of week
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Standard cron semantics: when both day fields are restricted,
+    // a time matches if either of them does
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            bail!(
+                "Cron expression '{expression}' must have 5 fields, found {count}",
+                count = fields.len()
+            );
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7)
+            .with_context(|| format!("Invalid day of week in '{expression}'"))?;
+        // Both 0 and 7 mean Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)
+                .with_context(|| format!("Invalid minute in '{expression}'"))?,
+            hours: parse_field(hour, 0, 23)
+                .with_context(|| format!("Invalid hour in '{expression}'"))?,
+            days_of_month: parse_field(dom, 1, 31)
+                .with_context(|| format!("Invalid day of month in '{expression}'"))?,
+            months: parse_field(month, 1, 12)
+                .with_context(|| format!("Invalid month in '{expression}'"))?,
+            days_of_week,
+            dom_restricted: dom != "*",
+            dow_restricted: dow != "*",
+        })
+    }
+
+    /// Whether the schedule fires in the minute containing `time`
+    pub fn matches(&self, time: &DateTime<Utc>) -> bool {
+        let dom_match = self.days_of_month & (1 << time.day()) != 0;
+        let dow_match = self.days_of_week & (1 << time.weekday().num_days_from_sunday()) != 0;
+
+        let day_match = if self.dom_restricted && self.dow_restricted {
+            dom_match || dow_match
+        } else {
+            dom_match && dow_match
+        };
+
+        self.minutes & (1 << time.minute()) != 0
+            && self.hours & (1 << time.hour()) != 0
+            && self.months & (1 << time.month()) != 0
+            && day_match
+    }
+}
+
+/// Parse one cron field (`*`, `5`, `1-5`, `*/15`, `0-30/10`, or a comma list)
+/// into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .with_context(|| format!("Invalid step '{step}'"))?;
+                if step == 0 {
+                    bail!("Step must be greater than zero");
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // "5/15" means starting at 5, every 15 until the end of the range
+            (value, if step.is_some() { max } else { value })
+        };
+
+        if start > end {
+            bail!("Range {start}-{end} is reversed");
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32> {
+    let parsed: u32 = value
+        .parse()
+        .with_context(|| format!("Invalid value '{value}'"))?;
+    if parsed < min || parsed > max {
+        bail!("Value {parsed} is outside {min}-{max}");
+    }
+    Ok(parsed)
+}
+
+/// Fires configured schedules once per minute
+pub struct Scheduler {
+    schedules: Vec<(ScheduleConfig, CronSchedule)>,
+}
+
+impl Scheduler {
+    pub fn new(schedules: &[ScheduleConfig]) -> Result<Self> {
+        let schedules = schedules
+            .iter()
+            .map(|schedule| {
+                schedule.validate()?;
+                let cron = CronSchedule::parse(&schedule.cron).with_context(|| {
+                    format!("Invalid schedule for {target}", target = schedule.target())
+                })?;
+                Ok((schedule.clone(), cron))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { schedules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schedules.is_empty()
+    }
+
+    /// Evaluate schedules at the start of every minute
+    pub async fn run(&self, supervisor: Supervisor) {
+        info!(
+            "Scheduler started with {count} schedules",
+            count = self.schedules.len()
+        );
+
+        loop {
+            // Sleep until just past the next minute boundary so each minute is evaluated once
+            let now = Utc::now();
+            let into_minute =
+                u64::from(now.second()) * 1000 + u64::from(now.timestamp_subsec_millis());
+            sleep(Duration::from_millis(
+                60_000 - into_minute.min(59_999) + 100,
+            ))
+            .await;
+
+            let now = Utc::now();
+            for (schedule, cron) in &self.schedules {
+                if !cron.matches(&now) {
+                    continue;
+                }
+
+                info!(
+                    "Schedule '{cron}' fired: setting {target} to {replicas}",
+                    cron = schedule.cron,
+                    target = schedule.target(),
+                    replicas = schedule.replicas
+                );
+
+                if let Err(e) = supervisor.run_schedule(schedule).await {
+                    error!(
+                        "Failed to apply schedule for {target}: {e}",
+                        target = schedule.target()
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::{Result, bail};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::LazyLock;
+use tokio::sync::Mutex;
+
+use super::security::SecurityFinding;
+use crate::state::StateManager;
+
+/// Memory entries read per pass; agents keeping more are only partly scanned
+const MAX_MEMORY_ENTRIES: usize = 10_000;
+
+/// Secrets remembered as already reported; past this the oldest are
+/// forgotten and may be reported again
+const MAX_REPORTED: usize = 10_000;
+
+/// Scan of agent memory and captured logs for leaked credentials
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SecretScanConfig {
+    pub enabled: bool,
+    pub scan_interval_secs: u64,
+    /// Replace secrets found in memory with a `[REDACTED:<kind>]` marker
+    pub redact_memory: bool,
+    /// Most recent log lines checked per agent on each pass
+    pub log_lines: usize,
+}
+
+impl Default for SecretScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scan_interval_secs: 300,
+            redact_memory: false,
+            log_lines: 1000,
+        }
+    }
+}
+
+const SEED_PHRASE: &str = "seed_phrase";
+const PRIVATE_KEY: &str = "private_key";
+
+// Checked in order; a match overlapping an earlier one is skipped, so more
+// specific formats come first
+static PATTERNS: LazyLock<Result<Vec<(&'static str, Regex)>, regex::Error>> = LazyLock::new(|| {
+    [
+        (
+            PRIVATE_KEY,
+            r"-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY-----(?s:.*?)(?:-----END (?:[A-Z0-9]+ )*PRIVATE KEY-----|$)",
+        ),
+        ("aws_access_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+        ("anthropic_api_key", r"\bsk-ant-[A-Za-z0-9_-]{20,}"),
+        ("openai_api_key", r"\bsk-(?:proj-)?[A-Za-z0-9_-]{20,}"),
+        ("github_token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+        ("slack_token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+        // BIP39 mnemonics are 12 to 24 words of 3 to 8 lowercase letters,
+        // checked against the wordlist once matched
+        (SEED_PHRASE, r"\b(?:[a-z]{3,8} ){11,23}[a-z]{3,8}\b"),
+    ]
+    .into_iter()
+    .map(|(kind, pattern)| Ok((kind, Regex::new(pattern)?)))
+    .collect()
+});
+
+fn patterns() -> Result<&'static [(&'static str, Regex)]> {
+    match &*PATTERNS {
+        Ok(patterns) => Ok(patterns),
+        Err(e) => bail!("Invalid secret pattern: {e}"),
+    }
+}
+
+/// Whether `phrase` has a BIP39 length and only words from the English list
+fn is_seed_phrase(phrase: &str) -> bool {
+    let words: Vec<&str> = phrase.split(' ').collect();
+    words.len().is_multiple_of(3)
+        && words
+            .iter()
+            .all(|word| bip39::Language::English.find_word(word).is_some())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretMatch {
+    pub kind: &'static str,
+    /// Enough of the secret to locate it, never the whole value
+    pub masked: String,
+    /// Tells apart secrets with the same masked form, without keeping them
+    fingerprint: u64,
+    start: usize,
+    end: usize,
+}
+
+/// Secrets in a piece of text, in order of appearance
+pub fn find_secrets(text: &str) -> Result<Vec<SecretMatch>> {
+    let mut found: Vec<SecretMatch> = Vec::new();
+
+    for (kind, pattern) in patterns()? {
+        for m in pattern.find_iter(text) {
+            if found
+                .iter()
+                .any(|other| m.start() < other.end && other.start < m.end())
+            {
+                continue;
+            }
+
+            let secret = m.as_str();
+            if *kind == SEED_PHRASE && !is_seed_phrase(secret) {
+                continue;
+            }
+
+            let mut hasher = DefaultHasher::new();
+            secret.hash(&mut hasher);
+
+            found.push(SecretMatch {
+                kind,
+                masked: mask(kind, secret),
+                fingerprint: hasher.finish(),
+                start: m.start(),
+                end: m.end(),
+            });
+        }
+    }
+
+    found.sort_by_key(|secret| secret.start);
+    Ok(found)
+}
+
+/// Replace every secret in `text` with a marker naming its kind
+pub fn redact(text: &str) -> Result<String> {
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for secret in find_secrets(text)? {
+        redacted.push_str(&text[last..secret.start]);
+        redacted.push_str(&format!("[REDACTED:{kind}]", kind = secret.kind));
+        last = secret.end;
+    }
+
+    redacted.push_str(&text[last..]);
+    Ok(redacted)
+}
+
+fn mask(kind: &str, secret: &str) -> String {
+    match kind {
+        PRIVATE_KEY => secret.lines().next().unwrap_or_default().to_string(),
+        SEED_PHRASE => format!(
+            "{first} ... ({count} words)",
+            first = secret.split(' ').next().unwrap_or_default(),
+            count = secret.split(' ').count()
+        ),
+        _ => format!(
+            "{prefix}... ({length} chars)",
+            prefix = secret.chars().take(6).collect::<String>(),
+            length = secret.len()
+        ),
+    }
+}
+
+/// Every string inside a JSON value
+fn strings_in(value: &Value) -> Vec<&str> {
+    match value {
+        Value::String(text) => vec![text],
+        Value::Array(items) => items.iter().flat_map(strings_in).collect(),
+        Value::Object(fields) => fields.values().flat_map(strings_in).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Redact the strings inside a JSON value, returning whether any changed
+pub fn redact_value(value: &mut Value) -> Result<bool> {
+    Ok(match value {
+        Value::String(text) => {
+            let redacted = redact(text)?;
+            let changed = redacted != *text;
+            *text = redacted;
+            changed
+        }
+        Value::Array(items) => {
+            let mut changed = false;
+            for item in items {
+                changed |= redact_value(item)?;
+            }
+            changed
+        }
+        Value::Object(fields) => {
+            let mut changed = false;
+            for field in fields.values_mut() {
+                changed |= redact_value(field)?;
+            }
+            changed
+        }
+        _ => false,
+    })
+}
+
+/// Keys of reported secrets, oldest forgotten first past `MAX_REPORTED`
+#[derive(Default)]
+struct Reported {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl Reported {
+    /// Remember `key`, returning whether it is new
+    fn insert(&mut self, key: String) -> bool {
+        if !self.keys.insert(key.clone()) {
+            return false;
+        }
+        self.order.push_back(key);
+        while self.order.len() > MAX_REPORTED {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// Finds secrets in agents' memory and logs, reporting each one only once
+pub struct SecretScanner {
+    config: SecretScanConfig,
+    reported: Mutex<Reported>,
+}
+
+impl SecretScanner {
+    pub fn new(config: SecretScanConfig) -> Self {
+        Self {
+            config,
+            reported: Mutex::new(Reported::default()),
+        }
+    }
+
+    pub fn config(&self) -> &SecretScanConfig {
+        &self.config
+    }
+
+    /// Check every memory value, redacting it in place if configured. The
+    /// state must be writable for redaction.
+    pub async fn scan_memory(
+        &self,
+        agent_id: &str,
+        state: &StateManager,
+    ) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+
+        for mut entry in state.list_memory(None, MAX_MEMORY_ENTRIES, 0).await? {
+            let secrets: Vec<SecretMatch> = strings_in(&entry.value)
+                .into_iter()
+                .map(find_secrets)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+            if secrets.is_empty() {
+                continue;
+            }
+
+            let source = format!("memory:{key}", key = entry.key);
+            findings.extend(self.new_findings(agent_id, &source, secrets).await);
+
+            if self.config.redact_memory && redact_value(&mut entry.value)? {
+                state.remember(&entry.key, entry.value).await?;
+            }
+        }
+
+        Ok(findings)
+    }
+
+    pub async fn scan_logs(
+        &self,
+        agent_id: &str,
+        lines: &[String],
+    ) -> Result<Vec<SecurityFinding>> {
+        let mut secrets = Vec::new();
+        for line in lines {
+            secrets.extend(find_secrets(line)?);
+        }
+        Ok(self.new_findings(agent_id, "logs", secrets).await)
+    }
+
+    async fn new_findings(
+        &self,
+        agent_id: &str,
+        source: &str,
+        secrets: Vec<SecretMatch>,
+    ) -> Vec<SecurityFinding> {
+        let mut reported = self.reported.lock().await;
+
+        secrets
+            .into_iter()
+            .filter(|secret| {
+                reported.insert(format!(
+                    "{agent_id}\n{source}\n{fingerprint:x}",
+                    fingerprint = secret.fingerprint
+                ))
+            })
+            .map(|secret| SecurityFinding::SecretExposure {
+                source: source.to_string(),
+                kind: secret.kind.to_string(),
+                excerpt: secret.masked,
+            })
+            .collect()
+    }
+}
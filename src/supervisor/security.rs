@@ -1,23 +1,193 @@
 use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use tokio::time::{Duration, interval};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant, interval};
 use tracing::{debug, error, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::checks::{
+    CheckConfig, CheckContext, CheckRegistry, EscapeCheck, FilesystemCheck, NetworkCheck,
+    PrivilegeCheck, ProcessCheck, SecurityCheck,
+};
+use super::notifier::Notifier;
+use super::proc_exec_poller::{ExecEvent, ProcExecPoller};
+use super::secrets::SecretScanConfig;
+use super::store::SupervisorStore;
+use super::yara::{self, YaraConfig};
+
+#[derive(Debug, Clone)]
 pub struct SecurityScanner {
     scan_interval: Duration,
-    container_whitelist: Vec<String>,
-    process_whitelist: Vec<String>,
-    syscall_whitelist: Vec<String>,
+    whitelists: Arc<RwLock<Whitelists>>,
+    whitelists_file: Option<PathBuf>,
+    proc_exec_fallback: bool,
+    proc_poll_interval: Duration,
+    checks: Arc<CheckRegistry>,
+    yara: YaraConfig,
+    notifier: Option<Notifier>,
+    store: Option<Arc<SupervisorStore>>,
+    /// Receives critical reports so the supervisor can contain the agent
+    escalation: Option<mpsc::UnboundedSender<SecurityReport>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScannerConfig {
+    /// Periodically inspect running containers for security findings
+    pub enabled: bool,
+    pub scan_interval_secs: u64,
+    #[serde(default)]
+    pub whitelists: Whitelists,
+    /// TOML file holding the whitelists instead of `whitelists`. It is read
+    /// again whenever the whitelists are reloaded.
+    #[serde(default)]
+    pub whitelists_file: Option<String>,
+    /// Fallback for the missing eBPF exec tracer: also poll the host process
+    /// table for execs in containers every `proc_poll_interval_ms`. The
+    /// `docker exec ps` process check still runs on every scan. No syscalls
+    /// are recorded, and processes that start and exit between two polls are
+    /// missed.
+    #[serde(default)]
+    pub proc_exec_fallback: bool,
+    #[serde(default = "default_proc_poll_interval_ms")]
+    pub proc_poll_interval_ms: u64,
+    /// What the supervisor does to an agent whose container has a critical report
+    #[serde(default)]
+    pub on_critical: CriticalAction,
+    #[serde(default)]
+    pub yara: YaraConfig,
+    #[serde(default)]
+    pub secrets: SecretScanConfig,
+    /// Per-check settings keyed by check name, for built-in and registered checks
+    #[serde(default)]
+    pub checks: HashMap<String, CheckConfig>,
+}
+
+/// Automatic response to critical security findings in an agent's container
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CriticalAction {
+    /// Only notify and store the report
+    Alert,
+    /// Freeze the agent until an operator approves it
+    #[default]
+    Quarantine,
+    /// Kill the agent
+    EmergencyStop,
+}
+
+fn default_proc_poll_interval_ms() -> u64 {
+    250
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scan_interval_secs: 30,
+            whitelists: Whitelists::default(),
+            whitelists_file: None,
+            proc_exec_fallback: false,
+            proc_poll_interval_ms: default_proc_poll_interval_ms(),
+            on_critical: CriticalAction::default(),
+            yara: YaraConfig::default(),
+            secrets: SecretScanConfig::default(),
+            checks: HashMap::new(),
+        }
+    }
+}
+
+/// What the scanner treats as expected. Lists left out keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Whitelists {
+    /// Container names that are not scanned at all
+    pub containers: Vec<String>,
+    /// Process names allowed in every container
+    pub processes: Vec<String>,
+    pub syscalls: Vec<String>,
+    /// Extra entries for individual agents, keyed by container name
+    pub agents: HashMap<String, WhitelistOverride>,
+}
+
+/// Added to the global whitelists for one agent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WhitelistOverride {
+    pub processes: Vec<String>,
+    pub syscalls: Vec<String>,
+}
+
+impl Default for Whitelists {
+    fn default() -> Self {
+        Self {
+            containers: vec!["replicante".to_string(), "supervisor".to_string()],
+            processes: vec![
+                "replicante".to_string(),
+                "supervisor".to_string(),
+                "sh".to_string(),
+                "bash".to_string(),
+            ],
+            syscalls: vec![
+                "read".to_string(),
+                "write".to_string(),
+                "open".to_string(),
+                "close".to_string(),
+                "stat".to_string(),
+                "mmap".to_string(),
+                "munmap".to_string(),
+                "brk".to_string(),
+                "rt_sigaction".to_string(),
+                "rt_sigprocmask".to_string(),
+                "ioctl".to_string(),
+                "access".to_string(),
+                "select".to_string(),
+                "poll".to_string(),
+                "epoll_wait".to_string(),
+            ],
+            agents: HashMap::new(),
+        }
+    }
+}
+
+impl Whitelists {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read whitelists file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse whitelists file {}", path.display()))
+    }
+
+    pub fn is_container_whitelisted(&self, container: &str) -> bool {
+        self.containers.iter().any(|name| name == container)
+    }
+
+    pub fn is_process_allowed(&self, container: &str, process_name: &str) -> bool {
+        let agent_processes = self
+            .agents
+            .get(container)
+            .map(|agent| agent.processes.as_slice())
+            .unwrap_or_default();
+
+        self.processes
+            .iter()
+            .chain(agent_processes)
+            .any(|p| process_name.contains(p.as_str()))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityReport {
-    timestamp: chrono::DateTime<chrono::Utc>,
-    container_id: String,
-    findings: Vec<SecurityFinding>,
-    risk_level: RiskLevel,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub container_id: String,
+    pub findings: Vec<SecurityFinding>,
+    pub risk_level: RiskLevel,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,9 +217,22 @@ pub enum SecurityFinding {
         value: f64,
         threshold: f64,
     },
+    /// A file in the agent's workspace matched a YARA rule
+    MalwareSignature {
+        rule: String,
+        path: String,
+    },
+    /// A credential in the agent's memory or logs
+    SecretExposure {
+        /// `memory:<key>` or `logs`
+        source: String,
+        kind: String,
+        /// Masked form of the secret
+        excerpt: String,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -57,40 +240,164 @@ pub enum RiskLevel {
     Critical,
 }
 
+impl SecurityFinding {
+    /// Risk of a single finding; a report takes the highest of its findings
+    pub fn risk_level(&self) -> RiskLevel {
+        match self {
+            SecurityFinding::PrivilegeEscalation { .. } => RiskLevel::Critical,
+            SecurityFinding::UnauthorizedProcess { .. }
+            | SecurityFinding::MalwareSignature { .. }
+            | SecurityFinding::SecretExposure { .. } => RiskLevel::High,
+            SecurityFinding::NetworkViolation { .. }
+            | SecurityFinding::FilesystemViolation { .. }
+            | SecurityFinding::SuspiciousSyscall { .. } => RiskLevel::Medium,
+            SecurityFinding::ResourceAnomaly { .. } => RiskLevel::Low,
+        }
+    }
+}
+
+impl RiskLevel {
+    /// Numeric severity, increasing with risk, used to filter stored reports
+    pub fn rank(&self) -> i64 {
+        match self {
+            RiskLevel::Low => 0,
+            RiskLevel::Medium => 1,
+            RiskLevel::High => 2,
+            RiskLevel::Critical => 3,
+        }
+    }
+}
+
+impl FromStr for RiskLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "low" => Ok(RiskLevel::Low),
+            "medium" => Ok(RiskLevel::Medium),
+            "high" => Ok(RiskLevel::High),
+            "critical" => Ok(RiskLevel::Critical),
+            _ => bail!("Unknown risk level '{value}', expected low, medium, high or critical"),
+        }
+    }
+}
+
 impl SecurityScanner {
     pub fn new() -> Self {
+        Self::with_checks(HashMap::new())
+    }
+
+    /// A scanner with the built-in checks, configured by `checks`
+    fn with_checks(checks: HashMap<String, CheckConfig>) -> Self {
+        let registry = CheckRegistry::new(checks);
+        registry.register(Arc::new(ProcessCheck));
+        registry.register(Arc::new(NetworkCheck));
+        registry.register(Arc::new(FilesystemCheck));
+        registry.register(Arc::new(PrivilegeCheck));
+        registry.register(Arc::new(EscapeCheck::new("/proc")));
+
         Self {
             scan_interval: Duration::from_secs(30),
-            container_whitelist: vec!["replicante".to_string(), "supervisor".to_string()],
-            process_whitelist: vec![
-                "replicante".to_string(),
-                "supervisor".to_string(),
-                "sh".to_string(),
-                "bash".to_string(),
-            ],
-            syscall_whitelist: vec![
-                "read".to_string(),
-                "write".to_string(),
-                "open".to_string(),
-                "close".to_string(),
-                "stat".to_string(),
-                "mmap".to_string(),
-                "munmap".to_string(),
-                "brk".to_string(),
-                "rt_sigaction".to_string(),
-                "rt_sigprocmask".to_string(),
-                "ioctl".to_string(),
-                "access".to_string(),
-                "select".to_string(),
-                "poll".to_string(),
-                "epoll_wait".to_string(),
-            ],
+            whitelists: Arc::new(RwLock::new(Whitelists::default())),
+            whitelists_file: None,
+            proc_exec_fallback: false,
+            proc_poll_interval: Duration::from_millis(default_proc_poll_interval_ms()),
+            checks: Arc::new(registry),
+            yara: YaraConfig::default(),
+            notifier: None,
+            store: None,
+            escalation: None,
         }
     }
 
+    pub async fn from_config(config: &ScannerConfig) -> Result<Self> {
+        let whitelists_file = config.whitelists_file.as_ref().map(PathBuf::from);
+        let whitelists = match &whitelists_file {
+            Some(path) => Whitelists::load(path).await?,
+            None => config.whitelists.clone(),
+        };
+
+        Ok(Self {
+            scan_interval: Duration::from_secs(config.scan_interval_secs),
+            whitelists: Arc::new(RwLock::new(whitelists)),
+            whitelists_file,
+            proc_exec_fallback: config.proc_exec_fallback,
+            proc_poll_interval: Duration::from_millis(config.proc_poll_interval_ms),
+            yara: config.yara.clone(),
+            ..Self::with_checks(config.checks.clone())
+        })
+    }
+
+    /// Run an additional check on every scanned container. Registering under
+    /// the name of an existing check replaces it.
+    pub fn register_check(&self, check: Arc<dyn SecurityCheck>) {
+        info!("Registered security check {name}", name = check.name());
+        self.checks.register(check);
+    }
+
+    /// Names of the checks run on each container scan
+    pub fn check_names(&self) -> Vec<String> {
+        self.checks.names()
+    }
+
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Persist reports with findings in the supervisor database
+    pub fn with_store(mut self, store: Arc<SupervisorStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Forward critical reports to the supervisor's automatic response
+    pub fn with_escalation(mut self, escalation: mpsc::UnboundedSender<SecurityReport>) -> Self {
+        self.escalation = Some(escalation);
+        self
+    }
+
+    /// The whitelists currently in effect
+    pub fn whitelists(&self) -> Whitelists {
+        self.whitelists
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Re-read the whitelists file and apply it to the following scans. The
+    /// current whitelists are kept if the file can't be loaded.
+    pub async fn reload_whitelists(&self) -> Result<Whitelists> {
+        let Some(path) = &self.whitelists_file else {
+            bail!("No whitelists_file configured for the security scanner");
+        };
+
+        let whitelists = Whitelists::load(path).await?;
+        *self
+            .whitelists
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = whitelists.clone();
+
+        info!(
+            "Reloaded security scanner whitelists from {path}",
+            path = path.display()
+        );
+        Ok(whitelists)
+    }
+
     pub async fn start_scanning(&self) -> Result<()> {
         info!("Starting security scanner");
 
+        if self.proc_exec_fallback {
+            let scanner = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = scanner.poll_proc_execs().await {
+                    error!("/proc exec poller stopped, only process scans remain: {e}");
+                }
+            });
+        }
+
+        self.checks.warn_unknown();
         let mut interval = interval(self.scan_interval);
 
         loop {
@@ -107,11 +414,22 @@ impl SecurityScanner {
 
         // Get list of running containers
         let containers = self.list_containers()?;
+        let whitelists = self.whitelists();
+        // Intervals are per check, not per container
+        let checks = self.checks.due(Instant::now());
+        if checks.is_empty() {
+            return Ok(());
+        }
 
         for container_id in containers {
-            if let Ok(report) = self.scan_container(&container_id).await
-                && !report.findings.is_empty()
-            {
+            if whitelists.is_container_whitelisted(&container_id) {
+                continue;
+            }
+
+            let report = self
+                .scan_container(&container_id, &whitelists, &checks)
+                .await;
+            if !report.findings.is_empty() {
                 self.handle_security_report(report).await?;
             }
         }
@@ -121,7 +439,7 @@ impl SecurityScanner {
 
     fn list_containers(&self) -> Result<Vec<String>> {
         let output = Command::new("docker")
-            .args(["ps", "-q"])
+            .args(["ps", "--format", "{{.Names}}"])
             .output()
             .context("Failed to list docker containers")?;
 
@@ -129,243 +447,166 @@ impl SecurityScanner {
             bail!("Failed to list containers");
         }
 
-        let container_ids = String::from_utf8_lossy(&output.stdout)
+        let containers = String::from_utf8_lossy(&output.stdout)
             .lines()
             .map(|s| s.to_string())
             .collect();
 
-        Ok(container_ids)
+        Ok(containers)
     }
 
-    async fn scan_container(&self, container_id: &str) -> Result<SecurityReport> {
+    async fn scan_container(
+        &self,
+        container_id: &str,
+        whitelists: &Whitelists,
+        checks: &[Arc<dyn SecurityCheck>],
+    ) -> SecurityReport {
+        let ctx = CheckContext {
+            container: container_id,
+            whitelists,
+        };
         let mut findings = Vec::new();
-        let mut max_risk = RiskLevel::Low;
 
-        // Check running processes
-        if let Ok(process_findings) = self.check_processes(container_id) {
-            for finding in process_findings {
-                if let SecurityFinding::UnauthorizedProcess { .. } = finding {
-                    max_risk = self.escalate_risk(max_risk, RiskLevel::High);
-                }
-                findings.push(finding);
-            }
-        }
-
-        // Check network connections
-        if let Ok(network_findings) = self.check_network(container_id) {
-            for finding in network_findings {
-                if let SecurityFinding::NetworkViolation { .. } = finding {
-                    max_risk = self.escalate_risk(max_risk, RiskLevel::Medium);
-                }
-                findings.push(finding);
-            }
-        }
-
-        // Check filesystem access
-        if let Ok(fs_findings) = self.check_filesystem(container_id) {
-            for finding in fs_findings {
-                if let SecurityFinding::FilesystemViolation { .. } = finding {
-                    max_risk = self.escalate_risk(max_risk, RiskLevel::Medium);
-                }
-                findings.push(finding);
+        for check in checks {
+            match check.run(&ctx).await {
+                Ok(found) => findings.extend(found),
+                Err(e) => debug!(
+                    "Security check {name} failed for container {container_id}: {e}",
+                    name = check.name()
+                ),
             }
         }
 
-        // Check for privilege escalation attempts
-        if let Ok(priv_findings) = self.check_privileges(container_id) {
-            for finding in priv_findings {
-                if let SecurityFinding::PrivilegeEscalation { .. } = finding {
-                    max_risk = self.escalate_risk(max_risk, RiskLevel::Critical);
-                }
-                findings.push(finding);
-            }
-        }
+        let risk_level = findings
+            .iter()
+            .map(SecurityFinding::risk_level)
+            .max()
+            .unwrap_or(RiskLevel::Low);
 
-        Ok(SecurityReport {
+        SecurityReport {
             timestamp: chrono::Utc::now(),
             container_id: container_id.to_string(),
             findings,
-            risk_level: max_risk,
-        })
+            risk_level,
+        }
     }
 
-    fn check_processes(&self, container_id: &str) -> Result<Vec<SecurityFinding>> {
-        let mut findings = Vec::new();
-
-        // Get process list from container
-        let output = Command::new("docker")
-            .args(["exec", container_id, "ps", "aux"])
-            .output()
-            .context("Failed to list processes in container")?;
-
-        if output.status.success() {
-            let processes = String::from_utf8_lossy(&output.stdout);
-
-            for line in processes.lines().skip(1) {
-                // Skip header
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() < 11 {
-                    continue;
-                }
-
-                let process_name = parts[10];
-
-                // Check if process is whitelisted
-                if !self
-                    .process_whitelist
-                    .iter()
-                    .any(|p| process_name.contains(p))
-                {
-                    findings.push(SecurityFinding::UnauthorizedProcess {
-                        pid: parts[1].parse().unwrap_or(0),
-                        name: process_name.to_string(),
-                        cmdline: parts[10..].join(" "),
-                    });
-                }
-            }
-        }
+    /// Scan an agent's workspace with the configured YARA rules, reporting
+    /// any match as a high risk finding
+    pub async fn scan_workspace(&self, agent_id: &str, workspace: &Path) -> Result<()> {
+        debug!(
+            "Running YARA scan of {workspace} for agent {agent_id}",
+            workspace = workspace.display()
+        );
+
+        let findings: Vec<SecurityFinding> = yara::scan_directory(&self.yara, workspace)
+            .await?
+            .into_iter()
+            .map(|found| SecurityFinding::MalwareSignature {
+                rule: found.rule,
+                path: found.path,
+            })
+            .collect();
 
-        Ok(findings)
+        self.report_findings(agent_id, findings, RiskLevel::High)
+            .await
     }
 
-    fn check_network(&self, container_id: &str) -> Result<Vec<SecurityFinding>> {
-        let mut findings = Vec::new();
-
-        // Check network connections
-        let output = Command::new("docker")
-            .args(["exec", container_id, "ss", "-tuln"])
-            .output()
-            .context("Failed to check network connections")?;
-
-        if output.status.success() {
-            let connections = String::from_utf8_lossy(&output.stdout);
-
-            for line in connections.lines().skip(1) {
-                // Skip header
-                if line.contains("LISTEN") || line.contains("ESTAB") {
-                    // Parse connection details
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() < 5 {
-                        continue;
-                    }
-
-                    let local_addr = parts[3];
-
-                    // Check for suspicious ports
-                    if let Some(port_str) = local_addr.split(':').next_back()
-                        && let Ok(port) = port_str.parse::<u16>()
-                        && self.is_suspicious_port(port)
-                    {
-                        findings.push(SecurityFinding::NetworkViolation {
-                            connection: local_addr.to_string(),
-                            port,
-                        });
-                    }
-                }
-            }
+    /// Report findings made outside the container scan, e.g. by the secret
+    /// scan, through the same logging, storage and escalation
+    pub async fn report_findings(
+        &self,
+        agent_id: &str,
+        findings: Vec<SecurityFinding>,
+        risk_level: RiskLevel,
+    ) -> Result<()> {
+        if findings.is_empty() {
+            return Ok(());
         }
 
-        Ok(findings)
-    }
+        let report = SecurityReport {
+            timestamp: chrono::Utc::now(),
+            container_id: agent_id.to_string(),
+            findings,
+            risk_level,
+        };
 
-    fn check_filesystem(&self, container_id: &str) -> Result<Vec<SecurityFinding>> {
-        let mut findings = Vec::new();
+        self.handle_security_report(report).await
+    }
 
-        // Check for suspicious file modifications
-        let output = Command::new("docker")
-            .args([
-                "exec",
-                container_id,
-                "find",
-                "/",
-                "-type",
-                "f",
-                "-mmin",
-                "-5",
-                "-ls",
-            ])
-            .output()
-            .context("Failed to check filesystem")?;
+    /// Report processes exec'd in containers as soon as a poll of `/proc`
+    /// sees them, between the scans' process checks
+    async fn poll_proc_execs(&self) -> Result<()> {
+        info!("Starting /proc exec poller");
 
-        if output.status.success() {
-            let files = String::from_utf8_lossy(&output.stdout);
+        let mut poller = ProcExecPoller::new("/proc");
+        // Container names by id, as the whitelists are keyed by name
+        let mut names = HashMap::new();
+        let mut ticker = interval(self.proc_poll_interval);
 
-            for line in files.lines() {
-                // Check for modifications to sensitive files
-                if line.contains("/etc/passwd")
-                    || line.contains("/etc/shadow")
-                    || line.contains("/etc/sudoers")
-                    || line.contains("/.ssh/")
-                {
-                    findings.push(SecurityFinding::FilesystemViolation {
-                        path: line.to_string(),
-                        operation: "modified".to_string(),
-                    });
+        loop {
+            ticker.tick().await;
+
+            // A pass over /proc is blocking file IO
+            let (returned, events) = tokio::task::spawn_blocking(move || {
+                let events = poller.poll();
+                (poller, events)
+            })
+            .await?;
+            poller = returned;
+            let events = events?;
+
+            // Forget the names of containers that are gone
+            names.retain(|id, _| poller.containers().contains(id));
+
+            for event in events {
+                if let Err(e) = self.handle_exec(event, &mut names).await {
+                    error!("Failed to handle exec event: {e}");
                 }
             }
         }
-
-        Ok(findings)
     }
 
-    fn check_privileges(&self, container_id: &str) -> Result<Vec<SecurityFinding>> {
-        let mut findings = Vec::new();
-
-        // Check if container is running as root
-        let output = Command::new("docker")
-            .args(["exec", container_id, "id"])
-            .output()
-            .context("Failed to check user privileges")?;
-
-        if output.status.success() {
-            let id_output = String::from_utf8_lossy(&output.stdout);
-
-            if id_output.contains("uid=0") {
-                findings.push(SecurityFinding::PrivilegeEscalation {
-                    details: "Container running as root".to_string(),
-                });
-            }
-        }
-
-        // Check for capability additions
-        let output = Command::new("docker")
-            .args([
-                "inspect",
-                container_id,
-                "--format",
-                "{{.HostConfig.CapAdd}}",
-            ])
-            .output()
-            .context("Failed to check capabilities")?;
-
-        if output.status.success() {
-            let caps = String::from_utf8_lossy(&output.stdout);
-
-            if caps.contains("SYS_ADMIN") || caps.contains("SYS_PTRACE") {
-                findings.push(SecurityFinding::PrivilegeEscalation {
-                    details: format!("Dangerous capabilities: {caps}"),
-                });
+    async fn handle_exec(
+        &self,
+        event: ExecEvent,
+        names: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        let container = match names.get(&event.container_id) {
+            Some(name) => name.clone(),
+            None => {
+                let name = container_name(&event.container_id).await;
+                names.insert(event.container_id.clone(), name.clone());
+                name
             }
+        };
+
+        // Match the first field of `ps aux` output, as the process scan does
+        let process_name = event
+            .cmdline
+            .split_whitespace()
+            .next()
+            .unwrap_or(&event.comm);
+
+        let whitelists = self.whitelists();
+        if whitelists.is_container_whitelisted(&container)
+            || whitelists.is_process_allowed(&container, process_name)
+        {
+            return Ok(());
         }
 
-        Ok(findings)
-    }
-
-    fn is_suspicious_port(&self, port: u16) -> bool {
-        match port {
-            22 | 23 | 135 | 139 | 445 | 3389 => true, // SSH, Telnet, SMB, RDP
-            1337 | 31337 | 4444 | 6666 => true,       // Common backdoor ports
-            _ => false,
-        }
-    }
-
-    fn escalate_risk(&self, current: RiskLevel, new: RiskLevel) -> RiskLevel {
-        match (current, new) {
-            (RiskLevel::Critical, _) | (_, RiskLevel::Critical) => RiskLevel::Critical,
-            (RiskLevel::High, _) | (_, RiskLevel::High) => RiskLevel::High,
-            (RiskLevel::Medium, _) | (_, RiskLevel::Medium) => RiskLevel::Medium,
-            _ => RiskLevel::Low,
-        }
+        let report = SecurityReport {
+            timestamp: event.timestamp,
+            container_id: container,
+            findings: vec![SecurityFinding::UnauthorizedProcess {
+                pid: event.pid,
+                name: process_name.to_string(),
+                cmdline: event.cmdline.clone(),
+            }],
+            risk_level: RiskLevel::High,
+        };
+
+        self.handle_security_report(report).await
     }
 
     async fn handle_security_report(&self, report: SecurityReport) -> Result<()> {
@@ -375,7 +616,21 @@ impl SecurityScanner {
                     "CRITICAL SECURITY ISSUE in container {}: {:?}",
                     report.container_id, report.findings
                 );
-                // Could trigger emergency shutdown
+                if let Some(escalation) = &self.escalation
+                    && escalation.send(report.clone()).is_err()
+                {
+                    warn!("Supervisor is no longer handling critical security reports");
+                }
+                if let Some(notifier) = &self.notifier {
+                    notifier.notify(
+                        RiskLevel::Critical,
+                        format!(
+                            "Critical security findings in container {container_id}",
+                            container_id = report.container_id
+                        ),
+                        serde_json::to_value(&report)?,
+                    );
+                }
             }
             RiskLevel::High => {
                 warn!(
@@ -404,16 +659,38 @@ impl SecurityScanner {
     }
 
     async fn store_security_report(&self, report: SecurityReport) -> Result<()> {
-        // TODO: Store in database or send to supervisor
-        let json = serde_json::to_string_pretty(&report)?;
+        let Some(store) = &self.store else {
+            debug!(
+                "No store configured, dropping security report for container {container_id}",
+                container_id = report.container_id
+            );
+            return Ok(());
+        };
 
-        // For now, just log it
-        debug!("Security report: {json}");
+        store.save_security_report(&report).await?;
 
         Ok(())
     }
 }
 
+/// Name of a container, or its short id if docker can't tell
+async fn container_name(container_id: &str) -> String {
+    tokio::process::Command::new("docker")
+        .args(["inspect", "--format", "{{.Name}}", container_id])
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .trim_start_matches('/')
+                .to_string()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| container_id.chars().take(12).collect())
+}
+
 impl Default for SecurityScanner {
     fn default() -> Self {
         Self::new()
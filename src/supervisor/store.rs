@@ -0,0 +1,611 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_rusqlite::Connection;
+use tracing::{debug, info};
+
+use super::monitor::IncidentReport;
+use super::network::{NetworkHistoryConfig, NetworkSample, SampleResolution};
+use super::security::{RiskLevel, SecurityReport};
+
+/// Persistent storage for supervisor-side records
+#[derive(Debug)]
+pub struct SupervisorStore {
+    conn: Arc<Connection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentSummary {
+    pub id: String,
+    pub agent_id: String,
+    pub trigger: String,
+    pub created_at: String,
+}
+
+/// A supervisor API call as it is recorded in the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub actor: String,
+    pub remote_addr: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub agent_id: Option<String>,
+    pub status: u16,
+    pub outcome: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSecurityReport {
+    pub id: i64,
+    #[serde(flatten)]
+    pub report: SecurityReport,
+}
+
+/// Filters for listing security reports. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityReportFilter {
+    pub container_id: Option<String>,
+    /// Only reports at or above this risk level
+    pub min_risk: Option<RiskLevel>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl SupervisorStore {
+    pub async fn new(database_path: &str) -> Result<Self> {
+        let conn = Connection::open(database_path)
+            .await
+            .context("Failed to open supervisor database")?;
+
+        conn.call(|conn| {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS incidents (
+                    id TEXT PRIMARY KEY,
+                    agent_id TEXT NOT NULL,
+                    trigger TEXT NOT NULL,
+                    report TEXT NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_incidents_agent ON incidents(agent_id)",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS audit_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    actor TEXT NOT NULL,
+                    remote_addr TEXT,
+                    method TEXT NOT NULL,
+                    path TEXT NOT NULL,
+                    agent_id TEXT,
+                    status INTEGER NOT NULL,
+                    outcome TEXT NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                )",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_audit_agent ON audit_log(agent_id)",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS security_reports (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    container_id TEXT NOT NULL,
+                    risk_level INTEGER NOT NULL,
+                    finding_count INTEGER NOT NULL,
+                    report TEXT NOT NULL,
+                    scanned_at INTEGER NOT NULL
+                )",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_security_reports_container
+                 ON security_reports(container_id, scanned_at)",
+                [],
+            )?;
+
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_security_reports_risk
+                 ON security_reports(risk_level, scanned_at)",
+                [],
+            )?;
+
+            // Raw samples are keyed by their timestamp, downsampled ones by
+            // the start of their bucket
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS network_samples (
+                    source TEXT NOT NULL,
+                    resolution TEXT NOT NULL,
+                    bucket INTEGER NOT NULL,
+                    duration_ms INTEGER NOT NULL,
+                    rx_bytes INTEGER NOT NULL,
+                    tx_bytes INTEGER NOT NULL,
+                    connections INTEGER NOT NULL,
+                    PRIMARY KEY (resolution, source, bucket)
+                )",
+                [],
+            )?;
+
+            // The audit log is append-only: reject any attempt to rewrite history
+            conn.execute_batch(
+                "CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+                 BEFORE UPDATE ON audit_log
+                 BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+                 CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+                 BEFORE DELETE ON audit_log
+                 BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;",
+            )?;
+
+            Ok(())
+        })
+        .await
+        .context("Failed to create supervisor tables")?;
+
+        info!("Supervisor database initialized at: {database_path}");
+
+        Ok(Self {
+            conn: Arc::new(conn),
+        })
+    }
+
+    pub async fn save_incident(&self, report: &IncidentReport) -> Result<()> {
+        let id = report.id.clone();
+        let agent_id = report.agent_id.clone();
+        let trigger = report.trigger.clone();
+        let report_json = serde_json::to_string(report)?;
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO incidents (id, agent_id, trigger, report) VALUES (?1, ?2, ?3, ?4)",
+                    params![id, agent_id, trigger, report_json],
+                )?;
+                Ok(())
+            })
+            .await
+            .context("Failed to save incident report")?;
+
+        debug!(
+            "Saved incident {id} for agent {agent_id}",
+            id = report.id,
+            agent_id = report.agent_id
+        );
+        Ok(())
+    }
+
+    pub async fn list_incidents(
+        &self,
+        agent_id: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<IncidentSummary>> {
+        let agent_id = agent_id.map(|id| id.to_string());
+
+        let incidents = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, agent_id, trigger, created_at
+                     FROM incidents
+                     WHERE (?1 IS NULL OR agent_id = ?1)
+                     ORDER BY created_at DESC, rowid DESC
+                     LIMIT ?2 OFFSET ?3",
+                )?;
+
+                let rows = stmt.query_map(params![agent_id, limit, offset], |row| {
+                    Ok(IncidentSummary {
+                        id: row.get(0)?,
+                        agent_id: row.get(1)?,
+                        trigger: row.get(2)?,
+                        created_at: row.get(3)?,
+                    })
+                })?;
+
+                let mut incidents = Vec::new();
+                for row in rows {
+                    incidents.push(row?);
+                }
+
+                Ok(incidents)
+            })
+            .await
+            .context("Failed to list incidents")?;
+
+        Ok(incidents)
+    }
+
+    pub async fn get_incident(&self, id: &str) -> Result<Option<IncidentReport>> {
+        let id = id.to_string();
+
+        let report_json = self
+            .conn
+            .call(move |conn| {
+                let report = conn
+                    .query_row(
+                        "SELECT report FROM incidents WHERE id = ?1",
+                        params![id],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .optional()?;
+                Ok(report)
+            })
+            .await
+            .context("Failed to get incident")?;
+
+        // Parse JSON outside the closure to avoid error type issues
+        match report_json {
+            Some(json) => Ok(Some(
+                serde_json::from_str(&json).context("Failed to parse stored incident report")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn record_audit(&self, record: AuditRecord) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO audit_log (actor, remote_addr, method, path, agent_id, status, outcome)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        record.actor,
+                        record.remote_addr,
+                        record.method,
+                        record.path,
+                        record.agent_id,
+                        record.status,
+                        record.outcome
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+            .context("Failed to record audit entry")?;
+
+        Ok(())
+    }
+
+    /// Audit entries, newest first, optionally filtered by agent and actor
+    pub async fn list_audit(
+        &self,
+        agent_id: Option<&str>,
+        actor: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AuditEntry>> {
+        let agent_id = agent_id.map(|id| id.to_string());
+        let actor = actor.map(|actor| actor.to_string());
+
+        let entries = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, actor, remote_addr, method, path, agent_id, status, outcome, created_at
+                     FROM audit_log
+                     WHERE (?1 IS NULL OR agent_id = ?1) AND (?2 IS NULL OR actor = ?2)
+                     ORDER BY id DESC
+                     LIMIT ?3 OFFSET ?4",
+                )?;
+
+                let rows = stmt.query_map(params![agent_id, actor, limit, offset], |row| {
+                    Ok(AuditEntry {
+                        id: row.get(0)?,
+                        record: AuditRecord {
+                            actor: row.get(1)?,
+                            remote_addr: row.get(2)?,
+                            method: row.get(3)?,
+                            path: row.get(4)?,
+                            agent_id: row.get(5)?,
+                            status: row.get(6)?,
+                            outcome: row.get(7)?,
+                        },
+                        created_at: row.get(8)?,
+                    })
+                })?;
+
+                let mut entries = Vec::new();
+                for row in rows {
+                    entries.push(row?);
+                }
+
+                Ok(entries)
+            })
+            .await
+            .context("Failed to list audit log")?;
+
+        Ok(entries)
+    }
+
+    pub async fn save_security_report(&self, report: &SecurityReport) -> Result<i64> {
+        let container_id = report.container_id.clone();
+        let risk_level = report.risk_level.rank();
+        let finding_count = report.findings.len();
+        let scanned_at = report.timestamp.timestamp_millis();
+        let report_json = serde_json::to_string(report)?;
+
+        let id = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO security_reports (container_id, risk_level, finding_count, report, scanned_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![container_id, risk_level, finding_count, report_json, scanned_at],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await
+            .context("Failed to save security report")?;
+
+        debug!(
+            "Saved security report {id} for container {container_id}",
+            container_id = report.container_id
+        );
+        Ok(id)
+    }
+
+    /// Security reports, newest first
+    pub async fn list_security_reports(
+        &self,
+        filter: &SecurityReportFilter,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<StoredSecurityReport>> {
+        let container_id = filter.container_id.clone();
+        let min_risk = filter.min_risk.as_ref().map(RiskLevel::rank);
+        let since = filter.since.map(|time| time.timestamp_millis());
+        let until = filter.until.map(|time| time.timestamp_millis());
+
+        let rows = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, report
+                     FROM security_reports
+                     WHERE (?1 IS NULL OR container_id = ?1)
+                       AND (?2 IS NULL OR risk_level >= ?2)
+                       AND (?3 IS NULL OR scanned_at >= ?3)
+                       AND (?4 IS NULL OR scanned_at <= ?4)
+                     ORDER BY scanned_at DESC, id DESC
+                     LIMIT ?5 OFFSET ?6",
+                )?;
+
+                let rows = stmt.query_map(
+                    params![container_id, min_risk, since, until, limit, offset],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+                )?;
+
+                let mut reports = Vec::new();
+                for row in rows {
+                    reports.push(row?);
+                }
+
+                Ok(reports)
+            })
+            .await
+            .context("Failed to list security reports")?;
+
+        rows.into_iter()
+            .map(|(id, json)| {
+                let report = serde_json::from_str(&json)
+                    .with_context(|| format!("Failed to parse stored security report {id}"))?;
+                Ok(StoredSecurityReport { id, report })
+            })
+            .collect()
+    }
+
+    pub async fn get_security_report(&self, id: i64) -> Result<Option<StoredSecurityReport>> {
+        let report_json = self
+            .conn
+            .call(move |conn| {
+                let report = conn
+                    .query_row(
+                        "SELECT report FROM security_reports WHERE id = ?1",
+                        params![id],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .optional()?;
+                Ok(report)
+            })
+            .await
+            .context("Failed to get security report")?;
+
+        match report_json {
+            Some(json) => Ok(Some(StoredSecurityReport {
+                id,
+                report: serde_json::from_str(&json)
+                    .context("Failed to parse stored security report")?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores samples at every resolution, adding each one to the minute
+    /// and hour buckets it falls in
+    pub async fn save_network_samples(&self, samples: &[NetworkSample]) -> Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<_> = samples
+            .iter()
+            .flat_map(|sample| {
+                let timestamp = sample.timestamp.timestamp_millis();
+                SampleResolution::ALL.map(|resolution| {
+                    let width = resolution.bucket_secs() * 1000;
+                    let bucket = if width == 0 {
+                        timestamp
+                    } else {
+                        timestamp - timestamp.rem_euclid(width)
+                    };
+                    (
+                        sample.source.clone(),
+                        resolution.as_str(),
+                        bucket,
+                        sample.duration_ms,
+                        sample.rx_bytes as i64,
+                        sample.tx_bytes as i64,
+                        sample.connections as i64,
+                    )
+                })
+            })
+            .collect();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO network_samples
+                            (source, resolution, bucket, duration_ms, rx_bytes, tx_bytes, connections)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                         ON CONFLICT (resolution, source, bucket) DO UPDATE SET
+                            duration_ms = duration_ms + excluded.duration_ms,
+                            rx_bytes = rx_bytes + excluded.rx_bytes,
+                            tx_bytes = tx_bytes + excluded.tx_bytes,
+                            connections = MAX(connections, excluded.connections)",
+                    )?;
+                    for (source, resolution, bucket, duration_ms, rx, tx_bytes, connections) in rows {
+                        stmt.execute(params![
+                            source,
+                            resolution,
+                            bucket,
+                            duration_ms,
+                            rx,
+                            tx_bytes,
+                            connections
+                        ])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+            .context("Failed to save network samples")?;
+
+        debug!("Saved {count} network samples", count = samples.len());
+        Ok(())
+    }
+
+    /// Network samples of a source at one resolution, oldest first
+    pub async fn list_network_samples(
+        &self,
+        source: Option<String>,
+        resolution: SampleResolution,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<NetworkSample>> {
+        let since = since.map(|time| time.timestamp_millis());
+        let until = until.map(|time| time.timestamp_millis());
+
+        let rows = self
+            .conn
+            .call(move |conn| {
+                // Take the newest rows within the limit, then put them back in order
+                let mut stmt = conn.prepare(
+                    "SELECT source, bucket, duration_ms, rx_bytes, tx_bytes, connections
+                     FROM (
+                        SELECT * FROM network_samples
+                        WHERE resolution = ?1
+                          AND (?2 IS NULL OR source = ?2)
+                          AND (?3 IS NULL OR bucket >= ?3)
+                          AND (?4 IS NULL OR bucket <= ?4)
+                        ORDER BY bucket DESC, source DESC
+                        LIMIT ?5
+                     )
+                     ORDER BY bucket ASC, source ASC",
+                )?;
+
+                let rows = stmt.query_map(
+                    params![resolution.as_str(), source, since, until, limit],
+                    |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, i64>(1)?,
+                            row.get::<_, i64>(2)?,
+                            row.get::<_, i64>(3)?,
+                            row.get::<_, i64>(4)?,
+                            row.get::<_, i64>(5)?,
+                        ))
+                    },
+                )?;
+
+                let mut samples = Vec::new();
+                for row in rows {
+                    samples.push(row?);
+                }
+
+                Ok(samples)
+            })
+            .await
+            .context("Failed to list network samples")?;
+
+        rows.into_iter()
+            .map(
+                |(source, bucket, duration_ms, rx_bytes, tx_bytes, connections)| {
+                    let timestamp = DateTime::from_timestamp_millis(bucket)
+                        .with_context(|| format!("Invalid network sample time {bucket}"))?;
+                    Ok(NetworkSample {
+                        source,
+                        timestamp,
+                        duration_ms,
+                        rx_bytes: rx_bytes as u64,
+                        tx_bytes: tx_bytes as u64,
+                        connections: connections as usize,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    /// Deletes samples older than their resolution's retention, returning
+    /// how many were removed
+    pub async fn prune_network_samples(&self, config: &NetworkHistoryConfig) -> Result<usize> {
+        let now = Utc::now().timestamp_millis();
+        let cutoffs: Vec<_> = SampleResolution::ALL
+            .iter()
+            .map(|&resolution| {
+                let retention = config.retention(resolution).as_millis() as i64;
+                (resolution.as_str(), now.saturating_sub(retention))
+            })
+            .collect();
+
+        let removed = self
+            .conn
+            .call(move |conn| {
+                let mut removed = 0;
+                for (resolution, cutoff) in cutoffs {
+                    removed += conn.execute(
+                        "DELETE FROM network_samples WHERE resolution = ?1 AND bucket < ?2",
+                        params![resolution, cutoff],
+                    )?;
+                }
+                Ok(removed)
+            })
+            .await
+            .context("Failed to prune network samples")?;
+
+        if removed > 0 {
+            debug!("Pruned {removed} network samples");
+        }
+        Ok(removed)
+    }
+}
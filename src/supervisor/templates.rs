@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{debug, info};
+
+use super::SandboxConfig;
+use crate::config::AgentConfig;
+use crate::llm::LLMConfig;
+use crate::{Config, MCPServerConfig};
+
+/// Templates shipped with the supervisor, embedded at compile time
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "explorer.toml",
+        include_str!("../../config/templates/explorer.toml"),
+    ),
+    (
+        "observer.toml",
+        include_str!("../../config/templates/observer.toml"),
+    ),
+];
+
+/// A preconfigured agent profile: goals, MCP servers and sandbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub initial_goals: String,
+    #[serde(default)]
+    pub reasoning_interval_secs: Option<u64>,
+    /// Falls back to the default LLM configuration when omitted
+    #[serde(default)]
+    pub llm: Option<LLMConfig>,
+    #[serde(default)]
+    pub mcp_servers: Vec<MCPServerConfig>,
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateSummary {
+    pub name: String,
+    pub description: String,
+    pub mcp_servers: Vec<String>,
+    pub sandboxed: bool,
+}
+
+impl AgentTemplate {
+    /// Build a full agent config for a new agent spawned from this template
+    pub fn to_agent_config(&self, agent_id: &str, database_path: &str) -> Config {
+        let defaults = Config::default();
+
+        Config {
+            agent: AgentConfig {
+                id: Some(agent_id.to_string()),
+                log_level: defaults.agent.log_level,
+                initial_goals: Some(self.initial_goals.clone()),
+                reasoning_interval_secs: self
+                    .reasoning_interval_secs
+                    .unwrap_or(defaults.agent.reasoning_interval_secs),
+                tuning: defaults.agent.tuning,
+            },
+            llm: self.llm.clone().unwrap_or(defaults.llm),
+            mcp_servers: self.mcp_servers.clone(),
+            database_path: database_path.to_string(),
+            secrets: None,
+            logging: defaults.logging,
+            metrics: defaults.metrics,
+            supervisor: defaults.supervisor,
+            wasm_tools: Vec::new(),
+        }
+    }
+
+    pub fn summary(&self) -> TemplateSummary {
+        TemplateSummary {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            mcp_servers: self.mcp_servers.iter().map(|s| s.name.clone()).collect(),
+            sandboxed: self.sandbox.as_ref().is_some_and(|s| s.enabled),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TemplateLibrary {
+    templates: BTreeMap<String, AgentTemplate>,
+}
+
+impl TemplateLibrary {
+    pub fn builtin() -> Result<Self> {
+        let mut library = Self::default();
+        for (file, contents) in BUILTIN_TEMPLATES {
+            let template: AgentTemplate = toml::from_str(contents)
+                .with_context(|| format!("Failed to parse built-in template {file}"))?;
+            library.insert(template);
+        }
+        Ok(library)
+    }
+
+    /// Load the built-in templates plus every `*.toml` file in `dir`.
+    /// Templates from `dir` replace built-ins with the same name.
+    pub async fn load(dir: Option<&str>) -> Result<Self> {
+        let mut library = Self::builtin()?;
+
+        let Some(dir) = dir else {
+            return Ok(library);
+        };
+
+        if !Path::new(dir).exists() {
+            debug!("Template directory {dir} does not exist, using built-in templates");
+            return Ok(library);
+        }
+
+        let mut entries = tokio::fs::read_dir(dir)
+            .await
+            .with_context(|| format!("Failed to read template directory {dir}"))?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+
+            let contents = tokio::fs::read_to_string(&path).await?;
+            let template: AgentTemplate = toml::from_str(&contents).with_context(|| {
+                format!("Failed to parse template {path}", path = path.display())
+            })?;
+            library.insert(template);
+        }
+
+        info!(
+            "Loaded {count} agent templates",
+            count = library.templates.len()
+        );
+        Ok(library)
+    }
+
+    pub fn insert(&mut self, template: AgentTemplate) {
+        self.templates.insert(template.name.clone(), template);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AgentTemplate> {
+        self.templates.get(name)
+    }
+
+    pub fn list(&self) -> Vec<TemplateSummary> {
+        self.templates
+            .values()
+            .map(AgentTemplate::summary)
+            .collect()
+    }
+}
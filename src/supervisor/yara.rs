@@ -0,0 +1,75 @@
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command;
+
+/// Scheduled YARA scan of agent workspaces, run with the `yara` command
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct YaraConfig {
+    pub enabled: bool,
+    /// The yara command, looked up in PATH unless it is a path
+    pub binary: String,
+    /// Rules file, in source or compiled (`yarac`) form; may `include` others
+    pub rules_file: String,
+    pub scan_interval_secs: u64,
+    /// Per-file scan timeout passed to yara
+    pub timeout_secs: u64,
+}
+
+impl Default for YaraConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            binary: "yara".to_string(),
+            rules_file: "config/yara/agent_workspace.yar".to_string(),
+            scan_interval_secs: 3600,
+            timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct YaraMatch {
+    pub rule: String,
+    pub path: String,
+}
+
+/// Recursively scan a directory, returning every rule match
+pub async fn scan_directory(config: &YaraConfig, dir: &Path) -> Result<Vec<YaraMatch>> {
+    let output = Command::new(&config.binary)
+        .arg("--recursive")
+        .arg("--no-warnings")
+        .arg(format!(
+            "--timeout={timeout}",
+            timeout = config.timeout_secs
+        ))
+        .arg(&config.rules_file)
+        .arg(dir)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {binary}", binary = config.binary))?;
+
+    if !output.status.success() {
+        bail!(
+            "yara scan of {dir} failed: {stderr}",
+            dir = dir.display(),
+            stderr = String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(parse_matches(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse yara's default output, one `<rule> <path>` line per match
+pub fn parse_matches(output: &str) -> Vec<YaraMatch> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(rule, path)| YaraMatch {
+            rule: rule.to_string(),
+            path: path.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,329 @@
+//! A programmable in-process MCP server for tests. [`MockServer`] answers
+//! through the same [`McpServer`] dispatch as the stdio servers in
+//! `src/bin`, but over in-memory streams, so client tests run without
+//! building or spawning anything. Each tool answers with canned output, a
+//! function of its arguments, or an error, after an optional latency.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use replicante::test_support::MockServer;
+//! use serde_json::json;
+//! use std::time::Duration;
+//!
+//! let mock = MockServer::new("mock")
+//!     .canned("get_weather", json!({"city": "Lisbon", "celsius": 21}))
+//!     .failing("broken", "disk full")
+//!     .latency("get_weather", Duration::from_millis(50));
+//! let client = mock.client().await?;
+//! let weather = client.use_tool("mock:get_weather", json!({})).await?;
+//! assert_eq!(weather["celsius"], 21);
+//! assert_eq!(mock.calls().len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::events::EventBus;
+use crate::jsonrpc::{DEFAULT_MAX_MESSAGE_BYTES, Framing, LineRead};
+use crate::mcp::{Connector, MCPClient, MCPServerConfig, ServerInput, ServerOutput};
+use crate::mcp_server::{McpServer, Tool, ToolContext, ToolHandler, ToolOutput, UnknownTool};
+
+/// Buffer of each in-memory stream between client and server
+const STREAM_BUFFER_BYTES: usize = 64 * 1024;
+
+type Respond = Arc<dyn Fn(&Value) -> Result<ToolOutput> + Send + Sync>;
+
+#[derive(Clone)]
+struct MockTool {
+    tool: Tool,
+    respond: Respond,
+    latency: Duration,
+}
+
+/// A `tools/call` the mock received
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockCall {
+    pub tool: String,
+    pub arguments: Value,
+}
+
+/// An in-process MCP server with programmable tools
+pub struct MockServer {
+    name: String,
+    framing: Framing,
+    max_message_bytes: usize,
+    tools: Vec<MockTool>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+}
+
+impl MockServer {
+    /// A server without tools; `name` is the prefix of its tools, as in
+    /// `name:tool`
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            framing: Framing::Newline,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            tools: Vec::new(),
+            calls: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// A tool answering with whatever `respond` returns for its arguments.
+    /// Errors become JSON-RPC errors, as with the stdio servers.
+    pub fn tool(
+        mut self,
+        tool: Tool,
+        respond: impl Fn(&Value) -> Result<ToolOutput> + Send + Sync + 'static,
+    ) -> Self {
+        self.tools
+            .retain(|existing| existing.tool.name() != tool.name());
+        self.tools.push(MockTool {
+            tool,
+            respond: Arc::new(respond),
+            latency: Duration::ZERO,
+        });
+        self
+    }
+
+    /// A tool always answering with `result`, as JSON text
+    pub fn canned(self, name: &str, result: Value) -> Self {
+        let output = ToolOutput::json(&result);
+        self.tool(Tool::new(name, "Returns a canned result"), move |_| {
+            Ok(output.clone())
+        })
+    }
+
+    /// A tool always reporting `message` in-band, with `isError` set
+    pub fn reporting_error(self, name: &str, message: &str) -> Self {
+        let output = ToolOutput::error(message);
+        self.tool(Tool::new(name, "Reports an error"), move |_| {
+            Ok(output.clone())
+        })
+    }
+
+    /// A tool always failing with a JSON-RPC error carrying `message`
+    pub fn failing(self, name: &str, message: &str) -> Self {
+        let message = message.to_string();
+        self.tool(Tool::new(name, "Fails"), move |_| Err(anyhow!("{message}")))
+    }
+
+    /// Delay every answer of `tool` by `latency`. Calls are answered one at
+    /// a time, as by a stdio server, so a slow call holds up those after it.
+    pub fn latency(mut self, tool: &str, latency: Duration) -> Self {
+        for mock in self
+            .tools
+            .iter_mut()
+            .filter(|mock| mock.tool.name() == tool)
+        {
+            mock.latency = latency;
+        }
+        self
+    }
+
+    /// Framing the client is configured with
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Message size limit of both client and server
+    pub fn max_message_bytes(mut self, max_message_bytes: usize) -> Self {
+        self.max_message_bytes = max_message_bytes;
+        self
+    }
+
+    /// Tool calls received so far, in order, unknown tools included
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// The client-side configuration of this server
+    pub fn config(&self) -> MCPServerConfig {
+        MCPServerConfig {
+            name: self.name.clone(),
+            transport: "in-process".to_string(),
+            command: String::new(),
+            args: Vec::new(),
+            retry_attempts: 1,
+            retry_delay_ms: 100,
+            health_check_interval_secs: 60,
+            max_message_bytes: self.max_message_bytes,
+            framing: self.framing,
+            env: BTreeMap::new(),
+            cwd: None,
+            user: None,
+        }
+    }
+
+    /// Starts a fresh server on every connection, made on start and on
+    /// every restart; all of them share the tools and the call log
+    pub fn connector(&self) -> Connector {
+        let name = self.name.clone();
+        let tools = self.tools.clone();
+        let calls = self.calls.clone();
+        let max_message_bytes = self.max_message_bytes;
+        Arc::new(move || {
+            let (client_input, server_input) = tokio::io::duplex(STREAM_BUFFER_BYTES);
+            let (server_output, client_output) = tokio::io::duplex(STREAM_BUFFER_BYTES);
+            let handler = MockHandler {
+                tools: tools.clone(),
+                calls: calls.clone(),
+            };
+            tokio::spawn(serve(
+                name.clone(),
+                handler,
+                max_message_bytes,
+                server_input,
+                server_output,
+            ));
+            let output: ServerOutput = Box::new(client_output);
+            let input: ServerInput = Box::new(client_input);
+            Ok((output, input))
+        })
+    }
+
+    /// A client connected to just this server
+    pub async fn client(&self) -> Result<MCPClient> {
+        connect(&[self], EventBus::default()).await
+    }
+}
+
+/// A client connected to all of `servers`, publishing on `events`
+pub async fn connect(servers: &[&MockServer], events: EventBus) -> Result<MCPClient> {
+    let servers = servers
+        .iter()
+        .map(|server| (server.config(), server.connector()))
+        .collect();
+    MCPClient::in_process(servers, events).await
+}
+
+struct MockHandler {
+    tools: Vec<MockTool>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+}
+
+impl ToolHandler for MockHandler {
+    fn tools(&self) -> Vec<Tool> {
+        self.tools.iter().map(|mock| mock.tool.clone()).collect()
+    }
+
+    fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        _context: &ToolContext,
+    ) -> Result<ToolOutput> {
+        self.calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(MockCall {
+                tool: name.to_string(),
+                arguments: arguments.clone(),
+            });
+        let mock = self
+            .tools
+            .iter()
+            .find(|mock| mock.tool.name() == name)
+            .ok_or_else(|| UnknownTool(name.to_string()))?;
+        std::thread::sleep(mock.latency);
+        (mock.respond)(arguments)
+    }
+}
+
+/// A request for the server thread, with where to send its response
+type Job = (Value, oneshot::Sender<Option<Value>>);
+
+/// Answer requests read from `input` on `output` until `input` closes. The
+/// [`McpServer`] runs on a thread of its own, as its handlers are neither
+/// `Send` nor async.
+async fn serve(
+    name: String,
+    handler: MockHandler,
+    max_message_bytes: usize,
+    input: DuplexStream,
+    mut output: DuplexStream,
+) -> Result<()> {
+    let (jobs, queue) = mpsc::channel::<Job>();
+    let server_name = name.clone();
+    std::thread::spawn(move || {
+        let mut server =
+            McpServer::new(server_name, "Mock MCP", handler).max_message_bytes(max_message_bytes);
+        while let Ok((request, reply)) = queue.recv() {
+            let _ = reply.send(server.handle_request(&request));
+        }
+    });
+
+    let mut reader = BufReader::new(input);
+    let framing = Framing::detect(reader.fill_buf().await?);
+    let mut buffer = Vec::new();
+    loop {
+        match framing
+            .read_async(&mut reader, &mut buffer, max_message_bytes)
+            .await?
+        {
+            LineRead::Eof => break,
+            // The client refuses to send these, so there is nothing to answer
+            LineRead::TooLarge(_) => continue,
+            LineRead::Line => {}
+        }
+        let Ok(request) = serde_json::from_slice::<Value>(&buffer) else {
+            continue;
+        };
+
+        let (reply, response) = oneshot::channel();
+        if jobs.send((request, reply)).is_err() {
+            break;
+        }
+        if let Ok(Some(response)) = response.await {
+            output
+                .write_all(framing.encode(&response.to_string()).as_bytes())
+                .await?;
+            output.flush().await?;
+        }
+    }
+
+    debug!("Mock MCP server {name} input closed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_mock_server_answers_from_its_tools() -> Result<()> {
+        let mock = MockServer::new("mock")
+            .canned("status", json!({"ok": true}))
+            .reporting_error("flaky", "try again")
+            .failing("broken", "disk full");
+        let client = mock.client().await?;
+
+        let mut tools = client.list_tools().await?;
+        tools.sort();
+        assert_eq!(tools, ["mock:broken", "mock:flaky", "mock:status"]);
+
+        assert_eq!(client.use_tool("mock:status", json!({})).await?["ok"], true);
+        let flaky = client.call_tool("mock:flaky", json!({})).await?;
+        assert_eq!(flaky["isError"], true);
+        let error = client
+            .call_tool("mock:broken", json!({"path": "/"}))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("disk full"), "{error}");
+
+        let called: Vec<_> = mock.calls().into_iter().map(|call| call.tool).collect();
+        assert_eq!(called, ["status", "flaky", "broken"]);
+        Ok(())
+    }
+}
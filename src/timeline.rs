@@ -0,0 +1,246 @@
+//! Rendering of the decision history as a timeline graph, for reports and
+//! debugging: Mermaid and Graphviz DOT flowcharts, or plain JSON.
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::DecisionRecord;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineFormat {
+    Mermaid,
+    Dot,
+    Json,
+}
+
+impl FromStr for TimelineFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "mermaid" => Ok(TimelineFormat::Mermaid),
+            "dot" => Ok(TimelineFormat::Dot),
+            "json" => Ok(TimelineFormat::Json),
+            _ => bail!("Unknown timeline format '{value}', expected mermaid, dot or json"),
+        }
+    }
+}
+
+/// One decision on the timeline
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub action: String,
+    pub parameters: Option<Value>,
+    /// Result status, or "pending" for a decision without a result
+    pub status: String,
+    pub duration_ms: Option<u64>,
+    pub summary: Option<String>,
+    /// The failed decision this one repeats with the same action and
+    /// parameters
+    pub retry_of: Option<i64>,
+    /// 1 for a first try, 2 for its first retry...
+    pub attempt: u32,
+}
+
+/// `decisions`, oldest first, as timeline entries with retries linked to
+/// the attempts they repeat
+pub fn timeline(decisions: &[DecisionRecord]) -> Vec<TimelineEntry> {
+    let mut entries: Vec<TimelineEntry> = Vec::with_capacity(decisions.len());
+    for decision in decisions {
+        let (status, duration_ms, summary) = match &decision.result {
+            Some(result) => (
+                result.status.clone(),
+                result.duration_ms,
+                result.summary.clone(),
+            ),
+            None => ("pending".to_string(), None, None),
+        };
+        let retried = entries.last().filter(|previous| {
+            !matches!(previous.status.as_str(), "success" | "pending")
+                && previous.action == decision.action
+                && previous.parameters == decision.parameters
+        });
+        entries.push(TimelineEntry {
+            id: decision.id,
+            timestamp: decision.timestamp,
+            action: decision.action.clone(),
+            parameters: decision.parameters.clone(),
+            status,
+            duration_ms,
+            summary,
+            retry_of: retried.map(|previous| previous.id),
+            attempt: retried.map_or(1, |previous| previous.attempt + 1),
+        });
+    }
+    entries
+}
+
+/// The timeline of `decisions` in `format`
+pub fn render(decisions: &[DecisionRecord], format: TimelineFormat) -> Result<String> {
+    let entries = timeline(decisions);
+    Ok(match format {
+        TimelineFormat::Mermaid => mermaid(&entries),
+        TimelineFormat::Dot => dot(&entries),
+        TimelineFormat::Json => serde_json::to_string_pretty(&entries)? + "\n",
+    })
+}
+
+/// Node text: id, action and the outcome with its duration
+fn label_lines(entry: &TimelineEntry) -> [String; 3] {
+    let mut outcome = entry.status.clone();
+    if let Some(ms) = entry.duration_ms {
+        let _ = write!(outcome, ", {ms} ms");
+    }
+    if entry.attempt > 1 {
+        let _ = write!(outcome, ", attempt {attempt}", attempt = entry.attempt);
+    }
+    [
+        format!(
+            "#{id} {time}",
+            id = entry.id,
+            time = entry.timestamp.format("%Y-%m-%d %H:%M:%S")
+        ),
+        entry.action.clone(),
+        outcome,
+    ]
+}
+
+/// Style class of an entry: success, error or other
+fn class(entry: &TimelineEntry) -> &'static str {
+    match entry.status.as_str() {
+        "success" => "success",
+        "error" | "timeout" => "error",
+        _ => "other",
+    }
+}
+
+fn mermaid(entries: &[TimelineEntry]) -> String {
+    let mut out = String::from("flowchart TD\n");
+    for entry in entries {
+        let label = label_lines(entry)
+            .iter()
+            .map(|line| line.replace('"', "#quot;"))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        let _ = writeln!(
+            out,
+            "    d{id}[\"{label}\"]:::{class}",
+            id = entry.id,
+            class = class(entry)
+        );
+    }
+    for pair in entries.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let arrow = if to.retry_of == Some(from.id) {
+            "-. retry .->"
+        } else {
+            "-->"
+        };
+        let _ = writeln!(out, "    d{from} {arrow} d{to}", from = from.id, to = to.id);
+    }
+    out.push_str("    classDef success fill:#dfd,stroke:#393\n");
+    out.push_str("    classDef error fill:#fdd,stroke:#c33\n");
+    out.push_str("    classDef other fill:#eee,stroke:#999\n");
+    out
+}
+
+fn dot(entries: &[TimelineEntry]) -> String {
+    let mut out = String::from(
+        "digraph timeline {\n    rankdir=TB;\n    node [shape=box, style=\"rounded,filled\"];\n",
+    );
+    for entry in entries {
+        let label = label_lines(entry)
+            .iter()
+            .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+            .collect::<Vec<_>>()
+            .join("\\n");
+        let fill = match class(entry) {
+            "success" => "#ddffdd",
+            "error" => "#ffdddd",
+            _ => "#eeeeee",
+        };
+        let _ = writeln!(
+            out,
+            "    d{id} [label=\"{label}\", fillcolor=\"{fill}\"];",
+            id = entry.id
+        );
+    }
+    for pair in entries.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let style = if to.retry_of == Some(from.id) {
+            " [style=dashed, label=\"retry\"]"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "    d{from} -> d{to}{style};",
+            from = from.id,
+            to = to.id
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DecisionResult;
+    use chrono::TimeZone;
+
+    fn decision(id: i64, action: &str, status: &str) -> DecisionRecord {
+        DecisionRecord {
+            id,
+            timestamp: Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, id as u32).unwrap(),
+            thought: "thinking".to_string(),
+            action: action.to_string(),
+            parameters: Some(serde_json::json!({"path": "a.txt"})),
+            result: Some(DecisionResult {
+                status: status.to_string(),
+                summary: None,
+                error: None,
+                duration_ms: Some(15),
+                tool_name: None,
+                tool_output: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_timeline_links_retries_and_renders_each_format() -> Result<()> {
+        let decisions = [
+            decision(1, "use_tool:fs:read_file", "error"),
+            decision(2, "use_tool:fs:read_file", "error"),
+            decision(3, "use_tool:fs:read_file", "success"),
+            decision(4, "use_tool:fs:read_file", "success"),
+        ];
+        let entries = timeline(&decisions);
+        let retries: Vec<_> = entries.iter().map(|e| (e.retry_of, e.attempt)).collect();
+        assert_eq!(retries, [(None, 1), (Some(1), 2), (Some(2), 3), (None, 1)]);
+
+        let mermaid = render(&decisions, TimelineFormat::Mermaid)?;
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains(
+            "d3[\"#3 2026-03-01 12:00:03<br/>use_tool:fs:read_file<br/>success, 15 ms, attempt 3\"]:::success"
+        ));
+        assert!(mermaid.contains("d1 -. retry .-> d2"));
+        assert!(mermaid.contains("d3 --> d4"));
+
+        let dot = render(&decisions, TimelineFormat::Dot)?;
+        assert!(dot.contains("d1 -> d2 [style=dashed, label=\"retry\"];"));
+        assert!(dot.contains("fillcolor=\"#ffdddd\""));
+
+        let json: Value = serde_json::from_str(&render(&decisions, TimelineFormat::Json)?)?;
+        assert_eq!(json[1]["retry_of"], 1);
+        assert_eq!("DOT".parse::<TimelineFormat>()?, TimelineFormat::Dot);
+        assert!("svg".parse::<TimelineFormat>().is_err());
+        Ok(())
+    }
+}
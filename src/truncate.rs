@@ -0,0 +1,211 @@
+//! Token-budgeted truncation of what goes into prompts and memory. Text
+//! keeps its head and tail around a marker saying how much was cut; JSON
+//! keeps its shape, with long strings and arrays shortened in place.
+//! Tokens are counted with [`estimate_tokens`], and text is cut on token
+//! boundaries.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+use crate::llm::{estimate_tokens, tokenizer};
+
+/// Characters per token when the tokenizer is unavailable
+const CHARS_PER_TOKEN: usize = 4;
+/// Strings are never cut shorter than this while fitting a budget
+const MIN_FIELD_TOKENS: usize = 16;
+/// Array items kept before the budget forces fewer
+const MAX_ITEMS: usize = 20;
+/// Array items kept at the least
+const MIN_ITEMS: usize = 2;
+
+/// `text` cut to about `max_tokens`, keeping its first two thirds and last
+/// third around a marker
+pub fn truncate_text(text: &str, max_tokens: usize) -> Cow<'_, str> {
+    // A token is at least one byte, so short text needs no encoding
+    if text.len() <= max_tokens {
+        return Cow::Borrowed(text);
+    }
+    let Some(bpe) = tokenizer() else {
+        return truncate_chars(text, max_tokens);
+    };
+
+    let tokens = bpe.encode_ordinary(text);
+    if tokens.len() <= max_tokens {
+        return Cow::Borrowed(text);
+    }
+    let head = max_tokens * 2 / 3;
+    let tail = max_tokens - head;
+    let bytes = |tokens: &[u32]| -> usize {
+        bpe._decode_native_and_split(tokens.to_vec())
+            .map(|token| token.len())
+            .sum()
+    };
+    // Tokens can split a character, so round inwards to whole ones
+    let head_end = text.floor_char_boundary(bytes(&tokens[..head]));
+    let tail_start = text.ceil_char_boundary(text.len() - bytes(&tokens[tokens.len() - tail..]));
+    marked(text, head_end, tail_start, tokens.len() - head - tail)
+}
+
+/// [`truncate_text`] by characters, for when the tokenizer is unavailable
+fn truncate_chars(text: &str, max_tokens: usize) -> Cow<'_, str> {
+    let chars = text.chars().count();
+    let budget = max_tokens * CHARS_PER_TOKEN;
+    if chars <= budget {
+        return Cow::Borrowed(text);
+    }
+
+    let head = budget * 2 / 3;
+    let tail = budget - head;
+    let head_end = byte_offset(text, head);
+    let tail_start = byte_offset(text, chars - tail);
+    let omitted = estimate_tokens(&text[head_end..tail_start]);
+    marked(text, head_end, tail_start, omitted)
+}
+
+/// `text` without the bytes from `head_end` to `tail_start`, marked as
+/// `omitted` tokens
+fn marked(text: &str, head_end: usize, tail_start: usize, omitted: usize) -> Cow<'_, str> {
+    Cow::Owned(format!(
+        "{head} ... [{omitted} tokens truncated] ... {tail}",
+        head = &text[..head_end],
+        tail = &text[tail_start.max(head_end)..]
+    ))
+}
+
+/// Just the first `max_chars` characters of `text`, for log previews
+pub fn preview(text: &str, max_chars: usize) -> &str {
+    &text[..byte_offset(text, max_chars)]
+}
+
+/// Estimated tokens of `value` as compact JSON
+pub fn json_tokens(value: &Value) -> usize {
+    estimate_tokens(&value.to_string())
+}
+
+/// `value` cut to about `max_tokens` with its structure intact. Strings
+/// longer than `field_tokens` are cut with [`truncate_text`], and if that
+/// is not enough, strings and arrays are cut shorter until it fits or they
+/// reach a floor. Returns `None` when `value` already fits.
+pub fn truncate_json(value: &Value, max_tokens: usize, field_tokens: usize) -> Option<Value> {
+    if json_tokens(value) <= max_tokens {
+        return None;
+    }
+
+    let mut field_tokens = field_tokens.max(MIN_FIELD_TOKENS);
+    let mut max_items = MAX_ITEMS;
+    loop {
+        let truncated = shrink(value, field_tokens, max_items);
+        let at_floor = field_tokens == MIN_FIELD_TOKENS && max_items == MIN_ITEMS;
+        if at_floor || json_tokens(&truncated) <= max_tokens {
+            return Some(truncated);
+        }
+        field_tokens = (field_tokens / 2).max(MIN_FIELD_TOKENS);
+        max_items = (max_items / 2).max(MIN_ITEMS);
+    }
+}
+
+fn shrink(value: &Value, field_tokens: usize, max_items: usize) -> Value {
+    match value {
+        Value::String(s) => Value::String(truncate_text(s, field_tokens).into_owned()),
+        Value::Array(items) if items.len() > max_items => {
+            let head = max_items.div_ceil(2);
+            let tail = max_items - head;
+            let omitted = items.len() - max_items;
+            items[..head]
+                .iter()
+                .map(|item| shrink(item, field_tokens, max_items))
+                .chain(std::iter::once(Value::String(format!(
+                    "... [{omitted} items truncated] ..."
+                ))))
+                .chain(
+                    items[items.len() - tail..]
+                        .iter()
+                        .map(|item| shrink(item, field_tokens, max_items)),
+                )
+                .collect()
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| shrink(item, field_tokens, max_items))
+            .collect(),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, field)| (key.clone(), shrink(field, field_tokens, max_items)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Byte offset of character `index` of `text`, or its length
+fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_truncate_text_keeps_head_and_tail() {
+        assert_eq!(truncate_text("short", 10), "short");
+
+        let text = format!(
+            "{head}{middle}{tail}",
+            head = "ä".repeat(40),
+            middle = "x".repeat(400),
+            tail = "END"
+        );
+        let truncated = truncate_text(&text, 30);
+        assert!(truncated.starts_with("ää"));
+        assert!(truncated.ends_with("xEND"));
+        assert!(truncated.contains("tokens truncated"));
+        assert!(estimate_tokens(&truncated) < 40);
+        assert_eq!(preview("ääx", 2), "ää");
+    }
+
+    #[test]
+    fn test_truncate_text_counts_real_tokens() {
+        assert_eq!(estimate_tokens("hello world"), 2);
+
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+        let truncated = truncate_text(&text, 60);
+        assert!(truncated.starts_with("The quick brown fox"));
+        assert!(truncated.ends_with("lazy dog. "));
+        // The kept text is within budget, plus the marker's own tokens
+        assert!(estimate_tokens(&truncated) <= 60 + 12, "{truncated}");
+        let omitted = estimate_tokens(&text) - 60;
+        assert!(truncated.contains(&format!("[{omitted} tokens truncated]")));
+    }
+
+    #[test]
+    fn test_truncate_json_keeps_structure_within_budget() {
+        let items: Vec<Value> = (0..500)
+            .map(|i| json!({"id": i, "text": "z".repeat(50)}))
+            .collect();
+        let value = json!({"status": "ok", "body": "y".repeat(10_000), "items": items});
+        assert_eq!(truncate_json(&json!({"a": 1}), 10, 5), None);
+
+        let truncated = truncate_json(&value, 500, 100).unwrap();
+        assert!(json_tokens(&truncated) <= 500, "{truncated}");
+        assert_eq!(truncated["status"], "ok");
+        assert!(
+            truncated["body"]
+                .as_str()
+                .unwrap()
+                .contains("tokens truncated")
+        );
+        let items = truncated["items"].as_array().unwrap();
+        assert_eq!(items[0]["id"], 0);
+        assert_eq!(items.last().unwrap()["id"], 499);
+        assert!(
+            items
+                .iter()
+                .any(|item| item.as_str().is_some_and(|s| s.contains("items truncated")))
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Tools implemented as small WASM modules and run in-process, a safer
+//! alternative to an MCP server subprocess for pure computation. A module
+//! runs with a fuel and memory budget and can only call the host
+//! functions its config grants.
+//!
+//! A module exports `memory`, `alloc(len: i32) -> i32` and
+//! `run(ptr: i32, len: i32) -> i64`. `run` gets the call's parameters as
+//! JSON at `ptr` and returns where its UTF-8 output lies, packed as
+//! `ptr << 32 | len`. Granted host functions are imported from the
+//! `replicante` module:
+//!
+//! - `log(ptr: i32, len: i32)` writes a line to the agent's log (`log`)
+//! - `now_ms() -> i64` is the Unix time in milliseconds (`clock`)
+//! - `random_u64() -> i64` is a random number (`random`)
+
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use tracing::info;
+use wasmtime::{
+    AsContext, Caller, Config as EngineConfig, Engine, Instance, Linker, Memory, Module, Store,
+    StoreLimits, StoreLimitsBuilder, Trap,
+};
+
+use crate::mcp::Tool;
+
+/// Server part of the `server:tool` names of WASM tools
+pub const SERVER_NAME: &str = "wasm";
+
+/// Module the granted host functions are imported from
+const HOST_MODULE: &str = "replicante";
+
+/// Host functions a module may be granted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    Log,
+    Clock,
+    Random,
+}
+
+impl Capability {
+    fn from_import(name: &str) -> Option<Self> {
+        match name {
+            "log" => Some(Self::Log),
+            "now_ms" => Some(Self::Clock),
+            "random_u64" => Some(Self::Random),
+            _ => None,
+        }
+    }
+}
+
+/// A `[[wasm_tools]]` entry
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct WasmToolConfig {
+    /// Called as `wasm:<name>`
+    pub name: String,
+    /// Path to the module, binary `.wasm` or text `.wat`
+    pub module: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema of the parameters, shown to the LLM
+    #[serde(default)]
+    pub input_schema: Option<Value>,
+    /// Host functions the module may import
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// Budget of a single call, roughly in WASM instructions
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    #[serde(default = "default_max_memory_mb")]
+    pub max_memory_mb: u64,
+}
+
+fn default_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_max_memory_mb() -> u64 {
+    16
+}
+
+struct WasmTool {
+    config: WasmToolConfig,
+    module: Module,
+}
+
+/// The loaded WASM tools of an agent
+#[derive(Clone)]
+pub struct WasmTools {
+    engine: Engine,
+    tools: Vec<std::sync::Arc<WasmTool>>,
+}
+
+struct HostState {
+    tool: String,
+    limits: StoreLimits,
+}
+
+impl WasmTools {
+    /// Compile every module, refusing ones that import a host function
+    /// they were not granted
+    pub fn load(configs: &[WasmToolConfig]) -> Result<Self> {
+        let mut engine_config = EngineConfig::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)?;
+
+        let mut tools = Vec::new();
+        for config in configs {
+            let module =
+                Module::from_file(&engine, Path::new(&config.module)).with_context(|| {
+                    format!(
+                        "Failed to load WASM tool {name} from {module}",
+                        name = config.name,
+                        module = config.module
+                    )
+                })?;
+            check_imports(config, &module)?;
+            info!("Loaded WASM tool {name}", name = config.name);
+            tools.push(std::sync::Arc::new(WasmTool {
+                config: config.clone(),
+                module,
+            }));
+        }
+        Ok(Self { engine, tools })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// The tools, named `wasm:<name>` like MCP tools
+    pub fn tools(&self) -> Vec<Tool> {
+        self.tools
+            .iter()
+            .map(|tool| Tool {
+                name: format!("{SERVER_NAME}:{name}", name = tool.config.name),
+                description: tool.config.description.clone(),
+                parameters: tool.config.input_schema.clone(),
+            })
+            .collect()
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.config.name == name)
+    }
+
+    /// Run tool `name` on `params`, returning an MCP `tools/call` result
+    /// with the module's output as its text
+    pub async fn call(&self, name: &str, params: Value) -> Result<Value> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.config.name == name)
+            .cloned()
+            .with_context(|| format!("No WASM tool named {name}"))?;
+        let engine = self.engine.clone();
+        let input = serde_json::to_vec(&params)?;
+
+        let output = tokio::task::spawn_blocking(move || run(&engine, &tool, &input)).await??;
+        Ok(serde_json::json!({
+            "content": [{"type": "text", "text": output}],
+            "isError": false
+        }))
+    }
+}
+
+fn check_imports(config: &WasmToolConfig, module: &Module) -> Result<()> {
+    for import in module.imports() {
+        let capability = (import.module() == HOST_MODULE)
+            .then(|| Capability::from_import(import.name()))
+            .flatten();
+        match capability {
+            Some(capability) if config.capabilities.contains(&capability) => {}
+            Some(capability) => bail!(
+                "WASM tool {name} imports {module}::{import}, which needs the {capability:?} capability",
+                name = config.name,
+                module = import.module(),
+                import = import.name()
+            ),
+            None => bail!(
+                "WASM tool {name} imports unknown host function {module}::{import}",
+                name = config.name,
+                module = import.module(),
+                import = import.name()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Instantiate the tool in a fresh store and call its `run` export
+fn run(engine: &Engine, tool: &WasmTool, input: &[u8]) -> Result<String> {
+    let config = &tool.config;
+    let limits = StoreLimitsBuilder::new()
+        .memory_size((config.max_memory_mb * 1024 * 1024) as usize)
+        .instances(1)
+        .build();
+    let mut store = Store::new(
+        engine,
+        HostState {
+            tool: config.name.clone(),
+            limits,
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(config.fuel)?;
+
+    let linker = linker(engine, &config.capabilities)?;
+    let instance = linker.instantiate(&mut store, &tool.module)?;
+    call_run(&mut store, &instance, input).map_err(|e| match e.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => anyhow::anyhow!(
+            "WASM tool {name} ran out of fuel ({fuel})",
+            name = config.name,
+            fuel = config.fuel
+        ),
+        _ => e.context(format!("WASM tool {name} failed", name = config.name)),
+    })
+}
+
+fn call_run(store: &mut Store<HostState>, instance: &Instance, input: &[u8]) -> Result<String> {
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .context("Module does not export its memory")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+    let run = instance.get_typed_func::<(i32, i32), i64>(&mut *store, "run")?;
+
+    let len = i32::try_from(input.len()).context("Parameters too large")?;
+    let ptr = alloc.call(&mut *store, len)?;
+    memory.write(&mut *store, ptr as u32 as usize, input)?;
+    let packed = run.call(&mut *store, (ptr, len))? as u64;
+
+    let output = read_memory(
+        &memory,
+        &*store,
+        (packed >> 32) as usize,
+        (packed & 0xffff_ffff) as usize,
+    )?;
+    String::from_utf8(output).context("Module output is not UTF-8")
+}
+
+/// Copy `len` bytes at `ptr` out of a module's memory. The range is checked
+/// against the memory's size before anything is allocated for it.
+fn read_memory(memory: &Memory, store: impl AsContext, ptr: usize, len: usize) -> Result<Vec<u8>> {
+    let size = memory.data_size(&store);
+    if ptr.checked_add(len).is_none_or(|end| end > size) {
+        bail!("Module memory range {ptr}+{len} is past the end of its {size} bytes");
+    }
+    let mut bytes = vec![0; len];
+    memory.read(&store, ptr, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Host functions for the granted capabilities only
+fn linker(engine: &Engine, capabilities: &[Capability]) -> Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    for capability in capabilities {
+        match capability {
+            Capability::Log => {
+                linker.func_wrap(
+                    HOST_MODULE,
+                    "log",
+                    |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<()> {
+                        let memory = caller
+                            .get_export("memory")
+                            .and_then(|export| export.into_memory())
+                            .context("Module does not export its memory")?;
+                        let message = read_memory(
+                            &memory,
+                            &caller,
+                            ptr as u32 as usize,
+                            len as u32 as usize,
+                        )?;
+                        info!(
+                            "[wasm:{tool}] {message}",
+                            tool = caller.data().tool,
+                            message = String::from_utf8_lossy(&message)
+                        );
+                        Ok(())
+                    },
+                )?;
+            }
+            Capability::Clock => {
+                linker.func_wrap(HOST_MODULE, "now_ms", || {
+                    chrono::Utc::now().timestamp_millis()
+                })?;
+            }
+            Capability::Random => {
+                linker.func_wrap(HOST_MODULE, "random_u64", rand::random::<i64>)?;
+            }
+        }
+    }
+    Ok(linker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echoes its input back, logging it first when `log` is imported
+    const ECHO: &str = r#"
+        (module
+          (import "replicante" "log" (func $log (param i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "run") (param $ptr i32) (param $len i32) (result i64)
+            (call $log (local.get $ptr) (local.get $len))
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+              (i64.extend_i32_u (local.get $len)))))
+    "#;
+
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "run") (param i32 i32) (result i64)
+            (loop $forever (br $forever))
+            (i64.const 0)))
+    "#;
+
+    /// Claims its output is 4 GiB long
+    const OVERSIZED: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "run") (param i32 i32) (result i64)
+            (i64.const 0xffffffff)))
+    "#;
+
+    fn tool(dir: &Path, name: &str, wat: &str, capabilities: Vec<Capability>) -> WasmToolConfig {
+        let module = dir.join(format!("{name}.wat"));
+        std::fs::write(&module, wat).unwrap();
+        WasmToolConfig {
+            name: name.to_string(),
+            module: module.to_string_lossy().into_owned(),
+            description: None,
+            input_schema: None,
+            capabilities,
+            fuel: 1_000_000,
+            max_memory_mb: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_granted_tool_runs_and_returns_its_output() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let tools = WasmTools::load(&[tool(dir.path(), "echo", ECHO, vec![Capability::Log])])?;
+
+        assert_eq!(tools.tools()[0].name, "wasm:echo");
+        let result = tools.call("echo", serde_json::json!({"n": 1})).await?;
+        assert_eq!(result["content"][0]["text"], r#"{"n":1}"#);
+        assert!(tools.call("missing", Value::Null).await.is_err());
+
+        let client = crate::mcp::MCPClient::new(&[])
+            .await?
+            .with_wasm_tools(tools);
+        assert_eq!(client.list_tools().await?, ["wasm:echo"]);
+        let result = client
+            .call_tool("wasm:echo", serde_json::json!("hi"))
+            .await?;
+        assert_eq!(result["content"][0]["text"], r#""hi""#);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ungranted_imports_and_runaway_loops_are_refused() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let error = WasmTools::load(&[tool(dir.path(), "echo", ECHO, vec![])])
+            .err()
+            .unwrap();
+        assert!(format!("{error:#}").contains("Log capability"), "{error:#}");
+
+        let tools = WasmTools::load(&[tool(dir.path(), "spin", SPIN, vec![])])?;
+        let error = tools.call("spin", Value::Null).await.unwrap_err();
+        assert!(error.to_string().contains("ran out of fuel"), "{error:#}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_output_past_the_end_of_memory_is_refused() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let tools = WasmTools::load(&[tool(dir.path(), "oversized", OVERSIZED, vec![])])?;
+        let error = tools.call("oversized", Value::Null).await.unwrap_err();
+        assert!(
+            format!("{error:#}").contains("past the end of its 65536 bytes"),
+            "{error:#}"
+        );
+        Ok(())
+    }
+}
@@ -13,17 +13,26 @@ fn create_test_config(db_path: &str) -> replicante::Config {
             log_level: Some("debug".to_string()),
             initial_goals: Some("Test autonomous reasoning cycle".to_string()),
             reasoning_interval_secs: 1,
+            tuning: Default::default(),
         },
         llm: replicante::llm::LLMConfig {
             provider: "mock".to_string(),
             api_key: None,
+            api_key_file: None,
+            script: None,
             model: "mock".to_string(),
             temperature: None,
             max_tokens: None,
             api_url: None,
             timeout_secs: None,
+            rate_limit: None,
         },
         mcp_servers: vec![],
+        secrets: None,
+        logging: Default::default(),
+        metrics: Default::default(),
+        supervisor: Default::default(),
+        wasm_tools: Vec::new(),
     }
 }
 
@@ -85,11 +85,14 @@ fn verify_llm_system_exists() {
     let config = LLMConfig {
         provider: "mock".to_string(),
         api_key: None,
+        api_key_file: None,
+        script: None,
         model: "mock".to_string(),
         temperature: None,
         max_tokens: None,
         api_url: None,
         timeout_secs: None,
+        rate_limit: None,
     };
 
     // This will panic at runtime if mock provider doesn't exist,
@@ -125,6 +128,7 @@ fn verify_state_manager_exists() {
 /// Verify MCP Client exists
 #[test]
 fn verify_mcp_client_exists() {
+    use replicante::jsonrpc::{DEFAULT_MAX_MESSAGE_BYTES, Framing};
     use replicante::mcp::{MCPClient, MCPServerConfig};
 
     // Verify types exist
@@ -136,6 +140,11 @@ fn verify_mcp_client_exists() {
         retry_attempts: 3,
         retry_delay_ms: 1000,
         health_check_interval_secs: 60,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
     };
 
     // Verify MCPClient type exists
@@ -176,11 +185,14 @@ fn verify_mock_llm_provider_exists() {
     let config = LLMConfig {
         provider: "mock".to_string(),
         api_key: None,
+        api_key_file: None,
+        script: None,
         model: "mock".to_string(),
         temperature: None,
         max_tokens: None,
         api_url: None,
         timeout_secs: None,
+        rate_limit: None,
     };
 
     match create_provider(&config) {
@@ -1,5 +1,9 @@
 use anyhow::Result;
+use replicante::events::{Event, EventBus};
+use replicante::jsonrpc::{DEFAULT_MAX_MESSAGE_BYTES, Framing};
 use replicante::mcp::{MCPClient, MCPServerConfig};
+use replicante::mcp_server::{Param, Tool, ToolOutput};
+use replicante::test_support::{self, MockCall, MockServer};
 use std::path::PathBuf;
 use std::time::Duration;
 use tokio::time::timeout;
@@ -8,6 +12,7 @@ use tokio::time::timeout;
 //
 // These tests verify the full MCP (Model Context Protocol) client-server communication:
 // - Server subprocess spawning and stdio communication
+// - In-process mock servers from `test_support`, for client behaviour
 // - JSON-RPC handshake and protocol compliance
 // - Tool discovery and execution
 // - Multiple concurrent servers
@@ -52,6 +57,35 @@ fn target_binary_path(bin_name: &str) -> String {
     path_str
 }
 
+/// The tools of the mock-mcp-server binary, served in-process
+fn mock_server(name: &str) -> MockServer {
+    MockServer::new(name)
+        .tool(
+            Tool::new("echo", "Echoes back the input")
+                .param(Param::string("message", "Message to echo").required()),
+            |arguments| {
+                let message = arguments["message"].as_str().unwrap_or("");
+                Ok(ToolOutput::text(format!("Echo: {message}")))
+            },
+        )
+        .tool(
+            Tool::new("add", "Adds two numbers")
+                .param(Param::number("a", "First addend").required())
+                .param(Param::number("b", "Second addend").required()),
+            |arguments| {
+                let a = arguments["a"].as_f64().unwrap_or(0.0);
+                let b = arguments["b"].as_f64().unwrap_or(0.0);
+                Ok(ToolOutput::text(format!("Result: {sum}", sum = a + b)))
+            },
+        )
+        .tool(Tool::new("get_time", "Gets the current time"), |_| {
+            Ok(ToolOutput::text(format!(
+                "Current time: {now}",
+                now = chrono::Utc::now().to_rfc3339()
+            )))
+        })
+}
+
 #[tokio::test]
 async fn test_echo_server() -> Result<()> {
     // Simple test with echo command that exits immediately
@@ -63,6 +97,11 @@ async fn test_echo_server() -> Result<()> {
         retry_attempts: 1,
         retry_delay_ms: 100,
         health_check_interval_secs: 60,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
     }];
 
     // This should not hang - echo exits immediately
@@ -81,21 +120,14 @@ async fn test_mock_server_full_flow() -> Result<()> {
 
     // Wrap entire test with timeout to prevent hanging
     timeout(Duration::from_secs(15), async {
-        let configs = vec![MCPServerConfig {
-            name: "mock".to_string(),
-            transport: "stdio".to_string(),
-            command: target_binary_path("mock-mcp-server"),
-            args: vec![],
-            retry_attempts: 1,
-            retry_delay_ms: 100,
-            health_check_interval_secs: 30,
-        }];
-
-        // Create client with shorter timeout
-        let client = timeout(Duration::from_secs(3), MCPClient::new(&configs)).await??;
-
-        // Wait a bit for initialization
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        let events = EventBus::default();
+        let mut tool_calls = events.subscribe();
+        let mock = mock_server("mock");
+        let client = timeout(
+            Duration::from_secs(3),
+            test_support::connect(&[&mock], events),
+        )
+        .await??;
 
         // List tools
         let tools = client.list_tools().await?;
@@ -159,6 +191,21 @@ async fn test_mock_server_full_flow() -> Result<()> {
             "Should return current time"
         );
 
+        // The raw result keeps the MCP content list as the server sent it
+        let raw = client
+            .call_tool("mock:echo", serde_json::json!({"message": "raw"}))
+            .await?;
+        assert_eq!(raw["isError"], serde_json::json!(false));
+        assert_eq!(raw["content"][0]["type"], "text");
+        assert!(raw["content"][0]["text"].as_str().unwrap().contains("raw"));
+
+        // Every call was announced on the event bus
+        let mut calls = Vec::new();
+        while let Ok(Event::ToolCalled { tool, success, .. }) = tool_calls.try_recv() {
+            calls.push((tool, success));
+        }
+        assert_eq!(calls.last(), Some(&("mock:echo".to_string(), true)));
+
         Ok(())
     })
     .await?
@@ -173,31 +220,12 @@ async fn test_multiple_servers() -> Result<()> {
 
     // Wrap entire test with timeout to prevent hanging
     timeout(Duration::from_secs(15), async {
-        let configs = vec![
-            MCPServerConfig {
-                name: "mock1".to_string(),
-                transport: "stdio".to_string(),
-                command: target_binary_path("mock-mcp-server"),
-                args: vec![],
-                retry_attempts: 1,
-                retry_delay_ms: 100,
-                health_check_interval_secs: 30,
-            },
-            MCPServerConfig {
-                name: "mock2".to_string(),
-                transport: "stdio".to_string(),
-                command: target_binary_path("mock-mcp-server"),
-                args: vec![],
-                retry_attempts: 1,
-                retry_delay_ms: 100,
-                health_check_interval_secs: 30,
-            },
-        ];
-
-        let client = timeout(Duration::from_secs(3), MCPClient::new(&configs)).await??;
-
-        // Wait for initialization
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        let (mock1, mock2) = (mock_server("mock1"), mock_server("mock2"));
+        let client = timeout(
+            Duration::from_secs(3),
+            test_support::connect(&[&mock1, &mock2], EventBus::default()),
+        )
+        .await??;
 
         let tools = client.list_tools().await?;
 
@@ -227,6 +255,11 @@ async fn test_http_mcp_server() -> Result<()> {
             retry_attempts: 1,
             retry_delay_ms: 100,
             health_check_interval_secs: 30,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: Default::default(),
+            cwd: None,
+            user: None,
         }];
 
         // Create client with timeout
@@ -267,10 +300,8 @@ async fn test_http_mcp_server() -> Result<()> {
             )
             .await?;
 
-        assert!(result["success"].as_bool().unwrap_or(false));
-        let content = result["content"].as_str().unwrap_or("");
-        assert!(
-            content.contains("65"),
+        assert_eq!(
+            result["result"], 65,
             "Calculate should return 15 + 25 * 2 = 65"
         );
 
@@ -284,12 +315,8 @@ async fn test_http_mcp_server() -> Result<()> {
             )
             .await?;
 
-        assert!(result["success"].as_bool().unwrap_or(false));
-        let content = result["content"].as_str().unwrap_or("");
-        assert!(
-            content.contains("London"),
-            "Weather should mention the city"
-        );
+        assert_eq!(result["city"], "London", "Weather should name the city");
+        assert!(result["temperature_c"].is_i64());
 
         // Test time tool
         let result = client
@@ -301,9 +328,11 @@ async fn test_http_mcp_server() -> Result<()> {
             )
             .await?;
 
-        assert!(result["success"].as_bool().unwrap_or(false));
-        let content = result["content"].as_str().unwrap_or("");
-        assert!(content.contains("UTC"), "Time should include timezone info");
+        assert_eq!(result["recognized"], true);
+        assert!(
+            result["time"].as_str().unwrap_or("").ends_with("UTC"),
+            "Time should include timezone info"
+        );
 
         Ok(())
     })
@@ -322,8 +351,13 @@ async fn test_server_failure_recovery() -> Result<()> {
             retry_attempts: 1,
             retry_delay_ms: 100,
             health_check_interval_secs: 60,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: Default::default(),
+            cwd: None,
+            user: None,
         },
-        // This should work if Python is available
+        // This one starts, though it exits right away
         MCPServerConfig {
             name: "working".to_string(),
             transport: "stdio".to_string(),
@@ -332,6 +366,11 @@ async fn test_server_failure_recovery() -> Result<()> {
             retry_attempts: 1,
             retry_delay_ms: 100,
             health_check_interval_secs: 60,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: Default::default(),
+            cwd: None,
+            user: None,
         },
     ];
 
@@ -370,3 +409,1976 @@ async fn test_invalid_tool_name() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_message_size_limit() -> Result<()> {
+    timeout(Duration::from_secs(15), async {
+        let client = mock_server("mock").max_message_bytes(4096).client().await?;
+
+        let result = client
+            .use_tool("mock:echo", serde_json::json!({"message": "small"}))
+            .await?;
+        assert_eq!(result["success"], true);
+
+        // Requests over the limit are refused before they are sent
+        let result = client
+            .use_tool(
+                "mock:echo",
+                serde_json::json!({"message": "x".repeat(8192)}),
+            )
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("exceeds the 4096 byte limit"), "{error}");
+
+        // The connection stays usable
+        let result = client
+            .use_tool("mock:add", serde_json::json!({"a": 1, "b": 2}))
+            .await?;
+        assert!(result["content"].as_str().unwrap_or("").contains('3'));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_content_length_framing() -> Result<()> {
+    timeout(Duration::from_secs(15), async {
+        // The server picks up the framing from the client's first message
+        let client = mock_server("mock")
+            .framing(Framing::ContentLength)
+            .client()
+            .await?;
+        assert!(
+            client
+                .list_tools()
+                .await?
+                .contains(&"mock:echo".to_string())
+        );
+
+        let result = client
+            .use_tool(
+                "mock:echo",
+                serde_json::json!({"message": "line one\nline two"}),
+            )
+            .await?;
+        assert_eq!(result["content"].as_str(), Some("Echo: line one\nline two"));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_mock_server_latency_and_errors() -> Result<()> {
+    timeout(Duration::from_secs(15), async {
+        let mock = mock_server("mock")
+            .latency("echo", Duration::from_millis(300))
+            .failing("delete", "permission denied")
+            .reporting_error("fetch", "404 Not Found");
+        let client = mock.client().await?;
+
+        let started = std::time::Instant::now();
+        let result = client
+            .use_tool("mock:echo", serde_json::json!({"message": "slow"}))
+            .await?;
+        assert_eq!(result["content"], "Echo: slow");
+        assert!(started.elapsed() >= Duration::from_millis(300));
+
+        let error = client
+            .use_tool("mock:delete", serde_json::json!({"path": "/tmp/x"}))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("permission denied"), "{error}");
+
+        let result = client.use_tool("mock:fetch", serde_json::json!({})).await?;
+        assert_eq!(result["success"], false);
+        assert_eq!(result["content"], "404 Not Found");
+
+        let error = client
+            .use_tool("mock:missing", serde_json::json!({}))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Unknown tool: missing"), "{error}");
+
+        assert_eq!(
+            mock.calls()[1],
+            MockCall {
+                tool: "delete".to_string(),
+                arguments: serde_json::json!({"path": "/tmp/x"}),
+            }
+        );
+        assert_eq!(mock.calls().len(), 4);
+        Ok(())
+    })
+    .await?
+}
+
+/// Start the filesystem server on `workspace`
+async fn filesystem_client(workspace: &std::path::Path) -> Result<MCPClient> {
+    filesystem_client_with_args(workspace, &[]).await
+}
+
+/// Start the filesystem server on `workspace` with extra command-line flags
+async fn filesystem_client_with_args(
+    workspace: &std::path::Path,
+    extra_args: &[&str],
+) -> Result<MCPClient> {
+    let mut args = vec![
+        "--workspace".to_string(),
+        workspace.to_string_lossy().to_string(),
+    ];
+    args.extend(extra_args.iter().map(|arg| arg.to_string()));
+
+    let configs = vec![MCPServerConfig {
+        name: "fs".to_string(),
+        transport: "stdio".to_string(),
+        command: target_binary_path("filesystem-mcp-server"),
+        args,
+        retry_attempts: 1,
+        retry_delay_ms: 100,
+        health_check_interval_secs: 30,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
+    }];
+
+    timeout(Duration::from_secs(3), MCPClient::new(&configs)).await?
+}
+
+#[tokio::test]
+async fn test_filesystem_search_files() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    std::fs::create_dir_all(workspace.path().join("src/bin"))?;
+    std::fs::write(
+        workspace.path().join("src/lib.rs"),
+        "pub mod config;\n\npub fn run() {}\n",
+    )?;
+    std::fs::write(
+        workspace.path().join("src/bin/main.rs"),
+        "fn main() {\n    replicante::run();\n}\n",
+    )?;
+    std::fs::write(workspace.path().join("notes.txt"), "TODO: run the tests\n")?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        // File names, at any depth
+        let result = client
+            .use_tool("fs:search_files", serde_json::json!({"pattern": "*.rs"}))
+            .await?;
+        assert_eq!(
+            result["files"],
+            serde_json::json!(["src/bin/main.rs", "src/lib.rs"])
+        );
+
+        // Paths relative to the search directory
+        let result = client
+            .use_tool(
+                "fs:search_files",
+                serde_json::json!({"path": "src", "pattern": "bin/*.rs"}),
+            )
+            .await?;
+        assert_eq!(result["files"], serde_json::json!(["src/bin/main.rs"]));
+
+        // Contents, with context
+        let result = client
+            .use_tool(
+                "fs:search_files",
+                serde_json::json!({"content": "RUN\\(", "case_insensitive": true, "context_lines": 1}),
+            )
+            .await?;
+        assert_eq!(
+            result["matches"],
+            serde_json::json!([
+                {
+                    "path": "src/bin/main.rs",
+                    "line": 2,
+                    "text": "    replicante::run();",
+                    "before": ["fn main() {"],
+                    "after": ["}"]
+                },
+                {
+                    "path": "src/lib.rs",
+                    "line": 3,
+                    "text": "pub fn run() {}",
+                    "before": [""],
+                    "after": []
+                }
+            ])
+        );
+
+        // Capped results
+        let result = client
+            .use_tool(
+                "fs:search_files",
+                serde_json::json!({"content": "run", "max_results": 1, "context_lines": 0}),
+            )
+            .await?;
+        assert_eq!(result["matches"].as_array().map(Vec::len), Some(1));
+        assert_eq!(result["matches"][0]["path"], "notes.txt");
+        assert_eq!(result["matches"][0]["text"], "TODO: run the tests");
+        assert_eq!(result["truncated"], true);
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_edit_file() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    let file = workspace.path().join("config.toml");
+    let original: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+    std::fs::write(&file, &original)?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        // Find/replace and line ranges, applied in order
+        let result = client
+            .use_tool(
+                "fs:edit_file",
+                serde_json::json!({
+                    "path": "config.toml",
+                    "edits": [
+                        {"old_text": "line 2\n", "new_text": "second\n"},
+                        {"start_line": 19, "end_line": 20, "new_text": "tail"}
+                    ]
+                }),
+            )
+            .await?;
+        assert_eq!(result["changed"], true);
+        let content = result["diff"].as_str().unwrap_or("");
+        assert!(content.contains("--- a/config.toml\n+++ b/config.toml\n"));
+        assert!(content.contains("@@ -1,5 +1,5 @@\n line 1\n-line 2\n+second\n"));
+        assert!(content.contains("-line 19\n-line 20\n+tail\n"));
+        let edited = std::fs::read_to_string(&file)?;
+        assert!(edited.starts_with("line 1\nsecond\nline 3\n"));
+        assert!(edited.ends_with("line 18\ntail\n"));
+
+        // Unified diff whose line numbers have drifted
+        let diff = "--- a/config.toml\n+++ b/config.toml\n@@ -8,3 +8,3 @@\n line 9\n-line 10\n+tenth\n line 11\n";
+        client
+            .use_tool(
+                "fs:edit_file",
+                serde_json::json!({"path": "config.toml", "diff": diff}),
+            )
+            .await?;
+        assert!(std::fs::read_to_string(&file)?.contains("line 9\ntenth\nline 11\n"));
+
+        // A failing edit leaves the file untouched
+        let before = std::fs::read_to_string(&file)?;
+        let result = client
+            .use_tool(
+                "fs:edit_file",
+                serde_json::json!({
+                    "path": "config.toml",
+                    "edits": [
+                        {"old_text": "line 4\n", "new_text": "fourth\n"},
+                        {"old_text": "line 1", "new_text": "ambiguous"}
+                    ]
+                }),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("matches"));
+        assert_eq!(std::fs::read_to_string(&file)?, before);
+
+        // Dry runs only report the diff
+        let result = client
+            .use_tool(
+                "fs:edit_file",
+                serde_json::json!({
+                    "path": "config.toml",
+                    "edits": [{"old_text": "tenth", "new_text": "10"}],
+                    "dry_run": true
+                }),
+            )
+            .await?;
+        assert_eq!(result["changed"], false);
+        assert!(result["diff"].as_str().unwrap_or("").contains("+10\n"));
+        assert_eq!(std::fs::read_to_string(&file)?, before);
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_move_and_copy() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    std::fs::create_dir_all(workspace.path().join("src/nested"))?;
+    std::fs::write(workspace.path().join("src/a.txt"), "alpha")?;
+    std::fs::write(workspace.path().join("src/nested/b.txt"), "beta")?;
+    std::fs::write(workspace.path().join("existing.txt"), "old")?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        // Directories are copied recursively, creating missing parents
+        let result = client
+            .use_tool(
+                "fs:copy_file",
+                serde_json::json!({"source": "src", "destination": "backup/src"}),
+            )
+            .await?;
+        assert_eq!(result["files_copied"], 2);
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("backup/src/nested/b.txt"))?,
+            "beta"
+        );
+
+        // Existing destinations need overwrite
+        let result = client
+            .use_tool(
+                "fs:move_file",
+                serde_json::json!({"source": "src/a.txt", "destination": "existing.txt"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("already exists"));
+        client
+            .use_tool(
+                "fs:move_file",
+                serde_json::json!({"source": "src/a.txt", "destination": "existing.txt", "overwrite": true}),
+            )
+            .await?;
+        assert!(!workspace.path().join("src/a.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("existing.txt"))?,
+            "alpha"
+        );
+
+        // Destinations stay inside the workspace
+        let result = client
+            .use_tool(
+                "fs:copy_file",
+                serde_json::json!({"source": "existing.txt", "destination": "../escaped.txt"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("outside workspace"));
+
+        // Refused copies and moves leave no new directories behind
+        let result = client
+            .use_tool(
+                "fs:copy_file",
+                serde_json::json!({"source": "missing.txt", "destination": "fresh/dir/a.txt"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
+        let result = client
+            .use_tool(
+                "fs:copy_file",
+                serde_json::json!({"source": "backup", "destination": "backup/inner/copy"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("inside source"));
+        assert!(!workspace.path().join("fresh").exists());
+        assert!(!workspace.path().join("backup/inner").exists());
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_file_info() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    std::fs::write(workspace.path().join("hello.txt"), "hello world")?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        let info = client
+            .use_tool("fs:file_info", serde_json::json!({"path": "hello.txt"}))
+            .await?;
+        assert_eq!(info["type"], "file");
+        assert_eq!(info["size"], 11);
+        assert_eq!(
+            info["sha256"],
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert!(info["modified"].as_str().is_some());
+        assert_eq!(info["readonly"], false);
+
+        let info = client
+            .use_tool("fs:file_info", serde_json::json!({"path": "."}))
+            .await?;
+        assert_eq!(info["type"], "directory");
+        assert!(info["sha256"].is_null());
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_tail_and_watch() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    let log = workspace.path().join("build.log");
+    std::fs::write(&log, "compiling a\ncompiling b\ncompiling c\n")?;
+    std::fs::write(workspace.path().join("stale.txt"), "x")?;
+
+    timeout(Duration::from_secs(20), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        let result = client
+            .use_tool(
+                "fs:tail_file",
+                serde_json::json!({"path": "build.log", "lines": 2}),
+            )
+            .await?;
+        assert_eq!(result["content"].as_str(), Some("compiling b\ncompiling c"));
+
+        // Output appended while following is returned too
+        let appender = {
+            let log = log.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                let mut file = std::fs::OpenOptions::new().append(true).open(log)?;
+                std::io::Write::write_all(&mut file, b"finished\n")
+            })
+        };
+        let result = client
+            .use_tool(
+                "fs:tail_file",
+                serde_json::json!({"path": "build.log", "lines": 1, "follow_secs": 1}),
+            )
+            .await?;
+        appender.await??;
+        assert_eq!(result["content"].as_str(), Some("compiling c"));
+        assert_eq!(result["appended"].as_str(), Some("finished\n"));
+        assert_eq!(result["followed_secs"], 1);
+
+        // Changes are reported relative to the previous token
+        let watch = client
+            .use_tool("fs:watch_path", serde_json::json!({}))
+            .await?;
+        assert_eq!(watch["files"], 2);
+        let token = watch["token"].as_str().unwrap_or("").to_string();
+
+        std::fs::write(workspace.path().join("new.txt"), "new")?;
+        std::fs::write(&log, "rebuilt\n")?;
+        std::fs::remove_file(workspace.path().join("stale.txt"))?;
+
+        let watch = client
+            .use_tool("fs:watch_path", serde_json::json!({"token": token}))
+            .await?;
+        assert_eq!(watch["created"], serde_json::json!(["new.txt"]));
+        assert_eq!(watch["modified"], serde_json::json!(["build.log"]));
+        assert_eq!(watch["deleted"], serde_json::json!(["stale.txt"]));
+        assert_ne!(watch["token"].as_str(), Some(token.as_str()));
+
+        // Tokens are single use
+        let result = client
+            .use_tool("fs:watch_path", serde_json::json!({"token": token}))
+            .await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Unknown or expired")
+        );
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_ranged_read() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    let content: String = (1..=100).map(|n| format!("row {n}\n")).collect();
+    std::fs::write(workspace.path().join("data.txt"), &content)?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        // Whole-file reads are unchanged
+        let result = client
+            .use_tool("fs:read_file", serde_json::json!({"path": "data.txt"}))
+            .await?;
+        assert_eq!(result["content"].as_str(), Some(content.as_str()));
+
+        let result = client
+            .use_tool(
+                "fs:read_file",
+                serde_json::json!({"path": "data.txt", "offset": 10, "limit": 2}),
+            )
+            .await?;
+        assert_eq!(result["content"].as_str(), Some("row 11\nrow 12"));
+        assert_eq!(result["first_line"], 11);
+        assert_eq!(result["last_line"], 12);
+        assert_eq!(result["total_lines"], 100);
+        assert_eq!(result["size"], content.len());
+
+        let result = client
+            .use_tool(
+                "fs:read_file",
+                serde_json::json!({"path": "data.txt", "offset": 4, "limit": 5, "unit": "bytes"}),
+            )
+            .await?;
+        assert_eq!(result["content"].as_str(), Some("1\nrow"));
+        assert_eq!(result["offset"], 4);
+        assert_eq!(result["end"], 9);
+        assert_eq!(result["size"], content.len());
+
+        let result = client
+            .use_tool(
+                "fs:read_file",
+                serde_json::json!({"path": "data.txt", "offset": 200}),
+            )
+            .await?;
+        assert_eq!(result["content"].as_str(), Some(""));
+        assert!(result["first_line"].is_null());
+        assert_eq!(result["total_lines"], 100);
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_base64_roundtrip() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = filesystem_client(workspace.path()).await?;
+
+        // "iVBORw0KGgo=" is the PNG signature, which is not valid UTF-8
+        let result = client
+            .use_tool(
+                "fs:write_file_base64",
+                serde_json::json!({"path": "images/logo.png", "content": "iVBORw0KGgo="}),
+            )
+            .await?;
+        assert_eq!(result["bytes_written"], 8);
+        assert_eq!(
+            std::fs::read(workspace.path().join("images/logo.png"))?,
+            b"\x89PNG\r\n\x1a\n"
+        );
+
+        let result = client
+            .use_tool(
+                "fs:read_file_base64",
+                serde_json::json!({"path": "images/logo.png"}),
+            )
+            .await?;
+        assert_eq!(result["content_base64"].as_str(), Some("iVBORw0KGgo="));
+
+        let result = client
+            .use_tool(
+                "fs:write_file_base64",
+                serde_json::json!({"path": "bad.bin", "content": "not base64!"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("Invalid base64"));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_filesystem_workspace_quota() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    let chunk = "x".repeat(600 * 1024);
+
+    timeout(Duration::from_secs(15), async {
+        let client =
+            filesystem_client_with_args(workspace.path(), &["--max-workspace-mb", "1"]).await?;
+
+        client
+            .use_tool(
+                "fs:write_file",
+                serde_json::json!({"path": "a.txt", "content": chunk}),
+            )
+            .await?;
+
+        // A second file would go over 1 MB
+        let result = client
+            .use_tool(
+                "fs:write_file",
+                serde_json::json!({"path": "b.txt", "content": chunk}),
+            )
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("Workspace quota exceeded"), "{error}");
+        assert!(!workspace.path().join("b.txt").exists());
+
+        let result = client
+            .use_tool(
+                "fs:copy_file",
+                serde_json::json!({"source": "a.txt", "destination": "c.txt"}),
+            )
+            .await;
+        assert!(result.is_err());
+
+        // Replacing a file only counts the growth
+        client
+            .use_tool(
+                "fs:write_file",
+                serde_json::json!({"path": "a.txt", "content": "x".repeat(900 * 1024)}),
+            )
+            .await?;
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Start the shell server on `workspace` without Docker tools
+async fn shell_client(workspace: &std::path::Path) -> Result<MCPClient> {
+    shell_client_with_args(workspace, &[]).await
+}
+
+/// Start the shell server on `workspace` without Docker tools, with extra flags
+async fn shell_client_with_args(
+    workspace: &std::path::Path,
+    extra_args: &[&str],
+) -> Result<MCPClient> {
+    let mut args = vec![
+        "--workspace".to_string(),
+        workspace.to_string_lossy().to_string(),
+        "--allow-docker".to_string(),
+        "false".to_string(),
+    ];
+    args.extend(extra_args.iter().map(|arg| arg.to_string()));
+
+    let configs = vec![MCPServerConfig {
+        name: "shell".to_string(),
+        transport: "stdio".to_string(),
+        command: target_binary_path("shell-mcp-server"),
+        args,
+        retry_attempts: 1,
+        retry_delay_ms: 100,
+        health_check_interval_secs: 30,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
+    }];
+
+    timeout(Duration::from_secs(3), MCPClient::new(&configs)).await?
+}
+
+/// Poll job_status until the job is no longer running
+async fn wait_for_job(client: &MCPClient, job_id: &str) -> Result<serde_json::Value> {
+    loop {
+        let status = client
+            .use_tool("shell:job_status", serde_json::json!({"job_id": job_id}))
+            .await?;
+        if status["running"] == false {
+            return Ok(status);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_shell_background_jobs() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+
+    timeout(Duration::from_secs(20), async {
+        let client = shell_client(workspace.path()).await?;
+
+        let job = client
+            .use_tool(
+                "shell:start_job",
+                serde_json::json!({"command": "echo building; sleep 0.3; echo failed >&2; exit 3"}),
+            )
+            .await?;
+        let job_id = job["job_id"].as_str().unwrap_or("").to_string();
+        assert_eq!(job_id, "job-1");
+
+        let status = wait_for_job(&client, &job_id).await?;
+        assert_eq!(status["exit_code"], 3);
+
+        let output = client
+            .use_tool("shell:job_output", serde_json::json!({"job_id": job_id}))
+            .await?;
+        assert_eq!(output["output"], "building\nfailed\n");
+        let next_offset = output["next_offset"].clone();
+
+        // Reading from next_offset only returns new output
+        let output = client
+            .use_tool(
+                "shell:job_output",
+                serde_json::json!({"job_id": job_id, "offset": next_offset}),
+            )
+            .await?;
+        assert_eq!(output["output"], "");
+
+        // Killing a job stops it
+        let job = client
+            .use_tool(
+                "shell:start_job",
+                serde_json::json!({"command": "sleep 30"}),
+            )
+            .await?;
+        let job_id = job["job_id"].as_str().unwrap_or("").to_string();
+        let result = client
+            .use_tool(
+                "shell:kill_job",
+                serde_json::json!({"job_id": job_id, "signal": "KILL"}),
+            )
+            .await?;
+        assert_eq!(result["signal"], "KILL");
+        assert_eq!(result["sent"], true);
+        let status = wait_for_job(&client, &job_id).await?;
+        assert!(status["exit_code"].is_null());
+
+        let jobs = client
+            .use_tool("shell:job_status", serde_json::json!({}))
+            .await?;
+        assert_eq!(jobs.as_array().map(|jobs| jobs.len()), Some(2));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_shell_sessions_keep_state() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    std::fs::create_dir(workspace.path().join("project"))?;
+
+    timeout(Duration::from_secs(20), async {
+        let client = shell_client(workspace.path()).await?;
+
+        let session = client
+            .use_tool(
+                "shell:open_session",
+                serde_json::json!({"env": {"GREETING": "hello"}}),
+            )
+            .await?;
+        let session_id = session["session_id"].as_str().unwrap_or("").to_string();
+
+        client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "cd project && export TARGET=world"}),
+            )
+            .await?;
+
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "echo \"$GREETING $TARGET\"; pwd; ls missing"}),
+            )
+            .await?;
+        assert_eq!(result["exit_code"], 2, "{result}");
+        assert!(result["cwd"].as_str().unwrap_or("").ends_with("/project"));
+        let stdout = result["stdout"].as_str().unwrap_or("");
+        assert!(stdout.starts_with("hello world\n"));
+        assert!(stdout.contains("/project\n"));
+        assert!(result["stderr"].as_str().unwrap_or("").starts_with("ls: "));
+
+        // Syntax errors are rejected without losing the session
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "if then"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("Syntax error"));
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "echo $TARGET"}),
+            )
+            .await?;
+        assert_eq!(result["stdout"].as_str(), Some("world\n"));
+
+        client
+            .use_tool(
+                "shell:close_session",
+                serde_json::json!({"session_id": session_id}),
+            )
+            .await?;
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "true"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("Unknown session"));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_shell_stdin_input() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+
+    timeout(Duration::from_secs(20), async {
+        let client = shell_client(workspace.path()).await?;
+
+        let result = client
+            .use_tool(
+                "shell:run_command",
+                serde_json::json!({"command": "read name; read answer; echo \"$name:$answer\"; wc -l", "stdin": "alice\nyes\nrest\n"}),
+            )
+            .await?;
+        assert_eq!(result["stdout"].as_str(), Some("alice:yes\n1\n"), "{result}");
+
+        // Without input a command sees end-of-file instead of the server's stdin
+        let result = client
+            .use_tool("shell:run_command", serde_json::json!({"command": "cat"}))
+            .await?;
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(result["stdout"].as_str(), Some(""));
+
+        let session = client
+            .use_tool("shell:open_session", serde_json::json!({}))
+            .await?;
+        let session_id = session["session_id"].as_str().unwrap_or("").to_string();
+
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "read first && read second && export PAIR=\"$first-$second\"", "stdin": "one\ntwo"}),
+            )
+            .await?;
+        assert_eq!(result["exit_code"], 0);
+
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session_id, "command": "echo $PAIR; cat"}),
+            )
+            .await?;
+        assert!(result["stdout"].as_str().unwrap_or("").starts_with("one-two\n"));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_shell_command_policy() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    let policy = workspace.path().join("policy.toml");
+    std::fs::write(&policy, "allow = [\"^(echo|ls|rm) \"]\n")?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = shell_client_with_args(
+            workspace.path(),
+            &[
+                "--command-policy",
+                &policy.to_string_lossy(),
+                "--deny-command",
+                r"rm\s+-rf",
+            ],
+        )
+        .await?;
+
+        let result = client
+            .use_tool(
+                "shell:run_command",
+                serde_json::json!({"command": "echo allowed"}),
+            )
+            .await?;
+        assert_eq!(result["stdout"].as_str(), Some("allowed\n"));
+
+        // Deny rules win over allow rules
+        let result = client
+            .use_tool(
+                "shell:run_command",
+                serde_json::json!({"command": "rm -rf project"}),
+            )
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains(r"denied by policy rule 'rm\s+-rf'"),
+            "{error}"
+        );
+
+        // Anything outside the allow list is rejected, in jobs too
+        let result = client
+            .use_tool(
+                "shell:start_job",
+                serde_json::json!({"command": "curl example.com"}),
+            )
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains("matches no allowed command pattern"),
+            "{error}"
+        );
+
+        // An allowed command cannot smuggle in another one
+        for command in [
+            "echo hi; curl example.com",
+            "echo hi && curl example.com",
+            "echo hi | sh",
+            "echo `curl example.com`",
+            "echo $(curl example.com)",
+            "echo hi\ncurl example.com",
+        ] {
+            let result = client
+                .use_tool("shell:run_command", serde_json::json!({"command": command}))
+                .await;
+            let error = result.unwrap_err().to_string();
+            assert!(
+                error.contains("is not allowed while commands are limited"),
+                "{error}"
+            );
+        }
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_shell_commands_join_cgroup_before_exec() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+    // Stands in for a cgroup v2 directory; the shell writes "0" to join it
+    let cgroup = tempfile::TempDir::new()?;
+    let procs = cgroup.path().join("cgroup.procs");
+    std::fs::write(&procs, "")?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = shell_client_with_args(
+            workspace.path(),
+            &["--cgroup", &cgroup.path().to_string_lossy()],
+        )
+        .await?;
+
+        let result = client
+            .use_tool("shell:run_command", serde_json::json!({"command": "true"}))
+            .await?;
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(std::fs::read_to_string(&procs)?, "0");
+
+        // A job that cannot join the cgroup is never started
+        std::fs::remove_file(&procs)?;
+        let result = client
+            .use_tool(
+                "shell:start_job",
+                serde_json::json!({"command": "touch started"}),
+            )
+            .await;
+        assert!(result.is_err());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!workspace.path().join("started").exists());
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_server_env_and_cwd() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+
+    let configs = vec![MCPServerConfig {
+        name: "shell".to_string(),
+        transport: "stdio".to_string(),
+        command: target_binary_path("shell-mcp-server"),
+        // A relative workspace resolves against the configured cwd
+        args: vec![
+            "--workspace".to_string(),
+            ".".to_string(),
+            "--allow-docker".to_string(),
+            "false".to_string(),
+        ],
+        retry_attempts: 1,
+        retry_delay_ms: 100,
+        health_check_interval_secs: 30,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: [("GREETING".to_string(), "hello from config".to_string())].into(),
+        cwd: Some(workspace.path().to_string_lossy().to_string()),
+        user: None,
+    }];
+
+    timeout(Duration::from_secs(15), async {
+        let client = MCPClient::new(&configs).await?;
+
+        let result = client
+            .use_tool(
+                "shell:run_command",
+                serde_json::json!({"command": "echo $GREETING; pwd"}),
+            )
+            .await?;
+        let expected = format!(
+            "hello from config\n{}\n",
+            workspace.path().canonicalize()?.display()
+        );
+        assert_eq!(result["stdout"].as_str(), Some(expected.as_str()));
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_shell_paginated_output() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+
+    timeout(Duration::from_secs(15), async {
+        let client =
+            shell_client_with_args(workspace.path(), &["--output-page-size-kb", "1"]).await?;
+
+        // 3000 bytes of output: "0000\n" .. "0599\n"
+        let result = client
+            .use_tool(
+                "shell:run_command",
+                serde_json::json!({"command": "seq -w 0 599 | sed 's/^/0/'"}),
+            )
+            .await?;
+        assert!(
+            result["stdout"]
+                .as_str()
+                .unwrap_or("")
+                .starts_with("0000\n0001\n")
+        );
+        assert_eq!(
+            result["stdout_continuation"],
+            serde_json::json!({"token": "out-1", "next_offset": 1024, "total_bytes": 3000})
+        );
+
+        let result = client
+            .use_tool(
+                "shell:get_output_chunk",
+                serde_json::json!({"token": "out-1", "offset": 1024}),
+            )
+            .await?;
+        let output = result["output"].as_str().unwrap_or("");
+        assert!(output.starts_with("\n0205\n"), "{output}");
+        assert_eq!(result["next_offset"], 2048);
+        assert_eq!(result["total_bytes"], 3000);
+
+        let result = client
+            .use_tool(
+                "shell:get_output_chunk",
+                serde_json::json!({"token": "out-1", "offset": 2048}),
+            )
+            .await?;
+        assert!(result["output"].as_str().unwrap_or("").ends_with("0599\n"));
+        assert!(result["next_offset"].is_null());
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_shell_resource_limits() -> Result<()> {
+    let workspace = tempfile::TempDir::new()?;
+
+    timeout(Duration::from_secs(15), async {
+        let client = shell_client_with_args(
+            workspace.path(),
+            &["--max-file-size-mb", "1", "--nice", "5"],
+        )
+        .await?;
+
+        let result = client
+            .use_tool("shell:run_command", serde_json::json!({"command": "nice"}))
+            .await?;
+        assert_eq!(result["stdout"].as_str(), Some("5\n"));
+
+        // Writes past the file size limit fail
+        let result = client
+            .use_tool(
+                "shell:run_command",
+                serde_json::json!({"command": "head -c 2000000 /dev/zero > big.bin"}),
+            )
+            .await?;
+        assert_ne!(result["exit_code"], 0);
+        assert!(std::fs::metadata(workspace.path().join("big.bin"))?.len() <= 1024 * 1024);
+
+        // Session shells get the same limits
+        let session = client
+            .use_tool("shell:open_session", serde_json::json!({}))
+            .await?;
+        let result = client
+            .use_tool(
+                "shell:run_in_session",
+                serde_json::json!({"session_id": session["session_id"], "command": "nice"}),
+            )
+            .await?;
+        assert_eq!(result["stdout"].as_str(), Some("5\n"));
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Start the HTTP server with extra command-line flags
+async fn http_client(extra_args: &[&str]) -> Result<MCPClient> {
+    let configs = vec![MCPServerConfig {
+        name: "http".to_string(),
+        transport: "stdio".to_string(),
+        command: target_binary_path("http-mcp-server"),
+        args: extra_args.iter().map(|arg| arg.to_string()).collect(),
+        retry_attempts: 1,
+        retry_delay_ms: 100,
+        health_check_interval_secs: 30,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
+    }];
+
+    timeout(Duration::from_secs(3), MCPClient::new(&configs)).await?
+}
+
+#[tokio::test]
+async fn test_http_request_validation() -> Result<()> {
+    timeout(Duration::from_secs(15), async {
+        let client = http_client(&[]).await?;
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": "http://example.invalid/api", "method": "POST"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("not in allowed domain list")
+        );
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": "https://httpbin.org/anything", "method": "TRACE"}),
+            )
+            .await?;
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("Unsupported method")
+        );
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": "https://httpbin.org/anything", "json": {}, "body": "x"}),
+            )
+            .await?;
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("only one of")
+        );
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_http_download_validation() -> Result<()> {
+    timeout(Duration::from_secs(15), async {
+        let workspace = tempfile::TempDir::new()?;
+        let workspace_arg = workspace.path().to_string_lossy().to_string();
+        let client = http_client(&["--workspace", &workspace_arg]).await?;
+
+        let result = client
+            .use_tool(
+                "http:download_file",
+                serde_json::json!({"url": "http://example.invalid/file", "path": "file.bin"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("not in allowed domain list")
+        );
+
+        let result = client
+            .use_tool(
+                "http:download_file",
+                serde_json::json!({"url": "https://httpbin.org/bytes/16", "path": "../escape.bin"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(result["content"].as_str().unwrap_or("").contains(".."));
+
+        std::fs::write(workspace.path().join("existing.bin"), "keep")?;
+        let result = client
+            .use_tool(
+                "http:download_file",
+                serde_json::json!({"url": "https://httpbin.org/bytes/16", "path": "existing.bin"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("already exists")
+        );
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("existing.bin"))?,
+            "keep"
+        );
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Serve HTTP on a local port; `handler` maps the raw request to extra
+/// response headers and a body, and headers starting with Location make
+/// the response a redirect
+async fn serve_http(handler: fn(&str) -> (String, String)) -> Result<std::net::SocketAddr> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 4096];
+                let read = stream.read(&mut buffer).await.unwrap_or(0);
+                let (headers, body) = handler(&String::from_utf8_lossy(&buffer[..read]));
+                let status = if headers.starts_with("Location: ") {
+                    "302 Found"
+                } else {
+                    "200 OK"
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/plain\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    Ok(addr)
+}
+
+#[tokio::test]
+async fn test_http_domain_policy() -> Result<()> {
+    timeout(Duration::from_secs(20), async {
+        let addr = serve_http(|request| {
+            if request.starts_with("GET /hop ") {
+                return ("Location: /greeting\r\n".to_string(), String::new());
+            }
+            if request.starts_with("GET /away ") {
+                return (
+                    "Location: http://localhost:1/\r\n".to_string(),
+                    String::new(),
+                );
+            }
+            (String::new(), "hello from the test server".to_string())
+        })
+        .await?;
+        let workspace = tempfile::TempDir::new()?;
+        let workspace_arg = workspace.path().to_string_lossy().to_string();
+        let client =
+            http_client(&["--allow-domain", "127.0.0.1", "--workspace", &workspace_arg]).await?;
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/greeting", addr)}),
+            )
+            .await?;
+        assert_eq!(result["status"], 200);
+        assert_eq!(result["body"], "hello from the test server");
+
+        // Explicit allow rules replace the default test domains
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": "https://httpbin.org/get"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("not in allowed domain list")
+        );
+
+        // Redirects are followed only to allowed domains
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/hop", addr)}),
+            )
+            .await?;
+        assert_eq!(result["body"], "hello from the test server");
+        let result = client
+            .use_tool(
+                "http:fetch_url",
+                serde_json::json!({"url": format!("http://{}/away", addr)}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("URL domain 'localhost' not in allowed domain list")
+        );
+
+        // sha256 of "hello from the test server"
+        let digest = {
+            use sha2::{Digest, Sha256};
+            format!("{:x}", Sha256::digest(b"hello from the test server"))
+        };
+        let result = client
+            .use_tool(
+                "http:download_file",
+                serde_json::json!({
+                    "url": format!("http://{}/file.txt", addr),
+                    "path": "downloads/file.txt",
+                    "sha256": digest
+                }),
+            )
+            .await?;
+        assert_eq!(result["bytes"], 26);
+        assert_eq!(result["verified"], true);
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("downloads/file.txt"))?,
+            "hello from the test server"
+        );
+
+        let result = client
+            .use_tool(
+                "http:download_file",
+                serde_json::json!({
+                    "url": format!("http://{}/file.txt", addr),
+                    "path": "mismatch.txt",
+                    "sha256": "00"
+                }),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("Checksum mismatch")
+        );
+        assert!(!workspace.path().join("mismatch.txt").exists());
+
+        let client = http_client(&["--allow-all-domains", "--deny-domain", "*.invalid"]).await?;
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": "http://api.example.invalid/"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("denied by domain policy rule '*.invalid'")
+        );
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_http_sessions_keep_cookies() -> Result<()> {
+    timeout(Duration::from_secs(20), async {
+        let addr = serve_http(|request| {
+            if request.starts_with("GET /login ") {
+                return (
+                    "Set-Cookie: token=abc123; Path=/\r\n".to_string(),
+                    "logged in".to_string(),
+                );
+            }
+            let cookie = request
+                .lines()
+                .find_map(|line| line.strip_prefix("cookie: "))
+                .unwrap_or("none");
+            let agent = request
+                .lines()
+                .find_map(|line| line.strip_prefix("x-agent: "))
+                .unwrap_or("none");
+            (String::new(), format!("cookie={} agent={}", cookie, agent))
+        })
+        .await?;
+        let client = http_client(&["--allow-domain", "127.0.0.1"]).await?;
+
+        let session = client
+            .use_tool(
+                "http:create_http_session",
+                serde_json::json!({"headers": {"X-Agent": "replicante"}}),
+            )
+            .await?;
+        let session = session["session"].as_str().unwrap_or("").to_string();
+        assert!(session.starts_with("session-"));
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/login", addr), "session": session}),
+            )
+            .await?;
+        assert_eq!(result["body"], "logged in");
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/whoami", addr), "session": session}),
+            )
+            .await?;
+        assert_eq!(result["body"], "cookie=token=abc123 agent=replicante");
+
+        // Requests outside the session carry no cookies
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/whoami", addr)}),
+            )
+            .await?;
+        assert_eq!(result["body"], "cookie=none agent=none");
+
+        client
+            .use_tool(
+                "http:close_http_session",
+                serde_json::json!({"session": session}),
+            )
+            .await?;
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/whoami", addr), "session": session}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("Unknown session")
+        );
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_http_politeness() -> Result<()> {
+    timeout(Duration::from_secs(20), async {
+        let addr = serve_http(|request| {
+            if request.starts_with("GET /robots.txt ") {
+                let robots =
+                    "User-agent: *\nDisallow: /\n\nUser-agent: polite-bot\nDisallow: /bot-only\n";
+                return (String::new(), robots.to_string());
+            }
+            let agent = request
+                .lines()
+                .find_map(|line| line.strip_prefix("user-agent: "))
+                .unwrap_or("none");
+            (String::new(), format!("agent={}", agent))
+        })
+        .await?;
+        let client = http_client(&[
+            "--allow-domain",
+            "127.0.0.1",
+            "--user-agent",
+            "polite-bot/2.0",
+            "--min-request-interval-ms",
+            "400",
+        ])
+        .await?;
+
+        // The polite-bot group replaces the catch-all group
+        let result = client
+            .use_tool(
+                "http:fetch_url",
+                serde_json::json!({"url": format!("http://{}/bot-only/page", addr)}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("disallowed by robots.txt")
+        );
+
+        let started = std::time::Instant::now();
+        let result = client
+            .use_tool(
+                "http:fetch_url",
+                serde_json::json!({"url": format!("http://{}/hello", addr)}),
+            )
+            .await?;
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("agent=polite-bot/2.0")
+        );
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/again", addr)}),
+            )
+            .await?;
+        assert_eq!(result["status"], 200);
+        assert!(started.elapsed() >= Duration::from_millis(400));
+
+        let client =
+            http_client(&["--allow-domain", "127.0.0.1", "--respect-robots", "false"]).await?;
+        let result = client
+            .use_tool(
+                "http:fetch_url",
+                serde_json::json!({"url": format!("http://{}/bot-only/page", addr)}),
+            )
+            .await?;
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("agent=MCP-Test/1.0")
+        );
+
+        Ok(())
+    })
+    .await?
+}
+
+#[tokio::test]
+async fn test_http_auth_profiles() -> Result<()> {
+    timeout(Duration::from_secs(20), async {
+        let addr = serve_http(|request| {
+            let header = |name: &str| {
+                request
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                    .unwrap_or("none")
+                    .to_string()
+            };
+            if request.starts_with("GET /same-host ") {
+                return ("Location: /me\r\n".to_string(), String::new());
+            }
+            if request.starts_with("GET /other-host ") {
+                let port = header("host").rsplit(':').next().unwrap_or("").to_string();
+                return (format!("Location: http://localhost:{port}/me\r\n"), String::new());
+            }
+            if request.contains("x-api-key: ") {
+                return (String::new(), format!("x-api-key={}", header("x-api-key")));
+            }
+            (String::new(), format!("authorization={}", header("authorization")))
+        })
+        .await?;
+
+        // Credentials reach the server only through its environment
+        let configs = vec![MCPServerConfig {
+            name: "http".to_string(),
+            transport: "stdio".to_string(),
+            command: "env".to_string(),
+            args: vec![
+                "HTTP_AUTH_LOCAL_BEARER=s3cret-token".to_string(),
+                "HTTP_AUTH_REMOTE_BEARER=other-token".to_string(),
+                "HTTP_AUTH_REMOTE_DOMAINS=api.example.com".to_string(),
+                "HTTP_AUTH_PINNED_HEADER=X-Api-Key: pinned-key".to_string(),
+                "HTTP_AUTH_PINNED_DOMAINS=127.0.0.1".to_string(),
+                target_binary_path("http-mcp-server"),
+                "--allow-domain".to_string(),
+                "127.0.0.1,localhost".to_string(),
+                "--min-request-interval-ms".to_string(),
+                "0".to_string(),
+            ],
+            retry_attempts: 1,
+            retry_delay_ms: 100,
+            health_check_interval_secs: 30,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            framing: Framing::Newline,
+            env: Default::default(),
+            cwd: None,
+            user: None,
+        }];
+        let client = timeout(Duration::from_secs(3), MCPClient::new(&configs)).await??;
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({
+                    "url": format!("http://{}/me", addr),
+                    "auth_profile": "local",
+                    "headers": {"Authorization": "Bearer guessed"}
+                }),
+            )
+            .await?;
+        assert_eq!(result["status"], 200);
+        assert_eq!(result["body"], "authorization=Bearer [REDACTED]");
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/me", addr), "auth_profile": "remote"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("may not be used with domain '127.0.0.1'")
+        );
+
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/me", addr), "auth_profile": "missing"}),
+            )
+            .await?;
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("available: local, pinned, remote")
+        );
+
+        // Redirects keep a profile's credential on its domains
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/same-host", addr), "auth_profile": "pinned"}),
+            )
+            .await?;
+        assert_eq!(result["body"], "x-api-key=[REDACTED]");
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/other-host", addr), "auth_profile": "pinned"}),
+            )
+            .await?;
+        assert_eq!(result["success"], false);
+        assert!(
+            result["content"]
+                .as_str()
+                .unwrap_or("")
+                .contains("redirect to 'localhost' is outside the auth profile's domains")
+        );
+        let result = client
+            .use_tool(
+                "http:http_request",
+                serde_json::json!({"url": format!("http://{}/other-host", addr)}),
+            )
+            .await?;
+        assert_eq!(result["body"], "authorization=none");
+
+        Ok(())
+    })
+    .await?
+}
+
+async fn vector_store_client(database: &std::path::Path) -> Result<MCPClient> {
+    let configs = vec![MCPServerConfig {
+        name: "vectors".to_string(),
+        transport: "stdio".to_string(),
+        command: target_binary_path("vector-store-mcp-server"),
+        args: vec![
+            "--database".to_string(),
+            database.to_string_lossy().to_string(),
+        ],
+        retry_attempts: 1,
+        retry_delay_ms: 100,
+        health_check_interval_secs: 30,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
+    }];
+
+    timeout(Duration::from_secs(3), MCPClient::new(&configs)).await?
+}
+
+#[tokio::test]
+async fn test_vector_store_shared_between_agents() -> Result<()> {
+    timeout(Duration::from_secs(15), async {
+        let dir = tempfile::TempDir::new()?;
+        let database = dir.path().join("knowledge.db");
+        let writer = vector_store_client(&database).await?;
+        let reader = vector_store_client(&database).await?;
+
+        for (id, text) in [
+            ("rust", "Rust ownership and borrowing rules prevent data races"),
+            ("bitcoin", "Lightning channels settle bitcoin payments off chain"),
+            ("cooking", "Simmer the tomato sauce for twenty minutes"),
+        ] {
+            let stored = writer
+                .use_tool(
+                    "vectors:store_embedding",
+                    serde_json::json!({"id": id, "text": text, "metadata": {"topic": id}}),
+                )
+                .await?;
+            assert_eq!(stored["id"], id);
+            assert_eq!(stored["embedding"], "hashed");
+        }
+
+        // A second agent's server sees the first agent's entries
+        let found = reader
+            .use_tool(
+                "vectors:semantic_search",
+                serde_json::json!({"query": "bitcoin lightning payments", "top_k": 2}),
+            )
+            .await?;
+        let results = found["results"].as_array().cloned().unwrap_or_default();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["id"], "bitcoin");
+        assert_eq!(results[0]["metadata"]["topic"], "bitcoin");
+
+        // Caller-provided embeddings live in their own namespace
+        writer
+            .use_tool(
+                "vectors:store_embedding",
+                serde_json::json!({"id": "a", "text": "a", "embedding": [1.0, 0.0], "namespace": "custom"}),
+            )
+            .await?;
+        writer
+            .use_tool(
+                "vectors:store_embedding",
+                serde_json::json!({"id": "b", "text": "b", "embedding": [0.0, 1.0], "namespace": "custom"}),
+            )
+            .await?;
+        let found = reader
+            .use_tool(
+                "vectors:semantic_search",
+                serde_json::json!({"embedding": [0.9, 0.1], "namespace": "custom", "min_score": 0.5}),
+            )
+            .await?;
+        assert_eq!(found["results"].as_array().map(|r| r.len()), Some(1));
+        assert_eq!(found["results"][0]["id"], "a");
+
+        let result = writer
+            .use_tool(
+                "vectors:store_embedding",
+                serde_json::json!({"text": "c", "embedding": [1.0, 0.0, 0.0], "namespace": "custom"}),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("2 dimensions"));
+
+        let deleted = reader
+            .use_tool("vectors:delete", serde_json::json!({"ids": ["bitcoin", "missing"]}))
+            .await?;
+        assert_eq!(deleted["deleted"], 1);
+        let found = writer
+            .use_tool(
+                "vectors:semantic_search",
+                serde_json::json!({"query": "bitcoin lightning payments", "top_k": 1}),
+            )
+            .await?;
+        assert_ne!(found["results"][0]["id"], "bitcoin");
+
+        let deleted = writer
+            .use_tool(
+                "vectors:delete",
+                serde_json::json!({"namespace": "custom", "all": true}),
+            )
+            .await?;
+        assert_eq!(deleted["deleted"], 2);
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Minimal in-memory Nostr relay: stores EVENTs and answers REQs on kinds, authors and #p
+async fn start_fake_relay() -> Result<(
+    String,
+    std::sync::Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+)> {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("ws://{}", listener.local_addr()?);
+    let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+
+    let stored = events.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let stored = stored.clone();
+            tokio::spawn(async move {
+                let Ok(mut socket) = tokio_tungstenite::accept_async(stream).await else {
+                    return;
+                };
+                while let Some(Ok(Message::Text(text))) = socket.next().await {
+                    let Ok(message) = serde_json::from_str::<serde_json::Value>(&text) else {
+                        continue;
+                    };
+                    let mut replies = Vec::new();
+                    match message[0].as_str() {
+                        Some("EVENT") => {
+                            stored.lock().unwrap().push(message[1].clone());
+                            replies.push(serde_json::json!(["OK", message[1]["id"], true, ""]));
+                        }
+                        Some("REQ") => {
+                            let filter = &message[2];
+                            let contains = |list: &serde_json::Value, value: &serde_json::Value| {
+                                list.as_array().is_none_or(|items| items.contains(value))
+                            };
+                            for event in stored.lock().unwrap().iter().rev() {
+                                let tagged = filter["#p"].as_array().is_none_or(|keys| {
+                                    event["tags"].as_array().is_some_and(|tags| {
+                                        tags.iter()
+                                            .any(|tag| tag[0] == "p" && keys.contains(&tag[1]))
+                                    })
+                                });
+                                if contains(&filter["kinds"], &event["kind"])
+                                    && contains(&filter["authors"], &event["pubkey"])
+                                    && tagged
+                                {
+                                    replies.push(serde_json::json!(["EVENT", message[1], event]));
+                                }
+                            }
+                            replies.push(serde_json::json!(["EOSE", message[1]]));
+                        }
+                        _ => {}
+                    }
+                    for reply in replies {
+                        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    Ok((url, events))
+}
+
+async fn nostr_client(name: &str, relay: &str, key_file: &std::path::Path) -> Result<MCPClient> {
+    let configs = vec![MCPServerConfig {
+        name: name.to_string(),
+        transport: "stdio".to_string(),
+        command: target_binary_path("nostr-mcp-server"),
+        args: vec![
+            "--relay".to_string(),
+            relay.to_string(),
+            "--secret-key-file".to_string(),
+            key_file.to_string_lossy().to_string(),
+            "--relay-timeout-secs".to_string(),
+            "5".to_string(),
+        ],
+        retry_attempts: 1,
+        retry_delay_ms: 100,
+        health_check_interval_secs: 30,
+        max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        framing: Framing::Newline,
+        env: Default::default(),
+        cwd: None,
+        user: None,
+    }];
+
+    timeout(Duration::from_secs(3), MCPClient::new(&configs)).await?
+}
+
+#[tokio::test]
+async fn test_nostr_notes_dms_and_follows() -> Result<()> {
+    timeout(Duration::from_secs(30), async {
+        let (relay, relay_events) = start_fake_relay().await?;
+        let dir = tempfile::TempDir::new()?;
+        let alice_key = dir.path().join("alice.key");
+        let alice = nostr_client("alice", &relay, &alice_key).await?;
+        let bob = nostr_client("bob", &relay, &dir.path().join("bob.key")).await?;
+
+        // Keys are generated into owner-only files and never returned by tools
+        let alice_identity = alice
+            .use_tool("alice:get_identity", serde_json::json!({}))
+            .await?;
+        let secret = std::fs::read_to_string(&alice_key)?;
+        assert!(!alice_identity.to_string().contains(secret.trim()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                std::fs::metadata(&alice_key)?.permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        let alice_npub = alice_identity["npub"].as_str().unwrap_or("").to_string();
+        assert!(alice_npub.starts_with("npub1"));
+        let bob_identity = bob
+            .use_tool("bob:get_identity", serde_json::json!({}))
+            .await?;
+        let bob_npub = bob_identity["npub"].as_str().unwrap_or("").to_string();
+
+        let published = alice
+            .use_tool(
+                "alice:publish_note",
+                serde_json::json!({"content": "hello bob", "mentions": [bob_npub]}),
+            )
+            .await?;
+        assert_eq!(published["accepted_by"], 1);
+
+        let mentions = bob
+            .use_tool("bob:read_mentions", serde_json::json!({}))
+            .await?;
+        assert_eq!(mentions["mentions"][0]["content"], "hello bob");
+        assert_eq!(mentions["mentions"][0]["author"], alice_npub);
+
+        alice
+            .use_tool(
+                "alice:send_dm",
+                serde_json::json!({"recipient": bob_npub, "content": "the secret plan"}),
+            )
+            .await?;
+        let dms = bob.use_tool("bob:read_dms", serde_json::json!({})).await?;
+        assert_eq!(dms["messages"][0]["content"], "the secret plan");
+        assert_eq!(dms["messages"][0]["from"], alice_npub);
+        // The relay only ever saw ciphertext
+        assert!(
+            !relay_events
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|event| event["content"] == "the secret plan")
+        );
+
+        let followed = alice
+            .use_tool(
+                "alice:follow",
+                serde_json::json!({"pubkey": bob_npub, "petname": "bob"}),
+            )
+            .await?;
+        assert_eq!(followed["following"], 1);
+        assert_eq!(followed["already_following"], false);
+        let followed = alice
+            .use_tool("alice:follow", serde_json::json!({"pubkey": bob_npub}))
+            .await?;
+        assert_eq!(followed["already_following"], true);
+
+        let result = alice
+            .use_tool(
+                "alice:send_dm",
+                serde_json::json!({"recipient": "npub1invalid", "content": "x"}),
+            )
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    })
+    .await?
+}